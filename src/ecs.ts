@@ -1,4 +1,4 @@
-import {assert, int} from './base.js';
+import {assert, drop, int, Vec3} from './base.js';
 
 //////////////////////////////////////////////////////////////////////////////
 
@@ -153,4 +153,147 @@ class EntityComponentSystem {
 
 //////////////////////////////////////////////////////////////////////////////
 
+// A spatial hash of entity AABBs. Each entity is listed in the cell (e.g. the
+// chunk) that contains its center. Systems that move entities call update(),
+// which hands entities off between cells as they cross cell boundaries.
+//
+// Queries expand their search by the largest entity half-extent we've seen,
+// so that they find entities whose AABBs stick out of their cells. We hash
+// cell coordinates to 10 bits per axis; aliased cells are harmless, because
+// queries check each entity's AABB. A query visits at most kSpatialAxisCells
+// cells per axis, which covers each key once, so results have no duplicates.
+
+const kSpatialAxisCells = 1 << 10;
+
+interface SpatialEntry {min: Vec3, max: Vec3, cell: int};
+
+class SpatialIndex {
+  private cellSize: number;
+  private maxExtent: number;
+  private cells: Map<int, EntityId[]>;
+  private entries: Map<EntityId, SpatialEntry>;
+
+  constructor(cellSize: number) {
+    this.cellSize = cellSize;
+    this.maxExtent = 0;
+    this.cells = new Map();
+    this.entries = new Map();
+  }
+
+  update(entity: EntityId, min: Vec3, max: Vec3) {
+    for (let i = 0; i < 3; i++) {
+      this.maxExtent = Math.max(this.maxExtent, (max[i] - min[i]) / 2);
+    }
+    const size = this.cellSize;
+    const cell = this.key((min[0] + max[0]) / 2 / size,
+                          (min[1] + max[1]) / 2 / size,
+                          (min[2] + max[2]) / 2 / size);
+
+    const entry = this.entries.get(entity);
+    if (entry) {
+      Vec3.copy(entry.min, min);
+      Vec3.copy(entry.max, max);
+      if (entry.cell === cell) return;
+      this.removeFromCell(entity, entry.cell);
+      entry.cell = cell;
+    } else {
+      const [a, b] = [Vec3.create(), Vec3.create()];
+      Vec3.copy(a, min);
+      Vec3.copy(b, max);
+      this.entries.set(entity, {min: a, max: b, cell});
+    }
+
+    const list = this.cells.get(cell);
+    if (list) {
+      list.push(entity);
+    } else {
+      this.cells.set(cell, [entity]);
+    }
+  }
+
+  remove(entity: EntityId) {
+    const entry = this.entries.get(entity);
+    if (!entry) return;
+    this.entries.delete(entity);
+    this.removeFromCell(entity, entry.cell);
+  }
+
+  entitiesInAABB(min: Vec3, max: Vec3,
+                 result: EntityId[] = []): EntityId[] {
+    const size = this.cellSize;
+    const extent = this.maxExtent;
+    const lo = [0, 1, 2].map(i => Math.floor((min[i] - extent) / size));
+    const hi = [0, 1, 2].map(i => Math.min(
+        Math.floor((max[i] + extent) / size), lo[i] + kSpatialAxisCells - 1));
+
+    for (let x = lo[0]; x <= hi[0]; x++) {
+      for (let y = lo[1]; y <= hi[1]; y++) {
+        for (let z = lo[2]; z <= hi[2]; z++) {
+          const list = this.cells.get(this.key(x, y, z));
+          if (!list) continue;
+          for (const entity of list) {
+            const entry = this.entries.get(entity)!;
+            if (this.overlaps(entry, min, max)) result.push(entity);
+          }
+        }
+      }
+    }
+    return result;
+  }
+
+  // Returns the entity's last AABB, as [min, max], or null if it isn't in the
+  // index. The caller must not modify the result.
+  getAABB(entity: EntityId): [Vec3, Vec3] | null {
+    const entry = this.entries.get(entity);
+    return entry ? [entry.min, entry.max] : null;
+  }
+
+  // Returns entities whose AABBs are within radius of the point (x, y, z).
+  entitiesNear(x: number, y: number, z: number, radius: number,
+               result: EntityId[] = []): EntityId[] {
+    const min = Vec3.from(x - radius, y - radius, z - radius);
+    const max = Vec3.from(x + radius, y + radius, z + radius);
+    const start = result.length;
+    this.entitiesInAABB(min, max, result);
+
+    const point = [x, y, z];
+    let count = start;
+    for (let i = start; i < result.length; i++) {
+      const entry = this.entries.get(result[i])!;
+      let distance = 0;
+      for (let j = 0; j < 3; j++) {
+        const p = point[j];
+        const d = Math.max(entry.min[j] - p, 0, p - entry.max[j]);
+        distance += d * d;
+      }
+      if (distance <= radius * radius) result[count++] = result[i];
+    }
+    result.length = count;
+    return result;
+  }
+
+  private key(x: number, y: number, z: number): int {
+    const mask = kSpatialAxisCells - 1;
+    const [cx, cy, cz] = [x, y, z].map(v => Math.floor(v) & mask);
+    return int((cx << 20) | (cy << 10) | cz);
+  }
+
+  private overlaps(entry: SpatialEntry, min: Vec3, max: Vec3): boolean {
+    for (let i = 0; i < 3; i++) {
+      if (entry.max[i] < min[i] || max[i] < entry.min[i]) return false;
+    }
+    return true;
+  }
+
+  private removeFromCell(entity: EntityId, cell: int) {
+    const list = this.cells.get(cell);
+    assert(list !== undefined);
+    drop(list!, entity);
+    if (list!.length === 0) this.cells.delete(cell);
+  }
+};
+
+//////////////////////////////////////////////////////////////////////////////
+
 export {Component, ComponentState, ComponentStore, EntityComponentSystem, EntityId, kNoEntity};
+export {SpatialIndex};
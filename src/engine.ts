@@ -1,8 +1,9 @@
 import {assert, drop, int, nonnull} from './base.js';
 import {Color, Tensor2, Tensor3, Vec3} from './base.js';
-import {EntityComponentSystem} from './ecs.js';
+import {EntityComponentSystem, EntityId, SpatialIndex} from './ecs.js';
 import {HighlightMesh, InstancedMesh, Geometry, Mesh} from './renderer.js';
-import {Instance, LightTexture, Renderer, Texture, VoxelMesh} from './renderer.js';
+import {Instance, LightTexture, Renderer, Texture} from './renderer.js';
+import {SmoothMesh, VoxelMesh} from './renderer.js';
 import {TerrainMesher} from './mesher.js';
 import {kSweepResolution, sweep} from './sweep.js';
 
@@ -137,6 +138,325 @@ interface Material {
   liquid: boolean,
   texture: Texture,
   textureIndex: int,
+  uvTransform: int,
+  normalMap: Texture | null,
+  fullBright: boolean,
+};
+
+interface EditDrop {
+  block: BlockId,
+  count: int,
+  centroid: Vec3,
+};
+
+interface Explosion {
+  destroyed: Vec3[],
+  drops: EditDrop[],
+  impulses: {entity: EntityId, impulse: Vec3}[],
+};
+
+interface Drop {
+  item: int,
+  count: int,
+};
+
+// The circumstances of a loot roll. Flags are game-defined, for example,
+// "killed by a player". See LootContext in loot.h.
+interface LootContext {
+  tool: int,
+  tier: int,
+  fortune: int,
+  flags: int,
+};
+
+// Holds if the roll uses the tool (if it's non-zero) at minTier or above,
+// with at least minFortune, and with all of the flags.
+interface LootCondition {
+  tool?: int,
+  minTier?: int,
+  minFortune?: int,
+  flags?: int,
+};
+
+// An inventory slot. Empty slots hold kNoItem with a count of 0.
+interface ItemStack {
+  item: int,
+  count: int,
+};
+
+// Matches the item or, if item is kNoItem, any item with one of the tags.
+// In a shaped pattern, an ingredient with neither is an empty cell.
+interface Ingredient {
+  item: int,
+  tags: int,
+};
+
+interface EntityTransform {
+  id: int,
+  x: number,
+  y: number,
+  z: number,
+  heading: number,
+};
+
+interface ChunkEdit {
+  index: int,
+  block: BlockId,
+};
+
+interface ChunkPacket {
+  cx: int,
+  cz: int,
+  voxels: Uint8Array,
+  extra: Uint8Array,
+  blockEntities: BlockEntity[],
+};
+
+// Game-defined state attached to one block, such as a chest's items. The
+// index is the block's voxel index in its chunk. See Chunk::getBlockEntity.
+interface BlockEntity {
+  index: int,
+  type: int,
+  data: Uint8Array,
+};
+
+interface ChunkDeltaPacket {
+  cx: int,
+  cz: int,
+  edits: ChunkEdit[],
+};
+
+interface BlockEditPacket {
+  x: int,
+  y: int,
+  z: int,
+  block: BlockId,
+};
+
+interface SyncedBlock {
+  mesh: boolean,
+  opaque: boolean,
+  solid: boolean,
+  light: int,
+  faces: MaybeMaterialId[],
+};
+
+interface SyncedMaterial {
+  liquid: boolean,
+  alphaTest: boolean,
+  texture: int,
+  color: Color,
+  uvTransform: int,
+  fullBright: boolean,
+};
+
+interface SyncedRegistry {
+  blocks: SyncedBlock[],
+  materials: SyncedMaterial[],
+};
+
+interface PushResult {
+  count: int,
+  min: Vec3,
+  max: Vec3,
+};
+
+// The inclusive bounds of the blocks that changed in one chunk.
+interface DirtyBox {
+  min: Vec3,
+  max: Vec3,
+};
+
+// Problems in the registry and loaded chunks, listing the first few of each
+// kind. See WorldReport in engine.cpp.
+interface WorldReport {
+  valid: boolean,
+  chunks: int,
+  badVoxels: int,
+  badFaces: int,
+  voxels: {x: int, y: int, z: int, block: int}[],
+  faces: {block: int, face: int, material: int}[],
+};
+
+interface Projectile {
+  id: int,
+  position: Vec3,
+  stuck: boolean,
+};
+
+// A debug primitive from one of the engine's subsystems. Lines go from a to
+// b, boxes span [a, b], and text is anchored at a. Colors are 0xRRGGBBAA.
+// See DebugDraw in debug.h.
+interface DebugCommand {
+  shape: int,
+  flag: int,
+  color: int,
+  a: Vec3,
+  b: Vec3,
+  text: string,
+};
+
+// Measurements of a builder's selection. `loaded` counts the selected voxels
+// in loaded chunks, which are the ones that `blocks` counts by block, and
+// `surface` counts faces of non-air blocks that touch air.
+interface SelectionStats {
+  volume: number,
+  loaded: number,
+  surface: number,
+  blocks: {block: BlockId, count: number}[],
+};
+
+// One layer of an imported heightmap's columns. See heightmaps.h.
+interface HeightmapLayer {
+  depth: int,
+  block: BlockId,
+};
+
+// Rules for validating players' edits. See EditRules in edits.h.
+interface PlayerEditRules {
+  reach: number,
+  rate: number,
+  burst: number,
+  miningSlack: number,
+};
+
+// What a player claims about an edit: where their eye was, the tool that
+// they held, how long they mined the old block, and the host's time, all in
+// seconds. See EditContext in edits.h.
+interface PlayerEditContext {
+  eye: Vec3,
+  tool: int,
+  tier: int,
+  mined: number,
+  time: number,
+};
+
+// A stack of items on the ground. See World::addItemDrop in engine.cpp.
+interface ItemDrop {
+  id: int,
+  item: int,
+  count: int,
+  position: Vec3,
+};
+
+// See ItemDropRules in engine.cpp for the defaults.
+interface ItemDropRules {
+  gravity: number,
+  drag: number,
+  mergeRadius: number,
+  despawnTime: number,
+  pickupDelay: number,
+  magnetRadius: number,
+  magnetSpeed: number,
+};
+
+// A voxel in a game-defined dimension, at one end of a portal. See
+// PortalLinks in dimensions.h.
+interface PortalEnd {
+  dimension: int,
+  x: int,
+  y: int,
+  z: int,
+};
+
+// A structure's game-defined type and its inclusive bounds in world space.
+// See StructureIndex in structures.h.
+interface Structure {
+  type: int,
+  min: [int, int, int],
+  max: [int, int, int],
+};
+
+// Mismatches between a chunk's copies of its neighbors' borders and the
+// neighbors, listing the first few of each kind. Layer is a kChunkLayer*
+// value. See BorderReport in engine.cpp.
+interface BorderReport {
+  valid: boolean,
+  badVoxels: int,
+  badLights: int,
+  badHeights: int,
+  cells: {layer: int, x: int, y: int, z: int,
+          expected: int, actual: int}[],
+};
+
+// Problems in a chunk's geometry. See MeshReport in mesher.h.
+interface MeshReport {
+  valid: boolean,
+  quads: int,
+  duplicates: int,
+  overlaps: int,
+  holes: int,
+  extras: int,
+  outOfRange: int,
+};
+
+// A chunk's voxels as 8x8x8 bricks, for GPU ray marching. See bricks.h for
+// the layout of each array.
+interface BrickExport {
+  count: int,
+  bricks: Uint32Array,
+  palette: Uint32Array,
+  indices: Uint32Array,
+};
+
+// A copy of a tensor from the engine. Element (x, y, z) is at index
+// x * stride[0] + y * stride[1] + z * stride[2] of data.
+interface TensorView {
+  data: Uint8Array | Uint16Array | Uint32Array | Float32Array,
+  shape: [int, int, int],
+  stride: [int, int, int],
+};
+
+// A chunk mesh with welded vertices. See weldQuads in mesher.h for the layout
+// of the four ints per vertex. If requested, repeats has two per vertex: how
+// many times its quad's texture tiles along each of the face's axes. Each
+// quad is six indices, and quadMaterials has the MaterialId of each quad.
+interface IndexedMesh {
+  vertices: Int32Array,
+  indices: Uint32Array,
+  repeats?: Uint16Array,
+  quadMaterials: Uint8Array,
+};
+
+// A single draw of a chunk mesh for WebGL1: one atlas page, and at most 64K
+// vertices, with 16-bit indices. Vertices are float attributes, with
+// kBatchVertexFloats per vertex. See batchIndexedMesh in mesher.h.
+interface MeshBatch {
+  page: int,
+  vertices: Float32Array,
+  indices: Uint16Array,
+  repeats?: Uint16Array,
+  quadMaterials: Uint8Array,
+};
+
+// An engine-to-host notification. See events.h for each type's fields.
+interface EngineEvent {
+  type: int,
+  x: int,
+  y: int,
+  z: int,
+  a: int,
+  b: int,
+};
+
+// Host callbacks for custom block behavior. A block only triggers a hook if
+// the hook's bit is set in its Registry.setBlockHooks mask. Hooks may edit
+// the world, which may trigger further hooks.
+interface BlockHooks {
+  onBlockPlace?: (block: BlockId, x: int, y: int, z: int,
+                  replaced: BlockId) => void,
+  onBlockBreak?: (block: BlockId, x: int, y: int, z: int,
+                  replacement: BlockId) => void,
+  onRandomTick?: (block: BlockId, x: int, y: int, z: int) => void,
+  onEntityCollide?: (block: BlockId, x: int, y: int, z: int,
+                     entity: EntityId) => void,
+};
+
+interface PowerChange {
+  x: int,
+  y: int,
+  z: int,
+  level: int,
 };
 
 interface BlockSprite {
@@ -152,9 +472,135 @@ const kWhite: Color = [1, 1, 1, 1];
 
 const kNoMaterial = 0 as 0;
 
+// Material UV transforms: a counterclockwise rotation, plus an optional flip
+// of u, applied before rotating. Keep these in sync with kUV* in mesher.h.
+const kUVRotate90  = int(1);
+const kUVRotate180 = int(2);
+const kUVRotate270 = int(3);
+const kUVFlip      = int(1 << 2);
+
+// Brick export sizes, in uint32s. Keep these in sync with bricks.h.
+const kBrickWords  = 18;
+const kBrickVolume = 512;
+
+// Chunk layers and tensor element types. Keep these in sync with ChunkLayer
+// in engine.cpp and TensorType in base.h.
+const kChunkLayerVoxels    = int(0);
+const kChunkLayerLights    = int(1);
+const kChunkLayerMetadata  = int(2);
+const kChunkLayerHeightmap = int(3);
+const kTensorTypeU8  = 0;
+const kTensorTypeU16 = 1;
+const kTensorTypeU32 = 2;
+const kTensorTypeF32 = 3;
+
+// Chunk pipeline stages. Keep these in sync with ChunkStage in engine.cpp.
+const kChunkStageEmpty     = int(0);
+const kChunkStageTerrain   = int(1);
+const kChunkStageCarved    = int(2);
+const kChunkStageDecorated = int(3);
+const kChunkStageLit       = int(4);
+const kChunkStageMeshed    = int(5);
+
+// Face direction bits, for setChunkFaceMask: [-x, +x, -y, +y, -z, +z]. Keep
+// this in sync with kAllFaces in mesher.h.
+const kAllFaces = int((1 << 6) - 1);
+
+// Chunk meshing algorithms. Keep these in sync with MesherKind in mesher.h.
+const kMesherGreedy        = int(0);
+const kMesherNaive         = int(1);
+const kMesherSurfaceNets   = int(2);
+const kMesherMarchingCubes = int(3);
+
+// Block class tags. Keep these in sync with kBlockTag* in mesher.h.
+const kBlockTagLog    = int(1 << 0);
+const kBlockTagLeaves = int(1 << 1);
+const kBlockTagFixed  = int(1 << 2);
+
+// Engine event types. Keep these in sync with EventType in events.h.
+const kEventBlockChanged = int(1);
+const kEventChunkMeshed  = int(2);
+const kEventLightUpdated = int(3);
+const kEventEntityLanded = int(4);
+const kEventFluidFlowed  = int(5);
+const kEventEditRejected = int(6);
+const kEventProjectileHit = int(7);
+const kEventPlayerEdit    = int(8);
+const kEventTriggerEnter  = int(9);
+const kEventTriggerStay   = int(10);
+const kEventTriggerExit   = int(11);
+
+// Block hooks. Keep these in sync with BlockHook in hooks.h.
+const kHookPlace         = int(1 << 0);
+const kHookBreak         = int(1 << 1);
+const kHookRandomTick    = int(1 << 2);
+const kHookEntityCollide = int(1 << 3);
+
+// Power block kinds. Keep these in sync with PowerKind in mesher.h.
+const kPowerSource = int(1);
+const kPowerWire   = int(2);
+const kPowerSink   = int(3);
+
+// Projectile responses. Keep these in sync with ProjectileResponse in mesher.h.
+const kProjectileStick  = int(0);
+const kProjectileBounce = int(1);
+const kProjectilePass   = int(2);
+
 const kEmptyBlock = 0 as BlockId;
 const kUnknownBlock = 1 as BlockId;
 
+// Debug draw flags and shapes. Keep these in sync with debug.h.
+const kDebugLighting    = int(1 << 0);
+const kDebugProjectiles = int(1 << 1);
+const kDebugItemDrops   = int(1 << 2);
+const kDebugCamera      = int(1 << 3);
+
+const kDebugLine = int(1);
+const kDebugBox  = int(2);
+const kDebugText = int(3);
+
+// Items are game-defined. Keep this in sync with kNoItem in items.h.
+const kNoItem = int(-1);
+
+// CSG ops and clipboard shapes. Keep these in sync with clipboard.h.
+const kCsgUnion     = int(0);
+const kCsgSubtract  = int(1);
+const kCsgIntersect = int(2);
+const kClipboardBox      = int(0);
+const kClipboardSphere   = int(1);
+const kClipboardCylinder = int(2);
+
+// Symmetry mirror planes. Keep these in sync with symmetry.h.
+const kMirrorX = int(1 << 0);
+const kMirrorY = int(1 << 1);
+const kMirrorZ = int(1 << 2);
+
+// Player edit verdicts. Keep these in sync with EditVerdict in edits.h.
+const kEditAccepted     = int(0);
+const kEditUnloaded     = int(1);
+const kEditUnchanged    = int(2);
+const kEditRateLimited  = int(3);
+const kEditOutOfReach   = int(4);
+const kEditProtected    = int(5);
+const kEditUnbreakable  = int(6);
+const kEditTooFast      = int(7);
+const kEditInvalidBlock = int(8);
+const kEditUnplaceable  = int(9);
+
+// Floats per MeshBatch vertex: x, y, z, texture, ao, wave, d, dir,
+// uv_transform, and bright, which is 1 for full-bright quads. Keep this in
+// sync with kBatchVertexFloats in mesher.h.
+const kBatchVertexFloats = int(10);
+
+// Path brush profiles. Keep these in sync with PathProfile in paths.h.
+const kPathRect   = int(0);
+const kPathCircle = int(1);
+
+// Registry limits. Keep these in sync with kMaxBlocks and kMaxMaterials in
+// mesher.h: ids are bytes, and material id 0 is kNoMaterial.
+const kMaxBlocks = 256;
+const kMaxMaterials = 255;
+
 class Registry {
   // If a block's light value is -1, then the block is opaque and it always
   // has a computed light level of 0.
@@ -168,6 +614,13 @@ class Registry {
   private faces: MaybeMaterialId[];
   private meshes: (InstancedMesh | null)[];
   private materials: Material[];
+  private treeSpecies: int = 0;
+  private projectileKinds: int = 0;
+  private hooks: int[] = [];
+  private climbable: boolean[] = [];
+  private slowdown: number[] = [];
+  private friction: number[] = [];
+  private restitution: number[] = [];
   private ids: Map<string, MaterialId>;
   private helper: WasmHelper;
   private renderer: Renderer;
@@ -209,6 +662,7 @@ class Registry {
       }
     })();
 
+    this.checkBlockLimit();
     let opaque = true;
     materials.forEach(x => {
       const id = this.ids.get(x);
@@ -233,6 +687,7 @@ class Registry {
   }
 
   addBlockMesh(mesh: InstancedMesh, solid: boolean, light: int = 0): BlockId {
+    this.checkBlockLimit();
     const result = this.opaque.length as BlockId;
     for (let i = 0; i < 6; i++) this.faces.push(kNoMaterial);
     this.meshes.push(mesh);
@@ -243,21 +698,352 @@ class Registry {
     return result;
   }
 
-  addMaterial(name: string, texture: Texture, liquid: boolean = false) {
+  // uvTransform is a combination of the kUV* flags. Materials that share a
+  // texture with different transforms reuse the same atlas slot.
+  // A material with a normal map gets bump-mapped lighting. The voxel shader
+  // derives each face's tangent frame from its direction and uvTransform.
+  // Full-bright materials ignore AO and lighting, e.g. for lava or portals.
+  addMaterial(name: string, texture: Texture, liquid: boolean = false,
+              uvTransform: int = 0, normalMap: Texture | null = null,
+              fullBright: boolean = false) {
     assert(name.length > 0, () => 'Empty material name!');
     assert(!this.ids.has(name), () => `Duplicate material: ${name}`);
+    assert(0 <= uvTransform && uvTransform < 8);
+    if (this.materials.length >= kMaxMaterials) {
+      throw new Error(`Too many materials: the limit is ${kMaxMaterials}`);
+    }
     const id = this.materials.length as MaterialId;
-    const textureIndex = this.renderer.addTexture(texture);
+    const shared = this.materials.find(
+        x => x.texture === texture && x.normalMap === normalMap);
+    const textureIndex =
+        shared ? shared.textureIndex : this.renderer.addTexture(texture);
+    if (normalMap && !shared) {
+      const slot = this.renderer.loadTexture(normalMap);
+      this.renderer.setNormalMapping(textureIndex, slot);
+    }
     this.ids.set(name, id);
-    this.materials.push({liquid, texture, textureIndex});
+    this.materials.push(
+        {liquid, texture, textureIndex, uvTransform, normalMap, fullBright});
     this.registerMaterial(id);
   }
 
+  // Each drop table entry independently drops [min, max] of the item with
+  // the given chance. Tables are created as we add entries to them.
+  addDropEntry(table: int, item: int, min: int, max: int, chance: number = 1) {
+    this.helper.module.asm.registerDropEntry(table, item, min, max, chance);
+  }
+
+  // A negative hardness is unbreakable. If tool is non-zero, the block only
+  // drops items when broken by that tool class at the given tier or above.
+  // A dropTable of -1 means that the block drops itself.
+  setBlockMining(id: BlockId, hardness: number, tool: int = 0,
+                 tier: int = 0, dropTable: int = -1): void {
+    assert(0 <= id && id < this.opaque.length);
+    this.helper.module.asm.registerBlockMining(
+        id, hardness, tool, tier, dropTable);
+  }
+
+  // Each tick, a fire spreads into each adjacent block with its ignite chance
+  // and burns away each adjacent block with its burn chance.
+  setBlockFlammability(id: BlockId, ignite: number, burn: number): void {
+    assert(0 <= id && id < this.opaque.length);
+    this.helper.module.asm.registerBlockFire(id, ignite, burn);
+  }
+
+  // Hooks is a bitmask of kHook* values. See BlockHooks and Env.setHooks.
+  setBlockHooks(id: BlockId, hooks: int): void {
+    assert(0 <= id && id < this.opaque.length);
+    this.hooks[id] = hooks;
+    this.helper.module.asm.registerBlockHooks(id, hooks);
+  }
+
+  hasBlockHook(id: BlockId, hook: int): boolean {
+    return ((this.hooks[id] || 0) & hook) !== 0;
+  }
+
+  // Entities in a climbable block, like a ladder or vines, climb it while
+  // jumping and slide down it slowly otherwise. Entities in a block, or
+  // standing on it, move at its slowdown times their usual speed: e.g. 0.25
+  // for cobwebs, or 0.5 for soul sand. See runPhysics in main.ts.
+  setBlockMovement(id: BlockId, climbable: boolean,
+                   slowdown: number = 1): void {
+    assert(0 <= id && id < this.opaque.length);
+    assert(0 < slowdown && slowdown <= 1);
+    this.climbable[id] = climbable;
+    this.slowdown[id] = slowdown;
+  }
+
+  isClimbable(id: BlockId): boolean {
+    return this.climbable[id] || false;
+  }
+
+  getSlowdown(id: BlockId): number {
+    return this.slowdown[id] || 1;
+  }
+
+  // Entities on the block scale their ground friction by its friction, e.g.
+  // 0.1 for ice, and land with at least its restitution, e.g. 0.8 for slime.
+  // Entities on several blocks blend them by area; see blendSurface.
+  setBlockSurface(id: BlockId, friction: number,
+                  restitution: number = 0): void {
+    assert(0 <= id && id < this.opaque.length);
+    assert(friction >= 0 && 0 <= restitution && restitution <= 1);
+    this.friction[id] = friction;
+    this.restitution[id] = restitution;
+  }
+
+  getFriction(id: BlockId): number {
+    const result = this.friction[id];
+    return result === undefined ? 1 : result;
+  }
+
+  getRestitution(id: BlockId): number {
+    return this.restitution[id] || 0;
+  }
+
+  // Tags is a bitmask of kBlockTag* values. When we remove a log, leaves that
+  // are too far from the nearest remaining log decay over the next few ticks.
+  setBlockTags(id: BlockId, tags: int): void {
+    assert(0 <= id && id < this.opaque.length);
+    this.helper.module.asm.registerBlockTags(id, tags);
+  }
+
+  // Transparent blocks can absorb extra light levels, on top of the one that
+  // light loses per block, so that water and leaves dim the light under them.
+  // Set it before loading chunks: we don't relight chunks that are loaded.
+  setBlockAttenuation(id: BlockId, attenuation: int): void {
+    assert(0 <= id && id < this.opaque.length);
+    assert(0 <= attenuation && attenuation < 15);
+    this.helper.module.asm.registerBlockAttenuation(id, attenuation);
+  }
+
+  // How much of an explosion ray's intensity the block absorbs; see
+  // Env.explode. Minecraft uses 6 for stone, 0.5 for dirt, and 100 for water.
+  setBlockBlastResistance(id: BlockId, resistance: number): void {
+    assert(0 <= id && id < this.opaque.length);
+    assert(resistance >= 0);
+    this.helper.module.asm.registerBlockBlastResistance(id, resistance);
+  }
+
+  // CSG edits only replace a block with one of at least its priority, and
+  // carving replaces blocks with air, which has priority 0 by default. Give
+  // ores or bedrock a higher priority to keep building tools off of them.
+  setBlockEditPriority(id: BlockId, priority: int): void {
+    assert(0 <= id && id < this.opaque.length);
+    assert(0 <= priority && priority < 256);
+    this.helper.module.asm.registerBlockEditPriority(id, priority);
+  }
+
+  // Gives the block a trigger volume, of a game-defined type from 1 to 31,
+  // spanning [min, max] in the unit voxel: e.g. a pressure plate's bottom
+  // sixteenth. Env.stepTriggers raises kEventTrigger* events as entities
+  // enter, stay in, and exit each type of trigger. Type 0 removes it.
+  setBlockTrigger(id: BlockId, type: int,
+                  min: [number, number, number] = [0, 0, 0],
+                  max: [number, number, number] = [1, 1, 1]): void {
+    assert(0 <= id && id < this.opaque.length);
+    assert(0 <= type && type < 32);
+    this.helper.module.asm.registerBlockTrigger(
+        id, type, min[0], min[1], min[2], max[0], max[1], max[2]);
+  }
+
+  // kind is one of the kPower* values. Sources emit power at their level;
+  // wires and sinks take power from their neighbors. See Env.propagatePower.
+  setBlockPower(id: BlockId, kind: int, level: int = int(0)): void {
+    assert(0 <= id && id < this.opaque.length);
+    assert(0 <= level && level <= 15);
+    this.helper.module.asm.registerBlockPower(id, kind, level);
+  }
+
+  // Returns a species id for use with setSapling and Env.growTree.
+  addTreeSpecies(trunk: BlockId, leaves: BlockId, minHeight: int,
+                 maxHeight: int, radius: int, conical: boolean): int {
+    const species = int(this.treeSpecies++);
+    this.helper.module.asm.registerTreeSpecies(
+        species, trunk, leaves, minHeight, maxHeight, radius, conical);
+    return species;
+  }
+
+  // Returns a kind id for use with Env.addProjectile. Gravity is in blocks
+  // per second squared, and drag is the fraction of speed lost per second.
+  // Bounces keep restitution of the projectile's speed.
+  addProjectileKind(gravity: number, drag: number, restitution: number): int {
+    const kind = int(this.projectileKinds++);
+    this.helper.module.asm.registerProjectileKind(
+        kind, gravity, drag, restitution);
+    return kind;
+  }
+
+  // response is one of the kProjectile* values. By default, projectiles
+  // stick in solid blocks, e.g. arrows; a bullet might pass through leaves
+  // and bounce off of metal. Projectiles always pass non-solid blocks.
+  setProjectileResponse(kind: int, id: BlockId, response: int): void {
+    assert(0 <= kind && kind < this.projectileKinds);
+    assert(0 <= id && id < this.opaque.length);
+    assert(kProjectileStick <= response && response <= kProjectilePass);
+    this.helper.module.asm.registerProjectileResponse(kind, id, response);
+  }
+
+  // On random ticks, the sapling grows into a tree with the given chance.
+  setSapling(id: BlockId, species: int, chance: number): void {
+    assert(0 <= id && id < this.opaque.length);
+    assert(0 <= species && species < this.treeSpecies);
+    this.helper.module.asm.registerSapling(id, species, chance);
+  }
+
+  // On random ticks, the block converts nearby uncovered target blocks lit
+  // to at least minLight, and it decays if it's covered by an opaque block.
+  // For example: setBlockSpread(grass, dirt, dirt, 9).
+  setBlockSpread(id: BlockId, target: BlockId,
+                 decay: BlockId, minLight: int): void {
+    assert(0 <= id && id < this.opaque.length);
+    this.helper.module.asm.registerBlockSpread(id, target, decay, minLight);
+  }
+
+  setFireBlock(id: BlockId): void {
+    assert(0 <= id && id < this.opaque.length);
+    this.helper.module.asm.registerFireBlock(id);
+  }
+
+  // Returns the time in seconds to break the block, or -1 if it can't be.
+  getBreakTime(id: BlockId, tool: int, tier: int): number {
+    return this.helper.module.asm.breakTime(id, tool, tier);
+  }
+
+  getDrops(id: BlockId, tool: int, tier: int, seed: int): Drop[] {
+    const module = this.helper.module;
+    const offset = module.asm.dropsFor(id, tool, tier, seed) >> 2;
+    const heap = module.HEAP32;
+    const result: Drop[] = [];
+    for (let i = 0; i < heap[offset]; i++) {
+      const base = offset + 1 + 2 * i;
+      result.push({item: int(heap[base]), count: int(heap[base + 1])});
+    }
+    return result;
+  }
+
+  // Crafting, shared by client prediction and server checks. Grids are
+  // row-major item arrays with kNoItem in empty cells. Shaped patterns match
+  // anywhere in the grid, or mirrored; shapeless ones in any arrangement. If
+  // several recipes match, the first one added wins. See crafting.h.
+  setItemTags(item: int, tags: int): void {
+    this.helper.module.asm.registerItemTags(item, tags);
+  }
+
+  // Items stack to 64 unless we set another limit. See Env.addToInventory.
+  setItemMaxStack(item: int, max: int): void {
+    this.helper.module.asm.registerItemMaxStack(item, max);
+  }
+
+  addShapedRecipe(width: int, height: int,
+                  pattern: Ingredient[], result: Drop): int {
+    assert(pattern.length === width * height);
+    const module = this.helper.module;
+    const data = this.writeIngredients(pattern);
+    const id = module.asm.registerShapedRecipe(
+        width, height, data, result.item, result.count);
+    module.asm.free(data);
+    return id;
+  }
+
+  addShapelessRecipe(ingredients: Ingredient[], result: Drop): int {
+    const module = this.helper.module;
+    const data = this.writeIngredients(ingredients);
+    const id = module.asm.registerShapelessRecipe(
+        data, int(ingredients.length), result.item, result.count);
+    module.asm.free(data);
+    return id;
+  }
+
+  // Returns the id of the recipe that the grid matches, or -1 for none.
+  matchRecipe(grid: ArrayLike<number>, width: int, height: int): int {
+    assert(grid.length === width * height);
+    const module = this.helper.module;
+    const data = module.asm.malloc(int(4 * Math.max(grid.length, 1)));
+    module.HEAP32.set(grid, data >> 2);
+    const result = module.asm.matchRecipe(data, width, height);
+    module.asm.free(data);
+    return result;
+  }
+
+  getRecipeResult(id: int): Drop | null {
+    const module = this.helper.module;
+    const data = module.asm.recipeResult(id);
+    if (data === 0) return null;
+    const offset = data >> 2;
+    const heap = module.HEAP32;
+    return {item: int(heap[offset]), count: int(heap[offset + 1])};
+  }
+
+  // Loot tables: each pool whose condition holds is rolled [minRolls,
+  // maxRolls] times with the given chance, and each roll picks one eligible
+  // entry by weight, which drops [min, max + fortune * fortuneBonus] of its
+  // item. Rolls are deterministic given the seed. See loot.h.
+  addLootPool(table: int, minRolls: int, maxRolls: int,
+              chance: number = 1, condition: LootCondition = {}): int {
+    const {tool = 0, minTier = 0, minFortune = 0, flags = 0} = condition;
+    return this.helper.module.asm.registerLootPool(
+        table, minRolls, maxRolls, chance, tool, minTier, minFortune, flags);
+  }
+
+  addLootEntry(table: int, pool: int, item: int, weight: int, min: int,
+               max: int, fortuneBonus: int = 0,
+               condition: LootCondition = {}): void {
+    const {tool = 0, minTier = 0, minFortune = 0, flags = 0} = condition;
+    this.helper.module.asm.registerLootEntry(table, pool, item, weight, min,
+        max, fortuneBonus, tool, minTier, minFortune, flags);
+  }
+
+  rollLoot(table: int, context: LootContext, seed: int): Drop[] {
+    const module = this.helper.module;
+    const data = module.asm.malloc(int(16));
+    const {tool, tier, fortune, flags} = context;
+    module.HEAP32.set([tool, tier, fortune, flags], data >> 2);
+    const offset = module.asm.rollLoot(table, data, seed) >> 2;
+    module.asm.free(data);
+
+    const heap = module.HEAP32;
+    const result: Drop[] = [];
+    for (let i = 0; i < heap[offset]; i++) {
+      const base = offset + 1 + 2 * i;
+      result.push({item: int(heap[base]), count: int(heap[base + 1])});
+    }
+    return result;
+  }
+
   // faces has 6 elements for each block type: [+x, -x, +y, -y, +z, -z]
   getBlockFaceMaterial(id: BlockId, face: int): MaybeMaterialId {
     return this.faces[id * 6 + face];
   }
 
+  // Resource packs: load each of the pack's textures into a new atlas slot,
+  // then point the material's texture id at it. Chunk meshes store texture
+  // ids, so this swap doesn't require remeshing.
+  replaceMaterialTexture(name: string, texture: Texture): void {
+    const id = this.ids.get(name);
+    if (id === undefined) throw new Error(`Unknown material: ${name}`);
+    const material = this.materials[id];
+    const slot = this.renderer.loadTexture(texture);
+    this.renderer.setTextureMapping(material.textureIndex, slot);
+  }
+
+  // The lower-level API: maps a material's texture id to an atlas slot from
+  // Renderer.loadTexture. Materials that share a texture share its id.
+  setTextureMapping(textureIndex: int, slot: int): void {
+    this.renderer.setTextureMapping(textureIndex, slot);
+  }
+
+  // Renders an isometric RGBA icon of the block from its +y, +z, and +x faces,
+  // using the same texture data that we upload for the voxel shader.
+  renderBlockIcon(id: BlockId, size: int): Uint8ClampedArray {
+    const textures = [2, 4, 0].map((face): int => {
+      const material = this.getBlockFaceMaterial(id, int(face));
+      if (material === kNoMaterial) return int(-1);
+      return this.getMaterialData(material).textureIndex;
+    });
+    return this.renderer.renderIcon(textures, size);
+  }
+
   getBlockMesh(id: BlockId): InstancedMesh | null {
     return this.meshes[id];
   }
@@ -267,6 +1053,16 @@ class Registry {
     return this.materials[id - 1];
   }
 
+  // The caller must free the result.
+  private writeIngredients(ingredients: Ingredient[]): WasmCharPtr {
+    const module = this.helper.module;
+    const data = module.asm.malloc(int(8 * Math.max(ingredients.length, 1)));
+    ingredients.forEach((x, i) => {
+      module.HEAP32.set([x.item, x.tags], (data >> 2) + 2 * i);
+    });
+    return data;
+  }
+
   private registerBlock(id: BlockId): void {
     assert(0 <= id && id < this.opaque.length);
     const b = 6 * id;
@@ -277,13 +1073,20 @@ class Registry {
         faces[b + 3], faces[b + 4], faces[b + 5]);
   }
 
+  private checkBlockLimit(): void {
+    if (this.opaque.length >= kMaxBlocks) {
+      throw new Error(`Too many blocks: the limit is ${kMaxBlocks}`);
+    }
+  }
+
   private registerMaterial(id: MaterialId): void {
     assert(0 <= id && id < this.materials.length);
     const material = this.materials[id]
     const [r, g, b, a] = material.texture.color;
     this.helper.module.asm.registerMaterial(
         id, material.liquid, material.texture.alphaTest,
-        material.textureIndex, r, g, b, a);
+        material.textureIndex, r, g, b, a, material.uvTransform,
+        material.fullBright);
   }
 };
 
@@ -305,219 +1108,1747 @@ class Performance {
     this.sum = 0;
   }
 
-  begin() {
-    this.last = this.now.now();
+  begin() {
+    this.last = this.now.now();
+  }
+
+  end() {
+    const index = this.index;
+    const next_index = index + 1;
+    this.index = int(next_index < this.ticks.length ? next_index : 0);
+    const tick = int(Math.round(1000 * (this.now.now() - this.last)));
+    this.sum += tick - this.ticks[index];
+    this.ticks[index] = tick;
+  }
+
+  frame(): int {
+    return this.index;
+  }
+
+  max(): number {
+    return Math.max.apply(null, this.ticks);
+  }
+
+  mean(): number {
+    return this.sum / this.ticks.length;
+  }
+};
+
+//////////////////////////////////////////////////////////////////////////////
+
+const kTickResolution = 4;
+const kTicksPerFrame = 4;
+const kTicksPerSecond = 60;
+
+type Callback = (dt: number) => void;
+
+class Timing {
+  private now: any;
+  private remesh: Callback;
+  private render: Callback;
+  private update: Callback;
+  private renderBinding: () => void;
+  private updateDelay: number;
+  private updateLimit: number;
+  private lastRender: int;
+  private lastUpdate: int;
+  remeshPerf: Performance;
+  renderPerf: Performance;
+  updatePerf: Performance;
+
+  constructor(remesh: Callback, render: Callback, update: Callback) {
+    this.now = performance || Date;
+    this.remesh = remesh;
+    this.render = render;
+    this.update = update;
+
+    const now = this.now.now();
+    this.lastRender = now;
+    this.lastUpdate = now;
+
+    this.renderBinding = this.renderHandler.bind(this);
+    requestAnimationFrame(this.renderBinding);
+
+    this.updateDelay = 1000 / kTicksPerSecond;
+    this.updateLimit = this.updateDelay * kTicksPerFrame;
+    const updateInterval = this.updateDelay / kTickResolution;
+    setInterval(this.updateHandler.bind(this), updateInterval);
+
+    this.remeshPerf = new Performance(this.now, 60);
+    this.renderPerf = new Performance(this.now, 60);
+    this.updatePerf = new Performance(this.now, 60);
+  }
+
+  renderHandler() {
+    requestAnimationFrame(this.renderBinding);
+    this.updateHandler();
+
+    const now = this.now.now();
+    const dt = (now - this.lastRender) / 1000;
+    this.lastRender = now;
+
+    try {
+      this.remeshPerf.begin();
+      this.remesh(dt);
+      this.remeshPerf.end();
+      this.renderPerf.begin();
+      this.render(dt);
+      this.renderPerf.end();
+    } catch (e) {
+      this.onError(e);
+    }
+  }
+
+  private updateHandler() {
+    let now = this.now.now();
+    const delay = this.updateDelay;
+    const limit = now + this.updateLimit;
+
+    while (this.lastUpdate + delay < now) {
+      try {
+        this.updatePerf.begin();
+        this.update(delay / 1000);
+        this.updatePerf.end();
+      } catch (e) {
+        this.onError(e);
+      }
+      this.lastUpdate += delay;
+      now = this.now.now();
+
+      if (now > limit) {
+        this.lastUpdate = now;
+        break;
+      }
+    }
+  }
+
+  private onError(e: any) {
+    this.remesh = this.render = this.update = () => {};
+    console.error(e);
+  }
+};
+
+//////////////////////////////////////////////////////////////////////////////
+
+const kTmpPos     = Vec3.create();
+const kTmpMin     = Vec3.create();
+const kTmpMax     = Vec3.create();
+const kTmpDelta   = Vec3.create();
+const kTmpImpacts = Vec3.create();
+const kTmpCellMin = Vec3.create();
+const kTmpCellMax = Vec3.create();
+
+const kMinZLowerBound = 0.001;
+const kMinZUpperBound = 0.1;
+
+const kChunkWidth  = 16;
+const kWorldHeight = 256;
+
+const kChunkRadius = 12;
+const kFrontierRadius = 8;
+const kFrontierLevels = 6;
+
+// At 4096 blocks from the origin, f32s still resolve 1/2048 of a block.
+const kRebaseDistance = 4096;
+
+const kSunlightLevel = 0xf;
+
+const lighting = (x: int): number => Math.pow(0.8, kSunlightLevel - x);
+
+interface Rect {x: int, y: int, w: int, h: int, value: int};
+
+interface Impostor {
+  resolution: int,
+  heights: Uint32Array,
+  colors: Uint8Array,
+};
+
+class Env {
+  entities: EntityComponentSystem;
+  spatial: SpatialIndex;
+  registry: Registry;
+  renderer: Renderer;
+  deterministic: boolean;
+  private helper: WasmHelper;
+  private cameraColor: Color;
+  private cameraMaterial: MaybeMaterialId;
+  private container: Container;
+  private highlight: HighlightMesh;
+  private highlightSide: int = -1;
+  private highlightPosition: Vec3;
+  private timing: Timing;
+  private frame: number = 0;
+
+  constructor(id: string) {
+    this.container = new Container(id);
+    this.entities = new EntityComponentSystem();
+    this.spatial = new SpatialIndex(kChunkWidth);
+    this.renderer = new Renderer(this.container.canvas);
+
+    this.helper = nonnull(helper);
+    this.helper.renderer = this.renderer;
+    this.helper.initializeWorld(kChunkRadius, kFrontierRadius, kFrontierLevels);
+
+    // Lockstep games need a fixed-point engine build, so that worldgen is
+    // bit-identical everywhere. Physics runs in JS, not in fixed point; we
+    // round its state to a grid in these builds, but see main.ts for what
+    // actually keeps it deterministic.
+    this.deterministic = (this.helper.features & kFeatureFixedPoint) !== 0;
+
+    this.registry = new Registry(this.helper, this.renderer);
+    this.highlight = this.renderer.addHighlightMesh();
+    this.highlightPosition = Vec3.create();
+
+    this.cameraColor = kWhite.slice() as Color;
+    this.cameraMaterial = kNoMaterial;
+
+    const remesh = this.remesh.bind(this);
+    const render = this.render.bind(this);
+    const update = this.update.bind(this);
+    this.timing = new Timing(remesh, render, update);
+  }
+
+  // Starts a multi-frame ray-traced AO bake for the loaded chunk (cx, cz).
+  // Meant for static scenes: we drop the bake if the chunk is reloaded.
+  bakeAmbientOcclusion(cx: int, cz: int, samples: int): boolean {
+    return this.helper.module.asm.bakeAmbientOcclusion(cx, cz, samples);
+  }
+
+  // Checks that every voxel in every loaded chunk is a registered block and
+  // that every block face uses a registered material, e.g. after a load.
+  validateWorld(): WorldReport {
+    const module = this.helper.module;
+    const offset = module.asm.validateWorld() >> 2;
+    const heap = module.HEAP32;
+    const numVoxels = heap[offset + 4], numFaces = heap[offset + 5];
+    const result: WorldReport = {
+      valid: heap[offset] !== 0,
+      chunks: int(heap[offset + 1]),
+      badVoxels: int(heap[offset + 2]),
+      badFaces: int(heap[offset + 3]),
+      voxels: [],
+      faces: [],
+    };
+    let cur = offset + 6;
+    for (let i = 0; i < numVoxels; i++, cur += 4) {
+      result.voxels.push({x: int(heap[cur + 0]), y: int(heap[cur + 1]),
+                          z: int(heap[cur + 2]), block: int(heap[cur + 3])});
+    }
+    for (let i = 0; i < numFaces; i++, cur += 3) {
+      result.faces.push({block: int(heap[cur + 0]), face: int(heap[cur + 1]),
+                         material: int(heap[cur + 2])});
+    }
+    return result;
+  }
+
+  // Re-meshes the loaded chunk (cx, cz) and checks its geometry against its
+  // voxels. Slow; meant for debugging and fuzzing the mesher.
+  validateMesh(cx: int, cz: int): MeshReport | null {
+    const ptr = this.helper.module.asm.validateMesh(cx, cz);
+    return ptr === 0 ? null : this.readMeshReport(ptr);
+  }
+
+  // Checks the loaded chunk (cx, cz)'s copies of its neighbors' voxels,
+  // heightmap, and edge lights against the neighbors. Border desyncs show up
+  // as seams; this makes them detectable without reproducing them visually.
+  checkBorders(cx: int, cz: int): BorderReport | null {
+    const module = this.helper.module;
+    const offset = module.asm.checkBorders(cx, cz) >> 2;
+    if (offset === 0) return null;
+    const heap = module.HEAP32;
+    const result: BorderReport = {
+      valid: heap[offset] !== 0,
+      badVoxels: int(heap[offset + 1]),
+      badLights: int(heap[offset + 2]),
+      badHeights: int(heap[offset + 3]),
+      cells: [],
+    };
+    for (let i = 0, cur = offset + 5; i < heap[offset + 4]; i++, cur += 6) {
+      const [layer, x, y, z, expected, actual] =
+          Array.from(heap.subarray(cur, cur + 6)).map(int);
+      result.cells.push({layer, x, y, z, expected, actual});
+    }
+    return result;
+  }
+
+  // Meshes each of the engine's golden-path fixture chunks and checks them
+  // against its reference mesher. Call it after changing the mesher.
+  checkMeshFixtures(): MeshReport {
+    return this.readMeshReport(this.helper.module.asm.checkMeshFixtures());
+  }
+
+  // Re-meshes the loaded chunk (cx, cz) as indexed triangles, sharing the
+  // vertices of adjacent quads. Phase 0 is solid geometry and 1 is water.
+  // Set repeats for texture-atlas renderers that wrap tiles themselves.
+  weldMesh(cx: int, cz: int, phase: int,
+           repeats: boolean = false): IndexedMesh | null {
+    const module = this.helper.module;
+    const ptr = module.asm.weldMesh(cx, cz, phase, repeats);
+    if (ptr === 0) return null;
+    const offset = ptr >> 2;
+    const vertices = 4 * module.HEAP32[offset];
+    const indices = module.HEAP32[offset + 1];
+    const start = offset + 2, end = start + vertices;
+    const result: IndexedMesh = {
+      vertices: module.HEAP32.slice(start, end),
+      indices: module.HEAPU32.slice(end, end + indices),
+      quadMaterials: new Uint8Array(0),
+    };
+    let next = end + indices;
+    if (repeats) {
+      result.repeats = module.HEAPU16.slice(2 * next, 2 * next + vertices / 2);
+      next += vertices / 4;
+    }
+    const quads = indices / 6;
+    result.quadMaterials = module.HEAPU8.slice(4 * next, 4 * next + quads);
+    return result;
+  }
+
+  // Like weldMesh, but split into batches for WebGL1 fallback renderers: by
+  // atlas page, where pages[texture] is the page of each texture id, and
+  // then into batches of at most 64K vertices, with 16-bit indices.
+  batchMesh(cx: int, cz: int, phase: int, pages: Uint8Array,
+            repeats: boolean = false): MeshBatch[] | null {
+    assert(pages.length === 256);
+    const module = this.helper.module;
+    const data = module.asm.malloc(int(pages.length));
+    module.HEAPU8.set(pages, data);
+    const ptr = module.asm.batchMesh(cx, cz, phase, repeats, data);
+    module.asm.free(data);
+    if (ptr === 0) return null;
+
+    const heap = module.HEAP32;
+    const result: MeshBatch[] = [];
+    let offset = (ptr >> 2) + 1;
+    for (let i = 0; i < heap[ptr >> 2]; i++) {
+      const page = int(heap[offset]);
+      const vertices = heap[offset + 1], indices = heap[offset + 2];
+      offset += 3;
+      const floats = kBatchVertexFloats * vertices;
+      const batch: MeshBatch = {
+        page,
+        vertices: module.HEAPF32.slice(offset, offset + floats),
+        indices: new Uint16Array(0),
+        quadMaterials: new Uint8Array(0),
+      };
+      offset += floats;
+      if (repeats) {
+        batch.repeats = module.HEAPU16.slice(2 * offset,
+                                             2 * (offset + vertices));
+        offset += vertices;
+      }
+      batch.indices = module.HEAPU16.slice(2 * offset, 2 * offset + indices);
+      offset += Math.ceil(indices / 2);
+      const quads = indices / 6;
+      batch.quadMaterials = module.HEAPU8.slice(4 * offset, 4 * offset + quads);
+      offset += Math.ceil(quads / 4);
+      result.push(batch);
+    }
+    return result;
+  }
+
+  // Shows a mesh of an n x n heightmap with its corner at (x, z), without
+  // loading any chunks: for superflat worlds and menu backgrounds. columns
+  // has 4 bytes per column, indexed by x + n * z: the surface block and its
+  // height, then the water block and its height. Pass n = 0 to clear it.
+  setHeightmapMesh(columns: Uint8Array, n: int, x: int, z: int): void {
+    assert(columns.length === 4 * n * n);
+    const module = this.helper.module;
+    const data = module.asm.malloc(int(Math.max(columns.length, 1)));
+    module.HEAPU8.set(columns, data);
+    module.asm.setHeightmapMesh(data, n, x, z);
+    module.asm.free(data);
+  }
+
+  // Records the exports that change the world, for replaying a session
+  // against a debug build. Start it after registering blocks and materials.
+  // Starting drops the partial tick that advanceWorld has accumulated.
+  startRecording(): void {
+    this.helper.module.asm.startRecording();
+  }
+
+  stopRecording(): Uint8Array {
+    const module = this.helper.module;
+    const data = module.asm.stopRecording();
+    const size = module.HEAPU32[data >> 2];
+    return module.HEAPU8.slice(data + 4, data + 4 + size);
+  }
+
+  // Replays a stream from stopRecording. Returns the number of commands
+  // replayed, or -1 if the stream is malformed.
+  replay(stream: Uint8Array): int {
+    const module = this.helper.module;
+    const data = module.asm.malloc(int(Math.max(stream.length, 1)));
+    module.HEAPU8.set(stream, data);
+    const result = module.asm.replay(data, int(stream.length));
+    module.asm.free(data);
+    return result;
+  }
+
+  // Prints one slice of the loaded chunk (cx, cz) as a grid of characters,
+  // one per block, for pasting into bug reports. axis is 0, 1, or 2 for x,
+  // y, or z. Returns an empty string if the chunk isn't loaded.
+  dumpSlice(cx: int, cz: int, axis: int, index: int): string {
+    const module = this.helper.module;
+    const ptr = module.asm.dumpSlice(cx, cz, axis, index);
+    const end = module.HEAPU8.indexOf(0, ptr);
+    return new TextDecoder().decode(module.HEAPU8.slice(ptr, end));
+  }
+
+  // Loads a slice from dumpSlice into the loaded chunk (cx, cz). Returns
+  // false, without changing any blocks, if the slice can't be parsed.
+  loadSlice(cx: int, cz: int, text: string): boolean {
+    const module = this.helper.module;
+    const bytes = new TextEncoder().encode(text);
+    const data = module.asm.malloc(int(bytes.length + 1));
+    module.HEAPU8.set(bytes, data);
+    module.HEAPU8[data + bytes.length] = 0;
+    const result = module.asm.loadSlice(cx, cz, data);
+    module.asm.free(data);
+    return result;
+  }
+
+  // Copies one of the loaded chunk (cx, cz)'s arrays: a kChunkLayer* value.
+  getChunkLayer(cx: int, cz: int, layer: int): TensorView | null {
+    const module = this.helper.module;
+    const ptr = module.asm.getChunkLayer(cx, cz, layer);
+    if (ptr === 0) return null;
+
+    const offset = ptr >> 2;
+    const heap = module.HEAP32;
+    const type = heap[offset];
+    const shape: [int, int, int] =
+        [int(heap[offset + 1]), int(heap[offset + 2]), int(heap[offset + 3])];
+    const stride: [int, int, int] =
+        [int(heap[offset + 4]), int(heap[offset + 5]), int(heap[offset + 6])];
+    const size = shape[0] * shape[1] * shape[2];
+    const data = heap[offset + 7];
+
+    switch (type) {
+      case kTensorTypeU8:
+        return {data: module.HEAPU8.slice(data, data + size), shape, stride};
+      case kTensorTypeU16: {
+        const start = data >> 1;
+        const copy = module.HEAPU16.slice(start, start + size);
+        return {data: copy, shape, stride};
+      }
+      case kTensorTypeU32: {
+        const start = data >> 2;
+        const copy = module.HEAPU32.slice(start, start + size);
+        return {data: copy, shape, stride};
+      }
+      case kTensorTypeF32: {
+        const start = data >> 2;
+        const copy = module.HEAPF32.slice(start, start + size);
+        return {data: copy, shape, stride};
+      }
+    }
+    throw new Error(`Unknown tensor type: ${type}`);
+  }
+
+  // Returns a copy of one mip level of the loaded chunk (cx, cz)'s cone-tracing
+  // volume: (occupancy, emission) bytes for each cell, in chunk index order.
+  // Level l is (16 >> l) x (256 >> l) x (16 >> l) cells, for l in [0, 4].
+  getConeVolume(cx: int, cz: int, level: int): Uint8Array | null {
+    const module = this.helper.module;
+    const ptr = module.asm.getConeVolume(cx, cz, level);
+    if (ptr === 0) return null;
+    const cells = (kChunkWidth >> level) ** 2 * (kWorldHeight >> level);
+    return module.HEAPU8.slice(ptr, ptr + 2 * cells);
+  }
+
+  // Returns a copy of one mip level of the loaded chunk (cx, cz)'s collision
+  // field, for GPU particles to collide with: one bit per cell, set if any
+  // voxel in the cell is solid, packed low bit first in chunk index order.
+  // Level l is (16 >> l) x (256 >> l) x (16 >> l) cells, for l in [0, 4].
+  // Upload it again whenever getCollisionFieldVersion changes.
+  getCollisionField(cx: int, cz: int, level: int): Uint32Array | null {
+    const module = this.helper.module;
+    const ptr = module.asm.getCollisionField(cx, cz, level);
+    if (ptr === 0) return null;
+    const cells = (kChunkWidth >> level) ** 2 * (kWorldHeight >> level);
+    const words = 2 * Math.ceil(cells / 64);
+    return module.HEAPU32.slice(ptr >> 2, (ptr >> 2) + words);
+  }
+
+  // Returns 0 if the chunk isn't loaded.
+  getCollisionFieldVersion(cx: int, cz: int): int {
+    return this.helper.module.asm.getCollisionFieldVersion(cx, cz);
+  }
+
+  // Exports the loaded chunk (cx, cz) as bricks with occupancy masks and
+  // per-brick palettes, ready to upload to a GPU buffer.
+  exportBricks(cx: int, cz: int): BrickExport | null {
+    const module = this.helper.module;
+    const ptr = module.asm.exportBricks(cx, cz);
+    if (ptr === 0) return null;
+    const offset = ptr >> 2;
+    const count = int(module.HEAPU32[offset]);
+    const bricks = offset + 2;
+    const palette = bricks + kBrickWords * count;
+    const indices = palette + module.HEAPU32[offset + 1];
+    const end = indices + (kBrickVolume / 4) * count;
+    return {
+      count,
+      bricks: module.HEAPU32.slice(bricks, palette),
+      palette: module.HEAPU32.slice(palette, indices),
+      indices: module.HEAPU32.slice(indices, end),
+    };
+  }
+
+  private readMeshReport(ptr: WasmInt32Ptr): MeshReport {
+    const data = this.helper.module.HEAP32.subarray(ptr >> 2, (ptr >> 2) + 7);
+    return {
+      valid: data[0] !== 0,
+      quads: int(data[1]),
+      duplicates: int(data[2]),
+      overlaps: int(data[3]),
+      holes: int(data[4]),
+      extras: int(data[5]),
+      outOfRange: int(data[6]),
+    };
+  }
+
+  // Returns the fraction of a sound at `source` that reaches `listener`.
+  getAudioOcclusion(listener: Vec3, source: Vec3): number {
+    const [lx, ly, lz] = listener;
+    const [sx, sy, sz] = source;
+    return this.helper.module.asm.audioOcclusion(lx, ly, lz, sx, sy, sz);
+  }
+
+  // Returns the distance from start along dir to the first solid block, or
+  // length if there's none in range. Unloaded chunks block the ray. Rays
+  // skip empty space quickly, so long ones, e.g. for visibility checks, are
+  // fine.
+  raycast(start: Vec3, dir: Vec3, length: number): number {
+    const [x, y, z] = start, [dx, dy, dz] = dir;
+    return this.helper.module.asm.raycast(x, y, z, dx, dy, dz, length);
+  }
+
+  // Returns the mean distance from (x, y, z) to the nearest solid blocks.
+  getRoomSize(x: number, y: number, z: number): number {
+    return this.helper.module.asm.roomSizeEstimate(x, y, z);
+  }
+
+  // Grows a tree with its trunk based at (x, y, z), e.g. when the player
+  // uses bonemeal on a sapling. Returns false if the trunk doesn't fit.
+  growTree(x: int, y: int, z: int, species: int, seed: int): boolean {
+    return this.helper.module.asm.growTree(x, y, z, species, seed);
+  }
+
+  // Turns a triangle mesh into blocks, with `resolution` voxels per model
+  // unit and the model's origin at (x, y, z). Surface fill keeps every
+  // voxel a triangle touches; solid fill also fills the inside of a closed
+  // mesh. Vertices are (x, y, z) triples. Returns the blocks changed.
+  voxelizeMesh(vertices: Float32Array, indices: Uint32Array, block: BlockId,
+               resolution: number, solid: boolean,
+               x: int, y: int, z: int): int {
+    const module = this.helper.module;
+    const vertexBytes = Math.max(vertices.length, 1) * 4;
+    const indexBytes = Math.max(indices.length, 1) * 4;
+    const data = module.asm.malloc(int(vertexBytes + indexBytes));
+    module.HEAPF32.set(vertices, data >> 2);
+    module.HEAPU32.set(indices, (data + vertexBytes) >> 2);
+    const result = module.asm.voxelizeMesh(
+        data, (data + vertexBytes) as WasmCharPtr, int(vertices.length / 3),
+        int(indices.length), block, resolution, solid, x, y, z);
+    module.asm.free(data);
+    return result;
+  }
+
+  // Imports a w x h heightmap of 16-bit samples, e.g. from World Machine,
+  // with its first sample at column (x, z). A sample s becomes a column of
+  // round(s * scale) voxels, filled from the top down with the layers; the
+  // last layer extends to the bottom. Returns the number of blocks changed.
+  importHeightmap(samples: Uint16Array, w: int, h: int, scale: number,
+                  layers: HeightmapLayer[], x: int, z: int): int {
+    assert(samples.length === w * h && layers.length > 0);
+    const module = this.helper.module;
+    const sampleBytes = 4 * Math.ceil(samples.length / 2);
+    const data = module.asm.malloc(int(sampleBytes + 8 * layers.length));
+    module.HEAPU16.set(samples, data >> 1);
+    layers.forEach((layer, i) => {
+      const offset = ((data + sampleBytes) >> 2) + 2 * i;
+      module.HEAP32.set([layer.depth, layer.block], offset);
+    });
+    const result = module.asm.importHeightmap(
+        data, w, h, scale, (data + sampleBytes) as WasmCharPtr,
+        int(layers.length), x, z);
+    module.asm.free(data);
+    return result;
+  }
+
+  // Stamps a paletted PNG into the layer at height y, one block per pixel,
+  // with its top-left pixel at (x, y, z) and rows along +z. Colors are
+  // 0xRRGGBB; pixels of other colors, and transparent ones, leave their
+  // voxels alone. Returns the blocks changed, or -1 if the PNG is invalid.
+  importLayerPng(png: Uint8Array, colors: {color: int, block: BlockId}[],
+                 x: int, y: int, z: int): int {
+    const module = this.helper.module;
+    const pngBytes = 4 * Math.ceil(png.length / 4);
+    const data = module.asm.malloc(int(pngBytes + 8 * colors.length));
+    module.HEAPU8.set(png, data);
+    colors.forEach((entry, i) => {
+      const offset = ((data + pngBytes) >> 2) + 2 * i;
+      module.HEAP32.set([entry.color, entry.block], offset);
+    });
+    const result = module.asm.importLayerPng(
+        data, int(png.length), (data + pngBytes) as WasmCharPtr,
+        int(colors.length), x, y, z);
+    module.asm.free(data);
+    return result;
+  }
+
+  // Exports a heightmap of the inclusive box of columns from min to max, as
+  // (x, z) pairs, from the top solid voxel of each column. Unloaded columns
+  // are 0. It's row-major, in the layout that importHeightmap takes.
+  exportHeightmap(min: [int, int], max: [int, int], scale: number):
+      Uint16Array {
+    const module = this.helper.module;
+    const size = (max[0] - min[0] + 1) * (max[1] - min[1] + 1);
+    const data = module.asm.exportHeightmap(
+        min[0], min[1], max[0], max[1], scale);
+    return module.HEAPU16.slice(data >> 1, (data >> 1) + size);
+  }
+
+  // Builder edits (setBlock, fillBlocks, voxelizeMesh, carvePath, and
+  // applyCsg) repeat at each image under the symmetry: mirrored across the
+  // kMirror* planes, at planes[i] on axis i, and then rotated `radial` times
+  // around the vertical axis at (axis[0], axis[1]). Planes and axes should
+  // fall on multiples of 0.5; quarter turns are exact, but other rotations
+  // round their images to the nearest voxel.
+  setSymmetry(mirrors: int, planes: [number, number, number],
+              radial: int = int(1), axis: [number, number] = [0, 0]): void {
+    assert(1 <= radial && radial <= 64);
+    const double = (x: number) => int(Math.round(2 * x));
+    this.helper.module.asm.setSymmetry(
+        mirrors, double(planes[0]), double(planes[1]), double(planes[2]),
+        radial, double(axis[0]), double(axis[1]));
+  }
+
+  clearSymmetry(): void {
+    this.helper.module.asm.setSymmetry(
+        int(0), int(0), int(0), int(0), int(1), int(0), int(0));
+  }
+
+  // A builder's selection: an inclusive box that tools grow, move, measure,
+  // and edit. While the selection mask is on, bulk edits (fillBlocks,
+  // voxelizeMesh, carvePath, and applyCsg) skip voxels outside of it.
+  setSelection(x0: int, y0: int, z0: int, x1: int, y1: int, z1: int): void {
+    this.helper.module.asm.setSelection(x0, y0, z0, x1, y1, z1);
+  }
+
+  clearSelection(): void {
+    this.helper.module.asm.clearSelection();
+  }
+
+  getSelection(): {min: [int, int, int], max: [int, int, int]} | null {
+    const module = this.helper.module;
+    const ptr = module.asm.getSelection();
+    if (ptr === 0) return null;
+    const [x0, y0, z0, x1, y1, z1] =
+        Array.from(module.HEAP32.subarray(ptr >> 2, (ptr >> 2) + 6)).map(int);
+    return {min: [x0, y0, z0], max: [x1, y1, z1]};
+  }
+
+  // face is in [-x, +x, -y, +y, -z, +z] order. A negative amount contracts
+  // the face, down to a single layer of voxels.
+  expandSelection(face: int, amount: int): void {
+    assert(0 <= face && face < 6);
+    this.helper.module.asm.expandSelection(face, amount);
+  }
+
+  shiftSelection(dx: int, dy: int, dz: int): void {
+    this.helper.module.asm.shiftSelection(dx, dy, dz);
+  }
+
+  setSelectionMask(enabled: boolean): void {
+    this.helper.module.asm.setSelectionMask(enabled);
+  }
+
+  getSelectionStats(): SelectionStats | null {
+    const module = this.helper.module;
+    const ptr = module.asm.getSelectionStats();
+    if (ptr === 0) return null;
+    const offset = ptr >> 3;
+    const [volume, loaded, surface, count] =
+        module.HEAPF64.subarray(offset, offset + 4);
+    const blocks = [];
+    for (let i = 0; i < count; i++) {
+      const base = offset + 4 + 2 * i;
+      const block = module.HEAPF64[base + 0] as BlockId;
+      blocks.push({block, count: module.HEAPF64[base + 1]});
+    }
+    return {volume, loaded, surface, blocks};
+  }
+
+  // Edits the selection like fillBlocks. Returns false if there isn't one.
+  fillSelection(block: BlockId, collectDrops: boolean = false): boolean {
+    return this.helper.module.asm.fillSelection(block, collectDrops);
+  }
+
+  // Replaces `from` with `to` in the selection. Returns the blocks changed.
+  replaceInSelection(from: BlockId, to: BlockId,
+                     collectDrops: boolean = false): int {
+    return this.helper.module.asm.replaceInSelection(from, to, collectDrops);
+  }
+
+  // Sweeps a brush along the points, or along a Catmull-Rom spline through
+  // them, and fills what it covers with the block: air carves a tunnel. A
+  // kPathRect profile stays upright, `width` across and `height` up from the
+  // path, like a road; a kPathCircle profile is a tube `width` across.
+  // Returns the number of blocks changed.
+  carvePath(points: Vec3[], profile: int, width: number, height: number,
+            spline: boolean, block: BlockId = kEmptyBlock): int {
+    const module = this.helper.module;
+    const size = Math.max(points.length, 1);
+    const data = module.asm.malloc(int(24 * size));
+    points.forEach((x, i) => {
+      module.HEAPF64.set([x[0], x[1], x[2]], (data >> 3) + 3 * i);
+    });
+    const result = module.asm.carvePath(
+        data, int(points.length), profile, width, height, spline, block);
+    module.asm.free(data);
+    return result;
+  }
+
+  // Clipboards are boxes of blocks, up to 256 on a side, that builder tools
+  // combine with the world by CSG. Ids are chosen by the caller, and making
+  // a clipboard replaces any old one with the same id. Air is empty, and
+  // unloaded voxels copy as unknown blocks, which CSG leaves alone.
+  copyToClipboard(id: int, x0: int, y0: int, z0: int,
+                  x1: int, y1: int, z1: int): void {
+    this.helper.module.asm.copyToClipboard(id, x0, y0, z0, x1, y1, z1);
+  }
+
+  // shape is one of the kClipboard* values. A nonzero shell hollows out the
+  // shape, leaving that many layers; cylinders run along y without caps.
+  createClipboardShape(id: int, shape: int, sx: int, sy: int, sz: int,
+                       block: BlockId, shell: int = int(0)): void {
+    this.helper.module.asm.createClipboardShape(
+        id, shape, sx, sy, sz, block, shell);
+  }
+
+  removeClipboard(id: int): boolean {
+    return this.helper.module.asm.removeClipboard(id);
+  }
+
+  // op is one of the kCsg* values. Union places the clipboard's blocks,
+  // subtract carves them out, and intersect clears the world under the
+  // clipboard's empty voxels. Returns the number of blocks changed.
+  applyCsg(id: int, op: int, x: int, y: int, z: int): int {
+    return this.helper.module.asm.applyCsg(id, op, x, y, z);
+  }
+
+  // Detonates an explosion of the given power, e.g. 4 for TNT, centered on
+  // the block (x, y, z). Rays from the center destroy the blocks that they
+  // reach before block resistance exhausts them. We also return a knockback
+  // impulse for each entity in the spatial index within 2 * power blocks,
+  // pointing away from the center and falling off linearly with distance.
+  // Blocks don't shield entities from the impulse.
+  explode(x: int, y: int, z: int, power: number, seed: int): Explosion {
+    const module = this.helper.module;
+    const offset = module.asm.explode(x, y, z, power, seed) >> 2;
+    const heap = module.HEAP32;
+    const destroyed: Vec3[] = [];
+    for (let i = 0; i < heap[offset]; i++) {
+      const base = offset + 1 + 3 * i;
+      destroyed.push(Vec3.from(heap[base], heap[base + 1], heap[base + 2]));
+    }
+    const drops = this.getEditDrops();
+
+    const [cx, cy, cz] = [x + 0.5, y + 0.5, z + 0.5];
+    const radius = 2 * power;
+    const impulses: {entity: EntityId, impulse: Vec3}[] = [];
+    for (const entity of this.spatial.entitiesNear(cx, cy, cz, radius)) {
+      const [min, max] = this.spatial.getAABB(entity)!;
+      const impulse = Vec3.from((min[0] + max[0]) / 2 - cx,
+                                (min[1] + max[1]) / 2 - cy,
+                                (min[2] + max[2]) / 2 - cz);
+      const distance = Vec3.length(impulse);
+      if (distance >= radius) continue;
+      if (distance > 0) Vec3.scale(impulse, impulse, 1 / distance);
+      else Vec3.set(impulse, 0, 1, 0);
+      Vec3.scale(impulse, impulse, power * (1 - distance / radius));
+      impulses.push({entity, impulse});
+    }
+    return {destroyed, drops, impulses};
+  }
+
+  // Records a structure's bounds, e.g. after placing a village, so that we
+  // can find it with getStructuresInBox and findNearestStructure later.
+  addStructure(type: int, min: [int, int, int], max: [int, int, int]): void {
+    const [x0, y0, z0] = min, [x1, y1, z1] = max;
+    this.helper.module.asm.addStructure(type, x0, y0, z0, x1, y1, z1);
+  }
+
+  // Returns the structures that overlap the inclusive box [min, max].
+  getStructuresInBox(min: [int, int, int], max: [int, int, int]): Structure[] {
+    const [x0, y0, z0] = min, [x1, y1, z1] = max;
+    const asm = this.helper.module.asm;
+    const offset = asm.getStructuresInBox(x0, y0, z0, x1, y1, z1) >> 2;
+    const heap = this.helper.module.HEAP32;
+    const result: Structure[] = [];
+    for (let i = 0; i < heap[offset]; i++) {
+      result.push(this.readStructure(offset + 1 + 7 * i));
+    }
+    return result;
+  }
+
+  // Returns the structure of the given type that's horizontally closest to
+  // (x, z), e.g. for a "locate" command, or null if there isn't one.
+  findNearestStructure(type: int, x: int, z: int): Structure | null {
+    const ptr = this.helper.module.asm.findNearestStructure(type, x, z);
+    return ptr === 0 ? null : this.readStructure(ptr >> 2);
+  }
+
+  private readStructure(offset: number): Structure {
+    const heap = this.helper.module.HEAP32;
+    const min: [int, int, int] =
+        [int(heap[offset + 1]), int(heap[offset + 2]), int(heap[offset + 3])];
+    const max: [int, int, int] =
+        [int(heap[offset + 4]), int(heap[offset + 5]), int(heap[offset + 6])];
+    return {type: int(heap[offset]), min, max};
+  }
+
+  // Call after editing blocks at the given positions to update the power
+  // levels of the circuits that touch them. Returns the wires and sinks whose
+  // levels changed; the host can then update their appearance or behavior.
+  propagatePower(positions: Vec3[]): PowerChange[] {
+    if (positions.length === 0) return [];
+    const module = this.helper.module;
+    const data = module.asm.malloc(int(12 * positions.length));
+    positions.forEach((p, i) => {
+      for (let j = 0; j < 3; j++) {
+        module.HEAP32[(data >> 2) + 3 * i + j] = Math.floor(p[j]);
+      }
+    });
+    const count = int(positions.length);
+    const offset = module.asm.propagatePower(data, count) >> 2;
+    module.asm.free(data);
+
+    const heap = module.HEAP32;
+    const result: PowerChange[] = [];
+    for (let i = 0; i < heap[offset]; i++) {
+      const base = offset + 1 + 4 * i;
+      result.push({x: int(heap[base + 0]), y: int(heap[base + 1]),
+                   z: int(heap[base + 2]), level: int(heap[base + 3])});
+    }
+    return result;
+  }
+
+  // Pushes the line of blocks starting at (x, y, z) one block along dir, an
+  // index into [-x, +x, -y, +y, -z, +z]. Returns null if the line is longer
+  // than maxCount, includes a kBlockTagFixed block, or would leave the world.
+  // Otherwise, returns the number of blocks moved and the changed region.
+  pushBlocks(x: int, y: int, z: int,
+             dir: int, maxCount: int): PushResult | null {
+    assert(0 <= dir && dir < 6);
+    const module = this.helper.module;
+    const offset = module.asm.pushBlocks(x, y, z, dir, maxCount) >> 2;
+    const heap = module.HEAP32;
+    if (heap[offset] < 0) return null;
+    const [x0, y0, z0, x1, y1, z1] = heap.subarray(offset + 1, offset + 7);
+    return {count: int(heap[offset]),
+            min: Vec3.from(x0, y0, z0), max: Vec3.from(x1, y1, z1)};
+  }
+
+  // Records a snapshot of networked entity state, received at the given time
+  // in seconds. Returns false, dropping the snapshot, if it's older than the
+  // latest one pushed.
+  pushEntitySnapshot(time: number, transforms: EntityTransform[]): boolean {
+    const module = this.helper.module;
+    const data = this.writeTransforms(transforms);
+    const count = int(transforms.length);
+    const result = module.asm.pushEntitySnapshot(time, data, count);
+    module.asm.free(data);
+    return result;
+  }
+
+  // Returns packed (id, x, y, z, heading) transforms for each entity at the
+  // given render time, interpolated between snapshots. Positions are relative
+  // to the camera's render origin. The result is a view into wasm memory,
+  // valid until the next call into the engine.
+  sampleEntities(time: number): Float32Array {
+    const module = this.helper.module;
+    const offset = module.asm.sampleEntities(time) >> 2;
+    const count = module.HEAPF32[offset];
+    return module.HEAPF32.subarray(offset + 1, offset + 1 + 5 * count);
+  }
+
+  clearEntitySnapshots(): void {
+    this.helper.module.asm.clearEntitySnapshots();
+  }
+
+  // Returns the events raised since the last drain, oldest first. Call this
+  // once per frame. If the queue overflowed, we drop the oldest events.
+  drainEvents(): {events: EngineEvent[], dropped: int} {
+    const module = this.helper.module;
+    const offset = module.asm.drainEvents() >> 2;
+    const heap = module.HEAP32;
+    const events: EngineEvent[] = [];
+    for (let i = 0; i < heap[offset]; i++) {
+      const [type, x, y, z, a, b] =
+          Array.from(heap.subarray(offset + 2 + 6 * i, offset + 8 + 6 * i));
+      events.push({type: int(type), x: int(x), y: int(y), z: int(z),
+                   a: int(a), b: int(b)});
+    }
+    return {events, dropped: int(heap[offset + 1])};
+  }
+
+  // Raises an event from a host-side system, like entity physics.
+  pushEvent(type: int, x: int, y: int, z: int,
+            a: int = int(0), b: int = int(0)): void {
+    this.helper.module.asm.pushEvent(type, x, y, z, a, b);
+  }
+
+  // Returns (cx, cz) for each chunk that we've edited since it was saved.
+  chunksNeedingSave(): [int, int][] {
+    const module = this.helper.module;
+    const offset = module.asm.chunksNeedingSave() >> 2;
+    const heap = module.HEAP32;
+    const result: [int, int][] = [];
+    for (let i = 0; i < heap[offset]; i++) {
+      const base = offset + 1 + 2 * i;
+      result.push([int(heap[base]), int(heap[base + 1])]);
+    }
+    return result;
+  }
+
+  // Saves up to budget chunks, returning them as ChunkData packets that the
+  // host should persist. Call this every frame or tick to spread out saves.
+  saveChunks(budget: int): Uint8Array[] {
+    const module = this.helper.module;
+    const data = module.asm.saveChunks(budget);
+    const count = module.HEAPU32[data >> 2];
+    const result: Uint8Array[] = [];
+    let offset = data + 4;
+    for (let i = 0; i < count; i++) {
+      const packet = nonnull(this.readPacket(offset as WasmCharPtr));
+      result.push(packet);
+      offset += 4 + packet.length;
+    }
+    return result;
+  }
+
+  // Network packets. Encoders return the packet's bytes; decoders return
+  // null if the packet is malformed. Chunk data is in chunk index order.
+
+  encodeChunkData(cx: int, cz: int): Uint8Array | null {
+    return this.readPacket(this.helper.module.asm.encodeChunkData(cx, cz));
+  }
+
+  decodeChunkData(packet: Uint8Array): ChunkPacket | null {
+    const module = this.helper.module;
+    const offset = this.withPacket(packet, module.asm.decodeChunkData);
+    if (offset === 0) return null;
+    const [cx, cz] = module.HEAP32.subarray(offset >> 2, (offset >> 2) + 2);
+    const size = kChunkWidth * kChunkWidth * kWorldHeight;
+    const voxels = module.HEAPU8.slice(offset + 8, offset + 8 + size);
+    const extra = nonnull(this.readPacket(
+        (offset + 8 + size) as WasmCharPtr));
+    const base = (offset + 12 + size + extra.length + 3) & ~3;
+    const blockEntities = this.readBlockEntities(base as WasmCharPtr);
+    return {cx: int(cx), cz: int(cz), voxels, extra, blockEntities};
+  }
+
+  // The game's opaque blob for the loaded chunk (cx, cz), such as ownership
+  // or script state. It's saved, sent in chunk packets, and kept across
+  // unloads with the chunk's voxels. Setting it marks the chunk unsaved.
+  getChunkExtra(cx: int, cz: int): Uint8Array | null {
+    return this.readPacket(this.helper.module.asm.getChunkExtra(cx, cz));
+  }
+
+  setChunkExtra(cx: int, cz: int, extra: Uint8Array): boolean {
+    const module = this.helper.module;
+    return this.withPacket(
+        extra, (data, size) => module.asm.setChunkExtra(cx, cz, data, size));
+  }
+
+  // Block entities: game state for single blocks, which ticks and block hooks
+  // look up by position. They're dropped when their blocks change, move with
+  // pushed blocks, and are saved and sent with their chunks. Creating one
+  // fails if the chunk isn't loaded or the block is air.
+  createBlockEntity(
+      x: int, y: int, z: int, type: int, data: Uint8Array): boolean {
+    const module = this.helper.module;
+    return this.withPacket(data, (ptr, size) =>
+        module.asm.createBlockEntity(x, y, z, type, ptr, size));
+  }
+
+  getBlockEntity(x: int, y: int, z: int): BlockEntity | null {
+    const data = this.helper.module.asm.getBlockEntity(x, y, z);
+    return data === 0 ? null : this.readBlockEntity(data)[0];
+  }
+
+  removeBlockEntity(x: int, y: int, z: int): boolean {
+    return this.helper.module.asm.removeBlockEntity(x, y, z);
+  }
+
+  getChunkBlockEntities(cx: int, cz: int): BlockEntity[] | null {
+    const data = this.helper.module.asm.getChunkBlockEntities(cx, cz);
+    return data === 0 ? null : this.readBlockEntities(data);
+  }
+
+  // Inventories, by game-defined id, with the stacking rules that both the
+  // client and server use to check actions. Operations return partial counts
+  // instead of failing: the count left over, removed, or moved. Moves merge
+  // into stacks of the same item and swap whole stacks of other items. See
+  // inventory.h.
+  createInventory(id: int, size: int): void {
+    this.helper.module.asm.createInventory(id, size);
+  }
+
+  removeInventory(id: int): boolean {
+    return this.helper.module.asm.removeInventory(id);
+  }
+
+  getInventory(id: int): ItemStack[] | null {
+    const module = this.helper.module;
+    const data = module.asm.getInventory(id);
+    if (data === 0) return null;
+    const offset = data >> 2;
+    const heap = module.HEAP32;
+    const result: ItemStack[] = [];
+    for (let i = 0; i < heap[offset]; i++) {
+      const base = offset + 1 + 2 * i;
+      result.push({item: int(heap[base]), count: int(heap[base + 1])});
+    }
+    return result;
+  }
+
+  setInventorySlot(id: int, slot: int, stack: ItemStack): boolean {
+    const {item, count} = stack;
+    return this.helper.module.asm.setInventorySlot(id, slot, item, count);
+  }
+
+  addToInventory(id: int, item: int, count: int): int {
+    return this.helper.module.asm.addToInventory(id, item, count);
+  }
+
+  removeFromInventory(id: int, item: int, count: int): int {
+    return this.helper.module.asm.removeFromInventory(id, item, count);
+  }
+
+  moveInventoryItems(from: int, fromSlot: int, to: int,
+                     toSlot: int, count: int): int {
+    return this.helper.module.asm.moveInventoryItems(
+        from, fromSlot, to, toSlot, count);
+  }
+
+  splitInventoryStack(id: int, fromSlot: int, toSlot: int): int {
+    return this.helper.module.asm.splitInventoryStack(id, fromSlot, toSlot);
+  }
+
+  encodeInventory(id: int): Uint8Array | null {
+    return this.readPacket(this.helper.module.asm.encodeInventory(id));
+  }
+
+  // Replaces the inventory, creating it if needed. Fails without changes if
+  // the packet is malformed or has a stack over its item's max stack size.
+  decodeInventory(id: int, packet: Uint8Array): boolean {
+    const module = this.helper.module;
+    return this.withPacket(
+        packet, (data, size) => module.asm.decodeInventory(id, data, size));
+  }
+
+  encodeChunkDelta(cx: int, cz: int, edits: ChunkEdit[]): Uint8Array {
+    const module = this.helper.module;
+    const data = module.asm.malloc(int(8 * Math.max(edits.length, 1)));
+    edits.forEach((x, i) => {
+      module.HEAP32.set([x.index, x.block], (data >> 2) + 2 * i);
+    });
+    const count = int(edits.length);
+    const result = module.asm.encodeChunkDelta(cx, cz, data, count);
+    module.asm.free(data);
+    return nonnull(this.readPacket(result));
+  }
+
+  decodeChunkDelta(packet: Uint8Array): ChunkDeltaPacket | null {
+    const module = this.helper.module;
+    const offset = this.withPacket(packet, module.asm.decodeChunkDelta) >> 2;
+    if (offset === 0) return null;
+    const heap = module.HEAP32;
+    const edits: ChunkEdit[] = [];
+    for (let i = 0; i < heap[offset + 2]; i++) {
+      const base = offset + 3 + 2 * i;
+      edits.push({index: int(heap[base]), block: heap[base + 1] as BlockId});
+    }
+    return {cx: int(heap[offset]), cz: int(heap[offset + 1]), edits};
+  }
+
+  encodeBlockEdit(x: int, y: int, z: int, block: BlockId): Uint8Array {
+    const result = this.helper.module.asm.encodeBlockEdit(x, y, z, block);
+    return nonnull(this.readPacket(result));
+  }
+
+  decodeBlockEdit(packet: Uint8Array): BlockEditPacket | null {
+    const module = this.helper.module;
+    const offset = this.withPacket(packet, module.asm.decodeBlockEdit) >> 2;
+    if (offset === 0) return null;
+    const [x, y, z, block] = module.HEAP32.subarray(offset, offset + 4);
+    return {x: int(x), y: int(y), z: int(z), block: block as BlockId};
+  }
+
+  encodeEntitySnapshot(
+      time: number, transforms: EntityTransform[]): Uint8Array {
+    const module = this.helper.module;
+    const data = this.writeTransforms(transforms);
+    const count = int(transforms.length);
+    const result = module.asm.encodeEntitySnapshot(time, data, count);
+    module.asm.free(data);
+    return nonnull(this.readPacket(result));
+  }
+
+  // Decodes the snapshot into the buffer used by sampleEntities. Returns false
+  // for malformed packets and for snapshots older than the latest one.
+  decodeEntitySnapshot(packet: Uint8Array): boolean {
+    const module = this.helper.module;
+    return this.withPacket(packet, module.asm.decodeEntitySnapshot);
+  }
+
+  encodeRegistrySync(): Uint8Array {
+    const result = this.helper.module.asm.encodeRegistrySync();
+    return nonnull(this.readPacket(result));
+  }
+
+  decodeRegistrySync(packet: Uint8Array): SyncedRegistry | null {
+    const module = this.helper.module;
+    const offset =
+        this.withPacket(packet, module.asm.decodeRegistrySync) >> 3;
+    if (offset === 0) return null;
+    const heap = module.HEAPF64;
+    const blocks: SyncedBlock[] = [];
+    const num_blocks = heap[offset];
+    for (let i = 0; i < num_blocks; i++) {
+      const base = offset + 1 + 10 * i;
+      const faces = Array.from(heap.subarray(base + 4, base + 10));
+      blocks.push({
+        mesh: heap[base] !== 0,
+        opaque: heap[base + 1] !== 0,
+        solid: heap[base + 2] !== 0,
+        light: int(heap[base + 3]),
+        faces: faces.map(x => int(x) as MaybeMaterialId),
+      });
+    }
+    const materials: SyncedMaterial[] = [];
+    const start = offset + 1 + 10 * num_blocks;
+    for (let i = 0; i < heap[start]; i++) {
+      const base = start + 1 + 9 * i;
+      const [r, g, b, a] = heap.subarray(base + 3, base + 7);
+      materials.push({
+        liquid: heap[base] !== 0,
+        alphaTest: heap[base + 1] !== 0,
+        texture: int(heap[base + 2]),
+        color: [r, g, b, a],
+        uvTransform: int(heap[base + 7]),
+        fullBright: heap[base + 8] !== 0,
+      });
+    }
+    return {blocks, materials};
+  }
+
+  getPowerLevel(x: int, y: int, z: int): int {
+    return this.helper.module.asm.getBlockMetadata(x, y, z);
+  }
+
+  // Advances fire in the chunk (cx, cz) by one tick. Returns a box around
+  // the changed blocks in each chunk that changed, which may include the
+  // chunk's neighbors; we remesh and relight them as we do for other edits.
+  tickFire(cx: int, cz: int, raining: boolean): DirtyBox[] {
+    const module = this.helper.module;
+    const offset = module.asm.tickFire(cx, cz, raining) >> 2;
+    const heap = module.HEAP32;
+    const result: DirtyBox[] = [];
+    for (let i = 0; i < heap[offset]; i++) {
+      const base = offset + 1 + 6 * i;
+      const [x0, y0, z0, x1, y1, z1] = heap.subarray(base, base + 6);
+      result.push({min: Vec3.from(x0, y0, z0), max: Vec3.from(x1, y1, z1)});
+    }
+    return result;
+  }
+
+  getBaseHeight(x: int, z: int): int {
+    return this.helper.module.asm.getBaseHeight(x, z);
+  }
+
+  // Returns heights and RGBA top colors for a resolution x resolution grid
+  // covering the chunk (cx, cz), for rendering it beyond the mesh radius.
+  buildImpostor(cx: int, cz: int, resolution: int): Impostor {
+    const module = this.helper.module;
+    const n = resolution * resolution;
+    const offset = module.asm.buildImpostor(cx, cz, resolution) >> 2;
+    const heights = module.HEAPU32.slice(offset, offset + n);
+    const colors = module.HEAPU8.slice(4 * (offset + n), 4 * (offset + 2 * n));
+    return {resolution, heights, colors};
+  }
+
+  getBlock(x: int, y: int, z: int): BlockId {
+    return this.helper.getBlock(x, y, z);
+  }
+
+  // Returns true if any block in the inclusive box [min, max] is solid, or
+  // if the box isn't loaded. It's a fast broadphase for physics sweeps: see
+  // the clear argument to sweep.
+  anySolidInBox(min: Vec3, max: Vec3): boolean {
+    for (let i = 0; i < 3; i++) {
+      kTmpCellMin[i] = Math.floor(min[i]);
+      kTmpCellMax[i] = Math.floor(max[i]);
+    }
+    return this.helper.module.asm.anySolidInBox(
+        int(kTmpCellMin[0]), int(kTmpCellMin[1]), int(kTmpCellMin[2]),
+        int(kTmpCellMax[0]), int(kTmpCellMax[1]), int(kTmpCellMax[2]));
+  }
+
+  // Returns a kChunkWidth x kChunkWidth RGBA top-down tile of the loaded
+  // chunk (cx, cz), or null if the chunk isn't loaded.
+  getMinimapTile(cx: int, cz: int): Uint8ClampedArray | null {
+    const module = this.helper.module;
+    const data = module.asm.renderMinimap(cx, cz);
+    if (data === 0) return null;
+    const size = 4 * kChunkWidth * kChunkWidth;
+    return new Uint8ClampedArray(module.HEAPU8.slice(data, data + size));
+  }
+
+  // Merges a w x h mask (x-major, 0 = empty) into maximal rectangles of equal
+  // values, using the same greedy algorithm that we use to mesh chunks.
+  greedyRects(mask: Int32Array, w: int, h: int): Rect[] {
+    assert(mask.length === w * h);
+    const module = this.helper.module;
+    const data = module.asm.malloc(int(4 * mask.length));
+    module.HEAP32.set(mask, data >> 2);
+    const ptr = data as int as WasmInt32Ptr;
+    const offset = module.asm.greedyRects(ptr, w, h) >> 2;
+    module.asm.free(data);
+
+    const result: Rect[] = [];
+    const heap = module.HEAP32;
+    const count = heap[offset];
+    for (let i = 0; i < count; i++) {
+      const base = offset + 1 + 5 * i;
+      result.push({x: int(heap[base + 0]), y: int(heap[base + 1]),
+                   w: int(heap[base + 2]), h: int(heap[base + 3]),
+                   value: int(heap[base + 4])});
+    }
+    return result;
+  }
+
+  getLight(x: int, y: int, z: int): number {
+    return lighting(this.helper.getLightLevel(x, y, z));
+  }
+
+  // Returns the y-coordinate of the highest opaque block at or below (x, y,
+  // z), for placing a blob shadow under an entity, or -1 if there's none.
+  getShadowHeight(x: int, z: int, y: int = int(kWorldHeight)): int {
+    return int(this.helper.module.asm.getShadowHeight(x, y, z));
+  }
+
+  // getShadowHeight for an nx x nz box of columns with its corner at (x, z).
+  // Column (i, j) is at index i + nx * j.
+  getShadowHeights(x: int, z: int, nx: int, nz: int,
+                   y: int = int(kWorldHeight)): Int32Array {
+    const module = this.helper.module;
+    const offset = module.asm.getShadowHeights(x, z, nx, nz, y) >> 2;
+    return module.HEAP32.slice(offset, offset + nx * nz);
+  }
+
+  // The light level in [0, 15] at a block, with the sky dimmed by
+  // setSkyDarkness, for gameplay rules like mob spawning. It's slow: it
+  // flood-fills block light around the block on each call.
+  getEffectiveLightLevel(x: int, y: int, z: int): int {
+    return int(this.helper.module.asm.getEffectiveLightLevel(x, y, z));
+  }
+
+  // Dims sunlight, but not block light, by the given number of light levels,
+  // e.g. at night. This is cheap: it doesn't relight or remesh any chunks.
+  setSkyDarkness(level: int): void {
+    assert(0 <= level && level <= kSunlightLevel);
+    this.helper.module.asm.setSkyDarkness(level);
+    this.renderer.setSkyDarkness(level);
+  }
+
+  getMutableInputs(): Record<Input, boolean> {
+    return this.container.inputs;
+  }
+
+  getTargetedBlock(): Vec3 | null {
+    return this.highlightSide < 0 ? null : this.highlightPosition;
+  }
+
+  getTargetedBlockSide(): int {
+    return this.highlightSide;
+  }
+
+  setBlock(x: int, y: int, z: int, block: BlockId): void {
+    this.helper.setBlock(x, y, z, block);
+  }
+
+  setHooks(hooks: BlockHooks): void {
+    this.helper.hooks = hooks;
+  }
+
+  // WASM memory growth detaches typed-array views over it. Views obtained
+  // from the engine (like sampleEntities results) are only valid while the
+  // generation is unchanged; the callback is called whenever it changes.
+  memoryGeneration(): int {
+    return this.helper.module.asm.memoryGeneration();
+  }
+
+  setMemoryGrowthCallback(fn: ((generation: int) => void) | null): void {
+    this.helper.onMemoryGrowth = fn;
+  }
+
+  // Frees the engine's scratch arena, returning its capacity in bytes. The
+  // engine resets it after each remesh; call this after other heavy work.
+  arenaReset(): int {
+    return this.helper.module.asm.arenaReset();
+  }
+
+  // Called by the entity physics when an entity touches the block at
+  // (x, y, z), to raise the block's onEntityCollide hook.
+  notifyEntityCollide(entity: EntityId, x: int, y: int, z: int): void {
+    const callback = this.helper.hooks.onEntityCollide;
+    if (!callback) return;
+    const block = this.getBlock(x, y, z);
+    if (!this.registry.hasBlockHook(block, kHookEntityCollide)) return;
+    callback(block, x, y, z, entity);
+  }
+
+  // Sets every loaded block in the inclusive box [p0, p1]. If collectDrops
+  // is set, returns a summary of the destroyed blocks, grouped by type.
+  fillBlocks(p0: Vec3, p1: Vec3, block: BlockId,
+             collectDrops: boolean = false): EditDrop[] {
+    const module = this.helper.module;
+    const [x0, y0, z0] = [0, 1, 2].map(i => int(Math.floor(p0[i])));
+    const [x1, y1, z1] = [0, 1, 2].map(i => int(Math.floor(p1[i])));
+    module.asm.fillBlocks(x0, y0, z0, x1, y1, z1, block, collectDrops);
+    return collectDrops ? this.getEditDrops() : [];
+  }
+
+  // Land claims: edits inside the inclusive box [min, max] fail, with a
+  // kEventEditRejected event, unless setEditor was last called with its
+  // owner. The owner must be nonzero: editor 0 may edit anything.
+  addProtectedRegion(min: [int, int, int], max: [int, int, int],
+                     owner: int): void {
+    assert(owner !== 0);
+    const [x0, y0, z0] = min, [x1, y1, z1] = max;
+    this.helper.module.asm.addProtectedRegion(x0, y0, z0, x1, y1, z1, owner);
+  }
+
+  removeProtectedRegions(owner: int): void {
+    this.helper.module.asm.removeProtectedRegions(owner);
+  }
+
+  // Sets the owner on whose behalf we apply later edits. Wrap each player's
+  // edits in setEditor(player) and setEditor(0).
+  setEditor(owner: int): void {
+    this.helper.module.asm.setEditor(owner);
+  }
+
+  // Tests each entity's AABB against block trigger volumes, and raises a
+  // kEventTriggerEnter or kEventTriggerExit event, with the entity and the
+  // trigger type, for each change since the last call; with stays, also a
+  // kEventTriggerStay event per ongoing contact. Pass every entity each
+  // step: entities left out exit their triggers.
+  stepTriggers(entities: {id: EntityId, min: Vec3, max: Vec3}[],
+               stays: boolean = false): void {
+    const module = this.helper.module;
+    const count = entities.length;
+    const size = Math.max(count, 1);
+    const data = module.asm.malloc(int(52 * size));
+    entities.forEach((x, i) => {
+      const {min, max} = x;
+      const box = [min[0], min[1], min[2], max[0], max[1], max[2]];
+      module.HEAPF64.set(box, (data >> 3) + 6 * i);
+      module.HEAP32[(data >> 2) + 12 * size + i] = x.id;
+    });
+    module.asm.stepTriggers(
+        data, (data + 48 * size) as WasmCharPtr, int(count), stays);
+    module.asm.free(data);
+  }
+
+  // Server-side intake for a player's edit: checks it against the rules and
+  // the player's rate limit, then applies it as that player if it passes.
+  // Returns a kEdit* verdict, and raises a kEventPlayerEdit event with it.
+  // The block comes from the client, so it's checked too: unregistered ids
+  // get kEditInvalidBlock, and unbreakable blocks get kEditUnplaceable.
+  tryPlayerEdit(player: int, x: int, y: int, z: int, block: BlockId,
+                context: PlayerEditContext): int {
+    assert(player !== 0);
+    const {eye, tool, tier, mined, time} = context;
+    return this.helper.module.asm.tryPlayerEdit(
+        player, x, y, z, block, eye[0], eye[1], eye[2],
+        tool, tier, mined, time);
+  }
+
+  setPlayerEditRules(rules: PlayerEditRules): void {
+    const {reach, rate, burst, miningSlack} = rules;
+    this.helper.module.asm.setPlayerEditRules(
+        reach, rate, burst, miningSlack);
   }
 
-  end() {
-    const index = this.index;
-    const next_index = index + 1;
-    this.index = int(next_index < this.ticks.length ? next_index : 0);
-    const tick = int(Math.round(1000 * (this.now.now() - this.last)));
-    this.sum += tick - this.ticks[index];
-    this.ticks[index] = tick;
+  // Call when a player leaves, to drop their rate limit state.
+  resetPlayerEditLimit(player: int): void {
+    this.helper.module.asm.resetPlayerEditLimit(player);
   }
 
-  frame(): int {
-    return this.index;
+  // Sets the render-space size of a voxel, e.g. (1, 0.5, 1) for half-height
+  // voxels. The engine, physics, and entity positions all stay in voxel
+  // units; only the renderer scales them. Meshes and their AO are computed
+  // per voxel, so they stretch with it. Set it before adding entities.
+  setVoxelScale(x: number, y: number, z: number): void {
+    this.renderer.camera.setVoxelScale(x, y, z);
+    this.setSafeZoomDistance();
   }
 
-  max(): number {
-    return Math.max.apply(null, this.ticks);
+  setCameraTarget(x: number, y: number, z: number): void {
+    const [ox, , oz] = this.renderer.camera.origin;
+    const limit = kRebaseDistance;
+    if (Math.abs(x - ox) > limit || Math.abs(z - oz) > limit) {
+      const mask = ~(kChunkWidth - 1);
+      this.rebaseOrigin(int(Math.floor(x) & mask), int(Math.floor(z) & mask));
+    }
+    this.renderer.camera.setTarget(x, y, z);
+    this.setSafeZoomDistance();
   }
 
-  mean(): number {
-    return this.sum / this.ticks.length;
+  // Moves the render origin, to which the renderer's f32 positions are
+  // relative. setCameraTarget calls it when the camera strays more than
+  // kRebaseDistance from the origin, so that vertices don't jitter in
+  // worlds that are millions of blocks across. Voxel meshes bake in their
+  // render-space positions, so this call remeshes every loaded chunk.
+  rebaseOrigin(x: int, z: int): void {
+    this.helper.module.asm.rebaseOrigin(x, z);
+    Vec3.set(this.renderer.camera.origin, x, 0, z);
   }
-};
 
-//////////////////////////////////////////////////////////////////////////////
+  setPointLight(x: int, y: int, z: int, level: int): void {
+    this.helper.setPointLight(x, y, z, level);
+  }
 
-const kTickResolution = 4;
-const kTicksPerFrame = 4;
-const kTicksPerSecond = 60;
+  // Switches the engine to another dimension: a world with its own chunks
+  // and lighting that shares our registry. We create dimensions on first
+  // use. Inactive dimensions are frozen and hidden; entities are the game's
+  // to move, e.g. when one steps through a portal.
+  setActiveDimension(id: int): void {
+    this.helper.module.asm.setActiveDimension(id);
+  }
 
-type Callback = (dt: number) => void;
+  getActiveDimension(): int {
+    return this.helper.module.asm.getActiveDimension();
+  }
 
-class Timing {
-  private now: any;
-  private remesh: Callback;
-  private render: Callback;
-  private update: Callback;
-  private renderBinding: () => void;
-  private updateDelay: number;
-  private updateLimit: number;
-  private lastRender: int;
-  private lastUpdate: int;
-  remeshPerf: Performance;
-  renderPerf: Performance;
-  updatePerf: Performance;
+  // Drops an inactive dimension other than 0, with its portal links. Returns
+  // false if the dimension is 0, active, or doesn't exist.
+  removeDimension(id: int): boolean {
+    return this.helper.module.asm.removeDimension(id);
+  }
 
-  constructor(remesh: Callback, render: Callback, update: Callback) {
-    this.now = performance || Date;
-    this.remesh = remesh;
-    this.render = render;
-    this.update = update;
+  // Sets the active dimension's worldgen seed. New dimensions use their id
+  // as their seed, so they generate different terrain. Call it before
+  // loading any chunks in the dimension.
+  setWorldSeed(seed: int): void {
+    this.helper.module.asm.setWorldSeed(seed);
+  }
 
-    const now = this.now.now();
-    this.lastRender = now;
-    this.lastUpdate = now;
+  linkPortal(a: PortalEnd, b: PortalEnd): void {
+    this.helper.module.asm.linkPortal(
+        a.dimension, a.x, a.y, a.z, b.dimension, b.x, b.y, b.z);
+  }
 
-    this.renderBinding = this.renderHandler.bind(this);
-    requestAnimationFrame(this.renderBinding);
+  unlinkPortal(a: PortalEnd): void {
+    this.helper.module.asm.unlinkPortal(a.dimension, a.x, a.y, a.z);
+  }
 
-    this.updateDelay = 1000 / kTicksPerSecond;
-    this.updateLimit = this.updateDelay * kTicksPerFrame;
-    const updateInterval = this.updateDelay / kTickResolution;
-    setInterval(this.updateHandler.bind(this), updateInterval);
+  getPortalLink(a: PortalEnd): PortalEnd | null {
+    const module = this.helper.module;
+    const ptr = module.asm.getPortalLink(a.dimension, a.x, a.y, a.z);
+    if (ptr === 0) return null;
+    const [dimension, x, y, z] =
+        Array.from(module.HEAP32.subarray(ptr >> 2, (ptr >> 2) + 4)).map(int);
+    return {dimension, x, y, z};
+  }
 
-    this.remeshPerf = new Performance(this.now, 60);
-    this.renderPerf = new Performance(this.now, 60);
-    this.updatePerf = new Performance(this.now, 60);
+  // Moves a light that follows an entity, e.g. a torch in a player's hand,
+  // to the block containing (x, y, z). Lights are keyed by the caller's id.
+  // A level of 0 removes the light. Light is a single channel, so dynamic
+  // lights have no color. Call it as often as the entity moves: we only
+  // relight when the light changes blocks or levels.
+  setDynamicLight(id: int, x: number, y: number, z: number, level: int): void {
+    const ix = int(Math.floor(x)), iy = int(Math.floor(y));
+    const iz = int(Math.floor(z));
+    this.helper.module.asm.setDynamicLight(id, ix, iy, iz, level);
   }
 
-  renderHandler() {
-    requestAnimationFrame(this.renderBinding);
-    this.updateHandler();
+  // Projectiles are simulated by the engine, since there may be thousands
+  // in flight. Ids are the caller's, e.g. entity ids, and adding an id that
+  // exists replaces that projectile. Each stepProjectiles call returns every
+  // projectile's state and raises a kEventProjectileHit event for each solid
+  // block hit. Projectiles that fly into unloaded chunks are dropped, so
+  // they're missing from the result. Check entity hits with env.spatial.
+  addProjectile(id: int, kind: int, position: Vec3, velocity: Vec3): void {
+    const [x, y, z] = position, [vx, vy, vz] = velocity;
+    this.helper.module.asm.addProjectile(id, kind, x, y, z, vx, vy, vz);
+  }
 
-    const now = this.now.now();
-    const dt = (now - this.lastRender) / 1000;
-    this.lastRender = now;
+  removeProjectile(id: int): void {
+    this.helper.module.asm.removeProjectile(id);
+  }
 
-    try {
-      this.remeshPerf.begin();
-      this.remesh(dt);
-      this.remeshPerf.end();
-      this.renderPerf.begin();
-      this.render(dt);
-      this.renderPerf.end();
-    } catch (e) {
-      this.onError(e);
-    }
+  stepProjectiles(dt: number): Projectile[] {
+    return this.readProjectiles(this.helper.module.asm.stepProjectiles(dt));
   }
 
-  private updateHandler() {
-    let now = this.now.now();
-    const delay = this.updateDelay;
-    const limit = now + this.updateLimit;
+  getProjectiles(): Projectile[] {
+    return this.readProjectiles(this.helper.module.asm.getProjectiles());
+  }
 
-    while (this.lastUpdate + delay < now) {
-      try {
-        this.updatePerf.begin();
-        this.update(delay / 1000);
-        this.updatePerf.end();
-      } catch (e) {
-        this.onError(e);
-      }
-      this.lastUpdate += delay;
-      now = this.now.now();
+  // A fixed-timestep loop over the simulation: each call runs as many ticks
+  // as fit in the elapsed time. A tick runs scheduled updates and random
+  // ticks, fire in loaded chunks, projectiles, and item drops, in that order.
+  // alpha is how far we are into the next tick, for interpolating renders.
+  // Use getProjectiles and getItemDrops to read the entities' new states.
+  advance(dtMs: number): {ticks: int, alpha: number} {
+    const module = this.helper.module;
+    const offset = module.asm.advanceWorld(dtMs) >> 3;
+    const [ticks, alpha] = module.HEAPF64.subarray(offset, offset + 2);
+    return {ticks: int(ticks), alpha};
+  }
 
-      if (now > limit) {
-        this.lastUpdate = now;
-        break;
-      }
-    }
+  // If a frame takes longer than maxTicks ticks, advance drops the rest of
+  // its time, so the simulation slows down instead of falling behind.
+  // Returns false, ignoring the call, unless both arguments are positive.
+  setTickRate(ticksPerSecond: number, maxTicks: int = 8): boolean {
+    return this.helper.module.asm.setTickRate(ticksPerSecond, maxTicks);
   }
 
-  private onError(e: any) {
-    this.remesh = this.render = this.update = () => {};
-    console.error(e);
+  // Rain puts out fires in advance's fire ticks.
+  setRaining(raining: boolean): void {
+    this.helper.module.asm.setRaining(raining);
   }
-};
 
-//////////////////////////////////////////////////////////////////////////////
+  // Snapshots of the active dimension's state: chunks, entities, scheduled
+  // updates, and the RNG. Snapshots share per-chunk state, so each one only
+  // copies the chunks that changed since the last, plus the projectiles,
+  // item drops, and scheduled updates. Release them when done.
+  snapshot(): int {
+    return this.helper.module.asm.snapshotWorld();
+  }
 
-const kTmpPos     = Vec3.create();
-const kTmpMin     = Vec3.create();
-const kTmpMax     = Vec3.create();
-const kTmpDelta   = Vec3.create();
-const kTmpImpacts = Vec3.create();
+  rollback(id: int): boolean {
+    return this.helper.module.asm.rollbackWorld(id);
+  }
 
-const kMinZLowerBound = 0.001;
-const kMinZUpperBound = 0.1;
+  releaseSnapshot(id: int): boolean {
+    return this.helper.module.asm.releaseWorldSnapshot(id);
+  }
 
-const kChunkWidth  = 16;
-const kWorldHeight = 256;
+  // Serializes a snapshot, to keep it across sessions or send it to a peer.
+  // Returns null if there's no snapshot with the given id.
+  encodeSnapshot(id: int): Uint8Array | null {
+    return this.readPacket(this.helper.module.asm.encodeWorldSnapshot(id));
+  }
 
-const kChunkRadius = 12;
-const kFrontierRadius = 8;
-const kFrontierLevels = 6;
+  // Adds a serialized snapshot to the active dimension and returns its id,
+  // or 0 if the data is malformed or has unregistered blocks.
+  decodeSnapshot(data: Uint8Array): int {
+    return this.withPacket(data, this.helper.module.asm.decodeWorldSnapshot);
+  }
 
-const kSunlightLevel = 0xf;
+  // Debug drawing: subsystems whose kDebug* flags are set append primitives
+  // as they work, such as the cells relit by each lighting pass. Drain the
+  // buffer once a frame and draw it as an overlay.
+  setDebugDrawFlags(flags: int): void {
+    this.helper.module.asm.setDebugDrawFlags(flags);
+  }
 
-const lighting = (x: int): number => Math.pow(0.8, kSunlightLevel - x);
+  drainDebugDraw(): DebugCommand[] {
+    const module = this.helper.module;
+    const data = module.asm.drainDebugDraw();
+    const base = data >> 2;
+    const [count, size] = module.HEAP32.subarray(base, base + 2);
+    const texts = data + 8 + 44 * count;
+    const bytes = module.HEAPU8.slice(texts, texts + size);
+    const decoder = new TextDecoder();
+
+    const result: DebugCommand[] = [];
+    for (let i = 0; i < count; i++) {
+      const offset = base + 2 + 11 * i;
+      const floats = module.HEAPF32.subarray(offset + 3, offset + 9);
+      const [start, length] = module.HEAP32.subarray(offset + 9, offset + 11);
+      result.push({
+        shape: int(module.HEAP32[offset]),
+        flag: int(module.HEAPU32[offset + 1]),
+        color: int(module.HEAPU32[offset + 2]),
+        a: Vec3.from(floats[0], floats[1], floats[2]),
+        b: Vec3.from(floats[3], floats[4], floats[5]),
+        text: decoder.decode(bytes.subarray(start, start + length)),
+      });
+    }
+    return result;
+  }
 
-class Env {
-  entities: EntityComponentSystem;
-  registry: Registry;
-  renderer: Renderer;
-  private helper: WasmHelper;
-  private cameraColor: Color;
-  private cameraMaterial: MaybeMaterialId;
-  private container: Container;
-  private highlight: HighlightMesh;
-  private highlightSide: int = -1;
-  private highlightPosition: Vec3;
-  private timing: Timing;
-  private frame: number = 0;
+  // Camera support. We treat the camera as a box with the given half-width
+  // around the eye. getCameraMedium returns the block that the eye is in
+  // (or air, if it's above a liquid's wavy surface) and the fraction of the
+  // box under liquid. resolveCameraPosition pushes the box out of opaque
+  // blocks by at most maxPush, easing back at `rate` per second as walls
+  // clear. It updates the position in place, and returns false if the box
+  // is stuck in a thicker wall.
+  getCameraMedium(position: Vec3, radius: number,
+                  wave: number): {block: BlockId, submerged: number} {
+    const module = this.helper.module;
+    const [x, y, z] = position;
+    const offset = module.asm.getCameraMedium(x, y, z, radius, wave) >> 3;
+    const [block, submerged] = module.HEAPF64.subarray(offset, offset + 2);
+    return {block: block as BlockId, submerged};
+  }
 
-  constructor(id: string) {
-    this.container = new Container(id);
-    this.entities = new EntityComponentSystem();
-    this.renderer = new Renderer(this.container.canvas);
+  resolveCameraPosition(position: Vec3, radius: number, maxPush: number,
+                        dt: number, rate: number): boolean {
+    const module = this.helper.module;
+    const [x, y, z] = position;
+    const data = module.asm.resolveCameraPosition(
+        x, y, z, radius, maxPush, dt, rate);
+    const offset = data >> 3;
+    const heap = module.HEAPF64;
+    Vec3.set(position, heap[offset], heap[offset + 1], heap[offset + 2]);
+    return heap[offset + 3] !== 0;
+  }
 
-    this.helper = nonnull(helper);
-    this.helper.renderer = this.renderer;
-    this.helper.initializeWorld(kChunkRadius, kFrontierRadius, kFrontierLevels);
+  // For third-person cameras: sweeps the camera box from the target (the
+  // player's head) along the offset toward the desired camera position, and
+  // returns the part of the offset that's clear of opaque blocks. The result
+  // snaps in when a wall gets in the way and eases back out at `rate` per
+  // second; a rate of 0 disables smoothing.
+  clipCamera(target: Vec3, offset: Vec3, radius: number,
+             dt: number = 0, rate: number = 0): Vec3 {
+    const module = this.helper.module;
+    const [tx, ty, tz] = target, [ox, oy, oz] = offset;
+    const data = module.asm.clipCamera(
+        tx, ty, tz, ox, oy, oz, radius, dt, rate);
+    const base = data >> 3;
+    const [x, y, z] = module.HEAPF64.subarray(base, base + 3);
+    return Vec3.from(x, y, z);
+  }
 
-    this.registry = new Registry(this.helper, this.renderer);
-    this.highlight = this.renderer.addHighlightMesh();
-    this.highlightPosition = Vec3.create();
+  // Item drops are simulated in the engine, like projectiles, and we pick
+  // their ids. Each step pulls drops toward the nearest magnet (usually a
+  // player) in range, merges nearby drops of the same item, and despawns old
+  // drops. pickUpItemDrops moves drops in range into an inventory, leaving
+  // what doesn't fit, and returns the stacks that it took.
+  addItemDrop(item: int, count: int, position: Vec3,
+              velocity: Vec3 = Vec3.create()): int {
+    const [x, y, z] = position, [vx, vy, vz] = velocity;
+    return this.helper.module.asm.addItemDrop(
+        item, count, x, y, z, vx, vy, vz);
+  }
 
-    this.cameraColor = kWhite.slice() as Color;
-    this.cameraMaterial = kNoMaterial;
+  removeItemDrop(id: int): void {
+    this.helper.module.asm.removeItemDrop(id);
+  }
 
-    const remesh = this.remesh.bind(this);
-    const render = this.render.bind(this);
-    const update = this.update.bind(this);
-    this.timing = new Timing(remesh, render, update);
+  setItemDropRules(rules: ItemDropRules): void {
+    const {gravity, drag, mergeRadius, despawnTime,
+           pickupDelay, magnetRadius, magnetSpeed} = rules;
+    this.helper.module.asm.setItemDropRules(
+        gravity, drag, mergeRadius, despawnTime,
+        pickupDelay, magnetRadius, magnetSpeed);
   }
 
-  getBaseHeight(x: int, z: int): int {
-    return this.helper.module.asm.getBaseHeight(x, z);
+  setItemMagnets(positions: Vec3[]): void {
+    const module = this.helper.module;
+    const size = Math.max(positions.length, 1);
+    const data = module.asm.malloc(int(24 * size));
+    positions.forEach((x, i) => {
+      module.HEAPF64.set([x[0], x[1], x[2]], (data >> 3) + 3 * i);
+    });
+    module.asm.setItemMagnets(data, int(positions.length));
+    module.asm.free(data);
   }
 
-  getBlock(x: int, y: int, z: int): BlockId {
-    return this.helper.getBlock(x, y, z);
+  stepItemDrops(dt: number): ItemDrop[] {
+    return this.readItemDrops(this.helper.module.asm.stepItemDrops(dt));
   }
 
-  getLight(x: int, y: int, z: int): number {
-    return lighting(this.helper.getLightLevel(x, y, z));
+  getItemDrops(): ItemDrop[] {
+    return this.readItemDrops(this.helper.module.asm.getItemDrops());
   }
 
-  getMutableInputs(): Record<Input, boolean> {
-    return this.container.inputs;
+  pickUpItemDrops(inventory: int, position: Vec3,
+                  radius: number): {id: int, stack: ItemStack}[] {
+    const module = this.helper.module;
+    const [x, y, z] = position;
+    const offset =
+        module.asm.pickUpItemDrops(inventory, x, y, z, radius) >> 2;
+    const heap = module.HEAP32;
+    const result = [];
+    for (let i = 0; i < heap[offset]; i++) {
+      const base = offset + 1 + 3 * i;
+      const stack = {item: int(heap[base + 1]), count: int(heap[base + 2])};
+      result.push({id: int(heap[base]), stack});
+    }
+    return result;
   }
 
-  getTargetedBlock(): Vec3 | null {
-    return this.highlightSide < 0 ? null : this.highlightPosition;
+  // Makes the world toroidal: worldgen, saves, and neighbor lookups wrap every
+  // numChunks chunks along x and z. Positions don't wrap, so the host can
+  // keep moving the camera in a straight line. Call it before loading any
+  // chunks, and make the world wider than the loaded area.
+  setWorldWrap(numChunks: int): void {
+    this.helper.module.asm.setWorldWrap(numChunks);
   }
 
-  getTargetedBlockSide(): int {
-    return this.highlightSide;
+  // Simplifies LOD meshes at levels >= minLevel, allowing up to maxError
+  // voxels of height error. Call it before loading the world: it only
+  // affects LOD chunks that we mesh afterwards.
+  setFrontierDecimation(minLevel: int, maxError: int): void {
+    this.helper.module.asm.setFrontierDecimation(minLevel, maxError);
   }
 
-  setBlock(x: int, y: int, z: int, block: BlockId): void {
-    this.helper.setBlock(x, y, z, block);
+  // LOD chunks have skirts: side quads on their borders that hide cracks
+  // against neighbors at other LODs. By default, they extend to the bottom
+  // of the world; shallower skirts save fill rate on far terrain.
+  setFrontierSkirtDepth(depth: int): void {
+    this.helper.module.asm.setFrontierSkirtDepth(depth);
   }
 
-  setCameraTarget(x: number, y: number, z: number): void {
-    this.renderer.camera.setTarget(x, y, z);
-    this.setSafeZoomDistance();
+  // Switches the chunk meshing algorithm, e.g. kMesherNaive to debug the
+  // greedy mesher, or one of the smooth meshers for natural-looking terrain.
+  // All loaded chunks are remeshed with the new algorithm.
+  setMesherKind(kind: int): void {
+    assert(kMesherGreedy <= kind && kind <= kMesherMarchingCubes);
+    this.helper.module.asm.setMesherKind(kind);
   }
 
-  setPointLight(x: int, y: int, z: int, level: int): void {
-    this.helper.setPointLight(x, y, z, level);
+  // Meshes only the given face directions of a loaded chunk, e.g. the faces
+  // that can face the camera, from getFaceMaskForCamera. That roughly halves
+  // quads for far chunks. Update it when the camera crosses a chunk's planes.
+  setChunkFaceMask(cx: int, cz: int, mask: int): boolean {
+    assert(0 <= mask && mask <= kAllFaces);
+    return this.helper.module.asm.setChunkFaceMask(cx, cz, mask);
+  }
+
+  // Returns the faces of chunk (cx, cz) that can face a camera at (x, y, z):
+  // on each axis, the camera sees one direction if it's outside the chunk's
+  // bounds, and both if it's between them.
+  getFaceMaskForCamera(cx: int, cz: int, x: number, y: number, z: number): int {
+    const axis = (value: number, min: number, max: number): int => {
+      if (value < min) return int(1);
+      if (value > max) return int(2);
+      return int(3);
+    };
+    const bx = cx * kChunkWidth, bz = cz * kChunkWidth;
+    return int(axis(x, bx, bx + kChunkWidth) |
+               (axis(y, 0, kWorldHeight) << 2) |
+               (axis(z, bz, bz + kChunkWidth) << 4));
+  }
+
+  // Keeps compact octree copies of chunks that unload within this radius, so
+  // that far edits show up in nearby LOD meshes and survive a reload without
+  // a round trip through saves. 0, the default, disables it.
+  setFarChunkRadius(radius: int): void {
+    this.helper.module.asm.setFarChunkRadius(radius);
   }
 
   recenter(x: number, y: number, z: number): void {
@@ -537,6 +2868,33 @@ class Env {
     this.helper.remeshWorld();
   }
 
+  // Meshes all of the given loaded chunks in one call, bypassing the
+  // per-frame budget; use it after a teleport. Returns a bitmask of the
+  // remeshed sections for each chunk, or 0 if it wasn't remeshed.
+  meshMany(chunks: [int, int][]): int[] {
+    const module = this.helper.module;
+    const data = module.asm.malloc(int(8 * Math.max(chunks.length, 1)));
+    chunks.forEach(([cx, cz], i) => {
+      module.HEAP32[(data >> 2) + 2 * i + 0] = cx;
+      module.HEAP32[(data >> 2) + 2 * i + 1] = cz;
+    });
+    const offset = module.asm.meshMany(data, int(chunks.length)) >> 2;
+    module.asm.free(data);
+
+    const heap = module.HEAP32;
+    return chunks.map((_, i) => int(heap[offset + 3 * i + 2]));
+  }
+
+  // Does at most `budget` chunk loads, relights, or remeshes to bring the
+  // chunk (cx, cz) to the target kChunkStage* value, and returns the stage
+  // that it reached. Call it each frame until it returns the target, e.g.
+  // to place a structure once its neighbors' terrain exists. It can only
+  // load chunks within the loaded radius. A budget of 0 returns the stage.
+  advanceChunk(cx: int, cz: int, target: int, budget: int): int {
+    assert(kChunkStageEmpty <= target && target <= kChunkStageMeshed);
+    return int(this.helper.module.asm.advanceChunk(cx, cz, target, budget));
+  }
+
   render(dt: number): void {
     if (!this.container.inputs.pointer) return;
 
@@ -570,9 +2928,108 @@ class Env {
 
   update(dt: number): void {
     if (!this.container.inputs.pointer) return;
+    this.helper.tickWorld();
     this.entities.update(dt);
   }
 
+  private readProjectiles(data: WasmFloat32Ptr): Projectile[] {
+    const module = this.helper.module;
+    const offset = data >> 2;
+    const heap = module.HEAPF32;
+    const result: Projectile[] = [];
+    for (let i = 0; i < heap[offset]; i++) {
+      const base = offset + 1 + 5 * i;
+      const id = int(heap[base]);
+      const [x, y, z] = heap.subarray(base + 1, base + 4);
+      const stuck = heap[base + 4] !== 0;
+      result.push({id, position: Vec3.from(x, y, z), stuck});
+    }
+    return result;
+  }
+
+  private readItemDrops(data: WasmFloat32Ptr): ItemDrop[] {
+    const module = this.helper.module;
+    const offset = data >> 2;
+    const heap = module.HEAPF32;
+    const result: ItemDrop[] = [];
+    for (let i = 0; i < heap[offset]; i++) {
+      const base = offset + 1 + 6 * i;
+      const [id, item, count, x, y, z] = heap.subarray(base, base + 6);
+      result.push({id: int(id), item: int(item), count: int(count),
+                   position: Vec3.from(x, y, z)});
+    }
+    return result;
+  }
+
+  // Reads a uint32 count and then that many block entities. Each one is an
+  // int32 index and type, a uint32 size, and data padded to 4 bytes.
+  private readBlockEntities(data: WasmCharPtr): BlockEntity[] {
+    const count = this.helper.module.HEAPU32[data >> 2];
+    const result: BlockEntity[] = [];
+    let offset = data + 4;
+    for (let i = 0; i < count; i++) {
+      const [entity, next] = this.readBlockEntity(offset as WasmCharPtr);
+      result.push(entity);
+      offset = next;
+    }
+    return result;
+  }
+
+  private readBlockEntity(data: WasmCharPtr): [BlockEntity, int] {
+    const module = this.helper.module;
+    const index = int(module.HEAP32[data >> 2]);
+    const type = int(module.HEAP32[(data >> 2) + 1]);
+    const size = module.HEAPU32[(data >> 2) + 2];
+    const start = data + 12;
+    const bytes = module.HEAPU8.slice(start, start + size);
+    return [{index, type, data: bytes}, int((start + size + 3) & ~3)];
+  }
+
+  private readPacket(data: WasmCharPtr): Uint8Array | null {
+    if (data === 0) return null;
+    const module = this.helper.module;
+    const size = module.HEAPU32[data >> 2];
+    return module.HEAPU8.slice(data + 4, data + 4 + size);
+  }
+
+  private withPacket<T>(
+      packet: Uint8Array, fn: (data: WasmCharPtr, size: int) => T): T {
+    const module = this.helper.module;
+    const size = int(packet.length);
+    const data = module.asm.malloc(int(Math.max(size, 1)));
+    module.HEAPU8.set(packet, data);
+    const result = fn(data, size);
+    module.asm.free(data);
+    return result;
+  }
+
+  private writeTransforms(transforms: EntityTransform[]): WasmCharPtr {
+    const module = this.helper.module;
+    const data = module.asm.malloc(int(40 * Math.max(transforms.length, 1)));
+    const base = data >> 2;
+    transforms.forEach((x, i) => {
+      module.HEAP32[base + 10 * i] = x.id;
+      module.HEAPF64.set([x.x, x.y, x.z], (base >> 1) + 5 * i + 1);
+      module.HEAPF32[base + 10 * i + 8] = x.heading;
+    });
+    return data;
+  }
+
+  private getEditDrops(): EditDrop[] {
+    const module = this.helper.module;
+    const offset = module.asm.getEditDrops() >> 2;
+    const heap = module.HEAPF32;
+    const result: EditDrop[] = [];
+    for (let i = 0; i < heap[offset]; i++) {
+      const base = offset + 1 + 5 * i;
+      const block = heap[base + 0] as BlockId;
+      const count = int(heap[base + 1]);
+      const [x, y, z] = heap.subarray(base + 2, base + 5);
+      result.push({block, count, centroid: Vec3.from(x, y, z)});
+    }
+    return result;
+  }
+
   private formatStat(perf: Performance): string {
     const format = (x: number) => (x / 1000).toFixed(2);
     return `${format(perf.mean())}ms / ${format(perf.max())}ms`;
@@ -589,7 +3046,7 @@ class Env {
 
   private setSafeZoomDistance(): void {
     const camera = this.renderer.camera;
-    const {direction, target, zoom} = camera;
+    const {voxel_direction: direction, target, zoom} = camera;
     const [x, y, z] = target;
 
     const check = (x: int, y: int, z: int) => {
@@ -638,7 +3095,7 @@ class Env {
 
   private updateHighlightMesh(): void {
     const camera = this.renderer.camera;
-    const {direction, target, zoom} = camera;
+    const {voxel_direction: direction, target, zoom} = camera;
 
     let move = false;
     this.highlight.mask = int((1 << 6) - 1);
@@ -744,13 +3201,17 @@ class Env {
 type WasmCharPtr   = int & {__cpp_type__: 'char*'};
 type WasmNoise2D   = int & {__cpp_type__: 'voxels::Noise2D*'};
 type WasmHeightmap = int & {__cpp_type__: 'voxels::Heightmap*'};
+type WasmUint32Ptr = int & {__cpp_type__: 'uint32_t*'};
+type WasmInt32Ptr  = int & {__cpp_type__: 'int*'};
+type WasmFloat32Ptr = int & {__cpp_type__: 'float*'};
+type WasmFloat64Ptr = int & {__cpp_type__: 'double*'};
 
 interface WasmModule {
   HEAP8:   Int8Array,
   HEAP16:  Int16Array,
   HEAP32:  Int32Array,
   HEAPF32: Float32Array,
-  HEAPF64: Float32Array,
+  HEAPF64: Float64Array,
   HEAPU8:  Uint8Array,
   HEAPU16: Uint16Array,
   HEAPU32: Uint32Array,
@@ -761,15 +3222,251 @@ interface WasmModule {
     initializeWorld: (chunkRadius: int, frontierRadius: int, frontierLevels: int) => void;
     recenterWorld: (x: int, z: int) => void,
     remeshWorld: () => void,
+    tickWorld: () => void,
 
     getBaseHeight: (x: int, z: int) => int,
     getBlock: (x: int, y: int, z: int) => BlockId,
     setBlock: (x: int, y: int, z: int, block: BlockId) => void,
+    fillBlocks: (x0: int, y0: int, z0: int, x1: int, y1: int, z1: int,
+                 block: BlockId, collectDrops: boolean) => void,
+    getEditDrops: () => WasmFloat32Ptr,
     getLightLevel: (x: int, y: int, z: int) => int,
+    getShadowHeight: (x: int, y: int, z: int) => int,
+    getShadowHeights: (x: int, z: int, nx: int, nz: int,
+                       y: int) => WasmInt32Ptr,
     setPointLight: (x: int, y: int, z: int, level: int) => void,
+    setDynamicLight: (id: int, x: int, y: int, z: int, level: int) => void,
+    rebaseOrigin: (x: int, z: int) => void,
+    setActiveDimension: (id: int) => void,
+    getActiveDimension: () => int,
+    removeDimension: (id: int) => boolean,
+    setWorldSeed: (seed: int) => void,
+    linkPortal: (d0: int, x0: int, y0: int, z0: int,
+                 d1: int, x1: int, y1: int, z1: int) => void,
+    unlinkPortal: (d: int, x: int, y: int, z: int) => void,
+    getPortalLink: (d: int, x: int, y: int, z: int) => WasmInt32Ptr,
+    addProjectile: (id: int, kind: int, x: number, y: number, z: number,
+                    vx: number, vy: number, vz: number) => void,
+    removeProjectile: (id: int) => void,
+    stepProjectiles: (dt: number) => WasmFloat32Ptr,
+    getProjectiles: () => WasmFloat32Ptr,
+    advanceWorld: (dtMs: number) => WasmFloat64Ptr,
+    setTickRate: (ticksPerSecond: number, maxTicks: int) => boolean,
+    setRaining: (raining: boolean) => void,
+    snapshotWorld: () => int,
+    rollbackWorld: (id: int) => boolean,
+    releaseWorldSnapshot: (id: int) => boolean,
+    encodeWorldSnapshot: (id: int) => WasmCharPtr,
+    decodeWorldSnapshot: (data: WasmCharPtr, size: int) => int,
+    setDebugDrawFlags: (flags: int) => void,
+    drainDebugDraw: () => WasmCharPtr,
+    getCameraMedium: (x: number, y: number, z: number, radius: number,
+                      wave: number) => WasmFloat64Ptr,
+    resolveCameraPosition: (x: number, y: number, z: number, radius: number,
+                            maxPush: number, dt: number,
+                            rate: number) => WasmFloat64Ptr,
+    clipCamera: (tx: number, ty: number, tz: number, ox: number, oy: number,
+                 oz: number, radius: number, dt: number,
+                 rate: number) => WasmFloat64Ptr,
+    addItemDrop: (item: int, count: int, x: number, y: number, z: number,
+                  vx: number, vy: number, vz: number) => int,
+    removeItemDrop: (id: int) => void,
+    setItemDropRules: (gravity: number, drag: number, mergeRadius: number,
+                       despawnTime: number, pickupDelay: number,
+                       magnetRadius: number, magnetSpeed: number) => void,
+    setItemMagnets: (positions: WasmCharPtr, count: int) => void,
+    stepItemDrops: (dt: number) => WasmFloat32Ptr,
+    getItemDrops: () => WasmFloat32Ptr,
+    pickUpItemDrops: (inventory: int, x: number, y: number, z: number,
+                      radius: number) => WasmInt32Ptr,
+    setWorldWrap: (numChunks: int) => void,
+    setSkyDarkness: (level: int) => void,
+    getEffectiveLightLevel: (x: int, y: int, z: int) => int,
+    setFrontierDecimation: (minLevel: int, maxError: int) => void,
+    setFrontierSkirtDepth: (depth: int) => void,
+    setMesherKind: (kind: int) => void,
+    setChunkFaceMask: (cx: int, cz: int, mask: int) => boolean,
+    setFarChunkRadius: (radius: int) => void,
+
+    audioOcclusion: (lx: number, ly: number, lz: number,
+                     sx: number, sy: number, sz: number) => number,
+    roomSizeEstimate: (x: number, y: number, z: number) => number,
+    raycast: (x: number, y: number, z: number, dx: number, dy: number,
+              dz: number, length: number) => number,
+    bakeAmbientOcclusion: (cx: int, cz: int, samples: int) => boolean,
+    validateMesh: (cx: int, cz: int) => WasmInt32Ptr,
+    checkBorders: (cx: int, cz: int) => WasmInt32Ptr,
+    validateWorld: () => WasmInt32Ptr,
+    checkMeshFixtures: () => WasmInt32Ptr,
+    weldMesh: (cx: int, cz: int, phase: int,
+               repeats: boolean) => WasmInt32Ptr,
+    batchMesh: (cx: int, cz: int, phase: int, repeats: boolean,
+                pages: WasmCharPtr) => WasmInt32Ptr,
+    exportBricks: (cx: int, cz: int) => WasmInt32Ptr,
+    getConeVolume: (cx: int, cz: int, level: int) => WasmCharPtr,
+    getCollisionField: (cx: int, cz: int, level: int) => WasmInt32Ptr,
+    getCollisionFieldVersion: (cx: int, cz: int) => int,
+    getChunkLayer: (cx: int, cz: int, layer: int) => WasmInt32Ptr,
+    dumpSlice: (cx: int, cz: int, axis: int, index: int) => WasmCharPtr,
+    loadSlice: (cx: int, cz: int, text: WasmCharPtr) => boolean,
+    setHeightmapMesh: (data: WasmCharPtr, n: int, x: int, z: int) => void,
+    startRecording: () => void,
+    stopRecording: () => WasmCharPtr,
+    replay: (data: WasmCharPtr, size: int) => int,
+    tickFire: (cx: int, cz: int, raining: boolean) => WasmInt32Ptr,
+    growTree: (x: int, y: int, z: int, species: int, seed: int) => boolean,
+    voxelizeMesh: (vertices: WasmCharPtr, indices: WasmCharPtr,
+                   numVertices: int, numIndices: int, block: BlockId,
+                   resolution: number, solid: boolean,
+                   x: int, y: int, z: int) => int,
+    importHeightmap: (samples: WasmCharPtr, w: int, h: int, scale: number,
+                      table: WasmCharPtr, numLayers: int,
+                      x: int, z: int) => int,
+    exportHeightmap: (x0: int, z0: int, x1: int, z1: int,
+                      scale: number) => WasmCharPtr,
+    importLayerPng: (data: WasmCharPtr, size: int, table: WasmCharPtr,
+                     numColors: int, x: int, y: int, z: int) => int,
+    setSymmetry: (mirrors: int, planeX2: int, planeY2: int, planeZ2: int,
+                  radial: int, axisX2: int, axisZ2: int) => void,
+    setSelection: (x0: int, y0: int, z0: int,
+                   x1: int, y1: int, z1: int) => void,
+    clearSelection: () => void,
+    getSelection: () => WasmInt32Ptr,
+    expandSelection: (face: int, amount: int) => void,
+    shiftSelection: (dx: int, dy: int, dz: int) => void,
+    setSelectionMask: (enabled: boolean) => void,
+    getSelectionStats: () => WasmFloat64Ptr,
+    fillSelection: (block: BlockId, collectDrops: boolean) => boolean,
+    replaceInSelection: (from: BlockId, to: BlockId,
+                         collectDrops: boolean) => int,
+    carvePath: (points: WasmCharPtr, count: int, profile: int, width: number,
+                height: number, spline: boolean, block: BlockId) => int,
+    copyToClipboard: (id: int, x0: int, y0: int, z0: int,
+                      x1: int, y1: int, z1: int) => void,
+    createClipboardShape: (id: int, shape: int, sx: int, sy: int, sz: int,
+                           block: BlockId, shell: int) => void,
+    removeClipboard: (id: int) => boolean,
+    applyCsg: (id: int, op: int, x: int, y: int, z: int) => int,
+    anySolidInBox: (x0: int, y0: int, z0: int,
+                    x1: int, y1: int, z1: int) => boolean,
+    explode: (x: int, y: int, z: int, power: number,
+              seed: int) => WasmInt32Ptr,
+    addProtectedRegion: (x0: int, y0: int, z0: int, x1: int, y1: int,
+                         z1: int, owner: int) => void,
+    removeProtectedRegions: (owner: int) => void,
+    setEditor: (owner: int) => void,
+    stepTriggers: (boxes: WasmCharPtr, entities: WasmCharPtr, count: int,
+                   stays: boolean) => void,
+    registerBlockTrigger: (block: BlockId, type: int, x0: number,
+                           y0: number, z0: number, x1: number, y1: number,
+                           z1: number) => void,
+    tryPlayerEdit: (player: int, x: int, y: int, z: int, block: int,
+                    eyeX: number, eyeY: number, eyeZ: number, tool: int,
+                    tier: int, mined: number, time: number) => int,
+    setPlayerEditRules: (reach: number, rate: number, burst: number,
+                         miningSlack: number) => void,
+    resetPlayerEditLimit: (player: int) => void,
+    addStructure: (type: int, x0: int, y0: int, z0: int,
+                   x1: int, y1: int, z1: int) => void,
+    getStructuresInBox: (x0: int, y0: int, z0: int,
+                         x1: int, y1: int, z1: int) => WasmInt32Ptr,
+    findNearestStructure: (type: int, x: int, z: int) => WasmInt32Ptr,
+    buildImpostor: (cx: int, cz: int, resolution: int) => WasmUint32Ptr,
+    greedyRects: (mask: WasmInt32Ptr, w: int, h: int) => WasmInt32Ptr,
+    meshMany: (points: WasmCharPtr, count: int) => WasmInt32Ptr,
+    advanceChunk: (cx: int, cz: int, target: int, budget: int) => int,
+    renderMinimap: (cx: int, cz: int) => WasmUint32Ptr,
 
     registerBlock: any,
+    registerBlockMining: (block: BlockId, hardness: number, tool: int,
+                          tier: int, dropTable: int) => void,
+    registerDropEntry: (table: int, item: int, min: int,
+                        max: int, chance: number) => void,
+    registerBlockFire: (block: BlockId, ignite: number, burn: number) => void,
+    registerBlockTags: (block: BlockId, tags: int) => void,
+    registerBlockAttenuation: (block: BlockId, attenuation: int) => void,
+    registerBlockBlastResistance: (block: BlockId, resistance: number) => void,
+    registerBlockEditPriority: (block: BlockId, priority: int) => void,
+    registerTreeSpecies: (species: int, trunk: BlockId, leaves: BlockId,
+                          minHeight: int, maxHeight: int, radius: int,
+                          conical: boolean) => void,
+    registerSapling: (block: BlockId, species: int, chance: number) => void,
+    registerProjectileKind: (kind: int, gravity: number, drag: number,
+                             restitution: number) => void,
+    registerProjectileResponse: (kind: int, block: BlockId,
+                                 response: int) => void,
+    registerBlockPower: (block: BlockId, kind: int, level: int) => void,
+    registerBlockHooks: (block: BlockId, hooks: int) => void,
+    propagatePower: (positions: WasmCharPtr, count: int) => WasmInt32Ptr,
+    getBlockMetadata: (x: int, y: int, z: int) => int,
+    pushEntitySnapshot:
+      (time: number, data: WasmCharPtr, count: int) => boolean,
+    sampleEntities: (time: number) => WasmFloat32Ptr,
+    clearEntitySnapshots: () => void,
+    apiVersion: () => int,
+    memoryGeneration: () => int,
+    arenaReset: () => int,
+    featureFlags: () => int,
+    layoutDescriptor: () => WasmInt32Ptr,
+    drainEvents: () => WasmInt32Ptr,
+    pushEvent: (type: int, x: int, y: int, z: int, a: int, b: int) => void,
+    chunksNeedingSave: () => WasmInt32Ptr,
+    saveChunks: (budget: int) => WasmCharPtr,
+    encodeChunkData: (cx: int, cz: int) => WasmCharPtr,
+    decodeChunkData: (data: WasmCharPtr, size: int) => WasmCharPtr,
+    getChunkExtra: (cx: int, cz: int) => WasmCharPtr,
+    setChunkExtra: (cx: int, cz: int, data: WasmCharPtr, size: int) =>
+        boolean,
+    createBlockEntity: (x: int, y: int, z: int, type: int,
+                        data: WasmCharPtr, size: int) => boolean,
+    getBlockEntity: (x: int, y: int, z: int) => WasmCharPtr,
+    removeBlockEntity: (x: int, y: int, z: int) => boolean,
+    getChunkBlockEntities: (cx: int, cz: int) => WasmCharPtr,
+    createInventory: (id: int, size: int) => void,
+    removeInventory: (id: int) => boolean,
+    getInventory: (id: int) => WasmInt32Ptr,
+    setInventorySlot: (id: int, slot: int, item: int, count: int) => boolean,
+    addToInventory: (id: int, item: int, count: int) => int,
+    removeFromInventory: (id: int, item: int, count: int) => int,
+    moveInventoryItems: (from: int, fromSlot: int, to: int,
+                         toSlot: int, count: int) => int,
+    splitInventoryStack: (id: int, fromSlot: int, toSlot: int) => int,
+    encodeInventory: (id: int) => WasmCharPtr,
+    decodeInventory: (id: int, data: WasmCharPtr, size: int) => boolean,
+    encodeChunkDelta: (cx: int, cz: int, edits: WasmCharPtr,
+                       count: int) => WasmCharPtr,
+    decodeChunkDelta: (data: WasmCharPtr, size: int) => WasmInt32Ptr,
+    encodeBlockEdit: (x: int, y: int, z: int, block: BlockId) => WasmCharPtr,
+    decodeBlockEdit: (data: WasmCharPtr, size: int) => WasmInt32Ptr,
+    encodeEntitySnapshot: (time: number, data: WasmCharPtr,
+                           count: int) => WasmCharPtr,
+    decodeEntitySnapshot: (data: WasmCharPtr, size: int) => boolean,
+    encodeRegistrySync: () => WasmCharPtr,
+    decodeRegistrySync: (data: WasmCharPtr, size: int) => WasmFloat64Ptr,
+    pushBlocks: (x: int, y: int, z: int,
+                 dir: int, maxCount: int) => WasmInt32Ptr,
+    registerBlockSpread: (block: BlockId, target: BlockId,
+                          decay: BlockId, minLight: int) => void,
+    registerFireBlock: (block: BlockId) => void,
     registerMaterial: any,
+
+    breakTime: (block: BlockId, tool: int, tier: int) => number,
+    dropsFor: (block: BlockId, tool: int, tier: int, seed: int) => WasmInt32Ptr,
+    registerItemTags: (item: int, tags: int) => void,
+    registerItemMaxStack: (item: int, max: int) => void,
+    registerShapedRecipe: (width: int, height: int, pattern: WasmCharPtr,
+                           result: int, count: int) => int,
+    registerShapelessRecipe: (data: WasmCharPtr, size: int,
+                              result: int, count: int) => int,
+    matchRecipe: (grid: WasmCharPtr, width: int, height: int) => int,
+    recipeResult: (id: int) => WasmInt32Ptr,
+    registerLootPool: (table: int, minRolls: int, maxRolls: int,
+                       chance: number, tool: int, minTier: int,
+                       minFortune: int, flags: int) => int,
+    registerLootEntry: (table: int, pool: int, item: int, weight: int,
+                        min: int, max: int, fortuneBonus: int, tool: int,
+                        minTier: int, minFortune: int, flags: int) => void,
+    rollLoot: (table: int, context: WasmCharPtr, seed: int) => WasmInt32Ptr,
   },
 };
 
@@ -805,14 +3502,60 @@ class WasmHandle<T> {
   }
 };
 
+// Keep these in sync with kApiVersion, kFeature*, and layoutDescriptor in
+// engine.cpp. Each layout is (id, size in bytes, field offsets).
+const kApiVersion = 1;
+const kFeatureSimd       = int(1 << 0);
+const kFeatureThreads    = int(1 << 1);
+const kFeatureLighting   = int(1 << 2);
+const kFeatureNetworking = int(1 << 3);
+const kFeatureFilesystem = int(1 << 4);
+const kFeatureFixedPoint = int(1 << 5);
+const kLayouts: [number, number, number[]][] = [
+  [1, 40, [0, 8, 16, 24, 32]],
+  [2, 24, [0, 4, 8, 12, 16, 20]],
+  [3, 4 * Geometry.StrideInInt32, []],
+];
+
+const checkCompatibility = (module: WasmModule): int => {
+  const version = module.asm.apiVersion();
+  if (version !== kApiVersion) {
+    throw new Error(`WASM API version ${version} !== ${kApiVersion}`);
+  }
+
+  const offset = module.asm.layoutDescriptor() >> 2;
+  const heap = module.HEAP32;
+  const layouts: Map<number, number[]> = new Map();
+  for (let i = 0, cur = offset + 1; i < heap[offset]; i++) {
+    const id = heap[cur], n = heap[cur + 2];
+    layouts.set(id, Array.from(heap.subarray(cur + 1, cur + 3 + n)));
+    cur += 3 + n;
+  }
+  for (const [id, size, offsets] of kLayouts) {
+    const expected = [size, offsets.length, ...offsets].join(',');
+    const actual = (layouts.get(id) || []).join(',');
+    if (actual === expected) continue;
+    throw new Error(`WASM layout ${id} mismatch: ${actual} !== ${expected}`);
+  }
+
+  const features = module.asm.featureFlags();
+  const required = kFeatureLighting | kFeatureNetworking;
+  if ((features & required) !== required) {
+    throw new Error(`WASM module missing features: ${features}`);
+  }
+  return features;
+};
+
 class WasmHelper {
   module: WasmModule;
+  features: int;
 
   // Bindings to call C++ from JavaScript.
 
   initializeWorld: (chunkRadius: int, frontierRadius: int, frontierLevels: int) => void;
   recenterWorld: (x: int, z: int) => void;
   remeshWorld: () => void;
+  tickWorld: () => void;
 
   getBlock: (x: int, y: int, z: int) => BlockId;
   setBlock: (x: int, y: int, z: int, block: BlockId) => void;
@@ -824,14 +3567,19 @@ class WasmHelper {
   instances: WasmHandle<Instance>;
   lights: WasmHandle<LightTexture>;
   meshes: WasmHandle<VoxelMesh>;
+  smooth_meshes: WasmHandle<SmoothMesh>;
   renderer: Renderer | null = null;
   block_to_instance: (InstancedMesh | null)[];
+  hooks: BlockHooks = {};
+  onMemoryGrowth: ((generation: int) => void) | null = null;
 
   constructor(module: WasmModule) {
     this.module = module;
+    this.features = checkCompatibility(module);
     this.initializeWorld = module.asm.initializeWorld;
     this.recenterWorld = module.asm.recenterWorld;
     this.remeshWorld = module.asm.remeshWorld;
+    this.tickWorld = module.asm.tickWorld;
     this.getBlock = module.asm.getBlock;
     this.setBlock = module.asm.setBlock;
     this.getLightLevel = module.asm.getLightLevel;
@@ -840,6 +3588,7 @@ class WasmHelper {
     this.instances = new WasmHandle();
     this.lights = new WasmHandle();
     this.meshes = new WasmHandle();
+    this.smooth_meshes = new WasmHandle();
     this.block_to_instance = [];
   }
 };
@@ -853,11 +3602,11 @@ const checkReady = () => {
   on_start_callbacks.forEach(x => x());
 };
 
-const js_AddLightTexture = (data: int, size: int) => {
+const js_AddLightTexture = (data: int, size: int, x: int, z: int) => {
   const h = nonnull(helper);
   const r = nonnull(h.renderer);
   const buffer = h.module.HEAPU8.subarray(data, data + size);
-  return h.lights.allocate(r.addLightTexture(buffer));
+  return h.lights.allocate(r.addLightTexture(buffer, x, z));
 };
 
 const js_FreeLightTexture = (handle: int) => {
@@ -875,6 +3624,11 @@ const js_FreeInstancedMesh = (handle: int): void => {
   nonnull(helper).instances.free(handle).dispose();
 };
 
+const js_SetInstancedMeshPosition =
+    (handle: int, x: int, y: int, z: int): void => {
+  nonnull(helper).instances.get(handle).setPosition(x + 0.5, y, z + 0.5);
+};
+
 const js_SetInstancedMeshLight = (handle: int, level: int) => {
   const h = nonnull(helper);
   h.instances.get(handle).setLight(lighting(level));
@@ -926,8 +3680,46 @@ const js_SetVoxelMeshMask = (handle: int, m0: int, m1: int, shown: int) => {
   h.meshes.get(handle).show(m0, m1, !!shown);
 };
 
-const js_SetVoxelMeshPosition = (handle: int, x: int, y: int, z: int): void => {
-  nonnull(helper).meshes.get(handle).setPosition(x, y, z);
+const js_AddSmoothMesh = (data: int, size: int) => {
+  const h = nonnull(helper);
+  const r = nonnull(h.renderer);
+  const offset = data >> 2;
+  const buffer = h.module.HEAPF32.slice(offset, offset + size);
+  return h.smooth_meshes.allocate(r.addSmoothMesh(buffer));
+};
+
+const js_FreeSmoothMesh = (handle: int): void => {
+  nonnull(helper).smooth_meshes.free(handle).dispose();
+};
+
+const js_SetSmoothMeshGeometry = (handle: int, data: int, size: int) => {
+  const h = nonnull(helper);
+  const offset = data >> 2;
+  const buffer = h.module.HEAPF32.slice(offset, offset + size);
+  h.smooth_meshes.get(handle).setGeometry(buffer);
+};
+
+const js_SetSmoothMeshLight = (handle: int, texture: int) => {
+  const h = nonnull(helper);
+  h.smooth_meshes.get(handle).setLight(h.lights.get(texture));
+};
+
+const js_SetSmoothMeshShown = (handle: int, shown: int) => {
+  nonnull(helper).smooth_meshes.get(handle).show(!!shown);
+};
+
+const js_OnMemoryGrowth = (generation: int): void => {
+  const callback = nonnull(helper).onMemoryGrowth;
+  if (callback) callback(generation);
+};
+
+const js_CallBlockHook = (hook: int, block: BlockId, x: int, y: int, z: int,
+                          context: int): void => {
+  const {onBlockPlace, onBlockBreak, onRandomTick} = nonnull(helper).hooks;
+  const other = context as BlockId;
+  if (hook === 0 && onBlockPlace) onBlockPlace(block, x, y, z, other);
+  if (hook === 1 && onBlockBreak) onBlockBreak(block, x, y, z, other);
+  if (hook === 2 && onRandomTick) onRandomTick(block, x, y, z);
 };
 
 const init = (fn: () => void) => on_start_callbacks.push(fn);
@@ -939,18 +3731,64 @@ window.onload = () => { loaded = true; checkReady(); };
   env.js_AddInstancedMesh      = js_AddInstancedMesh;
   env.js_FreeInstancedMesh     = js_FreeInstancedMesh;
   env.js_SetInstancedMeshLight = js_SetInstancedMeshLight;
+  env.js_SetInstancedMeshPosition = js_SetInstancedMeshPosition;
   env.js_AddVoxelMesh  = js_AddVoxelMesh;
   env.js_FreeVoxelMesh = js_FreeVoxelMesh;
   env.js_AddVoxelMeshGeometry = js_AddVoxelMeshGeometry;
   env.js_SetVoxelMeshGeometry = js_SetVoxelMeshGeometry;
   env.js_SetVoxelMeshLight    = js_SetVoxelMeshLight;
   env.js_SetVoxelMeshMask     = js_SetVoxelMeshMask;
-  env.js_SetVoxelMeshPosition = js_SetVoxelMeshPosition;
+  env.js_AddSmoothMesh  = js_AddSmoothMesh;
+  env.js_FreeSmoothMesh = js_FreeSmoothMesh;
+  env.js_SetSmoothMeshGeometry = js_SetSmoothMeshGeometry;
+  env.js_SetSmoothMeshLight    = js_SetSmoothMeshLight;
+  env.js_SetSmoothMeshShown    = js_SetSmoothMeshShown;
+  env.js_CallBlockHook = js_CallBlockHook;
+  env.js_OnMemoryGrowth = js_OnMemoryGrowth;
 };
 (window as any).onWasmCompile =
   (m: WasmModule) => { helper = new WasmHelper(m); checkReady(); };
 
 //////////////////////////////////////////////////////////////////////////////
 
-export {BlockId, MaterialId, Env, init};
-export {kChunkWidth, kEmptyBlock, kNoMaterial, kWorldHeight};
+export {BlockId, Drop, EditDrop, Explosion, MaterialId, Env, Impostor, Rect, init};
+export {BlockHooks, BorderReport, EngineEvent, EntityTransform, MeshReport};
+export {PortalEnd, PowerChange, Projectile, Structure, WorldReport};
+export {BrickExport, DirtyBox, IndexedMesh, Ingredient, PushResult};
+export {TensorView};
+export {MeshBatch};
+export {DebugCommand, ItemDrop, ItemDropRules, ItemStack};
+export {LootCondition, LootContext, SelectionStats};
+export {HeightmapLayer, PlayerEditContext, PlayerEditRules};
+export {BlockEditPacket, BlockEntity, ChunkDeltaPacket, ChunkEdit};
+export {ChunkPacket, SyncedBlock, SyncedMaterial, SyncedRegistry};
+export {kBlockTagFixed, kBlockTagLeaves, kBlockTagLog};
+export {kChunkLayerHeightmap, kChunkLayerLights};
+export {kChunkLayerMetadata, kChunkLayerVoxels};
+export {kAllFaces, kMesherGreedy, kMesherMarchingCubes, kMesherNaive};
+export {kMesherSurfaceNets};
+export {kBatchVertexFloats};
+export {kChunkStageCarved, kChunkStageDecorated, kChunkStageEmpty};
+export {kChunkStageLit, kChunkStageMeshed, kChunkStageTerrain};
+export {kPowerSink, kPowerSource, kPowerWire};
+export {kProjectileBounce, kProjectilePass, kProjectileStick};
+export {kHookBreak, kHookEntityCollide, kHookPlace, kHookRandomTick};
+export {kEventBlockChanged, kEventChunkMeshed, kEventEntityLanded};
+export {kEventEditRejected, kEventFluidFlowed, kEventLightUpdated};
+export {kEventPlayerEdit, kEventProjectileHit};
+export {kEventTriggerEnter, kEventTriggerExit, kEventTriggerStay};
+export {kEditAccepted, kEditOutOfReach, kEditProtected, kEditRateLimited};
+export {kEditTooFast, kEditUnbreakable, kEditUnchanged, kEditUnloaded};
+export {kEditInvalidBlock, kEditUnplaceable};
+export {kFeatureFilesystem, kFeatureFixedPoint};
+export {kFeatureLighting, kFeatureNetworking};
+export {kFeatureSimd, kFeatureThreads};
+export {kChunkWidth, kEmptyBlock, kNoItem, kNoMaterial, kWorldHeight};
+export {kUnknownBlock};
+export {kDebugCamera, kDebugItemDrops, kDebugLighting, kDebugProjectiles};
+export {kDebugBox, kDebugLine, kDebugText};
+export {kClipboardBox, kClipboardCylinder, kClipboardSphere};
+export {kCsgIntersect, kCsgSubtract, kCsgUnion};
+export {kPathCircle, kPathRect};
+export {kMirrorX, kMirrorY, kMirrorZ};
+export {kUVFlip, kUVRotate180, kUVRotate270, kUVRotate90};
@@ -0,0 +1,117 @@
+import {assert, int, Vec3} from './base.js';
+import {EntityId, SpatialIndex} from './ecs.js';
+
+//////////////////////////////////////////////////////////////////////////////
+
+// Interest management for networked play. For each player, we track the set
+// of chunks and entities in their area of interest, and each query returns
+// what entered and left that set since the last query, so the networking
+// layer only has to send what changed.
+//
+// A player's area of interest is the circle of chunks around the chunk that
+// contains them, as for the chunks that the engine keeps loaded, and the
+// entities within the same distance of them.
+
+type ChunkKey = [int, int];
+
+interface InterestDelta<T> {entered: T[], left: T[]};
+
+interface PlayerInterest {
+  position: Vec3,
+  chunkRadius: number,
+  chunks: Map<string, ChunkKey>,
+  entities: Set<EntityId>,
+};
+
+class InterestManager {
+  private chunkWidth: int;
+  private spatial: SpatialIndex;
+  private players: Map<EntityId, PlayerInterest>;
+
+  constructor(chunkWidth: int, spatial: SpatialIndex) {
+    this.chunkWidth = chunkWidth;
+    this.spatial = spatial;
+    this.players = new Map();
+  }
+
+  // Updates the player's position and view distance, in chunks, and returns
+  // the chunks that entered and left their area of interest.
+  relevantChunks(player: EntityId, position: Vec3,
+                 chunkRadius: number): InterestDelta<ChunkKey> {
+    const state = this.getState(player);
+    Vec3.copy(state.position, position);
+    state.chunkRadius = chunkRadius;
+
+    const w = this.chunkWidth;
+    const cx = Math.floor(position[0] / w);
+    const cz = Math.floor(position[2] / w);
+    const bound = chunkRadius * chunkRadius;
+    const floor = Math.floor(chunkRadius);
+
+    const chunks: Map<string, ChunkKey> = new Map();
+    const entered: ChunkKey[] = [];
+    for (let dx = -floor; dx <= floor; dx++) {
+      for (let dz = -floor; dz <= floor; dz++) {
+        if (dx * dx + dz * dz > bound) continue;
+        const chunk: ChunkKey = [int(cx + dx), int(cz + dz)];
+        const key = `${chunk[0]},${chunk[1]}`;
+        chunks.set(key, chunk);
+        if (!state.chunks.has(key)) entered.push(chunk);
+      }
+    }
+
+    const left: ChunkKey[] = [];
+    for (const [key, chunk] of state.chunks) {
+      if (!chunks.has(key)) left.push(chunk);
+    }
+    state.chunks = chunks;
+    return {entered, left};
+  }
+
+  // Returns the entities that entered and left the player's area of interest,
+  // as of the position and view distance of the last relevantChunks call.
+  relevantEntities(player: EntityId): InterestDelta<EntityId> {
+    const state = this.players.get(player);
+    assert(state !== undefined, () => `${player} has no interest state`);
+    const {position, chunkRadius} = state!;
+    const radius = chunkRadius * this.chunkWidth;
+
+    const nearby = this.spatial.entitiesNear(
+        position[0], position[1], position[2], radius);
+    const entities: Set<EntityId> = new Set();
+    const entered: EntityId[] = [];
+    for (const entity of nearby) {
+      if (entity === player) continue;
+      entities.add(entity);
+      if (!state!.entities.has(entity)) entered.push(entity);
+    }
+
+    const left: EntityId[] = [];
+    for (const entity of state!.entities) {
+      if (!entities.has(entity)) left.push(entity);
+    }
+    state!.entities = entities;
+    return {entered, left};
+  }
+
+  removePlayer(player: EntityId) {
+    this.players.delete(player);
+  }
+
+  private getState(player: EntityId): PlayerInterest {
+    const result = this.players.get(player);
+    if (result) return result;
+    const state = {
+      position: Vec3.create(),
+      chunkRadius: 0,
+      chunks: new Map(),
+      entities: new Set<EntityId>(),
+    };
+    this.players.set(player, state);
+    return state;
+  }
+};
+
+//////////////////////////////////////////////////////////////////////////////
+
+export {ChunkKey, InterestDelta, InterestManager};
@@ -1,6 +1,8 @@
 import {assert, int, nonnull, Color, Vec3} from './base.js';
 import {BlockId, Env, init} from './engine.js';
-import {kEmptyBlock, kNoMaterial, kWorldHeight} from './engine.js';
+import {kBlockTagLog, kEmptyBlock, kNoMaterial, kWorldHeight} from './engine.js';
+import {kUnknownBlock} from './engine.js';
+import {kEventEntityLanded, kEventFluidFlowed} from './engine.js';
 import {Component, ComponentState, ComponentStore} from './ecs.js';
 import {EntityId, kNoEntity} from './ecs.js';
 import {AStar, Check, PathNode, Point as AStarPoint} from './pathing.js';
@@ -89,6 +91,7 @@ const flowWater = (env: TypedEnv, water: BlockId, points: Point[]) => {
     const block = env.getBlock(p[0], p[1], p[2]);
     if (block !== kEmptyBlock || !hasWaterNeighbor(env, water, p)) continue;
     env.setBlock(p[0], p[1], p[2], water);
+    env.pushEvent(kEventFluidFlowed, p[0], p[1], p[2], water);
     for (const d of kWaterDisplacements) {
       const n: Point = [int(p[0] - d[0]), int(p[1] - d[1]), int(p[2] - d[2])];
       const key = `${n[0]},${n[1]},${n[2]}`;
@@ -142,7 +145,7 @@ const Position: Component<PositionState> = {
 
 // An entity's physics state tracks its location and velocity, and allows
 // other systems to apply forces and impulses to it. It updates the entity's
-// AABB and keeps its position in sync.
+// AABB, keeps its position in sync, and keeps env.spatial up to date.
 
 interface PhysicsState {
   id: EntityId,
@@ -154,9 +157,11 @@ interface PhysicsState {
   impulses: Vec3,
   resting: Vec3,
   inFluid: boolean,
-  inGrass: boolean,
+  climbing: boolean,
+  slowdown: number,
   friction: number,
   restitution: number,
+  groundFriction: number,
   mass: number,
   autoStep: number,
   autoStepMax: number,
@@ -172,6 +177,7 @@ const kTmpMax = Vec3.create();
 const kTmpMin = Vec3.create();
 const kTmpPos = Vec3.create();
 const kTmpResting = Vec3.create();
+const kTmpSurface = {friction: 1, restitution: 0};
 
 const setPhysicsFromPosition = (a: PositionState, b: PhysicsState) => {
   Vec3.set(kTmpPos, a.x, a.y, a.z);
@@ -186,7 +192,8 @@ const setPositionFromPhysics = (a: PositionState, b: PhysicsState) => {
   a.z = (b.min[2] + b.max[2]) / 2;
 };
 
-const applyFriction = (axis: int, state: PhysicsState, dv: Vec3) => {
+const applyFriction =
+    (axis: int, state: PhysicsState, dv: Vec3, surface: number) => {
   const resting = state.resting[axis];
   if (resting === 0 || resting * dv[axis] <= 0) return;
 
@@ -195,7 +202,7 @@ const applyFriction = (axis: int, state: PhysicsState, dv: Vec3) => {
   const length = Vec3.length(kTmpFriction);
   if (length === 0) return;
 
-  const loss = Math.abs(state.friction * dv[axis]);
+  const loss = Math.abs(state.friction * surface * dv[axis]);
   const scale = length < loss ? 0 : (length - loss) / length;
   state.vel[(axis + 1) % 3] *= scale;
   state.vel[(axis + 2) % 3] *= scale;
@@ -259,6 +266,50 @@ const tryAutoStepping =
   Vec3.copy(state.resting, kTmpResting);
 };
 
+// In deterministic builds, we round physics state to a fixed-point grid after
+// each step, the same grid as the engine's Fixed type. Rounding alone doesn't
+// make physics deterministic: values that straddle a grid boundary still
+// diverge. Physics is deterministic because its step only uses +, -, *, /,
+// floor, round, min, and max, which JS computes exactly on every platform.
+// Lockstep peers must still feed it identical inputs, such as the inputs
+// they send each other, not values from platform-dependent math like Math.sin.
+const kFixedScale = 1 << 16;
+
+const quantize = (v: Vec3) => {
+  for (let i = 0; i < 3; i++) {
+    v[i] = Math.round(v[i] * kFixedScale) / kFixedScale;
+  }
+};
+
+// Blends the surface properties of the solid blocks under the AABB, weighted
+// by the area of its base that each one supports. Entities that aren't on
+// any solid block get the defaults: friction 1 and restitution 0.
+//
+// We probe just below the base, rather than the whole block below it, so
+// that an entity resting on a partial block, like a slab, finds the slab.
+const blendSurface = (env: TypedEnv, min: Vec3, max: Vec3) => {
+  const y = int(Math.floor(min[1] - 1 / kFixedScale));
+  let total = 0, friction = 0, restitution = 0;
+  for (let x = Math.floor(min[0]); x < max[0]; x++) {
+    const dx = Math.min(max[0], x + 1) - Math.max(min[0], x);
+    for (let z = Math.floor(min[2]); z < max[2]; z++) {
+      const dz = Math.min(max[2], z + 1) - Math.max(min[2], z);
+      const block = env.getBlock(int(x), y, int(z));
+      if (!env.registry.solid[block]) continue;
+      const weight = dx * dz;
+      total += weight;
+      friction += weight * env.registry.getFriction(block);
+      restitution += weight * env.registry.getRestitution(block);
+    }
+  }
+  kTmpSurface.friction = total > 0 ? friction / total : 1;
+  kTmpSurface.restitution = total > 0 ? restitution / total : 0;
+};
+
+const gravityScale = (state: PhysicsState): number => {
+  return state.inFluid ? 0.25 : 1;
+};
+
 const runPhysics = (env: TypedEnv, dt: number, state: PhysicsState) => {
   if (state.mass <= 0) return;
 
@@ -266,6 +317,7 @@ const runPhysics = (env: TypedEnv, dt: number, state: PhysicsState) => {
     const block = env.getBlock(x, y, z);
     return !env.registry.solid[block];
   };
+  const clear = (min: Vec3, max: Vec3) => !env.anySolidInBox(min, max);
 
   const {min, max} = state;
   const x = int(Math.floor((min[0] + max[0]) / 2));
@@ -275,21 +327,29 @@ const runPhysics = (env: TypedEnv, dt: number, state: PhysicsState) => {
   const block = env.getBlock(x, y, z);
   const mesh = env.registry.getBlockMesh(block);
   state.inFluid = block !== kEmptyBlock && mesh === null;
-  state.inGrass = block === nonnull(env.blocks).bush;
+  state.climbing = env.registry.isClimbable(block);
+
+  // When we're on the ground, the block we stand on can slow us, too. If
+  // both slow us, the stronger slowdown wins.
+  const below = state.resting[1] < 0 ? env.getBlock(x, int(y - 1), z) : block;
+  state.slowdown = Math.min(env.registry.getSlowdown(block),
+                            env.registry.getSlowdown(below));
 
   const drag = state.inFluid ? 2 : 0;
   const left = Math.max(1 - drag * dt, 0);
-  const gravity = state.inFluid ? 0.25 : 1;
+  const gravity = gravityScale(state);
 
   Vec3.scale(kTmpAcceleration, state.forces, 1 / state.mass);
   Vec3.scaleAndAdd(kTmpAcceleration, kTmpAcceleration, kTmpGravity, gravity);
   Vec3.scale(kTmpDelta, kTmpAcceleration, dt);
   Vec3.scaleAndAdd(kTmpDelta, kTmpDelta, state.impulses, 1 / state.mass);
   if (state.friction) {
+    // Only the ground's friction depends on its blocks, e.g. for ice.
+    const ground = state.resting[1] < 0 ? state.groundFriction : 1;
     Vec3.add(kTmpAcceleration, kTmpDelta, state.vel);
-    applyFriction(0, state, kTmpAcceleration);
-    applyFriction(1, state, kTmpAcceleration);
-    applyFriction(2, state, kTmpAcceleration);
+    applyFriction(0, state, kTmpAcceleration, 1);
+    applyFriction(1, state, kTmpAcceleration, ground);
+    applyFriction(2, state, kTmpAcceleration, 1);
   }
 
   if (state.autoStep) {
@@ -298,10 +358,18 @@ const runPhysics = (env: TypedEnv, dt: number, state: PhysicsState) => {
   }
 
   // Update our state based on the computations above.
+  const grounded = state.resting[1] < 0;
   Vec3.add(state.vel, state.vel, kTmpDelta);
   Vec3.scale(state.vel, state.vel, left);
   Vec3.scale(kTmpDelta, state.vel, dt);
-  sweep(state.min, state.max, kTmpDelta, state.resting, check);
+  const impact = int(Math.round(Math.max(-state.vel[1], 0)));
+  sweep(state.min, state.max, kTmpDelta, state.resting, check, false, clear);
+  if (!grounded && state.resting[1] < 0) {
+    const x = int(Math.floor((min[0] + max[0]) / 2));
+    const z = int(Math.floor((min[2] + max[2]) / 2));
+    const y = int(Math.floor(min[1]));
+    env.pushEvent(kEventEntityLanded, x, y, z, state.id, impact);
+  }
   Vec3.set(state.forces, 0, 0, 0);
   Vec3.set(state.impulses, 0, 0, 0);
 
@@ -309,9 +377,39 @@ const runPhysics = (env: TypedEnv, dt: number, state: PhysicsState) => {
     tryAutoStepping(env, dt, state, kTmpMin, kTmpMax, check);
   }
 
+  // We blend the ground's surface once a step, after the sweep. Bouncy
+  // ground, like slime, bounces entities now, even if they don't bounce,
+  // and slippery ground, like ice, sets their friction for the next step.
+  const floor = state.resting[1] < 0;
+  if (floor) blendSurface(env, min, max);
+  state.groundFriction = floor ? kTmpSurface.friction : 1;
   for (let i = 0; i < 3; i++) {
     if (state.resting[i] === 0) continue;
-    state.vel[i] = -state.restitution * state.vel[i];
+    const restitution = i === 1 && floor
+      ? Math.max(state.restitution, kTmpSurface.restitution)
+      : state.restitution;
+    state.vel[i] = -restitution * state.vel[i];
+  }
+
+  if (env.deterministic) {
+    quantize(state.min);
+    quantize(state.max);
+    quantize(state.vel);
+  }
+
+  notifyCollisions(env, state);
+};
+
+const notifyCollisions = (env: TypedEnv, state: PhysicsState) => {
+  const {min, max, resting} = state;
+  for (let i = 0; i < 3; i++) {
+    if (resting[i] === 0) continue;
+    for (let j = 0; j < 3; j++) kTmpPos[j] = (min[j] + max[j]) / 2;
+    kTmpPos[i] = resting[i] > 0 ? max[i] + 0.5 : min[i] - 0.5;
+    const x = int(Math.floor(kTmpPos[0]));
+    const y = int(Math.floor(kTmpPos[1]));
+    const z = int(Math.floor(kTmpPos[2]));
+    env.notifyEntityCollide(state.id, x, y, z);
   }
 };
 
@@ -326,17 +424,21 @@ const Physics = (env: TypedEnv): Component<PhysicsState> => ({
     impulses: Vec3.create(),
     resting: Vec3.create(),
     inFluid: false,
-    inGrass: false,
+    climbing: false,
+    slowdown: 1,
     friction: 0,
     restitution: 0,
+    groundFriction: 1,
     mass: 1,
     autoStep: 0.0625,
     autoStepMax: 0.5,
   }),
   onAdd: (state: PhysicsState) => {
     setPhysicsFromPosition(env.position.getX(state.id), state);
+    env.spatial.update(state.id, state.min, state.max);
   },
   onRemove: (state: PhysicsState) => {
+    env.spatial.remove(state.id);
     const position = env.position.get(state.id);
     if (position) setPositionFromPhysics(position, state);
   },
@@ -344,7 +446,9 @@ const Physics = (env: TypedEnv): Component<PhysicsState> => ({
     for (const state of states) {
       runPhysics(env, dt, state);
       setPositionFromPhysics(env.position.getX(state.id), state);
+      env.spatial.update(state.id, state.min, state.max);
     }
+    env.stepTriggers(states);
   },
 });
 
@@ -359,8 +463,9 @@ interface MovementState {
   hovering: boolean,
   maxSpeed: number,
   moveForce: number,
-  grassPenalty: number,
   waterPenalty: number,
+  climbSpeed: number,
+  slideSpeed: number,
   responsiveness: number,
   runningFriction: number,
   standingFriction: number,
@@ -377,10 +482,18 @@ interface MovementState {
 };
 
 const movementPenalty = (state: MovementState, body: PhysicsState): number => {
-  return body.inFluid ? state.waterPenalty :
-         body.inGrass ? state.grassPenalty : 1;
+  return body.slowdown * (body.inFluid ? state.waterPenalty : 1);
 }
 
+// While climbing, we cancel gravity and push the entity towards its climbing
+// speed: up if it's jumping, or a slow slide down otherwise.
+const handleClimbing = (state: MovementState, body: PhysicsState) => {
+  const speed = state.jumping ? state.climbSpeed : -state.slideSpeed;
+  const target = body.slowdown * speed;
+  const gravity = -kTmpGravity[1] * gravityScale(body) * body.mass;
+  body.forces[1] += gravity + state.responsiveness * (target - body.vel[1]);
+};
+
 const handleJumping = (dt: number, state: MovementState,
                        body: PhysicsState, grounded: boolean) => {
   if (state._jumped) {
@@ -537,8 +650,8 @@ const tryToModifyBlock =
   }
 };
 
-const runMovement = (env: TypedEnv, dt: number, state: MovementState) => {
-  const body = env.physics.getX(state.id);
+const runMovement =
+    (dt: number, state: MovementState, body: PhysicsState) => {
   const grounded = body.resting[1] < 0;
   if (grounded) state._jumpCount = 0;
 
@@ -547,12 +660,16 @@ const runMovement = (env: TypedEnv, dt: number, state: MovementState) => {
     body.forces[1] += force;
   }
 
-  if (state.jumping) {
+  if (body.climbing && !state.hovering) {
+    handleClimbing(state, body);
+    state._jumpCount = 0;
+    state._jumped = false;
+  } else if (state.jumping) {
     handleJumping(dt, state, body, grounded);
-    state.jumping = false;
   } else {
     state._jumped = false;
   }
+  state.jumping = false;
 
   if (state.inputX || state.inputZ) {
     handleRunning(dt, state, body, grounded);
@@ -573,8 +690,9 @@ const Movement = (env: TypedEnv): Component<MovementState> => ({
     hovering: false,
     maxSpeed: 7.5,
     moveForce: 30,
-    grassPenalty: 0.5,
     waterPenalty: 0.5,
+    climbSpeed: 3,
+    slideSpeed: 1.5,
     responsiveness: 15,
     runningFriction: 0,
     standingFriction: 2,
@@ -590,10 +708,215 @@ const Movement = (env: TypedEnv): Component<MovementState> => ({
     hoverRiseForce: 80,
   }),
   onUpdate: (dt: number, states: MovementState[]) => {
-    for (const state of states) runMovement(env, dt, state);
+    for (const state of states) {
+      runMovement(dt, state, env.physics.getX(state.id));
+    }
   }
 });
 
+// Client-side prediction. simulatePlayer runs one step of the movement and
+// physics solvers for a single player. It only depends on its arguments and
+// on the world's blocks, so the client and server compute the same results.
+//
+// The client simulates each input as soon as it's issued and keeps it until
+// the server acknowledges it. When the server sends a correction, reconcile
+// rewinds to the server's state and replays the unacknowledged inputs.
+
+interface PlayerInput {
+  sequence: int,
+  dt: number,
+  inputX: number,
+  inputZ: number,
+  jumping: boolean,
+  hovering: boolean,
+};
+
+interface PlayerSnapshot {
+  min: Vec3,
+  max: Vec3,
+  vel: Vec3,
+  resting: Vec3,
+  groundFriction: number,
+  jumped: boolean,
+  jumpCount: number,
+  jumpTimeLeft: number,
+};
+
+const simulatePlayer = (env: TypedEnv, input: PlayerInput,
+                        state: MovementState, body: PhysicsState) => {
+  const round = (x: number) => env.deterministic
+    ? Math.round(x * kFixedScale) / kFixedScale : x;
+  state.inputX = round(input.inputX);
+  state.inputZ = round(input.inputZ);
+  state.jumping = input.jumping;
+  state.hovering = input.hovering;
+  runMovement(input.dt, state, body);
+  runPhysics(env, input.dt, body);
+};
+
+const savePlayer =
+    (state: MovementState, body: PhysicsState): PlayerSnapshot => {
+  const copy = (x: Vec3) => Vec3.from(x[0], x[1], x[2]);
+  return {
+    min: copy(body.min),
+    max: copy(body.max),
+    vel: copy(body.vel),
+    resting: copy(body.resting),
+    groundFriction: body.groundFriction,
+    jumped: state._jumped,
+    jumpCount: state._jumpCount,
+    jumpTimeLeft: state._jumpTimeLeft,
+  };
+};
+
+const restorePlayer = (snapshot: PlayerSnapshot,
+                       state: MovementState, body: PhysicsState) => {
+  Vec3.copy(body.min, snapshot.min);
+  Vec3.copy(body.max, snapshot.max);
+  Vec3.copy(body.vel, snapshot.vel);
+  Vec3.copy(body.resting, snapshot.resting);
+  body.groundFriction = snapshot.groundFriction;
+  Vec3.set(body.forces, 0, 0, 0);
+  Vec3.set(body.impulses, 0, 0, 0);
+  state._jumped = snapshot.jumped;
+  state._jumpCount = snapshot.jumpCount;
+  state._jumpTimeLeft = snapshot.jumpTimeLeft;
+};
+
+class PlayerPrediction {
+  private pending: PlayerInput[] = [];
+
+  predict(env: TypedEnv, input: PlayerInput,
+          state: MovementState, body: PhysicsState) {
+    assert(this.pending.length === 0 ||
+           this.pending[this.pending.length - 1].sequence < input.sequence);
+    this.pending.push(input);
+    simulatePlayer(env, input, state, body);
+  }
+
+  // Resets to the server's state as of input `ack`, then replays the inputs
+  // that the server hasn't processed yet.
+  reconcile(env: TypedEnv, ack: int, snapshot: PlayerSnapshot,
+            state: MovementState, body: PhysicsState) {
+    let acked = 0;
+    while (acked < this.pending.length &&
+           this.pending[acked].sequence <= ack) {
+      acked++;
+    }
+    this.pending.splice(0, acked);
+
+    restorePlayer(snapshot, state, body);
+    for (const input of this.pending) {
+      simulatePlayer(env, input, state, body);
+    }
+  }
+};
+
+// Server-side movement validation. The server keeps the last state that it
+// accepted for each player and, for each input, runs the same solver as
+// simulatePlayer from that state. It then compares the client's claimed
+// state with its own, so cheats can't rely on differences in collision.
+//
+// Errors within the tolerances pass, to absorb floating-point drift in
+// non-deterministic builds. Otherwise, we report the most likely cheat:
+// noclip if the claimed box overlaps solid blocks, fly if it's too high,
+// speed if it's too far horizontally, and a plain mismatch otherwise.
+//
+// Before querying the world with the claimed box, we check that it's well
+// formed: finite, with min <= max, and the size of the player's box. If it
+// isn't, the verdict is invalid. If it touches unloaded chunks, where we
+// can't check for solid blocks, the verdict is unknown.
+
+const kMoveOk       = int(0);
+const kMoveNoclip   = int(1);
+const kMoveFly      = int(2);
+const kMoveSpeed    = int(3);
+const kMoveMismatch = int(4);
+const kMoveInvalid  = int(5);
+const kMoveUnknown  = int(6);
+
+interface MoveTolerance {
+  position: number,
+  velocity: number,
+};
+
+interface MoveVerdict {
+  verdict: int,
+  error: number,
+  expected: PlayerSnapshot,
+};
+
+const kDefaultMoveTolerance: MoveTolerance = {position: 0.05, velocity: 0.5};
+
+// Leaves the state and body at the server's result, which is also returned
+// in the verdict for the server to send back as a correction.
+const validateMove =
+    (env: TypedEnv, prev: PlayerSnapshot, claimed: PlayerSnapshot,
+     input: PlayerInput, state: MovementState, body: PhysicsState,
+     tolerance: MoveTolerance = kDefaultMoveTolerance): MoveVerdict => {
+  restorePlayer(prev, state, body);
+  simulatePlayer(env, input, state, body);
+  const expected = savePlayer(state, body);
+
+  let valid = true;
+  for (let i = 0; i < 3; i++) {
+    const {min, max, vel} = claimed;
+    const size = max[i] - min[i];
+    const expectedSize = expected.max[i] - expected.min[i];
+    const finite = isFinite(min[i]) && isFinite(max[i]) && isFinite(vel[i]);
+    if (!finite || !(min[i] <= max[i]) ||
+        !(Math.abs(size - expectedSize) <= tolerance.position)) {
+      valid = false;
+    }
+  }
+  if (!valid) return {verdict: kMoveInvalid, error: Infinity, expected};
+
+  // The box is smaller than a chunk, so its corners cover every chunk it
+  // touches. At y = 0, getBlock only returns unknown in unloaded chunks.
+  let unloaded = false;
+  for (let i = 0; i < 4; i++) {
+    const x = int(Math.floor(i & 1 ? claimed.max[0] : claimed.min[0]));
+    const z = int(Math.floor(i & 2 ? claimed.max[2] : claimed.min[2]));
+    if (env.getBlock(x, 0, z) === kUnknownBlock) unloaded = true;
+  }
+
+  const epsilon = 1 / kFixedScale;
+  Vec3.set(kTmpMin, epsilon, epsilon, epsilon);
+  Vec3.add(kTmpMin, claimed.min, kTmpMin);
+  Vec3.set(kTmpMax, epsilon, epsilon, epsilon);
+  Vec3.sub(kTmpMax, claimed.max, kTmpMax);
+  const noclip = !unloaded && env.anySolidInBox(kTmpMin, kTmpMax);
+
+  let error = 0;
+  for (let i = 0; i < 3; i++) {
+    error = Math.max(error, Math.abs(claimed.min[i] - expected.min[i]));
+    error = Math.max(error, Math.abs(claimed.max[i] - expected.max[i]));
+  }
+  let speed = 0;
+  for (let i = 0; i < 3; i++) {
+    speed = Math.max(speed, Math.abs(claimed.vel[i] - expected.vel[i]));
+  }
+
+  const moved = (snapshot: PlayerSnapshot, i: int) =>
+      Math.abs(snapshot.min[i] - prev.min[i]);
+  const horizontal = (snapshot: PlayerSnapshot) =>
+      Math.sqrt(moved(snapshot, 0) ** 2 + moved(snapshot, 2) ** 2);
+  const rise = claimed.min[1] - expected.min[1];
+  const extra = horizontal(claimed) - horizontal(expected);
+
+  const verdict = (() => {
+    if (unloaded) return kMoveUnknown;
+    if (noclip) return kMoveNoclip;
+    if (rise > tolerance.position) return kMoveFly;
+    if (extra > tolerance.position) return kMoveSpeed;
+    if (error > tolerance.position || speed > tolerance.velocity) {
+      return kMoveMismatch;
+    }
+    return kMoveOk;
+  })();
+  return {verdict, error, expected};
+};
+
 // An entity with an input component processes inputs.
 
 interface InputState {
@@ -1119,8 +1442,10 @@ const addEntity = (env: TypedEnv, image: string, size: number,
   const position = env.position.add(entity);
   position.x = x + 0.5;
   position.z = z + 0.5;
-  position.w = w;
-  position.h = h;
+  // Sizes are in render units; collision boxes are in voxel units.
+  const scale = env.renderer.camera.scale;
+  position.w = w / scale[0];
+  position.h = h / scale[1];
   position.y = safeHeight(env, position);
 
   const movement = env.movement.add(entity);
@@ -1202,6 +1527,10 @@ const main = () => {
     trunk:   registry.addBlock(['trunk', 'trunk-side'], true),
     water:   registry.addBlock(['water', 'blue', 'blue'], false),
   };
+  registry.setBlockMovement(blocks.bush, false, 0.5);
+  registry.setBlockSpread(blocks.grass, blocks.dirt, blocks.dirt, 9);
+  registry.setBlockTags(blocks.trunk, kBlockTagLog);
+  registry.setBlockAttenuation(blocks.water, 1);
 
   env.blocks = blocks;
   env.refresh();
@@ -1211,4 +1540,8 @@ const main = () => {
 
 init(main);
 
-export {};
+export {PlayerInput, PlayerPrediction, PlayerSnapshot};
+export {MoveTolerance, MoveVerdict, validateMove};
+export {kMoveFly, kMoveMismatch, kMoveNoclip, kMoveOk, kMoveSpeed};
+export {kMoveInvalid, kMoveUnknown};
+export {savePlayer, simulatePlayer};
@@ -12,6 +12,7 @@ interface CullingPlane {
 
 const kTmpDelta = Vec3.create();
 const kTmpPlane = Vec3.create();
+const kZeroVec3 = Vec3.create();
 
 class Camera {
   heading = 0; // In radians: [0, 2π)
@@ -22,6 +23,17 @@ class Camera {
   position: Vec3;
   target: Vec3;
 
+  // The render origin: engine meshes' positions are relative to it. See
+  // Env.rebaseOrigin.
+  origin: Vec3;
+
+  // The size of a voxel in render space, e.g. (1, 0.5, 1) for half-height
+  // voxels. Everything outside the renderer works in voxel units; we scale
+  // positions and geometry when we build transforms. voxel_direction is our
+  // view direction in voxel units, for raycasts and for placing the camera.
+  scale: Vec3;
+  voxel_direction: Vec3;
+
   // Used to smooth out mouse inputs.
   private last_dx: number;
   private last_dy: number;
@@ -42,6 +54,9 @@ class Camera {
     this.direction = Vec3.from(0, 0, 1);
     this.position = Vec3.create();
     this.target = Vec3.create();
+    this.origin = Vec3.create();
+    this.scale = Vec3.from(1, 1, 1);
+    this.voxel_direction = Vec3.from(0, 0, 1);
 
     this.last_dx = 0;
     this.last_dy = 0;
@@ -96,6 +111,7 @@ class Camera {
     Vec3.set(dir, 0, 0, 1);
     Vec3.rotateX(dir, dir, this.pitch);
     Vec3.rotateY(dir, dir, this.heading);
+    this.updateVoxelDirection();
 
     // Scrolling is trivial to apply: add and clamp.
     if (dscroll === 0) return;
@@ -120,16 +136,20 @@ class Camera {
   }
 
   getTransform(): Mat4 {
-    Mat4.view(this.view, this.position, this.direction);
-    Mat4.multiply(this.transform, this.projection, this.view);
-    return this.transform;
+    Vec3.copy(kTmpDelta, this.position);
+    return this.getScaledTransform(this.transform, kTmpDelta);
   }
 
-  getTransformFor(offset: Vec3): Mat4 {
+  // If relative is set, offset is relative to the render origin.
+  getTransformFor(offset: Vec3, relative: boolean = false): Mat4 {
     Vec3.sub(kTmpDelta, this.position, offset);
-    Mat4.view(this.view, kTmpDelta, this.direction);
-    Mat4.multiply(this.transform_for, this.projection, this.view);
-    return this.transform_for;
+    if (relative) Vec3.sub(kTmpDelta, kTmpDelta, this.origin);
+    return this.getScaledTransform(this.transform_for, kTmpDelta);
+  }
+
+  setVoxelScale(x: number, y: number, z: number) {
+    Vec3.set(this.scale, x, y, z);
+    this.updateVoxelDirection();
   }
 
   setMinZ(minZ: number) {
@@ -140,11 +160,28 @@ class Camera {
 
   setSafeZoomDistance(bump: number, zoom: number) {
     zoom = Math.max(Math.min(zoom, this.zoom), 0);
-    Vec3.scaleAndAdd(this.position, this.target, this.direction, -zoom);
+    Vec3.scaleAndAdd(this.position, this.target, this.voxel_direction, -zoom);
     this.position[1] += bump;
     this.safe_zoom = zoom;
   }
 
+  // Computes projection * view * scale, for a camera at the given position
+  // in voxel units, and writes it to the result.
+  private getScaledTransform(result: Mat4, position: Vec3): Mat4 {
+    const scale = this.scale;
+    for (let i = 0; i < 3; i++) position[i] *= scale[i];
+    Mat4.view(this.view, position, this.direction);
+    Mat4.multiply(result, this.projection, this.view);
+    for (let i = 0; i < 12; i++) result[i] *= scale[i >> 2];
+    return result;
+  }
+
+  private updateVoxelDirection() {
+    const {direction, scale, voxel_direction} = this;
+    for (let i = 0; i < 3; i++) voxel_direction[i] = direction[i] / scale[i];
+    Vec3.normalize(voxel_direction, voxel_direction);
+  }
+
   setTarget(x: number, y: number, z: number) {
     Vec3.set(this.target, x, y, z);
   }
@@ -167,10 +204,12 @@ class Shader {
   private uniforms: Map<string, Uniform>;
   private attributes: Map<string, Attribute>;
 
-  constructor(gl: WebGL2RenderingContext, source: string) {
+  // The header goes before the vertex shader's precision statements. It's
+  // where #extension directives must go.
+  constructor(gl: WebGL2RenderingContext, source: string, header = '') {
     this.gl = gl;
     const parts = source.split('#split');
-    const vertex = this.compile(parts[0], gl.VERTEX_SHADER);
+    const vertex = this.compile(parts[0], gl.VERTEX_SHADER, header);
     const fragment = this.compile(parts[1], gl.FRAGMENT_SHADER);
     this.program = this.link(vertex, fragment);
     this.uniforms = new Map();
@@ -212,10 +251,12 @@ class Shader {
     return name.startsWith('gl_') || name.startsWith('webgl_');
   }
 
-  private compile(source: string, type: number): WebGLShader {
+  private compile(source: string, type: number,
+                  header = ''): WebGLShader {
     const gl = this.gl;
     const result = nonnull(gl.createShader(type));
     gl.shaderSource(result, `#version 300 es
+                             ${header}
                              precision highp float;
                              precision highp sampler2DArray;
                              precision highp sampler3D;
@@ -257,12 +298,25 @@ interface Texture {
   h: int,
 };
 
+// Quads refer to textures by 8-bit texture ids, which we map to atlas slots
+// in the voxel shader. Swapping a resource pack loads new slots and remaps
+// the ids, without remeshing. The map packs two 16-bit slots into each uint.
+//
+// Texture ids may also map to a normal-map slot, stored the same way, but
+// offset by 1 so that 0 means "no normal map". Normal maps use the DirectX
+// convention: green points toward the bottom of the image, along +v.
+const kMaxTextureIds = 256;
+
 class TextureAtlas {
+  mapping: Uint32Array;
+  normals: Uint32Array;
+  mappingDirty: boolean;
   private gl: WebGL2RenderingContext;
   private texture: WebGLTexture;
   private canvas: CanvasRenderingContext2D | null;
   private images: Map<string, HTMLImageElement>;
   private nextResult: int;
+  private nextTextureId: int;
   private data: Uint8Array;
   private sparkle_data: Uint8Array;
   private sparkle_last: Uint8Array;
@@ -274,6 +328,10 @@ class TextureAtlas {
     this.canvas = null;
     this.images = new Map();
     this.nextResult = 0;
+    this.nextTextureId = 0;
+    this.mapping = new Uint32Array(kMaxTextureIds / 2);
+    this.normals = new Uint32Array(kMaxTextureIds / 2);
+    this.mappingDirty = true;
     this.data = new Uint8Array();
     this.sparkle_data = new Uint8Array();
     this.sparkle_last = new Uint8Array();
@@ -285,7 +343,41 @@ class TextureAtlas {
     gl.texParameteri(id, gl.TEXTURE_MIN_FILTER, gl.NEAREST_MIPMAP_LINEAR);
   }
 
+  // Loads the texture into a new atlas slot and returns a new texture id
+  // that maps to it.
   addTexture(texture: Texture): int {
+    const id = int(this.nextTextureId++);
+    assert(id < kMaxTextureIds, () => 'Too many textures!');
+    this.setTextureMapping(id, this.loadTexture(texture));
+    return id;
+  }
+
+  getTextureMapping(id: int): int {
+    assert(0 <= id && id < kMaxTextureIds);
+    return int((this.mapping[id >> 1] >> (16 * (id & 1))) & 0xffff);
+  }
+
+  setTextureMapping(id: int, slot: int): void {
+    assert(0 <= id && id < kMaxTextureIds);
+    assert(0 <= slot && slot < this.nextResult);
+    const shift = 16 * (id & 1);
+    const rest = this.mapping[id >> 1] & ~(0xffff << shift);
+    this.mapping[id >> 1] = rest | (slot << shift);
+    this.mappingDirty = true;
+  }
+
+  // Pass a slot of -1 to remove the texture id's normal map.
+  setNormalMapping(id: int, slot: int): void {
+    assert(0 <= id && id < kMaxTextureIds);
+    assert(-1 <= slot && slot < this.nextResult && slot < 0xffff);
+    const shift = 16 * (id & 1);
+    const rest = this.normals[id >> 1] & ~(0xffff << shift);
+    this.normals[id >> 1] = rest | ((slot + 1) << shift);
+    this.mappingDirty = true;
+  }
+
+  // Loads the texture into a new atlas slot and returns the slot.
+  loadTexture(texture: Texture): int {
     const index = int(this.nextResult++);
     const image = this.image(texture.url);
     if (image.complete) {
@@ -302,6 +394,54 @@ class TextureAtlas {
     gl.bindTexture(TEXTURE_2D_ARRAY, this.texture);
   }
 
+  // Rasterizes an isometric view of a cube into a size x size RGBA image.
+  // textures are the texture ids for the [top, left, right] faces, or -1 to
+  // leave a face out. We shade the side faces like the voxel shader's AO.
+  renderIcon(textures: int[], size: int): Uint8ClampedArray {
+    assert(textures.length === 3);
+    const result = new Uint8ClampedArray(4 * size * size);
+    if (!this.canvas) return result;
+
+    const cell = this.canvas.canvas.width;
+    const length = cell * cell * 4;
+    const shades = [1.0, 0.8, 0.6];
+
+    for (let py = 0; py < size; py++) {
+      for (let px = 0; px < size; px++) {
+        // Screen-space coordinates of the pixel's center, scaled to [0, 1].
+        const x = (px + 0.5) / size, y = (py + 0.5) / size;
+
+        // Project onto each face in turn. (a, b) are texture coordinates.
+        let face = -1, a = 0, b = 0;
+        const u = 2 * y + x - 0.5, v = 2 * y - x + 0.5;
+        if (0 <= u && u < 1 && 0 <= v && v < 1) {
+          face = 0; a = u; b = v;
+        } else if (x < 0.5) {
+          face = 1; a = 2 * x; b = 2 * y - 0.5 - 0.5 * a;
+        } else {
+          face = 2; a = 2 * x - 1; b = 2 * y - 1 + 0.5 * a;
+        }
+        if (!(0 <= b && b < 1)) continue;
+
+        const id = textures[face];
+        const index = id < 0 ? -1 : this.getTextureMapping(id);
+        const offset = length * index;
+        if (index < 0 || this.data.length < offset + length) continue;
+
+        const i = Math.min(Math.floor(b * cell), cell - 1);
+        const j = Math.min(Math.floor(a * cell), cell - 1);
+        const source = offset + 4 * (i * cell + j);
+        const target = 4 * (py * size + px);
+        const shade = shades[face];
+        result[target + 0] = shade * this.data[source + 0];
+        result[target + 1] = shade * this.data[source + 1];
+        result[target + 2] = shade * this.data[source + 2];
+        result[target + 3] = this.data[source + 3];
+      }
+    }
+    return result;
+  }
+
   sparkle(): void {
     if (!this.canvas) return;
     if (this.sparkle_indices.length === 0) return;
@@ -535,6 +675,18 @@ class SpriteAtlas {
 
 //////////////////////////////////////////////////////////////////////////////
 
+// Triangles of a box whose corners are indexed as in Geometry.getBounds(),
+// with bit 0 set for max-x, bit 1 for max-y, and bit 2 for max-z. Triangles
+// are wound counter-clockwise when viewed from outside of the box.
+const kBoxTriangles = [
+  0, 4, 6, 0, 6, 2, // -x
+  1, 3, 7, 1, 7, 5, // +x
+  0, 1, 5, 0, 5, 4, // -y
+  2, 6, 7, 2, 7, 3, // +y
+  0, 2, 3, 0, 3, 1, // -z
+  4, 5, 7, 4, 7, 6, // +z
+];
+
 class Geometry {
   // struct Quad {
   //   // int 0
@@ -556,7 +708,7 @@ class Geometry {
   //   uint8_t wave:    4; // 4 x 1-bit wave flags
   //   uint8_t dim:     2;
   //   uint8_t dir:     1;
-  //   uint8_t lit:     1;
+  //   uint8_t bright:  1; // full-bright: no AO or lighting
   // };
   static StrideInInt32: int = 4;
   static StrideInBytes: int = 16;
@@ -567,6 +719,7 @@ class Geometry {
   private lower_bound: Vec3;
   private upper_bound: Vec3;
   private bounds: Float64Array;
+  private bounding_mesh: Float32Array;
 
   constructor(quads: Int32Array, num_quads: int) {
     this.quads = quads;
@@ -574,6 +727,7 @@ class Geometry {
     this.lower_bound = Vec3.create();
     this.upper_bound = Vec3.create();
     this.bounds = new Float64Array(24);
+    this.bounding_mesh = new Float32Array(3 * kBoxTriangles.length);
     this.dirty = true;
   }
 
@@ -597,6 +751,14 @@ class Geometry {
     return this.bounds;
   }
 
+  // Returns the 12 triangles of the tight bounding box of this geometry, as
+  // an unindexed list of (x, y, z) positions relative to the mesh position.
+  // The renderer can draw it for occlusion queries or software depth tests.
+  getBoundingMesh(): Float32Array {
+    if (this.dirty) this.computeBounds();
+    return this.bounding_mesh;
+  }
+
   private computeBounds() {
     if (!this.dirty) return this.bounds;
     const {bounds, lower_bound, upper_bound} = this;
@@ -640,6 +802,14 @@ class Geometry {
         bounds[offset + j] = (i & (1 << j)) ? upper_bound[j] : lower_bound[j];
       }
     }
+
+    const bounding_mesh = this.bounding_mesh;
+    for (let i = 0; i < kBoxTriangles.length; i++) {
+      const corner = 3 * kBoxTriangles[i];
+      for (let j = 0; j < 3; j++) {
+        bounding_mesh[3 * i + j] = bounds[corner + j];
+      }
+    }
     this.dirty = false;
   }
 
@@ -743,13 +913,18 @@ class BufferAllocator {
   }
 };
 
+// x and z are the world-space position of the chunk's (0, 0, 0) voxel.
 class LightTexture {
   texture: WebGLTexture;
+  x: int;
+  z: int;
   private allocator: TextureAllocator;
 
-  constructor(data: Uint8Array, allocator: TextureAllocator) {
+  constructor(data: Uint8Array, x: int, z: int, allocator: TextureAllocator) {
     this.allocator = allocator;
     this.texture = this.allocator.alloc(data);
+    this.x = x;
+    this.z = z;
   }
 
   dispose(): void {
@@ -827,12 +1002,15 @@ class Mesh<S> {
     this.addToMeshes();
   }
 
-  cull(bounds: Float64Array, camera: Camera, planes: CullingPlane[]): boolean {
+  cull(bounds: Float64Array, camera: Camera, planes: CullingPlane[],
+       relative: boolean = false): boolean {
     const position = this.position;
     const camera_position = camera.position;
-    const dx = position[0] - camera_position[0];
-    const dy = position[1] - camera_position[1];
-    const dz = position[2] - camera_position[2];
+    const origin = relative ? camera.origin : kZeroVec3;
+    const dx = position[0] + origin[0] - camera_position[0];
+    const dy = position[1] + origin[1] - camera_position[1];
+    const dz = position[2] + origin[2] - camera_position[2];
+    const [sx, sy, sz] = camera.scale;
 
     for (const plane of planes) {
       const {x, y, z, index} = plane;
@@ -840,7 +1018,8 @@ class Mesh<S> {
       const bx = bounds[offset + 0];
       const by = bounds[offset + 1];
       const bz = bounds[offset + 2];
-      const value = (bx + dx) * x + (by + dy) * y + (bz + dz) * z;
+      const value = (bx + dx) * sx * x + (by + dy) * sy * y +
+                    (bz + dz) * sz * z;
       if (value < 0) return true;
     }
     return false;
@@ -880,11 +1059,25 @@ class Mesh<S> {
 
 //////////////////////////////////////////////////////////////////////////////
 
+// The most draws in one multi-draw call. The voxel shader has a mask uniform
+// per draw, so this number is bounded by its vertex uniform budget.
+const kMaxMultiDraw = 64;
+
 const kVoxelShader = `
   uniform ivec2 u_mask;
+#ifdef GL_ANGLE_multi_draw
+  // In a multi-draw of arena ranges, each draw's mask is in u_masks instead.
+  uniform int u_multiDraw;
+  uniform ivec2 u_masks[${kMaxMultiDraw}];
+#endif
+  // The render origin minus the light texture's world-space position, which
+  // converts render-space positions to the chunk-relative ones lighting uses.
+  uniform ivec2 u_lightOffset;
   uniform float u_move;
   uniform float u_wave;
   uniform mat4 u_transform;
+  uniform uvec4 u_textureMap[32];
+  uniform uvec4 u_normalMap[32];
 
   in ivec3 a_pos;
   in ivec2 a_size;
@@ -892,7 +1085,9 @@ const kVoxelShader = `
   in uint  a_ao;
   in uint  a_mask;
   in uint  a_texture;
-  // 4-bit wave; 2-bit dim; 1-bit dir
+  // 4-bit wave; 2-bit dim; 1-bit dir; 1-bit full-bright. (dim, dir) is the
+  // quad's face id: we look up its normal below, rather than streaming
+  // normals per vertex.
   in int   a_wddl;
 
   out vec3 v_pos;
@@ -900,6 +1095,10 @@ const kVoxelShader = `
   out float v_ao;
   out float v_move;
   flat out int v_dim;
+  flat out int v_bright;
+  flat out int v_normalSlot;
+  flat out vec3 v_normal;
+  flat out vec4 v_tangent;
 
   int unpackI2(uint packed, int index) {
     return (int(packed) >> (2 * index)) & 3;
@@ -916,7 +1115,13 @@ const kVoxelShader = `
     float w = float(((index + 1) & 3) >> 1);
     float h = float(((index + 0) & 3) >> 1);
 
-    v_uvw = vec3(0.0, 0.0, float(a_texture));
+    int id = int(a_texture);
+    uint slots = u_textureMap[id >> 3][(id >> 1) & 3];
+    int slot = int((slots >> (16 * (id & 1))) & 0xffffu);
+    uint normals = u_normalMap[id >> 3][(id >> 1) & 3];
+    v_normalSlot = int((normals >> (16 * (id & 1))) & 0xffffu) - 1;
+
+    v_uvw = vec3(0.0, 0.0, float(slot));
     const float kTextureBuffer = 0.01;
     if (dim == 2) {
       v_uvw[0] = (float(a_size[0]) - kTextureBuffer) * w * -dir;
@@ -926,6 +1131,37 @@ const kVoxelShader = `
       v_uvw[1] = (float(a_size[0]) - kTextureBuffer) * (1.0 - w);
     }
 
+    // The material's UV transform: flip u, then rotate counterclockwise.
+    // Textures repeat, so we can rotate about the origin.
+    int uv_transform = int(a_indices) >> 12;
+    if ((uv_transform & 4) != 0) v_uvw[0] = -v_uvw[0];
+    int rotation = uv_transform & 3;
+    vec2 uv = v_uvw.xy;
+    if (rotation == 1) v_uvw.xy = vec2(-uv[1], uv[0]);
+    if (rotation == 2) v_uvw.xy = -uv;
+    if (rotation == 3) v_uvw.xy = vec2(uv[1], -uv[0]);
+
+    // Tangent frame for normal mapping: T and B are the directions in which
+    // u and v increase, so they follow the same branches as the UVs above.
+    // The handedness in v_tangent.w is the sign of dot(cross(N, T), B).
+    vec3 t = vec3(0.0), b = vec3(0.0);
+    if (dim == 2) {
+      t[(dim + 1) % 3] = -dir;
+      b[(dim + 2) % 3] = -1.0;
+    } else {
+      t[(dim + 2) % 3] = dir;
+      b[(dim + 1) % 3] = -1.0;
+    }
+    if ((uv_transform & 4) != 0) t = -t;
+    vec3 tb = t;
+    if (rotation == 1) { t = -b; b = tb; }
+    if (rotation == 2) { t = -t; b = -b; }
+    if (rotation == 3) { t = b; b = -tb; }
+    v_normal = vec3(0.0);
+    v_normal[dim] = dir;
+    float handedness = dot(cross(v_normal, t), b) < 0.0 ? -1.0 : 1.0;
+    v_tangent = vec4(t, handedness);
+
     float wave = float((a_wddl >> index) & 0x1);
     v_move = wave * u_move;
 
@@ -936,19 +1172,28 @@ const kVoxelShader = `
     gl_Position = u_transform * vec4(pos, 1.0);
 
     v_dim = dim;
+    v_bright = (a_wddl & 128) != 0 ? 1 : 0;
     v_pos = pos;
+    v_pos[0] += float(u_lightOffset[0]);
+    v_pos[2] += float(u_lightOffset[1]);
     v_pos[dim] += 0.5 * dir;
 
     int mask = int(a_mask);
     int mask_index = mask >> 5;
     int mask_value = 1 << (mask & 31);
-    bool hide = (u_mask[mask_index] & mask_value) != 0;
+#ifdef GL_ANGLE_multi_draw
+    ivec2 masks = u_multiDraw != 0 ? u_masks[gl_DrawID] : u_mask;
+#else
+    ivec2 masks = u_mask;
+#endif
+    bool hide = (masks[mask_index] & mask_value) != 0;
     if (hide) gl_Position[3] = 0.0;
   }
 #split
   uniform float u_alphaTest;
   uniform vec3 u_fogColor;
   uniform float u_fogDepth;
+  uniform float u_skyDarkness;
   uniform int u_hasLight;
   uniform sampler2DArray u_texture;
   uniform sampler3D u_light;
@@ -958,13 +1203,37 @@ const kVoxelShader = `
   in float v_ao;
   in float v_move;
   flat in int v_dim;
+  flat in int v_bright;
+  flat in int v_normalSlot;
+  flat in vec3 v_normal;
+  flat in vec4 v_tangent;
 
   out vec4 o_color;
 
+  // Normal maps only perturb the light relative to the unmapped face, so
+  // that blocks without one are lit the same as before.
+  const vec3 kBumpLight = vec3(0.3, 0.9, 0.3);
+  const float kBumpAmbient = 0.5;
+
+  float getBumpFactor(vec2 uv) {
+    if (v_normalSlot < 0) return 1.0;
+    vec3 texel = texture(u_texture, vec3(uv, float(v_normalSlot))).xyz;
+    vec3 n = 2.0 * texel - 1.0;
+    vec3 b = v_tangent.w * cross(v_normal, v_tangent.xyz);
+    vec3 normal = normalize(n.x * v_tangent.xyz + n.y * b + n.z * v_normal);
+    vec3 light = normalize(kBumpLight);
+    float base = kBumpAmbient + max(dot(v_normal, light), 0.0);
+    return (kBumpAmbient + max(dot(normal, light), 0.0)) / base;
+  }
+
+  // Texels hold the light level in their low 4 bits and the part of it due
+  // to block light alone in their high 4 bits, so we can dim the sky.
   float getLightTexel(ivec3 pos) {
     if (pos[0] < 0) return 0.0;
-    if (pos[0] >= 0xff) return 15.0;
-    return round(256.0 * texelFetch(u_light, pos, 0)[0]);
+    if (pos[0] >= 0xff) return 15.0 - u_skyDarkness;
+    float texel = round(255.0 * texelFetch(u_light, pos, 0)[0]);
+    float block = floor(texel / 16.0);
+    return max(block, texel - 16.0 * block - u_skyDarkness);
   }
 
   float getLightLevel() {
@@ -1018,7 +1287,9 @@ const kVoxelShader = `
     float fog = clamp(exp2(-depth * depth), 0.0, 1.0);
 
     vec3 index = v_uvw + vec3(v_move, v_move, 0.0);
-    vec4 color = vec4(vec3(light * v_ao), 1.0) * texture(u_texture, index);
+    light *= getBumpFactor(index.xy) * v_ao;
+    if (v_bright != 0) light = 1.0;
+    vec4 color = vec4(vec3(light), 1.0) * texture(u_texture, index);
     o_color = mix(color, vec4(u_fogColor, color[3]), fog);
     if (o_color[3] < 0.5 * u_alphaTest) discard;
   }
@@ -1026,12 +1297,18 @@ const kVoxelShader = `
 
 class VoxelShader extends Shader {
   u_mask:      WebGLUniformLocation | null;
+  u_masks:     WebGLUniformLocation | null;
+  u_multiDraw: WebGLUniformLocation | null;
   u_move:      WebGLUniformLocation | null;
+  u_lightOffset: WebGLUniformLocation | null;
   u_wave:      WebGLUniformLocation | null;
   u_transform: WebGLUniformLocation | null;
+  u_textureMap: WebGLUniformLocation | null;
+  u_normalMap: WebGLUniformLocation | null;
   u_alphaTest: WebGLUniformLocation | null;
   u_fogColor:  WebGLUniformLocation | null;
   u_fogDepth:  WebGLUniformLocation | null;
+  u_skyDarkness: WebGLUniformLocation | null;
   u_hasLight:  WebGLUniformLocation | null;
   u_light:     WebGLUniformLocation | null;
 
@@ -1043,15 +1320,22 @@ class VoxelShader extends Shader {
   a_texture: number | null;
   a_wddl:    number | null;
 
-  constructor(gl: WebGL2RenderingContext) {
-    super(gl, kVoxelShader);
+  constructor(gl: WebGL2RenderingContext, multi_draw: boolean) {
+    const header = multi_draw ? '#extension GL_ANGLE_multi_draw : require' : '';
+    super(gl, kVoxelShader, header);
     this.u_mask      = this.getUniformLocation('u_mask');
+    this.u_masks     = this.getUniformLocation('u_masks[0]');
+    this.u_multiDraw = this.getUniformLocation('u_multiDraw');
     this.u_move      = this.getUniformLocation('u_move');
+    this.u_lightOffset = this.getUniformLocation('u_lightOffset');
     this.u_wave      = this.getUniformLocation('u_wave');
     this.u_transform = this.getUniformLocation('u_transform');
+    this.u_textureMap = this.getUniformLocation('u_textureMap');
+    this.u_normalMap = this.getUniformLocation('u_normalMap');
     this.u_alphaTest = this.getUniformLocation('u_alphaTest');
     this.u_fogColor  = this.getUniformLocation('u_fogColor');
     this.u_fogDepth  = this.getUniformLocation('u_fogDepth');
+    this.u_skyDarkness = this.getUniformLocation('u_skyDarkness');
     this.u_hasLight  = this.getUniformLocation('u_hasLight');
     this.u_light     = this.getUniformLocation('u_light');
 
@@ -1065,11 +1349,294 @@ class VoxelShader extends Shader {
   }
 };
 
+const prepareVoxelAttribute =
+    (gl: WebGL2RenderingContext, location: number | null,
+     type: number, size: int, offset: number) => {
+  if (location === null) return;
+  const stride = Geometry.StrideInBytes;
+  gl.enableVertexAttribArray(location);
+  gl.vertexAttribIPointer(location, size, type, stride, offset);
+  gl.vertexAttribDivisor(location, 2);
+};
+
+const prepareVoxelAttributes =
+    (gl: WebGL2RenderingContext, shader: VoxelShader, base: number) => {
+  const {BYTE, SHORT, UNSIGNED_BYTE: UBYTE, UNSIGNED_SHORT: USHORT} = gl;
+  prepareVoxelAttribute(gl, shader.a_pos,     SHORT,  3, base + 0);
+  prepareVoxelAttribute(gl, shader.a_indices, USHORT, 1, base + 6);
+  prepareVoxelAttribute(gl, shader.a_size,    SHORT,  2, base + 8);
+  prepareVoxelAttribute(gl, shader.a_mask,    UBYTE,  1, base + 12);
+  prepareVoxelAttribute(gl, shader.a_texture, UBYTE,  1, base + 13);
+  prepareVoxelAttribute(gl, shader.a_ao,      UBYTE,  1, base + 14);
+  prepareVoxelAttribute(gl, shader.a_wddl,    BYTE,   1, base + 15);
+};
+
+//////////////////////////////////////////////////////////////////////////////
+
+class ArenaRange {
+  offset: int = 0; // in quads
+  count: int = 0;  // in quads
+  index: int = -1; // in the arena's list of live ranges
+};
+
+// Not yet in lib.dom.d.ts. See the WebGL extension registry.
+interface BaseInstanceExtension {
+  drawArraysInstancedBaseInstanceWEBGL(
+      mode: number, first: int, count: int,
+      instances: int, base: int): void;
+};
+
+interface MultiDrawBaseInstanceExtension {
+  multiDrawArraysInstancedBaseInstanceWEBGL(
+      mode: number, firsts: Int32Array, firstsOffset: int,
+      counts: Int32Array, countsOffset: int,
+      instances: Int32Array, instancesOffset: int,
+      bases: Uint32Array, basesOffset: int, drawCount: int): void;
+};
+
+// In arena mode, all voxel meshes share a single large quad buffer. Each mesh
+// owns an (offset, count) range of it. Freed ranges become holes, sorted by
+// offset, which we merge with their neighbors; a hole that reaches the end of
+// the used space shrinks it instead. When we run out of room, we compact every
+// live range into a new buffer (growing it if necessary), and callers may
+// defragment directly.
+//
+// The arena's vertex array points at offset 0 and we bind it once per frame.
+// With the base-instance extension, a range's draw starts at its offset via
+// the base instance; with the multi-draw extension, we also batch unlit
+// ranges into one call, with masks indexed by gl_DrawID. Without either, we
+// fall back to re-pointing the attributes at each range before drawing it.
+class GeometryArena {
+  private gl: WebGL2RenderingContext;
+  private shader: VoxelShader;
+  private vao: WebGLVertexArrayObject;
+  private buffer: WebGLBuffer;
+  private capacity: int;
+  private end: int = 0;
+  private usage: int = 0;
+  private holes: ArenaRange[] = [];
+  private ranges: ArenaRange[] = [];
+  private base_instance: BaseInstanceExtension | null;
+  private multi_draw: MultiDrawBaseInstanceExtension | null;
+  private batch_firsts: Int32Array;
+  private batch_counts: Int32Array;
+  private batch_instances: Int32Array;
+  private batch_bases: Uint32Array;
+  private batch_masks: Int32Array;
+  private batch_size: int = 0;
+
+  constructor(gl: WebGL2RenderingContext, shader: VoxelShader,
+              multi_draw: MultiDrawBaseInstanceExtension | null) {
+    this.gl = gl;
+    this.shader = shader;
+    this.vao = nonnull(gl.createVertexArray());
+    this.capacity = int(1 << 16);
+    this.buffer = this.createBuffer(this.capacity);
+    this.prepareVertexArray();
+
+    const name = 'WEBGL_draw_instanced_base_vertex_base_instance';
+    this.base_instance = gl.getExtension(name) as BaseInstanceExtension | null;
+    this.multi_draw = multi_draw;
+    this.batch_firsts = new Int32Array(kMaxMultiDraw);
+    this.batch_counts = new Int32Array(kMaxMultiDraw).fill(3);
+    this.batch_instances = new Int32Array(kMaxMultiDraw);
+    this.batch_bases = new Uint32Array(kMaxMultiDraw);
+    this.batch_masks = new Int32Array(2 * kMaxMultiDraw);
+  }
+
+  alloc(data: Int32Array): ArenaRange {
+    const n = int(data.length / Geometry.StrideInInt32);
+    assert(data.length === n * Geometry.StrideInInt32);
+
+    const range = new ArenaRange();
+    range.count = n;
+
+    const holes = this.holes;
+    const hole = holes.findIndex(x => x.count >= n);
+    if (hole >= 0) {
+      const entry = holes[hole];
+      range.offset = entry.offset;
+      entry.offset = int(entry.offset + n);
+      entry.count = int(entry.count - n);
+      if (entry.count === 0) holes.splice(hole, 1);
+    } else {
+      if (this.end + n > this.capacity) {
+        let capacity = this.capacity;
+        while (capacity < 2 * (this.usage + n)) capacity = int(2 * capacity);
+        this.relocate(capacity);
+      }
+      range.offset = this.end;
+      this.end = int(this.end + n);
+    }
+
+    const gl = this.gl;
+    const offset = range.offset * Geometry.StrideInBytes;
+    gl.bindBuffer(ARRAY_BUFFER, this.buffer);
+    gl.bufferSubData(ARRAY_BUFFER, offset, data, 0, data.length);
+
+    range.index = int(this.ranges.length);
+    this.ranges.push(range);
+    this.usage = int(this.usage + n);
+    return range;
+  }
+
+  free(range: ArenaRange): void {
+    const ranges = this.ranges;
+    assert(range === ranges[range.index]);
+    const last = ranges[ranges.length - 1];
+    ranges[range.index] = last;
+    last.index = range.index;
+    ranges.pop();
+    range.index = -1;
+    this.usage = int(this.usage - range.count);
+
+    // Find the first hole after this range, then merge with its neighbors.
+    const holes = this.holes;
+    let i = 0;
+    while (i < holes.length && holes[i].offset < range.offset) i++;
+    const prev = i > 0 ? holes[i - 1] : null;
+    const next = i < holes.length ? holes[i] : null;
+
+    let hole = range;
+    if (prev && prev.offset + prev.count === hole.offset) {
+      prev.count = int(prev.count + hole.count);
+      hole = prev;
+      i--;
+    } else {
+      holes.splice(i, 0, hole);
+    }
+    if (next && hole.offset + hole.count === next.offset) {
+      hole.count = int(hole.count + next.count);
+      holes.splice(i + 1, 1);
+    }
+
+    if (hole.offset + hole.count === this.end) {
+      assert(i === holes.length - 1);
+      this.end = hole.offset;
+      holes.pop();
+    }
+  }
+
+  bind(): void {
+    this.gl.bindVertexArray(this.vao);
+  }
+
+  canBatch(): boolean {
+    return this.multi_draw !== null;
+  }
+
+  // Adds an unlit range to the current multi-draw batch. Callers must set
+  // all shared uniforms first, and must flush the batch before changing them.
+  addToBatch(range: ArenaRange, mask: Int32Array): void {
+    assert(this.multi_draw !== null);
+    const i = this.batch_size;
+    this.batch_instances[i] = int(2 * range.count);
+    this.batch_bases[i] = range.offset;
+    this.batch_masks[2 * i + 0] = mask[0];
+    this.batch_masks[2 * i + 1] = mask[1];
+    this.batch_size = int(i + 1);
+    if (this.batch_size === kMaxMultiDraw) this.flushBatch();
+  }
+
+  flushBatch(): void {
+    const n = this.batch_size;
+    if (n === 0) return;
+    const {gl, shader} = this;
+    const ext = nonnull(this.multi_draw);
+    gl.uniform1i(shader.u_hasLight, 0);
+    gl.uniform1i(shader.u_multiDraw, 1);
+    gl.uniform2iv(shader.u_masks, this.batch_masks, 0, 2 * n);
+    ext.multiDrawArraysInstancedBaseInstanceWEBGL(
+        gl.TRIANGLES, this.batch_firsts, 0, this.batch_counts, 0,
+        this.batch_instances, 0, this.batch_bases, 0, n);
+    gl.uniform1i(shader.u_multiDraw, 0);
+    this.batch_size = 0;
+  }
+
+  // Draws a single range. Callers must bind the arena and set uniforms.
+  draw(range: ArenaRange): void {
+    const gl = this.gl;
+    const instances = int(2 * range.count);
+    if (this.base_instance) {
+      const ext = this.base_instance;
+      ext.drawArraysInstancedBaseInstanceWEBGL(
+          gl.TRIANGLES, 0, 3, instances, range.offset);
+      return;
+    }
+    gl.bindBuffer(ARRAY_BUFFER, this.buffer);
+    const base = range.offset * Geometry.StrideInBytes;
+    prepareVoxelAttributes(gl, this.shader, base);
+    gl.drawArraysInstanced(gl.TRIANGLES, 0, 3, instances);
+  }
+
+  defragment(): void {
+    this.relocate(this.capacity);
+  }
+
+  stats(): string {
+    const mb = (quads: int) => {
+      const bytes = quads * Geometry.StrideInBytes;
+      return `${(bytes / (1024 * 1024)).toFixed(2)}`;
+    };
+    const {usage, end, capacity} = this;
+    const holes = this.holes.length;
+    return `Arena: ${mb(usage)} / ${mb(end)} / ${mb(capacity)}Mb ` +
+           `(${holes} holes)`;
+  }
+
+  private createBuffer(capacity: int): WebGLBuffer {
+    const gl = this.gl;
+    const buffer = nonnull(gl.createBuffer());
+    gl.bindBuffer(ARRAY_BUFFER, buffer);
+    gl.bufferData(ARRAY_BUFFER, capacity * Geometry.StrideInBytes,
+                  gl.DYNAMIC_DRAW);
+    return buffer;
+  }
+
+  private prepareVertexArray(): void {
+    const gl = this.gl;
+    gl.bindVertexArray(this.vao);
+    gl.bindBuffer(ARRAY_BUFFER, this.buffer);
+    prepareVoxelAttributes(gl, this.shader, 0);
+    gl.bindVertexArray(null);
+  }
+
+  private relocate(capacity: int): void {
+    const gl = this.gl;
+    const stride = Geometry.StrideInBytes;
+    const buffer = this.createBuffer(capacity);
+    gl.bindBuffer(gl.COPY_READ_BUFFER, this.buffer);
+    gl.bindBuffer(gl.COPY_WRITE_BUFFER, buffer);
+
+    let end = 0;
+    const ranges = this.ranges.slice().sort((a, b) => a.offset - b.offset);
+    for (const range of ranges) {
+      const size = range.count * stride;
+      const src = range.offset * stride, dst = end * stride;
+      gl.copyBufferSubData(gl.COPY_READ_BUFFER, gl.COPY_WRITE_BUFFER,
+                           src, dst, size);
+      range.offset = int(end);
+      end += range.count;
+    }
+    assert(end === this.usage);
+
+    gl.deleteBuffer(this.buffer);
+    this.buffer = buffer;
+    this.capacity = capacity;
+    this.end = int(end);
+    this.holes.length = 0;
+    this.prepareVertexArray();
+  }
+};
+
+//////////////////////////////////////////////////////////////////////////////
+
 class VoxelMesh extends Mesh<VoxelShader> {
   private manager: VoxelManager;
   private geo: Geometry;
   private vao: WebGLVertexArrayObject | null = null;
   private quads: Buffer | null = null;
+  private range: ArenaRange | null = null;
   private light: LightTexture | null = null;
   private mask: Int32Array;
 
@@ -1089,23 +1656,34 @@ class VoxelMesh extends Mesh<VoxelShader> {
 
   draw(camera: Camera, planes: CullingPlane[]): boolean {
     const bounds = this.geo.getBounds();
-    if (this.cull(bounds, camera, planes)) return false;
+    if (this.cull(bounds, camera, planes, true)) return false;
 
     this.prepareBuffers();
-    const transform = camera.getTransformFor(this.position);
+
+    const {arena} = this.manager;
+    if (arena && !this.light && arena.canBatch()) {
+      arena.addToBatch(nonnull(this.range), this.mask);
+      return true;
+    }
 
     const gl = this.gl;
-    const n = this.geo.num_quads;
-    gl.bindVertexArray(this.vao);
+    if (!arena) gl.bindVertexArray(this.vao);
     if (this.light) {
-      gl.uniform1i(this.manager.shader.u_hasLight, 1);
+      const {shader} = this.manager;
+      const [ox, , oz] = camera.origin;
+      const {x, z} = this.light;
+      gl.uniform1i(shader.u_hasLight, 1);
+      gl.uniform2i(shader.u_lightOffset, ox - x, oz - z);
       gl.bindTexture(TEXTURE_3D, this.light.texture);
     } else {
       gl.uniform1i(this.manager.shader.u_hasLight, 0);
     }
     gl.uniform2iv(this.shader.u_mask, this.mask);
-    gl.uniformMatrix4fv(this.shader.u_transform, false, transform);
-    gl.drawArraysInstanced(gl.TRIANGLES, 0, 3, n * 2);
+    if (arena) {
+      arena.draw(nonnull(this.range));
+    } else {
+      gl.drawArraysInstanced(gl.TRIANGLES, 0, 3, this.geo.num_quads * 2);
+    }
     return true;
   }
 
@@ -1122,10 +1700,6 @@ class VoxelMesh extends Mesh<VoxelShader> {
     this.light = light as LightTexture;
   }
 
-  setPosition(x: number, y: number, z: number): void {
-    Vec3.set(this.position, x, y, z);
-  }
-
   show(m0: int, m1: int, shown: boolean): void {
     this.mask[0] = m0;
     this.mask[1] = m1;
@@ -1135,58 +1709,57 @@ class VoxelMesh extends Mesh<VoxelShader> {
   }
 
   private destroyBuffers() {
-    const {gl, light, quads} = this;
+    const {gl, light, quads, range} = this;
     gl.deleteVertexArray(this.vao);
     if (quads) this.manager.allocator.free(quads);
+    if (range) nonnull(this.manager.arena).free(range);
     this.vao = null;
     this.quads = null;
+    this.range = null;
     this.light = null;
   }
 
   private prepareBuffers() {
-    if (this.vao) return;
+    if (this.vao || this.range) return;
     const {gl, shader} = this;
-    this.vao = nonnull(gl.createVertexArray());
-    gl.bindVertexArray(this.vao);
-    this.prepareQuads(this.geo.quads);
-    const {BYTE, SHORT, UNSIGNED_BYTE: UBYTE, UNSIGNED_SHORT: USHORT} = gl;
-    this.prepareAttribute(shader.a_pos,     SHORT,  3, 0);
-    this.prepareAttribute(shader.a_indices, USHORT, 1, 6);
-    this.prepareAttribute(shader.a_size,    SHORT,  2, 8);
-    this.prepareAttribute(shader.a_mask,    UBYTE,  1, 12);
-    this.prepareAttribute(shader.a_texture, UBYTE,  1, 13);
-    this.prepareAttribute(shader.a_ao,      UBYTE,  1, 14);
-    this.prepareAttribute(shader.a_wddl,    BYTE,   1, 15);
-  }
-
-  private prepareAttribute(
-      location: number | null, type: number, size: int, offset: int) {
-    if (location === null) return;
-    const gl = this.gl;
-    const stride = Geometry.StrideInBytes;
-    gl.enableVertexAttribArray(location);
-    gl.vertexAttribIPointer(location, size, type, stride, offset);
-    gl.vertexAttribDivisor(location, 2);
-  }
-
-  private prepareQuads(data: Int32Array) {
     const n = this.geo.num_quads * Geometry.StrideInInt32;
+    const data = this.geo.quads;
     const subarray = data.length > n ? data.subarray(0, n) : data;
+
+    const arena = this.manager.arena;
+    if (arena) {
+      this.range = arena.alloc(subarray);
+      return;
+    }
+
+    this.vao = nonnull(gl.createVertexArray());
+    gl.bindVertexArray(this.vao);
     this.quads = this.manager.allocator.alloc(subarray, false);
+    prepareVoxelAttributes(gl, shader, 0);
   }
 };
 
 class VoxelManager implements MeshManager<VoxelShader> {
   gl: WebGL2RenderingContext;
   allocator: BufferAllocator;
+  arena: GeometryArena | null;
   shader: VoxelShader;
   atlas: TextureAtlas;
+  skyDarkness: number = 0;
   private phases: VoxelMesh[][];
 
-  constructor(gl: WebGL2RenderingContext, allocator: BufferAllocator) {
+  constructor(gl: WebGL2RenderingContext,
+              allocator: BufferAllocator, arena: boolean) {
     this.gl = gl;
     this.allocator = allocator;
-    this.shader = new VoxelShader(gl);
+
+    // WebGL only defines the shader's GL_ANGLE_multi_draw macro, which gates
+    // its per-draw masks, if we enable both extensions before compiling it.
+    const name = 'WEBGL_multi_draw_instanced_base_vertex_base_instance';
+    const multi_draw = arena && gl.getExtension('WEBGL_multi_draw') ?
+      (gl.getExtension(name) as MultiDrawBaseInstanceExtension | null) : null;
+    this.shader = new VoxelShader(gl, multi_draw !== null);
+    this.arena = arena ? new GeometryArena(gl, this.shader, multi_draw) : null;
     this.atlas = new TextureAtlas(gl);
     this.phases = [[], [], []];
   }
@@ -1199,21 +1772,31 @@ class VoxelManager implements MeshManager<VoxelShader> {
 
   render(camera: Camera, planes: CullingPlane[], stats: Stats,
          overlay: ScreenOverlay, move: number, wave: number, phase: int): void {
-    const {atlas, gl, shader} = this;
+    const {arena, atlas, gl, shader} = this;
     let drawn = 0;
 
     atlas.bind();
     shader.bind();
+    arena?.bind();
     const meshes = this.phases[phase];
     const fog_color = overlay.getFogColor();
     const fog_depth = overlay.getFogDepth(camera);
+    // Voxel meshes are in render space, so they all share one transform.
+    const transform = camera.getTransformFor(kZeroVec3, true);
+    gl.uniformMatrix4fv(shader.u_transform, false, transform);
     gl.uniform1f(shader.u_move, move);
     gl.uniform1f(shader.u_wave, wave);
     gl.uniform1f(shader.u_alphaTest, 1);
     gl.uniform3fv(shader.u_fogColor, fog_color);
     gl.uniform1f(shader.u_fogDepth, fog_depth);
+    gl.uniform1f(shader.u_skyDarkness, this.skyDarkness);
     gl.uniform1i(shader.u_light, 1);
     gl.activeTexture(gl.TEXTURE1);
+    if (atlas.mappingDirty) {
+      gl.uniform4uiv(shader.u_textureMap, atlas.mapping);
+      gl.uniform4uiv(shader.u_normalMap, atlas.normals);
+      atlas.mappingDirty = false;
+    }
 
     // Rendering phases:
     //   0) Opaque and alpha-tested voxel meshes.
@@ -1222,6 +1805,7 @@ class VoxelManager implements MeshManager<VoxelShader> {
       for (const mesh of meshes) {
         if (mesh.draw(camera, planes)) drawn++;
       }
+      arena?.flushBatch();
     } else {
       gl.enable(gl.BLEND);
       gl.disable(gl.CULL_FACE);
@@ -1229,6 +1813,7 @@ class VoxelManager implements MeshManager<VoxelShader> {
       for (const mesh of meshes) {
         if (mesh.draw(camera, planes)) drawn++;
       }
+      arena?.flushBatch();
       gl.enable(gl.CULL_FACE);
       gl.disable(gl.BLEND);
     }
@@ -1240,6 +1825,276 @@ class VoxelManager implements MeshManager<VoxelShader> {
 
 //////////////////////////////////////////////////////////////////////////////
 
+// Smooth meshes are unindexed triangles from the engine's smooth meshers,
+// with a vec3 position and a texture id per vertex. They have no face ids,
+// so we take normals from the derivatives of the position, and we project
+// textures along each normal's major axis, which tiles them across
+// triangles. We look up light in the texel outside the surface.
+const kSmoothShader = `
+  uniform mat4 u_transform;
+  uniform uvec4 u_textureMap[32];
+
+  in vec3 a_pos;
+  in float a_texture;
+
+  out vec3 v_pos;
+  flat out int v_slot;
+
+  void main() {
+    int id = int(a_texture);
+    uint slots = u_textureMap[id >> 3][(id >> 1) & 3];
+    v_slot = int((slots >> (16 * (id & 1))) & 0xffffu);
+    v_pos = a_pos;
+    gl_Position = u_transform * vec4(a_pos, 1.0);
+  }
+#split
+  uniform ivec2 u_lightOffset;
+  uniform vec3 u_fogColor;
+  uniform float u_fogDepth;
+  uniform float u_skyDarkness;
+  uniform int u_hasLight;
+  uniform sampler2DArray u_texture;
+  uniform sampler3D u_light;
+
+  in vec3 v_pos;
+  flat in int v_slot;
+
+  out vec4 o_color;
+
+  // Without AO, smooth surfaces need some shading to show their shape. We
+  // scale it so that flat ground is as bright as a block's top face.
+  const vec3 kShadeLight = vec3(0.3, 0.9, 0.3);
+  const float kShadeAmbient = 0.5;
+
+  // See getLightTexel in the voxel shader.
+  float getLightTexel(ivec3 pos) {
+    if (pos[0] < 0) return 0.0;
+    if (pos[0] >= 0xff) return 15.0 - u_skyDarkness;
+    float texel = round(255.0 * texelFetch(u_light, pos, 0)[0]);
+    float block = floor(texel / 16.0);
+    return max(block, texel - 16.0 * block - u_skyDarkness);
+  }
+
+  float getLightLevel(vec3 normal) {
+    if (u_hasLight != 1) return 15.0;
+    vec3 pos = v_pos + 0.5 * normal;
+    pos[0] += float(u_lightOffset[0]);
+    pos[2] += float(u_lightOffset[1]);
+    return getLightTexel(ivec3(clamp(int(floor(pos[1])), 0, 0xff),
+                               clamp(int(floor(pos[0])) + 1, 0, 0x11),
+                               clamp(int(floor(pos[2])) + 1, 0, 0x11)));
+  }
+
+  void main() {
+    vec3 normal = normalize(cross(dFdx(v_pos), dFdy(v_pos)));
+    vec3 n = abs(normal);
+    vec2 uv = n[1] >= max(n[0], n[2])
+        ? v_pos.xz : vec2(n[0] >= n[2] ? v_pos[2] : v_pos[0], -v_pos[1]);
+
+    vec3 sun = normalize(kShadeLight);
+    float shade = (kShadeAmbient + max(dot(normal, sun), 0.0)) /
+                  (kShadeAmbient + sun[1]);
+    float light = pow(0.8, 15.0 - getLightLevel(normal)) * shade;
+
+    float depth = u_fogDepth * gl_FragCoord.w;
+    float fog = clamp(exp2(-depth * depth), 0.0, 1.0);
+
+    vec4 color = texture(u_texture, vec3(uv, float(v_slot)));
+    color = vec4(vec3(light), 1.0) * color;
+    o_color = mix(color, vec4(u_fogColor, color[3]), fog);
+  }
+`;
+
+class SmoothShader extends Shader {
+  u_transform:   WebGLUniformLocation | null;
+  u_textureMap:  WebGLUniformLocation | null;
+  u_lightOffset: WebGLUniformLocation | null;
+  u_fogColor:    WebGLUniformLocation | null;
+  u_fogDepth:    WebGLUniformLocation | null;
+  u_skyDarkness: WebGLUniformLocation | null;
+  u_hasLight:    WebGLUniformLocation | null;
+  u_light:       WebGLUniformLocation | null;
+
+  a_pos:     number | null;
+  a_texture: number | null;
+
+  constructor(gl: WebGL2RenderingContext) {
+    super(gl, kSmoothShader);
+    this.u_transform   = this.getUniformLocation('u_transform');
+    this.u_textureMap  = this.getUniformLocation('u_textureMap');
+    this.u_lightOffset = this.getUniformLocation('u_lightOffset');
+    this.u_fogColor    = this.getUniformLocation('u_fogColor');
+    this.u_fogDepth    = this.getUniformLocation('u_fogDepth');
+    this.u_skyDarkness = this.getUniformLocation('u_skyDarkness');
+    this.u_hasLight    = this.getUniformLocation('u_hasLight');
+    this.u_light       = this.getUniformLocation('u_light');
+
+    this.a_pos     = this.getAttribLocation('a_pos');
+    this.a_texture = this.getAttribLocation('a_texture');
+  }
+};
+
+// Floats per smooth mesh vertex: x, y, z, and texture. Keep this in sync
+// with kSmoothVertexFloats in smooth.h.
+const kSmoothVertexFloats = int(4);
+
+class SmoothMesh extends Mesh<SmoothShader> {
+  private manager: SmoothManager;
+  private vertices: Float32Array;
+  private vao: WebGLVertexArrayObject | null = null;
+  private buffer: Buffer | null = null;
+  private light: LightTexture | null = null;
+  private bounds: Float64Array;
+
+  constructor(manager: SmoothManager, meshes: SmoothMesh[],
+              vertices: Float32Array) {
+    super(manager, meshes);
+    this.manager = manager;
+    this.vertices = vertices;
+    this.bounds = new Float64Array(24);
+    this.computeBounds();
+  }
+
+  dispose(): void {
+    super.dispose();
+    this.destroyBuffers();
+  }
+
+  draw(camera: Camera, planes: CullingPlane[]): boolean {
+    if (this.cull(this.bounds, camera, planes, true)) return false;
+
+    this.prepareBuffers();
+
+    const {gl, shader} = this;
+    gl.bindVertexArray(this.vao);
+    if (this.light) {
+      const [ox, , oz] = camera.origin;
+      const {x, z} = this.light;
+      gl.uniform1i(shader.u_hasLight, 1);
+      gl.uniform2i(shader.u_lightOffset, ox - x, oz - z);
+      gl.bindTexture(TEXTURE_3D, this.light.texture);
+    } else {
+      gl.uniform1i(shader.u_hasLight, 0);
+    }
+    const count = this.vertices.length / kSmoothVertexFloats;
+    gl.drawArrays(gl.TRIANGLES, 0, count);
+    return true;
+  }
+
+  setGeometry(vertices: Float32Array): void {
+    this.destroyBuffers();
+    this.vertices = vertices;
+    this.computeBounds();
+  }
+
+  setLight(light: ILightTexture): void {
+    this.light = light as LightTexture;
+  }
+
+  show(shown: boolean): void {
+    if (shown === this.shown()) return;
+    shown ? this.addToMeshes() : this.removeFromMeshes();
+  }
+
+  private computeBounds() {
+    const {bounds, vertices} = this;
+    const lower = [Infinity, Infinity, Infinity];
+    const upper = [-Infinity, -Infinity, -Infinity];
+    for (let i = 0; i < vertices.length; i += kSmoothVertexFloats) {
+      for (let j = 0; j < 3; j++) {
+        const value = vertices[i + j];
+        if (lower[j] > value) lower[j] = value;
+        if (upper[j] < value) upper[j] = value;
+      }
+    }
+    for (let i = 0; i < 8; i++) {
+      for (let j = 0; j < 3; j++) {
+        bounds[3 * i + j] = (i & (1 << j)) ? upper[j] : lower[j];
+      }
+    }
+  }
+
+  private destroyBuffers() {
+    const {gl, buffer} = this;
+    gl.deleteVertexArray(this.vao);
+    if (buffer) this.manager.allocator.free(buffer);
+    this.vao = null;
+    this.buffer = null;
+    this.light = null;
+  }
+
+  private prepareBuffers() {
+    if (this.vao) return;
+    const {gl, shader} = this;
+    this.vao = nonnull(gl.createVertexArray());
+    gl.bindVertexArray(this.vao);
+    this.buffer = this.manager.allocator.alloc(this.vertices, false);
+
+    const stride = 4 * kSmoothVertexFloats;
+    if (shader.a_pos !== null) {
+      gl.enableVertexAttribArray(shader.a_pos);
+      gl.vertexAttribPointer(shader.a_pos, 3, gl.FLOAT, false, stride, 0);
+    }
+    if (shader.a_texture !== null) {
+      gl.enableVertexAttribArray(shader.a_texture);
+      gl.vertexAttribPointer(shader.a_texture, 1, gl.FLOAT, false, stride, 12);
+    }
+  }
+};
+
+class SmoothManager implements MeshManager<SmoothShader> {
+  gl: WebGL2RenderingContext;
+  allocator: BufferAllocator;
+  shader: SmoothShader;
+  skyDarkness: number = 0;
+  private atlas: TextureAtlas;
+  private meshes: SmoothMesh[];
+
+  constructor(gl: WebGL2RenderingContext, allocator: BufferAllocator,
+              atlas: TextureAtlas) {
+    this.gl = gl;
+    this.allocator = allocator;
+    this.shader = new SmoothShader(gl);
+    this.atlas = atlas;
+    this.meshes = [];
+  }
+
+  addMesh(vertices: Float32Array): SmoothMesh {
+    assert(vertices.length > 0);
+    assert(vertices.length % (3 * kSmoothVertexFloats) === 0);
+    return new SmoothMesh(this, this.meshes, vertices);
+  }
+
+  render(camera: Camera, planes: CullingPlane[], stats: Stats,
+         overlay: ScreenOverlay): void {
+    const {atlas, gl, meshes, shader} = this;
+    if (meshes.length === 0) return;
+    let drawn = 0;
+
+    // Smooth meshes are opaque, and like voxel meshes, they're in render
+    // space, so they all share one transform. The voxel manager clears the
+    // atlas's dirty bit, so we upload the texture mapping every frame.
+    atlas.bind();
+    shader.bind();
+    const transform = camera.getTransformFor(kZeroVec3, true);
+    gl.uniformMatrix4fv(shader.u_transform, false, transform);
+    gl.uniform4uiv(shader.u_textureMap, atlas.mapping);
+    gl.uniform3fv(shader.u_fogColor, overlay.getFogColor());
+    gl.uniform1f(shader.u_fogDepth, overlay.getFogDepth(camera));
+    gl.uniform1f(shader.u_skyDarkness, this.skyDarkness);
+    gl.uniform1i(shader.u_light, 1);
+    gl.activeTexture(gl.TEXTURE1);
+    for (const mesh of meshes) {
+      if (mesh.draw(camera, planes)) drawn++;
+    }
+
+    stats.drawn += drawn;
+    stats.total += meshes.length;
+  }
+};
+
+//////////////////////////////////////////////////////////////////////////////
+
 class Instance {
   constructor(public mesh: InstancedMesh, public index: int) {}
   dispose(): void {
@@ -1512,9 +2367,11 @@ class InstancedManager implements MeshManager<InstancedShader> {
     const {billboard, gl, meshes, origin, origin_32, shader} = this;
     let drawn = 0;
 
-    origin_32[0] = origin[0] = Math.floor(camera.position[0]);
-    origin_32[1] = origin[1] = Math.floor(camera.position[1]);
-    origin_32[2] = origin[2] = Math.floor(camera.position[2]);
+    // Instance positions are relative to the render origin, as f32s.
+    for (let i = 0; i < 3; i++) {
+      origin[i] = Math.floor(camera.position[i]);
+      origin_32[i] = origin[i] - camera.origin[i];
+    }
     const transform = camera.getTransformFor(origin);
 
     const pitch  = -0.33 * camera.pitch;
@@ -2102,7 +2959,18 @@ interface ISpriteMesh extends IMesh {
   setSTUV: (s: number, t: number, u: number, v: number) => void,
 };
 
-interface IVoxelMesh extends IMesh {
+// Smooth meshes have no position either. See kSmoothShader.
+interface ISmoothMesh {
+  dispose: () => void,
+  setGeometry: (vertices: Float32Array) => void,
+  setLight: (light: ILightTexture) => void,
+  show: (shown: boolean) => void,
+};
+
+// Voxel meshes have no position: their quads are already relative to the
+// render origin, and the engine remeshes them when it moves.
+interface IVoxelMesh {
+  dispose: () => void,
   getGeometry: () => Geometry,
   setGeometry: (geo: Geometry) => void,
   setLight: (light: ILightTexture) => void,
@@ -2118,6 +2986,7 @@ class Renderer {
   private highlight_manager: HighlightManager;
   private instanced_manager: InstancedManager;
   private shadow_manager: ShadowManager;
+  private smooth_manager: SmoothManager;
   private sprite_manager: SpriteManager;
   private voxels_manager: VoxelManager;
 
@@ -2127,6 +2996,7 @@ class Renderer {
     const scale  = parseFloat(params.get('scale') || '1');
     const antialias_base = size === 'small' ? '1' : '0';
     const antialias = parseInt(params.get('antialias') || antialias_base);
+    const arena = parseInt(params.get('arena') || '0');
 
     const container = nonnull(nonnull(canvas.parentElement).parentElement);
     container.classList.add(size);
@@ -2162,17 +3032,36 @@ class Renderer {
     this.instanced_manager = new InstancedManager(gl, allocator, atlas);
     this.shadow_manager = new ShadowManager(gl, unit_square_vao);
     this.sprite_manager = new SpriteManager(gl, atlas, unit_square_vao);
-    this.voxels_manager = new VoxelManager(gl, allocator);
+    this.voxels_manager = new VoxelManager(gl, allocator, arena === 1);
+    this.smooth_manager =
+        new SmoothManager(gl, allocator, this.voxels_manager.atlas);
   }
 
-  addLightTexture(data: Uint8Array): ILightTexture {
-    return new LightTexture(data, this.talloc);
+  addLightTexture(data: Uint8Array, x: int, z: int): ILightTexture {
+    return new LightTexture(data, x, z, this.talloc);
   }
 
   addTexture(texture: Texture): int {
     return this.voxels_manager.atlas.addTexture(texture);
   }
 
+  loadTexture(texture: Texture): int {
+    return this.voxels_manager.atlas.loadTexture(texture);
+  }
+
+  setTextureMapping(id: int, slot: int): void {
+    this.voxels_manager.atlas.setTextureMapping(id, slot);
+  }
+
+  setNormalMapping(id: int, slot: int): void {
+    this.voxels_manager.atlas.setNormalMapping(id, slot);
+  }
+
+  setSkyDarkness(level: number): void {
+    this.voxels_manager.skyDarkness = level;
+    this.smooth_manager.skyDarkness = level;
+  }
+
   addHighlightMesh(): IHighlightMesh {
     return this.highlight_manager.addMesh();
   }
@@ -2189,10 +3078,22 @@ class Renderer {
     return this.sprite_manager.addMesh(size, sprite);
   }
 
+  renderIcon(textures: int[], size: int): Uint8ClampedArray {
+    return this.voxels_manager.atlas.renderIcon(textures, size);
+  }
+
   addVoxelMesh(geo: Geometry, phase: int): IVoxelMesh {
     return this.voxels_manager.addMesh(geo, phase);
   }
 
+  addSmoothMesh(vertices: Float32Array): ISmoothMesh {
+    return this.smooth_manager.addMesh(vertices);
+  }
+
+  defragment(): void {
+    this.voxels_manager.arena?.defragment();
+  }
+
   render(move: number, wave: number, sparkle: boolean): string {
     const {gl, overlay} = this;
     const [r, g, b] = kDefaultSkyColor;
@@ -2208,12 +3109,16 @@ class Renderer {
     this.sprite_manager.render(camera, planes, stats);
     this.instanced_manager.render(camera, planes, stats, overlay);
     this.voxels_manager.render(camera, planes, stats, overlay, move, wave, 0);
+    this.smooth_manager.render(camera, planes, stats, overlay);
     this.highlight_manager.render(camera, planes, stats);
     this.shadow_manager.render(camera, planes, stats);
     this.voxels_manager.render(camera, planes, stats, overlay, move, wave, 1);
     overlay.draw();
 
+    const arena = this.voxels_manager.arena;
+    const arena_stats = arena ? `${arena.stats()}\r\n` : '';
     return `${this.balloc.stats()}\r\n` +
+           arena_stats +
            `${this.talloc.stats()}\r\n` +
            `Draw calls: ${stats.drawn} / ${stats.total}\r\n` +
            `Instances: ${stats.drawnInstances} / ${stats.totalInstances}`;
@@ -2229,5 +3134,5 @@ class Renderer {
 export {kShadowAlpha, Geometry, Renderer, Sprite, Texture};
 export {IMesh as Mesh, ISpriteMesh as SpriteMesh, IShadowMesh as ShadowMesh,
         IHighlightMesh as HighlightMesh, IInstance as Instance,
-        IInstancedMesh as InstancedMesh, IVoxelMesh as VoxelMesh,
-        ILightTexture as LightTexture};
+        IInstancedMesh as InstancedMesh, ISmoothMesh as SmoothMesh,
+        IVoxelMesh as VoxelMesh, ILightTexture as LightTexture};
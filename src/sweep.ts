@@ -4,23 +4,50 @@ import {int, Vec3} from './base.js';
 
 type Check = (x: int, y: int, z: int) => boolean;
 
+// An optional broadphase: returns true if every voxel in the inclusive box
+// [min, max] passes the check, so that we can move through it in one step.
+type Clear = (min: Vec3, max: Vec3) => boolean;
+
+// We sweep in fixed point, relative to the integer part of the box's min
+// corner, so that the int32 math stays exact at any world coordinate.
 const kSweepShift = 12;
 const kSweepResolution = 1 << kSweepShift;
 const kSweepMask = kSweepResolution - 1;
 
 const kSpeeds = [0, 0, 0, 0];
 const kDistances = [0, 0, 0, kSweepResolution];
+const kBase = Vec3.create();
 const kVoxel = Vec3.create();
+const kClearMin = Vec3.create();
+const kClearMax = Vec3.create();
 
 const sweep = (min: Vec3, max: Vec3, delta: Vec3, impacts: Vec3,
-               check: Check, stop_on_impact: boolean = false) => {
+               check: Check, stop_on_impact: boolean = false,
+               clear: Clear | null = null) => {
   for (let i = 0; i < 3; i++) {
-    min[i] = (min[i] * kSweepResolution) | 0;
-    max[i] = (max[i] * kSweepResolution) | 0;
+    const base = kBase[i] = Math.floor(min[i]);
+    min[i] = ((min[i] - base) * kSweepResolution) | 0;
+    max[i] = ((max[i] - base) * kSweepResolution) | 0;
     delta[i] = (delta[i] * kSweepResolution) | 0;
     impacts[i] = 0;
   }
 
+  if (clear) {
+    for (let i = 0; i < 3; i++) {
+      const lo = Math.min(min[i], min[i] + delta[i]);
+      const hi = Math.max(max[i], max[i] + delta[i]);
+      kClearMin[i] = (lo >> kSweepShift) + kBase[i];
+      kClearMax[i] = ((hi - 1) >> kSweepShift) + kBase[i];
+    }
+    if (clear(kClearMin, kClearMax)) {
+      for (let i = 0; i < 3; i++) {
+        min[i] += delta[i];
+        max[i] += delta[i];
+        delta[i] = 0;
+      }
+    }
+  }
+
   while (delta[0] || delta[1] || delta[2]) {
     let best = 3;
     let bounded = true;
@@ -63,14 +90,15 @@ const sweep = (min: Vec3, max: Vec3, delta: Vec3, impacts: Vec3,
 
     {
       const i = best;
-      kVoxel[i] = (direction > 0 ? max[i] - 1 : min[i]) >> kSweepShift;
+      const edge = direction > 0 ? max[i] - 1 : min[i];
+      kVoxel[i] = (edge >> kSweepShift) + kBase[i];
 
       const j = i < 2 ? i + 1 : i - 2;
       const k = i < 1 ? i + 2 : i - 1;
-      const jlo = min[j] >> kSweepShift;
-      const jhi = (max[j] - 1) >> kSweepShift;
-      const klo = min[k] >> kSweepShift;
-      const khi = (max[k] - 1) >> kSweepShift;
+      const jlo = (min[j] >> kSweepShift) + kBase[j];
+      const jhi = ((max[j] - 1) >> kSweepShift) + kBase[j];
+      const klo = (min[k] >> kSweepShift) + kBase[k];
+      const khi = ((max[k] - 1) >> kSweepShift) + kBase[k];
 
       let done = false;
       for (kVoxel[j] = jlo; !done && kVoxel[j] <= jhi; kVoxel[j]++) {
@@ -90,8 +118,8 @@ const sweep = (min: Vec3, max: Vec3, delta: Vec3, impacts: Vec3,
   }
 
   for (let i = 0; i < 3; i++) {
-    min[i] = min[i] / kSweepResolution;
-    max[i] = max[i] / kSweepResolution;
+    min[i] = min[i] / kSweepResolution + kBase[i];
+    max[i] = max[i] / kSweepResolution + kBase[i];
   }
 };
 
@@ -0,0 +1,39 @@
+#pragma once
+
+#include <array>
+#include <cstdint>
+
+#include "base.h"
+
+//////////////////////////////////////////////////////////////////////////////
+
+namespace voxels {
+
+//////////////////////////////////////////////////////////////////////////////
+
+// The six face-adjacent neighbors of a voxel, as (dx, dy, dz).
+constexpr std::array<int, 3> kFaceNeighbors[6] = {
+  {-1, 0, 0}, {1, 0, 0}, {0, -1, 0}, {0, 1, 0}, {0, 0, -1}, {0, 0, 1},
+};
+
+// The view of the world that gameplay systems, such as fire, read through,
+// so that they can live outside of the engine. World implements it. Reads
+// match World's: Block::Unknown in unloaded chunks, Block::Bedrock below the
+// world, and Block::Air above it.
+//
+// Systems return the changes that they want to make instead of writing them,
+// so that the World can apply them with its usual edit checks and events.
+struct BlockAccess {
+  virtual Block getBlock(int x, int y, int z) = 0;
+  virtual int getLightLevel(int x, int y, int z) = 0;
+  virtual uint8_t getMetadata(int x, int y, int z) = 0;
+
+ protected:
+  ~BlockAccess() = default;
+};
+
+struct BlockChange { int x; int y; int z; Block block; };
+
+//////////////////////////////////////////////////////////////////////////////
+
+} // namespace voxels
@@ -1,7 +1,10 @@
 #pragma once
 
+#include <algorithm>
 #include <cassert>
 #include <memory>
+#include <type_traits>
+#include <vector>
 
 #include "parallel-hashmap/phmap.h"
 
@@ -40,6 +43,10 @@ constexpr int kChunkBits  = 4;
 constexpr int kChunkWidth = 1 << kChunkBits;
 constexpr int kWorldHeight = 256;
 
+// Require a layer of air blocks at the top of the world. Doing so simplifies
+// our data structures and shaders (for example, a height fits in a uint8_t).
+constexpr int kBuildHeight = kWorldHeight - 1;
+
 enum class Block : uint8_t {
   Air, Unknown, Bedrock, Bush, Dirt, Fungi, Grass,
   Rock, Sand, Snow, Stone, Trunk, Water };
@@ -55,6 +62,32 @@ constexpr T safe_cast(U u) {
   return result;
 }
 
+// A small, fast, seedable RNG: splitmix64. We use it for gameplay logic that
+// should be deterministic given a seed, like drops and random block ticks.
+struct Random {
+  explicit Random(uint64_t seed = 0) : state(seed) {}
+
+  uint64_t next() {
+    auto x = (state += 0x9e3779b97f4a7c15ull);
+    x = (x ^ (x >> 30)) * 0xbf58476d1ce4e5b9ull;
+    x = (x ^ (x >> 27)) * 0x94d049bb133111ebull;
+    return x ^ (x >> 31);
+  }
+
+  // Returns a uniform double in [0, 1).
+  double uniform() {
+    return static_cast<double>(next() >> 11) / static_cast<double>(1ull << 53);
+  }
+
+  // Random(getState()) continues the sequence from where this one is.
+  uint64_t getState() const {
+    return state;
+  }
+
+ private:
+  uint64_t state;
+};
+
 template <typename T>
 std::unique_ptr<T[]> allocate_array(size_t size, T value) {
   auto result = std::make_unique<T[]>(size);
@@ -62,6 +95,101 @@ std::unique_ptr<T[]> allocate_array(size_t size, T value) {
   return result;
 }
 
+// A bump allocator for temporary buffers: meshing masks, BFS queues, sort
+// scratch, and so on. Allocation is a pointer bump, and memory is reclaimed
+// all at once, either by releasing to an earlier mark or by a full reset.
+//
+// When a page fills up, we add a new page twice our capacity. reset coalesces
+// the pages into one, so after a warm-up frame, we stop allocating at all.
+struct Arena {
+  struct Mark { size_t page; size_t used; };
+
+  explicit Arena(size_t capacity = 1 << 16) {
+    addPage(capacity);
+  }
+
+  // Returns uninitialized space for `count` values of type T, which is valid
+  // until a release or reset frees it. We never run destructors.
+  template <typename T>
+  T* allocate(size_t count) {
+    static_assert(std::is_trivially_destructible_v<T>);
+    const auto bytes = std::max(count * sizeof(T), size_t(1));
+    const auto align = alignof(T);
+    for (;; current++) {
+      if (current == pages.size()) addPage(std::max(2 * capacity, bytes));
+      auto& page = pages[current];
+      const auto start = (page.used + align - 1) & ~(align - 1);
+      if (start + bytes <= page.size) {
+        page.used = start + bytes;
+        return reinterpret_cast<T*>(page.data.get() + start);
+      }
+    }
+  }
+
+  template <typename T>
+  T* allocate(size_t count, T value) {
+    const auto result = allocate<T>(count);
+    std::fill_n(result, count, value);
+    return result;
+  }
+
+  Mark mark() const {
+    return {current, pages[current].used};
+  }
+
+  void release(Mark mark) {
+    assert(mark.page <= current);
+    for (auto i = mark.page + 1; i <= current; i++) pages[i].used = 0;
+    current = mark.page;
+    pages[current].used = mark.used;
+  }
+
+  void reset() {
+    if (pages.size() > 1) {
+      const auto total = capacity;
+      pages.clear();
+      capacity = 0;
+      addPage(total);
+    }
+    current = 0;
+    pages[0].used = 0;
+  }
+
+  size_t getCapacity() const {
+    return capacity;
+  }
+
+ private:
+  struct Page {
+    std::unique_ptr<uint8_t[]> data;
+    size_t size;
+    size_t used;
+  };
+
+  void addPage(size_t size) {
+    pages.push_back({std::make_unique<uint8_t[]>(size), size, 0});
+    capacity += size;
+  }
+
+  std::vector<Page> pages;
+  size_t capacity = 0;
+  size_t current = 0;
+
+  DISALLOW_COPY_AND_ASSIGN(Arena);
+};
+
+// Releases everything allocated from an arena within a scope.
+struct ArenaScope {
+  explicit ArenaScope(Arena& arena) : arena(arena), saved(arena.mark()) {}
+  ~ArenaScope() { arena.release(saved); }
+
+ private:
+  Arena& arena;
+  Arena::Mark saved;
+
+  DISALLOW_COPY_AND_ASSIGN(ArenaScope);
+};
+
 struct Point {
   int normSquared() const {
     return x * x + z * z;
@@ -87,6 +215,31 @@ struct Point {
   int z;
 };
 
+// A type-erased view of a tensor's data, for exporting it to the host. Any
+// tensor's elements are u8, u16, u32, or f32 values (or enums over them), so
+// the host can wrap them in the matching typed array. Strides are in elements.
+enum class TensorType : int { U8, U16, U32, F32 };
+
+template <typename T>
+constexpr TensorType tensorType() {
+  static_assert(std::is_arithmetic_v<T> || std::is_enum_v<T>);
+  if constexpr (std::is_same_v<T, float>) {
+    return TensorType::F32;
+  } else {
+    static_assert(!std::is_floating_point_v<T>);
+    static_assert(sizeof(T) == 1 || sizeof(T) == 2 || sizeof(T) == 4);
+    return sizeof(T) == 1 ? TensorType::U8 :
+           sizeof(T) == 2 ? TensorType::U16 : TensorType::U32;
+  }
+}
+
+struct TensorView {
+  const void* data;
+  TensorType type;
+  int shape[3];
+  int stride[3];
+};
+
 template <typename T, size_t X, size_t Z>
 struct Tensor2 {
   T get(int x, int z) const {
@@ -104,6 +257,12 @@ struct Tensor2 {
     return x + (z * X);
   }
 
+  // A 2D tensor is viewed as an X x 1 x Z tensor.
+  TensorView view() const {
+    return {data.data(), tensorType<T>(), {int(X), 1, int(Z)},
+            {int(stride[0]), 0, int(stride[1])}};
+  }
+
   NonCopyArray<T, X * Z> data;
   constexpr static size_t shape[2]  = {X, Z};
   constexpr static size_t stride[2] = {1, X};
@@ -126,9 +285,63 @@ struct Tensor3 {
     return y + (x * Y) + (z * X * Y);
   }
 
+  // Iteration helpers. y is the fastest axis, so a column of Y values at a
+  // given (x, z) is contiguous; these helpers walk whole columns at a time.
+  // Boxes are half-open: [x0, x1) x [y0, y1) x [z0, z1).
+
+  // Calls fn(x, z, column), where column[y] is the value at (x, y, z).
+  template <typename Fn>
+  void eachColumn(Fn&& fn) {
+    for (auto z = 0; z < Z; z++) {
+      for (auto x = 0; x < X; x++) fn(x, z, &data[index(x, 0, z)]);
+    }
+  }
+
+  template <typename Fn>
+  void eachColumn(Fn&& fn) const {
+    for (auto z = 0; z < Z; z++) {
+      for (auto x = 0; x < X; x++) fn(x, z, &data[index(x, 0, z)]);
+    }
+  }
+
+  // Calls fn(x, y, z, value) for each cell in the box, with a mutable value.
+  template <typename Fn>
+  void eachInBox(int x0, int y0, int z0, int x1, int y1, int z1, Fn&& fn) {
+    checkBox(x0, y0, z0, x1, y1, z1);
+    for (auto z = z0; z < z1; z++) {
+      for (auto x = x0; x < x1; x++) {
+        auto column = &data[index(x, 0, z)];
+        for (auto y = y0; y < y1; y++) fn(x, y, z, column[y]);
+      }
+    }
+  }
+
+  void fillBox(int x0, int y0, int z0, int x1, int y1, int z1, T value) {
+    checkBox(x0, y0, z0, x1, y1, z1);
+    if (y0 >= y1) return;
+    for (auto z = z0; z < z1; z++) {
+      for (auto x = x0; x < x1; x++) {
+        const auto start = &data[index(x, y0, z)];
+        std::fill(start, start + (y1 - y0), value);
+      }
+    }
+  }
+
+  TensorView view() const {
+    return {data.data(), tensorType<T>(), {int(X), int(Y), int(Z)},
+            {int(stride[0]), int(stride[1]), int(stride[2])}};
+  }
+
   NonCopyArray<T, X * Y * Z> data;
   constexpr static size_t shape[3]  = {X, Y, Z};
   constexpr static size_t stride[3] = {Y, 1, X * Y};
+
+ private:
+  static void checkBox(int x0, int y0, int z0, int x1, int y1, int z1) {
+    assert(0 <= x0 && x0 <= x1 && x1 <= X);
+    assert(0 <= y0 && y0 <= y1 && y1 <= Y);
+    assert(0 <= z0 && z0 <= z1 && z1 <= Z);
+  }
 };
 
 template <typename T> using ChunkTensor1 =
@@ -0,0 +1,81 @@
+#include "bricks.h"
+
+#include <array>
+
+//////////////////////////////////////////////////////////////////////////////
+
+namespace voxels {
+
+//////////////////////////////////////////////////////////////////////////////
+
+void exportBricks(const Block* voxels, BrickExport& result) {
+  result.count = 0;
+  result.bricks.clear();
+  result.palette.clear();
+  result.indices.clear();
+
+  static_assert(kChunkWidth % kBrickSize == 0);
+  static_assert(kWorldHeight % kBrickSize == 0);
+  constexpr auto nx = kChunkWidth / kBrickSize;
+  constexpr auto ny = kWorldHeight / kBrickSize;
+  using Tensor = ChunkTensor3<Block>;
+
+  for (auto bz = 0; bz < nx; bz++) {
+    for (auto bx = 0; bx < nx; bx++) {
+      for (auto by = 0; by < ny; by++) {
+        std::array<uint64_t, kBrickSize> occupancy = {};
+        std::array<uint8_t, kBrickVolume> indices;
+        std::array<int, 256> lookup;
+        lookup.fill(-1);
+
+        const auto start = result.palette.size();
+        for (auto y = 0; y < kBrickSize; y++) {
+          for (auto z = 0; z < kBrickSize; z++) {
+            for (auto x = 0; x < kBrickSize; x++) {
+              const auto block = voxels[Tensor::index(
+                  bx * kBrickSize + x, by * kBrickSize + y,
+                  bz * kBrickSize + z)];
+              const auto bit = x | (z << kBrickBits);
+              if (block != Block::Air) occupancy[y] |= uint64_t{1} << bit;
+
+              auto& entry = lookup[static_cast<uint8_t>(block)];
+              if (entry < 0) {
+                entry = static_cast<int>(result.palette.size() - start);
+                result.palette.push_back(static_cast<uint32_t>(block));
+              }
+              indices[bit | (y << (2 * kBrickBits))] =
+                  static_cast<uint8_t>(entry);
+            }
+          }
+        }
+
+        auto empty = true;
+        for (const auto mask : occupancy) empty &= mask == 0;
+        if (empty) {
+          result.palette.resize(start);
+          continue;
+        }
+
+        const auto size = result.palette.size() - start;
+        result.count++;
+        result.bricks.push_back(static_cast<uint32_t>(bx | by << 8 | bz << 16));
+        result.bricks.push_back(static_cast<uint32_t>(start | size << 16));
+        for (const auto mask : occupancy) {
+          result.bricks.push_back(static_cast<uint32_t>(mask));
+          result.bricks.push_back(static_cast<uint32_t>(mask >> 32));
+        }
+        for (auto i = 0; i < kBrickVolume; i += 4) {
+          result.indices.push_back(
+              static_cast<uint32_t>(indices[i + 0]) << 0  |
+              static_cast<uint32_t>(indices[i + 1]) << 8  |
+              static_cast<uint32_t>(indices[i + 2]) << 16 |
+              static_cast<uint32_t>(indices[i + 3]) << 24);
+        }
+      }
+    }
+  }
+}
+
+//////////////////////////////////////////////////////////////////////////////
+
+} // namespace voxels
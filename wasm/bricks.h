@@ -0,0 +1,44 @@
+#pragma once
+
+#include <cstdint>
+#include <vector>
+
+#include "base.h"
+
+//////////////////////////////////////////////////////////////////////////////
+
+namespace voxels {
+
+//////////////////////////////////////////////////////////////////////////////
+
+// A GPU-friendly export of a chunk's voxels, for compute-shader ray marching.
+// We split the chunk into 8 x 8 x 8 bricks and skip bricks that are all air.
+// Each brick is a fixed-size record of kBrickWords uint32s:
+//
+//   - pos:       bx | by << 8 | bz << 16, in bricks within the chunk
+//   - palette:   offset | size << 16, into the shared palette array
+//   - occupancy: eight 64-bit masks, as (lo, hi) pairs. Bit (x | z << 3) of
+//                the y-th mask is set if the voxel at (x, y, z) isn't air.
+//
+// Palettes list the distinct blocks in each brick, one per uint32. Indices
+// hold an 8-bit palette index per voxel, 4 to a uint32, with kBrickVolume
+// indices per brick in (x | z << 3 | y << 6) order, like the masks' bits.
+
+constexpr int kBrickBits = 3;
+constexpr int kBrickSize = 1 << kBrickBits;
+constexpr int kBrickVolume = kBrickSize * kBrickSize * kBrickSize;
+constexpr int kBrickWords = 2 + 2 * kBrickSize;
+
+struct BrickExport {
+  int count = 0;
+  std::vector<uint32_t> bricks;
+  std::vector<uint32_t> palette;
+  std::vector<uint32_t> indices;
+};
+
+// Takes a chunk's voxels in chunk index order. Clears the result first.
+void exportBricks(const Block* voxels, BrickExport& result);
+
+//////////////////////////////////////////////////////////////////////////////
+
+} // namespace voxels
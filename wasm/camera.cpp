@@ -0,0 +1,23 @@
+#include "camera.h"
+
+//////////////////////////////////////////////////////////////////////////////
+
+namespace voxels {
+
+//////////////////////////////////////////////////////////////////////////////
+
+double CameraSmoother::update(double target, double dt, double rate,
+                              bool snap_below) {
+  const auto snap = snap_below ? target < value : target > value;
+  if (!valid || snap || rate <= 0) {
+    value = target;
+    valid = true;
+    return value;
+  }
+  value += (target - value) * (1 - std::exp(-rate * std::max(dt, 0.0)));
+  return value;
+}
+
+//////////////////////////////////////////////////////////////////////////////
+
+} // namespace voxels
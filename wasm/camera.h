@@ -0,0 +1,157 @@
+#pragma once
+
+#include <array>
+#include <cmath>
+
+#include "base.h"
+
+//////////////////////////////////////////////////////////////////////////////
+
+namespace voxels {
+
+//////////////////////////////////////////////////////////////////////////////
+
+// Camera-support queries, so that the host's cameras agree with the voxels
+// about where they are. These helpers take a callback that classifies voxels
+// for the camera: walls are opaque blocks, which the camera must stay out
+// of, and liquids tint the screen when the camera is under their surface.
+//
+// We model the camera as a box with the given half-width around its eye, so
+// the near plane never pokes into terrain. A liquid's surface is lowered by
+// `wave` when the voxel above it isn't the same liquid, to match the mesher.
+
+enum class CameraCell : uint8_t { Open, Liquid, Wall };
+
+// Returns the fraction of the camera box's height that's under the surface
+// of a liquid, in the camera's column.
+template <typename Fn>
+double getSubmergedFraction(const std::array<double, 3>& eye, double radius,
+                            double wave, Fn cell) {
+  const auto x = static_cast<int>(std::floor(eye[0]));
+  const auto z = static_cast<int>(std::floor(eye[2]));
+  const auto y0 = eye[1] - radius, y1 = eye[1] + radius;
+  const auto height = y1 - y0;
+
+  auto total = 0.0;
+  auto point = false;
+  for (auto y = static_cast<int>(std::floor(y0));
+       y <= static_cast<int>(std::floor(y1)); y++) {
+    if (cell(x, y, z) != CameraCell::Liquid) continue;
+    const auto capped = cell(x, y + 1, z) == CameraCell::Liquid;
+    const auto surface = y + 1 - (capped ? 0 : wave);
+    total += std::max(std::min(surface, y1) - std::max<double>(y, y0), 0.0);
+    if (y <= eye[1] && eye[1] < surface) point = true;
+  }
+  if (height <= 0) return point ? 1 : 0;
+  return std::min(total / height, 1.0);
+}
+
+template <typename Fn>
+bool boxHitsWall(const std::array<double, 3>& eye, double radius, Fn cell) {
+  std::array<int, 3> lo, hi;
+  for (auto i = 0; i < 3; i++) {
+    lo[i] = static_cast<int>(std::floor(eye[i] - radius));
+    hi[i] = static_cast<int>(std::ceil(eye[i] + radius)) - 1;
+  }
+  for (auto x = lo[0]; x <= hi[0]; x++) {
+    for (auto y = lo[1]; y <= hi[1]; y++) {
+      for (auto z = lo[2]; z <= hi[2]; z++) {
+        if (cell(x, y, z) == CameraCell::Wall) return true;
+      }
+    }
+  }
+  return false;
+}
+
+// Sweeps the camera box from the target (usually the player's head) along
+// the offset toward the desired camera position, and returns the distance
+// that it can move before it hits a wall. We march in steps of at most
+// kClipStep, which is thinner than any wall, and then bisect the last step.
+template <typename Fn>
+double clipCamera(const std::array<double, 3>& target,
+                  const std::array<double, 3>& offset, double radius,
+                  Fn cell) {
+  constexpr auto kClipStep = 1.0 / 16;
+  constexpr auto kBisections = 8;
+
+  auto dir = offset;
+  const auto length =
+      std::sqrt(dir[0] * dir[0] + dir[1] * dir[1] + dir[2] * dir[2]);
+  if (length == 0) return 0;
+  for (auto& x : dir) x /= length;
+
+  const auto at = [&](double t) {
+    return std::array{target[0] + t * dir[0], target[1] + t * dir[1],
+                      target[2] + t * dir[2]};
+  };
+  if (boxHitsWall(at(0), radius, cell)) return 0;
+
+  auto free = 0.0;
+  while (free < length) {
+    const auto next = std::min(free + kClipStep, length);
+    if (boxHitsWall(at(next), radius, cell)) {
+      auto blocked = next;
+      for (auto i = 0; i < kBisections; i++) {
+        const auto mid = (free + blocked) / 2;
+        (boxHitsWall(at(mid), radius, cell) ? blocked : free) = mid;
+      }
+      return free;
+    }
+    free = next;
+  }
+  return length;
+}
+
+// Moves the eye the shortest distance along one axis, up to max_push, so
+// that the camera box doesn't overlap any walls. Returns false and leaves
+// the eye alone if there's no such move.
+template <typename Fn>
+bool pushOutOfWalls(std::array<double, 3>& eye, double radius,
+                    double max_push, Fn cell) {
+  const auto blocked = [&](const std::array<double, 3>& p) {
+    return boxHitsWall(p, radius, cell);
+  };
+  if (!blocked(eye)) return true;
+
+  // The candidate moves put the box's trailing face just past a voxel face.
+  constexpr auto kEpsilon = 1e-3;
+  auto best = max_push;
+  auto found = false;
+  auto result = eye;
+  for (auto axis = 0; axis < 3; axis++) {
+    for (const auto sign : {-1, 1}) {
+      const auto edge = eye[axis] - sign * radius;
+      auto face = sign > 0 ? std::floor(edge) + 1 : std::ceil(edge) - 1;
+      for (;; face += sign) {
+        const auto move = std::abs(face - edge) + kEpsilon;
+        if (move > best) break;
+        auto p = eye;
+        p[axis] += sign * move;
+        if (blocked(p)) continue;
+        best = move;
+        found = true;
+        result = p;
+        break;
+      }
+    }
+  }
+  if (found) eye = result;
+  return found;
+}
+
+// Eases a camera value toward its target at the given rate per second. If
+// snap_below is set, the value drops to lower targets at once and only eases
+// up toward higher ones; otherwise, the reverse. Cameras snap away from walls
+// and ease back, so they never clip.
+struct CameraSmoother {
+  double update(double target, double dt, double rate, bool snap_below);
+  void reset() { valid = false; }
+
+ private:
+  double value = 0;
+  bool valid = false;
+};
+
+//////////////////////////////////////////////////////////////////////////////
+
+} // namespace voxels
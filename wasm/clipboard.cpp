@@ -0,0 +1,79 @@
+#include "clipboard.h"
+
+#include <algorithm>
+
+//////////////////////////////////////////////////////////////////////////////
+
+namespace voxels {
+
+//////////////////////////////////////////////////////////////////////////////
+
+namespace {
+
+// Returns the voxel's offset from the center of an axis of the given size,
+// relative to the semi-axis r, for the ellipsoid tests below.
+double normalize(int x, int size, double r) {
+  return (x + 0.5 - 0.5 * size) / r;
+}
+
+} // namespace
+
+//////////////////////////////////////////////////////////////////////////////
+
+Clipboard::Clipboard(int sx, int sy, int sz, Block fill) {
+  dims = {std::clamp(sx, 1, kMaxClipboardSize),
+          std::clamp(sy, 1, kMaxClipboardSize),
+          std::clamp(sz, 1, kMaxClipboardSize)};
+  voxels.assign(dims[0] * dims[1] * dims[2], fill);
+}
+
+Clipboard Clipboard::shape(ClipboardShape shape, int sx, int sy, int sz,
+                           Block block, int shell) {
+  auto result = Clipboard(sx, sy, sz);
+  const auto [nx, ny, nz] = result.dims;
+  shell = std::max(shell, 0);
+
+  // Voxels inside the ellipsoid, or the elliptic cylinder if !use_y, with
+  // the given semi-axes. Non-positive semi-axes contain nothing.
+  const auto within = [&](int x, int y, int z, double rx, double ry,
+                          double rz, bool use_y) {
+    if (rx <= 0 || (use_y && ry <= 0) || rz <= 0) return false;
+    const auto u = normalize(x, nx, rx);
+    const auto v = use_y ? normalize(y, ny, ry) : 0.0;
+    const auto w = normalize(z, nz, rz);
+    return u * u + v * v + w * w <= 1;
+  };
+
+  const auto contains = [&](int x, int y, int z) {
+    switch (shape) {
+      case ClipboardShape::Box: {
+        if (shell == 0) return true;
+        const auto depth = std::min({x, nx - 1 - x, y, ny - 1 - y,
+                                     z, nz - 1 - z});
+        return depth < shell;
+      }
+      case ClipboardShape::Sphere:
+      case ClipboardShape::Cylinder: {
+        const auto use_y = shape == ClipboardShape::Sphere;
+        const auto rx = 0.5 * nx, ry = 0.5 * ny, rz = 0.5 * nz;
+        if (!within(x, y, z, rx, ry, rz, use_y)) return false;
+        if (shell == 0) return true;
+        return !within(x, y, z, rx - shell, ry - shell, rz - shell, use_y);
+      }
+    }
+    return false;
+  };
+
+  for (auto z = 0; z < nz; z++) {
+    for (auto x = 0; x < nx; x++) {
+      for (auto y = 0; y < ny; y++) {
+        if (contains(x, y, z)) result.set(x, y, z, block);
+      }
+    }
+  }
+  return result;
+}
+
+//////////////////////////////////////////////////////////////////////////////
+
+} // namespace voxels
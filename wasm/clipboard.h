@@ -0,0 +1,60 @@
+#pragma once
+
+#include <array>
+#include <vector>
+
+#include "base.h"
+
+//////////////////////////////////////////////////////////////////////////////
+
+namespace voxels {
+
+//////////////////////////////////////////////////////////////////////////////
+
+// Clipboards: boxes of voxels, copied from the world or generated as shapes,
+// that building tools combine with the world by CSG. See World::applyCsg.
+// Voxels are stored with y as the fastest axis, like chunks.
+//
+// Air voxels are empty. Union places the clipboard's other voxels, Subtract
+// carves them out of the world, and Intersect clears the world under the
+// empty ones. Intersect only acts within the clipboard's bounds, so it trims
+// the world in the box rather than clearing everything outside the shape.
+// Unknown voxels, copied from unloaded chunks, leave the world as it is.
+
+constexpr int kMaxClipboardSize = 256;
+
+enum class CsgOp : uint8_t { Union, Subtract, Intersect };
+
+// Shapes fill their box. Spheres and cylinders are ellipsoidal if the box
+// isn't square, and cylinders run along the y axis. A nonzero shell keeps
+// that many layers inside the surface and leaves the rest empty: a hollow
+// box or sphere, or a tube, since cylinders don't get end caps.
+enum class ClipboardShape : uint8_t { Box, Sphere, Cylinder };
+
+struct Clipboard {
+  // Sizes are clamped to [1, kMaxClipboardSize].
+  Clipboard(int sx, int sy, int sz, Block fill = Block::Air);
+
+  static Clipboard shape(ClipboardShape shape, int sx, int sy, int sz,
+                         Block block, int shell);
+
+  const std::array<int, 3>& size() const { return dims; }
+
+  Block get(int x, int y, int z) const { return voxels[index(x, y, z)]; }
+  void set(int x, int y, int z, Block block) { voxels[index(x, y, z)] = block; }
+
+ private:
+  int index(int x, int y, int z) const {
+    assert(0 <= x && x < dims[0]);
+    assert(0 <= y && y < dims[1]);
+    assert(0 <= z && z < dims[2]);
+    return y + dims[1] * (x + dims[0] * z);
+  }
+
+  std::array<int, 3> dims;
+  std::vector<Block> voxels;
+};
+
+//////////////////////////////////////////////////////////////////////////////
+
+} // namespace voxels
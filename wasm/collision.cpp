@@ -0,0 +1,129 @@
+#include "collision.h"
+
+#include <algorithm>
+
+//////////////////////////////////////////////////////////////////////////////
+
+namespace voxels {
+
+//////////////////////////////////////////////////////////////////////////////
+
+namespace {
+
+constexpr int sizeX(int level) { return kChunkWidth >> level; }
+constexpr int sizeY(int level) { return kWorldHeight >> level; }
+
+constexpr int cellIndex(int level, int x, int y, int z) {
+  return y + sizeY(level) * (x + sizeX(level) * z);
+}
+
+uint32_t nextVersion() {
+  static uint32_t version = 0;
+  return ++version;
+}
+
+} // namespace
+
+//////////////////////////////////////////////////////////////////////////////
+
+CollisionField::CollisionField() {
+  for (auto l = 0; l < kCollisionLevels; l++) {
+    const auto cells = sizeX(l) * sizeY(l) * sizeX(l);
+    levels[l].resize((cells + 63) / 64, 0);
+  }
+}
+
+void CollisionField::build(const Registry& registry, const Block* voxels) {
+  for (auto z = 0; z < kChunkWidth; z++) {
+    for (auto x = 0; x < kChunkWidth; x++) {
+      for (auto y = 0; y < kWorldHeight; y++) {
+        const auto block = voxels[ChunkTensor3<Block>::index(x, y, z)];
+        setBit(0, x, y, z, registry.getBlockUnsafe(block).solid);
+      }
+    }
+  }
+  for (auto l = 1; l < kCollisionLevels; l++) {
+    for (auto z = 0; z < sizeX(l); z++) {
+      for (auto x = 0; x < sizeX(l); x++) {
+        for (auto y = 0; y < sizeY(l); y++) setMip(l, x, y, z);
+      }
+    }
+  }
+  version = nextVersion();
+}
+
+void CollisionField::update(const Registry& registry, const Block* voxels,
+                            int x, int y, int z) {
+  assert(0 <= x && x < kChunkWidth);
+  assert(0 <= y && y < kWorldHeight);
+  assert(0 <= z && z < kChunkWidth);
+
+  const auto block = voxels[ChunkTensor3<Block>::index(x, y, z)];
+  const auto solid = registry.getBlockUnsafe(block).solid;
+  if (get(0, x, y, z) == solid) return;
+
+  setBit(0, x, y, z, solid);
+  for (auto l = 1; l < kCollisionLevels; l++) setMip(l, x >> l, y >> l, z >> l);
+  version = nextVersion();
+}
+
+bool CollisionField::get(int level, int x, int y, int z) const {
+  assert(0 <= level && level < kCollisionLevels);
+  const auto index = cellIndex(level, x, y, z);
+  return (levels[level][index >> 6] >> (index & 63)) & 1;
+}
+
+uint64_t CollisionField::getRun(int x, int y, int z) const {
+  return levels[0][cellIndex(0, x, y, z) >> 6];
+}
+
+bool CollisionField::anySolid(
+    int x0, int y0, int z0, int x1, int y1, int z1) const {
+  assert(0 <= x0 && x0 <= x1 && x1 < kChunkWidth);
+  assert(0 <= y0 && y0 <= y1 && y1 < kWorldHeight);
+  assert(0 <= z0 && z0 <= z1 && z1 < kChunkWidth);
+
+  for (auto z = z0; z <= z1; z++) {
+    for (auto x = x0; x <= x1; x++) {
+      for (auto y = y0; y <= y1;) {
+        const auto offset = y & 63;
+        const auto count = std::min(64 - offset, y1 - y + 1);
+        const auto bits = count == 64 ? ~uint64_t{0}
+                                      : ((uint64_t{1} << count) - 1);
+        if (getRun(x, y, z) & (bits << offset)) return true;
+        y += count;
+      }
+    }
+  }
+  return false;
+}
+
+const uint64_t* CollisionField::getLevel(int level) const {
+  assert(0 <= level && level < kCollisionLevels);
+  return levels[level].data();
+}
+
+int CollisionField::getLevelWords(int level) const {
+  assert(0 <= level && level < kCollisionLevels);
+  return static_cast<int>(levels[level].size());
+}
+
+void CollisionField::setBit(int level, int x, int y, int z, bool value) {
+  const auto index = cellIndex(level, x, y, z);
+  const auto bit = uint64_t{1} << (index & 63);
+  auto& word = levels[level][index >> 6];
+  word = value ? (word | bit) : (word & ~bit);
+}
+
+void CollisionField::setMip(int level, int x, int y, int z) {
+  auto any = false;
+  for (auto i = 0; i < 8 && !any; i++) {
+    any = get(level - 1, 2 * x + ((i & 1) ? 1 : 0), 2 * y + ((i & 2) ? 1 : 0),
+              2 * z + ((i & 4) ? 1 : 0));
+  }
+  setBit(level, x, y, z, any);
+}
+
+//////////////////////////////////////////////////////////////////////////////
+
+} // namespace voxels
@@ -0,0 +1,68 @@
+#pragma once
+
+#include <array>
+#include <cstdint>
+#include <vector>
+
+#include "base.h"
+#include "mesher.h"
+
+//////////////////////////////////////////////////////////////////////////////
+
+namespace voxels {
+
+//////////////////////////////////////////////////////////////////////////////
+
+// A mip-mapped solidity bitmask for a chunk, for GPU particle systems that
+// collide with terrain, and for our own raycasts and physics queries. Level
+// l has (kChunkWidth >> l) x (kWorldHeight >> l) x (kChunkWidth >> l) cells,
+// down to a 1 x 16 x 1 column at the last level, and a cell's bit is set if
+// any voxel in it is solid.
+//
+// Cells are in chunk index order: y is the fastest axis, then x, then z.
+// Bits are packed low bit first into 64-bit words, so each level can also
+// be read as little-endian 32-bit words.
+//
+// We keep every loaded chunk's field up to date on edits; a block change
+// touches one bit per level. Each change also takes a new version number,
+// unique across chunks, so the host knows when to upload a field again.
+
+constexpr int kCollisionLevels = kChunkBits + 1;
+
+struct CollisionField {
+  CollisionField();
+
+  // Recomputes every level from voxels, in chunk index order.
+  void build(const Registry& registry, const Block* voxels);
+
+  // Call this after changing the block at (x, y, z) in `voxels`.
+  void update(const Registry& registry, const Block* voxels,
+              int x, int y, int z);
+
+  bool get(int level, int x, int y, int z) const;
+
+  // Returns the level-0 word with the 64-voxel run of the column at (x, z)
+  // that contains y. Runs are aligned to multiples of 64 in y.
+  uint64_t getRun(int x, int y, int z) const;
+
+  // Returns true if any voxel in the inclusive box is solid. We test each
+  // column's bits a word at a time.
+  bool anySolid(int x0, int y0, int z0, int x1, int y1, int z1) const;
+
+  const uint64_t* getLevel(int level) const;
+  int getLevelWords(int level) const;
+  uint32_t getVersion() const { return version; }
+
+ private:
+  void setBit(int level, int x, int y, int z, bool value);
+  void setMip(int level, int x, int y, int z);
+
+  std::array<std::vector<uint64_t>, kCollisionLevels> levels;
+  uint32_t version = 0;
+
+  DISALLOW_COPY_AND_ASSIGN(CollisionField);
+};
+
+//////////////////////////////////////////////////////////////////////////////
+
+} // namespace voxels
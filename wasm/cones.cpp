@@ -0,0 +1,95 @@
+#include "cones.h"
+
+#include <algorithm>
+
+//////////////////////////////////////////////////////////////////////////////
+
+namespace voxels {
+
+//////////////////////////////////////////////////////////////////////////////
+
+namespace {
+
+constexpr int kOpaqueOccupancy = 255;
+constexpr int kPartialOccupancy = 128;
+constexpr int kEmissionScale = 17;
+
+constexpr int sizeX(int level) { return kChunkWidth >> level; }
+constexpr int sizeY(int level) { return kWorldHeight >> level; }
+
+constexpr int cellIndex(int level, int x, int y, int z) {
+  return 2 * (y + sizeY(level) * (x + sizeX(level) * z));
+}
+
+} // namespace
+
+//////////////////////////////////////////////////////////////////////////////
+
+ConeVolume::ConeVolume(const Registry& registry, const Block* voxels)
+    : registry(registry) {
+  for (auto l = 0; l < kConeLevels; l++) {
+    levels[l].resize(2 * sizeX(l) * sizeY(l) * sizeX(l), 0);
+  }
+  for (auto z = 0; z < kChunkWidth; z++) {
+    for (auto x = 0; x < kChunkWidth; x++) {
+      for (auto y = 0; y < kWorldHeight; y++) setBase(voxels, x, y, z);
+    }
+  }
+  for (auto l = 1; l < kConeLevels; l++) {
+    for (auto z = 0; z < sizeX(l); z++) {
+      for (auto x = 0; x < sizeX(l); x++) {
+        for (auto y = 0; y < sizeY(l); y++) setMip(l, x, y, z);
+      }
+    }
+  }
+}
+
+void ConeVolume::update(const Block* voxels, int x, int y, int z) {
+  assert(0 <= x && x < kChunkWidth);
+  assert(0 <= y && y < kWorldHeight);
+  assert(0 <= z && z < kChunkWidth);
+
+  setBase(voxels, x, y, z);
+  for (auto l = 1; l < kConeLevels; l++) setMip(l, x >> l, y >> l, z >> l);
+}
+
+const uint8_t* ConeVolume::getLevel(int level) const {
+  assert(0 <= level && level < kConeLevels);
+  return levels[level].data();
+}
+
+int ConeVolume::getLevelBytes(int level) const {
+  assert(0 <= level && level < kConeLevels);
+  return static_cast<int>(levels[level].size());
+}
+
+void ConeVolume::setBase(const Block* voxels, int x, int y, int z) {
+  const auto block = voxels[ChunkTensor3<Block>::index(x, y, z)];
+  const auto& data = registry.getBlockUnsafe(block);
+  const auto occupancy =
+      data.opaque ? kOpaqueOccupancy : data.mesh ? kPartialOccupancy : 0;
+  const auto emission = kEmissionScale * std::max<int>(data.light, 0);
+
+  const auto index = cellIndex(0, x, y, z);
+  levels[0][index + 0] = static_cast<uint8_t>(occupancy);
+  levels[0][index + 1] = static_cast<uint8_t>(std::min(emission, 0xff));
+}
+
+void ConeVolume::setMip(int level, int x, int y, int z) {
+  const auto& below = levels[level - 1];
+  auto occupancy = 0, emission = 0;
+  for (auto i = 0; i < 8; i++) {
+    const auto index = cellIndex(level - 1, 2 * x + (i & 1),
+                                 2 * y + ((i >> 1) & 1), 2 * z + (i >> 2));
+    occupancy += below[index + 0];
+    emission += below[index + 1];
+  }
+
+  const auto index = cellIndex(level, x, y, z);
+  levels[level][index + 0] = static_cast<uint8_t>((occupancy + 4) / 8);
+  levels[level][index + 1] = static_cast<uint8_t>((emission + 4) / 8);
+}
+
+//////////////////////////////////////////////////////////////////////////////
+
+} // namespace voxels
@@ -0,0 +1,52 @@
+#pragma once
+
+#include <array>
+#include <cstdint>
+#include <vector>
+
+#include "base.h"
+#include "mesher.h"
+
+//////////////////////////////////////////////////////////////////////////////
+
+namespace voxels {
+
+//////////////////////////////////////////////////////////////////////////////
+
+// Mip-mapped occupancy and emission volumes for a chunk, for shaders that do
+// voxel cone tracing. Level l has (kChunkWidth >> l) x (kWorldHeight >> l) x
+// (kChunkWidth >> l) cells, down to a 1 x 16 x 1 column at the last level.
+//
+// Each cell is two bytes: occupancy, then emission. At level 0, occupancy is
+// 255 for opaque blocks, 128 for other meshed blocks (leaves, water), and 0
+// for air; emission is the block's light level, scaled to [0, 255]. Higher
+// levels average the 8 cells below them. Cells are in chunk index order:
+// y is the fastest axis, then x, then z.
+//
+// We update the mip chain incrementally on edits: a block change touches one
+// cell per level.
+
+constexpr int kConeLevels = kChunkBits + 1;
+
+struct ConeVolume {
+  ConeVolume(const Registry& registry, const Block* voxels);
+
+  // Call this after changing the block at (x, y, z) in `voxels`.
+  void update(const Block* voxels, int x, int y, int z);
+
+  const uint8_t* getLevel(int level) const;
+  int getLevelBytes(int level) const;
+
+ private:
+  void setBase(const Block* voxels, int x, int y, int z);
+  void setMip(int level, int x, int y, int z);
+
+  const Registry& registry;
+  std::array<std::vector<uint8_t>, kConeLevels> levels;
+
+  DISALLOW_COPY_AND_ASSIGN(ConeVolume);
+};
+
+//////////////////////////////////////////////////////////////////////////////
+
+} // namespace voxels
@@ -0,0 +1,163 @@
+#include "crafting.h"
+
+#include <algorithm>
+
+//////////////////////////////////////////////////////////////////////////////
+
+namespace voxels {
+
+//////////////////////////////////////////////////////////////////////////////
+
+namespace {
+
+bool empty(const Ingredient& ingredient) {
+  return ingredient.item == kNoItem && ingredient.tags == 0;
+}
+
+bool matches(const ItemRegistry& items, const Ingredient& ingredient,
+             int item) {
+  if (empty(ingredient)) return item == kNoItem;
+  if (item == kNoItem) return false;
+  if (ingredient.item != kNoItem) return ingredient.item == item;
+  return items.hasAnyTag(item, ingredient.tags);
+}
+
+// The inclusive bounds of the non-empty cells of a row-major grid. If every
+// cell is empty, x0 > x1 and y0 > y1.
+struct Bounds { int x0, y0, x1, y1; };
+
+template <typename Fn>
+Bounds getBounds(int width, int height, Fn nonempty) {
+  Bounds result{width, height, -1, -1};
+  for (auto y = 0; y < height; y++) {
+    for (auto x = 0; x < width; x++) {
+      if (!nonempty(x + width * y)) continue;
+      result.x0 = std::min(result.x0, x);
+      result.y0 = std::min(result.y0, y);
+      result.x1 = std::max(result.x1, x);
+      result.y1 = std::max(result.y1, y);
+    }
+  }
+  return result;
+}
+
+} // namespace
+
+//////////////////////////////////////////////////////////////////////////////
+
+int RecipeBook::addShaped(int width, int height, const Ingredient* pattern,
+                          int result, int count) {
+  assert(width > 0 && height > 0);
+  const auto bounds = getBounds(
+      width, height, [&](int i) { return !empty(pattern[i]); });
+  assert(bounds.x0 <= bounds.x1);
+
+  Recipe recipe{true, bounds.x1 - bounds.x0 + 1, bounds.y1 - bounds.y0 + 1,
+                {}, result, count};
+  for (auto y = bounds.y0; y <= bounds.y1; y++) {
+    for (auto x = bounds.x0; x <= bounds.x1; x++) {
+      recipe.ingredients.push_back(pattern[x + width * y]);
+    }
+  }
+  recipes.push_back(std::move(recipe));
+  return static_cast<int>(recipes.size()) - 1;
+}
+
+int RecipeBook::addShapeless(const Ingredient* ingredients, int size,
+                             int result, int count) {
+  assert(size > 0);
+  Recipe recipe{false, 0, 0, {ingredients, ingredients + size},
+                result, count};
+  for (const auto& ingredient : recipe.ingredients) {
+    assert(!empty(ingredient));
+  }
+  recipes.push_back(std::move(recipe));
+  return static_cast<int>(recipes.size()) - 1;
+}
+
+const Recipe* RecipeBook::get(int id) const {
+  return 0 <= id && id < recipes.size() ? &recipes[id] : nullptr;
+}
+
+int RecipeBook::match(const ItemRegistry& items, const int* grid,
+                      int width, int height) const {
+  if (width <= 0 || height <= 0) return -1;
+  const auto bounds = getBounds(
+      width, height, [&](int i) { return grid[i] != kNoItem; });
+  if (bounds.x0 > bounds.x1) return -1;
+
+  const auto w = bounds.x1 - bounds.x0 + 1;
+  const auto h = bounds.y1 - bounds.y0 + 1;
+  const auto start = grid + bounds.x0 + width * bounds.y0;
+
+  static std::vector<int> cells;
+  cells.clear();
+  for (auto i = 0; i < width * height; i++) {
+    if (grid[i] != kNoItem) cells.push_back(grid[i]);
+  }
+
+  for (auto i = 0; i < recipes.size(); i++) {
+    const auto& recipe = recipes[i];
+    if (recipe.shaped) {
+      if (recipe.width != w || recipe.height != h) continue;
+      if (matchShaped(items, recipe, start, width, false) ||
+          matchShaped(items, recipe, start, width, true)) {
+        return static_cast<int>(i);
+      }
+    } else if (matchShapeless(items, recipe, cells)) {
+      return static_cast<int>(i);
+    }
+  }
+  return -1;
+}
+
+bool RecipeBook::matchShaped(const ItemRegistry& items, const Recipe& recipe,
+                             const int* grid, int stride, bool mirror) const {
+  for (auto y = 0; y < recipe.height; y++) {
+    for (auto x = 0; x < recipe.width; x++) {
+      const auto px = mirror ? recipe.width - x - 1 : x;
+      const auto& ingredient = recipe.ingredients[px + recipe.width * y];
+      if (!matches(items, ingredient, grid[x + stride * y])) return false;
+    }
+  }
+  return true;
+}
+
+bool RecipeBook::matchShapeless(const ItemRegistry& items,
+                                const Recipe& recipe,
+                                const std::vector<int>& cells) const {
+  // Tag ingredients can overlap, so a greedy assignment may fail where some
+  // other one works. We find a perfect matching of ingredients to cells with
+  // augmenting paths. Grids are small, so the cubic bound is fine.
+  const auto n = static_cast<int>(cells.size());
+  if (recipe.ingredients.size() != n) return false;
+
+  static std::vector<int> owner;
+  static std::vector<bool> visited;
+  owner.assign(n, -1);
+
+  const auto augment = [&](int ingredient, auto& self) -> bool {
+    for (auto cell = 0; cell < n; cell++) {
+      if (visited[cell]) continue;
+      if (!matches(items, recipe.ingredients[ingredient], cells[cell])) {
+        continue;
+      }
+      visited[cell] = true;
+      if (owner[cell] < 0 || self(owner[cell], self)) {
+        owner[cell] = ingredient;
+        return true;
+      }
+    }
+    return false;
+  };
+
+  for (auto i = 0; i < n; i++) {
+    visited.assign(n, false);
+    if (!augment(i, augment)) return false;
+  }
+  return true;
+}
+
+//////////////////////////////////////////////////////////////////////////////
+
+} // namespace voxels
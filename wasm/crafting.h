@@ -0,0 +1,68 @@
+#pragma once
+
+#include <cstdint>
+#include <vector>
+
+#include "base.h"
+#include "items.h"
+
+//////////////////////////////////////////////////////////////////////////////
+
+namespace voxels {
+
+//////////////////////////////////////////////////////////////////////////////
+
+// A registry of crafting recipes, and a matcher for crafting grids. Grids
+// are row-major arrays of items, with kNoItem in empty cells, so cell (x, y)
+// is at index x + width * y.
+//
+// A shaped recipe is a pattern of ingredients that must appear in the grid
+// in the same arrangement, or mirrored left-to-right, anywhere in it. We trim
+// empty rows and columns from patterns and from the grid, so a 2x2 pattern
+// matches in any corner of a 3x3 grid. A shapeless recipe needs each of its
+// ingredients in some non-empty cell, in any arrangement, and nothing else.
+//
+// If several recipes match a grid, the first one registered wins.
+
+// Matches the given item or, if item is kNoItem, any item with one of the
+// given tags. In a shaped pattern, an ingredient with neither is an empty
+// cell, and only an empty grid cell matches it.
+struct Ingredient {
+  int item = kNoItem;
+  uint32_t tags = 0;
+};
+
+struct Recipe {
+  bool shaped;
+  int width;
+  int height;
+  std::vector<Ingredient> ingredients;
+  int result;
+  int count;
+};
+
+struct RecipeBook {
+  // Both return the new recipe's id. Patterns are row-major, like grids.
+  int addShaped(int width, int height, const Ingredient* pattern,
+                int result, int count);
+  int addShapeless(const Ingredient* ingredients, int size,
+                   int result, int count);
+
+  const Recipe* get(int id) const;
+
+  // Returns the id of the first recipe that the grid matches, or -1.
+  int match(const ItemRegistry& items, const int* grid,
+            int width, int height) const;
+
+ private:
+  bool matchShaped(const ItemRegistry& items, const Recipe& recipe,
+                   const int* grid, int stride, bool mirror) const;
+  bool matchShapeless(const ItemRegistry& items, const Recipe& recipe,
+                      const std::vector<int>& cells) const;
+
+  std::vector<Recipe> recipes;
+};
+
+//////////////////////////////////////////////////////////////////////////////
+
+} // namespace voxels
@@ -0,0 +1,65 @@
+#include "debug.h"
+
+#include <cstring>
+
+//////////////////////////////////////////////////////////////////////////////
+
+namespace voxels {
+
+//////////////////////////////////////////////////////////////////////////////
+
+void DebugDraw::line(uint32_t flag, const std::array<double, 3>& a,
+                     const std::array<double, 3>& b, uint32_t color) {
+  push(DebugShape::Line, flag, color, a, b, nullptr);
+}
+
+void DebugDraw::box(uint32_t flag, const std::array<double, 3>& min,
+                    const std::array<double, 3>& max, uint32_t color) {
+  push(DebugShape::Box, flag, color, min, max, nullptr);
+}
+
+void DebugDraw::text(uint32_t flag, const std::array<double, 3>& anchor,
+                     uint32_t color, const std::string& value) {
+  push(DebugShape::Text, flag, color, anchor, anchor, &value);
+}
+
+const uint8_t* DebugDraw::drain() {
+  const int32_t header[2] = {static_cast<int32_t>(commands.size()),
+                             static_cast<int32_t>(texts.size())};
+  const auto bytes = commands.size() * sizeof(DebugCommand);
+  result.resize(sizeof(header) + bytes + texts.size());
+  memcpy(result.data(), header, sizeof(header));
+  memcpy(result.data() + sizeof(header), commands.data(), bytes);
+  memcpy(result.data() + sizeof(header) + bytes, texts.data(), texts.size());
+  commands.clear();
+  texts.clear();
+  return result.data();
+}
+
+void DebugDraw::push(DebugShape shape, uint32_t flag, uint32_t color,
+                     const std::array<double, 3>& a,
+                     const std::array<double, 3>& b,
+                     const std::string* value) {
+  if (!enabled(flag) || commands.size() >= kMaxDebugCommands) return;
+
+  DebugCommand command{shape, flag, color, {}, {}, 0, 0};
+  for (auto i = 0; i < 3; i++) {
+    command.a[i] = static_cast<float>(a[i]);
+    command.b[i] = static_cast<float>(b[i]);
+  }
+  if (value) {
+    command.text_offset = static_cast<int32_t>(texts.size());
+    command.text_size = static_cast<int32_t>(value->size());
+    texts += *value;
+  }
+  commands.push_back(command);
+}
+
+DebugDraw& getDebugDraw() {
+  static DebugDraw debug_draw;
+  return debug_draw;
+}
+
+//////////////////////////////////////////////////////////////////////////////
+
+} // namespace voxels
@@ -0,0 +1,81 @@
+#pragma once
+
+#include <array>
+#include <cstdint>
+#include <string>
+#include <vector>
+
+#include "base.h"
+
+//////////////////////////////////////////////////////////////////////////////
+
+namespace voxels {
+
+//////////////////////////////////////////////////////////////////////////////
+
+// A buffer of debug primitives that our subsystems append to as they work,
+// for the host to draw as an overlay. Each subsystem has a flag, and it only
+// draws when its flag is set, so the buffer costs nothing when it's off.
+// Positions are in world space and colors are 0xRRGGBBAA.
+//
+// The host drains the buffer once a frame. If it doesn't, we stop appending
+// at kMaxDebugCommands, rather than growing without bound.
+
+constexpr uint32_t kDebugLighting    = 1 << 0;
+constexpr uint32_t kDebugProjectiles = 1 << 1;
+constexpr uint32_t kDebugItemDrops   = 1 << 2;
+constexpr uint32_t kDebugCamera      = 1 << 3;
+
+constexpr int kMaxDebugCommands = 1 << 16;
+
+enum class DebugShape : int32_t { Line = 1, Box, Text };
+
+// Lines go from a to b. Boxes span [a, b]. Text is anchored at a, and it's
+// a slice of the buffer's text bytes.
+struct DebugCommand {
+  DebugShape shape;
+  uint32_t flag;
+  uint32_t color;
+  float a[3];
+  float b[3];
+  int32_t text_offset;
+  int32_t text_size;
+};
+
+static_assert(sizeof(DebugCommand) == 11 * sizeof(int32_t));
+
+struct DebugDraw {
+  DebugDraw() = default;
+
+  void setFlags(uint32_t value) { flags = value; }
+  bool enabled(uint32_t flag) const { return (flags & flag) != 0; }
+
+  void line(uint32_t flag, const std::array<double, 3>& a,
+            const std::array<double, 3>& b, uint32_t color);
+  void box(uint32_t flag, const std::array<double, 3>& min,
+           const std::array<double, 3>& max, uint32_t color);
+  void text(uint32_t flag, const std::array<double, 3>& anchor,
+            uint32_t color, const std::string& value);
+
+  // Returns the number of commands and the number of text bytes, as int32s,
+  // followed by the commands and then the text. Clears the buffer.
+  const uint8_t* drain();
+
+ private:
+  void push(DebugShape shape, uint32_t flag, uint32_t color,
+            const std::array<double, 3>& a, const std::array<double, 3>& b,
+            const std::string* value);
+
+  uint32_t flags = 0;
+  std::vector<DebugCommand> commands;
+  std::string texts;
+  std::vector<uint8_t> result;
+
+  DISALLOW_COPY_AND_ASSIGN(DebugDraw);
+};
+
+DebugDraw& getDebugDraw();
+
+//////////////////////////////////////////////////////////////////////////////
+
+} // namespace voxels
@@ -0,0 +1,44 @@
+#include "dimensions.h"
+
+#include <iterator>
+
+//////////////////////////////////////////////////////////////////////////////
+
+namespace voxels {
+
+//////////////////////////////////////////////////////////////////////////////
+
+void PortalLinks::link(const PortalEnd& a, const PortalEnd& b) {
+  unlink(a);
+  unlink(b);
+  links[key(a)] = b;
+  links[key(b)] = a;
+}
+
+void PortalLinks::unlink(const PortalEnd& a) {
+  const auto it = links.find(key(a));
+  if (it == links.end()) return;
+  links.erase(key(it->second));
+  links.erase(key(a));
+}
+
+void PortalLinks::unlinkDimension(int dimension) {
+  for (auto it = links.begin(); it != links.end();) {
+    const auto drop = it->first[0] == dimension ||
+                      it->second.dimension == dimension;
+    it = drop ? links.erase(it) : std::next(it);
+  }
+}
+
+const PortalEnd* PortalLinks::find(const PortalEnd& a) const {
+  const auto it = links.find(key(a));
+  return it == links.end() ? nullptr : &it->second;
+}
+
+PortalLinks::Key PortalLinks::key(const PortalEnd& end) {
+  return {end.dimension, end.x, end.y, end.z};
+}
+
+//////////////////////////////////////////////////////////////////////////////
+
+} // namespace voxels
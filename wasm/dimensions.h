@@ -0,0 +1,54 @@
+#pragma once
+
+#include <array>
+#include <map>
+
+#include "base.h"
+
+//////////////////////////////////////////////////////////////////////////////
+
+namespace voxels {
+
+//////////////////////////////////////////////////////////////////////////////
+
+// Bookkeeping for portals between dimensions. Each dimension is a separate
+// world, with its own chunks and lighting, and all of them share one block
+// registry. A portal end is a voxel in some dimension, and each end links to
+// at most one other end; links are two-way. Dimension ids are game-defined.
+//
+// We don't check that the voxels at either end are portal blocks: the game
+// decides what a portal looks like, and unlinks ends when it breaks them.
+
+struct PortalEnd {
+  int dimension;
+  int x;
+  int y;
+  int z;
+};
+
+struct PortalLinks {
+  // Links a and b to each other, dropping any links that either had.
+  void link(const PortalEnd& a, const PortalEnd& b);
+
+  // Drops the link at a, and the link back to a from its other end.
+  void unlink(const PortalEnd& a);
+
+  // Drops every link to or from the given dimension.
+  void unlinkDimension(int dimension);
+
+  // Returns the end that a links to, or nullptr if it isn't linked.
+  const PortalEnd* find(const PortalEnd& a) const;
+
+  size_t size() const { return links.size() / 2; }
+
+ private:
+  using Key = std::array<int, 4>;
+
+  static Key key(const PortalEnd& end);
+
+  std::map<Key, PortalEnd> links;
+};
+
+//////////////////////////////////////////////////////////////////////////////
+
+} // namespace voxels
@@ -0,0 +1,31 @@
+#include "edits.h"
+
+#include <algorithm>
+
+//////////////////////////////////////////////////////////////////////////////
+
+namespace voxels {
+
+//////////////////////////////////////////////////////////////////////////////
+
+bool EditLimiter::take(int player, double time, const EditRules& rules) {
+  const auto bucket0 = Bucket{rules.burst, time};
+  const auto [it, added] = buckets.try_emplace(player, bucket0);
+  auto& bucket = it->second;
+  if (!added) {
+    const auto elapsed = std::max(time - bucket.time, 0.0);
+    bucket.tokens = std::min(bucket.tokens + elapsed * rules.rate, rules.burst);
+    bucket.time = std::max(time, bucket.time);
+  }
+  if (bucket.tokens < 1) return false;
+  bucket.tokens -= 1;
+  return true;
+}
+
+void EditLimiter::reset(int player) {
+  buckets.erase(player);
+}
+
+//////////////////////////////////////////////////////////////////////////////
+
+} // namespace voxels
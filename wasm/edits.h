@@ -0,0 +1,65 @@
+#pragma once
+
+#include <array>
+#include <cstdint>
+
+#include "base.h"
+
+//////////////////////////////////////////////////////////////////////////////
+
+namespace voxels {
+
+//////////////////////////////////////////////////////////////////////////////
+
+// Validation for block edits that players send to a server. We check them
+// here, with the same registry that clients use, so the server's rules and
+// the clients' rules can't drift apart. See World::tryApplyPlayerEdit.
+//
+// An edit passes if its block is registered and placeable (not Unknown,
+// and not unbreakable, like bedrock), the voxel is loaded and would change,
+// the player has tokens left in their rate limit, the voxel is within reach
+// of their eye, no protected region blocks it, and, when it replaces a
+// block, the block is breakable and the player has mined it for long enough
+// with their tool.
+// Mining time may fall short of the block's break time by a fraction of
+// mining_slack, to allow for latency.
+//
+// Rate limits are token buckets: each player may make `burst` edits at once
+// and then `rate` edits per second. Times are the host's, in seconds.
+
+enum class EditVerdict : uint8_t {
+  Accepted, Unloaded, Unchanged, RateLimited, OutOfReach, Protected,
+  Unbreakable, TooFast, InvalidBlock, Unplaceable,
+};
+
+struct EditRules {
+  double reach = 6;
+  double rate = 20;
+  double burst = 10;
+  double mining_slack = 0.2;
+};
+
+struct EditContext {
+  std::array<double, 3> eye;
+  int tool;
+  int tier;
+  double mined;
+  double time;
+};
+
+struct EditLimiter {
+  // Takes a token from the player's bucket. Returns false if it's empty.
+  bool take(int player, double time, const EditRules& rules);
+
+  // Forgets a player's bucket, e.g. when they disconnect.
+  void reset(int player);
+
+ private:
+  struct Bucket { double tokens; double time; };
+
+  HashMap<int, Bucket> buckets;
+};
+
+//////////////////////////////////////////////////////////////////////////////
+
+} // namespace voxels
@@ -2,14 +2,53 @@
 #include <array>
 #include <cassert>
 #include <cmath>
+#include <cstddef>
 #include <cstdint>
 #include <cstdio>
+#include <cstring>
+#include <limits>
+#include <memory>
 #include <optional>
 #include <vector>
 
+#include "access.h"
 #include "base.h"
+#include "bricks.h"
+#include "camera.h"
+#include "clipboard.h"
+#include "collision.h"
+#include "cones.h"
+#include "crafting.h"
+#include "debug.h"
+#include "dimensions.h"
+#include "edits.h"
+#include "events.h"
+#include "fire.h"
+#include "fixtures.h"
+#include "heightmaps.h"
+#include "hooks.h"
+#include "inventory.h"
+#include "items.h"
+#include "loot.h"
+#include "memory.h"
 #include "mesher.h"
+#include "octree.h"
+#include "packets.h"
+#include "paths.h"
+#include "png.h"
+#include "power.h"
 #include "renderer.h"
+#include "replay.h"
+#include "selection.h"
+#include "slices.h"
+#include "snapshots.h"
+#include "spread.h"
+#include "structures.h"
+#include "symmetry.h"
+#include "trees.h"
+#include "triggers.h"
+#include "updates.h"
+#include "voxelize.h"
 #include "worldgen.h"
 
 //////////////////////////////////////////////////////////////////////////////
@@ -57,6 +96,7 @@ struct LightDelta { int location; int value; };
 //
 // If we allow for blocks that filter more than one light level at a
 // time, then the lower bounds fail, but the upper bounds still hold.
+// Callers subtract a neighbor's attenuation from the lower bound.
 //
 constexpr int maxUpdatedNeighborLight(int next, int prev) {
   const auto max = std::max(next, prev);
@@ -74,9 +114,40 @@ constexpr int kNumChunksToMeshPerFrame    = 1;
 constexpr int kNumChunksToLightPerFrame   = 4;
 constexpr int kNumLODChunksToMeshPerFrame = 4;
 
-// Require a layer of air blocks at the top of the world. Doing so simplifies
-// our data structures and shaders (for example, a height fits in a uint8_t).
-constexpr int kBuildHeight = kWorldHeight - 1;
+// Baked ambient occlusion casts many rays per voxel, so we spread the work
+// across frames. An unoccluded ray travels kBakeRayLength blocks. A voxel
+// that no rays escape from loses kBakeMaxDarkening light levels.
+constexpr int kNumBakeRaysPerFrame = 1 << 14;
+constexpr double kBakeRayLength = 8.0;
+constexpr double kBakeRayStep = 0.25;
+constexpr int kBakeMaxDarkening = 4;
+
+// Each game tick, we tick this many random voxels in each loaded chunk.
+constexpr int kRandomTicksPerChunk = 48;
+
+// The version of the World::encodeSnapshot format. Decoding rejects others.
+constexpr uint8_t kSnapshotVersion = 1;
+
+// Audio queries trace rays through the world. Each solid block on the path
+// from a source to the listener transmits kAudioTransmission of its sound.
+// We average a few parallel rays so that thin edges don't cause popping.
+constexpr double kAudioTransmission = 0.5;
+constexpr double kAudioRayLength = 32.0;
+constexpr std::array<double, 3> kAudioRayOffsets[7] = {
+  { 0.00,  0.00,  0.00},
+  {-0.25,  0.00,  0.00}, { 0.25,  0.00,  0.00},
+  { 0.00, -0.25,  0.00}, { 0.00,  0.25,  0.00},
+  { 0.00,  0.00, -0.25}, { 0.00,  0.00,  0.25},
+};
+
+// We mesh chunks in vertical sections, so that an edit only remeshes the
+// sections that it touches. Section meshes use chunk-relative coordinates,
+// so they all share the chunk's position and light texture.
+constexpr int kSectionBits = 4;
+constexpr int kSectionHeight = 1 << kSectionBits;
+constexpr int kNumSections = kWorldHeight >> kSectionBits;
+constexpr uint32_t kAllSections = (1ull << kNumSections) - 1;
+static_assert(kNumSections <= 32);
 
 // Used to mask x- and z-values into a chunk's index range.
 constexpr int kChunkMask = kChunkWidth - 1;
@@ -115,6 +186,100 @@ void checkEquilevels(const T& equilevels, const U& voxels) {
 
 //////////////////////////////////////////////////////////////////////////////
 
+// The per-voxel and per-column arrays of a chunk that we can export. Lights
+// are stage 1 lights, without the stage 2 light from neighboring chunks.
+enum class ChunkLayer { Voxels, Lights, Metadata, Heightmap, Count };
+
+// How far along the pipeline a chunk is. Worldgen produces terrain, carves
+// caves, and places decorations in a single pass when we load a chunk, so a
+// loaded chunk is always at least Decorated: Terrain and Carved are there so
+// that callers can name the stages that they depend on. Lit and Meshed need
+// all neighbors to be loaded, and edits can send a chunk back to Decorated
+// or Lit until we relight or remesh it.
+enum class ChunkStage { Empty, Terrain, Carved, Decorated, Lit, Meshed, Count };
+
+// Problems with the world's data, such as after loading a bad save: voxels
+// whose block isn't registered, and block faces whose material isn't. We
+// list the first few problems of each kind, as (x, y, z, block) for voxels
+// and as (block, face, material) for faces.
+constexpr int kMaxWorldProblems = 16;
+
+struct WorldReport {
+  bool valid() const {
+    return bad_voxels + bad_faces == 0;
+  }
+
+  int chunks = 0;
+  int bad_voxels = 0;
+  int bad_faces = 0;
+  std::vector<std::array<int, 4>> voxels;
+  std::vector<std::array<int, 3>> faces;
+};
+
+// Mismatches between a chunk's copies of its neighbors' border data and the
+// neighbors themselves: the voxels and heightmap in the mesher's apron, and
+// the neighbors' edge lights that we cached during stage 2 lighting. We list
+// the first few of each kind as (layer, x, y, z, expected, actual), where
+// layer is a ChunkLayer and heightmap entries have y = 0.
+struct BorderReport {
+  bool valid() const {
+    return bad_voxels + bad_lights + bad_heights == 0;
+  }
+
+  int bad_voxels = 0;
+  int bad_lights = 0;
+  int bad_heights = 0;
+  std::vector<std::array<int, 6>> cells;
+};
+
+// A moving point light, at a voxel in world space. See
+// World::setDynamicLight.
+struct DynamicLight {
+  int x, y, z;
+  int level;
+};
+
+// A projectile in flight, or stuck in a block. See World::stepProjectiles.
+struct Projectile {
+  int id;
+  int kind;
+  std::array<double, 3> position;
+  std::array<double, 3> velocity;
+  bool stuck;
+};
+
+// A stack of items dropped in the world. See World::addItemDrop.
+struct ItemDrop {
+  int id;
+  int item;
+  int count;
+  std::array<double, 3> position;
+  std::array<double, 3> velocity;
+  double age;
+};
+
+// Physics and lifetime rules for item drops. Drops of the same item within
+// merge_radius of each other merge, up to the item's max stack size. Drops
+// within magnet_radius of a magnet (usually a player) fly toward it at
+// magnet_speed, once they're older than pickup_delay.
+struct ItemDropRules {
+  double gravity = 20;
+  double drag = 1;
+  double merge_radius = 0.75;
+  double despawn_time = 300;
+  double pickup_delay = 0.5;
+  double magnet_radius = 2.5;
+  double magnet_speed = 8;
+};
+
+// A box of voxels, with inclusive bounds, that only its owner may edit. See
+// World::addProtectedRegion.
+struct ProtectedRegion {
+  std::array<int, 3> min;
+  std::array<int, 3> max;
+  int owner;
+};
+
 struct World;
 
 struct Chunk {
@@ -122,30 +287,53 @@ struct Chunk {
 
   void create(Point p, World* w) {
     assert(w != nullptr);
-    assert(!solid && !water);
+    assert(!hasMesh());
 
     point = p;
     world = w;
     neighbors = 0;
+    unsaved = false;
+    saved_state.reset();
+    saved_state_stale = true;
 
     instances.clear();
     point_lights.clear();
+    dynamic_lights.clear();
+    emitters.clear();
+    fires.clear();
+    baked_ao.clear();
+    bake_index = -1;
+    face_mask = kAllFaces;
+    cones.reset();
     stage1_dirty.clear();
     stage1_edges.clear();
     stage2_lights.clear();
+    block_lights.clear();
+    block_lights_stale = true;
 
+    metadata.data.fill(0);
     load();
+    if (!loadRestoredState()) {
+      loadFarChunk();
+      loadExtra();
+    }
+    collision.build(getRegistry(), voxels.data.data());
     lightingInit();
+    loadDynamicLights();
 
     eachNeighbor([&](Chunk* chunk) {
       chunk->notifyNeighborLoaded();
       neighbors++;
     });
-    dirty = stage2_dirty = true;
+    dirty = kAllSections;
+    stage2_dirty = true;
     ready = checkReady();
   }
 
   void destroy() {
+    if (unsaved) stashUnsaved();
+    storeFarChunk();
+    storeExtra();
     dropMeshes();
     eachNeighbor([](Chunk* chunk) {
       chunk->notifyNeighborDisposed();
@@ -160,6 +348,297 @@ struct Chunk {
     return voxels.get(x, y, z);
   }
 
+  // Returns the y-coordinate of the highest block in the column that has a
+  // top face, or -1 if there's no such block.
+  int getTopFaceHeight(int x, int z) const {
+    const auto& registry = getRegistry();
+    auto y = static_cast<int>(heightmap.get(x, z)) - 1;
+    for (; y >= 0; y--) {
+      const auto block = voxels.get(x, y, z);
+      if (registry.getBlockUnsafe(block).faces[2] != kNoMaterial) break;
+    }
+    return y;
+  }
+
+  // Returns the y-coordinate of the highest opaque block in the column at
+  // or below y, or -1 if there's no such block.
+  int getShadowHeight(int x, int y, int z) const {
+    const auto& registry = getRegistry();
+    y = std::min(y, static_cast<int>(heightmap.get(x, z)) - 1);
+    for (; y >= 0; y--) {
+      if (registry.getBlockUnsafe(voxels.get(x, y, z)).opaque) break;
+    }
+    return y;
+  }
+
+  const Block* getVoxels() const {
+    return voxels.data.data();
+  }
+
+  // Adds the chunk's voxels with unregistered blocks to the report.
+  void validate(WorldReport& report) const {
+    const auto num_blocks = getRegistry().getNumBlocks();
+    const auto bx = point.x << kChunkBits, bz = point.z << kChunkBits;
+    report.chunks++;
+    voxels.eachColumn([&](int x, int z, const Block* column) {
+      for (auto y = 0; y < kWorldHeight; y++) {
+        const auto block = static_cast<int>(column[y]);
+        if (block < num_blocks) continue;
+        if (report.bad_voxels++ < kMaxWorldProblems) {
+          report.voxels.push_back({bx + x, y, bz + z, block});
+        }
+      }
+    });
+  }
+
+  TensorView getLayer(ChunkLayer layer) const {
+    switch (layer) {
+      case ChunkLayer::Voxels:    return voxels.view();
+      case ChunkLayer::Lights:    return stage1_lights.view();
+      case ChunkLayer::Metadata:  return metadata.view();
+      case ChunkLayer::Heightmap: return heightmap.view();
+      case ChunkLayer::Count: break;
+    }
+    assert(false);
+    return voxels.view();
+  }
+
+  // We build the cone-tracing volume on first use, then keep it up to date
+  // as blocks change, until the chunk is reloaded.
+  const ConeVolume& getConeVolume() {
+    if (!cones) cones.emplace(getRegistry(), voxels.data.data());
+    return *cones;
+  }
+
+  const CollisionField& getCollisionField() const {
+    return collision;
+  }
+
+  // Meshes each section again and checks the geometry. See Mesher.
+  void validateMesh(MeshReport& report) {
+    auto& mesher = prepareMesher();
+    mesher.face_mask = kAllFaces;
+    for (auto i = 0; i < kNumSections; i++) {
+      const auto y_min = i * kSectionHeight, y_max = y_min + kSectionHeight;
+      mesher.meshChunk(y_min, y_max);
+      mesher.validateGeometry(y_min, y_max, report);
+    }
+  }
+
+  // Compares the border of the mesher's view of this chunk against the
+  // neighbors, and, if our stage 2 lights are up to date, our cached edge
+  // lights against the neighbors' lights. Missing neighbors read as air.
+  void checkBorders(BorderReport& report) {
+    const auto& mesher = prepareMesher();
+    const auto bx = point.x << kChunkBits, bz = point.z << kChunkBits;
+    const auto add = [&](int& count, ChunkLayer layer, int x, int y, int z,
+                         int expected, int actual) {
+      if (count++ >= kMaxWorldProblems) return;
+      report.cells.push_back({static_cast<int>(layer), bx + x, y, bz + z,
+                              expected, actual});
+    };
+
+    for (const auto& [delta, dstPos, srcPos, size] : kMesherOffsets) {
+      if (delta == Point{0, 0}) continue;
+      const auto chunk = getNeighbor(delta);
+      for (auto x = 0; x < size.x; x++) {
+        for (auto z = 0; z < size.z; z++) {
+          const auto sx = srcPos.x + x, sz = srcPos.z + z;
+          const auto dx = dstPos.x + x, dz = dstPos.z + z;
+          const auto lx = dx - 1, lz = dz - 1;
+
+          const int height = chunk ? chunk->heightmap.get(sx, sz) : 0;
+          const int copied = mesher.heightmap.get(dx, dz);
+          if (height != copied) {
+            add(report.bad_heights, ChunkLayer::Heightmap,
+                lx, 0, lz, height, copied);
+          }
+          for (auto y = 0; y < kWorldHeight; y++) {
+            const auto block =
+                chunk ? chunk->voxels.get(sx, y, sz) : Block::Air;
+            const auto actual = mesher.voxels.get(dx, y + 1, dz);
+            if (block == actual) continue;
+            add(report.bad_voxels, ChunkLayer::Voxels, lx, y, lz,
+                static_cast<int>(block), static_cast<int>(actual));
+          }
+        }
+      }
+    }
+
+    if (!ready || stage2_dirty) return;
+
+    // Edge lights are stored in the order that lightingStage2 wrote
+    // them. A neighbor's stage 2 lights are only comparable if it is clean.
+    auto source = stage2_edge_lights.data();
+    for (const auto& [delta, dstPos, srcPos, size] : kMesherOffsets) {
+      if (delta == Point{0, 0}) continue;
+      const auto chunk = getNeighbor(delta);
+      const auto skip = !chunk || !chunk->ready || chunk->stage2_dirty;
+      for (auto x = 0; x < size.x; x++) {
+        for (auto z = 0; z < size.z; z++) {
+          const auto sx = srcPos.x + x, sz = srcPos.z + z;
+          const auto lx = dstPos.x + x - 1, lz = dstPos.z + z - 1;
+          for (auto y = 0; !skip && y < kWorldHeight; y++) {
+            const auto index = chunk->voxels.index(sx, y, sz);
+            const auto it = chunk->stage2_lights.find(index);
+            const int light = it != chunk->stage2_lights.end()
+                ? it->second : chunk->stage1_lights.data[index];
+            const int cached = source[y];
+            if (light == cached) continue;
+            add(report.bad_lights, ChunkLayer::Lights, lx, y, lz,
+                light, cached);
+          }
+          source += kWorldHeight;
+        }
+      }
+    }
+    assert(source == stage2_edge_lights.data() + stage2_edge_lights.size());
+  }
+
+  // Meshes each section again and welds the given phase's quads: 0 for
+  // solid geometry, 1 for water. See weldQuads.
+  void weldMesh(int phase, bool repeats, IndexedMesh& result) {
+    auto& mesher = prepareMesher();
+    VoxelMesh::Quads quads;
+    auto& materials = result.quad_materials;
+    for (auto i = 0; i < kNumSections; i++) {
+      mesher.meshChunk(i * kSectionHeight, (i + 1) * kSectionHeight);
+      const auto& geo = phase == 0 ? mesher.solid_geo : mesher.water_geo;
+      const auto& ids =
+          phase == 0 ? mesher.solid_materials : mesher.water_materials;
+      quads.insert(quads.end(), geo.begin(), geo.end());
+      materials.insert(materials.end(), ids.begin(), ids.end());
+    }
+    weldQuads(quads, result, repeats);
+  }
+
+  // True if we've edited the chunk since it was loaded or last saved.
+  bool needsSave() const {
+    return unsaved;
+  }
+
+  void markSaved() {
+    unsaved = false;
+  }
+
+  // An opaque blob that the game attaches to the chunk, such as ownership
+  // or script state. It's saved, sent, and unloaded with the voxels.
+  const std::vector<uint8_t>& getExtra() const {
+    return extra;
+  }
+
+  void setExtra(const uint8_t* data, int size) {
+    assert(size >= 0);
+    extra.assign(data, data + size);
+    unsaved = true;
+    saved_state_stale = true;
+  }
+
+  // Block entities: rich state for single blocks, such as a chest's items.
+  // Changing a block drops its entity. Like the extra blob, they're saved,
+  // sent, and unloaded with the voxels.
+  const BlockEntity* getBlockEntity(int x, int y, int z) const {
+    const auto it = block_entities.find(voxels.index(x, y, z));
+    return it != block_entities.end() ? &it->second : nullptr;
+  }
+
+  void setBlockEntity(int x, int y, int z, BlockEntity&& entity) {
+    assert(0 <= x && x < kChunkWidth);
+    assert(0 <= z && z < kChunkWidth);
+    assert(0 <= y && y < kBuildHeight);
+
+    const auto index = voxels.index(x, y, z);
+    entity.index = index;
+    block_entities.insert_or_assign(index, std::move(entity));
+    unsaved = true;
+    saved_state_stale = true;
+  }
+
+  std::optional<BlockEntity> takeBlockEntity(int x, int y, int z) {
+    const auto it = block_entities.find(voxels.index(x, y, z));
+    if (it == block_entities.end()) return std::nullopt;
+    auto result = std::optional<BlockEntity>(std::move(it->second));
+    block_entities.erase(it);
+    unsaved = true;
+    saved_state_stale = true;
+    return result;
+  }
+
+  // Writes the chunk's block entities to the result, in order of index.
+  void getBlockEntities(std::vector<BlockEntity>& result) const {
+    result.clear();
+    for (const auto& [index, entity] : block_entities) {
+      result.push_back(entity);
+    }
+    std::sort(result.begin(), result.end(),
+              [](const auto& a, const auto& b) { return a.index < b.index; });
+  }
+
+  // The chunk's contents, for world snapshots. Chunks share their last saved
+  // state until an edit marks it stale, so snapshots copy only the chunks
+  // that changed since the previous one.
+  struct SavedState {
+    std::vector<Block> voxels;
+    std::vector<uint8_t> metadata;
+    std::vector<uint8_t> extra;
+    std::vector<BlockEntity> entities;
+  };
+
+  std::shared_ptr<const SavedState> saveState() {
+    if (saved_state && !saved_state_stale) return saved_state;
+    auto state = std::make_shared<SavedState>();
+    state->voxels.assign(voxels.data.begin(), voxels.data.end());
+    state->metadata.assign(metadata.data.begin(), metadata.data.end());
+    state->extra = extra;
+    getBlockEntities(state->entities);
+    saved_state = std::move(state);
+    saved_state_stale = false;
+    return saved_state;
+  }
+
+  // Edits the chunk to match the state, through setBlock, so that lighting,
+  // meshes, and collision catch up incrementally.
+  void restoreState(const std::shared_ptr<const SavedState>& state) {
+    assert(state != nullptr);
+    if (state == saved_state && !saved_state_stale) return;
+
+    for (auto z = 0; z < kChunkWidth; z++) {
+      for (auto x = 0; x < kChunkWidth; x++) {
+        for (auto y = 0; y < kBuildHeight; y++) {
+          setBlock(x, y, z, state->voxels[voxels.index(x, y, z)]);
+        }
+      }
+    }
+    std::copy(state->metadata.begin(), state->metadata.end(),
+              metadata.data.begin());
+    extra = state->extra;
+    block_entities.clear();
+    for (const auto& entity : state->entities) {
+      block_entities.emplace(entity.index, entity);
+    }
+    unsaved = true;
+    saved_state = state;
+    saved_state_stale = false;
+  }
+
+  // Per-block state, such as a power level. It's reset when the block changes.
+  uint8_t getMetadata(int x, int y, int z) const {
+    assert(0 <= x && x < kChunkWidth);
+    assert(0 <= z && z < kChunkWidth);
+    assert(0 <= y && y < kBuildHeight);
+
+    return metadata.get(x, y, z);
+  }
+
+  void setMetadata(int x, int y, int z, uint8_t value) {
+    assert(0 <= x && x < kChunkWidth);
+    assert(0 <= z && z < kChunkWidth);
+    assert(0 <= y && y < kBuildHeight);
+
+    metadata.set(x, y, z, value);
+    saved_state_stale = true;
+  }
+
   int getLightLevel(int x, int y, int z) {
     assert(0 <= x && x < kChunkWidth);
     assert(0 <= z && z < kChunkWidth);
@@ -174,8 +653,34 @@ struct Chunk {
     return std::min(base + (data.mesh ? 1 : 0), kSunlightLevel);
   }
 
+  // Like getLightLevel, but only counting light from blocks and point lights.
+  int getBlockLightLevel(int x, int y, int z) {
+    assert(0 <= x && x < kChunkWidth);
+    assert(0 <= z && z < kChunkWidth);
+    assert(0 <= y && y < kWorldHeight);
+
+    const auto& levels = getBlockLights();
+    const auto it = levels.find(blockLightKey(x, y, z));
+    const auto base = it != levels.end() ? it->second : 0;
+
+    const auto& data = getRegistry().getBlockUnsafe(voxels.get(x, y, z));
+    return std::min(base + (data.mesh ? 1 : 0), kSunlightLevel);
+  }
+
+  const HashSet<int>& getFires() const {
+    return fires;
+  }
+
+  // True if there are no blocks above (x, y, z), so rain can reach it.
+  bool isSkyExposed(int x, int y, int z) const {
+    return heightmap.get(x, z) <= y + 1;
+  }
+
   bool hasMesh() const {
-    return solid || water;
+    for (const auto& section : sections) {
+      if (section.solid || section.water || section.smooth) return true;
+    }
+    return false;
   }
 
   bool needsRelight() const {
@@ -183,21 +688,128 @@ struct Chunk {
   }
 
   bool needsRemesh() const {
-    return dirty && ready;
+    return dirty != 0 && ready;
+  }
+
+  bool isReady() const {
+    return ready;
+  }
+
+  ChunkStage getStage() const {
+    if (!ready || stage2_dirty) return ChunkStage::Decorated;
+    return dirty == 0 ? ChunkStage::Meshed : ChunkStage::Lit;
+  }
+
+  void markAllDirty() {
+    dirty = kAllSections;
+  }
+
+  // Meshes each section again, now, because the render origin moved and our
+  // quads are relative to it. Sections that were dirty stay dirty.
+  void rebase() {
+    if (!hasMesh()) return;
+    const auto saved = dirty;
+    dirty = kAllSections;
+    remeshTerrain();
+    dirty = saved;
+  }
+
+  // Limits the chunk's meshes to the given face directions. See kAllFaces.
+  void setFaceMask(int mask) {
+    assert(0 <= mask && mask <= kAllFaces);
+    if (face_mask == mask) return;
+    face_mask = mask;
+    dirty = kAllSections;
+  }
+
+  bool needsBake() const {
+    return bake_index >= 0 && ready && hasMesh();
+  }
+
+  // Starts an offline-quality ambient occlusion bake for this chunk, casting
+  // `samples` rays from each non-opaque voxel next to an opaque one. The bake
+  // runs incrementally (see continueBake) and is dropped if we reload.
+  void startBake(int samples) {
+    assert(samples > 0);
+    baked_ao.clear();
+    bake_index = 0;
+    bake_samples = samples;
+  }
+
+  // Bakes voxels until we've cast about `budget` rays. Returns the number of
+  // rays cast. When the bake completes, we update the light texture.
+  int continueBake(int budget) {
+    assert(needsBake());
+
+    // Use a Fibonacci sphere: a deterministic, near-uniform ray distribution.
+    static std::vector<std::array<double, 3>> rays;
+    if (rays.size() != bake_samples) {
+      rays.clear();
+      const auto golden = M_PI * (3 - sqrt(5.0));
+      for (auto i = 0; i < bake_samples; i++) {
+        const auto y = 1 - 2 * (i + 0.5) / bake_samples;
+        const auto r = sqrt(std::max(1 - y * y, 0.0));
+        const auto phi = golden * i;
+        rays.push_back({r * cos(phi), y, r * sin(phi)});
+      }
+    }
+
+    auto cast = 0;
+    const auto limit = static_cast<int>(voxels.data.size());
+    for (; bake_index < limit && cast < budget; bake_index++) {
+      const auto x = (bake_index >> 8) & 0xf;
+      const auto y = (bake_index >> 0) & 0xff;
+      const auto z = (bake_index >> 12) & 0xf;
+      if (isOpaqueAt(x, y, z)) continue;
+
+      auto surface = false;
+      for (const auto& delta : kFaceNeighbors) {
+        const auto [dx, dy, dz] = delta;
+        if (isOpaqueAt(x + dx, y + dy, z + dz)) surface = true;
+      }
+      if (!surface) continue;
+
+      // A voxel on a flat floor has half of its rays blocked, so we double
+      // the escaped fraction: such voxels should count as unoccluded.
+      auto escaped = 0;
+      for (const auto& ray : rays) {
+        if (castBakeRay(x, y, z, ray)) escaped++;
+      }
+      cast += bake_samples;
+
+      const auto visibility =
+          std::min(2.0 * escaped / bake_samples, 1.0);
+      const auto darkening = static_cast<int>(
+          std::round(kBakeMaxDarkening * (1 - visibility)));
+      if (darkening > 0) baked_ao[bake_index] = darkening;
+    }
+
+    if (bake_index == limit) {
+      bake_index = -1;
+      setLightTexture();
+    }
+    return cast;
   }
 
   void relightChunk() {
     // Called from remeshChunk to set the meshes' light textures, even if
     // !this.needsRelight(). Each step checks a dirty flag, so that's okay.
+    lightChunk();
+    setLightTexture();
+  }
+
+  // Computes the chunk's light, without touching its meshes. Stage 2 reads
+  // the neighbors' stage 1 lights, so we bring those up to date first.
+  void lightChunk() {
     eachNeighbor([](Chunk* chunk) {
       chunk->lightingStage1();
     });
     lightingStage1();
     lightingStage2();
-    setLightTexture();
   }
 
-  void remeshChunk() {
+  // Returns a bitmask of the sections that we remeshed.
+  uint32_t remeshChunk() {
     assert(needsRemesh());
     if (!hasMesh()) {
       markFrontierDirty();
@@ -205,7 +817,9 @@ struct Chunk {
     remeshSprites();
     remeshTerrain();
     relightChunk();
-    dirty = false;
+    const auto result = dirty;
+    dirty = 0;
+    return result;
   }
 
   void setBlock(int x, int y, int z, Block block) {
@@ -218,16 +832,33 @@ struct Chunk {
     if (old_block == block) return;
 
     voxels.data[index] = block;
+    metadata.data[index] = 0;
+    unsaved = true;
+    saved_state_stale = true;
+    if (!block_entities.empty()) block_entities.erase(index);
     stage1_dirty.insert(index);
-    dirty = stage2_dirty = true;
+    stage2_dirty = true;
+    markDirty(y);
     updateHeightmap(x, z, y, 1, block, index);
     updateInstance(index, old_block, block);
     equilevels[y] = 0;
+    if (cones) cones->update(voxels.data.data(), x, y, z);
+    collision.update(getRegistry(), voxels.data.data(), x, y, z);
+
+    const auto fire = getRegistry().getFireBlock();
+    if (block == fire) fires.insert(index);
+    if (old_block == fire) fires.erase(index);
+
+    if (getRegistry().getBlockUnsafe(block).light > 0) {
+      emitters.insert(index);
+    } else {
+      emitters.erase(index);
+    }
 
     constexpr auto M = kChunkMask;
     const auto neighbor = [&](int dx, int dz) {
       const auto neighbor = getNeighbor({dx, dz});
-      if (neighbor) neighbor->dirty = true;
+      if (neighbor) neighbor->markDirty(y);
     };
     if (x == 0) neighbor(-1,  0);
     if (x == M) neighbor( 1,  0);
@@ -255,6 +886,28 @@ struct Chunk {
     stage2_dirty = true;
   }
 
+  // Sets the level of the brightest dynamic light in the voxel. See
+  // World::setDynamicLight.
+  void setDynamicLight(int x, int y, int z, int level) {
+    assert(0 <= x && x < kChunkWidth);
+    assert(0 <= z && z < kChunkWidth);
+    assert(0 <= y && y < kWorldHeight);
+
+    const auto index = voxels.index(x, y, z);
+    const auto it = dynamic_lights.find(index);
+    const auto old_level = it != dynamic_lights.end() ? it->second : 0;
+    if (old_level == level) return;
+
+    if (level > 0) {
+      dynamic_lights[index] = level;
+    } else {
+      dynamic_lights.erase(it);
+    }
+
+    stage1_dirty.insert(index);
+    stage2_dirty = true;
+  }
+
  private:
   struct ChunkItem { Block block; uint8_t index; };
 
@@ -263,21 +916,52 @@ struct Chunk {
   Mesher& getMesher() const;
   Chunk* getNeighbor(Point delta) const;
   const Registry& getRegistry() const;
+  Worldgen& getWorldgen() const;
   void markFrontierDirty() const;
+  void stashUnsaved() const;
+  void storeFarChunk() const;
+  void loadFarChunk();
+  // Keep the extra blob and block entities across unloads. See getExtra.
+  void storeExtra();
+  void loadExtra();
+  // Applies the state that a rollback stashed for this chunk, if there is
+  // one. It replaces the far chunk, the extra blob, and block entities.
+  bool loadRestoredState();
+  void applyVoxels(const Block* dense);
+  void loadDynamicLights();
+
+  // The light that point lights and dynamic lights emit at the index.
+  int getPointLight(int index) const {
+    const auto a = point_lights.find(index);
+    const auto b = dynamic_lights.find(index);
+    return std::max(a != point_lights.end() ? a->second : 0,
+                    b != dynamic_lights.end() ? b->second : 0);
+  }
 
   bool checkReady() const {
     return neighbors == kNumNeighbors;
   }
 
+  void markDirty(int y) {
+    // An edit at level y can change faces and AO for voxels at y - 1 and
+    // y + 1, which may be in adjacent sections.
+    const auto lo = std::max(y - 1, 0) >> kSectionBits;
+    const auto hi = std::min(y + 1, kWorldHeight - 1) >> kSectionBits;
+    for (auto i = lo; i <= hi; i++) dirty |= 1 << i;
+  }
+
   void dropMeshes() {
     if (hasMesh()) markFrontierDirty();
     for (auto& [index, instance] : instances) {
       instance.mesh = std::nullopt;
     }
     light = std::nullopt;
-    solid = std::nullopt;
-    water = std::nullopt;
-    dirty = true;
+    for (auto& section : sections) {
+      section.solid = std::nullopt;
+      section.water = std::nullopt;
+      section.smooth = std::nullopt;
+    }
+    dirty = kAllSections;
   }
 
   template <typename Fn>
@@ -288,6 +972,31 @@ struct Chunk {
     }
   }
 
+  // Takes chunk-relative coordinates, which may extend into a neighbor.
+  // Treats unloaded voxels and voxels below the world as opaque.
+  bool isOpaqueAt(int x, int y, int z) const {
+    if (y < 0) return true;
+    if (y >= kWorldHeight) return false;
+    const auto delta = Point{x >> kChunkBits, z >> kChunkBits};
+    const auto chunk = delta == Point{0, 0} ? this : getNeighbor(delta);
+    if (!chunk) return true;
+    const auto block = chunk->voxels.get(x & kChunkMask, y, z & kChunkMask);
+    return getRegistry().getBlockUnsafe(block).opaque;
+  }
+
+  bool castBakeRay(int x, int y, int z, const std::array<double, 3>& ray) {
+    const auto [dx, dy, dz] = ray;
+    for (auto t = kBakeRayStep; t <= kBakeRayLength; t += kBakeRayStep) {
+      const auto ix = static_cast<int>(floor(x + 0.5 + t * dx));
+      const auto iy = static_cast<int>(floor(y + 0.5 + t * dy));
+      const auto iz = static_cast<int>(floor(z + 0.5 + t * dz));
+      if (ix == x && iy == y && iz == z) continue;
+      if (iy >= kWorldHeight) return true;
+      if (isOpaqueAt(ix, iy, iz)) return false;
+    }
+    return true;
+  }
+
   void lightingInit() {
     // Use for fast bitwise index propagation below.
     static_assert(decltype(stage1_lights)::stride[0] == kLightSpread[1].diff);
@@ -353,20 +1062,19 @@ struct Chunk {
       const auto from_block = static_cast<int>(data.light);
       if (from_block < 0) return 0;
 
-      const auto it = point_lights.find(index);
-      const auto from_point = it != point_lights.end() ? it->second : 0;
-      const auto base = std::max(from_block, from_point);
+      const auto base = std::max(from_block, getPointLight(index));
 
       const auto height = heightmap.data[index >> 8];
       if ((index & 0xff) >= height) return kSunlightLevel;
 
-      auto max_neighbor = base + 1;
+      auto max_neighbor = 0;
       for (const auto& spread : kLightSpread) {
         if ((index & spread.mask) == spread.test) continue;
         const auto neighbor = stage1_lights.data[index + spread.diff];
         if (neighbor > max_neighbor) max_neighbor = neighbor;
       }
-      return max_neighbor - 1;
+      const auto attenuation = registry.getAttenuation(voxels.data[index]);
+      return std::max(base, max_neighbor - 1 - attenuation);
     };
 
     // Enqueues new indices that may be affected by the given change.
@@ -375,10 +1083,18 @@ struct Chunk {
         if ((index & spread.mask) == spread.test) continue;
         const auto neighbor_index = index + spread.diff;
         const auto neighbor = stage1_lights.data[neighbor_index];
-        if (lo <= neighbor && neighbor <= hi) next.insert(neighbor_index);
+        const auto block = voxels.data[neighbor_index];
+        const auto min = lo - registry.getAttenuation(block);
+        if (min <= neighbor && neighbor <= hi) next.insert(neighbor_index);
       }
     };
 
+    // The bounds of the cells that changed, for debug drawing.
+    auto& debug = getDebugDraw();
+    const auto drawing = debug.enabled(kDebugLighting);
+    auto lo_cell = std::array{kChunkWidth, kWorldHeight, kChunkWidth};
+    auto hi_cell = std::array{-1, -1, -1};
+
     while (!prev.empty()) {
       for (const auto index : prev) {
         const auto prev_level = stage1_lights.data[index];
@@ -386,6 +1102,14 @@ struct Chunk {
         if (next_level == prev_level) continue;
 
         stage1_lights.data[index] = static_cast<uint8_t>(next_level);
+        if (drawing) {
+          const auto cell = std::array{
+              (index >> 8) & kChunkMask, index & 0xff, index >> 12};
+          for (auto i = 0; i < 3; i++) {
+            lo_cell[i] = std::min(lo_cell[i], cell[i]);
+            hi_cell[i] = std::max(hi_cell[i], cell[i]);
+          }
+        }
 
         if (edge(index)) {
           // The edge lights map only contains cells on the edge that are not
@@ -408,15 +1132,31 @@ struct Chunk {
 
     assert(stage1_dirty.empty());
     eachNeighbor([](Chunk* chunk) { chunk->stage2_dirty = true; });
+
+    if (drawing && hi_cell[0] >= 0) {
+      const auto base = std::array<double, 3>{
+          static_cast<double>(point.x << kChunkBits), 0,
+          static_cast<double>(point.z << kChunkBits)};
+      std::array<double, 3> min, max;
+      for (auto i = 0; i < 3; i++) {
+        min[i] = base[i] + lo_cell[i];
+        max[i] = base[i] + hi_cell[i] + 1;
+      }
+      debug.box(kDebugLighting, min, max, 0xffd040ff);
+    }
   }
 
   void lightingStage2() {
     if (!(ready && stage2_dirty)) return;
+    block_lights_stale = true;
 
     const auto& registry = getRegistry();
     const auto opaque = [&](Block block) {
       return registry.getBlockUnsafe(block).opaque;
     };
+    const auto attenuate = [&](int level, Block block) {
+      return level - registry.getAttenuation(block);
+    };
 
     const auto getIndex = [](Point delta) {
       return (delta.x + 1) | ((delta.z + 1) << 2);
@@ -469,9 +1209,11 @@ struct Chunk {
         // Update the neighbor cell's light value to `level`, if it is greater
         // than the neighbor's current light value.
         const auto propagate = [&](int level, int neighbor_index) {
+          const auto neighbor_block = neighbor_voxel[neighbor_index];
+          level = attenuate(level, neighbor_block);
           const auto neighbor_level = neighbor_light[neighbor_index];
           if (level <= neighbor_level) return;
-          if (!neighbor_level && opaque(neighbor_voxel[neighbor_index])) return;
+          if (!neighbor_level && opaque(neighbor_block)) return;
 
           const auto neighbor_location = neighbor_index | neighbor_union;
           neighbor_light[neighbor_index] = static_cast<uint8_t>(level);
@@ -547,7 +1289,6 @@ struct Chunk {
     constexpr int max = static_cast<int>(kSunlightLevel - 2);
     for (auto level = max; level > 0; level--) {
       const auto prev = &lightBuffers[max - level];
-      const auto next = level > 1 ? &lightBuffers[max - level + 1] : nullptr;
       const auto prev_level = level + 1;
 
       for (const auto location : *prev) {
@@ -565,13 +1306,20 @@ struct Chunk {
           auto& neighbor_light = neighbor_chunk->stage1_lights.data;
 
           const auto neighbor_index = neighbor_location & 0xffff;
+          const auto neighbor_block = neighbor_voxel[neighbor_index];
+          const auto neighbor_target = attenuate(level, neighbor_block);
           const auto neighbor_level = neighbor_light[neighbor_index];
-          if (level <= neighbor_level) continue;
-          if (!neighbor_level && opaque(neighbor_voxel[neighbor_index])) continue;
+          if (neighbor_target <= neighbor_level) continue;
+          if (!neighbor_level && opaque(neighbor_block)) continue;
 
-          neighbor_light[neighbor_index] = static_cast<uint8_t>(level);
+          neighbor_light[neighbor_index] =
+              static_cast<uint8_t>(neighbor_target);
           lightDeltas.push_back({neighbor_location, neighbor_level});
-          if (next) next->push_back(neighbor_location);
+          if (neighbor_target <= 1) continue;
+
+          // Attenuating blocks skip levels, so we may skip the next buffer.
+          const auto buffer = kSunlightLevel - neighbor_target - 1;
+          lightBuffers[buffer].push_back(neighbor_location);
         }
       }
     }
@@ -640,9 +1388,37 @@ struct Chunk {
       mergedLights.set(x + 1, y, z + 1, static_cast<uint8_t>(pair.second));
     }
 
-    light.emplace(mergedLights);
-    if (solid) solid->setLight(*light);
-    if (water) water->setLight(*light);
+    if (bake_index < 0) {
+      for (const auto& pair : baked_ao) {
+        const auto x = (pair.first >> 8 ) & 0xf;
+        const auto y = (pair.first >> 0 ) & 0xff;
+        const auto z = (pair.first >> 12) & 0xf;
+        const auto level = mergedLights.get(x + 1, y, z + 1);
+        const auto baked = std::max(level - pair.second, 0);
+        mergedLights.set(x + 1, y, z + 1, static_cast<uint8_t>(baked));
+      }
+    }
+
+    // Pack block light alone into the high bits, so the shader can dim the
+    // sky without dimming torches. See floodBlockLights.
+    for (const auto& [key, level] : getBlockLights()) {
+      const auto y = key & 0xff;
+      const auto x = ((key >> 8 ) & 0x3f) - kChunkWidth;
+      const auto z = ((key >> 14) & 0x3f) - kChunkWidth;
+      if (!(-1 <= x && x <= kChunkWidth && -1 <= z && z <= kChunkWidth)) {
+        continue;
+      }
+      auto& texel = mergedLights.data[mergedLights.index(x + 1, y, z + 1)];
+      const auto block_light = std::min(level, static_cast<int>(texel));
+      texel = static_cast<uint8_t>(texel | (block_light << 4));
+    }
+
+    light.emplace(mergedLights, point.x << kChunkBits, point.z << kChunkBits);
+    for (auto& section : sections) {
+      if (section.solid) section.solid->setLight(*light);
+      if (section.water) section.water->setLight(*light);
+      if (section.smooth) section.smooth->setLight(*light);
+    }
 
     for (auto& [index, instance] : instances) {
       if (!instance.mesh) continue;
@@ -651,6 +1427,89 @@ struct Chunk {
     }
   }
 
+  // Sunlight and block light share one light level, so to dim the sky
+  // without relighting, we need block light alone, too. We don't store it in
+  // a light array: emitters are sparse, so we flood-fill from the emitters in
+  // this chunk's zone and cache the result until the chunk is relit. Each key
+  // is a cell as packed by blockLightKey.
+  static int blockLightKey(int x, int y, int z) {
+    return y | ((x + kChunkWidth) << 8) | ((z + kChunkWidth) << 14);
+  }
+
+  // Returns the flood of block light around the chunk, which we cache until
+  // the chunk is relit, since gameplay queries read it one voxel at a time.
+  const HashMap<int, int>& getBlockLights() {
+    if (block_lights_stale) floodBlockLights(block_lights);
+    block_lights_stale = false;
+    return block_lights;
+  }
+
+  void floodBlockLights(HashMap<int, int>& result) const {
+    struct Cell { int x, y, z; };
+    static NonCopyArray<std::vector<Cell>, kSunlightLevel + 1> buckets;
+    result.clear();
+
+    const auto& registry = getRegistry();
+    NonCopyArray<const Chunk*, 9> zone;
+    for (auto i = 0; i < 9; i++) {
+      zone[i] = i == 4 ? this : getNeighbor({i % 3 - 1, i / 3 - 1});
+    }
+    const auto lookup = [&](int x, int z) -> const Chunk* {
+      constexpr auto lo = -kChunkWidth, hi = 2 * kChunkWidth;
+      if (!(lo <= x && x < hi && lo <= z && z < hi)) return nullptr;
+      return zone[((x >> kChunkBits) + 1) + 3 * ((z >> kChunkBits) + 1)];
+    };
+
+    const auto seed = [&](int x, int y, int z, int level) {
+      level = std::min(level, static_cast<int>(kSunlightLevel));
+      if (level <= 0) return;
+      auto& entry = result[blockLightKey(x, y, z)];
+      if (level <= entry) return;
+      entry = level;
+      buckets[level].push_back({x, y, z});
+    };
+
+    for (auto i = 0; i < 9; i++) {
+      const auto chunk = zone[i];
+      if (!chunk) continue;
+      const auto bx = (i % 3 - 1) * kChunkWidth;
+      const auto bz = (i / 3 - 1) * kChunkWidth;
+      const auto add = [&](int index, int level) {
+        const auto x = bx + ((index >> 8) & 0xf);
+        const auto z = bz + ((index >> 12) & 0xf);
+        seed(x, index & 0xff, z, level);
+      };
+      for (const auto index : chunk->emitters) {
+        add(index, registry.getBlockUnsafe(chunk->voxels.data[index]).light);
+      }
+      for (const auto& [index, level] : chunk->point_lights) add(index, level);
+      for (const auto& [index, level] : chunk->dynamic_lights) {
+        add(index, level);
+      }
+    }
+
+    constexpr int kDeltas[6][3] = {
+      {-1, 0, 0}, {1, 0, 0}, {0, -1, 0}, {0, 1, 0}, {0, 0, -1}, {0, 0, 1}};
+    for (auto level = kSunlightLevel; level > 1; level--) {
+      // Neighbors go in lower buckets, so this bucket can't grow as we go.
+      for (const auto [x, y, z] : buckets[level]) {
+        if (result[blockLightKey(x, y, z)] != level) continue;
+        for (const auto& [dx, dy, dz] : kDeltas) {
+          const auto nx = x + dx, ny = y + dy, nz = z + dz;
+          if (!(0 <= ny && ny < kWorldHeight)) continue;
+          const auto chunk = lookup(nx, nz);
+          if (!chunk) continue;
+
+          const auto block = chunk->voxels.get(nx & kChunkMask, ny,
+                                               nz & kChunkMask);
+          if (registry.getBlockUnsafe(block).opaque) continue;
+          seed(nx, ny, nz, level - 1 - registry.getAttenuation(block));
+        }
+      }
+    }
+    for (auto& bucket : buckets) bucket.clear();
+  }
+
   void load() {
     NonCopyArray<int, kWorldHeight> mismatches;
     heightmap.data.fill(0);
@@ -658,7 +1517,7 @@ struct Chunk {
 
     static_assert(alignof(ChunkItem) == 1);
     constexpr auto size = sizeof(ChunkItem);
-    const auto data = loadChunkData(point.x, point.z);
+    const auto data = getWorldgen().loadChunkData(point.x, point.z);
     const uint8_t* cur = data.start;
     const auto base = reinterpret_cast<const ChunkItem*>(cur);
 
@@ -759,7 +1618,7 @@ struct Chunk {
     }
   }
 
-  void remeshTerrain() {
+  Mesher& prepareMesher() {
     auto& mesher = getMesher();
 
     static_assert(sizeof(equilevels[0]) == 1);
@@ -781,15 +1640,35 @@ struct Chunk {
     }
 
     checkEquilevels(mesher.equilevels, mesher.voxels);
+    mesher.face_mask = face_mask;
+    mesher.origin = {0, 0, 0};
+    return mesher;
+  }
+
+  void remeshTerrain() {
+    auto& mesher = prepareMesher();
+    const auto& render = getRenderOrigin();
+    mesher.origin = {(point.x << kChunkBits) - render[0], -render[1],
+                     (point.z << kChunkBits) - render[2]};
 
     const auto mesh = [&](auto& mesh, const auto& quads, int phase) {
       if (quads.empty()) return mesh.reset();
       mesh ? mesh->setGeometry(quads) : void(mesh.emplace(quads, phase));
-      mesh->setPosition(point.x << kChunkBits, 0, point.z << kChunkBits);
+      if (light) mesh->setLight(*light);
+    };
+    const auto smooth = [&](auto& mesh, const auto& geo) {
+      if (geo.empty()) return mesh.reset();
+      mesh ? mesh->setGeometry(geo) : void(mesh.emplace(geo));
+      if (light) mesh->setLight(*light);
     };
-    mesher.meshChunk();
-    mesh(solid, mesher.solid_geo, 0);
-    mesh(water, mesher.water_geo, 1);
+    for (auto i = 0; i < kNumSections; i++) {
+      if (!(dirty & (1 << i))) continue;
+      auto& section = sections[i];
+      mesher.meshChunk(i * kSectionHeight, (i + 1) * kSectionHeight);
+      mesh(section.solid, mesher.solid_geo, 0);
+      mesh(section.water, mesher.water_geo, 1);
+      smooth(section.smooth, mesher.smooth_geo);
+    }
   }
 
   void copyHeightmap(MeshTensor2<uint8_t>& dst, Point dstPos,
@@ -882,7 +1761,10 @@ struct Chunk {
     if (light > 0) {
       for (auto i = 0; i < count; i++) {
         stage1_dirty.insert(index + i);
+        emitters.insert(index + i);
       }
+    } else if (!emitters.empty()) {
+      for (auto i = 0; i < count; i++) emitters.erase(index + i);
     }
 
     updateHeightmap(x, z, start, count, block, index);
@@ -948,18 +1830,25 @@ struct Chunk {
   // edge lighting (including the implicit lights implied by the heightmap).
   // We store stage 2 lights sparsely, as a delta on stage 1 lights.
 
-  // Basic chunk metadata.
-  bool dirty;
+  // Basic chunk metadata. `dirty` is a bitmask of sections to remesh, and
+  // `face_mask` is a bitmask of the face directions that we mesh.
+  uint32_t dirty;
   bool ready;
   bool stage2_dirty;
+  bool unsaved;
   Point point;
   World* world;
   int neighbors;
+  int face_mask;
 
   // JS renderer resources.
+  struct Section {
+    std::optional<VoxelMesh> solid;
+    std::optional<VoxelMesh> water;
+    std::optional<SmoothMesh> smooth;
+  };
   std::optional<LightTexture> light;
-  std::optional<VoxelMesh> solid;
-  std::optional<VoxelMesh> water;
+  NonCopyArray<Section, kNumSections> sections;
 
   struct Instance {
     Block block;
@@ -972,11 +1861,45 @@ struct Chunk {
   HashSet<int> stage1_edges;
   HashMap<int, int> stage2_lights;
   HashMap<int, int> point_lights;
+  HashMap<int, int> dynamic_lights;
+
+  // Indices of burning voxels, if the registry has a fire block.
+  HashSet<int> fires;
+
+  // Indices of voxels whose block emits light. See floodBlockLights.
+  HashSet<int> emitters;
+
+  // Baked ambient occlusion, as light levels to subtract for a voxel index.
+  // bake_index is the next voxel to bake, or -1 if we aren't baking.
+  HashMap<int, int> baked_ao;
+  int bake_index = -1;
+  int bake_samples = 0;
+
+  // Mip-mapped volumes for cone tracing, if a caller has asked for them.
+  std::optional<ConeVolume> cones;
+
+  // Mip-mapped solidity bits, which we keep up to date for every chunk.
+  CollisionField collision;
+
+  // The game's blob for this chunk. See getExtra.
+  std::vector<uint8_t> extra;
+
+  // Block entities, by voxel index. See getBlockEntity.
+  HashMap<int, BlockEntity> block_entities;
+
+  // The cached flood of block light. See getBlockLights.
+  HashMap<int, int> block_lights;
+  bool block_lights_stale = true;
+
+  // The state we last saved for a world snapshot. See saveState.
+  std::shared_ptr<const SavedState> saved_state;
+  bool saved_state_stale = true;
 
   // Large data arrays, in increasing order of size.
   ChunkTensor1<uint8_t> equilevels;
   ChunkTensor2<uint8_t> heightmap;
   ChunkTensor3<uint8_t> stage1_lights;
+  ChunkTensor3<uint8_t> metadata;
   ChunkTensor3<Block> voxels;
 
   static constexpr size_t kEdgeLights = 4 * (kChunkWidth + 1) * kWorldHeight;
@@ -1063,6 +1986,13 @@ struct Circle {
     }
   }
 
+  // Returns true if p is within the circle, whether or not it's loaded.
+  bool contains(Point p) const {
+    const auto diff = p - center;
+    const auto ax = abs(diff.x), az = abs(diff.z);
+    return ax < numDeltas && az <= deltas[ax];
+  }
+
   T* get(Point p) const {
     const auto result = lookup[getIndex(p)];
     return result && result->point == p ? result : nullptr;
@@ -1126,9 +2056,25 @@ struct LODMultiMesh {
     return states[index].meshed;
   }
 
-  void show(int index, int mask_value) {
-    assert(states[index].meshed);
-    setMask(index, mask_value);
+  // Drops our geometry and marks every chunk as unmeshed. The caller must
+  // mesh the chunks again and then call refreshMask.
+  void reset() {
+    for (auto& state : states) state.meshed = false;
+    solid = std::nullopt;
+    water = std::nullopt;
+  }
+
+  void refreshMask() {
+    const auto m0 = static_cast<int32_t>(mask);
+    const auto m1 = static_cast<int32_t>(mask >> 32);
+    const auto shown = mask != -1;
+    if (solid) solid->setMask(m0, m1, shown);
+    if (water) water->setMask(m0, m1, shown);
+  }
+
+  void show(int index, int mask_value) {
+    assert(states[index].meshed);
+    setMask(index, mask_value);
     if (states[index].enabled) return;
 
     assert(numEnabled < kMultiMeshArea);
@@ -1145,12 +2091,7 @@ struct LODMultiMesh {
     const auto shift = index << 2;
     mask &= ~(static_cast<uint64_t>(kLODSingleMask) << shift);
     mask |= static_cast<uint64_t>(mask_value) << shift;
-
-    const auto m0 = static_cast<int32_t>(mask);
-    const auto m1 = static_cast<int32_t>(mask >> 32);
-    const auto shown = mask != -1;
-    if (solid) solid->setMask(m0, m1, shown);
-    if (water) water->setMask(m0, m1, shown);
+    refreshMask();
   }
 
   std::optional<VoxelMesh> solid;
@@ -1210,6 +2151,22 @@ struct Frontier {
     if (level < levels.size()) levels[level].dirty = true;
   }
 
+  // Decimates LOD meshes at levels >= min_level, with at most max_error
+  // voxels of height error. Only affects LOD chunks meshed after the call.
+  void setDecimation(int min_level, int max_error) {
+    assert(0 <= min_level);
+    assert(0 <= max_error && max_error < 64);
+    decimation_level = min_level;
+    decimation_error = max_error;
+  }
+
+  // Limits LOD skirts to the given depth, in voxels. See Mesher. Like
+  // setDecimation, this only affects LOD chunks meshed after the call.
+  void setSkirtDepth(int depth) {
+    assert(0 <= depth && depth <= kWorldHeight);
+    skirt_depth = depth;
+  }
+
   void recenter(Point p) {
     for (auto& level : levels) {
       p.x >>= 1;
@@ -1223,6 +2180,23 @@ struct Frontier {
     for (auto l = 0; l < size; l++) computeLODAtLevel(l);
   }
 
+  // Meshes each live LOD chunk again, now, because the render origin moved
+  // and our quads are relative to it. Multi-meshes may hold data for chunks
+  // that we've disposed; we drop it, and we'll mesh them if they come back.
+  void rebase() {
+    std::vector<FrontierChunk*> meshed;
+    for (auto& level : levels) {
+      level.chunks.each([&](Point p) {
+        const auto chunk = level.chunks.get(p);
+        if (chunk && chunk->hasMesh()) meshed.push_back(chunk);
+        return false;
+      });
+    }
+    for (auto& [key, mesh] : meshes) mesh->reset();
+    for (const auto chunk : meshed) createLODMeshes(chunk);
+    for (auto& [key, mesh] : meshes) mesh->refreshMask();
+  }
+
  private:
   struct Level {
     Level(double radius) : chunks(radius) {}
@@ -1232,6 +2206,9 @@ struct Frontier {
 
   Mesher& getMesher() const;
   Chunk* getWorldChunk(Point p) const;
+  const Octree* getFarChunk(Point p) const;
+  const Registry& getRegistry() const;
+  Worldgen& getWorldgen() const;
 
   LODMultiMesh* getOrCreateMultiMesh(Point p, int l) {
     const auto shift = 12;
@@ -1260,35 +2237,61 @@ struct Frontier {
     const auto level = chunk->level;
     const auto cx = chunk->point.x, cz = chunk->point.z;
 
-    // The (x, z) pos of the base of this chunk in world coordinates.
+    // The (x, z) pos of the base of this chunk in render coordinates.
     const auto shift = kChunkBits + level;
-    const auto pos = Point{cx << (shift + 1), cz << (shift + 1)};
-
-    // The (x, z) pos of this chunk's multimesh, in world coordinates.
-    //
-    // Note that we offset the multimesh, so that quad coordinates relative
-    // this this position can be positive or negative, and are centered on 0.
-    const auto multi = static_cast<int>(kMultiMeshSide);
-    const auto mesh_pos = Point{(2 * (cx & ~(multi - 1)) + multi) << shift,
-                                (2 * (cz & ~(multi - 1)) + multi) << shift};
+    const auto& render = getRenderOrigin();
+    const auto pos = Point{(cx << (shift + 1)) - render[0],
+                           (cz << (shift + 1)) - render[2]};
 
     auto& mesher = getMesher();
-    const auto [start, end] = loadHeightmap(cx, cz, level);
+    mesher.frontier_error = level >= decimation_level ? decimation_error : 0;
+    mesher.frontier_skirt = skirt_depth;
+
+    const auto [start, end] = getWorldgen().loadHeightmap(cx, cz, level);
     static_assert(sizeof(start[0]) == sizeof(Mesher::HeightmapEntry));
     assert((end - start) == kChunkWidth * kChunkWidth);
-    mesher.meshFrontier(reinterpret_cast<const Mesher::HeightmapEntry*>(start),
-                        kChunkWidth, pos - mesh_pos, 2 << level, chunk->index);
+    auto heightmap = reinterpret_cast<const Mesher::HeightmapEntry*>(start);
+    if (level == 0) heightmap = patchFarChunks(cx, cz, heightmap);
+    mesher.meshFrontier(heightmap, kChunkWidth, pos, 2 << level, chunk->index);
 
     const auto mesh = [&](auto& mesh, const auto& quads, int phase) {
       if (quads.empty()) return;
       mesh ? mesh->appendGeometry(quads) : void(mesh.emplace(quads, phase));
-      mesh->setPosition(mesh_pos.x, 0, mesh_pos.z);
     };
     mesh(chunk->mesh->solid, mesher.solid_geo, 0);
     mesh(chunk->mesh->water, mesher.water_geo, 1);
     chunk->mesh->states[chunk->index].meshed = true;
   }
 
+  // A level-0 LOD chunk covers 2 x 2 world chunks, with one quadrant of its
+  // heightmap for each. If we've kept octrees for any of them, we sample the
+  // octrees instead of worldgen, so that edits show up in the LOD mesh.
+  const Mesher::HeightmapEntry* patchFarChunks(
+      int cx, int cz, const Mesher::HeightmapEntry* heightmap) {
+    constexpr auto n = kChunkWidth / 2;
+    auto patched = false;
+    for (auto i = 0; i < 4; i++) {
+      const auto octree = getFarChunk({2 * cx + (i & 1), 2 * cz + (i >> 1)});
+      if (!octree) continue;
+      if (!patched) {
+        std::copy(heightmap, heightmap + far_heightmap.size(),
+                  far_heightmap.begin());
+        patched = true;
+      }
+
+      std::array<Mesher::HeightmapEntry, n * n> quadrant;
+      octree->computeHeightmap(getRegistry(), 2, quadrant.data());
+      const auto ox = (i & 1) * n, oz = (i >> 1) * n;
+      for (auto z = 0; z < n; z++) {
+        for (auto x = 0; x < n; x++) {
+          const auto index = (x + ox) + kChunkWidth * (z + oz);
+          far_heightmap[index] = quadrant[x + n * z];
+        }
+      }
+    }
+    return patched ? far_heightmap.data() : heightmap;
+  }
+
   void computeLODAtLevel(int l) {
     auto& level = levels[l];
     if (!level.dirty) return;
@@ -1335,17 +2338,30 @@ struct Frontier {
   World* world;
   std::vector<Level> levels;
   HashMap<int, std::unique_ptr<LODMultiMesh>> meshes;
+  std::array<Mesher::HeightmapEntry, kChunkWidth * kChunkWidth> far_heightmap;
+  int decimation_level = 0;
+  int decimation_error = 0;
+  int skirt_depth = kWorldHeight;
 };
 
 //////////////////////////////////////////////////////////////////////////////
 
-struct World {
-  World(double chunkRadius, int frontierRadius, int numLevels)
+// Every dimension's World shares this registry. See setActiveDimension.
+Registry& getSharedRegistry() {
+  static Registry registry;
+  return registry;
+}
+
+struct World final : BlockAccess {
+  World(double chunkRadius, int frontierRadius, int numLevels,
+        uint32_t seed = 0)
     : chunks(chunkRadius)
     , frontier(this, chunkRadius, frontierRadius, numLevels)
-    , mesher(registry) {}
+    , registry(getSharedRegistry())
+    , mesher(registry)
+    , worldgen(seed) {}
 
-  Block getBlock(int x, int y, int z) {
+  Block getBlock(int x, int y, int z) override {
     if (y < 0) return Block::Bedrock;
     if (y >= kBuildHeight) return Block::Air;
 
@@ -1356,7 +2372,20 @@ struct World {
     return chunk ? chunk->getBlock(xm, y, zm) : Block::Unknown;
   }
 
-  int getLightLevel(int x, int y, int z) {
+  // Opaque blocks are walls, and blocks with a liquid bottom face are liquids,
+  // matching the host's underwater overlay. Unloaded voxels are open.
+  CameraCell getCameraCell(int x, int y, int z) {
+    const auto block = getBlock(x, y, z);
+    if (block == Block::Unknown) return CameraCell::Open;
+    const auto& data = registry.getBlock(block);
+    if (data.opaque) return CameraCell::Wall;
+    const auto face = data.faces[3];
+    if (face == kNoMaterial) return CameraCell::Open;
+    const auto& material = registry.getMaterial(assertMaterial(face));
+    return material.liquid ? CameraCell::Liquid : CameraCell::Open;
+  }
+
+  int getLightLevel(int x, int y, int z) override {
     if (y < 0) return 0;
     if (y >= kWorldHeight) return kSunlightLevel;
 
@@ -1367,191 +2396,5378 @@ struct World {
     return chunk ? chunk->getLightLevel(xm, y, zm) : kSunlightLevel;
   }
 
-  void setBlock(int x, int y, int z, Block block) {
-    if (!(0 <= y && y < kBuildHeight)) return;
+  // Returns the y-coordinate of the highest opaque block at or below (x, y,
+  // z), where an entity there would cast its shadow, or -1 if there's none
+  // or if the chunk isn't loaded.
+  int getShadowHeight(int x, int y, int z) const {
+    if (y < 0) return -1;
+    const auto cx = x >> kChunkBits, xm = x & kChunkMask;
+    const auto cz = z >> kChunkBits, zm = z & kChunkMask;
+    const auto chunk = chunks.get({cx, cz});
+    if (!chunk) return -1;
+    return chunk->getShadowHeight(xm, std::min(y, kWorldHeight - 1), zm);
+  }
+
+  // getShadowHeight for each column in an nx x nz box with its corner at
+  // (x, z), with column (i, j) at index i + nx * j.
+  const int* getShadowHeights(int x, int z, int nx, int nz, int y) const {
+    static std::vector<int> result;
+    result.resize(static_cast<size_t>(nx * nz));
+    for (auto j = 0; j < nz; j++) {
+      for (auto i = 0; i < nx; i++) {
+        result[i + nx * j] = getShadowHeight(x + i, y, z + j);
+      }
+    }
+    return result.data();
+  }
+
+  // Dims sunlight by `level` light levels, e.g. at night. Block light isn't
+  // affected. We don't relight anything: we only store the level for
+  // getEffectiveLightLevel, and the renderer applies it in its shader.
+  void setSkyDarkness(int level) {
+    assert(0 <= level && level <= kSunlightLevel);
+    sky_darkness = level;
+  }
+
+  // The light level at (x, y, z) with the sky dimmed by setSkyDarkness, for
+  // gameplay rules such as mob spawning. It's much slower than getLightLevel.
+  int getEffectiveLightLevel(int x, int y, int z) {
+    const auto level = getLightLevel(x, y, z) - sky_darkness;
+    if (sky_darkness == 0 || !(0 <= y && y < kWorldHeight)) {
+      return std::max(level, 0);
+    }
 
     const auto cx = x >> kChunkBits, xm = x & kChunkMask;
     const auto cz = z >> kChunkBits, zm = z & kChunkMask;
     const auto chunk = chunks.get({cx, cz});
+    if (!chunk) return std::max(level, 0);
+    return std::max(chunk->getBlockLightLevel(xm, y, zm), level);
+  }
+
+  // Persistence: edits mark chunks as unsaved, and the host saves them a few
+  // at a time with saveChunks, spreading autosave across frames. When we
+  // unload an unsaved chunk, we stash its data to return in the next save.
+  //
+  // Returns a count, followed by (cx, cz) for each chunk needing a save.
+  const int* chunksNeedingSave() {
+    static std::vector<int> result;
+    result.assign({0});
+    for (const auto& stashed : unsaved_chunks) {
+      result.insert(result.end(), {stashed.cx, stashed.cz});
+      result[0]++;
+    }
+    chunks.each([&](Point point) {
+      const auto chunk = chunks.get(point);
+      if (!chunk || !chunk->needsSave()) return false;
+      const auto wrapped = wrapPoint(point);
+      result.insert(result.end(), {wrapped.x, wrapped.z});
+      result[0]++;
+      return false;
+    });
+    return result.data();
+  }
+
+  // Encodes up to `budget` unsaved chunks as ChunkData packets and marks
+  // them saved. Returns a uint32_t count, followed by the packets, each with
+  // its uint32_t size prefix. Stashed chunks are saved first, oldest first,
+  // so that a host that applies the packets in order keeps the newest data.
+  const uint8_t* saveChunks(int budget) {
+    static std::vector<uint8_t> result;
+    result.assign(sizeof(uint32_t), 0);
+    auto count = static_cast<uint32_t>(0);
+
+    const auto append = [&](const uint8_t* packet, size_t size) {
+      result.insert(result.end(), packet, packet + size);
+      count++;
+    };
+    auto drained = unsaved_chunks.begin();
+    for (; count < budget && drained != unsaved_chunks.end(); drained++) {
+      append(drained->packet->data(), drained->packet->size());
+    }
+    unsaved_chunks.erase(unsaved_chunks.begin(), drained);
+    chunks.each([&](Point point) {
+      if (count >= budget) return true;
+      const auto chunk = chunks.get(point);
+      if (!chunk || !chunk->needsSave()) return false;
+      const auto wrapped = wrapPoint(point);
+      static std::vector<BlockEntity> entities;
+      chunk->getBlockEntities(entities);
+      const auto packet = encodeChunkData(wrapped.x, wrapped.z,
+          chunk->getVoxels(), chunk->getExtra(), entities);
+      uint32_t size = 0;
+      memcpy(&size, packet, sizeof(size));
+      append(packet, sizeof(size) + size);
+      chunk->markSaved();
+      return false;
+    });
+
+    memcpy(result.data(), &count, sizeof(count));
+    return result.data();
+  }
+
+  // Returns the chunk's voxels in chunk index order, or null if the chunk at
+  // (cx, cz) isn't loaded.
+  const Block* getChunkVoxels(int cx, int cz) {
+    const auto chunk = chunks.get({cx, cz});
+    return chunk ? chunk->getVoxels() : nullptr;
+  }
 
-    if (chunk) chunk->setBlock(xm, y, zm, block);
+  const std::vector<uint8_t>* getChunkExtra(int cx, int cz) {
+    const auto chunk = chunks.get({cx, cz});
+    return chunk ? &chunk->getExtra() : nullptr;
   }
 
-  void setPointLight(int x, int y, int z, int level) {
-    if (!(0 <= y && y < kWorldHeight)) return;
+  // Attaches the blob to the loaded chunk (cx, cz), replacing its old one,
+  // and marks the chunk unsaved. Returns false if the chunk isn't loaded.
+  bool setChunkExtra(int cx, int cz, const uint8_t* data, int size) {
+    const auto chunk = chunks.get({cx, cz});
+    if (!chunk) return false;
+    chunk->setExtra(data, size);
+    return true;
+  }
+
+  // Returns the loaded chunk (cx, cz)'s block entities, in order of index,
+  // or nullptr if it isn't loaded.
+  const std::vector<BlockEntity>* getBlockEntities(int cx, int cz) {
+    const auto chunk = chunks.get({cx, cz});
+    if (!chunk) return nullptr;
+    static std::vector<BlockEntity> result;
+    chunk->getBlockEntities(result);
+    return &result;
+  }
 
+  const BlockEntity* getBlockEntity(int x, int y, int z) {
+    if (!(0 <= y && y < kBuildHeight)) return nullptr;
     const auto cx = x >> kChunkBits, xm = x & kChunkMask;
     const auto cz = z >> kChunkBits, zm = z & kChunkMask;
     const auto chunk = chunks.get({cx, cz});
+    return chunk ? chunk->getBlockEntity(xm, y, zm) : nullptr;
+  }
 
-    // We can't support a block light of kSunlightLevel until we have separate
-    // channels for block light and sunlight.
-    level = std::min(level, static_cast<int>(kSunlightLevel - 1));
-    if (chunk) chunk->setPointLight(xm, y, zm, level);
+  // Attaches an entity of a game-defined type to the block at (x, y, z),
+  // replacing any old one. Fails if the chunk isn't loaded or if the block
+  // is Air, since entities belong to blocks and go away with them.
+  bool createBlockEntity(int x, int y, int z, int type,
+                         const uint8_t* data, int size) {
+    if (!(0 <= y && y < kBuildHeight)) return false;
+    const auto cx = x >> kChunkBits, xm = x & kChunkMask;
+    const auto cz = z >> kChunkBits, zm = z & kChunkMask;
+    const auto chunk = chunks.get({cx, cz});
+    if (!chunk || chunk->getBlock(xm, y, zm) == Block::Air) return false;
+
+    assert(size >= 0);
+    chunk->setBlockEntity(xm, y, zm, {0, type, {data, data + size}});
+    return true;
   }
 
-  void recenter(Point p) {
-    const auto c = Point{p.x >> kChunkBits, p.z >> kChunkBits};
-    chunks.recenter(c);
-    frontier.recenter(c);
+  bool removeBlockEntity(int x, int y, int z) {
+    return takeBlockEntity(x, y, z).has_value();
+  }
 
-    auto loaded = 0;
+  // Snapshots: copies of the world's state that we can roll back to, as
+  // many times as needed until the host releases them. A snapshot holds the
+  // loaded chunks, the stores of unloaded ones (unsaved packets, extras,
+  // block entities, and restored chunks), projectiles, item drops, scheduled
+  // updates, the tick count, and the RNG.
+  //
+  // A snapshot holds pointers to per-chunk state, rather than the state: a
+  // loaded chunk shares its saved state until an edit marks it stale, and the
+  // stores hold immutable entries. So each snapshot only copies the chunks
+  // that changed since the last one, along with the projectiles, item drops,
+  // and scheduled updates, and a pointer for each chunk.
+  //
+  // Rolling back restores loaded chunks with setBlock. Snapshot chunks that
+  // have since unloaded go to the restored chunk store, which applies their
+  // whole state when they reload, and to the unsaved packets, so that the
+  // next save sees the old state. Within far_radius, they also replace the
+  // far chunk that LOD meshes sample. Chunks that weren't loaded when we took
+  // the snapshot keep their current state.
+  int snapshot() {
+    const auto id = next_snapshot_id++;
+    auto& result = snapshots[id];
     chunks.each([&](Point point) {
-      const auto existing = chunks.get(point);
-      if (existing != nullptr) return false;
-      chunks.set(point, this);
-      return (++loaded) == kNumChunksToLoadPerFrame;
+      const auto chunk = chunks.get(point);
+      if (chunk) result.chunks.push_back({point, chunk->saveState()});
+      return false;
     });
+    result.unsaved_chunks = unsaved_chunks;
+    result.chunk_extras = chunk_extras;
+    result.stored_block_entities = stored_block_entities;
+    result.restored_chunks = restored_chunks;
+    result.projectiles = projectiles;
+    result.item_drops = item_drops;
+    result.next_item_drop_id = next_item_drop_id;
+    result.scheduled = scheduled;
+    result.ticks = ticks;
+    result.rng = rng;
+    return id;
   }
 
-  void remesh() {
-    auto lit = 0, meshed = 0, total = 0;
-    chunks.each([&](Point point) {
-      total++;
-      const auto canRelight = lit < kNumChunksToLightPerFrame;
-      const auto canRemesh = total <= 9 || meshed < kNumChunksToMeshPerFrame;
-      if (!(canRelight || canRemesh)) return true;
+  // Returns false if there's no snapshot with the given id.
+  bool rollback(int id) {
+    const auto it = snapshots.find(id);
+    if (it == snapshots.end()) return false;
+    const auto& saved = it->second;
+
+    unsaved_chunks = saved.unsaved_chunks;
+    chunk_extras = saved.chunk_extras;
+    stored_block_entities = saved.stored_block_entities;
+    restored_chunks = saved.restored_chunks;
+    for (const auto& [point, state] : saved.chunks) {
+      const auto chunk = chunks.get(point);
+      if (chunk) {
+        chunk->restoreState(state);
+        continue;
+      }
+      const auto key = farChunkKey(point);
+      storeFarChunk(point, state->voxels.data());
+      chunk_extras.erase(key);
+      stored_block_entities.erase(key);
+      restored_chunks.insert_or_assign(key, state);
+      stashUnsavedChunk(point, state->voxels.data(), state->extra,
+                        state->entities);
+    }
 
+    // A chunk may have reloaded while its state was in the restored store.
+    chunks.each([&](Point point) {
       const auto chunk = chunks.get(point);
       if (!chunk) return false;
-
-      if (canRemesh && chunk->needsRemesh()) {
-        chunk->remeshChunk();
-        meshed++;
-      } else if (canRelight && chunk->needsRelight()) {
-        chunk->relightChunk();
-        lit++;
+      if (const auto state = takeRestoredChunk(point)) {
+        chunk->restoreState(state);
       }
       return false;
     });
-    frontier.remeshFrontier();
+
+    projectiles = saved.projectiles;
+    item_drops = saved.item_drops;
+    next_item_drop_id = saved.next_item_drop_id;
+    scheduled = saved.scheduled;
+    ticks = saved.ticks;
+    rng = saved.rng;
+    return true;
   }
 
-  Registry& mutableRegistry() { return registry; };
+  bool releaseSnapshot(int id) {
+    return snapshots.erase(id) > 0;
+  }
 
- private:
-  friend struct Chunk;
-  friend struct Frontier;
+  // Serializes a snapshot, so that the host can keep a rollback point across
+  // sessions or send it to a peer. Returns a buffer with a uint32_t size
+  // prefix, or nullptr if there's no snapshot with the given id.
+  //
+  // The format is a version byte, the tick count, RNG state, and next item
+  // drop id, and then counted sections: loaded chunks, restored chunks,
+  // unsaved packets, extras, block entities, projectiles, item drops, and
+  // scheduled updates. Chunks are ChunkData packets followed by metadata.
+  // Integers and doubles are raw little-endian values.
+  const uint8_t* encodeSnapshot(int id) const {
+    const auto it = snapshots.find(id);
+    if (it == snapshots.end()) return nullptr;
+    const auto& saved = it->second;
+
+    static std::vector<uint8_t> result;
+    result.assign(sizeof(uint32_t), 0);
+    const auto put = [&](const auto& value) {
+      const auto bytes = reinterpret_cast<const uint8_t*>(&value);
+      result.insert(result.end(), bytes, bytes + sizeof(value));
+    };
+    const auto count = [&](size_t value) {
+      put(static_cast<uint32_t>(value));
+    };
+    const auto blob = [&](const uint8_t* data, size_t size) {
+      count(size);
+      result.insert(result.end(), data, data + size);
+    };
+    const auto chunk = [&](Point point, const Chunk::SavedState& state) {
+      const auto packet = encodeChunkData(
+          point.x, point.z, state.voxels.data(), state.extra, state.entities);
+      uint32_t size = 0;
+      memcpy(&size, packet, sizeof(size));
+      blob(packet + sizeof(size), size);
+      blob(state.metadata.data(), state.metadata.size());
+    };
 
-  Circle<Chunk> chunks;
-  Frontier frontier;
-  Registry registry;
-  Mesher mesher;
+    result.push_back(kSnapshotVersion);
+    put(saved.ticks);
+    put(saved.rng.getState());
+    put(saved.next_item_drop_id);
 
-  DISALLOW_COPY_AND_ASSIGN(World);
-};
+    count(saved.chunks.size());
+    for (const auto& [point, state] : saved.chunks) chunk(point, *state);
+    count(saved.restored_chunks.size());
+    for (const auto& [key, state] : saved.restored_chunks) {
+      chunk(farChunkPoint(key), *state);
+    }
+    count(saved.unsaved_chunks.size());
+    for (const auto& unsaved : saved.unsaved_chunks) {
+      const auto& packet = *unsaved.packet;
+      blob(packet.data() + sizeof(uint32_t), packet.size() - sizeof(uint32_t));
+    }
+    count(saved.chunk_extras.size());
+    for (const auto& [key, extra] : saved.chunk_extras) {
+      put(key);
+      blob(extra->data(), extra->size());
+    }
+    count(saved.stored_block_entities.size());
+    for (const auto& [key, entities] : saved.stored_block_entities) {
+      put(key);
+      count(entities->size());
+      for (const auto& entity : *entities) {
+        put(entity.index);
+        put(entity.type);
+        blob(entity.data.data(), entity.data.size());
+      }
+    }
 
-//////////////////////////////////////////////////////////////////////////////
+    count(saved.projectiles.size());
+    for (const auto& projectile : saved.projectiles) {
+      put(projectile.id);
+      put(projectile.kind);
+      put(projectile.position);
+      put(projectile.velocity);
+      put(static_cast<uint8_t>(projectile.stuck));
+    }
+    count(saved.item_drops.size());
+    for (const auto& drop : saved.item_drops) {
+      put(drop.id);
+      put(drop.item);
+      put(drop.count);
+      put(drop.position);
+      put(drop.velocity);
+      put(drop.age);
+    }
+    auto scheduled_copy = saved.scheduled;
+    count(scheduled_copy.size());
+    for (; !scheduled_copy.empty(); scheduled_copy.pop()) {
+      const auto& update = scheduled_copy.top();
+      put(update.tick);
+      put(update.x);
+      put(update.y);
+      put(update.z);
+    }
 
-Mesher& Chunk::getMesher() const {
-  return world->mesher;
-}
+    const auto size =
+        static_cast<uint32_t>(result.size() - sizeof(uint32_t));
+    memcpy(result.data(), &size, sizeof(size));
+    return result.data();
+  }
 
-Chunk* Chunk::getNeighbor(Point delta) const {
-  return world->chunks.get(point + delta);
-}
+  // Adds the serialized snapshot to this world's snapshots, and returns its
+  // new id. Returns 0, without changes, if the data is malformed, or if it
+  // has a block or projectile kind that isn't registered.
+  int decodeSnapshot(const uint8_t* data, int size) {
+    if (size < 1 || data[0] != kSnapshotVersion) return 0;
+    auto cur = data + 1;
+    const auto end = data + size;
+
+    const auto get = [&](auto& value) {
+      if (end - cur < static_cast<ptrdiff_t>(sizeof(value))) return false;
+      memcpy(&value, cur, sizeof(value));
+      cur += sizeof(value);
+      return true;
+    };
+    // Each element takes at least `min_size` bytes, which bounds a count.
+    const auto count = [&](uint32_t& value, int min_size) {
+      return get(value) &&
+             static_cast<ptrdiff_t>(value) <= (end - cur) / min_size;
+    };
+    const auto blob = [&](std::vector<uint8_t>& value) {
+      uint32_t length = 0;
+      if (!get(length) || end - cur < static_cast<ptrdiff_t>(length)) {
+        return false;
+      }
+      value.assign(cur, cur + length);
+      cur += length;
+      return true;
+    };
+    const auto num_blocks = registry.getNumBlocks();
+    const auto valid = [&](const std::vector<Block>& voxels) {
+      return std::all_of(voxels.begin(), voxels.end(), [&](Block block) {
+        return static_cast<size_t>(block) < num_blocks;
+      });
+    };
+    static std::vector<uint8_t> packet;
+    const auto chunk = [&](Point& point, SharedState& result) {
+      auto state = std::make_shared<Chunk::SavedState>();
+      state->voxels.resize(kChunkVoxels);
+      if (!blob(packet) ||
+          !decodeChunkData(packet.data(), static_cast<int>(packet.size()),
+                           point.x, point.z, state->voxels.data(),
+                           state->extra, state->entities) ||
+          !valid(state->voxels) || !blob(state->metadata) ||
+          state->metadata.size() != static_cast<size_t>(kChunkVoxels)) {
+        return false;
+      }
+      result = std::move(state);
+      return true;
+    };
 
-const Registry& Chunk::getRegistry() const {
-  return world->registry;
-}
+    Snapshot result;
+    uint64_t rng_state = 0;
+    uint32_t num_items = 0;
+    if (!get(result.ticks) || !get(rng_state) ||
+        !get(result.next_item_drop_id)) {
+      return 0;
+    }
+    result.rng = Random(rng_state);
+
+    if (!count(num_items, 8)) return 0;
+    for (auto i = 0; i < num_items; i++) {
+      auto point = Point{0, 0};
+      auto state = SharedState();
+      if (!chunk(point, state)) return 0;
+      result.chunks.push_back({point, std::move(state)});
+    }
+    if (!count(num_items, 8)) return 0;
+    for (auto i = 0; i < num_items; i++) {
+      auto point = Point{0, 0};
+      auto state = SharedState();
+      if (!chunk(point, state)) return 0;
+      result.restored_chunks.insert_or_assign(farChunkKey(point), state);
+    }
+    if (!count(num_items, 4)) return 0;
+    for (auto i = 0; i < num_items; i++) {
+      auto point = Point{0, 0};
+      if (!blob(packet)) return 0;
+      auto voxels = std::vector<Block>(kChunkVoxels);
+      std::vector<uint8_t> extra;
+      std::vector<BlockEntity> entities;
+      if (!decodeChunkData(packet.data(), static_cast<int>(packet.size()),
+                           point.x, point.z, voxels.data(), extra,
+                           entities) || !valid(voxels)) {
+        return 0;
+      }
+      const auto length = static_cast<uint32_t>(packet.size());
+      auto stashed = std::vector<uint8_t>(sizeof(length));
+      memcpy(stashed.data(), &length, sizeof(length));
+      stashed.insert(stashed.end(), packet.begin(), packet.end());
+      result.unsaved_chunks.push_back(
+          {point.x, point.z,
+           std::make_shared<const std::vector<uint8_t>>(std::move(stashed))});
+    }
+    if (!count(num_items, 12)) return 0;
+    for (auto i = 0; i < num_items; i++) {
+      uint64_t key = 0;
+      std::vector<uint8_t> extra;
+      if (!get(key) || !blob(extra)) return 0;
+      result.chunk_extras.insert_or_assign(
+          key, std::make_shared<const std::vector<uint8_t>>(std::move(extra)));
+    }
+    if (!count(num_items, 12)) return 0;
+    for (auto i = 0; i < num_items; i++) {
+      uint64_t key = 0;
+      uint32_t num_entities = 0;
+      if (!get(key) || !count(num_entities, 12)) return 0;
+      std::vector<BlockEntity> entities(num_entities);
+      for (auto& entity : entities) {
+        if (!get(entity.index) || !get(entity.type) || !blob(entity.data)) {
+          return 0;
+        }
+        if (!(0 <= entity.index && entity.index < kChunkVoxels)) return 0;
+      }
+      using Entities = const std::vector<BlockEntity>;
+      result.stored_block_entities.insert_or_assign(
+          key, std::make_shared<Entities>(std::move(entities)));
+    }
 
-void Chunk::markFrontierDirty() const {
-  world->frontier.markDirty(0);
-}
+    if (!count(num_items, 57)) return 0;
+    for (auto i = 0; i < num_items; i++) {
+      Projectile projectile;
+      uint8_t stuck = 0;
+      if (!get(projectile.id) || !get(projectile.kind) ||
+          !get(projectile.position) || !get(projectile.velocity) ||
+          !get(stuck) || !registry.getProjectileKind(projectile.kind)) {
+        return 0;
+      }
+      projectile.stuck = stuck != 0;
+      result.projectiles.push_back(projectile);
+    }
+    if (!count(num_items, 68)) return 0;
+    for (auto i = 0; i < num_items; i++) {
+      ItemDrop drop;
+      if (!get(drop.id) || !get(drop.item) || !get(drop.count) ||
+          !get(drop.position) || !get(drop.velocity) || !get(drop.age) ||
+          !(drop.item >= 0 && drop.count > 0)) {
+        return 0;
+      }
+      result.item_drops.push_back(drop);
+    }
+    if (!count(num_items, 20)) return 0;
+    for (auto i = 0; i < num_items; i++) {
+      ScheduledUpdate update;
+      if (!get(update.tick) || !get(update.x) || !get(update.y) ||
+          !get(update.z)) {
+        return 0;
+      }
+      result.scheduled.push(update);
+    }
+    if (cur != end) return 0;
 
-void FrontierChunk::destroy() {
-  if (hasMesh()) frontier->markDirty(level + 1);
-  mesh->disable(index);
-}
+    const auto id = next_snapshot_id++;
+    snapshots.insert_or_assign(id, std::move(result));
+    return id;
+  }
 
-Mesher& Frontier::getMesher() const {
-  return world->mesher;
-}
+ private:
+  std::optional<BlockEntity> takeBlockEntity(int x, int y, int z) {
+    if (!(0 <= y && y < kBuildHeight)) return std::nullopt;
+    const auto cx = x >> kChunkBits, xm = x & kChunkMask;
+    const auto cz = z >> kChunkBits, zm = z & kChunkMask;
+    const auto chunk = chunks.get({cx, cz});
+    return chunk ? chunk->takeBlockEntity(xm, y, zm) : std::nullopt;
+  }
 
-Chunk* Frontier::getWorldChunk(Point p) const {
-  return world->chunks.get(p);
-}
+  void putBlockEntity(int x, int y, int z, BlockEntity&& entity) {
+    if (!(0 <= y && y < kBuildHeight)) return;
+    const auto cx = x >> kChunkBits, xm = x & kChunkMask;
+    const auto cz = z >> kChunkBits, zm = z & kChunkMask;
+    const auto chunk = chunks.get({cx, cz});
+    if (chunk) chunk->setBlockEntity(xm, y, zm, std::move(entity));
+  }
 
-//////////////////////////////////////////////////////////////////////////////
+ public:
 
-} // namespace voxels
+  // Returns the slice as text (see slices.h), or an empty string if the
+  // chunk at (cx, cz) isn't loaded.
+  std::string dumpSlice(int cx, int cz, int axis, int index) {
+    const auto chunk = chunks.get({cx, cz});
+    if (!chunk) return {};
+    return voxels::dumpSlice(chunk->getVoxels(), axis, index);
+  }
 
-//////////////////////////////////////////////////////////////////////////////
+  // Sets the chunk's blocks from a slice's text, with the usual edit events.
+  // Returns false if the chunk isn't loaded, if the slice is malformed or has
+  // unregistered blocks, or if the edit isn't allowed, in which case we don't
+  // change any blocks.
+  bool loadSlice(int cx, int cz, const char* text) {
+    Slice slice;
+    if (!chunks.get({cx, cz}) || !parseSlice(text, slice)) return false;
+    for (const auto block : slice.blocks) {
+      if (static_cast<int>(block) >= registry.getNumBlocks()) return false;
+    }
 
-std::optional<voxels::World> world;
+    auto min = slice.getVoxel(0, 0);
+    auto max = slice.getVoxel(slice.rows - 1, slice.cols - 1);
+    for (auto i = 0; i < 3; i++) {
+      if (min[i] > max[i]) std::swap(min[i], max[i]);
+    }
+    const auto dx = cx << kChunkBits, dz = cz << kChunkBits;
+    min[0] += dx; max[0] += dx;
+    min[2] += dz; max[2] += dz;
+    if (!checkEdit(min, max)) return false;
+
+    for (auto row = 0; row < slice.rows; row++) {
+      for (auto col = 0; col < slice.cols; col++) {
+        const auto [x, y, z] = slice.getVoxel(row, col);
+        const auto block = slice.blocks[row * slice.cols + col];
+        setBlock((cx << kChunkBits) + x, y, (cz << kChunkBits) + z, block);
+      }
+    }
+    return true;
+  }
 
-WASM_EXPORT(initializeWorld)
-void initializeWorld(int chunkRadius, int frontierRadius, int frontierLevels) {
-  assert(!world);
-  const double adjusted = static_cast<double>(chunkRadius) + 0.5;
-  world.emplace(adjusted, frontierRadius, frontierLevels);
-}
+  // Replaces the standalone heightmap mesh, e.g. for a menu background. See
+  // Mesher::meshHeightmap. Pass n = 0 to remove it.
+  void setHeightmapMesh(const Mesher::HeightmapEntry* data, int n,
+                        int x, int z) {
+    heightmap_data.assign(data, data + n * n);
+    heightmap_size = n;
+    heightmap_pos = {x, z};
+    remeshHeightmap();
+  }
 
-WASM_EXPORT(recenterWorld)
-void recenterWorld(int x, int z) {
-  assert(world);
-  world->recenter({x, z});
-}
+  // Moves the render origin. Voxel meshes' quads are relative to it, so we
+  // mesh every chunk, LOD chunk, and the heightmap mesh again, right away.
+  // That's a hitch, but the host only rebases when the camera moves far.
+  // Dimensions share the origin, so we call this on activating one, too.
+  void rebaseOrigin(int x, int z) {
+    setRenderOrigin(x, 0, z);
+    if (meshed_origin == Point{x, z}) return;
+    meshed_origin = {x, z};
+    chunks.each([&](Point point) {
+      const auto chunk = chunks.get(point);
+      if (chunk) chunk->rebase();
+      return false;
+    });
+    frontier.rebase();
+    remeshHeightmap();
+  }
 
-WASM_EXPORT(remeshWorld)
-void remeshWorld() {
-  assert(world);
-  world->remesh();
-}
+  // Meshes only the given face directions of chunk (cx, cz) from now on,
+  // remeshing it if the mask changed. Returns false if it isn't loaded.
+  bool setChunkFaceMask(int cx, int cz, int mask) {
+    const auto chunk = chunks.get({cx, cz});
+    if (chunk) chunk->setFaceMask(mask);
+    return chunk != nullptr;
+  }
 
-WASM_EXPORT(getBaseHeight)
-int getBaseHeight(int x, int z) {
-  return voxels::getBaseHeight(x, z);
-}
+  bool getChunkLayer(int cx, int cz, ChunkLayer layer, TensorView& result) {
+    const auto chunk = chunks.get({cx, cz});
+    if (!chunk) return false;
+    result = chunk->getLayer(layer);
+    return true;
+  }
 
-WASM_EXPORT(getBlock)
-int getBlock(int x, int y, int z) {
-  assert(world);
-  return static_cast<int>(world->getBlock(x, y, z));
-}
+  const uint8_t* getConeVolume(int cx, int cz, int level) {
+    const auto chunk = chunks.get({cx, cz});
+    return chunk ? chunk->getConeVolume().getLevel(level) : nullptr;
+  }
+
+  const CollisionField* getCollisionField(int cx, int cz) {
+    const auto chunk = chunks.get({cx, cz});
+    return chunk ? &chunk->getCollisionField() : nullptr;
+  }
+
+  // Returns true if any voxel in the inclusive box [min, max] is solid, or
+  // if the box extends into unloaded chunks or below the world, where we
+  // can't say. Physics uses it to skip sweeps through open air.
+  bool anySolidInBox(const std::array<int, 3>& min,
+                     const std::array<int, 3>& max) {
+    for (auto i = 0; i < 3; i++) assert(min[i] <= max[i]);
+    if (min[1] < 0) return true;
+    const auto y0 = min[1], y1 = std::min(max[1], kWorldHeight - 1);
+    if (y0 > y1) return false;
+
+    for (auto cz = min[2] >> kChunkBits; cz <= max[2] >> kChunkBits; cz++) {
+      for (auto cx = min[0] >> kChunkBits; cx <= max[0] >> kChunkBits; cx++) {
+        const auto chunk = chunks.get({cx, cz});
+        if (!chunk) return true;
+
+        const auto bx = cx << kChunkBits, bz = cz << kChunkBits;
+        const auto x0 = std::max(min[0] - bx, 0);
+        const auto z0 = std::max(min[2] - bz, 0);
+        const auto x1 = std::min(max[0] - bx, kChunkMask);
+        const auto z1 = std::min(max[2] - bz, kChunkMask);
+        const auto& field = chunk->getCollisionField();
+        if (field.anySolid(x0, y0, z0, x1, y1, z1)) return true;
+      }
+    }
+    return false;
+  }
+
+  bool exportBricks(int cx, int cz, BrickExport& result) {
+    const auto chunk = chunks.get({cx, cz});
+    if (!chunk) return false;
+    voxels::exportBricks(chunk->getVoxels(), result);
+    return true;
+  }
+
+  // Checks the registry and every loaded chunk. See WorldReport.
+  void validate(WorldReport& report) {
+    const auto num_materials = registry.getNumMaterials();
+    for (auto i = 0; i < registry.getNumBlocks(); i++) {
+      const auto& data = registry.getBlock(static_cast<Block>(i));
+      for (auto face = 0; face < 6; face++) {
+        const auto material = data.faces[face];
+        if (material == kNoMaterial) continue;
+        if (assertMaterialUnsafe(material).id < num_materials) continue;
+        if (report.bad_faces++ < kMaxWorldProblems) {
+          report.faces.push_back({i, face, material.id});
+        }
+      }
+    }
+    chunks.each([&](Point point) {
+      const auto chunk = chunks.get(point);
+      if (chunk) chunk->validate(report);
+      return false;
+    });
+  }
+
+  bool validateMesh(int cx, int cz, MeshReport& report) {
+    const auto chunk = chunks.get({cx, cz});
+    if (!chunk) return false;
+    chunk->validateMesh(report);
+    return true;
+  }
+
+  bool checkBorders(int cx, int cz, BorderReport& report) {
+    const auto chunk = chunks.get({cx, cz});
+    if (!chunk) return false;
+    chunk->checkBorders(report);
+    return true;
+  }
+
+  void checkMeshFixtures(MeshReport& report) {
+    voxels::checkMeshFixtures(mesher, registry, report);
+  }
+
+  bool weldMesh(int cx, int cz, int phase, bool repeats,
+                IndexedMesh& result) {
+    const auto chunk = chunks.get({cx, cz});
+    if (!chunk) return false;
+    chunk->weldMesh(phase, repeats, result);
+    return true;
+  }
+
+  uint8_t getMetadata(int x, int y, int z) override {
+    if (!(0 <= y && y < kBuildHeight)) return 0;
+
+    const auto cx = x >> kChunkBits, xm = x & kChunkMask;
+    const auto cz = z >> kChunkBits, zm = z & kChunkMask;
+    const auto chunk = chunks.get({cx, cz});
+
+    return chunk ? chunk->getMetadata(xm, y, zm) : 0;
+  }
+
+  void setMetadata(int x, int y, int z, uint8_t value) {
+    if (!(0 <= y && y < kBuildHeight)) return;
+
+    const auto cx = x >> kChunkBits, xm = x & kChunkMask;
+    const auto cz = z >> kChunkBits, zm = z & kChunkMask;
+    const auto chunk = chunks.get({cx, cz});
+
+    if (chunk) chunk->setMetadata(xm, y, zm, value);
+  }
+
+  void setBlock(int x, int y, int z, Block block) {
+    if (!(0 <= y && y < kBuildHeight)) return;
+
+    const auto cx = x >> kChunkBits, xm = x & kChunkMask;
+    const auto cz = z >> kChunkBits, zm = z & kChunkMask;
+    const auto chunk = chunks.get({cx, cz});
+
+    if (!chunk) return;
+
+    const auto old_block = chunk->getBlock(xm, y, zm);
+    if (old_block == block || !checkEdit({x, y, z}, {x, y, z})) return;
+    chunk->setBlock(xm, y, zm, block);
+    events.push({EventType::BlockChanged, x, y, z,
+                 static_cast<int>(old_block), static_cast<int>(block)});
+
+    const auto log = [&](Block b) { return registry.hasTag(b, kBlockTagLog); };
+    if (log(old_block) && !log(block)) scheduleLeafDecay(x, y, z);
+
+    if (registry.hasHook(old_block, BlockHook::Break)) {
+      const auto context = static_cast<int>(block);
+      callBlockHook(BlockHook::Break, old_block, x, y, z, context);
+    }
+    if (registry.hasHook(block, BlockHook::Place)) {
+      const auto context = static_cast<int>(old_block);
+      callBlockHook(BlockHook::Place, block, x, y, z, context);
+    }
+  }
+
+  // Sets every loaded block in the inclusive box from (x0, y0, z0) to
+  // (x1, y1, z1). If collect_drops is set, we also record a summary of the
+  // blocks that we destroyed in edit_drops, for the host to read afterwards.
+  void fillBlocks(int x0, int y0, int z0, int x1, int y1, int z1,
+                  Block block, bool collect_drops) {
+    clearEditDrops();
+    y0 = std::max(y0, 0);
+    y1 = std::min(y1, kBuildHeight - 1);
+    if (x0 > x1 || y0 > y1 || z0 > z1) return;
+    if (!checkSymmetricEdit({x0, y0, z0}, {x1, y1, z1})) return;
+
+    const auto fill = [&](int x, int y, int z) {
+      if (!(0 <= y && y < kBuildHeight) || !inSelectionMask(x, y, z)) return;
+      const auto old_block = getBlock(x, y, z);
+      if (old_block == block || old_block == Block::Unknown) return;
+      setBlock(x, y, z, block);
+      if (collect_drops) recordEditDrop(old_block, x, y, z);
+    };
+
+    // Images under mirrors and quarter turns are boxes, which we fill in the
+    // same order as the original. Other rotations go voxel by voxel.
+    for (auto i = 0; i < symmetry.count(); i++) {
+      auto min = std::array{x0, y0, z0}, max = std::array{x1, y1, z1};
+      const auto box = symmetry.preservesBoxes(i);
+      if (box) symmetry.applyToBox(i, min, max);
+      for (auto x = min[0]; x <= max[0]; x++) {
+        for (auto z = min[2]; z <= max[2]; z++) {
+          for (auto y = min[1]; y <= max[1]; y++) {
+            if (box) {
+              fill(x, y, z);
+            } else {
+              const auto [ix, iy, iz] = symmetry.apply(i, {x, y, z});
+              fill(ix, iy, iz);
+            }
+          }
+        }
+      }
+    }
+  }
+
+  // Builder edits repeat at each image of the symmetry, if the host set one:
+  // the setBlock and fillBlocks exports, placeVoxels, and applyCsg. Edits by
+  // the simulation, hooks, or the network don't. See symmetry.h.
+  void setSymmetry(const Symmetry& value) {
+    symmetry = value;
+  }
+
+  // Sets the block at each image of (x, y, z), or at none of them if any of
+  // them is in a region that the editor may not edit.
+  void setBlockSymmetric(int x, int y, int z, Block block) {
+    if (!checkSymmetricEdit({x, y, z}, {x, y, z})) return;
+    std::vector<std::array<int, 3>> images;
+    symmetry.images({x, y, z}, images);
+    for (const auto& [ix, iy, iz] : images) setBlock(ix, iy, iz, block);
+  }
+
+  // The builder's selection. While the selection mask is on, fillBlocks,
+  // placeVoxels, and applyCsg skip voxels outside of it. See selection.h.
+  void setSelection(const std::optional<Selection>& value) {
+    selection = value;
+  }
+
+  const std::optional<Selection>& getSelection() const {
+    return selection;
+  }
+
+  void expandSelection(int face, int amount) {
+    if (selection) selection->expand(face, amount);
+  }
+
+  void shiftSelection(int dx, int dy, int dz) {
+    if (selection) selection->shift(dx, dy, dz);
+  }
+
+  void setSelectionMask(bool value) {
+    selection_mask = value;
+  }
+
+  // Measures the selection. Returns its volume, the number of its voxels in
+  // loaded chunks, and the surface area of its blocks (their faces that touch
+  // air, in or out of the selection), followed by a count and (block, count)
+  // for each block in the loaded voxels, in order of block. Returns nullptr
+  // if there's no selection.
+  const double* getSelectionStats() {
+    if (!selection) return nullptr;
+    static std::vector<double> result;
+    std::array<int64_t, 256> histogram = {};
+    auto loaded = int64_t{0};
+    auto surface = int64_t{0};
+
+    const auto& [min, max] = *selection;
+    const auto y0 = std::max(min[1], 0);
+    const auto y1 = std::min(max[1], kBuildHeight - 1);
+    chunks.each([&](Point point) {
+      if (!chunks.get(point)) return false;
+      const auto bx = point.x << kChunkBits, bz = point.z << kChunkBits;
+      const auto x0 = std::max(min[0], bx);
+      const auto x1 = std::min(max[0], bx + kChunkWidth - 1);
+      const auto z0 = std::max(min[2], bz);
+      const auto z1 = std::min(max[2], bz + kChunkWidth - 1);
+      for (auto x = x0; x <= x1; x++) {
+        for (auto z = z0; z <= z1; z++) {
+          for (auto y = y0; y <= y1; y++) {
+            const auto block = getBlock(x, y, z);
+            histogram[static_cast<size_t>(block)]++;
+            loaded++;
+            if (block == Block::Air) continue;
+            surface += (getBlock(x - 1, y, z) == Block::Air) +
+                       (getBlock(x + 1, y, z) == Block::Air) +
+                       (getBlock(x, y - 1, z) == Block::Air) +
+                       (getBlock(x, y + 1, z) == Block::Air) +
+                       (getBlock(x, y, z - 1) == Block::Air) +
+                       (getBlock(x, y, z + 1) == Block::Air);
+          }
+        }
+      }
+      return false;
+    });
+
+    result.assign({static_cast<double>(selection->volume()),
+                   static_cast<double>(loaded),
+                   static_cast<double>(surface), 0});
+    for (size_t i = 0; i < histogram.size(); i++) {
+      if (histogram[i] == 0) continue;
+      result.push_back(static_cast<double>(i));
+      result.push_back(static_cast<double>(histogram[i]));
+      result[3]++;
+    }
+    return result.data();
+  }
+
+  // Replaces one block with another in the selection, like a fillBlocks that
+  // only touches voxels containing `from`. Returns the number of blocks
+  // changed, or 0 if there's no selection or the edit isn't allowed.
+  int replaceInSelection(Block from, Block to, bool collect_drops) {
+    clearEditDrops();
+    if (!selection || from == to) return 0;
+    const auto& [min, max] = *selection;
+    const auto y0 = std::max(min[1], 0);
+    const auto y1 = std::min(max[1], kBuildHeight - 1);
+    if (y0 > y1) return 0;
+    if (!checkEdit({min[0], y0, min[2]}, {max[0], y1, max[2]})) return 0;
+
+    auto result = 0;
+    for (auto x = min[0]; x <= max[0]; x++) {
+      for (auto z = min[2]; z <= max[2]; z++) {
+        for (auto y = y0; y <= y1; y++) {
+          if (getBlock(x, y, z) != from) continue;
+          setBlock(x, y, z, to);
+          if (collect_drops) recordEditDrop(from, x, y, z);
+          result++;
+        }
+      }
+    }
+    return result;
+  }
+
+  // Like checkEdit, for the images of the box under the symmetry.
+  bool checkSymmetricEdit(const std::array<int, 3>& min,
+                          const std::array<int, 3>& max) {
+    for (auto i = 0; i < symmetry.count(); i++) {
+      auto lo = min, hi = max;
+      symmetry.applyToBox(i, lo, hi);
+      if (!checkEdit(lo, hi)) return false;
+    }
+    return true;
+  }
+
+  bool inSelectionMask(int x, int y, int z) const {
+    return !selection_mask || !selection || selection->contains(x, y, z);
+  }
+
+  // Land claims: edits inside a protected region fail unless the current
+  // editor owns it, and we push an EditRejected event instead. Editor 0 is
+  // the server, or the engine's own simulation, and may edit anything, so
+  // owners must be nonzero. Bulk edits are all-or-nothing.
+  void addProtectedRegion(const ProtectedRegion& region) {
+    assert(region.owner != 0);
+    for (auto i = 0; i < 3; i++) assert(region.min[i] <= region.max[i]);
+    protected_regions.push_back(region);
+  }
+
+  void removeProtectedRegions(int owner) {
+    auto& regions = protected_regions;
+    regions.erase(std::remove_if(regions.begin(), regions.end(),
+                                 [&](const ProtectedRegion& region) {
+                                   return region.owner == owner;
+                                 }),
+                  regions.end());
+  }
+
+  // Sets the owner on whose behalf we make subsequent edits. Set it before
+  // applying a player's edit, and reset it to 0 afterwards, so that ticks
+  // such as fire spread aren't subject to claims.
+  void setEditor(int owner) {
+    editor = owner;
+  }
+
+  // Returns true if the current editor may edit every voxel in the inclusive
+  // box [min, max]. If not, pushes an EditRejected event and returns false.
+  bool checkEdit(const std::array<int, 3>& min, const std::array<int, 3>& max) {
+    const auto region = getEditBlocker(min, max);
+    if (!region) return true;
+    events.push({EventType::EditRejected, min[0], min[1], min[2],
+                 region->owner, editor});
+    return false;
+  }
+
+  // Returns a protected region that the current editor may not edit and that
+  // overlaps the inclusive box [min, max], or nullptr if there isn't one.
+  const ProtectedRegion* getEditBlocker(
+      const std::array<int, 3>& min, const std::array<int, 3>& max) const {
+    if (editor == 0) return nullptr;
+    for (const auto& region : protected_regions) {
+      if (region.owner == editor) continue;
+      auto overlaps = true;
+      for (auto i = 0; i < 3; i++) {
+        if (region.max[i] < min[i] || max[i] < region.min[i]) overlaps = false;
+      }
+      if (overlaps) return &region;
+    }
+    return nullptr;
+  }
+
+  // Validates a block edit that a player sent to the server, and applies it
+  // if it passes, as that player. Either way, we push a PlayerEdit event with
+  // the verdict. Player ids are editors, so they must be nonzero. The edit
+  // rules and rate limits are shared by every dimension. See edits.h.
+  //
+  // The block id comes from the client, so it may be any int.
+  EditVerdict tryPlayerEdit(int player, int x, int y, int z, int block,
+                            const EditContext& context,
+                            const EditRules& rules, EditLimiter& limiter) {
+    assert(player != 0);
+    const auto verdict = checkPlayerEdit(
+        player, x, y, z, block, context, rules, limiter);
+    if (verdict == EditVerdict::Accepted) {
+      applyPlayerEdit(player, x, y, z, static_cast<Block>(block));
+    }
+    events.push({EventType::PlayerEdit, x, y, z,
+                 player, static_cast<int>(verdict)});
+    return verdict;
+  }
+
+  // Sets a block as the given player, without changing the current editor.
+  void applyPlayerEdit(int player, int x, int y, int z, Block block) {
+    const auto saved = editor;
+    editor = player;
+    setBlock(x, y, z, block);
+    editor = saved;
+  }
+
+  // The drops recorded by the last bulk edit: a count, followed by (block,
+  // count, x, y, z) for each destroyed block type, where (x, y, z) is the
+  // centroid of the destroyed blocks of that type.
+  const float* getEditDrops() {
+    static std::vector<float> result;
+    result.clear();
+    result.push_back(static_cast<float>(edit_drops.size()));
+    for (const auto& drop : edit_drops) {
+      const auto n = static_cast<double>(drop.count);
+      result.push_back(static_cast<float>(drop.block));
+      result.push_back(static_cast<float>(drop.count));
+      result.push_back(static_cast<float>(drop.x / n + 0.5));
+      result.push_back(static_cast<float>(drop.y / n + 0.5));
+      result.push_back(static_cast<float>(drop.z / n + 0.5));
+    }
+    return result.data();
+  }
+
+  // Wraps chunk coordinates modulo the given number of chunks, for toroidal
+  // worlds. We don't wrap loaded chunks: instead, chunks keep unbounded
+  // coordinates, and worldgen and saves use wrapped ones. That way, the
+  // neighbors of a chunk on the seam are loaded as usual, so meshing, AO,
+  // lighting, and block queries all work across it without special cases.
+  //
+  // That means that a world must be wider than the loaded area; otherwise,
+  // two loaded chunks would be copies of one chunk, and edits would diverge.
+  // LOD meshes may show more copies of the world, which is fine.
+  void setWrap(int num_chunks) {
+    assert(num_chunks >= 0);
+    wrap = num_chunks;
+    worldgen.setWrap(num_chunks);
+  }
+
+  Point wrapPoint(Point point) const {
+    if (wrap == 0) return point;
+    return {((point.x % wrap) + wrap) % wrap, ((point.z % wrap) + wrap) % wrap};
+  }
+
+  void setFrontierDecimation(int min_level, int max_error) {
+    frontier.setDecimation(min_level, max_error);
+  }
+
+  void setFrontierSkirtDepth(int depth) {
+    frontier.setSkirtDepth(depth);
+  }
+
+  // Switches the algorithm used to mesh chunks, and remeshes every loaded
+  // chunk with it. LOD meshes are heightmaps, so they're unaffected.
+  void setMesherKind(MesherKind kind) {
+    assert(kind < MesherKind::Count);
+    if (mesher.kind == kind) return;
+    mesher.kind = kind;
+    chunks.each([&](Point point) {
+      const auto chunk = chunks.get(point);
+      if (chunk) chunk->markAllDirty();
+      return false;
+    });
+  }
+
+  // Keeps octree copies of unloaded chunks within `radius` chunks of the
+  // center. See far_chunks. Pass 0 to disable this and drop all copies.
+  void setFarChunkRadius(int radius) {
+    assert(radius >= 0);
+    far_radius = radius;
+    if (radius == 0) far_chunks.clear();
+  }
+
+  void setPointLight(int x, int y, int z, int level) {
+    if (!(0 <= y && y < kWorldHeight)) return;
+
+    const auto cx = x >> kChunkBits, xm = x & kChunkMask;
+    const auto cz = z >> kChunkBits, zm = z & kChunkMask;
+    const auto chunk = chunks.get({cx, cz});
+
+    // We can't support a block light of kSunlightLevel until we have separate
+    // channels for block light and sunlight.
+    level = std::min(level, static_cast<int>(kSunlightLevel - 1));
+    if (chunk) chunk->setPointLight(xm, y, zm, level);
+  }
+
+  // Moves the dynamic light `id` to the voxel (x, y, z), creating it if it
+  // doesn't exist. A level of 0 removes it. Dynamic lights are point lights
+  // for moving sources, like a torch in a player's hand: we relight around
+  // their old and new voxels incrementally, like for any other edit, and we
+  // keep them while their chunks are unloaded.
+  void setDynamicLight(int id, int x, int y, int z, int level) {
+    level = std::clamp(level, 0, static_cast<int>(kSunlightLevel - 1));
+    const auto it = dynamic_lights.find(id);
+    std::optional<DynamicLight> old;
+    if (it != dynamic_lights.end()) old = it->second;
+    if (old && old->x == x && old->y == y && old->z == z &&
+        old->level == level) {
+      return;
+    }
+
+    if (level > 0) {
+      dynamic_lights[id] = {x, y, z, level};
+    } else if (old) {
+      dynamic_lights.erase(it);
+    }
+    if (old) refreshDynamicLight(old->x, old->y, old->z);
+    if (level > 0) refreshDynamicLight(x, y, z);
+  }
+
+  // Sets the chunk's dynamic light at (x, y, z) to the brightest of the
+  // dynamic lights there. There are few dynamic lights, so we scan them.
+  void refreshDynamicLight(int x, int y, int z) {
+    if (!(0 <= y && y < kWorldHeight)) return;
+
+    const auto cx = x >> kChunkBits, xm = x & kChunkMask;
+    const auto cz = z >> kChunkBits, zm = z & kChunkMask;
+    const auto chunk = chunks.get({cx, cz});
+    if (!chunk) return;
+
+    auto level = 0;
+    for (const auto& [id, light] : dynamic_lights) {
+      if (light.x == x && light.y == y && light.z == z) {
+        level = std::max(level, light.level);
+      }
+    }
+    chunk->setDynamicLight(xm, y, zm, level);
+  }
+
+  // Projectiles are stepped here, rather than with the host's entities,
+  // because there may be thousands of them in flight. Ids are chosen by the
+  // host, e.g. entity ids; adding a projectile with an existing id replaces
+  // it. Projectiles don't collide with entities: the host can check their
+  // positions against its spatial index.
+  void addProjectile(int id, int kind, const std::array<double, 3>& position,
+                     const std::array<double, 3>& velocity) {
+    assert(registry.getProjectileKind(kind));
+    removeProjectile(id);
+    projectiles.push_back({id, kind, position, velocity, false});
+  }
+
+  void removeProjectile(int id) {
+    projectiles.erase(std::remove_if(projectiles.begin(), projectiles.end(),
+                                     [&](const Projectile& projectile) {
+                                       return projectile.id == id;
+                                     }),
+                      projectiles.end());
+  }
+
+  // Advances each projectile by dt seconds, pushing a ProjectileHit event
+  // for each solid block that it enters, and returns their states: a count,
+  // followed by (id, x, y, z, stuck) for each. We drop projectiles that fly
+  // into unloaded chunks.
+  const float* stepProjectiles(double dt) {
+    auto count = size_t{0};
+    for (auto& projectile : projectiles) {
+      if (!projectile.stuck && !stepProjectile(projectile, dt)) continue;
+      projectiles[count++] = projectile;
+    }
+    projectiles.resize(count);
+    return getProjectiles();
+  }
+
+  // Returns projectile states in the format of stepProjectiles.
+  const float* getProjectiles() const {
+    static std::vector<float> result;
+    result.clear();
+    result.push_back(static_cast<float>(projectiles.size()));
+    for (const auto& [id, kind, position, velocity, stuck] : projectiles) {
+      result.push_back(static_cast<float>(id));
+      for (const auto x : position) result.push_back(static_cast<float>(x));
+      result.push_back(stuck ? 1 : 0);
+    }
+    return result.data();
+  }
+
+  // Returns false if the projectile left the loaded chunks.
+  bool stepProjectile(Projectile& projectile, double dt) {
+    // Back off from the faces of blocks that we stop at, so that the next
+    // step starts outside of them.
+    constexpr auto kEpsilon = 1e-3;
+
+    const auto& kind = *registry.getProjectileKind(projectile.kind);
+    auto& position = projectile.position;
+    auto& velocity = projectile.velocity;
+    const auto start = position;
+    velocity[1] -= kind.gravity * dt;
+    const auto damping = std::max(1 - kind.drag * dt, 0.0);
+    for (auto& v : velocity) v *= damping;
+
+    auto dir = velocity;
+    const auto length = normalize(dir) * dt;
+    if (length == 0) return true;
+
+    auto first = true, lost = false;
+    auto response = ProjectileResponse::Pass;
+    auto axis = 0;
+    std::array<int, 3> prev;
+    const auto t = traceRay(position, dir, length, [&](int x, int y, int z) {
+      const auto cell = std::array{x, y, z};
+      if (first) {
+        first = false;
+        prev = cell;
+        return false;
+      }
+      const auto block = getBlock(x, y, z);
+      if (block == Block::Unknown) {
+        lost = true;
+        return true;
+      }
+
+      auto hit = ProjectileResponse::Pass;
+      if (registry.getBlock(block).solid) {
+        events.push({EventType::ProjectileHit, x, y, z,
+                     projectile.id, static_cast<int>(block)});
+        hit = kind.responses[static_cast<size_t>(block)];
+      }
+      if (hit == ProjectileResponse::Pass) {
+        prev = cell;
+        return false;
+      }
+      for (auto i = 0; i < 3; i++) {
+        if (cell[i] != prev[i]) axis = i;
+      }
+      response = hit;
+      return true;
+    });
+    if (lost) return false;
+
+    for (auto i = 0; i < 3; i++) position[i] += t * dir[i];
+    getDebugDraw().line(kDebugProjectiles, start, position,
+                        response == ProjectileResponse::Pass ? 0xff4040ff
+                                                             : 0xffff40ff);
+    if (response == ProjectileResponse::Stick) {
+      for (auto i = 0; i < 3; i++) position[i] -= kEpsilon * dir[i];
+      velocity = {0, 0, 0};
+      projectile.stuck = true;
+    } else if (response == ProjectileResponse::Bounce) {
+      position[axis] -= dir[axis] > 0 ? kEpsilon : -kEpsilon;
+      velocity[axis] = -velocity[axis];
+      for (auto& v : velocity) v *= kind.restitution;
+    }
+    return true;
+  }
+
+  // Item drops are stepped here, like projectiles, because mining a vein or
+  // blowing up a hill can drop hundreds of them. Unlike projectiles, we pick
+  // their ids, since merging removes drops. Drops in unloaded chunks freeze
+  // in place, but they still despawn.
+  int addItemDrop(int item, int count, const std::array<double, 3>& position,
+                  const std::array<double, 3>& velocity) {
+    assert(item >= 0 && count > 0);
+    const auto id = next_item_drop_id++;
+    item_drops.push_back({id, item, count, position, velocity, 0});
+    return id;
+  }
+
+  void removeItemDrop(int id) {
+    item_drops.erase(std::remove_if(item_drops.begin(), item_drops.end(),
+                                    [&](const ItemDrop& drop) {
+                                      return drop.id == id;
+                                    }),
+                     item_drops.end());
+  }
+
+  void setItemDropRules(const ItemDropRules& rules) {
+    item_drop_rules = rules;
+  }
+
+  // Magnets, such as players, pull nearby drops toward them during steps.
+  void setItemMagnets(const double* positions, int count) {
+    assert(count >= 0);
+    item_magnets.resize(count);
+    for (auto i = 0; i < count; i++) {
+      for (auto j = 0; j < 3; j++) item_magnets[i][j] = positions[3 * i + j];
+    }
+  }
+
+  // Advances each drop by dt seconds: pulls it toward the nearest magnet,
+  // moves it, despawns it if it's too old, and merges it into nearby drops
+  // of the same item. Returns their states: a count, followed by (id, item,
+  // count, x, y, z) for each.
+  const float* stepItemDrops(double dt, const ItemRegistry& items) {
+    const auto& rules = item_drop_rules;
+    auto count = size_t{0};
+    for (auto& drop : item_drops) {
+      drop.age += dt;
+      if (drop.age >= rules.despawn_time) continue;
+      pullItemDrop(drop);
+      stepItemDrop(drop, dt);
+      item_drops[count++] = drop;
+    }
+    item_drops.resize(count);
+    mergeItemDrops(items);
+    drawItemDrops();
+    return getItemDrops();
+  }
+
+  // Returns item drop states in the format of stepItemDrops.
+  const float* getItemDrops() const {
+    static std::vector<float> result;
+    result.clear();
+    result.push_back(static_cast<float>(item_drops.size()));
+    for (const auto& drop : item_drops) {
+      result.push_back(static_cast<float>(drop.id));
+      result.push_back(static_cast<float>(drop.item));
+      result.push_back(static_cast<float>(drop.count));
+      for (const auto x : drop.position) {
+        result.push_back(static_cast<float>(x));
+      }
+    }
+    return result.data();
+  }
+
+  void drawItemDrops() const {
+    auto& debug = getDebugDraw();
+    if (!debug.enabled(kDebugItemDrops)) return;
+
+    constexpr auto kSize = 0.125;
+    for (const auto& drop : item_drops) {
+      auto min = drop.position, max = drop.position;
+      for (auto i = 0; i < 3; i++) {
+        min[i] -= kSize;
+        max[i] += kSize;
+      }
+      debug.box(kDebugItemDrops, min, max, 0x40ff40ff);
+      const auto label = std::to_string(drop.item) + " x" +
+                         std::to_string(drop.count);
+      debug.text(kDebugItemDrops, max, 0xffffffff, label);
+    }
+    for (const auto& magnet : item_magnets) {
+      auto min = magnet, max = magnet;
+      for (auto i = 0; i < 3; i++) {
+        min[i] -= item_drop_rules.magnet_radius;
+        max[i] += item_drop_rules.magnet_radius;
+      }
+      debug.box(kDebugItemDrops, min, max, 0x4040ff80);
+    }
+  }
+
+  // Calls take(id, item, count) for each drop within radius of the position
+  // that's older than the pickup delay, nearest first. take returns the count
+  // that it didn't accept, which stays in the drop.
+  template <typename Fn>
+  void pickUpItemDrops(const std::array<double, 3>& position, double radius,
+                       Fn take) {
+    static std::vector<std::pair<double, size_t>> nearby;
+    nearby.clear();
+    for (auto i = size_t{0}; i < item_drops.size(); i++) {
+      const auto& drop = item_drops[i];
+      if (drop.age < item_drop_rules.pickup_delay) continue;
+      auto distance = 0.0;
+      for (auto j = 0; j < 3; j++) {
+        const auto d = drop.position[j] - position[j];
+        distance += d * d;
+      }
+      if (distance <= radius * radius) nearby.push_back({distance, i});
+    }
+    std::sort(nearby.begin(), nearby.end());
+
+    for (const auto& [distance, index] : nearby) {
+      auto& drop = item_drops[index];
+      const int left = take(drop.id, drop.item, drop.count);
+      drop.count = std::clamp(left, 0, drop.count);
+    }
+    item_drops.erase(std::remove_if(item_drops.begin(), item_drops.end(),
+                                    [](const ItemDrop& drop) {
+                                      return drop.count == 0;
+                                    }),
+                     item_drops.end());
+  }
+
+  void pullItemDrop(ItemDrop& drop) const {
+    const auto& rules = item_drop_rules;
+    if (drop.age < rules.pickup_delay) return;
+
+    const std::array<double, 3>* best = nullptr;
+    auto best_distance = rules.magnet_radius * rules.magnet_radius;
+    for (const auto& magnet : item_magnets) {
+      auto distance = 0.0;
+      for (auto i = 0; i < 3; i++) {
+        const auto d = magnet[i] - drop.position[i];
+        distance += d * d;
+      }
+      if (distance > best_distance) continue;
+      best_distance = distance;
+      best = &magnet;
+    }
+    if (!best) return;
+
+    auto dir = std::array<double, 3>{};
+    for (auto i = 0; i < 3; i++) dir[i] = (*best)[i] - drop.position[i];
+    if (normalize(dir) == 0) return;
+    for (auto i = 0; i < 3; i++) drop.velocity[i] = rules.magnet_speed * dir[i];
+  }
+
+  // Moves the drop one axis at a time, in substeps of at most half a voxel,
+  // and stops it at the faces of solid blocks. Drops are points.
+  void stepItemDrop(ItemDrop& drop, double dt) {
+    constexpr auto kEpsilon = 1e-3;
+    constexpr auto kMaxMove = 0.5;
+
+    const auto& rules = item_drop_rules;
+    auto& position = drop.position;
+    auto& velocity = drop.velocity;
+    const auto cell = [](double x) { return static_cast<int>(std::floor(x)); };
+    const auto frozen = getBlock(cell(position[0]), cell(position[1]),
+                                 cell(position[2])) == Block::Unknown;
+    if (frozen) return;
+
+    velocity[1] -= rules.gravity * dt;
+    const auto damping = std::max(1 - rules.drag * dt, 0.0);
+    for (auto& v : velocity) v *= damping;
+
+    auto max = 0.0;
+    for (const auto v : velocity) max = std::max(max, std::abs(v * dt));
+    const auto steps = std::max(static_cast<int>(std::ceil(max / kMaxMove)), 1);
+    const auto step = dt / steps;
+
+    for (auto i = 0; i < steps; i++) {
+      for (auto axis = 0; axis < 3; axis++) {
+        const auto v = velocity[axis];
+        if (v == 0) continue;
+        auto next = position;
+        next[axis] += v * step;
+        const auto block =
+            getBlock(cell(next[0]), cell(next[1]), cell(next[2]));
+        if (block == Block::Unknown || registry.getBlock(block).solid) {
+          const auto face = cell(next[axis]) + (v > 0 ? 0 : 1);
+          position[axis] = face + (v > 0 ? -kEpsilon : kEpsilon);
+          velocity[axis] = 0;
+
+          // Ground friction: drops on the ground slide to a stop.
+          if (axis == 1 && v < 0) {
+            const auto friction = std::max(1 - 8 * dt, 0.0);
+            velocity[0] *= friction;
+            velocity[2] *= friction;
+          }
+        } else {
+          position[axis] = next[axis];
+        }
+      }
+    }
+  }
+
+  // Merges drops of the same item within the merge radius into the older
+  // drop, up to the item's max stack size. We bucket drops in a grid with
+  // cells as wide as the radius, so we only compare neighboring buckets.
+  void mergeItemDrops(const ItemRegistry& items) {
+    const auto radius = item_drop_rules.merge_radius;
+    if (radius <= 0 || item_drops.size() < 2) return;
+
+    static HashMap<uint64_t, std::vector<size_t>> buckets;
+    buckets.clear();
+    const auto key = [](int x, int y, int z) {
+      const auto bits = [](int v) {
+        return static_cast<uint64_t>(static_cast<uint32_t>(v) & 0x1fffff);
+      };
+      return (bits(x) << 42) | (bits(y) << 21) | bits(z);
+    };
+    const auto bucket = [&](double x) {
+      return static_cast<int>(std::floor(x / radius));
+    };
+    for (auto i = size_t{0}; i < item_drops.size(); i++) {
+      const auto& p = item_drops[i].position;
+      buckets[key(bucket(p[0]), bucket(p[1]), bucket(p[2]))].push_back(i);
+    }
+
+    // Drops are in order of id, so lower indices are older.
+    for (auto i = size_t{0}; i < item_drops.size(); i++) {
+      auto& drop = item_drops[i];
+      const auto max = items.getMaxStack(drop.item);
+      if (drop.count == 0 || drop.count >= max) continue;
+
+      const auto& p = drop.position;
+      const auto bx = bucket(p[0]), by = bucket(p[1]), bz = bucket(p[2]);
+      for (auto dx = -1; dx <= 1; dx++) {
+        for (auto dy = -1; dy <= 1; dy++) {
+          for (auto dz = -1; dz <= 1; dz++) {
+            const auto it = buckets.find(key(bx + dx, by + dy, bz + dz));
+            if (it == buckets.end()) continue;
+            for (const auto j : it->second) {
+              if (j <= i) continue;
+              auto& other = item_drops[j];
+              if (other.item != drop.item || other.count == 0) continue;
+              if (drop.count + other.count > max) continue;
+
+              auto distance = 0.0;
+              for (auto k = 0; k < 3; k++) {
+                const auto d = other.position[k] - p[k];
+                distance += d * d;
+              }
+              if (distance > radius * radius) continue;
+              drop.count += other.count;
+              drop.age = std::min(drop.age, other.age);
+              other.count = 0;
+            }
+          }
+        }
+      }
+    }
+    item_drops.erase(std::remove_if(item_drops.begin(), item_drops.end(),
+                                    [](const ItemDrop& drop) {
+                                      return drop.count == 0;
+                                    }),
+                     item_drops.end());
+  }
+
+  void recenter(Point p) {
+    const auto c = Point{p.x >> kChunkBits, p.z >> kChunkBits};
+    chunks.recenter(c);
+    frontier.recenter(c);
+    pruneFarChunks(c);
+
+    auto loaded = 0;
+    chunks.each([&](Point point) {
+      const auto existing = chunks.get(point);
+      if (existing != nullptr) return false;
+      chunks.set(point, this);
+      return (++loaded) == kNumChunksToLoadPerFrame;
+    });
+  }
+
+  void remesh() {
+    auto lit = 0, meshed = 0, total = 0;
+    chunks.each([&](Point point) {
+      total++;
+      const auto canRelight = lit < kNumChunksToLightPerFrame;
+      const auto canRemesh = total <= 9 || meshed < kNumChunksToMeshPerFrame;
+      if (!(canRelight || canRemesh)) return true;
+
+      const auto chunk = chunks.get(point);
+      if (!chunk) return false;
+
+      if (canRemesh && chunk->needsRemesh()) {
+        chunk->remeshChunk();
+        events.push({EventType::ChunkMeshed, point.x, 0, point.z, 0, 0});
+        meshed++;
+      } else if (canRelight && chunk->needsRelight()) {
+        chunk->relightChunk();
+        events.push({EventType::LightUpdated, point.x, 0, point.z, 0, 0});
+        lit++;
+      }
+      return false;
+    });
+    frontier.remeshFrontier();
+
+    auto cast = 0;
+    chunks.each([&](Point point) {
+      const auto chunk = chunks.get(point);
+      if (!chunk || !chunk->needsBake()) return false;
+      cast += chunk->continueBake(kNumBakeRaysPerFrame - cast);
+      return cast >= kNumBakeRaysPerFrame;
+    });
+  }
+
+  // Remeshes each of the `count` chunks listed as (cx, cz) pairs in `points`,
+  // ignoring the per-frame meshing budget. Returns a (cx, cz, sections)
+  // triple per chunk, where sections is a bitmask of the sections that we
+  // remeshed: 0 if the chunk isn't loaded, isn't ready, or was up to date.
+  const int* meshMany(const int* points, int count) {
+    static std::vector<int> result;
+    result.clear();
+    for (auto i = 0; i < count; i++) {
+      const auto point = Point{points[2 * i + 0], points[2 * i + 1]};
+      const auto chunk = chunks.get(point);
+      auto sections = 0;
+      if (chunk && chunk->needsRemesh()) {
+        sections = static_cast<int>(chunk->remeshChunk());
+        events.push({EventType::ChunkMeshed, point.x, 0, point.z, 0, 0});
+      }
+      result.insert(result.end(), {point.x, point.z, sections});
+    }
+    return result.data();
+  }
+
+  // Does up to `budget` steps of work to move chunk (cx, cz) toward the
+  // target stage, and returns the stage that it reaches. A step loads one
+  // chunk, lights one chunk, or meshes one chunk. Lighting needs the chunk's
+  // neighbors, so we load them first. We can only load chunks within the
+  // loaded radius around the center. A budget of 0 queries the stage.
+  ChunkStage advanceChunk(int cx, int cz, ChunkStage target, int budget) {
+    const auto point = Point{cx, cz};
+    const auto load = [&](Point p) {
+      if (chunks.get(p)) return true;
+      if (budget == 0 || !chunks.contains(p)) return false;
+      chunks.set(p, this);
+      budget--;
+      return true;
+    };
+
+    if (target == ChunkStage::Empty || !load(point)) {
+      const auto chunk = chunks.get(point);
+      return chunk ? chunk->getStage() : ChunkStage::Empty;
+    }
+    if (target > ChunkStage::Decorated) {
+      for (const auto& delta : kNeighbors) load(point + delta);
+    }
+
+    const auto chunk = chunks.get(point);
+    if (target >= ChunkStage::Lit && budget > 0 &&
+        chunk->isReady() && chunk->getStage() == ChunkStage::Decorated) {
+      chunk->lightChunk();
+      events.push({EventType::LightUpdated, cx, 0, cz, 0, 0});
+      budget--;
+    }
+    if (target >= ChunkStage::Meshed && budget > 0 && chunk->needsRemesh()) {
+      chunk->remeshChunk();
+      events.push({EventType::ChunkMeshed, cx, 0, cz, 0, 0});
+      budget--;
+    }
+    return chunk->getStage();
+  }
+
+  // Returns the fraction of sound that reaches the listener from the source,
+  // in [0, 1], for driving a low-pass filter on the source.
+  double audioOcclusion(const std::array<double, 3>& listener,
+                        const std::array<double, 3>& source) {
+    auto total = 0.0;
+    for (const auto& offset : kAudioRayOffsets) {
+      auto start = listener, dir = source;
+      for (auto i = 0; i < 3; i++) {
+        start[i] += offset[i];
+        dir[i] -= listener[i];
+      }
+      const auto length = normalize(dir);
+      if (length == 0) {
+        total += 1;
+        continue;
+      }
+
+      auto blocked = 0;
+      traceSolidRay(start, dir, length, [&](int x, int y, int z) {
+        if (isSolid(x, y, z)) blocked++;
+        return false;
+      });
+      total += pow(kAudioTransmission, blocked);
+    }
+    return total / std::size(kAudioRayOffsets);
+  }
+
+  // Returns the distance along the ray from start in direction dir to the
+  // first solid block, or `length` if there isn't one within that range.
+  // Unloaded chunks stop the ray.
+  double raycast(const std::array<double, 3>& start,
+                 const std::array<double, 3>& dir, double length) {
+    auto unit = dir;
+    if (normalize(unit) == 0) return 0;
+    const auto hit = [&](int x, int y, int z) {
+      return isSolid(x, y, z) || getBlock(x, y, z) == Block::Unknown;
+    };
+    return traceSolidRay(start, unit, length, hit);
+  }
+
+  // Returns the mean distance to a solid block along a bundle of rays in all
+  // directions, capped at kAudioRayLength, for sizing reverb at a point.
+  double roomSizeEstimate(const std::array<double, 3>& point) {
+    auto total = 0.0, count = 0.0;
+    for (auto dx = -1; dx <= 1; dx++) {
+      for (auto dy = -1; dy <= 1; dy++) {
+        for (auto dz = -1; dz <= 1; dz++) {
+          auto dir = std::array<double, 3>{1.0 * dx, 1.0 * dy, 1.0 * dz};
+          if (normalize(dir) == 0) continue;
+          const auto hit = [&](int x, int y, int z) {
+            return isSolid(x, y, z);
+          };
+          total += traceSolidRay(point, dir, kAudioRayLength, hit);
+          count++;
+        }
+      }
+    }
+    return total / count;
+  }
+
+  // Runs one game tick: scheduled updates that are due, followed by random
+  // block updates in all loaded chunks.
+  void tick() {
+    ticks++;
+    for (auto i = 0; i < kMaxScheduledUpdatesPerTick; i++) {
+      if (scheduled.empty() || scheduled.top().tick > ticks) break;
+      const auto update = scheduled.top();
+      scheduled.pop();
+      runScheduledUpdate(update.x, update.y, update.z);
+    }
+
+    chunks.each([&](Point point) {
+      const auto chunk = chunks.get(point);
+      if (!chunk) return false;
+
+      const auto bx = point.x << kChunkBits, bz = point.z << kChunkBits;
+      for (auto i = 0; i < kRandomTicksPerChunk; i++) {
+        const auto index = static_cast<int>(rng.next() & 0xffff);
+        const auto x = (index >> 8) & 0xf;
+        const auto y = (index >> 0) & 0xff;
+        const auto z = (index >> 12) & 0xf;
+        if (y < kBuildHeight) randomTick(bx + x, y, bz + z);
+      }
+      return false;
+    });
+  }
+
+  // Advances fire in every loaded chunk by one tick.
+  void tickFires(bool raining) {
+    if (registry.getFireBlock() == Block::Air) return;
+    static std::vector<Point> points;
+    points.clear();
+    chunks.each([&](Point point) {
+      if (chunks.get(point)) points.push_back(point);
+      return false;
+    });
+    for (const auto& point : points) tickFire(point.x, point.z, raining);
+  }
+
+  // Advances fire in the chunk (cx, cz) by one tick. See fire.h. Each change
+  // marks the sections and lighting around it dirty, as setBlock does.
+  //
+  // Returns a count, followed by the inclusive bounds (x0, y0, z0, x1, y1,
+  // z1) of the blocks that changed in each chunk. Fire spreads across chunk
+  // borders, so there may be a box for each neighboring chunk, too.
+  const int* tickFire(int cx, int cz, bool raining) {
+    static std::vector<int> result;
+    result.assign(1, 0);
+    const auto chunk = chunks.get({cx, cz});
+    if (!chunk || registry.getFireBlock() == Block::Air) return result.data();
+
+    static std::vector<BurningVoxel> fires;
+    static std::vector<BlockChange> changes;
+    fires.clear();
+    changes.clear();
+
+    const auto bx = cx << kChunkBits, bz = cz << kChunkBits;
+    for (const auto index : chunk->getFires()) {
+      const auto xm = (index >> 8) & 0xf;
+      const auto ym = (index >> 0) & 0xff;
+      const auto zm = (index >> 12) & 0xf;
+      const auto exposed = chunk->isSkyExposed(xm, ym, zm);
+      fires.push_back({bx + xm, ym, bz + zm, exposed});
+    }
+    voxels::tickFire(*this, registry, fires, raining, rng, changes);
+
+    const auto mark = [&](int x, int y, int z) {
+      const auto chunk = [](int x0, int z0) {
+        return Point{x0 >> kChunkBits, z0 >> kChunkBits};
+      };
+      const auto point = chunk(x, z);
+      for (auto i = 1; i < result.size(); i += 6) {
+        if (chunk(result[i], result[i + 2]) != point) continue;
+        const auto box = &result[i];
+        box[0] = std::min(box[0], x); box[3] = std::max(box[3], x);
+        box[1] = std::min(box[1], y); box[4] = std::max(box[4], y);
+        box[2] = std::min(box[2], z); box[5] = std::max(box[5], z);
+        return;
+      }
+      result.insert(result.end(), {x, y, z, x, y, z});
+      result[0]++;
+    };
+    for (const auto& [x, y, z, block] : changes) {
+      const auto old_block = getBlock(x, y, z);
+      setBlock(x, y, z, block);
+      if (getBlock(x, y, z) != old_block) mark(x, y, z);
+    }
+    return result.data();
+  }
+
+  // Recomputes power levels for the circuits touching the given positions,
+  // which the host has just edited, and stores them as block metadata. See
+  // power.h. Returns a count, followed by (x, y, z, level) for each wire or
+  // sink whose power level changed.
+  const int* propagatePower(const int* positions, int count) {
+    static std::vector<PowerChange> changes;
+    static std::vector<int> result;
+    voxels::propagatePower(*this, registry, positions, count, changes);
+
+    result.clear();
+    result.push_back(0);
+    for (const auto& [x, y, z, level] : changes) {
+      setMetadata(x, y, z, level);
+      result.insert(result.end(), {x, y, z, static_cast<int>(level)});
+      result[0]++;
+    }
+    return result.data();
+  }
+
+  // Pushes the line of blocks starting at (x, y, z) one block in direction
+  // `dir`, an index into kFaceNeighbors, moving their metadata with them.
+  // The line ends at the first Air or Water block, which it displaces. Fails,
+  // without moving anything, if the line is longer than max_count or if it
+  // includes a fixed or unloaded block, or if it would leave the world.
+  //
+  // Returns the number of blocks moved, or -1 on failure, followed by the
+  // inclusive bounds (x0, y0, z0, x1, y1, z1) of the blocks we changed.
+  const int* pushBlocks(int x, int y, int z, int dir, int max_count) {
+    assert(0 <= dir && dir < 6);
+    assert(max_count >= 0);
+    static std::array<int, 7> result;
+    result.fill(0);
+    result[0] = -1;
+
+    const auto [dx, dy, dz] = kFaceNeighbors[dir];
+    const auto movable = [&](Block block) {
+      return block != Block::Unknown && block != Block::Bedrock &&
+             !registry.hasTag(block, kBlockTagFixed);
+    };
+    const auto empty = [](Block block) {
+      return block == Block::Air || block == Block::Water;
+    };
+
+    auto count = 0;
+    for (;; count++) {
+      const auto ty = y + count * dy;
+      if (!(0 <= ty && ty < kBuildHeight)) return result.data();
+      const auto block = getBlock(x + count * dx, ty, z + count * dz);
+      if (empty(block)) break;
+      if (count == max_count || !movable(block)) return result.data();
+    }
+
+    const auto ex = x + count * dx, ey = y + count * dy, ez = z + count * dz;
+    const auto min = std::array{std::min(x, ex), std::min(y, ey), std::min(z, ez)};
+    const auto max = std::array{std::max(x, ex), std::max(y, ey), std::max(z, ez)};
+    if (!checkEdit(min, max)) return result.data();
+
+    for (auto i = count; i > 0; i--) {
+      const auto sx = x + (i - 1) * dx, sy = y + (i - 1) * dy;
+      const auto sz = z + (i - 1) * dz;
+      const auto metadata = getMetadata(sx, sy, sz);
+      auto entity = takeBlockEntity(sx, sy, sz);
+      setBlock(sx + dx, sy + dy, sz + dz, getBlock(sx, sy, sz));
+      setMetadata(sx + dx, sy + dy, sz + dz, metadata);
+      if (entity) {
+        putBlockEntity(sx + dx, sy + dy, sz + dz, std::move(*entity));
+      }
+    }
+    if (count > 0) setBlock(x, y, z, Block::Air);
+
+    result = {count, min[0], min[1], min[2], max[0], max[1], max[2]};
+    return result.data();
+  }
+
+  // Places blocks at offsets from (x, y, z). Fails, without placing any
+  // blocks, if a required block's cell is out of bounds or occupied. Cells
+  // that contain Air or the `replaceable` block are unoccupied. Blocks that
+  // aren't required are skipped if their cells are occupied.
+  bool stamp(int x, int y, int z, const std::vector<StampBlock>& blocks,
+             Block replaceable = Block::Air) {
+    const auto empty = [&](const StampBlock& b) {
+      const auto block = getBlock(x + b.dx, y + b.dy, z + b.dz);
+      return block == Block::Air || block == replaceable;
+    };
+    for (const auto& b : blocks) {
+      if (!b.required) continue;
+      const auto ty = y + b.dy;
+      if (!(0 <= ty && ty < kBuildHeight) || !empty(b)) return false;
+    }
+    for (const auto& b : blocks) {
+      if (empty(b)) setBlock(x + b.dx, y + b.dy, z + b.dz, b.block);
+    }
+    return true;
+  }
+
+  // Sets the block at each offset from (x, y, z), such as the voxels of a
+  // model from voxelizeMesh. Like fillBlocks, it's all-or-nothing under land
+  // claims and skips unloaded voxels. Returns the number of blocks changed.
+  int placeVoxels(int x, int y, int z,
+                  const std::vector<std::array<int, 3>>& offsets,
+                  Block block) {
+    if (offsets.empty()) return 0;
+    auto min = offsets[0], max = offsets[0];
+    for (const auto& offset : offsets) {
+      for (auto i = 0; i < 3; i++) {
+        min[i] = std::min(min[i], offset[i]);
+        max[i] = std::max(max[i], offset[i]);
+      }
+    }
+    const auto origin = std::array{x, y, z};
+    for (auto i = 0; i < 3; i++) {
+      min[i] += origin[i];
+      max[i] += origin[i];
+    }
+    if (!checkSymmetricEdit(min, max)) return 0;
+
+    auto result = 0;
+    std::vector<std::array<int, 3>> images;
+    for (const auto& [dx, dy, dz] : offsets) {
+      symmetry.images({x + dx, y + dy, z + dz}, images);
+      for (const auto& [ix, iy, iz] : images) {
+        if (!(0 <= iy && iy < kBuildHeight)) continue;
+        if (!inSelectionMask(ix, iy, iz)) continue;
+        const auto old_block = getBlock(ix, iy, iz);
+        if (old_block == block || old_block == Block::Unknown) continue;
+        setBlock(ix, iy, iz, block);
+        result++;
+      }
+    }
+    return result;
+  }
+
+  // Replaces the columns under a w x h heightmap, with its first sample at
+  // (x, z), by the layers up to each column's height and air above it. It's
+  // all-or-nothing under land claims, and skips unloaded columns. Returns
+  // the number of blocks changed. See heightmaps.h.
+  int importHeightmap(int x, int z, const uint16_t* samples, int w, int h,
+                      double scale, const std::vector<HeightmapLayer>& layers) {
+    if (w <= 0 || h <= 0 || layers.empty()) return 0;
+    if (!checkEdit({x, 0, z}, {x + w - 1, kBuildHeight - 1, z + h - 1})) {
+      return 0;
+    }
+
+    auto result = 0;
+    for (auto j = 0; j < h; j++) {
+      for (auto i = 0; i < w; i++) {
+        const auto cx = x + i, cz = z + j;
+        if (getBlock(cx, 0, cz) == Block::Unknown) continue;
+        const auto sample = samples[i + w * j];
+        const auto height =
+            std::min(heightmapColumnHeight(sample, scale), kBuildHeight);
+        for (auto y = 0; y < kBuildHeight; y++) {
+          const auto block = y < height
+              ? heightmapLayerBlock(layers, height - 1 - y) : Block::Air;
+          if (getBlock(cx, y, cz) == block) continue;
+          setBlock(cx, y, cz, block);
+          result++;
+        }
+      }
+    }
+    return result;
+  }
+
+  // Sets a w x h layer of blocks at height y, where cell (i, j) is the voxel
+  // (x + i, y, z + j) and Unknown cells stay as they are. It's all-or-nothing
+  // under land claims, and skips unloaded voxels. Returns the number of
+  // blocks changed.
+  int stampLayer(int x, int y, int z, int w, int h,
+                 const std::vector<Block>& cells) {
+    if (w <= 0 || h <= 0 || !(0 <= y && y < kBuildHeight)) return 0;
+    if (!checkEdit({x, y, z}, {x + w - 1, y, z + h - 1})) return 0;
+
+    auto result = 0;
+    for (auto j = 0; j < h; j++) {
+      for (auto i = 0; i < w; i++) {
+        const auto block = cells[i + w * j];
+        if (block == Block::Unknown) continue;
+        const auto old_block = getBlock(x + i, y, z + j);
+        if (old_block == block || old_block == Block::Unknown) continue;
+        setBlock(x + i, y, z + j, block);
+        result++;
+      }
+    }
+    return result;
+  }
+
+  // Writes a heightmap of the inclusive box of columns from (x0, z0) to (x1,
+  // z1), from the top solid voxel in each column. Unloaded columns are 0.
+  void exportHeightmap(int x0, int z0, int x1, int z1, double scale,
+                       std::vector<uint16_t>& result) {
+    result.clear();
+    for (auto z = z0; z <= z1; z++) {
+      for (auto x = x0; x <= x1; x++) {
+        auto height = 0;
+        for (auto y = kBuildHeight - 1; y >= 0; y--) {
+          const auto block = getBlock(x, y, z);
+          if (block == Block::Unknown) break;
+          if (registry.getBlock(block).solid) {
+            height = y + 1;
+            break;
+          }
+        }
+        result.push_back(heightmapSample(height, scale));
+      }
+    }
+  }
+
+  // Finds the trigger volumes that each entity's AABB overlaps, given as six
+  // doubles per entity, and pushes a TriggerEnter or TriggerExit event for
+  // each change since the last call, plus a TriggerStay event for each
+  // continuing contact if stays is set. Pass every entity each time: any
+  // entity that's missing exits its triggers. See triggers.h.
+  void stepTriggers(const double* boxes, const int* entities, int count,
+                    bool stays) {
+    std::vector<std::pair<int, TriggerContacts>> current;
+    for (auto i = 0; i < count; i++) {
+      const auto box = &boxes[6 * i];
+      auto& [entity, contacts] = current.emplace_back();
+      entity = entities[i];
+
+      std::array<int, 3> lo, hi;
+      auto skip = false;
+      for (auto j = 0; j < 3; j++) {
+        lo[j] = static_cast<int>(std::floor(box[j]));
+        hi[j] = static_cast<int>(std::ceil(box[j + 3])) - 1;
+        if (!(hi[j] - lo[j] < kMaxTriggerExtent)) skip = true;
+      }
+      if (skip) continue;
+      lo[1] = std::max(lo[1], 0);
+      hi[1] = std::min(hi[1], kBuildHeight - 1);
+
+      for (auto x = lo[0]; x <= hi[0]; x++) {
+        for (auto z = lo[2]; z <= hi[2]; z++) {
+          for (auto y = lo[1]; y <= hi[1]; y++) {
+            const auto block = getBlock(x, y, z);
+            if (block == Block::Unknown) continue;
+            const auto& trigger = registry.getTrigger(block);
+            const auto bit = 1u << trigger.type;
+            if (trigger.type == 0 || (contacts.mask & bit)) continue;
+
+            const auto voxel = std::array{x, y, z};
+            auto overlaps = true;
+            for (auto j = 0; j < 3; j++) {
+              if (!(box[j] < voxel[j] + trigger.max[j] &&
+                    voxel[j] + trigger.min[j] < box[j + 3])) {
+                overlaps = false;
+              }
+            }
+            if (!overlaps) continue;
+            contacts.mask |= bit;
+            contacts.voxels[trigger.type] = voxel;
+          }
+        }
+      }
+    }
+
+    static std::vector<TriggerChange> changes;
+    triggers.update(current, stays, changes);
+    for (const auto& [phase, entity, type, voxel] : changes) {
+      const auto event = phase == TriggerPhase::Enter ? EventType::TriggerEnter
+                       : phase == TriggerPhase::Stay  ? EventType::TriggerStay
+                                                      : EventType::TriggerExit;
+      events.push({event, voxel[0], voxel[1], voxel[2], entity, type});
+    }
+  }
+
+  // Copies the voxels in the box from (x, y, z) to the clipboard. Voxels in
+  // unloaded chunks are Unknown. See clipboard.h.
+  void copyToClipboard(int x, int y, int z, Clipboard& clipboard) {
+    const auto [sx, sy, sz] = clipboard.size();
+    for (auto dz = 0; dz < sz; dz++) {
+      for (auto dx = 0; dx < sx; dx++) {
+        for (auto dy = 0; dy < sy; dy++) {
+          const auto ty = y + dy;
+          const auto block = 0 <= ty && ty < kBuildHeight
+              ? getBlock(x + dx, ty, z + dz) : Block::Unknown;
+          clipboard.set(dx, dy, dz, block);
+        }
+      }
+    }
+  }
+
+  // Combines the clipboard, with its min corner at (x, y, z), with the world.
+  // Like fillBlocks, it's all-or-nothing under land claims. Voxels whose edit
+  // priority is above that of their replacement keep their blocks. Returns
+  // the number of blocks changed.
+  int applyCsg(const Clipboard& clipboard, CsgOp op, int x, int y, int z) {
+    const auto [sx, sy, sz] = clipboard.size();
+    const auto max = std::array{x + sx - 1, y + sy - 1, z + sz - 1};
+    if (!checkSymmetricEdit({x, y, z}, max)) return 0;
+
+    auto result = 0;
+    std::vector<std::array<int, 3>> images;
+    for (auto dz = 0; dz < sz; dz++) {
+      for (auto dx = 0; dx < sx; dx++) {
+        for (auto dy = 0; dy < sy; dy++) {
+          const auto source = clipboard.get(dx, dy, dz);
+          if (source == Block::Unknown) continue;
+
+          // Intersect acts under empty voxels; the others, under the rest.
+          const auto empty = source == Block::Air;
+          if ((op == CsgOp::Intersect) != empty) continue;
+          const auto block = op == CsgOp::Union ? source : Block::Air;
+
+          symmetry.images({x + dx, y + dy, z + dz}, images);
+          for (const auto& [ix, iy, iz] : images) {
+            if (!(0 <= iy && iy < kBuildHeight)) continue;
+            if (!inSelectionMask(ix, iy, iz)) continue;
+            const auto old_block = getBlock(ix, iy, iz);
+            if (old_block == block || old_block == Block::Unknown) continue;
+            if (registry.getEditPriority(old_block) >
+                registry.getEditPriority(block)) {
+              continue;
+            }
+            setBlock(ix, iy, iz, block);
+            result++;
+          }
+        }
+      }
+    }
+    return result;
+  }
+
+  // Detonates an explosion of the given power centered on the block (x, y, z)
+  // and returns the destroyed blocks: a count, followed by (x, y, z) for each.
+  // Their drops are recorded in edit_drops, as for fillBlocks.
+  //
+  // We cast rays, as Minecraft does, from the center towards each voxel on
+  // the surface of a 16^3 cube. Each ray starts with a random fraction of the
+  // power and marches in 0.3-block steps, losing intensity to each block's
+  // blast resistance and to distance, and destroys every block it reaches
+  // with intensity left. Unloaded chunks stop rays, as do unbreakable blocks,
+  // which have a negative mining hardness. Unlike other bulk edits, we skip
+  // voxels in protected regions instead of failing, and push one EditRejected
+  // event if we skipped any.
+  const int* explode(int x, int y, int z, double power, uint64_t seed) {
+    static std::vector<int> result;
+    result.clear();
+    result.push_back(0);
+    clearEditDrops();
+
+    static std::vector<std::array<int, 3>> destroyed;
+    destroyed.clear();
+
+    constexpr auto kRays = 16;
+    constexpr auto kStep = 0.3;
+    const auto breakable = [&](Block block) {
+      if (block == Block::Bedrock || block == Block::Unknown) return false;
+      return registry.getMining(block).hardness >= 0;
+    };
+
+    auto rng = Random(seed);
+    for (auto i = 0; i < kRays; i++) {
+      for (auto j = 0; j < kRays; j++) {
+        for (auto k = 0; k < kRays; k++) {
+          const auto surface = [](int v) { return v == 0 || v == kRays - 1; };
+          if (!surface(i) && !surface(j) && !surface(k)) continue;
+
+          auto dx = 2.0 * i / (kRays - 1) - 1;
+          auto dy = 2.0 * j / (kRays - 1) - 1;
+          auto dz = 2.0 * k / (kRays - 1) - 1;
+          const auto norm = sqrt(dx * dx + dy * dy + dz * dz);
+          dx *= kStep / norm; dy *= kStep / norm; dz *= kStep / norm;
+
+          auto px = x + 0.5, py = y + 0.5, pz = z + 0.5;
+          auto intensity = power * (0.7 + 0.6 * rng.uniform());
+          while (intensity > 0) {
+            const auto bx = static_cast<int>(floor(px));
+            const auto by = static_cast<int>(floor(py));
+            const auto bz = static_cast<int>(floor(pz));
+            const auto block = getBlock(bx, by, bz);
+            if (block == Block::Unknown) break;
+            if (block != Block::Air) {
+              if (!breakable(block)) break;
+              const auto resistance = registry.getBlastResistance(block);
+              intensity -= (resistance + kStep) * kStep;
+              if (intensity > 0) destroyed.push_back({bx, by, bz});
+            }
+            intensity -= 0.75 * kStep;
+            px += dx; py += dy; pz += dz;
+          }
+        }
+      }
+    }
+
+    std::sort(destroyed.begin(), destroyed.end());
+    destroyed.erase(std::unique(destroyed.begin(), destroyed.end()),
+                    destroyed.end());
+
+    auto rejected = static_cast<const ProtectedRegion*>(nullptr);
+    for (const auto& [bx, by, bz] : destroyed) {
+      const auto block = getBlock(bx, by, bz);
+      if (block == Block::Air || !breakable(block)) continue;
+      const auto p = std::array{bx, by, bz};
+      if (const auto region = getEditBlocker(p, p)) {
+        rejected = region;
+        continue;
+      }
+      setBlock(bx, by, bz, Block::Air);
+      recordEditDrop(block, bx, by, bz);
+      result.insert(result.end(), {bx, by, bz});
+      result[0]++;
+    }
+    if (rejected) {
+      events.push({EventType::EditRejected, x, y, z, rejected->owner, editor});
+    }
+    return result.data();
+  }
+
+  // Grows a tree of the given species with its trunk based at (x, y, z),
+  // which may contain a sapling. Returns false if the trunk doesn't fit.
+  bool growTree(int x, int y, int z, int species, uint64_t seed) {
+    const auto data = registry.getTreeSpecies(species);
+    if (!data) return false;
+
+    static std::vector<StampBlock> blocks;
+    buildTree(*data, seed, blocks);
+    return stamp(x, y, z, blocks, getBlock(x, y, z));
+  }
+
+  // Records a structure's bounds, for queries. Placing its blocks is up to
+  // the caller, e.g. with stamp. See StructureIndex.
+  void addStructure(const Structure& structure) {
+    structures.add(structure);
+  }
+
+  const std::vector<Structure>& getStructuresInBox(
+      const std::array<int, 3>& min, const std::array<int, 3>& max) const {
+    static std::vector<Structure> result;
+    result.clear();
+    structures.query(min, max, result);
+    return result;
+  }
+
+  const Structure* findNearestStructure(int type, int x, int z) const {
+    return structures.nearest(type, x, z);
+  }
+
+  bool bakeAmbientOcclusion(int cx, int cz, int samples) {
+    const auto chunk = chunks.get({cx, cz});
+    if (chunk) chunk->startBake(samples);
+    return chunk != nullptr;
+  }
+
+  // Returns resolution x resolution heights for the chunk at (cx, cz),
+  // followed by as many RGBA top colors, without generating its voxels.
+  const uint32_t* buildImpostor(int cx, int cz, int resolution) {
+    static std::vector<uint32_t> result;
+    const auto [start, end] = worldgen.loadImpostor(cx, cz, resolution);
+    const auto n = static_cast<size_t>(end - start);
+    result.resize(2 * n);
+
+    for (auto i = 0; i < n; i++) {
+      const auto entry = start[i];
+      const auto water = (entry >> 16) != 0;
+      const auto top = water ? entry >> 16 : entry & 0xffff;
+      result[i] = (top >> 8) & 0xff;
+      result[i + n] = getTopColor(static_cast<Block>(top & 0xff), 1.0);
+    }
+    return result.data();
+  }
+
+  // Returns a kChunkWidth x kChunkWidth RGBA tile for the chunk at (cx, cz),
+  // with x as the fast axis, or nullptr if the chunk isn't loaded. Each pixel
+  // is the top color of the highest block with a top face, shaded by height.
+  const uint32_t* renderMinimap(int cx, int cz) {
+    static NonCopyArray<uint32_t, kChunkWidth * kChunkWidth> result;
+    const auto chunk = chunks.get({cx, cz});
+    if (!chunk) return nullptr;
+
+    for (auto z = 0; z < kChunkWidth; z++) {
+      for (auto x = 0; x < kChunkWidth; x++) {
+        const auto y = chunk->getTopFaceHeight(x, z);
+        const auto shade = 0.5 + 0.5 * std::max(y, 0) / kBuildHeight;
+        const auto color = y >= 0
+            ? getTopColor(chunk->getBlock(x, y, z), shade) : 0;
+        result[x + z * kChunkWidth] = color;
+      }
+    }
+    return result.data();
+  }
+
+  Registry& mutableRegistry() { return registry; };
+  EventQueue& mutableEvents() { return events; };
+  Worldgen& mutableWorldgen() { return worldgen; };
+
+ private:
+  void remeshHeightmap() {
+    heightmap_solid.reset();
+    heightmap_water.reset();
+    const auto n = heightmap_size;
+    if (n == 0) return;
+
+    const auto& render = getRenderOrigin();
+    const auto pos = heightmap_pos - Point{render[0], render[2]};
+    mesher.meshHeightmap(heightmap_data.data(), n, pos);
+    const auto mesh = [&](auto& mesh, const auto& quads, int phase) {
+      if (quads.empty()) return;
+      mesh.emplace(quads, phase);
+    };
+    mesh(heightmap_solid, mesher.solid_geo, 0);
+    mesh(heightmap_water, mesher.water_geo, 1);
+  }
+
+  void runScheduledUpdate(int x, int y, int z) {
+    const auto block = getBlock(x, y, z);
+    if (registry.hasTag(block, kBlockTagLeaves)) {
+      if (!hasLogNearby(*this, registry, x, y, z)) {
+        setBlock(x, y, z, Block::Air);
+      }
+    }
+  }
+
+  void schedule(int x, int y, int z, int delay) {
+    assert(delay > 0);
+    scheduled.push({ticks + static_cast<uint64_t>(delay), x, y, z});
+  }
+
+  // Called when we remove the log at (x, y, z). Schedules decay for leaves
+  // that are now too far from any log. See updates.h.
+  void scheduleLeafDecay(int x, int y, int z) {
+    static std::vector<std::array<int, 3>> leaves;
+    leaves.clear();
+    findDecayingLeaves(*this, registry, x, y, z, leaves);
+    for (const auto& [lx, ly, lz] : leaves) {
+      const auto delay = 1 + static_cast<int>(
+          rng.next() % kLeafDecayMaxDelay);
+      schedule(lx, ly, lz, delay);
+    }
+  }
+
+  void randomTick(int x, int y, int z) {
+    const auto block = getBlock(x, y, z);
+    const auto& spread = registry.getSpread(block);
+    if (spread.target != Block::Air) {
+      const auto change =
+          spreadBlock(*this, registry, x, y, z, block, spread, rng);
+      if (change) setBlock(change->x, change->y, change->z, change->block);
+    }
+
+    const auto& sapling = registry.getSapling(block);
+    if (sapling.species >= 0 && rng.uniform() < sapling.chance) {
+      growTree(x, y, z, sapling.species, rng.next());
+    }
+
+    if (registry.hasHook(block, BlockHook::RandomTick)) {
+      callBlockHook(BlockHook::RandomTick, block, x, y, z, 0);
+    }
+  }
+
+  // Runs the checks in edits.h in order, returning the first that fails.
+  // Edits with a bad block, to an unloaded voxel, or that change nothing are
+  // free. Every other edit costs a token, even if a later check rejects it.
+  EditVerdict checkPlayerEdit(int player, int x, int y, int z, int id,
+                              const EditContext& context,
+                              const EditRules& rules, EditLimiter& limiter) {
+    const auto num_blocks = static_cast<int>(registry.getNumBlocks());
+    if (!(0 <= id && id < num_blocks)) return EditVerdict::InvalidBlock;
+    const auto block = static_cast<Block>(id);
+    if (block == Block::Unknown) return EditVerdict::InvalidBlock;
+    if (block != Block::Air && registry.getMining(block).hardness < 0) {
+      return EditVerdict::Unplaceable;
+    }
+
+    if (!(0 <= y && y < kBuildHeight)) return EditVerdict::Unloaded;
+    const auto old_block = getBlock(x, y, z);
+    if (old_block == Block::Unknown) return EditVerdict::Unloaded;
+    if (old_block == block) return EditVerdict::Unchanged;
+    if (!limiter.take(player, context.time, rules)) {
+      return EditVerdict::RateLimited;
+    }
+
+    // Measure reach to the nearest point of the voxel, not to its center.
+    const auto voxel = std::array{x, y, z};
+    auto distance_squared = 0.0;
+    for (auto i = 0; i < 3; i++) {
+      const auto eye = context.eye[i];
+      const auto nearest = std::clamp(eye, 1.0 * voxel[i], voxel[i] + 1.0);
+      distance_squared += (eye - nearest) * (eye - nearest);
+    }
+    if (!(distance_squared <= rules.reach * rules.reach)) {
+      return EditVerdict::OutOfReach;
+    }
+
+    const auto saved = editor;
+    editor = player;
+    const auto blocker = getEditBlocker(voxel, voxel);
+    editor = saved;
+    if (blocker) return EditVerdict::Protected;
+
+    if (old_block == Block::Air) return EditVerdict::Accepted;
+    const auto time =
+        registry.getBreakTime(old_block, context.tool, context.tier);
+    if (time < 0) return EditVerdict::Unbreakable;
+    if (context.mined < time * (1 - rules.mining_slack)) {
+      return EditVerdict::TooFast;
+    }
+    return EditVerdict::Accepted;
+  }
+
+  void clearEditDrops() {
+    edit_drops.clear();
+  }
+
+  void recordEditDrop(Block block, int x, int y, int z) {
+    if (block == Block::Air) return;
+    const auto matches = [&](const EditDrop& drop) {
+      return drop.block == block;
+    };
+    auto it = std::find_if(edit_drops.begin(), edit_drops.end(), matches);
+    if (it == edit_drops.end()) {
+      edit_drops.push_back({block, 0, 0, 0, 0});
+      it = edit_drops.end() - 1;
+    }
+    it->count++;
+    it->x += x;
+    it->y += y;
+    it->z += z;
+  }
+
+  // Loaded voxels are tested with their chunk's collision bits, rather than
+  // with a registry lookup.
+  bool isSolid(int x, int y, int z) {
+    if (0 <= y && y < kWorldHeight) {
+      const auto cx = x >> kChunkBits, xm = x & kChunkMask;
+      const auto cz = z >> kChunkBits, zm = z & kChunkMask;
+      const auto chunk = chunks.get({cx, cz});
+      if (chunk) return chunk->getCollisionField().get(0, xm, y, zm);
+    }
+    return registry.getBlock(getBlock(x, y, z)).solid;
+  }
+
+  static double normalize(std::array<double, 3>& v) {
+    const auto length = sqrt(v[0] * v[0] + v[1] * v[1] + v[2] * v[2]);
+    if (length == 0) return length;
+    for (auto& x : v) x /= length;
+    return length;
+  }
+
+  // Calls fn(x, y, z) for each voxel that a ray from start along the unit
+  // vector dir passes through, in order, until it has traveled `length`.
+  // Stops when fn returns true, and returns the distance traveled.
+  template <typename Fn>
+  static double traceRay(const std::array<double, 3>& start,
+                         const std::array<double, 3>& dir,
+                         double length, Fn fn) {
+    constexpr auto kInfinity = std::numeric_limits<double>::infinity();
+    std::array<int, 3> cell, step;
+    std::array<double, 3> next, delta;
+    for (auto i = 0; i < 3; i++) {
+      cell[i] = static_cast<int>(floor(start[i]));
+      step[i] = dir[i] > 0 ? 1 : -1;
+      delta[i] = dir[i] != 0 ? fabs(1 / dir[i]) : kInfinity;
+      const auto boundary = dir[i] > 0
+          ? cell[i] + 1 - start[i] : start[i] - cell[i];
+      next[i] = dir[i] != 0 ? boundary * delta[i] : kInfinity;
+    }
+
+    auto t = 0.0;
+    while (t < length) {
+      if (fn(cell[0], cell[1], cell[2])) return t;
+      const auto i = next[0] < next[1] ? (next[0] < next[2] ? 0 : 2)
+                                       : (next[1] < next[2] ? 1 : 2);
+      t = next[i];
+      cell[i] += step[i];
+      next[i] += delta[i];
+    }
+    return length;
+  }
+
+  // Like traceRay, but only calls fn(x, y, z) for voxels that are solid, or
+  // that are outside of the loaded chunks. We test one collision bit per
+  // voxel, and skip empty space using the collision field's mips: when the
+  // ray is in an empty 16^3 or 4^3 cube, we step straight to its exit, and
+  // we step through empty 64-voxel runs of a column in one go. Long rays
+  // through open air, like sun shadow rays or visibility checks, are cheap.
+  template <typename Fn>
+  double traceSolidRay(const std::array<double, 3>& start,
+                       const std::array<double, 3>& dir,
+                       double length, Fn fn) {
+    constexpr auto kInfinity = std::numeric_limits<double>::infinity();
+    std::array<int, 3> cell, step;
+    std::array<double, 3> next, delta;
+    for (auto i = 0; i < 3; i++) {
+      cell[i] = static_cast<int>(floor(start[i]));
+      step[i] = dir[i] > 0 ? 1 : -1;
+      delta[i] = dir[i] != 0 ? fabs(1 / dir[i]) : kInfinity;
+      const auto boundary = dir[i] > 0
+          ? cell[i] + 1 - start[i] : start[i] - cell[i];
+      next[i] = dir[i] != 0 ? boundary * delta[i] : kInfinity;
+    }
+
+    const CollisionField* field = nullptr;
+    auto field_point = Point{0, 0};
+    auto has_field_point = false;
+
+    // Steps out of the empty cube, aligned to its size, that contains the
+    // current cell. We replay traceRay's crossings of each axis, so that we
+    // leave the cell and boundaries exactly where it would.
+    auto t = 0.0;
+    const auto skip = [&](int size) {
+      std::array<int, 3> limit;
+      std::array<double, 3> exit;
+      for (auto i = 0; i < 3; i++) {
+        const auto base = cell[i] & ~(size - 1);
+        limit[i] = step[i] > 0 ? base + size - cell[i] : cell[i] - base + 1;
+        exit[i] = next[i];
+        for (auto k = 1; k < limit[i]; k++) exit[i] += delta[i];
+      }
+      const auto a = exit[0] < exit[1] ? (exit[0] < exit[2] ? 0 : 2)
+                                       : (exit[1] < exit[2] ? 1 : 2);
+      for (auto i = 0; i < 3; i++) {
+        const auto max = i == a ? limit[i] : limit[i] - 1;
+        auto n = 0;
+        for (; n < max && (i == a || next[i] < exit[a]); n++) {
+          next[i] += delta[i];
+        }
+        cell[i] += n * step[i];
+      }
+      t = exit[a];
+    };
+
+    while (t < length) {
+      const auto [x, y, z] = cell;
+      const auto point = Point{x >> kChunkBits, z >> kChunkBits};
+      if (!has_field_point || point.x != field_point.x ||
+          point.z != field_point.z) {
+        const auto chunk = chunks.get(point);
+        field = chunk ? &chunk->getCollisionField() : nullptr;
+        field_point = point;
+        has_field_point = true;
+      }
+
+      const auto loaded = field && 0 <= y && y < kWorldHeight;
+      const auto xm = x & kChunkMask, zm = z & kChunkMask;
+      if (loaded && !field->get(kChunkBits, 0, y >> kChunkBits, 0)) {
+        skip(kChunkWidth);
+        continue;
+      } else if (loaded && !field->get(2, xm >> 2, y >> 2, zm >> 2)) {
+        skip(4);
+        continue;
+      }
+
+      const auto run = loaded ? field->getRun(xm, y, zm) : 0;
+      const auto solid = loaded ? ((run >> (y & 63)) & 1) != 0
+                                : y < kWorldHeight;
+      if (solid && fn(x, y, z)) return t;
+
+      const auto i = next[0] < next[1] ? (next[0] < next[2] ? 0 : 2)
+                                       : (next[1] < next[2] ? 1 : 2);
+      auto count = 1;
+      if (i == 1 && loaded && run == 0) {
+        // Take every y-step that stays in this run, up to the next x or z
+        // boundary. Each step enters another empty voxel.
+        const auto offset = y & 63;
+        const auto in_run = step[1] > 0 ? 64 - offset : offset + 1;
+        const auto limit = std::min(next[0], next[2]);
+        const auto before = limit == kInfinity
+            ? in_run : static_cast<int>((limit - next[1]) / delta[1]) + 1;
+        count = std::max(std::min(in_run, before), 1);
+      }
+      t = next[i] + (count - 1) * delta[i];
+      cell[i] += count * step[i];
+      next[i] += count * delta[i];
+    }
+    return length;
+  }
+
+  // Packs the color of the block's top face as RGBA8, with RGB scaled by the
+  // given shade. Returns transparent black for blocks without a top face.
+  uint32_t getTopColor(Block block, double shade) const {
+    const auto material = registry.getBlock(block).faces[2];
+    if (material == kNoMaterial) return 0;
+
+    const auto& data = registry.getMaterial(assertMaterial(material)).color;
+    auto result = static_cast<uint32_t>(0);
+    for (auto i = 0; i < 4; i++) {
+      const auto scale = i < 3 ? shade : 1.0;
+      const auto value = std::clamp(scale * data[i], 0.0, 1.0);
+      result |= static_cast<uint32_t>(std::round(255 * value)) << (8 * i);
+    }
+    return result;
+  }
+
+  friend struct Chunk;
+  friend struct Frontier;
+
+  struct EditDrop { Block block; int count; double x, y, z; };
+
+  // The stores of unloaded chunk state hold immutable, shared entries, so
+  // that snapshots can copy them without copying any chunk data.
+  using SharedBytes = std::shared_ptr<const std::vector<uint8_t>>;
+  using SharedEntities = std::shared_ptr<const std::vector<BlockEntity>>;
+  using SharedState = std::shared_ptr<const Chunk::SavedState>;
+
+  // An encoded ChunkData packet, with its size prefix.
+  struct UnsavedChunk { int cx, cz; SharedBytes packet; };
+
+  struct Snapshot {
+    std::vector<std::pair<Point, SharedState>> chunks;
+    std::vector<UnsavedChunk> unsaved_chunks;
+    HashMap<uint64_t, SharedBytes> chunk_extras;
+    HashMap<uint64_t, SharedEntities> stored_block_entities;
+    HashMap<uint64_t, SharedState> restored_chunks;
+    std::vector<Projectile> projectiles;
+    std::vector<ItemDrop> item_drops;
+    int next_item_drop_id;
+    UpdateQueue scheduled;
+    uint64_t ticks;
+    Random rng;
+  };
+
+  // Far chunks: when a chunk unloads, we keep an octree copy of it while it's
+  // within far_radius chunks of the center. Level-0 LOD meshes sample these
+  // copies, and reloading the chunk converts its copy back to dense voxels,
+  // so far edits survive without a round trip through the host's saves.
+  //
+  // LOD meshes that were built before we stored a chunk aren't rebuilt.
+  //
+  // This key is shared by every store of unloaded chunk state. It uses the
+  // wrapped point, so that in a toroidal world, all copies of a chunk share
+  // one copy of that state.
+  uint64_t farChunkKey(Point point) const {
+    point = wrapPoint(point);
+    return (static_cast<uint64_t>(static_cast<uint32_t>(point.x)) << 32) |
+           static_cast<uint64_t>(static_cast<uint32_t>(point.z));
+  }
+
+  static Point farChunkPoint(uint64_t key) {
+    return {static_cast<int32_t>(key >> 32), static_cast<int32_t>(key)};
+  }
+
+  void storeFarChunk(Point point, const Block* voxels) {
+    if (far_radius == 0) return;
+    far_chunks.insert_or_assign(farChunkKey(point), Octree(voxels));
+  }
+
+  std::optional<Octree> takeFarChunk(Point point) {
+    const auto it = far_chunks.find(farChunkKey(point));
+    if (it == far_chunks.end()) return std::nullopt;
+    auto result = std::optional<Octree>(std::move(it->second));
+    far_chunks.erase(it);
+    return result;
+  }
+
+  const Octree* getFarChunk(Point point) const {
+    const auto it = far_chunks.find(farChunkKey(point));
+    return it == far_chunks.end() ? nullptr : &it->second;
+  }
+
+  void pruneFarChunks(Point center) {
+    // In a toroidal world, we measure to the nearest copy of each chunk.
+    const auto bound = far_radius * far_radius;
+    const auto shortest = [&](int delta) {
+      if (wrap == 0) return delta;
+      delta = ((delta % wrap) + wrap) % wrap;
+      return 2 * delta > wrap ? delta - wrap : delta;
+    };
+    center = wrapPoint(center);
+    for (auto it = far_chunks.begin(); it != far_chunks.end();) {
+      const auto point = farChunkPoint(it->first);
+      const auto delta = Point{shortest(point.x - center.x),
+                               shortest(point.z - center.z)};
+      if (delta.normSquared() > bound) {
+        far_chunks.erase(it++);
+      } else {
+        ++it;
+      }
+    }
+  }
+
+  // Chunk extras: when a chunk unloads, we keep its blob until it reloads,
+  // so the blob stays with the chunk even if the host never saves it.
+  void storeChunkExtra(Point point, std::vector<uint8_t>&& extra) {
+    if (extra.empty()) return;
+    chunk_extras.insert_or_assign(
+        farChunkKey(point),
+        std::make_shared<const std::vector<uint8_t>>(std::move(extra)));
+  }
+
+  std::vector<uint8_t> takeChunkExtra(Point point) {
+    const auto it = chunk_extras.find(farChunkKey(point));
+    if (it == chunk_extras.end()) return {};
+    auto result = *it->second;
+    chunk_extras.erase(it);
+    return result;
+  }
+
+  void storeBlockEntities(Point point, std::vector<BlockEntity>&& entities) {
+    if (entities.empty()) return;
+    stored_block_entities.insert_or_assign(
+        farChunkKey(point),
+        std::make_shared<const std::vector<BlockEntity>>(std::move(entities)));
+  }
+
+  std::vector<BlockEntity> takeBlockEntities(Point point) {
+    const auto it = stored_block_entities.find(farChunkKey(point));
+    if (it == stored_block_entities.end()) return {};
+    auto result = *it->second;
+    stored_block_entities.erase(it);
+    return result;
+  }
+
+  // Restored chunks: when we roll back to a snapshot that has a chunk that's
+  // since unloaded, we keep its whole saved state here until it reloads.
+  // Unlike far chunks, we never prune these, since they may be the only
+  // copy of the chunk's state at that snapshot, metadata included.
+  SharedState takeRestoredChunk(Point point) {
+    const auto it = restored_chunks.find(farChunkKey(point));
+    if (it == restored_chunks.end()) return nullptr;
+    auto result = std::move(it->second);
+    restored_chunks.erase(it);
+    return result;
+  }
+
+  void stashUnsavedChunk(Point point, const Block* voxels,
+                         const std::vector<uint8_t>& extra,
+                         const std::vector<BlockEntity>& entities) {
+    point = wrapPoint(point);
+    const auto packet =
+        encodeChunkData(point.x, point.z, voxels, extra, entities);
+    uint32_t size = 0;
+    memcpy(&size, packet, sizeof(size));
+
+    // A chunk may be unloaded twice before we save it. Only its newest data
+    // matters, so we drop the older stash.
+    unsaved_chunks.erase(std::remove_if(
+        unsaved_chunks.begin(), unsaved_chunks.end(), [&](const auto& x) {
+          return x.cx == point.x && x.cz == point.z;
+        }), unsaved_chunks.end());
+    unsaved_chunks.push_back({point.x, point.z,
+                              std::make_shared<const std::vector<uint8_t>>(
+                                  packet, packet + sizeof(size) + size)});
+  }
+
+  Circle<Chunk> chunks;
+  Frontier frontier;
+  Registry& registry;
+  Mesher mesher;
+  Worldgen worldgen;
+  std::vector<Mesher::HeightmapEntry> heightmap_data;
+  Point heightmap_pos;
+  int heightmap_size = 0;
+  Point meshed_origin = {0, 0};
+  std::optional<VoxelMesh> heightmap_solid;
+  std::optional<VoxelMesh> heightmap_water;
+  HashMap<uint64_t, Octree> far_chunks;
+  HashMap<uint64_t, SharedBytes> chunk_extras;
+  HashMap<uint64_t, SharedEntities> stored_block_entities;
+  HashMap<uint64_t, SharedState> restored_chunks;
+  HashMap<int, DynamicLight> dynamic_lights;
+  std::vector<Projectile> projectiles;
+  std::vector<ItemDrop> item_drops;
+  std::vector<std::array<double, 3>> item_magnets;
+  ItemDropRules item_drop_rules;
+  int next_item_drop_id = 1;
+  StructureIndex structures;
+  std::vector<ProtectedRegion> protected_regions;
+  Symmetry symmetry;
+  std::optional<Selection> selection;
+  bool selection_mask = false;
+  TriggerTracker triggers;
+  int editor = 0;
+  int far_radius = 0;
+  int sky_darkness = 0;
+  int wrap = 0;
+
+  std::vector<EditDrop> edit_drops;
+  EventQueue events;
+  std::vector<UnsavedChunk> unsaved_chunks;
+  UpdateQueue scheduled;
+  uint64_t ticks = 0;
+  Random rng;
+  HashMap<int, Snapshot> snapshots;
+  int next_snapshot_id = 1;
+
+  DISALLOW_COPY_AND_ASSIGN(World);
+};
+
+//////////////////////////////////////////////////////////////////////////////
+
+Mesher& Chunk::getMesher() const {
+  return world->mesher;
+}
+
+Chunk* Chunk::getNeighbor(Point delta) const {
+  return world->chunks.get(point + delta);
+}
+
+const Registry& Chunk::getRegistry() const {
+  return world->registry;
+}
+
+Worldgen& Chunk::getWorldgen() const {
+  return world->worldgen;
+}
+
+void Chunk::markFrontierDirty() const {
+  world->frontier.markDirty(0);
+}
+
+void Chunk::stashUnsaved() const {
+  static std::vector<BlockEntity> entities;
+  getBlockEntities(entities);
+  world->stashUnsavedChunk(point, voxels.data.data(), extra, entities);
+}
+
+void Chunk::storeFarChunk() const {
+  world->storeFarChunk(point, voxels.data.data());
+}
+
+void Chunk::loadDynamicLights() {
+  for (const auto& [id, light] : world->dynamic_lights) {
+    const auto cx = light.x >> kChunkBits, cz = light.z >> kChunkBits;
+    if (Point{cx, cz} != point) continue;
+    if (!(0 <= light.y && light.y < kWorldHeight)) continue;
+
+    const auto x = light.x & kChunkMask, z = light.z & kChunkMask;
+    const auto index = voxels.index(x, light.y, z);
+    const auto it = dynamic_lights.find(index);
+    if (it != dynamic_lights.end() && it->second >= light.level) continue;
+    setDynamicLight(x, light.y, z, light.level);
+  }
+}
+
+void Chunk::storeExtra() {
+  world->storeChunkExtra(point, std::move(extra));
+  extra.clear();
+
+  static std::vector<BlockEntity> entities;
+  getBlockEntities(entities);
+  world->storeBlockEntities(point, std::move(entities));
+  entities.clear();
+  block_entities.clear();
+}
+
+void Chunk::loadExtra() {
+  extra = world->takeChunkExtra(point);
+  block_entities.clear();
+  for (auto& entity : world->takeBlockEntities(point)) {
+    const auto index = entity.index;
+    block_entities.emplace(index, std::move(entity));
+  }
+}
+
+void Chunk::loadFarChunk() {
+  // The octree may have edits that worldgen doesn't, so we overwrite the
+  // generated voxels wherever they differ.
+  const auto octree = world->takeFarChunk(point);
+  if (!octree) return;
+
+  static std::vector<Block> dense(voxels.data.size());
+  octree->toDense(dense.data());
+  applyVoxels(dense.data());
+}
+
+bool Chunk::loadRestoredState() {
+  const auto state = world->takeRestoredChunk(point);
+  if (!state) return false;
+
+  world->takeFarChunk(point);
+  applyVoxels(state->voxels.data());
+  std::copy(state->metadata.begin(), state->metadata.end(),
+            metadata.data.begin());
+  extra = state->extra;
+  block_entities.clear();
+  for (const auto& entity : state->entities) {
+    block_entities.emplace(entity.index, entity);
+  }
+  return true;
+}
+
+void Chunk::applyVoxels(const Block* dense) {
+  for (auto z = 0; z < kChunkWidth; z++) {
+    for (auto x = 0; x < kChunkWidth; x++) {
+      for (auto y = 0; y < kBuildHeight; y++) {
+        const auto index = voxels.index(x, y, z);
+        const auto old_block = voxels.data[index];
+        const auto new_block = dense[index];
+        if (old_block == new_block) continue;
+        setColumn(x, z, y, 1, new_block);
+        updateInstance(index, old_block, new_block);
+        equilevels[y] = 0;
+      }
+    }
+  }
+}
+
+void FrontierChunk::destroy() {
+  if (hasMesh()) frontier->markDirty(level + 1);
+  mesh->disable(index);
+}
+
+Mesher& Frontier::getMesher() const {
+  return world->mesher;
+}
+
+Chunk* Frontier::getWorldChunk(Point p) const {
+  return world->chunks.get(p);
+}
+
+const Octree* Frontier::getFarChunk(Point p) const {
+  return world->getFarChunk(p);
+}
+
+const Registry& Frontier::getRegistry() const {
+  return world->registry;
+}
+
+Worldgen& Frontier::getWorldgen() const {
+  return world->worldgen;
+}
+
+//////////////////////////////////////////////////////////////////////////////
+
+} // namespace voxels
+
+//////////////////////////////////////////////////////////////////////////////
+
+// Each dimension is a World with its own chunks, lighting, simulation, and
+// worldgen, and they all share the registry. Exports act on the active
+// dimension, `world`. Dimension 0 is the one that initializeWorld creates.
+voxels::HashMap<int, std::unique_ptr<voxels::World>> dimensions;
+voxels::World* world = nullptr;
+int active_dimension = 0;
+voxels::PortalLinks portal_links;
+
+struct {
+  double chunk_radius;
+  int frontier_radius;
+  int frontier_levels;
+} world_config;
+
+voxels::ReplayLog replay_log;
+voxels::SnapshotBuffer snapshots;
+
+// Concatenates the buffers that an export takes into one replay payload.
+// Only call this while recording, since buffers such as meshes can be big.
+const std::vector<uint8_t>& packReplayPayload(
+    std::initializer_list<std::pair<const void*, size_t>> parts) {
+  static std::vector<uint8_t> result;
+  result.clear();
+  for (const auto& [data, size] : parts) {
+    const auto start = static_cast<const uint8_t*>(data);
+    result.insert(result.end(), start, start + size);
+  }
+  return result;
+}
+
+// The fixed-timestep loop behind advanceWorld. We keep time in integer
+// microseconds, so that the number of ticks run for a sequence of frames
+// doesn't depend on floating-point rounding.
+struct {
+  int64_t accumulator = 0;
+  int64_t tick_length = 50000;
+  int max_ticks = 8;
+  bool raining = false;
+} tick_loop;
+
+// Smoothing state for the camera queries: the size of the push out of walls
+// on each axis, the direction of the last push, and the clipped distance.
+struct {
+  std::array<voxels::CameraSmoother, 3> push;
+  std::array<double, 3> sign = {1, 1, 1};
+  voxels::CameraSmoother clip;
+} camera_state;
+
+// Item rules don't depend on the world, so all dimensions share them.
+voxels::ItemRegistry items;
+voxels::LootTables loot_tables;
+voxels::RecipeBook recipes;
+
+// Inventories by game-defined id, such as a player's or a chest's.
+voxels::HashMap<int, voxels::Inventory> inventories;
+
+static voxels::Inventory* getInventoryById(int id) {
+  const auto it = inventories.find(id);
+  return it != inventories.end() ? &it->second : nullptr;
+}
+
+// Clipboards by tool-defined id, for CSG edits. See clipboard.h.
+voxels::HashMap<int, voxels::Clipboard> clipboards;
+
+// Rules and rate limits for players' edits, shared by all dimensions.
+voxels::EditRules edit_rules;
+voxels::EditLimiter edit_limiter;
+
+// The host checks these at startup, so that it fails fast instead of
+// corrupting memory if it's paired with the wrong build of this module.
+// Bump kApiVersion when an export's signature or shared data layout changes.
+constexpr int kApiVersion = 1;
+
+constexpr int kFeatureSimd       = 1 << 0;
+constexpr int kFeatureThreads    = 1 << 1;
+constexpr int kFeatureLighting   = 1 << 2;
+constexpr int kFeatureNetworking = 1 << 3;
+constexpr int kFeatureFilesystem = 1 << 4;
+constexpr int kFeatureFixedPoint = 1 << 5;
+
+WASM_EXPORT(apiVersion)
+int apiVersion() {
+  return kApiVersion;
+}
+
+WASM_EXPORT(featureFlags)
+int featureFlags() {
+  auto result = kFeatureLighting | kFeatureNetworking;
+#ifdef __wasm_simd128__
+  result |= kFeatureSimd;
+#endif
+#ifdef __EMSCRIPTEN_PTHREADS__
+  result |= kFeatureThreads;
+#endif
+#ifndef __EMSCRIPTEN__
+  result |= kFeatureFilesystem;
+#endif
+#ifdef VOXELS_FIXED_POINT
+  result |= kFeatureFixedPoint;
+#endif
+  return result;
+}
+
+WASM_EXPORT(layoutDescriptor)
+const int* layoutDescriptor() {
+  // Returns a count, followed by (id, size, n, offset_1, ..., offset_n) for
+  // each struct that we share with the host.
+  using voxels::EntityTransform;
+  using voxels::Event;
+  using Quad = voxels::VoxelMesh::Quad;
+
+  static std::vector<int> result;
+  if (!result.empty()) return result.data();
+
+  const auto add = [&](int id, size_t size, std::vector<size_t> offsets) {
+    result.insert(result.end(), {id, static_cast<int>(size),
+                                 static_cast<int>(offsets.size())});
+    for (const auto x : offsets) result.push_back(static_cast<int>(x));
+    result[0]++;
+  };
+  result.push_back(0);
+  add(1, sizeof(EntityTransform), {
+    offsetof(EntityTransform, id), offsetof(EntityTransform, x),
+    offsetof(EntityTransform, y), offsetof(EntityTransform, z),
+    offsetof(EntityTransform, heading),
+  });
+  add(2, sizeof(Event), {
+    offsetof(Event, type), offsetof(Event, x), offsetof(Event, y),
+    offsetof(Event, z), offsetof(Event, a), offsetof(Event, b),
+  });
+  add(3, sizeof(Quad), {});
+  return result.data();
+}
+
+WASM_EXPORT(initializeWorld)
+void initializeWorld(int chunkRadius, int frontierRadius, int frontierLevels) {
+  assert(!world);
+  const double adjusted = static_cast<double>(chunkRadius) + 0.5;
+  world_config = {adjusted, frontierRadius, frontierLevels};
+  auto& dimension = dimensions[0];
+  dimension = std::make_unique<voxels::World>(
+      adjusted, frontierRadius, frontierLevels);
+  world = dimension.get();
+  voxels::checkMemoryGrowth();
+}
+
+// Makes the given dimension active, creating it with the same radii as
+// dimension 0 if it doesn't exist yet. Later exports, including the tick,
+// only act on the active dimension; the others keep their chunks but are
+// frozen, and their meshes are hidden. A new dimension's worldgen seed is
+// its id, so each one generates different terrain; see setWorldSeed.
+WASM_EXPORT(setActiveDimension)
+void setActiveDimension(int id) {
+  assert(world);
+  replay_log.record(voxels::ReplayOp::SetDimension, {id});
+  if (id == active_dimension) return;
+
+  auto& dimension = dimensions[id];
+  voxels::setRenderLayer(id);
+  if (!dimension) {
+    const auto& [radius, frontier_radius, frontier_levels] = world_config;
+    dimension = std::make_unique<voxels::World>(
+        radius, frontier_radius, frontier_levels, static_cast<uint32_t>(id));
+  }
+  world = dimension.get();
+  active_dimension = id;
+  const auto& origin = voxels::getRenderOrigin();
+  world->rebaseOrigin(origin[0], origin[2]);
+  voxels::checkMemoryGrowth();
+}
+
+WASM_EXPORT(getActiveDimension)
+int getActiveDimension() {
+  return active_dimension;
+}
+
+// Drops an inactive dimension's chunks, meshes, and portal links. Unsaved
+// edits in it are lost, so save its chunks first. Returns false, and does
+// nothing, for dimension 0, the active dimension, or an unknown id.
+WASM_EXPORT(removeDimension)
+bool removeDimension(int id) {
+  assert(world);
+  if (id == 0 || id == active_dimension) return false;
+  const auto it = dimensions.find(id);
+  if (it == dimensions.end()) return false;
+  replay_log.record(voxels::ReplayOp::RemoveDimension, {id});
+  dimensions.erase(it);
+  portal_links.unlinkDimension(id);
+  return true;
+}
+
+// Sets the active dimension's worldgen seed. Call it right after creating
+// the dimension, before loading any chunks in it: we don't regenerate chunks
+// that are already loaded. Dimension 0's seed starts at 0.
+WASM_EXPORT(setWorldSeed)
+void setWorldSeed(uint32_t seed) {
+  assert(world);
+  replay_log.record(voxels::ReplayOp::SetWorldSeed,
+                    {static_cast<int32_t>(seed)});
+  world->mutableWorldgen().setSeed(seed);
+}
+
+WASM_EXPORT(linkPortal)
+void linkPortal(int d0, int x0, int y0, int z0,
+                int d1, int x1, int y1, int z1) {
+  portal_links.link({d0, x0, y0, z0}, {d1, x1, y1, z1});
+}
+
+WASM_EXPORT(unlinkPortal)
+void unlinkPortal(int d, int x, int y, int z) {
+  portal_links.unlink({d, x, y, z});
+}
+
+// Returns the (dimension, x, y, z) end that the given portal end links to,
+// or nullptr if it isn't linked.
+WASM_EXPORT(getPortalLink)
+const int* getPortalLink(int d, int x, int y, int z) {
+  const auto end = portal_links.find({d, x, y, z});
+  if (!end) return nullptr;
+  static std::array<int, 4> result;
+  result = {end->dimension, end->x, end->y, end->z};
+  return result.data();
+}
+
+WASM_EXPORT(recenterWorld)
+void recenterWorld(int x, int z) {
+  assert(world);
+  replay_log.record(voxels::ReplayOp::Recenter, {x, z});
+  world->recenter({x, z});
+  voxels::checkMemoryGrowth();
+}
+
+WASM_EXPORT(remeshWorld)
+void remeshWorld() {
+  assert(world);
+  replay_log.record(voxels::ReplayOp::Remesh, {});
+  world->remesh();
+  voxels::frameArena().reset();
+  voxels::checkMemoryGrowth();
+}
+
+// Meshes a batch of chunks in one call, e.g. after a teleport. points holds
+// `count` (cx, cz) pairs. See World::meshMany for the result's layout.
+WASM_EXPORT(meshMany)
+const int* meshMany(const int* points, int count) {
+  assert(world);
+  const auto result = world->meshMany(points, count);
+  voxels::frameArena().reset();
+  voxels::checkMemoryGrowth();
+  return result;
+}
+
+// Advances chunk (cx, cz) toward the target ChunkStage, doing at most
+// `budget` loads, relights, or remeshes. Returns the chunk's stage.
+WASM_EXPORT(advanceChunk)
+int advanceChunk(int cx, int cz, int target, int budget) {
+  assert(world);
+  using voxels::ChunkStage;
+  assert(0 <= target && target < static_cast<int>(ChunkStage::Count));
+  assert(budget >= 0);
+  const auto result =
+      world->advanceChunk(cx, cz, static_cast<ChunkStage>(target), budget);
+  voxels::frameArena().reset();
+  voxels::checkMemoryGrowth();
+  return static_cast<int>(result);
+}
+
+WASM_EXPORT(tickWorld)
+void tickWorld() {
+  assert(world);
+  replay_log.record(voxels::ReplayOp::Tick, {});
+  world->tick();
+  voxels::checkMemoryGrowth();
+}
+
+// Sets the simulation rate for advanceWorld, and the most ticks that one
+// call may run. If a frame takes longer than that, we drop the extra time,
+// and the simulation runs slow instead of falling further behind.
+//
+// Ticks last from 1us to 1000s; we clamp rates outside that range. Returns
+// false, leaving the rate unchanged, if either argument isn't positive.
+WASM_EXPORT(setTickRate)
+bool setTickRate(double ticksPerSecond, int maxTicks) {
+  if (!(ticksPerSecond > 0) || maxTicks <= 0) return false;
+  const auto tick_length = static_cast<int32_t>(
+      std::clamp(std::round(1e6 / ticksPerSecond), 1.0, 1e9));
+  replay_log.record(voxels::ReplayOp::SetTickRate, {tick_length, maxTicks});
+  tick_loop.tick_length = tick_length;
+  tick_loop.max_ticks = maxTicks;
+  return true;
+}
+
+WASM_EXPORT(setRaining)
+void setRaining(bool raining) {
+  replay_log.record(voxels::ReplayOp::SetRaining, {raining});
+  tick_loop.raining = raining;
+}
+
+WASM_EXPORT(advanceWorld)
+const double* advanceWorld(double dtMs) {
+  // Runs as many fixed ticks as fit in the elapsed time. Each tick runs, in
+  // order: scheduled updates and random ticks, fire in all loaded chunks,
+  // projectiles, and item drops. Returns the number of ticks run, followed
+  // by the interpolation alpha in [0, 1): how far we are into the next tick.
+  static std::array<double, 2> result;
+
+  assert(world);
+  const auto dt = static_cast<int32_t>(
+      std::clamp(std::round(1000 * dtMs), 0.0, 1e9));
+  replay_log.record(voxels::ReplayOp::Advance, {dt});
+
+  auto& loop = tick_loop;
+  const auto limit = loop.max_ticks * loop.tick_length;
+  loop.accumulator = std::min(loop.accumulator + dt, limit);
+
+  auto ticks = 0;
+  const auto seconds = static_cast<double>(loop.tick_length) / 1e6;
+  while (loop.accumulator >= loop.tick_length) {
+    loop.accumulator -= loop.tick_length;
+    world->tick();
+    world->tickFires(loop.raining);
+    world->stepProjectiles(seconds);
+    world->stepItemDrops(seconds, items);
+    ticks++;
+  }
+  voxels::checkMemoryGrowth();
+
+  const auto alpha = static_cast<double>(loop.accumulator) /
+                     static_cast<double>(loop.tick_length);
+  result = {static_cast<double>(ticks), alpha};
+  return result.data();
+}
+
+// Returns projectile states in the format of stepProjectiles, for hosts that
+// step them with advanceWorld.
+WASM_EXPORT(getProjectiles)
+const float* getProjectiles() {
+  assert(world);
+  const auto result = world->getProjectiles();
+  voxels::checkMemoryGrowth();
+  return result;
+}
+
+WASM_EXPORT(getItemDrops)
+const float* getItemDrops() {
+  assert(world);
+  const auto result = world->getItemDrops();
+  voxels::checkMemoryGrowth();
+  return result;
+}
+
+// Snapshots of the active dimension, for undo or client-side prediction.
+// Ids are unique within a dimension. See World::snapshot.
+WASM_EXPORT(snapshotWorld)
+int snapshotWorld() {
+  assert(world);
+  replay_log.record(voxels::ReplayOp::Snapshot, {});
+  const auto result = world->snapshot();
+  voxels::checkMemoryGrowth();
+  return result;
+}
+
+WASM_EXPORT(rollbackWorld)
+bool rollbackWorld(int id) {
+  assert(world);
+  replay_log.record(voxels::ReplayOp::Rollback, {id});
+  const auto result = world->rollback(id);
+  voxels::checkMemoryGrowth();
+  return result;
+}
+
+WASM_EXPORT(releaseWorldSnapshot)
+bool releaseWorldSnapshot(int id) {
+  assert(world);
+  return world->releaseSnapshot(id);
+}
+
+// Serializes a snapshot, with a uint32_t size prefix. Returns nullptr if
+// there's no snapshot with the given id. See World::encodeSnapshot.
+WASM_EXPORT(encodeWorldSnapshot)
+const uint8_t* encodeWorldSnapshot(int id) {
+  assert(world);
+  const auto result = world->encodeSnapshot(id);
+  voxels::checkMemoryGrowth();
+  return result;
+}
+
+// Adds a serialized snapshot to the active dimension, and returns its id,
+// or 0 if the data is malformed.
+WASM_EXPORT(decodeWorldSnapshot)
+int decodeWorldSnapshot(const uint8_t* data, int size) {
+  assert(world);
+  replay_log.record(voxels::ReplayOp::DecodeSnapshot, {}, data, size);
+  const auto result = world->decodeSnapshot(data, size);
+  voxels::checkMemoryGrowth();
+  return result;
+}
+
+WASM_EXPORT(memoryGeneration)
+int memoryGeneration() {
+  return voxels::checkMemoryGrowth();
+}
+
+WASM_EXPORT(arenaReset)
+int arenaReset() {
+  auto& arena = voxels::frameArena();
+  arena.reset();
+  return voxels::safe_cast<int>(arena.getCapacity());
+}
+
+WASM_EXPORT(pushEntitySnapshot)
+bool pushEntitySnapshot(double time, const voxels::EntityTransform* data,
+                        int count) {
+  return snapshots.push(time, data, count);
+}
+
+WASM_EXPORT(sampleEntities)
+const float* sampleEntities(double time) {
+  return snapshots.sample(time, voxels::getRenderOrigin());
+}
+
+WASM_EXPORT(clearEntitySnapshots)
+void clearEntitySnapshots() {
+  snapshots.clear();
+}
+
+// Block entities are serialized for the host as an int32 voxel index within
+// the chunk, an int32 type, a uint32 size, and data padded to 4 bytes.
+static void appendWord(std::vector<uint8_t>& result, uint32_t word) {
+  const auto bytes = reinterpret_cast<const uint8_t*>(&word);
+  result.insert(result.end(), bytes, bytes + sizeof(word));
+}
+
+static void appendBlockEntity(std::vector<uint8_t>& result,
+                              const voxels::BlockEntity& entity) {
+  appendWord(result, static_cast<uint32_t>(entity.index));
+  appendWord(result, static_cast<uint32_t>(entity.type));
+  appendWord(result, static_cast<uint32_t>(entity.data.size()));
+  result.insert(result.end(), entity.data.begin(), entity.data.end());
+  result.resize((result.size() + 3) & ~size_t{3}, 0);
+}
+
+static void appendBlockEntities(
+    std::vector<uint8_t>& result,
+    const std::vector<voxels::BlockEntity>& entities) {
+  appendWord(result, static_cast<uint32_t>(entities.size()));
+  for (const auto& entity : entities) appendBlockEntity(result, entity);
+}
+
+WASM_EXPORT(encodeChunkData)
+const uint8_t* encodeChunkData(int cx, int cz) {
+  assert(world);
+  const auto voxels = world->getChunkVoxels(cx, cz);
+  if (!voxels) return nullptr;
+  const auto& extra = *world->getChunkExtra(cx, cz);
+  const auto& entities = *world->getBlockEntities(cx, cz);
+  return voxels::encodeChunkData(cx, cz, voxels, extra, entities);
+}
+
+WASM_EXPORT(decodeChunkData)
+const uint8_t* decodeChunkData(const uint8_t* data, int size) {
+  // Returns cx and cz as int32s, followed by the chunk's voxels, by the
+  // chunk's extra data with a uint32_t size prefix, and then (padded to 4
+  // bytes) by its block entities with a uint32_t count prefix.
+  static std::vector<uint8_t> result;
+  static std::vector<uint8_t> extra;
+  static std::vector<voxels::BlockEntity> entities;
+  constexpr auto header = 2 * sizeof(int);
+  result.resize(header + voxels::kChunkVoxels);
+  const auto blocks = reinterpret_cast<voxels::Block*>(&result[header]);
+  int cx = 0, cz = 0;
+  if (!voxels::decodeChunkData(data, size, cx, cz, blocks, extra, entities)) {
+    return nullptr;
+  }
+  memcpy(&result[0], &cx, sizeof(cx));
+  memcpy(&result[4], &cz, sizeof(cz));
+
+  const auto extra_size = static_cast<uint32_t>(extra.size());
+  const auto bytes = reinterpret_cast<const uint8_t*>(&extra_size);
+  result.insert(result.end(), bytes, bytes + sizeof(extra_size));
+  result.insert(result.end(), extra.begin(), extra.end());
+  result.resize((result.size() + 3) & ~size_t{3}, 0);
+  appendBlockEntities(result, entities);
+  voxels::checkMemoryGrowth();
+  return result.data();
+}
+
+// Returns the loaded chunk (cx, cz)'s extra data with a uint32_t size
+// prefix, or nullptr if it isn't loaded.
+WASM_EXPORT(getChunkExtra)
+const uint8_t* getChunkExtra(int cx, int cz) {
+  assert(world);
+  const auto extra = world->getChunkExtra(cx, cz);
+  if (!extra) return nullptr;
+  static std::vector<uint8_t> result;
+  const auto size = static_cast<uint32_t>(extra->size());
+  result.resize(sizeof(size));
+  memcpy(result.data(), &size, sizeof(size));
+  result.insert(result.end(), extra->begin(), extra->end());
+  voxels::checkMemoryGrowth();
+  return result.data();
+}
+
+WASM_EXPORT(setChunkExtra)
+bool setChunkExtra(int cx, int cz, const uint8_t* data, int size) {
+  assert(world);
+  replay_log.record(voxels::ReplayOp::SetChunkExtra, {cx, cz}, data, size);
+  return world->setChunkExtra(cx, cz, data, size);
+}
+
+// Game ticks and block hooks look up a block's state with getBlockEntity.
+// The engine only moves entities with pushed blocks and drops them when
+// their blocks change; their data is opaque to it.
+WASM_EXPORT(createBlockEntity)
+bool createBlockEntity(int x, int y, int z, int type,
+                       const uint8_t* data, int size) {
+  assert(world);
+  replay_log.record(voxels::ReplayOp::CreateBlockEntity, {x, y, z, type},
+                    data, size);
+  const auto result = world->createBlockEntity(x, y, z, type, data, size);
+  voxels::checkMemoryGrowth();
+  return result;
+}
+
+// Returns the block entity at (x, y, z) in the format of appendBlockEntity,
+// or nullptr if there isn't one.
+WASM_EXPORT(getBlockEntity)
+const uint8_t* getBlockEntity(int x, int y, int z) {
+  assert(world);
+  const auto entity = world->getBlockEntity(x, y, z);
+  if (!entity) return nullptr;
+  static std::vector<uint8_t> result;
+  result.clear();
+  appendBlockEntity(result, *entity);
+  voxels::checkMemoryGrowth();
+  return result.data();
+}
+
+WASM_EXPORT(removeBlockEntity)
+bool removeBlockEntity(int x, int y, int z) {
+  assert(world);
+  replay_log.record(voxels::ReplayOp::RemoveBlockEntity, {x, y, z});
+  return world->removeBlockEntity(x, y, z);
+}
+
+// Returns the loaded chunk (cx, cz)'s block entities with a uint32_t count
+// prefix, or nullptr if it isn't loaded.
+WASM_EXPORT(getChunkBlockEntities)
+const uint8_t* getChunkBlockEntities(int cx, int cz) {
+  assert(world);
+  const auto entities = world->getBlockEntities(cx, cz);
+  if (!entities) return nullptr;
+  static std::vector<uint8_t> result;
+  result.clear();
+  appendBlockEntities(result, *entities);
+  voxels::checkMemoryGrowth();
+  return result.data();
+}
+
+WASM_EXPORT(encodeChunkDelta)
+const uint8_t* encodeChunkDelta(int cx, int cz, const int* edits, int count) {
+  static std::vector<voxels::ChunkEdit> input;
+  input.clear();
+  for (auto i = 0; i < count; i++) {
+    const auto block = static_cast<voxels::Block>(edits[2 * i + 1]);
+    input.push_back({edits[2 * i], block});
+  }
+  return voxels::encodeChunkDelta(cx, cz, input);
+}
+
+WASM_EXPORT(decodeChunkDelta)
+const int* decodeChunkDelta(const uint8_t* data, int size) {
+  // Returns cx, cz, and a count, followed by (index, block) for each edit.
+  static std::vector<voxels::ChunkEdit> edits;
+  static std::vector<int> result;
+  int cx = 0, cz = 0;
+  if (!voxels::decodeChunkDelta(data, size, cx, cz, edits)) return nullptr;
+  result.assign({cx, cz, static_cast<int>(edits.size())});
+  for (const auto& edit : edits) {
+    result.insert(result.end(), {edit.index, static_cast<int>(edit.block)});
+  }
+  return result.data();
+}
+
+WASM_EXPORT(encodeBlockEdit)
+const uint8_t* encodeBlockEdit(int x, int y, int z, int block) {
+  using voxels::safe_cast;
+  return voxels::encodeBlockEdit({x, y, z, safe_cast<voxels::Block>(block)});
+}
+
+WASM_EXPORT(decodeBlockEdit)
+const int* decodeBlockEdit(const uint8_t* data, int size) {
+  static std::array<int, 4> result;
+  voxels::BlockEdit edit;
+  if (!voxels::decodeBlockEdit(data, size, edit)) return nullptr;
+  result = {edit.x, edit.y, edit.z, static_cast<int>(edit.block)};
+  return result.data();
+}
+
+WASM_EXPORT(encodeEntitySnapshot)
+const uint8_t* encodeEntitySnapshot(
+    double time, const voxels::EntityTransform* data, int count) {
+  return voxels::encodeEntitySnapshot(time, data, count);
+}
+
+WASM_EXPORT(decodeEntitySnapshot)
+bool decodeEntitySnapshot(const uint8_t* data, int size) {
+  // Pushes the decoded snapshot into the interpolation buffer. Returns false
+  // if the packet is malformed or older than the latest snapshot.
+  static std::vector<voxels::EntityTransform> transforms;
+  auto time = 0.0;
+  if (!voxels::decodeEntitySnapshot(data, size, time, transforms)) {
+    return false;
+  }
+  const auto count = static_cast<int>(transforms.size());
+  return snapshots.push(time, transforms.data(), count);
+}
+
+WASM_EXPORT(encodeRegistrySync)
+const uint8_t* encodeRegistrySync() {
+  assert(world);
+  return voxels::encodeRegistrySync(world->mutableRegistry());
+}
+
+WASM_EXPORT(decodeRegistrySync)
+const double* decodeRegistrySync(const uint8_t* data, int size) {
+  // Returns a count, followed by the arguments to registerBlock, without
+  // the block id, for each block: (mesh, opaque, solid, light, faces[6]).
+  // Then comes a count, followed by the arguments to registerMaterial for
+  // each material: (liquid, alphaTest, texture, r, g, b, a, uvTransform,
+  // fullBright). Values are doubles so that colors survive the trip.
+  static std::vector<voxels::BlockData> blocks;
+  static std::vector<voxels::MaterialData> materials;
+  static std::vector<double> result;
+  if (!voxels::decodeRegistrySync(data, size, blocks, materials)) {
+    return nullptr;
+  }
+  const auto to_double = [](auto x) { return static_cast<double>(x); };
+  result.assign({to_double(blocks.size())});
+  for (const auto& block : blocks) {
+    result.insert(result.end(), {
+      to_double(block.mesh), to_double(block.opaque),
+      to_double(block.solid), to_double(block.light),
+    });
+    for (const auto& face : block.faces) result.push_back(face.id);
+  }
+  result.push_back(to_double(materials.size()));
+  for (const auto& material : materials) {
+    const auto& color = material.color;
+    result.insert(result.end(), {
+      to_double(material.liquid), to_double(material.alphaTest),
+      to_double(material.texture), color[0], color[1], color[2], color[3],
+      to_double(material.uv_transform), to_double(material.fullBright),
+    });
+  }
+  voxels::checkMemoryGrowth();
+  return result.data();
+}
+
+WASM_EXPORT(drainEvents)
+const int32_t* drainEvents() {
+  assert(world);
+  return world->mutableEvents().drain();
+}
+
+WASM_EXPORT(pushEvent)
+void pushEvent(int type, int x, int y, int z, int a, int b) {
+  using voxels::safe_cast;
+
+  assert(world);
+  const auto event_type = safe_cast<voxels::EventType>(type);
+  world->mutableEvents().push({event_type, x, y, z, a, b});
+}
+
+WASM_EXPORT(chunksNeedingSave)
+const int* chunksNeedingSave() {
+  assert(world);
+  return world->chunksNeedingSave();
+}
+
+WASM_EXPORT(saveChunks)
+const uint8_t* saveChunks(int budget) {
+  assert(world);
+  return world->saveChunks(budget);
+}
+
+WASM_EXPORT(getBaseHeight)
+int getBaseHeight(int x, int z) {
+  assert(world);
+  return world->mutableWorldgen().getBaseHeight(x, z);
+}
+
+WASM_EXPORT(getBlock)
+int getBlock(int x, int y, int z) {
+  assert(world);
+  return static_cast<int>(world->getBlock(x, y, z));
+}
+
+WASM_EXPORT(getShadowHeight)
+int getShadowHeight(int x, int y, int z) {
+  assert(world);
+  return world->getShadowHeight(x, y, z);
+}
+
+WASM_EXPORT(getShadowHeights)
+const int* getShadowHeights(int x, int z, int nx, int nz, int y) {
+  assert(world);
+  assert(nx >= 0 && nz >= 0);
+  const auto result = world->getShadowHeights(x, z, nx, nz, y);
+  voxels::checkMemoryGrowth();
+  return result;
+}
+
+WASM_EXPORT(getLightLevel)
+int getLightLevel(int x, int y, int z) {
+  assert(world);
+  return world->getLightLevel(x, y, z);
+}
+
+WASM_EXPORT(getEffectiveLightLevel)
+int getEffectiveLightLevel(int x, int y, int z) {
+  assert(world);
+  return world->getEffectiveLightLevel(x, y, z);
+}
+
+WASM_EXPORT(setSkyDarkness)
+void setSkyDarkness(int level) {
+  assert(world);
+  replay_log.record(voxels::ReplayOp::SetSkyDarkness, {level});
+  world->setSkyDarkness(level);
+}
+
+WASM_EXPORT(setBlock)
+void setBlock(int x, int y, int z, int block) {
+  assert(world);
+  replay_log.record(voxels::ReplayOp::SetBlock, {x, y, z, block});
+  world->setBlockSymmetric(x, y, z, static_cast<voxels::Block>(block));
+}
+
+// Sets the symmetry for builder edits. mirrors is a mask of kMirror* bits;
+// planes and the radial axis are at twice their coordinates. Pass mirrors =
+// 0 and radial = 1 to turn symmetry off. See symmetry.h.
+WASM_EXPORT(setSymmetry)
+void setSymmetry(int mirrors, int planeX2, int planeY2, int planeZ2,
+                 int radial, int axisX2, int axisZ2) {
+  assert(world);
+  replay_log.record(voxels::ReplayOp::SetSymmetry,
+                    {mirrors, planeX2, planeY2, planeZ2, radial, axisX2,
+                     axisZ2});
+  assert(1 <= radial && radial <= voxels::kMaxRadialSymmetry);
+  world->setSymmetry({mirrors, {planeX2, planeY2, planeZ2}, radial,
+                      axisX2, axisZ2});
+}
+
+WASM_EXPORT(fillBlocks)
+void fillBlocks(int x0, int y0, int z0, int x1, int y1, int z1,
+                int block, bool collectDrops) {
+  assert(world);
+  replay_log.record(voxels::ReplayOp::FillBlocks,
+                    {x0, y0, z0, x1, y1, z1, block, collectDrops});
+  world->fillBlocks(x0, y0, z0, x1, y1, z1,
+                    static_cast<voxels::Block>(block), collectDrops);
+}
+
+// The builder's selection, an inclusive box. See voxels::Selection.
+WASM_EXPORT(setSelection)
+void setSelection(int x0, int y0, int z0, int x1, int y1, int z1) {
+  assert(world);
+  replay_log.record(voxels::ReplayOp::SetSelection, {x0, y0, z0, x1, y1, z1});
+  world->setSelection(voxels::Selection({x0, y0, z0}, {x1, y1, z1}));
+}
+
+WASM_EXPORT(clearSelection)
+void clearSelection() {
+  assert(world);
+  replay_log.record(voxels::ReplayOp::ClearSelection, {});
+  world->setSelection(std::nullopt);
+}
+
+// Grows the selection's face by the amount, or shrinks it if it's negative.
+// face is in [-x, +x, -y, +y, -z, +z] order.
+WASM_EXPORT(expandSelection)
+void expandSelection(int face, int amount) {
+  assert(world);
+  replay_log.record(voxels::ReplayOp::ExpandSelection, {face, amount});
+  assert(0 <= face && face < 6);
+  world->expandSelection(face, amount);
+}
+
+WASM_EXPORT(shiftSelection)
+void shiftSelection(int dx, int dy, int dz) {
+  assert(world);
+  replay_log.record(voxels::ReplayOp::ShiftSelection, {dx, dy, dz});
+  world->shiftSelection(dx, dy, dz);
+}
+
+// While the mask is on, bulk edits skip voxels outside of the selection.
+WASM_EXPORT(setSelectionMask)
+void setSelectionMask(bool enabled) {
+  assert(world);
+  replay_log.record(voxels::ReplayOp::SetSelectionMask, {enabled});
+  world->setSelectionMask(enabled);
+}
+
+// Returns (x0, y0, z0, x1, y1, z1) for the selection, with the min corner
+// first, or nullptr if there's no selection.
+WASM_EXPORT(getSelection)
+const int* getSelection() {
+  static std::array<int, 6> result;
+  assert(world);
+  const auto& selection = world->getSelection();
+  if (!selection) return nullptr;
+  const auto& [min, max] = *selection;
+  result = {min[0], min[1], min[2], max[0], max[1], max[2]};
+  return result.data();
+}
+
+WASM_EXPORT(getSelectionStats)
+const double* getSelectionStats() {
+  assert(world);
+  const auto result = world->getSelectionStats();
+  voxels::checkMemoryGrowth();
+  return result;
+}
+
+// Fills the selection with the block, as fillBlocks. Returns false if
+// there's no selection.
+WASM_EXPORT(fillSelection)
+bool fillSelection(int block, bool collectDrops) {
+  assert(world);
+  const auto& selection = world->getSelection();
+  if (!selection) return false;
+  const auto [min, max] = *selection;
+  fillBlocks(min[0], min[1], min[2], max[0], max[1], max[2],
+             block, collectDrops);
+  return true;
+}
+
+WASM_EXPORT(replaceInSelection)
+int replaceInSelection(int from, int to, bool collectDrops) {
+  using voxels::safe_cast;
+
+  assert(world);
+  replay_log.record(voxels::ReplayOp::ReplaceInSelection,
+                    {from, to, collectDrops});
+  return world->replaceInSelection(safe_cast<voxels::Block>(from),
+                                   safe_cast<voxels::Block>(to), collectDrops);
+}
+
+WASM_EXPORT(addProtectedRegion)
+void addProtectedRegion(int x0, int y0, int z0, int x1, int y1, int z1,
+                        int owner) {
+  assert(world);
+  replay_log.record(voxels::ReplayOp::ProtectRegion,
+                    {x0, y0, z0, x1, y1, z1, owner});
+  world->addProtectedRegion({{std::min(x0, x1), std::min(y0, y1),
+                              std::min(z0, z1)},
+                             {std::max(x0, x1), std::max(y0, y1),
+                              std::max(z0, z1)}, owner});
+  voxels::checkMemoryGrowth();
+}
+
+WASM_EXPORT(removeProtectedRegions)
+void removeProtectedRegions(int owner) {
+  assert(world);
+  replay_log.record(voxels::ReplayOp::UnprotectRegions, {owner});
+  world->removeProtectedRegions(owner);
+}
+
+WASM_EXPORT(setEditor)
+void setEditor(int owner) {
+  assert(world);
+  replay_log.record(voxels::ReplayOp::SetEditor, {owner});
+  world->setEditor(owner);
+}
+
+WASM_EXPORT(setPlayerEditRules)
+void setPlayerEditRules(double reach, double rate, double burst,
+                        double miningSlack) {
+  edit_rules = {reach, rate, burst, miningSlack};
+}
+
+WASM_EXPORT(resetPlayerEditLimit)
+void resetPlayerEditLimit(int player) {
+  edit_limiter.reset(player);
+}
+
+WASM_EXPORT(tryPlayerEdit)
+int tryPlayerEdit(int player, int x, int y, int z, int block,
+                  double eyeX, double eyeY, double eyeZ, int tool, int tier,
+                  double mined, double time) {
+  // We record the edits that we accept, rather than the checks, which depend
+  // on the host's clock. Returns the EditVerdict.
+  assert(world);
+  const auto context = voxels::EditContext{
+      {eyeX, eyeY, eyeZ}, tool, tier, mined, time};
+  const auto verdict = world->tryPlayerEdit(
+      player, x, y, z, block, context, edit_rules, edit_limiter);
+  if (verdict == voxels::EditVerdict::Accepted) {
+    replay_log.record(voxels::ReplayOp::PlayerEdit, {player, x, y, z, block});
+  }
+  voxels::checkMemoryGrowth();
+  return static_cast<int>(verdict);
+}
+
+// Replays an edit that tryPlayerEdit accepted, skipping its checks.
+static void applyPlayerEdit(int player, int x, int y, int z, int block) {
+  using voxels::safe_cast;
+
+  replay_log.record(voxels::ReplayOp::PlayerEdit, {player, x, y, z, block});
+  world->applyPlayerEdit(player, x, y, z, safe_cast<voxels::Block>(block));
+}
+
+WASM_EXPORT(getEditDrops)
+const float* getEditDrops() {
+  assert(world);
+  return world->getEditDrops();
+}
+
+// Makes the world toroidal, wrapping every numChunks chunks along x and z.
+// Call it right after initializeWorld, before loading any chunks.
+WASM_EXPORT(setWorldWrap)
+void setWorldWrap(int numChunks) {
+  assert(world);
+  replay_log.record(voxels::ReplayOp::SetWorldWrap, {numChunks});
+  world->setWrap(numChunks);
+}
+
+WASM_EXPORT(setFrontierDecimation)
+void setFrontierDecimation(int minLevel, int maxError) {
+  assert(world);
+  world->setFrontierDecimation(minLevel, maxError);
+}
+
+WASM_EXPORT(setFrontierSkirtDepth)
+void setFrontierSkirtDepth(int depth) {
+  assert(world);
+  world->setFrontierSkirtDepth(depth);
+}
+
+// Limits chunk (cx, cz)'s meshes to the face directions in the given mask,
+// for far chunks that the camera only sees from one side. See kAllFaces.
+WASM_EXPORT(setChunkFaceMask)
+bool setChunkFaceMask(int cx, int cz, int mask) {
+  assert(world);
+  assert(0 <= mask && mask <= voxels::kAllFaces);
+  return world->setChunkFaceMask(cx, cz, mask);
+}
+
+WASM_EXPORT(setMesherKind)
+void setMesherKind(int kind) {
+  assert(world);
+  assert(0 <= kind && kind < static_cast<int>(voxels::MesherKind::Count));
+  world->setMesherKind(static_cast<voxels::MesherKind>(kind));
+}
+
+// Moves the render origin, e.g. when the camera strays far from it. See
+// setRenderOrigin in renderer.h.
+WASM_EXPORT(rebaseOrigin)
+void rebaseOrigin(int x, int z) {
+  assert(world);
+  world->rebaseOrigin(x, z);
+}
+
+WASM_EXPORT(setFarChunkRadius)
+void setFarChunkRadius(int radius) {
+  assert(world);
+  world->setFarChunkRadius(radius);
+}
+
+WASM_EXPORT(setDynamicLight)
+void setDynamicLight(int id, int x, int y, int z, int level) {
+  assert(world);
+  replay_log.record(voxels::ReplayOp::SetDynamicLight, {id, x, y, z, level});
+  world->setDynamicLight(id, x, y, z, level);
+  voxels::checkMemoryGrowth();
+}
+
+WASM_EXPORT(addProjectile)
+void addProjectile(int id, int kind, double x, double y, double z,
+                   double vx, double vy, double vz) {
+  assert(world);
+  const double values[] = {x, y, z, vx, vy, vz};
+  replay_log.record(voxels::ReplayOp::AddProjectile, {id, kind},
+                    values, sizeof(values));
+  world->addProjectile(id, kind, {x, y, z}, {vx, vy, vz});
+  voxels::checkMemoryGrowth();
+}
+
+WASM_EXPORT(removeProjectile)
+void removeProjectile(int id) {
+  assert(world);
+  replay_log.record(voxels::ReplayOp::RemoveProjectile, {id});
+  world->removeProjectile(id);
+}
+
+WASM_EXPORT(stepProjectiles)
+const float* stepProjectiles(double dt) {
+  assert(world);
+  replay_log.record(voxels::ReplayOp::StepProjectiles, {}, &dt, sizeof(dt));
+  const auto result = world->stepProjectiles(dt);
+  voxels::checkMemoryGrowth();
+  return result;
+}
+
+// Flags is a bitmask of kDebug* values, one for each subsystem to draw.
+WASM_EXPORT(setDebugDrawFlags)
+void setDebugDrawFlags(uint32_t flags) {
+  voxels::getDebugDraw().setFlags(flags);
+}
+
+WASM_EXPORT(drainDebugDraw)
+const uint8_t* drainDebugDraw() {
+  const auto result = voxels::getDebugDraw().drain();
+  voxels::checkMemoryGrowth();
+  return result;
+}
+
+WASM_EXPORT(getCameraMedium)
+const double* getCameraMedium(double x, double y, double z,
+                              double radius, double wave) {
+  // Returns the block that the eye is in, or Air if it's in a liquid block
+  // but above the surface, and the fraction of the camera box under liquid.
+  using voxels::CameraCell;
+  static std::array<double, 2> result;
+
+  assert(world);
+  const auto cell = [&](int x, int y, int z) {
+    return world->getCameraCell(x, y, z);
+  };
+  const auto eye = std::array{x, y, z};
+  const auto xi = static_cast<int>(std::floor(x));
+  const auto yi = static_cast<int>(std::floor(y));
+  const auto zi = static_cast<int>(std::floor(z));
+
+  auto block = world->getBlock(xi, yi, zi);
+  if (block == voxels::Block::Unknown) block = voxels::Block::Air;
+  if (cell(xi, yi, zi) == CameraCell::Liquid &&
+      voxels::getSubmergedFraction(eye, 0, wave, cell) == 0) {
+    block = voxels::Block::Air;
+  }
+  const auto submerged = voxels::getSubmergedFraction(eye, radius, wave, cell);
+  result = {static_cast<double>(block), submerged};
+  return result.data();
+}
+
+WASM_EXPORT(resolveCameraPosition)
+const double* resolveCameraPosition(double x, double y, double z,
+                                    double radius, double maxPush,
+                                    double dt, double rate) {
+  // Pushes a camera box out of walls, easing back as the walls clear, and
+  // returns the camera's position, followed by 1 if it's clear or 0 if it's
+  // stuck in a wall that's too thick to push out of.
+  static std::array<double, 4> result;
+
+  assert(world);
+  const auto cell = [&](int x, int y, int z) {
+    return world->getCameraCell(x, y, z);
+  };
+  auto eye = std::array{x, y, z};
+  const auto clear = voxels::pushOutOfWalls(eye, radius, maxPush, cell);
+
+  const auto target = std::array{x, y, z};
+  for (auto i = 0; i < 3; i++) {
+    const auto push = eye[i] - target[i];
+    if (push != 0) camera_state.sign[i] = push > 0 ? 1 : -1;
+    const auto size = camera_state.push[i].update(
+        std::abs(push), dt, rate, /*snap_below=*/false);
+    result[i] = target[i] + camera_state.sign[i] * size;
+  }
+  result[3] = clear ? 1 : 0;
+
+  auto& debug = voxels::getDebugDraw();
+  if (debug.enabled(voxels::kDebugCamera)) {
+    std::array<double, 3> min, max;
+    for (auto i = 0; i < 3; i++) {
+      min[i] = result[i] - radius;
+      max[i] = result[i] + radius;
+    }
+    debug.line(voxels::kDebugCamera, target, eye, 0xff40ffff);
+    debug.box(voxels::kDebugCamera, min, max,
+              clear ? 0xff40ffff : 0xff4040ff);
+  }
+  return result.data();
+}
+
+WASM_EXPORT(clipCamera)
+const double* clipCamera(double tx, double ty, double tz, double ox,
+                         double oy, double oz, double radius, double dt,
+                         double rate) {
+  // Returns the part of the offset from the target that a camera box can
+  // move along without entering a wall. The distance snaps in when a wall
+  // comes between the target and the camera, and eases back out at `rate`
+  // per second; a rate of 0 disables smoothing.
+  static std::array<double, 3> result;
+
+  assert(world);
+  const auto cell = [&](int x, int y, int z) {
+    return world->getCameraCell(x, y, z);
+  };
+  const auto offset = std::array{ox, oy, oz};
+  const auto length = std::sqrt(ox * ox + oy * oy + oz * oz);
+  const auto allowed = voxels::clipCamera({tx, ty, tz}, offset, radius, cell);
+  const auto smoothed = camera_state.clip.update(
+      allowed, dt, rate, /*snap_below=*/true);
+
+  const auto scale = length > 0 ? std::min(smoothed, length) / length : 0;
+  for (auto i = 0; i < 3; i++) result[i] = scale * offset[i];
+
+  auto& debug = voxels::getDebugDraw();
+  if (debug.enabled(voxels::kDebugCamera)) {
+    const auto target = std::array{tx, ty, tz};
+    auto end = target, min = target, max = target;
+    for (auto i = 0; i < 3; i++) {
+      end[i] += result[i];
+      min[i] = end[i] - radius;
+      max[i] = end[i] + radius;
+    }
+    debug.line(voxels::kDebugCamera, target, end, 0x40ffffff);
+    debug.box(voxels::kDebugCamera, min, max,
+              allowed < length ? 0xff4040ff : 0x40ffffff);
+  }
+  return result.data();
+}
+
+// Returns the new drop's id.
+WASM_EXPORT(addItemDrop)
+int addItemDrop(int item, int count, double x, double y, double z,
+                double vx, double vy, double vz) {
+  assert(world);
+  const double values[] = {x, y, z, vx, vy, vz};
+  replay_log.record(voxels::ReplayOp::AddItemDrop, {item, count},
+                    values, sizeof(values));
+  const auto id = world->addItemDrop(item, count, {x, y, z}, {vx, vy, vz});
+  voxels::checkMemoryGrowth();
+  return id;
+}
+
+WASM_EXPORT(removeItemDrop)
+void removeItemDrop(int id) {
+  assert(world);
+  replay_log.record(voxels::ReplayOp::RemoveItemDrop, {id});
+  world->removeItemDrop(id);
+}
+
+WASM_EXPORT(setItemDropRules)
+void setItemDropRules(double gravity, double drag, double mergeRadius,
+                      double despawnTime, double pickupDelay,
+                      double magnetRadius, double magnetSpeed) {
+  assert(world);
+  const double values[] = {gravity, drag, mergeRadius, despawnTime,
+                           pickupDelay, magnetRadius, magnetSpeed};
+  replay_log.record(voxels::ReplayOp::SetItemDropRules, {},
+                    values, sizeof(values));
+  world->setItemDropRules({gravity, drag, mergeRadius, despawnTime,
+                           pickupDelay, magnetRadius, magnetSpeed});
+}
+
+// Positions are (x, y, z) doubles.
+WASM_EXPORT(setItemMagnets)
+void setItemMagnets(const double* positions, int count) {
+  assert(world);
+  const auto size = static_cast<int>(3 * sizeof(double)) * count;
+  replay_log.record(voxels::ReplayOp::SetItemMagnets, {}, positions, size);
+  world->setItemMagnets(positions, count);
+  voxels::checkMemoryGrowth();
+}
+
+WASM_EXPORT(stepItemDrops)
+const float* stepItemDrops(double dt) {
+  assert(world);
+  replay_log.record(voxels::ReplayOp::StepItemDrops, {}, &dt, sizeof(dt));
+  const auto result = world->stepItemDrops(dt, items);
+  voxels::checkMemoryGrowth();
+  return result;
+}
+
+WASM_EXPORT(pickUpItemDrops)
+const int* pickUpItemDrops(int inventory, double x, double y, double z,
+                           double radius) {
+  // Adds the drops within radius to the inventory, leaving what doesn't fit
+  // on the ground. Returns a count, followed by (id, item, count) for each
+  // drop that we took items from.
+  static std::vector<int> result;
+  result.assign(1, 0);
+
+  assert(world);
+  const double values[] = {x, y, z, radius};
+  replay_log.record(voxels::ReplayOp::PickUpItemDrops, {inventory},
+                    values, sizeof(values));
+  const auto target = getInventoryById(inventory);
+  if (!target) return result.data();
+
+  world->pickUpItemDrops({x, y, z}, radius, [&](int id, int item, int count) {
+    const auto left = target->add(items, item, count);
+    if (left < count) {
+      result.push_back(id);
+      result.push_back(item);
+      result.push_back(count - left);
+      result[0]++;
+    }
+    return left;
+  });
+  voxels::checkMemoryGrowth();
+  return result.data();
+}
+
+WASM_EXPORT(setPointLight)
+void setPointLight(int x, int y, int z, int level) {
+  assert(world);
+  replay_log.record(voxels::ReplayOp::SetPointLight, {x, y, z, level});
+  world->setPointLight(x, y, z, level);
+}
+
+WASM_EXPORT(bakeAmbientOcclusion)
+bool bakeAmbientOcclusion(int cx, int cz, int samples) {
+  assert(world);
+  return world->bakeAmbientOcclusion(cx, cz, samples);
+}
+
+static const int* packMeshReport(const voxels::MeshReport& report) {
+  static std::vector<int> result;
+  result.assign({report.valid() ? 1 : 0, report.quads, report.duplicates,
+                 report.overlaps, report.holes, report.extras,
+                 report.out_of_range});
+  voxels::checkMemoryGrowth();
+  return result.data();
+}
+
+// Returns [valid, chunks, bad_voxels, bad_faces, num_voxels, num_faces],
+// followed by num_voxels (x, y, z, block) tuples and num_faces (block, face,
+// material) tuples. See WorldReport.
+WASM_EXPORT(validateWorld)
+const int* validateWorld() {
+  assert(world);
+  voxels::WorldReport report;
+  world->validate(report);
+
+  static std::vector<int> result;
+  result.assign({report.valid() ? 1 : 0, report.chunks, report.bad_voxels,
+                 report.bad_faces, static_cast<int>(report.voxels.size()),
+                 static_cast<int>(report.faces.size())});
+  for (const auto& x : report.voxels) {
+    result.insert(result.end(), x.begin(), x.end());
+  }
+  for (const auto& x : report.faces) {
+    result.insert(result.end(), x.begin(), x.end());
+  }
+  voxels::checkMemoryGrowth();
+  return result.data();
+}
+
+// Returns [valid, quads, duplicates, overlaps, holes, extras, out_of_range]
+// for the loaded chunk (cx, cz), or nullptr if it isn't loaded.
+WASM_EXPORT(validateMesh)
+const int* validateMesh(int cx, int cz) {
+  assert(world);
+  voxels::MeshReport report;
+  if (!world->validateMesh(cx, cz, report)) return nullptr;
+  return packMeshReport(report);
+}
+
+// Returns [valid, bad_voxels, bad_lights, bad_heights, num_cells], followed
+// by num_cells (layer, x, y, z, expected, actual) tuples, for the loaded
+// chunk (cx, cz), or nullptr if it isn't loaded. See BorderReport.
+WASM_EXPORT(checkBorders)
+const int* checkBorders(int cx, int cz) {
+  assert(world);
+  voxels::BorderReport report;
+  if (!world->checkBorders(cx, cz, report)) return nullptr;
+
+  static std::vector<int> result;
+  result.assign({report.valid() ? 1 : 0, report.bad_voxels, report.bad_lights,
+                 report.bad_heights, static_cast<int>(report.cells.size())});
+  for (const auto& x : report.cells) {
+    result.insert(result.end(), x.begin(), x.end());
+  }
+  voxels::checkMemoryGrowth();
+  return result.data();
+}
+
+// Checks the mesher against the golden-path fixtures in fixtures.h. Returns
+// a report laid out as in validateMesh.
+WASM_EXPORT(checkMeshFixtures)
+const int* checkMeshFixtures() {
+  assert(world);
+  voxels::MeshReport report;
+  world->checkMeshFixtures(report);
+  return packMeshReport(report);
+}
+
+// Appends one material id per quad, four to an int, padded to a whole int.
+static void packQuadMaterials(const std::vector<voxels::MaybeMaterial>& ids,
+                              std::vector<int>& result) {
+  for (size_t i = 0; i < ids.size(); i += 4) {
+    uint32_t packed = 0;
+    for (size_t j = 0; j < 4 && i + j < ids.size(); j++) {
+      packed |= static_cast<uint32_t>(ids[i + j].id) << (8 * j);
+    }
+    result.push_back(static_cast<int>(packed));
+  }
+}
+
+// Returns [vertex_count, index_count], followed by the vertices and indices
+// of the welded mesh of the loaded chunk (cx, cz), or nullptr if it isn't
+// loaded. Phase 0 is solid geometry and 1 is water; see weldQuads. If
+// repeats is set, we follow them with one int per vertex, packing its two
+// repeat counts as 16-bit halves. Last come the quad materials, one byte
+// per quad of six indices.
+WASM_EXPORT(weldMesh)
+const int* weldMesh(int cx, int cz, int phase, bool repeats) {
+  assert(world);
+  static std::vector<int> result;
+  voxels::IndexedMesh mesh;
+  if (!world->weldMesh(cx, cz, phase, repeats, mesh)) return nullptr;
+
+  const auto vertices = static_cast<int>(mesh.vertices.size() / 4);
+  const auto indices = static_cast<int>(mesh.indices.size());
+  result.assign({vertices, indices});
+  result.insert(result.end(), mesh.vertices.begin(), mesh.vertices.end());
+  result.insert(result.end(), mesh.indices.begin(), mesh.indices.end());
+  for (size_t i = 0; i + 1 < mesh.repeats.size(); i += 2) {
+    result.push_back(mesh.repeats[i] | (mesh.repeats[i + 1] << 16));
+  }
+  packQuadMaterials(mesh.quad_materials, result);
+  voxels::checkMemoryGrowth();
+  return result.data();
+}
+
+// Returns the welded mesh of the loaded chunk (cx, cz), split into batches
+// for WebGL1, or nullptr if it isn't loaded. pages maps each texture id to
+// its atlas page. We return a batch count, followed by each batch: [page,
+// vertex_count, index_count], the vertices as kBatchVertexFloats floats
+// each, the repeats packed as in weldMesh if requested, the 16-bit indices,
+// two per int, padded to a whole int, and the quad materials, as in
+// weldMesh. See batchIndexedMesh.
+WASM_EXPORT(batchMesh)
+const int* batchMesh(int cx, int cz, int phase, bool repeats,
+                     const uint8_t* pages) {
+  assert(world);
+  static std::vector<int> result;
+  static std::vector<voxels::MeshBatch> batches;
+  voxels::IndexedMesh mesh;
+  if (!world->weldMesh(cx, cz, phase, repeats, mesh)) return nullptr;
+  voxels::batchIndexedMesh(mesh, pages, batches);
+
+  result.assign({static_cast<int>(batches.size())});
+  for (const auto& batch : batches) {
+    const auto vertices = static_cast<int>(
+        batch.vertices.size() / voxels::kBatchVertexFloats);
+    const auto indices = static_cast<int>(batch.indices.size());
+    result.insert(result.end(), {batch.page, vertices, indices});
+    for (const auto value : batch.vertices) {
+      int32_t bits = 0;
+      memcpy(&bits, &value, sizeof(bits));
+      result.push_back(bits);
+    }
+    for (size_t i = 0; i + 1 < batch.repeats.size(); i += 2) {
+      result.push_back(batch.repeats[i] | (batch.repeats[i + 1] << 16));
+    }
+    for (size_t i = 0; i < batch.indices.size(); i += 2) {
+      const auto next = i + 1 < batch.indices.size() ? batch.indices[i + 1] : 0;
+      result.push_back(batch.indices[i] | (next << 16));
+    }
+    packQuadMaterials(batch.quad_materials, result);
+  }
+  voxels::checkMemoryGrowth();
+  return result.data();
+}
+
+// Returns a null-terminated text dump of a slice of the loaded chunk (cx,
+// cz), or an empty string if it isn't loaded. See slices.h for the format.
+WASM_EXPORT(dumpSlice)
+const char* dumpSlice(int cx, int cz, int axis, int index) {
+  assert(world);
+  static std::string result;
+  if (!(0 <= axis && axis < 3)) return "";
+  const auto limit = axis == 1 ? voxels::kWorldHeight : voxels::kChunkWidth;
+  if (!(0 <= index && index < limit)) return "";
+  result = world->dumpSlice(cx, cz, axis, index);
+  voxels::checkMemoryGrowth();
+  return result.c_str();
+}
+
+WASM_EXPORT(loadSlice)
+bool loadSlice(int cx, int cz, const char* text) {
+  assert(world);
+  replay_log.record(voxels::ReplayOp::LoadSlice, {cx, cz},
+                    text, static_cast<int>(strlen(text)));
+  return world->loadSlice(cx, cz, text);
+}
+
+// Meshes a standalone n x n heightmap whose corner is at (x, z). data holds
+// an entry per column, indexed by x + n * z: a (block, height) pair for the
+// solid surface and another for water, one byte each. Pass n = 0 to clear.
+WASM_EXPORT(setHeightmapMesh)
+void setHeightmapMesh(const uint8_t* data, int n, int x, int z) {
+  assert(world);
+  using Entry = voxels::Mesher::HeightmapEntry;
+  static_assert(sizeof(Entry) == 4);
+  world->setHeightmapMesh(reinterpret_cast<const Entry*>(data), n, x, z);
+  voxels::frameArena().reset();
+  voxels::checkMemoryGrowth();
+}
+
+// Returns [type, shape_x, shape_y, shape_z, stride_x, stride_y, stride_z,
+// pointer] for one of the loaded chunk (cx, cz)'s arrays, or nullptr if it
+// isn't loaded. See TensorView. The pointer is only valid until the next
+// call that modifies the world.
+WASM_EXPORT(getChunkLayer)
+const int* getChunkLayer(int cx, int cz, int layer) {
+  assert(world);
+  using voxels::ChunkLayer;
+  if (!(0 <= layer && layer < static_cast<int>(ChunkLayer::Count))) {
+    return nullptr;
+  }
+
+  static std::array<int, 8> result;
+  auto view = voxels::TensorView{};
+  if (!world->getChunkLayer(cx, cz, static_cast<ChunkLayer>(layer), view)) {
+    return nullptr;
+  }
+  result = {static_cast<int>(view.type),
+            view.shape[0], view.shape[1], view.shape[2],
+            view.stride[0], view.stride[1], view.stride[2],
+            static_cast<int>(reinterpret_cast<uintptr_t>(view.data))};
+  return result.data();
+}
+
+// Returns the given mip level of the loaded chunk (cx, cz)'s cone-tracing
+// volume, or nullptr if it isn't loaded. See cones.h for the layout.
+WASM_EXPORT(getConeVolume)
+const uint8_t* getConeVolume(int cx, int cz, int level) {
+  assert(world);
+  if (!(0 <= level && level < voxels::kConeLevels)) return nullptr;
+  const auto result = world->getConeVolume(cx, cz, level);
+  voxels::checkMemoryGrowth();
+  return result;
+}
+
+// Returns the given mip level of the loaded chunk (cx, cz)'s collision field,
+// or nullptr if it isn't loaded. See collision.h for the layout.
+WASM_EXPORT(getCollisionField)
+const uint64_t* getCollisionField(int cx, int cz, int level) {
+  assert(world);
+  if (!(0 <= level && level < voxels::kCollisionLevels)) return nullptr;
+  const auto field = world->getCollisionField(cx, cz);
+  return field ? field->getLevel(level) : nullptr;
+}
+
+// Returns the version of the loaded chunk (cx, cz)'s collision field, which
+// changes whenever the field does, or 0 if it isn't loaded.
+WASM_EXPORT(getCollisionFieldVersion)
+uint32_t getCollisionFieldVersion(int cx, int cz) {
+  assert(world);
+  const auto field = world->getCollisionField(cx, cz);
+  return field ? field->getVersion() : 0;
+}
+
+// Returns [count, palette_size], followed by the bricks, palette, and
+// indices of the loaded chunk (cx, cz), or nullptr if it isn't loaded. See
+// bricks.h for the layout of each array.
+WASM_EXPORT(exportBricks)
+const uint32_t* exportBricks(int cx, int cz) {
+  assert(world);
+  static voxels::BrickExport bricks;
+  static std::vector<uint32_t> result;
+  if (!world->exportBricks(cx, cz, bricks)) return nullptr;
+
+  const auto palette_size = static_cast<uint32_t>(bricks.palette.size());
+  result.assign({static_cast<uint32_t>(bricks.count), palette_size});
+  result.insert(result.end(), bricks.bricks.begin(), bricks.bricks.end());
+  result.insert(result.end(), bricks.palette.begin(), bricks.palette.end());
+  result.insert(result.end(), bricks.indices.begin(), bricks.indices.end());
+  voxels::checkMemoryGrowth();
+  return result.data();
+}
+
+WASM_EXPORT(anySolidInBox)
+bool anySolidInBox(int x0, int y0, int z0, int x1, int y1, int z1) {
+  assert(world);
+  return world->anySolidInBox({x0, y0, z0}, {x1, y1, z1});
+}
+
+WASM_EXPORT(audioOcclusion)
+double audioOcclusion(double lx, double ly, double lz,
+                      double sx, double sy, double sz) {
+  assert(world);
+  return world->audioOcclusion({lx, ly, lz}, {sx, sy, sz});
+}
+
+WASM_EXPORT(raycast)
+double raycast(double x, double y, double z,
+               double dx, double dy, double dz, double length) {
+  assert(world);
+  return world->raycast({x, y, z}, {dx, dy, dz}, length);
+}
+
+WASM_EXPORT(roomSizeEstimate)
+double roomSizeEstimate(double x, double y, double z) {
+  assert(world);
+  return world->roomSizeEstimate({x, y, z});
+}
+
+WASM_EXPORT(greedyRects)
+const int* greedyRects(int* mask, int w, int h) {
+  // Returns a count, followed by (x, y, w, h, value) for each rectangle.
+  // The mask is w x h with x as the fast axis, and it is cleared in place.
+  static std::vector<int> result;
+  result.clear();
+  result.push_back(0);
+  voxels::greedyRects(mask, nullptr, h, w,
+                      [&](int iu, int iv, int du, int dv, int value) {
+    result.insert(result.end(), {iv, iu, dv, du, value});
+    result[0]++;
+  });
+  return result.data();
+}
+
+WASM_EXPORT(buildImpostor)
+const uint32_t* buildImpostor(int cx, int cz, int resolution) {
+  assert(world);
+  return world->buildImpostor(cx, cz, resolution);
+}
+
+WASM_EXPORT(renderMinimap)
+const uint32_t* renderMinimap(int cx, int cz) {
+  assert(world);
+  return world->renderMinimap(cx, cz);
+}
+
+WASM_EXPORT(registerBlock)
+void registerBlock(
+    int block, bool mesh, bool opaque, bool solid, int light,
+    int face0, int face1, int face2, int face3, int face4, int face5) {
+  using voxels::safe_cast;
+  const auto material = [](int x) {
+    return voxels::MaybeMaterial{safe_cast<uint8_t>(x)};
+  };
+
+  assert(world);
+  world->mutableRegistry().addBlock(safe_cast<voxels::Block>(block), {
+    mesh, opaque, solid, safe_cast<int8_t>(light),
+    {material(face0), material(face1), material(face2),
+     material(face3), material(face4), material(face5)},
+  });
+}
+
+WASM_EXPORT(registerBlockMining)
+void registerBlockMining(int block, double hardness,
+                         int tool, int tier, int drop_table) {
+  using voxels::safe_cast;
+
+  assert(world);
+  world->mutableRegistry().setMining(safe_cast<voxels::Block>(block), {
+    hardness, safe_cast<uint8_t>(tool), safe_cast<uint8_t>(tier), drop_table,
+  });
+}
+
+WASM_EXPORT(registerBlockTags)
+void registerBlockTags(int block, int tags) {
+  using voxels::safe_cast;
+
+  assert(world);
+  world->mutableRegistry().setTags(
+      safe_cast<voxels::Block>(block), safe_cast<uint8_t>(tags));
+}
+
+WASM_EXPORT(registerBlockAttenuation)
+void registerBlockAttenuation(int block, int attenuation) {
+  using voxels::safe_cast;
+
+  assert(world);
+  world->mutableRegistry().setAttenuation(
+      safe_cast<voxels::Block>(block), safe_cast<uint8_t>(attenuation));
+}
+
+WASM_EXPORT(registerBlockBlastResistance)
+void registerBlockBlastResistance(int block, double resistance) {
+  using voxels::safe_cast;
+
+  assert(world);
+  world->mutableRegistry().setBlastResistance(
+      safe_cast<voxels::Block>(block), resistance);
+}
+
+WASM_EXPORT(registerTreeSpecies)
+void registerTreeSpecies(int species, int trunk, int leaves, int minHeight,
+                         int maxHeight, int radius, bool conical) {
+  using voxels::safe_cast;
 
-WASM_EXPORT(getLightLevel)
-int getLightLevel(int x, int y, int z) {
   assert(world);
-  return world->getLightLevel(x, y, z);
+  world->mutableRegistry().addTreeSpecies(species, {
+    safe_cast<voxels::Block>(trunk), safe_cast<voxels::Block>(leaves),
+    safe_cast<uint8_t>(minHeight), safe_cast<uint8_t>(maxHeight),
+    safe_cast<uint8_t>(radius), conical,
+  });
 }
 
-WASM_EXPORT(setBlock)
-void setBlock(int x, int y, int z, int block) {
+WASM_EXPORT(registerProjectileKind)
+void registerProjectileKind(int kind, double gravity, double drag,
+                            double restitution) {
   assert(world);
-  world->setBlock(x, y, z, static_cast<voxels::Block>(block));
+  world->mutableRegistry().addProjectileKind(kind, gravity, drag, restitution);
 }
 
-WASM_EXPORT(setPointLight)
-void setPointLight(int x, int y, int z, int level) {
+WASM_EXPORT(registerProjectileResponse)
+void registerProjectileResponse(int kind, int block, int response) {
+  using voxels::safe_cast;
+
   assert(world);
-  world->setPointLight(x, y, z, level);
+  assert(0 <= response &&
+         response < static_cast<int>(voxels::ProjectileResponse::Count));
+  world->mutableRegistry().setProjectileResponse(
+      kind, safe_cast<voxels::Block>(block),
+      static_cast<voxels::ProjectileResponse>(response));
 }
 
-WASM_EXPORT(registerBlock)
-void registerBlock(
-    int block, bool mesh, bool opaque, bool solid, int light,
-    int face0, int face1, int face2, int face3, int face4, int face5) {
+WASM_EXPORT(registerBlockHooks)
+void registerBlockHooks(int block, int hooks) {
   using voxels::safe_cast;
-  const auto material = [](int x) {
-    return voxels::MaybeMaterial{safe_cast<uint8_t>(x)};
-  };
 
   assert(world);
-  world->mutableRegistry().addBlock(safe_cast<voxels::Block>(block), {
-    mesh, opaque, solid, safe_cast<int8_t>(light),
-    {material(face0), material(face1), material(face2),
-     material(face3), material(face4), material(face5)},
+  world->mutableRegistry().setHooks(
+      safe_cast<voxels::Block>(block), safe_cast<uint8_t>(hooks));
+}
+
+WASM_EXPORT(registerBlockPower)
+void registerBlockPower(int block, int kind, int level) {
+  using voxels::safe_cast;
+
+  assert(world);
+  world->mutableRegistry().setPower(safe_cast<voxels::Block>(block), {
+    safe_cast<voxels::PowerKind>(kind), safe_cast<uint8_t>(level),
+  });
+}
+
+WASM_EXPORT(propagatePower)
+const int* propagatePower(const int* positions, int count) {
+  assert(world);
+  replay_log.record(voxels::ReplayOp::PropagatePower, {count},
+                    positions, 3 * static_cast<int>(sizeof(int)) * count);
+  return world->propagatePower(positions, count);
+}
+
+WASM_EXPORT(getBlockMetadata)
+int getBlockMetadata(int x, int y, int z) {
+  assert(world);
+  return world->getMetadata(x, y, z);
+}
+
+WASM_EXPORT(pushBlocks)
+const int* pushBlocks(int x, int y, int z, int dir, int maxCount) {
+  assert(world);
+  replay_log.record(voxels::ReplayOp::PushBlocks, {x, y, z, dir, maxCount});
+  return world->pushBlocks(x, y, z, dir, maxCount);
+}
+
+WASM_EXPORT(registerSapling)
+void registerSapling(int block, int species, double chance) {
+  using voxels::safe_cast;
+
+  assert(world);
+  world->mutableRegistry().setSapling(
+      safe_cast<voxels::Block>(block), {species, chance});
+}
+
+WASM_EXPORT(addStructure)
+void addStructure(int type, int x0, int y0, int z0, int x1, int y1, int z1) {
+  assert(world);
+  world->addStructure({type, {std::min(x0, x1), std::min(y0, y1),
+                              std::min(z0, z1)},
+                             {std::max(x0, x1), std::max(y0, y1),
+                              std::max(z0, z1)}});
+  voxels::checkMemoryGrowth();
+}
+
+// Returns a count, followed by (type, x0, y0, z0, x1, y1, z1) for each
+// structure that overlaps the box between the two corners, inclusive.
+WASM_EXPORT(getStructuresInBox)
+const int* getStructuresInBox(int x0, int y0, int z0, int x1, int y1, int z1) {
+  assert(world);
+  static std::vector<int> result;
+  const auto& structures = world->getStructuresInBox(
+      {std::min(x0, x1), std::min(y0, y1), std::min(z0, z1)},
+      {std::max(x0, x1), std::max(y0, y1), std::max(z0, z1)});
+
+  result.assign({static_cast<int>(structures.size())});
+  for (const auto& [type, min, max] : structures) {
+    result.insert(result.end(),
+                  {type, min[0], min[1], min[2], max[0], max[1], max[2]});
+  }
+  voxels::checkMemoryGrowth();
+  return result.data();
+}
+
+// Returns (type, x0, y0, z0, x1, y1, z1) for the structure of the given type
+// that's horizontally closest to (x, z), or nullptr if there isn't one.
+WASM_EXPORT(findNearestStructure)
+const int* findNearestStructure(int type, int x, int z) {
+  assert(world);
+  static std::array<int, 7> result;
+  const auto structure = world->findNearestStructure(type, x, z);
+  if (!structure) return nullptr;
+  const auto& min = structure->min;
+  const auto& max = structure->max;
+  result = {type, min[0], min[1], min[2], max[0], max[1], max[2]};
+  return result.data();
+}
+
+WASM_EXPORT(growTree)
+bool growTree(int x, int y, int z, int species, uint32_t seed) {
+  assert(world);
+  replay_log.record(voxels::ReplayOp::GrowTree,
+                    {x, y, z, species, static_cast<int32_t>(seed)});
+  return world->growTree(x, y, z, species, seed);
+}
+
+// Voxelizes a triangle mesh, with `resolution` voxels per model unit, and
+// fills the voxels with the block, placing the model's origin at (x, y, z).
+// Solid fill expects a closed mesh. Returns the number of blocks changed.
+// See voxelize.h.
+WASM_EXPORT(voxelizeMesh)
+int voxelizeMesh(const float* vertices, const uint32_t* indices,
+                 int numVertices, int numIndices, int block,
+                 double resolution, bool solid, int x, int y, int z) {
+  assert(world);
+  if (replay_log.recording()) {
+    const auto& payload = packReplayPayload({
+      {&resolution, sizeof(resolution)},
+      {vertices, 3 * sizeof(float) * numVertices},
+      {indices, sizeof(uint32_t) * numIndices},
+    });
+    replay_log.record(voxels::ReplayOp::VoxelizeMesh,
+                      {numVertices, numIndices, block, solid, x, y, z},
+                      payload.data(), static_cast<int>(payload.size()));
+  }
+  static std::vector<std::array<int, 3>> offsets;
+  const auto fill =
+      solid ? voxels::VoxelizeFill::Solid : voxels::VoxelizeFill::Surface;
+  voxels::voxelizeMesh(vertices, numVertices, indices, numIndices,
+                       resolution, fill, offsets);
+  const auto result = world->placeVoxels(
+      x, y, z, offsets, voxels::safe_cast<voxels::Block>(block));
+  voxels::checkMemoryGrowth();
+  return result;
+}
+
+// Sweeps a brush along the path through the `count` points, given as (x, y,
+// z) doubles, and fills the voxels it covers with the block: air to carve a
+// tunnel, or a road's surface block. profile is 0 for a rectangle or 1 for a
+// circle. Returns the number of blocks changed. See paths.h.
+WASM_EXPORT(carvePath)
+int carvePath(const double* points, int count, int profile, double width,
+              double height, bool spline, int block) {
+  assert(world);
+  assert(0 <= profile && profile <= 1);
+  if (replay_log.recording()) {
+    const auto& payload = packReplayPayload({
+      {&width, sizeof(width)},
+      {&height, sizeof(height)},
+      {points, 3 * sizeof(double) * count},
+    });
+    replay_log.record(voxels::ReplayOp::CarvePath, {profile, spline, block},
+                      payload.data(), static_cast<int>(payload.size()));
+  }
+  static std::vector<std::array<int, 3>> cells;
+  const auto brush = voxels::PathBrush{
+      static_cast<voxels::PathProfile>(profile), width, height, spline};
+  voxels::sweepPath(points, count, brush, cells);
+  const auto result = world->placeVoxels(
+      0, 0, 0, cells, voxels::safe_cast<voxels::Block>(block));
+  voxels::checkMemoryGrowth();
+  return result;
+}
+
+// Imports a w x h heightmap of 16-bit samples, with its first sample at the
+// column (x, z). The table holds numLayers (depth, block) pairs, from the top
+// of each column down. Returns the number of blocks changed. See
+// heightmaps.h.
+WASM_EXPORT(importHeightmap)
+int importHeightmap(const uint16_t* samples, int w, int h, double scale,
+                    const int* table, int numLayers, int x, int z) {
+  using voxels::safe_cast;
+
+  assert(world);
+  assert(0 < w && w <= voxels::kMaxHeightmapSize);
+  assert(0 < h && h <= voxels::kMaxHeightmapSize);
+  if (replay_log.recording()) {
+    const auto& payload = packReplayPayload({
+      {&scale, sizeof(scale)},
+      {table, 2 * sizeof(int) * numLayers},
+      {samples, sizeof(uint16_t) * w * h},
+    });
+    replay_log.record(voxels::ReplayOp::ImportHeightmap,
+                      {w, h, numLayers, x, z},
+                      payload.data(), static_cast<int>(payload.size()));
+  }
+  static std::vector<voxels::HeightmapLayer> layers;
+  layers.clear();
+  for (auto i = 0; i < numLayers; i++) {
+    const auto block = safe_cast<voxels::Block>(table[2 * i + 1]);
+    layers.push_back({table[2 * i], block});
+  }
+  const auto result =
+      world->importHeightmap(x, z, samples, w, h, scale, layers);
+  voxels::checkMemoryGrowth();
+  return result;
+}
+
+// Returns a heightmap of the inclusive box of columns from (x0, z0) to (x1,
+// z1), as 16-bit samples, in the same layout that importHeightmap takes.
+WASM_EXPORT(exportHeightmap)
+const uint16_t* exportHeightmap(int x0, int z0, int x1, int z1,
+                                double scale) {
+  assert(world);
+  assert(x0 <= x1 && x1 - x0 < voxels::kMaxHeightmapSize);
+  assert(z0 <= z1 && z1 - z0 < voxels::kMaxHeightmapSize);
+  static std::vector<uint16_t> result;
+  world->exportHeightmap(x0, z0, x1, z1, scale, result);
+  voxels::checkMemoryGrowth();
+  return result.data();
+}
+
+// Decodes a paletted PNG and stamps one block per pixel into the layer at
+// height y, with the image's top-left pixel at (x, y, z) and rows along +z.
+// The table holds numColors (0xRRGGBB, block) pairs; pixels of other colors,
+// and transparent pixels, leave their voxels alone. Returns the number of
+// blocks changed, or -1 if the image doesn't decode. See png.h.
+WASM_EXPORT(importLayerPng)
+int importLayerPng(const uint8_t* data, int size, const int* table,
+                   int numColors, int x, int y, int z) {
+  using voxels::safe_cast;
+
+  assert(world);
+  if (replay_log.recording()) {
+    const auto& payload = packReplayPayload({
+      {table, 2 * sizeof(int) * numColors},
+      {data, safe_cast<size_t>(size)},
+    });
+    replay_log.record(voxels::ReplayOp::ImportLayerPng,
+                      {numColors, x, y, z},
+                      payload.data(), static_cast<int>(payload.size()));
+  }
+  static voxels::IndexedImage image;
+  if (!voxels::decodeIndexedPng(data, safe_cast<size_t>(size), image)) {
+    return -1;
+  }
+
+  // Map palette entries, rather than pixels, to blocks.
+  std::vector<voxels::Block> blocks(image.palette.size(),
+                                    voxels::Block::Unknown);
+  for (size_t i = 0; i < blocks.size(); i++) {
+    if (image.alpha[i] == 0) continue;
+    for (auto j = 0; j < numColors; j++) {
+      if (safe_cast<uint32_t>(table[2 * j]) != image.palette[i]) continue;
+      blocks[i] = safe_cast<voxels::Block>(table[2 * j + 1]);
+      break;
+    }
+  }
+  static std::vector<voxels::Block> cells;
+  cells.clear();
+  for (const auto pixel : image.pixels) cells.push_back(blocks[pixel]);
+
+  const auto result =
+      world->stampLayer(x, y, z, image.width, image.height, cells);
+  voxels::checkMemoryGrowth();
+  return result;
+}
+
+// Entity AABBs are six doubles each, min then max. See World::stepTriggers.
+WASM_EXPORT(stepTriggers)
+void stepTriggers(const double* boxes, const int* entities, int count,
+                  bool stays) {
+  assert(world);
+  world->stepTriggers(boxes, entities, count, stays);
+  voxels::checkMemoryGrowth();
+}
+
+WASM_EXPORT(registerBlockTrigger)
+void registerBlockTrigger(int block, int type, double x0, double y0,
+                          double z0, double x1, double y1, double z1) {
+  using voxels::safe_cast;
+
+  assert(world);
+  world->mutableRegistry().setTrigger(
+      safe_cast<voxels::Block>(block),
+      {safe_cast<uint8_t>(type), {x0, y0, z0}, {x1, y1, z1}});
+}
+
+WASM_EXPORT(registerBlockEditPriority)
+void registerBlockEditPriority(int block, int priority) {
+  using voxels::safe_cast;
+
+  assert(world);
+  world->mutableRegistry().setEditPriority(
+      safe_cast<voxels::Block>(block), safe_cast<uint8_t>(priority));
+}
+
+// Copies the box between the two corners, inclusive, to the clipboard with
+// the given id, replacing any old one. Each side is at most 256 voxels.
+WASM_EXPORT(copyToClipboard)
+void copyToClipboard(int id, int x0, int y0, int z0, int x1, int y1, int z1) {
+  assert(world);
+  replay_log.record(voxels::ReplayOp::CopyToClipboard,
+                    {id, x0, y0, z0, x1, y1, z1});
+  auto clipboard = voxels::Clipboard(std::abs(x1 - x0) + 1,
+                                     std::abs(y1 - y0) + 1,
+                                     std::abs(z1 - z0) + 1);
+  world->copyToClipboard(std::min(x0, x1), std::min(y0, y1),
+                         std::min(z0, z1), clipboard);
+  clipboards.insert_or_assign(id, std::move(clipboard));
+  voxels::checkMemoryGrowth();
+}
+
+// Fills the clipboard with the given id with a shape of the block, replacing
+// any old one. See voxels::ClipboardShape.
+WASM_EXPORT(createClipboardShape)
+void createClipboardShape(int id, int shape, int sx, int sy, int sz,
+                          int block, int shell) {
+  using voxels::safe_cast;
+
+  replay_log.record(voxels::ReplayOp::CreateClipboardShape,
+                    {id, shape, sx, sy, sz, block, shell});
+  assert(0 <= shape && shape <= 2);
+  clipboards.insert_or_assign(id, voxels::Clipboard::shape(
+      static_cast<voxels::ClipboardShape>(shape), sx, sy, sz,
+      safe_cast<voxels::Block>(block), shell));
+  voxels::checkMemoryGrowth();
+}
+
+WASM_EXPORT(removeClipboard)
+bool removeClipboard(int id) {
+  return clipboards.erase(id) > 0;
+}
+
+// Combines the clipboard, with its min corner at (x, y, z), with the world:
+// op is 0 for union, 1 for subtract, or 2 for intersect. Returns the number
+// of blocks changed. See World::applyCsg.
+WASM_EXPORT(applyCsg)
+int applyCsg(int id, int op, int x, int y, int z) {
+  assert(world);
+  replay_log.record(voxels::ReplayOp::ApplyCsg, {id, op, x, y, z});
+  const auto it = clipboards.find(id);
+  if (it == clipboards.end()) return 0;
+  assert(0 <= op && op <= 2);
+  return world->applyCsg(it->second, static_cast<voxels::CsgOp>(op), x, y, z);
+}
+
+WASM_EXPORT(explode)
+const int* explode(int x, int y, int z, float power, uint32_t seed) {
+  assert(world);
+  int32_t bits = 0;
+  memcpy(&bits, &power, sizeof(bits));
+  replay_log.record(voxels::ReplayOp::Explode,
+                    {x, y, z, bits, static_cast<int32_t>(seed)});
+  const auto result = world->explode(x, y, z, power, seed);
+  voxels::checkMemoryGrowth();
+  return result;
+}
+
+WASM_EXPORT(registerBlockSpread)
+void registerBlockSpread(int block, int target, int decay, int minLight) {
+  using voxels::safe_cast;
+
+  assert(world);
+  world->mutableRegistry().setSpread(safe_cast<voxels::Block>(block), {
+    safe_cast<voxels::Block>(target), safe_cast<voxels::Block>(decay),
+    safe_cast<uint8_t>(minLight),
   });
 }
 
+WASM_EXPORT(registerBlockFire)
+void registerBlockFire(int block, double ignite, double burn) {
+  using voxels::safe_cast;
+
+  assert(world);
+  world->mutableRegistry().setFire(
+      safe_cast<voxels::Block>(block), {ignite, burn});
+}
+
+WASM_EXPORT(registerFireBlock)
+void registerFireBlock(int block) {
+  using voxels::safe_cast;
+
+  assert(world);
+  world->mutableRegistry().setFireBlock(safe_cast<voxels::Block>(block));
+}
+
+WASM_EXPORT(tickFire)
+const int* tickFire(int cx, int cz, bool raining) {
+  assert(world);
+  replay_log.record(voxels::ReplayOp::TickFire, {cx, cz, raining});
+  return world->tickFire(cx, cz, raining);
+}
+
+WASM_EXPORT(registerDropEntry)
+void registerDropEntry(int table, int item, int min, int max, double chance) {
+  assert(world);
+  world->mutableRegistry().addDropEntry(table, {item, min, max, chance});
+}
+
+WASM_EXPORT(breakTime)
+double breakTime(int block, int tool, int tier) {
+  using voxels::safe_cast;
+
+  assert(world);
+  const auto& registry = world->mutableRegistry();
+  return registry.getBreakTime(safe_cast<voxels::Block>(block), tool, tier);
+}
+
+WASM_EXPORT(dropsFor)
+const int* dropsFor(int block, int tool, int tier, uint32_t seed) {
+  // Returns a count, followed by (item, count) for each drop.
+  using voxels::safe_cast;
+  static std::vector<voxels::DropRecord> drops;
+  static std::vector<int> result;
+
+  assert(world);
+  const auto& registry = world->mutableRegistry();
+  registry.getDrops(safe_cast<voxels::Block>(block), tool, tier, seed, drops);
+
+  result.clear();
+  result.push_back(static_cast<int>(drops.size()));
+  for (const auto& drop : drops) {
+    result.push_back(drop.item);
+    result.push_back(drop.count);
+  }
+  return result.data();
+}
+
+WASM_EXPORT(registerItemTags)
+void registerItemTags(int item, uint32_t tags) {
+  items.setTags(item, tags);
+  voxels::checkMemoryGrowth();
+}
+
+// Ingredients are (item, tags) pairs of int32s. See Ingredient.
+static const voxels::Ingredient* readIngredients(const int* data, int size) {
+  static std::vector<voxels::Ingredient> result;
+  result.resize(size);
+  for (auto i = 0; i < size; i++) {
+    result[i] = {data[2 * i], static_cast<uint32_t>(data[2 * i + 1])};
+  }
+  return result.data();
+}
+
+WASM_EXPORT(registerShapedRecipe)
+int registerShapedRecipe(int width, int height, const int* pattern,
+                         int result, int count) {
+  const auto ingredients = readIngredients(pattern, width * height);
+  const auto id = recipes.addShaped(width, height, ingredients, result, count);
+  voxels::checkMemoryGrowth();
+  return id;
+}
+
+WASM_EXPORT(registerShapelessRecipe)
+int registerShapelessRecipe(const int* data, int size, int result, int count) {
+  const auto ingredients = readIngredients(data, size);
+  const auto id = recipes.addShapeless(ingredients, size, result, count);
+  voxels::checkMemoryGrowth();
+  return id;
+}
+
+WASM_EXPORT(matchRecipe)
+int matchRecipe(const int* grid, int width, int height) {
+  return recipes.match(items, grid, width, height);
+}
+
+WASM_EXPORT(recipeResult)
+const int* recipeResult(int id) {
+  // Returns the recipe's (item, count), or nullptr if there's no such recipe.
+  static std::array<int, 2> result;
+  const auto recipe = recipes.get(id);
+  if (!recipe) return nullptr;
+  result = {recipe->result, recipe->count};
+  return result.data();
+}
+
+WASM_EXPORT(registerItemMaxStack)
+void registerItemMaxStack(int item, int max) {
+  items.setMaxStack(item, max);
+  voxels::checkMemoryGrowth();
+}
+
+// Creates an empty inventory with the given number of slots, replacing any
+// old inventory with the same id.
+WASM_EXPORT(createInventory)
+void createInventory(int id, int size) {
+  inventories.insert_or_assign(id, voxels::Inventory(size));
+  voxels::checkMemoryGrowth();
+}
+
+WASM_EXPORT(removeInventory)
+bool removeInventory(int id) {
+  return inventories.erase(id) > 0;
+}
+
+WASM_EXPORT(getInventory)
+const int* getInventory(int id) {
+  // Returns the slot count, followed by (item, count) for each slot, or
+  // nullptr if there's no such inventory.
+  static std::vector<int> result;
+  const auto inventory = getInventoryById(id);
+  if (!inventory) return nullptr;
+
+  result.clear();
+  result.push_back(inventory->size());
+  for (auto i = 0; i < inventory->size(); i++) {
+    const auto& stack = inventory->get(i);
+    result.push_back(stack.item);
+    result.push_back(stack.count);
+  }
+  voxels::checkMemoryGrowth();
+  return result.data();
+}
+
+WASM_EXPORT(setInventorySlot)
+bool setInventorySlot(int id, int slot, int item, int count) {
+  const auto inventory = getInventoryById(id);
+  return inventory && inventory->set(items, slot, {item, count});
+}
+
+// Returns the count that didn't fit.
+WASM_EXPORT(addToInventory)
+int addToInventory(int id, int item, int count) {
+  const auto inventory = getInventoryById(id);
+  return inventory ? inventory->add(items, item, count) : count;
+}
+
+// Returns the count removed.
+WASM_EXPORT(removeFromInventory)
+int removeFromInventory(int id, int item, int count) {
+  const auto inventory = getInventoryById(id);
+  return inventory ? inventory->remove(item, count) : 0;
+}
+
+// Returns the count moved. See Inventory::move.
+WASM_EXPORT(moveInventoryItems)
+int moveInventoryItems(int from, int fromSlot, int to, int toSlot, int count) {
+  const auto source = getInventoryById(from);
+  const auto target = getInventoryById(to);
+  if (!source || !target) return 0;
+  return source->move(items, fromSlot, *target, toSlot, count);
+}
+
+WASM_EXPORT(splitInventoryStack)
+int splitInventoryStack(int id, int fromSlot, int toSlot) {
+  const auto inventory = getInventoryById(id);
+  return inventory ? inventory->split(fromSlot, toSlot) : 0;
+}
+
+WASM_EXPORT(encodeInventory)
+const uint8_t* encodeInventory(int id) {
+  const auto inventory = getInventoryById(id);
+  if (!inventory) return nullptr;
+  const auto result = voxels::encodeInventory(*inventory);
+  voxels::checkMemoryGrowth();
+  return result;
+}
+
+// Replaces the inventory with the packet's contents, creating it if needed.
+// Fails, without changing anything, if the packet is malformed or if it has
+// a stack larger than its item's max stack size.
+WASM_EXPORT(decodeInventory)
+bool decodeInventory(int id, const uint8_t* data, int size) {
+  static std::vector<voxels::ItemStack> slots;
+  if (!voxels::decodeInventory(data, size, slots)) return false;
+
+  auto inventory = voxels::Inventory(static_cast<int>(slots.size()));
+  for (auto i = 0; i < slots.size(); i++) {
+    if (!inventory.set(items, static_cast<int>(i), slots[i])) return false;
+  }
+  inventories.insert_or_assign(id, std::move(inventory));
+  voxels::checkMemoryGrowth();
+  return true;
+}
+
+WASM_EXPORT(registerLootPool)
+int registerLootPool(int table, int minRolls, int maxRolls, double chance,
+                     int tool, int minTier, int minFortune, uint32_t flags) {
+  const auto condition =
+      voxels::LootCondition{tool, minTier, minFortune, flags};
+  const auto pool =
+      loot_tables.addPool(table, minRolls, maxRolls, chance, condition);
+  voxels::checkMemoryGrowth();
+  return pool;
+}
+
+WASM_EXPORT(registerLootEntry)
+void registerLootEntry(int table, int pool, int item, int weight, int min,
+                       int max, int fortuneBonus, int tool, int minTier,
+                       int minFortune, uint32_t flags) {
+  const auto condition =
+      voxels::LootCondition{tool, minTier, minFortune, flags};
+  loot_tables.addEntry(
+      table, pool, {item, weight, min, max, fortuneBonus, condition});
+  voxels::checkMemoryGrowth();
+}
+
+WASM_EXPORT(rollLoot)
+const int* rollLoot(int table, const int* context, uint32_t seed) {
+  // The context is (tool, tier, fortune, flags) as int32s. Returns a count,
+  // followed by (item, count) for each stack, like dropsFor.
+  static std::vector<voxels::DropRecord> drops;
+  static std::vector<int> result;
+
+  const auto flags = static_cast<uint32_t>(context[3]);
+  loot_tables.roll(table, {context[0], context[1], context[2], flags},
+                   seed, drops);
+
+  result.clear();
+  result.push_back(static_cast<int>(drops.size()));
+  for (const auto& drop : drops) {
+    result.push_back(drop.item);
+    result.push_back(drop.count);
+  }
+  voxels::checkMemoryGrowth();
+  return result.data();
+}
+
 WASM_EXPORT(registerMaterial)
 void registerMaterial(int material, bool liquid, bool alphaTest, int texture,
-                      double r, double g, double b, double a) {
+                      double r, double g, double b, double a,
+                      int uvTransform, bool fullBright) {
   using voxels::safe_cast;
 
   assert(world);
   world->mutableRegistry().addMaterial({safe_cast<uint8_t>(material)}, {
     liquid, alphaTest, safe_cast<uint8_t>(texture), {r, g, b, a},
+    safe_cast<uint8_t>(uvTransform), fullBright,
   });
 }
+
+// Recordings start with the tick loop's settings, so that replays run the
+// same ticks even if the host set them up before recording.
+WASM_EXPORT(startRecording)
+void startRecording() {
+  auto& loop = tick_loop;
+  loop.accumulator = 0;
+  replay_log.start();
+  const auto tick_length = static_cast<int32_t>(loop.tick_length);
+  replay_log.record(voxels::ReplayOp::SetTickRate,
+                    {tick_length, loop.max_ticks});
+  replay_log.record(voxels::ReplayOp::SetRaining, {loop.raining});
+}
+
+// Stops recording and returns the stream: a uint32_t byte count, followed by
+// the bytes. See replay.h for the format.
+WASM_EXPORT(stopRecording)
+const uint8_t* stopRecording() {
+  replay_log.stop();
+  voxels::checkMemoryGrowth();
+  return replay_log.data();
+}
+
+// Runs each command in a recorded stream through the same exports that
+// recorded it, so if we're recording, the new stream includes them, too.
+// Returns the number of commands, or -1 if the stream is malformed, in which
+// case we don't run any of them.
+WASM_EXPORT(replay)
+int replay(const uint8_t* data, int size) {
+  using voxels::ReplayOp;
+
+  assert(world);
+  static std::vector<voxels::ReplayCommand> commands;
+  if (!voxels::decodeReplay(data, size, commands)) return -1;
+
+  tick_loop.accumulator = 0;
+  for (const auto& [op, tick, args, payload] : commands) {
+    const auto a = args.data();
+    switch (op) {
+      case ReplayOp::Tick: tickWorld(); break;
+      case ReplayOp::Recenter: recenterWorld(a[0], a[1]); break;
+      case ReplayOp::Remesh: remeshWorld(); break;
+      case ReplayOp::SetBlock: setBlock(a[0], a[1], a[2], a[3]); break;
+      case ReplayOp::FillBlocks:
+        fillBlocks(a[0], a[1], a[2], a[3], a[4], a[5], a[6], a[7] != 0);
+        break;
+      case ReplayOp::SetPointLight:
+        setPointLight(a[0], a[1], a[2], a[3]);
+        break;
+      case ReplayOp::PushBlocks:
+        pushBlocks(a[0], a[1], a[2], a[3], a[4]);
+        break;
+      case ReplayOp::GrowTree:
+        growTree(a[0], a[1], a[2], a[3], static_cast<uint32_t>(a[4]));
+        break;
+      case ReplayOp::TickFire: tickFire(a[0], a[1], a[2] != 0); break;
+      case ReplayOp::ProtectRegion:
+        addProtectedRegion(a[0], a[1], a[2], a[3], a[4], a[5], a[6]);
+        break;
+      case ReplayOp::UnprotectRegions: removeProtectedRegions(a[0]); break;
+      case ReplayOp::SetEditor: setEditor(a[0]); break;
+      case ReplayOp::PlayerEdit:
+        applyPlayerEdit(a[0], a[1], a[2], a[3], a[4]);
+        break;
+      case ReplayOp::SetDynamicLight:
+        setDynamicLight(a[0], a[1], a[2], a[3], a[4]);
+        break;
+      case ReplayOp::Explode: {
+        auto power = 0.0f;
+        memcpy(&power, &a[3], sizeof(power));
+        explode(a[0], a[1], a[2], power, static_cast<uint32_t>(a[4]));
+        break;
+      }
+      case ReplayOp::SetDimension: setActiveDimension(a[0]); break;
+      case ReplayOp::RemoveDimension: removeDimension(a[0]); break;
+      case ReplayOp::Advance: advanceWorld(a[0] / 1000.0); break;
+      case ReplayOp::Snapshot: snapshotWorld(); break;
+      case ReplayOp::Rollback: rollbackWorld(a[0]); break;
+      case ReplayOp::CopyToClipboard:
+        copyToClipboard(a[0], a[1], a[2], a[3], a[4], a[5], a[6]);
+        break;
+      case ReplayOp::CreateClipboardShape:
+        createClipboardShape(a[0], a[1], a[2], a[3], a[4], a[5], a[6]);
+        break;
+      case ReplayOp::ApplyCsg: applyCsg(a[0], a[1], a[2], a[3], a[4]); break;
+      case ReplayOp::SetSymmetry:
+        setSymmetry(a[0], a[1], a[2], a[3], a[4], a[5], a[6]);
+        break;
+      case ReplayOp::SetSelection:
+        setSelection(a[0], a[1], a[2], a[3], a[4], a[5]);
+        break;
+      case ReplayOp::ClearSelection: clearSelection(); break;
+      case ReplayOp::ExpandSelection: expandSelection(a[0], a[1]); break;
+      case ReplayOp::ShiftSelection: shiftSelection(a[0], a[1], a[2]); break;
+      case ReplayOp::SetSelectionMask: setSelectionMask(a[0] != 0); break;
+      case ReplayOp::ReplaceInSelection:
+        replaceInSelection(a[0], a[1], a[2] != 0);
+        break;
+      case ReplayOp::AddProjectile: {
+        double v[6];
+        memcpy(v, payload.data(), sizeof(v));
+        addProjectile(a[0], a[1], v[0], v[1], v[2], v[3], v[4], v[5]);
+        break;
+      }
+      case ReplayOp::RemoveProjectile: removeProjectile(a[0]); break;
+      case ReplayOp::StepProjectiles: {
+        auto dt = 0.0;
+        memcpy(&dt, payload.data(), sizeof(dt));
+        stepProjectiles(dt);
+        break;
+      }
+      case ReplayOp::SetChunkExtra: {
+        const auto size = static_cast<int>(payload.size());
+        setChunkExtra(a[0], a[1], payload.data(), size);
+        break;
+      }
+      case ReplayOp::CreateBlockEntity: {
+        const auto size = static_cast<int>(payload.size());
+        createBlockEntity(a[0], a[1], a[2], a[3], payload.data(), size);
+        break;
+      }
+      case ReplayOp::RemoveBlockEntity:
+        removeBlockEntity(a[0], a[1], a[2]);
+        break;
+      case ReplayOp::AddItemDrop: {
+        double v[6];
+        memcpy(v, payload.data(), sizeof(v));
+        addItemDrop(a[0], a[1], v[0], v[1], v[2], v[3], v[4], v[5]);
+        break;
+      }
+      case ReplayOp::RemoveItemDrop: removeItemDrop(a[0]); break;
+      case ReplayOp::SetItemDropRules: {
+        double v[7];
+        memcpy(v, payload.data(), sizeof(v));
+        setItemDropRules(v[0], v[1], v[2], v[3], v[4], v[5], v[6]);
+        break;
+      }
+      case ReplayOp::SetItemMagnets: {
+        static std::vector<double> positions;
+        const auto count = payload.size() / (3 * sizeof(double));
+        positions.resize(3 * count);
+        memcpy(positions.data(), payload.data(), 3 * count * sizeof(double));
+        setItemMagnets(positions.data(), static_cast<int>(count));
+        break;
+      }
+      case ReplayOp::StepItemDrops: {
+        auto dt = 0.0;
+        memcpy(&dt, payload.data(), sizeof(dt));
+        stepItemDrops(dt);
+        break;
+      }
+      case ReplayOp::PickUpItemDrops: {
+        double v[4];
+        memcpy(v, payload.data(), sizeof(v));
+        pickUpItemDrops(a[0], v[0], v[1], v[2], v[3]);
+        break;
+      }
+      case ReplayOp::SetTickRate: setTickRate(1e6 / a[0], a[1]); break;
+      case ReplayOp::SetRaining: setRaining(a[0] != 0); break;
+      case ReplayOp::VoxelizeMesh: {
+        static std::vector<float> vertices;
+        static std::vector<uint32_t> indices;
+        const auto nv = static_cast<size_t>(std::max(a[0], 0));
+        const auto ni = static_cast<size_t>(std::max(a[1], 0));
+        const auto vertex_bytes = 3 * sizeof(float) * nv;
+        const auto index_bytes = sizeof(uint32_t) * ni;
+        // We skip commands whose payload doesn't match their counts.
+        auto resolution = 0.0;
+        if (payload.size() != sizeof(resolution) + vertex_bytes + index_bytes) {
+          break;
+        }
+        vertices.resize(3 * nv);
+        indices.resize(ni);
+        auto cur = payload.data();
+        memcpy(&resolution, cur, sizeof(resolution));
+        cur += sizeof(resolution);
+        memcpy(vertices.data(), cur, vertex_bytes);
+        memcpy(indices.data(), cur + vertex_bytes, index_bytes);
+        voxelizeMesh(vertices.data(), indices.data(), a[0], a[1], a[2],
+                     resolution, a[3] != 0, a[4], a[5], a[6]);
+        break;
+      }
+      case ReplayOp::CarvePath: {
+        static std::vector<double> points;
+        double size[2];
+        if (payload.size() < sizeof(size)) break;
+        memcpy(size, payload.data(), sizeof(size));
+        const auto count = (payload.size() - sizeof(size)) / sizeof(double) / 3;
+        points.resize(3 * count);
+        memcpy(points.data(), payload.data() + sizeof(size),
+               points.size() * sizeof(double));
+        carvePath(points.data(), static_cast<int>(count), a[0], size[0],
+                  size[1], a[1] != 0, a[2]);
+        break;
+      }
+      case ReplayOp::ImportHeightmap: {
+        static std::vector<uint16_t> samples;
+        static std::vector<int> table;
+        const auto w = static_cast<size_t>(std::max(a[0], 0));
+        const auto area = w * static_cast<size_t>(std::max(a[1], 0));
+        const auto layers = static_cast<size_t>(std::max(a[2], 0));
+        const auto table_bytes = 2 * sizeof(int) * layers;
+        const auto sample_bytes = sizeof(uint16_t) * area;
+        auto scale = 0.0;
+        if (payload.size() != sizeof(scale) + table_bytes + sample_bytes) {
+          break;
+        }
+        table.resize(2 * layers);
+        samples.resize(area);
+        auto cur = payload.data();
+        memcpy(&scale, cur, sizeof(scale));
+        cur += sizeof(scale);
+        memcpy(table.data(), cur, table_bytes);
+        memcpy(samples.data(), cur + table_bytes, sample_bytes);
+        importHeightmap(samples.data(), a[0], a[1], scale, table.data(),
+                        a[2], a[3], a[4]);
+        break;
+      }
+      case ReplayOp::ImportLayerPng: {
+        static std::vector<int> table;
+        const auto colors = static_cast<size_t>(std::max(a[0], 0));
+        const auto table_bytes = 2 * sizeof(int) * colors;
+        if (payload.size() < table_bytes) break;
+        table.resize(2 * colors);
+        memcpy(table.data(), payload.data(), table_bytes);
+        const auto size = payload.size() - table_bytes;
+        importLayerPng(payload.data() + table_bytes, static_cast<int>(size),
+                       table.data(), a[0], a[1], a[2], a[3]);
+        break;
+      }
+      case ReplayOp::SetSkyDarkness:
+        if (!(0 <= a[0] && a[0] <= voxels::kSunlightLevel)) break;
+        setSkyDarkness(a[0]);
+        break;
+      case ReplayOp::LoadSlice: {
+        const std::string text(payload.begin(), payload.end());
+        loadSlice(a[0], a[1], text.c_str());
+        break;
+      }
+      case ReplayOp::PropagatePower: {
+        static std::vector<int> positions;
+        const auto count = static_cast<size_t>(std::max(a[0], 0));
+        if (payload.size() != 3 * sizeof(int) * count) break;
+        positions.resize(3 * count);
+        memcpy(positions.data(), payload.data(), payload.size());
+        propagatePower(positions.data(), a[0]);
+        break;
+      }
+      case ReplayOp::SetWorldWrap: setWorldWrap(a[0]); break;
+      case ReplayOp::SetWorldSeed:
+        setWorldSeed(static_cast<uint32_t>(a[0]));
+        break;
+      case ReplayOp::DecodeSnapshot: {
+        const auto size = static_cast<int>(payload.size());
+        decodeWorldSnapshot(payload.data(), size);
+        break;
+      }
+      case ReplayOp::Count: assert(false);
+    }
+  }
+  return static_cast<int>(commands.size());
+}
@@ -0,0 +1,25 @@
+#include "events.h"
+
+//////////////////////////////////////////////////////////////////////////////
+
+namespace voxels {
+
+//////////////////////////////////////////////////////////////////////////////
+
+const int32_t* EventQueue::drain() {
+  result.assign({size, dropped});
+  for (auto i = 0; i < size; i++) {
+    const auto& event = ring[(start + i) % kMaxEvents];
+    result.insert(result.end(), {static_cast<int32_t>(event.type),
+                                 event.x, event.y, event.z,
+                                 event.a, event.b});
+  }
+  start = 0;
+  size = 0;
+  dropped = 0;
+  return result.data();
+}
+
+//////////////////////////////////////////////////////////////////////////////
+
+} // namespace voxels
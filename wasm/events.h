@@ -0,0 +1,91 @@
+#pragma once
+
+#include <cstdint>
+#include <vector>
+
+#include "base.h"
+
+//////////////////////////////////////////////////////////////////////////////
+
+namespace voxels {
+
+//////////////////////////////////////////////////////////////////////////////
+
+// A queue of engine-to-host notifications, which the host drains once per
+// frame. The queue is a ring buffer of kMaxEvents; if it fills up between
+// drains, we drop the oldest events and report how many we dropped.
+//
+// Each event has a position and two type-specific values, a and b:
+//   - BlockChanged:  (x, y, z), a = the old block, b = the new block.
+//   - ChunkMeshed:   (cx, 0, cz).
+//   - LightUpdated:  (cx, 0, cz).
+//   - EntityLanded:  (x, y, z), a = the entity, b = its impact speed.
+//   - FluidFlowed:   (x, y, z), a = the fluid block.
+//   - EditRejected:  (x, y, z), the edit's min corner, a = the protected
+//                    region's owner, b = the editor. See World::checkEdit.
+//   - ProjectileHit: (x, y, z), a = the projectile, b = the solid block
+//                    that it entered. See World::stepProjectiles.
+//   - PlayerEdit:    (x, y, z), a = the player, b = the EditVerdict, which
+//                    is 0 if we applied it. See World::tryPlayerEdit.
+//   - TriggerEnter,
+//     TriggerStay,
+//     TriggerExit:   (x, y, z), the trigger's voxel, a = the entity, b = the
+//                    trigger type. See World::stepTriggers.
+//
+// The host raises EntityLanded and FluidFlowed events, because entity
+// physics and fluid flow run on the host.
+
+enum class EventType : int32_t {
+  BlockChanged = 1,
+  ChunkMeshed,
+  LightUpdated,
+  EntityLanded,
+  FluidFlowed,
+  EditRejected,
+  ProjectileHit,
+  PlayerEdit,
+  TriggerEnter,
+  TriggerStay,
+  TriggerExit,
+};
+
+struct Event {
+  EventType type;
+  int32_t x, y, z;
+  int32_t a, b;
+};
+
+static_assert(sizeof(Event) == 6 * sizeof(int32_t));
+
+constexpr int kMaxEvents = 1 << 12;
+
+struct EventQueue {
+  EventQueue() = default;
+
+  void push(const Event& event) {
+    if (size == kMaxEvents) {
+      start = (start + 1) % kMaxEvents;
+      size--;
+      dropped++;
+    }
+    ring[(start + size) % kMaxEvents] = event;
+    size++;
+  }
+
+  // Returns a count and the number of dropped events, followed by the six
+  // int32 fields of each event, oldest first. Clears the queue.
+  const int32_t* drain();
+
+ private:
+  int start = 0;
+  int size = 0;
+  int dropped = 0;
+  std::vector<int32_t> result;
+  NonCopyArray<Event, kMaxEvents> ring;
+
+  DISALLOW_COPY_AND_ASSIGN(EventQueue);
+};
+
+//////////////////////////////////////////////////////////////////////////////
+
+} // namespace voxels
@@ -0,0 +1,41 @@
+#include "fire.h"
+
+#include "mesher.h"
+
+//////////////////////////////////////////////////////////////////////////////
+
+namespace voxels {
+
+//////////////////////////////////////////////////////////////////////////////
+
+void tickFire(BlockAccess& world, const Registry& registry,
+              const std::vector<BurningVoxel>& fires, bool raining,
+              Random& rng, std::vector<BlockChange>& result) {
+  const auto fire = registry.getFireBlock();
+  if (fire == Block::Air) return;
+
+  for (const auto& [x, y, z, exposed] : fires) {
+    auto fuel = false;
+    auto water = raining && exposed;
+    for (const auto& [dx, dy, dz] : kFaceNeighbors) {
+      const auto block = world.getBlock(x + dx, y + dy, z + dz);
+      if (block == Block::Water) water = true;
+
+      const auto& data = registry.getFire(block);
+      if (data.ignite > 0 && rng.uniform() < data.ignite) {
+        result.push_back({x + dx, y + dy, z + dz, fire});
+      } else if (data.burn > 0 && rng.uniform() < data.burn) {
+        result.push_back({x + dx, y + dy, z + dz, Block::Air});
+      }
+      if (data.burn > 0) fuel = true;
+    }
+
+    if (water || (!fuel && rng.uniform() < kFireBurnOutChance)) {
+      result.push_back({x, y, z, Block::Air});
+    }
+  }
+}
+
+//////////////////////////////////////////////////////////////////////////////
+
+} // namespace voxels
@@ -0,0 +1,45 @@
+#pragma once
+
+#include <cstdint>
+#include <vector>
+
+#include "access.h"
+#include "base.h"
+
+//////////////////////////////////////////////////////////////////////////////
+
+namespace voxels {
+
+//////////////////////////////////////////////////////////////////////////////
+
+// Fire: the registry's fire block, if it has one, spreads to and burns away
+// flammable neighbors, burns out without fuel, and goes out next to water or
+// in the rain. Chunks track their burning voxels, so a tick only visits
+// those. See Registry::setFire and Registry::setFireBlock.
+
+struct Registry;
+
+// Each tick, a fire spreads into each adjacent block with its ignite chance,
+// and burns away each adjacent block with its burn chance. Blocks with zero
+// chances are non-flammable.
+struct FireData {
+  double ignite = 0.0;
+  double burn = 0.0;
+};
+
+// The chance that a fire with no flammable neighbors burns out in a tick.
+constexpr double kFireBurnOutChance = 0.25;
+
+// A burning voxel, and whether the rain can reach it.
+struct BurningVoxel { int x; int y; int z; bool exposed; };
+
+// Advances the given fires by one tick, appending the blocks to change to
+// the result. The same voxel may appear more than once; the last change to
+// it wins.
+void tickFire(BlockAccess& world, const Registry& registry,
+              const std::vector<BurningVoxel>& fires, bool raining,
+              Random& rng, std::vector<BlockChange>& result);
+
+//////////////////////////////////////////////////////////////////////////////
+
+} // namespace voxels
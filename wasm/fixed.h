@@ -0,0 +1,124 @@
+#pragma once
+
+#include <cstdint>
+
+#include "base.h"
+
+//////////////////////////////////////////////////////////////////////////////
+
+namespace voxels {
+
+//////////////////////////////////////////////////////////////////////////////
+
+// Deterministic fixed-point math, for lockstep simulation. Floating-point
+// results can differ across wasm and native x86 or ARM builds (fused
+// multiply-adds, libm implementations), but integer math never does.
+//
+// A Fixed is a signed 64-bit value with kFixedBits fractional bits. Building
+// with -DVOXELS_FIXED_POINT switches worldgen to this type; see Real below.
+//
+// Math functions are hidden friends, so they're only found by ADL and don't
+// hide the <cmath> overloads for doubles.
+
+constexpr int kFixedBits = 16;
+
+struct Fixed {
+  constexpr Fixed() = default;
+  constexpr Fixed(int value) : raw(static_cast<int64_t>(value) << kFixedBits) {}
+
+  // Only use this conversion for constants: its result is deterministic, but
+  // the double that it's called with might not be.
+  constexpr explicit Fixed(double value)
+      : raw(static_cast<int64_t>(value * (1 << kFixedBits) +
+                                 (value < 0 ? -0.5 : 0.5))) {}
+
+  static constexpr Fixed fromRaw(int64_t raw) {
+    auto result = Fixed{};
+    result.raw = raw;
+    return result;
+  }
+
+  // Truncates toward zero, like a cast from a double.
+  constexpr explicit operator int() const {
+    const auto whole = (raw < 0 ? -raw : raw) >> kFixedBits;
+    return static_cast<int>(raw < 0 ? -whole : whole);
+  }
+
+  constexpr double toDouble() const {
+    return static_cast<double>(raw) / (1 << kFixedBits);
+  }
+
+  constexpr Fixed operator-() const { return fromRaw(-raw); }
+
+  friend constexpr Fixed operator+(Fixed a, Fixed b) {
+    return fromRaw(a.raw + b.raw);
+  }
+  friend constexpr Fixed operator-(Fixed a, Fixed b) {
+    return fromRaw(a.raw - b.raw);
+  }
+  friend constexpr Fixed operator*(Fixed a, Fixed b) {
+    return fromRaw((a.raw * b.raw) >> kFixedBits);
+  }
+  friend constexpr Fixed operator/(Fixed a, Fixed b) {
+    assert(b.raw != 0);
+    return fromRaw((a.raw << kFixedBits) / b.raw);
+  }
+
+  Fixed& operator+=(Fixed o) { return *this = *this + o; }
+  Fixed& operator-=(Fixed o) { return *this = *this - o; }
+  Fixed& operator*=(Fixed o) { return *this = *this * o; }
+  Fixed& operator/=(Fixed o) { return *this = *this / o; }
+
+  friend constexpr bool operator==(Fixed a, Fixed b) { return a.raw == b.raw; }
+  friend constexpr bool operator!=(Fixed a, Fixed b) { return a.raw != b.raw; }
+  friend constexpr bool operator< (Fixed a, Fixed b) { return a.raw <  b.raw; }
+  friend constexpr bool operator<=(Fixed a, Fixed b) { return a.raw <= b.raw; }
+  friend constexpr bool operator> (Fixed a, Fixed b) { return a.raw >  b.raw; }
+  friend constexpr bool operator>=(Fixed a, Fixed b) { return a.raw >= b.raw; }
+
+  friend constexpr Fixed abs(Fixed x) {
+    return fromRaw(x.raw < 0 ? -x.raw : x.raw);
+  }
+
+  friend constexpr Fixed floor(Fixed x) {
+    constexpr auto mask = (int64_t{1} << kFixedBits) - 1;
+    return fromRaw(x.raw & ~mask);
+  }
+
+  friend constexpr Fixed fmax(Fixed a, Fixed b) {
+    return a.raw < b.raw ? b : a;
+  }
+
+  // Returns 0 for negative inputs, where the double version returns NaN.
+  friend constexpr Fixed sqrt(Fixed x) {
+    if (x.raw <= 0) return Fixed{};
+    auto value = static_cast<uint64_t>(x.raw) << kFixedBits;
+    auto bit = uint64_t{1} << 62;
+    while (bit > value) bit >>= 2;
+
+    auto result = uint64_t{0};
+    for (; bit != 0; bit >>= 2) {
+      if (value >= result + bit) {
+        value -= result + bit;
+        result = (result >> 1) + bit;
+      } else {
+        result >>= 1;
+      }
+    }
+    return fromRaw(static_cast<int64_t>(result));
+  }
+
+  int64_t raw = 0;
+};
+
+// The scalar type for code that must be deterministic when built with
+// VOXELS_FIXED_POINT. Without it, it's a double, and results are unchanged.
+#ifdef VOXELS_FIXED_POINT
+using Real = Fixed;
+#else
+using Real = double;
+#endif
+
+//////////////////////////////////////////////////////////////////////////////
+
+} // namespace voxels
@@ -0,0 +1,159 @@
+#include "fixtures.h"
+
+#include <iterator>
+
+//////////////////////////////////////////////////////////////////////////////
+
+namespace voxels {
+
+//////////////////////////////////////////////////////////////////////////////
+
+namespace {
+
+// Fixtures only use levels below this height, to keep meshing them cheap.
+constexpr int kFixtureHeight = 32;
+
+struct FixtureBlocks {
+  Block opaque = Block::Air;
+  Block alpha_test = Block::Air;
+  Block liquid = Block::Air;
+};
+
+FixtureBlocks getFixtureBlocks(const Registry& registry) {
+  FixtureBlocks result;
+  auto found_opaque = false, found_alpha_test = false, found_liquid = false;
+
+  for (auto i = 1; i < registry.getNumBlocks(); i++) {
+    const auto block = static_cast<Block>(i);
+    const auto& data = registry.getBlock(block);
+    if (!data.mesh) continue;
+    if (data.opaque) {
+      if (!found_opaque) result.opaque = block;
+      found_opaque = true;
+      continue;
+    }
+    const auto face = data.faces[0];
+    if (face == kNoMaterial) continue;
+    const auto& material = registry.getMaterial(assertMaterial(face));
+    if (material.liquid && !found_liquid) {
+      result.liquid = block;
+      found_liquid = true;
+    } else if (material.alphaTest && !found_alpha_test) {
+      result.alpha_test = block;
+      found_alpha_test = true;
+    }
+  }
+  return result;
+}
+
+} // namespace
+
+//////////////////////////////////////////////////////////////////////////////
+
+void loadMeshFixture(Mesher& mesher, const Registry& registry,
+                     MeshFixture fixture) {
+  const auto [opaque, alpha_test, liquid] = getFixtureBlocks(registry);
+
+  // Mesher coordinates include a 1-voxel border, so chunk voxels are at
+  // [1, kChunkWidth] in x and z. We leave the synthetic y border alone.
+  auto& voxels = mesher.voxels;
+  const auto width = static_cast<int>(voxels.shape[0]);
+  const auto set = [&](int x, int y, int z, Block block) {
+    voxels.set(x + 1, y + 1, z + 1, block);
+  };
+  voxels.fillBox(0, 1, 0, width, kWorldHeight + 1, width, Block::Air);
+  mesher.face_mask = kAllFaces;
+  mesher.origin = {0, 0, 0};
+
+  switch (fixture) {
+    case MeshFixture::Single: {
+      set(7, 4, 7, opaque);
+      break;
+    }
+    case MeshFixture::Slab: {
+      for (auto x = 2; x < 10; x++) {
+        for (auto z = 3; z < 7; z++) set(x, 2, z, opaque);
+      }
+      break;
+    }
+    case MeshFixture::Checker: {
+      for (auto x = 0; x < kChunkWidth; x++) {
+        for (auto y = 0; y < 8; y++) {
+          for (auto z = 0; z < kChunkWidth; z++) {
+            if ((x + y + z) % 2 == 0) set(x, y, z, opaque);
+          }
+        }
+      }
+      break;
+    }
+    case MeshFixture::Pool: {
+      // A basin with one wall missing, so the water has exposed sides, and
+      // with a block above the surface, which patches the surface quads.
+      for (auto x = 3; x < 12; x++) {
+        for (auto z = 3; z < 12; z++) {
+          set(x, 2, z, opaque);
+          const auto wall = x == 3 || x == 11 || z == 11;
+          for (auto y = 3; y < 6; y++) {
+            set(x, y, z, wall ? opaque : y < 5 ? liquid : Block::Air);
+          }
+        }
+      }
+      set(6, 5, 6, opaque);
+      break;
+    }
+    case MeshFixture::Leaves: {
+      for (auto x = 4; x < 9; x++) {
+        for (auto y = 4; y < 8; y++) {
+          for (auto z = 4; z < 9; z++) set(x, y, z, alpha_test);
+        }
+      }
+      for (auto y = 0; y < 6; y++) set(6, y, 6, opaque);
+      break;
+    }
+    case MeshFixture::Border: {
+      // Blocks on each chunk edge, and neighboring blocks in the border.
+      for (auto i = 0; i < kChunkWidth; i += 3) {
+        set(0, 1, i, opaque);
+        set(kChunkWidth - 1, 2, i, liquid);
+        set(i, 3, 0, alpha_test);
+        set(i, 4, kChunkWidth - 1, opaque);
+        set(-1, 1, i + 1, opaque);
+        set(kChunkWidth, 2, i + 1, opaque);
+        set(i + 1, 3, -1, liquid);
+        set(i + 1, 4, kChunkWidth, alpha_test);
+      }
+      break;
+    }
+    case MeshFixture::Noise: {
+      const Block choices[] = {Block::Air, Block::Air, opaque, alpha_test, liquid};
+      auto rng = Random(17);
+      for (auto x = -1; x <= kChunkWidth; x++) {
+        for (auto y = 0; y < kFixtureHeight; y++) {
+          for (auto z = -1; z <= kChunkWidth; z++) {
+            set(x, y, z, choices[rng.next() % std::size(choices)]);
+          }
+        }
+      }
+      break;
+    }
+    case MeshFixture::Count: assert(false);
+  }
+
+  // Disable the heightmap and equilevels optimizations: fixtures are small.
+  mesher.heightmap.data.fill(kFixtureHeight);
+  for (auto y = 1; y <= kWorldHeight; y++) mesher.equilevels[y] = 0;
+}
+
+void checkMeshFixtures(Mesher& mesher, const Registry& registry,
+                       MeshReport& report) {
+  constexpr auto count = static_cast<int>(MeshFixture::Count);
+  for (auto i = 0; i < count; i++) {
+    loadMeshFixture(mesher, registry, static_cast<MeshFixture>(i));
+    mesher.meshChunk(0, kFixtureHeight);
+    mesher.validateGeometry(0, kFixtureHeight, report);
+  }
+}
+
+//////////////////////////////////////////////////////////////////////////////
+
+} // namespace voxels
@@ -0,0 +1,35 @@
+#pragma once
+
+#include "base.h"
+#include "mesher.h"
+
+//////////////////////////////////////////////////////////////////////////////
+
+namespace voxels {
+
+//////////////////////////////////////////////////////////////////////////////
+
+// Golden-path chunks for the mesher: small, hand-built voxel layouts that
+// cover its tricky cases (merging, liquids, alpha-tested faces, and faces
+// on the chunk border). We check each one by comparing meshChunk's output
+// against meshReference's, so forks that change the mesher can keep using
+// them as a safety net.
+//
+// Fixtures use the first registered opaque, alpha-tested, and liquid blocks.
+// If one of these kinds isn't registered, the fixtures use air instead.
+
+enum class MeshFixture {
+  Single, Slab, Checker, Pool, Leaves, Border, Noise, Count };
+
+// Replaces the mesher's inputs with the given fixture.
+void loadMeshFixture(Mesher& mesher, const Registry& registry,
+                     MeshFixture fixture);
+
+// Loads each fixture in turn, meshes it, and validates its geometry, adding
+// problems to the report. Overwrites the mesher's inputs and outputs.
+void checkMeshFixtures(Mesher& mesher, const Registry& registry,
+                       MeshReport& report);
+
+//////////////////////////////////////////////////////////////////////////////
+
+} // namespace voxels
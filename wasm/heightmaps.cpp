@@ -0,0 +1,37 @@
+#include "heightmaps.h"
+
+#include <algorithm>
+#include <cassert>
+#include <cmath>
+
+//////////////////////////////////////////////////////////////////////////////
+
+namespace voxels {
+
+//////////////////////////////////////////////////////////////////////////////
+
+int heightmapColumnHeight(uint16_t sample, double scale) {
+  const auto height = std::round(sample * scale);
+  if (!(height > 0)) return 0;
+  return static_cast<int>(std::min(height, 1.0 * kWorldHeight));
+}
+
+uint16_t heightmapSample(int height, double scale) {
+  if (!(scale > 0)) return 0;
+  const auto sample = std::round(height / scale);
+  return static_cast<uint16_t>(std::clamp(sample, 0.0, 65535.0));
+}
+
+Block heightmapLayerBlock(const std::vector<HeightmapLayer>& layers,
+                          int depth) {
+  assert(!layers.empty());
+  for (size_t i = 0; i + 1 < layers.size(); i++) {
+    depth -= layers[i].depth;
+    if (depth < 0) return layers[i].block;
+  }
+  return layers.back().block;
+}
+
+//////////////////////////////////////////////////////////////////////////////
+
+} // namespace voxels
@@ -0,0 +1,49 @@
+#pragma once
+
+#include <cstdint>
+#include <vector>
+
+#include "base.h"
+
+//////////////////////////////////////////////////////////////////////////////
+
+namespace voxels {
+
+//////////////////////////////////////////////////////////////////////////////
+
+// Import and export of 16-bit heightmaps, so that artists can round-trip
+// terrain through tools like World Machine or Gaea. Samples are row-major:
+// the sample for column (x + i, z + j) is at index i + w * j.
+//
+// A sample s means a column with round(s * scale) solid voxels, clamped to
+// the build height, so its top voxel is at y = height - 1. Exporting takes
+// the height of each column's top solid voxel and divides by the scale, so
+// importing and then exporting at the same scale returns the same samples,
+// up to clamping and rounding.
+//
+// Imports fill each column from the top down with a table of layers, such
+// as one voxel of grass over three of dirt over stone. Each layer is `depth`
+// voxels deep, except the last, which extends down to the bottom.
+
+constexpr int kMaxHeightmapSize = 1024;
+
+struct HeightmapLayer {
+  int depth;
+  Block block;
+};
+
+// Returns the number of solid voxels in the column for a sample, clamped to
+// kWorldHeight. Callers clamp it further to the build height.
+int heightmapColumnHeight(uint16_t sample, double scale);
+
+// Returns the sample for a column with `height` solid voxels.
+uint16_t heightmapSample(int height, double scale);
+
+// Returns the block `depth` voxels below the top of a column, where the top
+// voxel is at depth 0. The layers must not be empty.
+Block heightmapLayerBlock(const std::vector<HeightmapLayer>& layers,
+                          int depth);
+
+//////////////////////////////////////////////////////////////////////////////
+
+} // namespace voxels
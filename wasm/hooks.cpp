@@ -0,0 +1,23 @@
+#include "hooks.h"
+
+#include "emscripten.h"
+
+//////////////////////////////////////////////////////////////////////////////
+
+namespace voxels {
+
+//////////////////////////////////////////////////////////////////////////////
+
+EM_JS(void, js_CallBlockHook,
+      (int hook, int block, int x, int y, int z, int context),
+      { throw new Error(); });
+
+void callBlockHook(BlockHook hook, Block block,
+                   int x, int y, int z, int context) {
+  js_CallBlockHook(static_cast<int>(hook), static_cast<int>(block),
+                   x, y, z, context);
+}
+
+//////////////////////////////////////////////////////////////////////////////
+
+} // namespace voxels
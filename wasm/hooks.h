@@ -0,0 +1,37 @@
+#pragma once
+
+#include <cstdint>
+
+#include "base.h"
+
+//////////////////////////////////////////////////////////////////////////////
+
+namespace voxels {
+
+//////////////////////////////////////////////////////////////////////////////
+
+// Host callbacks for custom block behavior. Blocks opt in to each hook with
+// a bitmask in the registry, so that our hot loops only call out to the host
+// for blocks that have custom behavior.
+//
+// Each hook is called with the block, its position, and a context value:
+//   - Place: the block that was replaced.
+//   - Break: the block that replaced it.
+//   - RandomTick: unused (0).
+//   - EntityCollide: the colliding entity's id. The host's physics raises
+//     this hook itself, so we never call it from the engine.
+//
+// Hooks may edit the world; edits made by a hook may trigger other hooks.
+
+enum class BlockHook : uint8_t { Place, Break, RandomTick, EntityCollide };
+
+constexpr uint8_t hookBit(BlockHook hook) {
+  return static_cast<uint8_t>(1 << static_cast<int>(hook));
+}
+
+void callBlockHook(BlockHook hook, Block block,
+                   int x, int y, int z, int context);
+
+//////////////////////////////////////////////////////////////////////////////
+
+} // namespace voxels
@@ -0,0 +1,118 @@
+#include "inventory.h"
+
+#include <algorithm>
+
+//////////////////////////////////////////////////////////////////////////////
+
+namespace voxels {
+
+//////////////////////////////////////////////////////////////////////////////
+
+Inventory::Inventory(int size) : slots(std::max(size, 0)) {}
+
+const ItemStack& Inventory::get(int slot) const {
+  assert(valid(slot));
+  return slots[slot];
+}
+
+bool Inventory::set(const ItemRegistry& items, int slot,
+                    const ItemStack& stack) {
+  if (!valid(slot)) return false;
+  if (stack.item == kNoItem || stack.count == 0) {
+    slots[slot] = {};
+    return true;
+  }
+  if (stack.item < 0 || stack.count < 0) return false;
+  if (stack.count > items.getMaxStack(stack.item)) return false;
+  slots[slot] = stack;
+  return true;
+}
+
+int Inventory::add(const ItemRegistry& items, int item, int count) {
+  if (item < 0 || count <= 0) return std::max(count, 0);
+  const auto max = items.getMaxStack(item);
+
+  for (auto& slot : slots) {
+    if (count == 0) break;
+    if (slot.item != item || slot.count >= max) continue;
+    const auto moved = std::min(count, max - slot.count);
+    slot.count += moved;
+    count -= moved;
+  }
+  for (auto& slot : slots) {
+    if (count == 0) break;
+    if (slot.item != kNoItem) continue;
+    const auto moved = std::min(count, max);
+    slot = {item, moved};
+    count -= moved;
+  }
+  return count;
+}
+
+int Inventory::remove(int item, int count) {
+  if (item < 0 || count <= 0) return 0;
+  auto removed = 0;
+  for (auto i = size() - 1; i >= 0 && removed < count; i--) {
+    auto& slot = slots[i];
+    if (slot.item != item) continue;
+    const auto taken = std::min(count - removed, slot.count);
+    slot.count -= taken;
+    removed += taken;
+    if (slot.count == 0) slot = {};
+  }
+  return removed;
+}
+
+int Inventory::countItem(int item) const {
+  auto result = 0;
+  for (const auto& slot : slots) {
+    if (slot.item == item) result += slot.count;
+  }
+  return result;
+}
+
+int Inventory::move(const ItemRegistry& items, int from, Inventory& target,
+                    int to, int count) {
+  if (!valid(from) || !target.valid(to)) return 0;
+  if (this == &target && from == to) return 0;
+
+  auto& source = slots[from];
+  auto& dest = target.slots[to];
+  if (source.item == kNoItem || count <= 0) return 0;
+  count = std::min(count, source.count);
+
+  if (dest.item != kNoItem && dest.item != source.item) {
+    if (count != source.count) return 0;
+    std::swap(source, dest);
+    return count;
+  }
+
+  const auto space = items.getMaxStack(source.item) - dest.count;
+  const auto moved = std::min(count, std::max(space, 0));
+  if (moved == 0) return 0;
+
+  dest = {source.item, dest.count + moved};
+  source.count -= moved;
+  if (source.count == 0) source = {};
+  return moved;
+}
+
+int Inventory::split(int from, int to) {
+  if (!valid(from) || !valid(to) || from == to) return 0;
+  auto& source = slots[from];
+  auto& dest = slots[to];
+  if (source.count < 2 || dest.item != kNoItem) return 0;
+
+  const auto moved = (source.count + 1) / 2;
+  dest = {source.item, moved};
+  source.count -= moved;
+  return moved;
+}
+
+void Inventory::clear() {
+  std::fill(slots.begin(), slots.end(), ItemStack{});
+}
+
+//////////////////////////////////////////////////////////////////////////////
+
+} // namespace voxels
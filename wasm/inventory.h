@@ -0,0 +1,69 @@
+#pragma once
+
+#include <vector>
+
+#include "base.h"
+#include "items.h"
+
+//////////////////////////////////////////////////////////////////////////////
+
+namespace voxels {
+
+//////////////////////////////////////////////////////////////////////////////
+
+// A fixed-size array of item stacks, such as a player's hotbar or a chest's
+// contents, with the stacking rules that both the client and the server use
+// to check inventory actions. An empty slot holds kNoItem with a count of 0,
+// and a non-empty slot holds between 1 and the item's max stack size.
+//
+// Operations take the item registry for max stack sizes. Each one either
+// applies in full or returns a partial count, and never breaks the slot
+// invariant above, so the server can apply a client's action as-is and then
+// compare counts.
+
+struct ItemStack {
+  int item = kNoItem;
+  int count = 0;
+};
+
+struct Inventory {
+  explicit Inventory(int size);
+
+  int size() const { return static_cast<int>(slots.size()); }
+  const ItemStack& get(int slot) const;
+
+  // Returns false, without changing the slot, if the stack is invalid.
+  bool set(const ItemRegistry& items, int slot, const ItemStack& stack);
+
+  // Adds up to count of the item, topping up existing stacks of it before
+  // filling empty slots, in slot order. Returns the count that didn't fit.
+  int add(const ItemRegistry& items, int item, int count);
+
+  // Removes up to count of the item, from the last slots first. Returns the
+  // count removed.
+  int remove(int item, int count);
+
+  int countItem(int item) const;
+
+  // Moves up to count items from a slot to a slot of the target, which may
+  // be this inventory. Merges them into a stack of the same item, up to its
+  // max size; swaps whole stacks of different items. Returns the count that
+  // moved from the source slot, which is 0 if the move isn't allowed.
+  int move(const ItemRegistry& items, int from, Inventory& target,
+           int to, int count);
+
+  // Moves the larger half of a stack into an empty slot. Returns the count
+  // moved, which is 0 if the target slot isn't empty.
+  int split(int from, int to);
+
+  void clear();
+
+ private:
+  bool valid(int slot) const { return 0 <= slot && slot < size(); }
+
+  std::vector<ItemStack> slots;
+};
+
+//////////////////////////////////////////////////////////////////////////////
+
+} // namespace voxels
@@ -0,0 +1,34 @@
+#include "items.h"
+
+//////////////////////////////////////////////////////////////////////////////
+
+namespace voxels {
+
+//////////////////////////////////////////////////////////////////////////////
+
+void ItemRegistry::setTags(int item, uint32_t value) {
+  assert(item >= 0);
+  if (item >= tags.size()) tags.resize(item + 1, 0);
+  tags[item] = value;
+}
+
+uint32_t ItemRegistry::getTags(int item) const {
+  return 0 <= item && item < tags.size() ? tags[item] : 0;
+}
+
+void ItemRegistry::setMaxStack(int item, int value) {
+  assert(item >= 0 && value > 0);
+  if (item >= max_stacks.size()) {
+    max_stacks.resize(item + 1, kDefaultMaxStack);
+  }
+  max_stacks[item] = value;
+}
+
+int ItemRegistry::getMaxStack(int item) const {
+  const auto known = 0 <= item && item < max_stacks.size();
+  return known ? max_stacks[item] : kDefaultMaxStack;
+}
+
+//////////////////////////////////////////////////////////////////////////////
+
+} // namespace voxels
@@ -0,0 +1,44 @@
+#pragma once
+
+#include <cstdint>
+#include <vector>
+
+#include "base.h"
+
+//////////////////////////////////////////////////////////////////////////////
+
+namespace voxels {
+
+//////////////////////////////////////////////////////////////////////////////
+
+// Gameplay data for items, shared by crafting and the other item rules, so
+// that the client's predictions and the server's checks agree. Items are
+// game-defined ints >= 0, like the items in drop tables, and kNoItem marks
+// an empty slot. The engine doesn't map blocks to items: the game does.
+//
+// Tags are a game-defined bitmask of item classes, such as "planks" or
+// "fuel", so that rules can accept any item of a class. Items that we have
+// no data for have no tags and stack to kDefaultMaxStack.
+
+constexpr int kNoItem = -1;
+constexpr int kDefaultMaxStack = 64;
+
+struct ItemRegistry {
+  void setTags(int item, uint32_t value);
+  uint32_t getTags(int item) const;
+
+  void setMaxStack(int item, int value);
+  int getMaxStack(int item) const;
+
+  bool hasAnyTag(int item, uint32_t mask) const {
+    return (getTags(item) & mask) != 0;
+  }
+
+ private:
+  std::vector<uint32_t> tags;
+  std::vector<int> max_stacks;
+};
+
+//////////////////////////////////////////////////////////////////////////////
+
+} // namespace voxels
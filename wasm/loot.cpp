@@ -0,0 +1,102 @@
+#include "loot.h"
+
+#include <algorithm>
+
+//////////////////////////////////////////////////////////////////////////////
+
+namespace voxels {
+
+//////////////////////////////////////////////////////////////////////////////
+
+namespace {
+
+// Returns a uniform int in [min, max], or min if the range is empty.
+int uniformInt(Random& rng, int min, int max) {
+  if (max <= min) return min;
+  return min + static_cast<int>(rng.uniform() * (max - min + 1));
+}
+
+void addDrop(int item, int count, std::vector<DropRecord>& result) {
+  if (count <= 0) return;
+  for (auto& drop : result) {
+    if (drop.item == item) {
+      drop.count += count;
+      return;
+    }
+  }
+  result.push_back({item, count});
+}
+
+} // namespace
+
+//////////////////////////////////////////////////////////////////////////////
+
+bool LootCondition::check(const LootContext& context) const {
+  if (tool != 0 && (context.tool != tool || context.tier < min_tier)) {
+    return false;
+  }
+  return context.fortune >= min_fortune &&
+         (context.flags & flags) == flags;
+}
+
+int LootTables::addPool(int table, int min_rolls, int max_rolls,
+                        double chance, const LootCondition& condition) {
+  assert(table >= 0);
+  assert(0 <= min_rolls && min_rolls <= max_rolls);
+  if (table >= tables.size()) tables.resize(table + 1);
+  auto& pools = tables[table];
+  pools.push_back({min_rolls, max_rolls, chance, condition, {}});
+  return static_cast<int>(pools.size()) - 1;
+}
+
+void LootTables::addEntry(int table, int pool, const LootEntry& entry) {
+  assert(0 <= table && table < tables.size());
+  assert(0 <= pool && pool < tables[table].size());
+  assert(entry.weight > 0 && entry.min <= entry.max);
+  tables[table][pool].entries.push_back(entry);
+}
+
+void LootTables::roll(int table, const LootContext& context, uint32_t seed,
+                      std::vector<DropRecord>& result) const {
+  result.clear();
+  if (!(0 <= table && table < tables.size())) return;
+
+  // We take the same random draws for a pool whether or not its conditions
+  // hold, so that conditions don't reshuffle the rolls of later pools.
+  static std::vector<const LootEntry*> eligible;
+  auto rng = Random(seed);
+
+  for (const auto& pool : tables[table]) {
+    const auto skip = rng.uniform() >= pool.chance;
+    const auto rolls = uniformInt(rng, pool.min_rolls, pool.max_rolls);
+    const auto active = !skip && pool.condition.check(context);
+
+    eligible.clear();
+    auto total = 0;
+    for (const auto& entry : pool.entries) {
+      if (!active || !entry.condition.check(context)) continue;
+      eligible.push_back(&entry);
+      total += entry.weight;
+    }
+
+    for (auto i = 0; i < rolls; i++) {
+      auto pick = static_cast<int>(rng.uniform() * total);
+      const auto count_roll = rng.uniform();
+      if (total == 0) continue;
+
+      auto index = 0;
+      while (pick >= eligible[index]->weight) pick -= eligible[index++]->weight;
+      const auto& entry = *eligible[index];
+
+      const auto fortune = std::max(context.fortune, 0);
+      const auto max = entry.max + fortune * entry.fortune_bonus;
+      const auto count = entry.min +
+          static_cast<int>(count_roll * std::max(max - entry.min + 1, 1));
+      addDrop(entry.item, count, result);
+    }
+  }
+}
+
+//////////////////////////////////////////////////////////////////////////////
+
+} // namespace voxels
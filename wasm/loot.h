@@ -0,0 +1,80 @@
+#pragma once
+
+#include <cstdint>
+#include <vector>
+
+#include "base.h"
+#include "mesher.h"
+
+//////////////////////////////////////////////////////////////////////////////
+
+namespace voxels {
+
+//////////////////////////////////////////////////////////////////////////////
+
+// Loot tables, for block drops and for filling generated chests. Rolls are
+// deterministic given the table, the context, and the seed, so the client
+// and server agree on them. Simple per-block drops can use the drop tables
+// in the registry instead; see MiningData.
+//
+// A table is a list of pools. Each pool whose condition holds is rolled a
+// uniform [min_rolls, max_rolls] times, and each roll picks one of the pool's
+// entries whose condition holds, with odds proportional to their weights.
+// The picked entry drops a uniform [min, max + fortune * fortune_bonus] of
+// its item. Results are merged into one stack per item, in order of first
+// drop, and we don't split them by stack size.
+
+// The circumstances of a roll: the tool class and tier used, the fortune
+// level, and game-defined flags, such as "killed by a player".
+struct LootContext {
+  int tool = 0;
+  int tier = 0;
+  int fortune = 0;
+  uint32_t flags = 0;
+};
+
+// Holds if the context uses the given tool (if it's non-zero) at the given
+// tier or above, has at least the given fortune, and has all of the flags.
+struct LootCondition {
+  int tool = 0;
+  int min_tier = 0;
+  int min_fortune = 0;
+  uint32_t flags = 0;
+
+  bool check(const LootContext& context) const;
+};
+
+struct LootEntry {
+  int item;
+  int weight;
+  int min;
+  int max;
+  int fortune_bonus;
+  LootCondition condition;
+};
+
+struct LootPool {
+  int min_rolls;
+  int max_rolls;
+  double chance;
+  LootCondition condition;
+  std::vector<LootEntry> entries;
+};
+
+struct LootTables {
+  // Tables are created as we add pools to them. Returns the pool's index.
+  int addPool(int table, int min_rolls, int max_rolls, double chance,
+              const LootCondition& condition);
+  void addEntry(int table, int pool, const LootEntry& entry);
+
+  // Writes the table's drops to the result. Unknown tables drop nothing.
+  void roll(int table, const LootContext& context, uint32_t seed,
+            std::vector<DropRecord>& result) const;
+
+ private:
+  std::vector<std::vector<LootPool>> tables;
+};
+
+//////////////////////////////////////////////////////////////////////////////
+
+} // namespace voxels
@@ -0,0 +1,48 @@
+#include "memory.h"
+
+#include <cstddef>
+
+#include "emscripten.h"
+
+//////////////////////////////////////////////////////////////////////////////
+
+namespace voxels {
+
+//////////////////////////////////////////////////////////////////////////////
+
+EM_JS(void, js_OnMemoryGrowth, (int generation), { throw new Error(); });
+
+namespace {
+
+int generation = 0;
+size_t pages = 0;
+
+size_t getMemoryPages() {
+#ifdef __wasm__
+  return __builtin_wasm_memory_size(0);
+#else
+  return 0;
+#endif
+}
+
+} // namespace
+
+int checkMemoryGrowth() {
+  const auto current = getMemoryPages();
+  if (current == pages) return generation;
+
+  // The first check records our initial size; it isn't a growth.
+  const auto grew = pages != 0;
+  pages = current;
+  if (grew) js_OnMemoryGrowth(++generation);
+  return generation;
+}
+
+Arena& frameArena() {
+  static Arena arena;
+  return arena;
+}
+
+//////////////////////////////////////////////////////////////////////////////
+
+} // namespace voxels
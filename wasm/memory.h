@@ -0,0 +1,27 @@
+#pragma once
+
+#include "base.h"
+
+//////////////////////////////////////////////////////////////////////////////
+
+namespace voxels {
+
+//////////////////////////////////////////////////////////////////////////////
+
+// Allocations may grow WASM memory, which detaches any typed-array views that
+// the host holds over it. We count growths in a generation number, so the
+// host can tell when its views are stale, and we notify the host on growth.
+//
+// We can't hook the allocator, so we check for growth at the end of each
+// export that does significant allocation, and when asked for a generation.
+
+// Returns the current generation, calling the host if memory has grown.
+int checkMemoryGrowth();
+
+// Scratch space for temporary buffers. Callers should free their allocations
+// with an ArenaScope; the engine also resets this arena after each frame.
+Arena& frameArena();
+
+//////////////////////////////////////////////////////////////////////////////
+
+} // namespace voxels
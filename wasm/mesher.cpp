@@ -5,10 +5,14 @@
 #include <cmath>
 #include <cstdint>
 #include <cstdio>
+#include <cstring>
 #include <optional>
 #include <type_traits>
+#include <utility>
 #include <vector>
 
+#include "memory.h"
+
 //////////////////////////////////////////////////////////////////////////////
 
 namespace voxels {
@@ -17,6 +21,14 @@ namespace voxels {
 
 namespace {
 
+// Greedy meshing packs each face into a mask entry, as ao | dir | material.
+// Entries must be positive ints, since greedyRects treats 0 as "no face".
+constexpr int kMaskAOBits = 0xff;
+constexpr int kMaskDirBit = 1 << 8;
+constexpr int kMaskMaterialShift = 9;
+static_assert((int64_t{kMaxMaterials} << kMaskMaterialShift) <
+              (int64_t{1} << 31));
+
 constexpr int pack_indices(const NonCopyArray<int, 6>& indices) {
   auto result = 0;
   for (auto i = 0; i < indices.size(); i++) {
@@ -36,6 +48,41 @@ constexpr NonCopyArray<int, 4> kIndexOffsets = {
   pack_indices({3, 1, 0, 3, 2, 1}),
 };
 
+// The number of distinct chunk geometries that we keep in the mesh cache.
+// Players tend to toggle the same few blocks back and forth, so a handful
+// of entries is enough to catch most repeated remeshes.
+constexpr size_t kMeshCacheSize = 16;
+
+// Adds an offset to each quad's position, leaving its other fields intact.
+void translateQuads(VoxelMesh::Quads& quads,
+                    const std::array<int, 3>& offset) {
+  if (offset == std::array<int, 3>{0, 0, 0}) return;
+
+  const auto unpack = [](uint32_t packed) {
+    return static_cast<int>(static_cast<int16_t>(packed & 0xffff));
+  };
+  const auto pack = [](int value) {
+    assert(value == static_cast<int16_t>(value));
+    return static_cast<uint32_t>(value & 0xffff);
+  };
+  for (auto& quad : quads) {
+    const auto x = unpack(quad[0]) + offset[0];
+    const auto y = unpack(quad[0] >> 16) + offset[1];
+    const auto z = unpack(quad[1]) + offset[2];
+    quad[0] = pack(x) | (pack(y) << 16);
+    quad[1] = pack(z) | (quad[1] & 0xffff0000);
+  }
+}
+
+// Adds an offset to each smooth vertex's position.
+void translateSmoothGeometry(std::vector<float>& geo,
+                             const std::array<int, 3>& offset) {
+  if (offset == std::array<int, 3>{0, 0, 0}) return;
+  for (size_t i = 0; i < geo.size(); i += kSmoothVertexFloats) {
+    for (auto j = 0; j < 3; j++) geo[i + j] += static_cast<float>(offset[j]);
+  }
+}
+
 } // namespace
 
 //////////////////////////////////////////////////////////////////////////////
@@ -53,9 +100,60 @@ Mesher::Mesher(const Registry& r) : registry(r) {
   }
 }
 
-void Mesher::meshChunk() {
+void Mesher::meshChunk(int y_min, int y_max) {
+  assert(0 <= y_min && y_min < y_max && y_max <= kWorldHeight);
+  const auto hash = computeCacheKey(y_min, y_max, cache_key);
+  if (!lookupCachedGeometry(hash, cache_key)) {
+    if (kind == MesherKind::Naive) {
+      solid_geo.clear();
+      water_geo.clear();
+      solid_materials.clear();
+      water_materials.clear();
+      computeNaiveGeometry(y_min, y_max, face_mask, &solid_geo, &water_geo);
+    } else {
+      computeChunkGeometry(y_min, y_max);
+    }
+    computeSmoothGeometry(y_min, y_max);
+    insertCachedGeometry(hash, cache_key);
+  }
+  translateQuads(solid_geo, origin);
+  translateQuads(water_geo, origin);
+  translateSmoothGeometry(smooth_geo, origin);
+}
+
+bool Mesher::smooth() const {
+  return kind == MesherKind::SurfaceNets || kind == MesherKind::MarchingCubes;
+}
+
+void Mesher::computeSmoothGeometry(int y_min, int y_max) {
+  smooth_geo.clear();
+  if (!smooth()) return;
+
+  // We smooth opaque blocks only. getFaceDir skips their faces to match.
+  smooth_textures.fill(-1);
+  for (size_t i = 0; i < registry.getNumBlocks(); i++) {
+    const auto& data = registry.getBlockUnsafe(static_cast<Block>(i));
+    if (!data.opaque) continue;
+    for (auto face = 0; face < 6; face++) {
+      const auto id = data.faces[face];
+      const auto& material =
+          registry.getMaterialUnsafe(assertMaterialUnsafe(id));
+      smooth_textures[6 * i + face] = id == kNoMaterial ? 0 : material.texture;
+    }
+  }
+
+  if (kind == MesherKind::SurfaceNets) {
+    meshSurfaceNets(voxels, smooth_textures, y_min, y_max, smooth_geo);
+  } else {
+    meshMarchingCubes(voxels, smooth_textures, y_min, y_max, smooth_geo);
+  }
+}
+
+void Mesher::computeChunkGeometry(int y_min, int y_max) {
   solid_geo.clear();
   water_geo.clear();
+  solid_materials.clear();
+  water_materials.clear();
 
   auto max_height = 0;
   for (const auto entry : heightmap.data) {
@@ -78,25 +176,268 @@ void Mesher::meshChunk() {
            registry.getBlock(block1).opaque;
   };
 
-  const auto limit = equilevels.size() - 1;
-  for (auto i = 0; i < limit; i++) {
+  for (auto i = y_min; i < y_max; i++) {
     if (skip_level(i)) continue;
     auto j = i + 1;
-    for (; j < limit; j++) {
+    for (; j < y_max; j++) {
       if (skip_level(j)) break;
     }
-    auto y_min = i;
-    auto y_max = std::min(j, max_height) + 1;
-    if (y_min >= y_max) break;
-    computeChunkGeometry(y_min, y_max);
+    const auto limit = std::min(j, max_height);
+    if (i >= limit) break;
+    computeLevelGeometry(i, limit);
     i = j;
   }
 }
 
+bool Mesher::lookupCachedGeometry(uint64_t hash, const CacheKey& key) {
+  const auto it = cache_index.find(hash);
+  if (it == cache_index.end() || !(it->second->key == key)) return false;
+
+  cache.splice(cache.begin(), cache, it->second);
+  const auto& entry = cache.front();
+  solid_geo = entry.solid_geo;
+  water_geo = entry.water_geo;
+  solid_materials = entry.solid_materials;
+  water_materials = entry.water_materials;
+  smooth_geo = entry.smooth_geo;
+  return true;
+}
+
+void Mesher::insertCachedGeometry(uint64_t hash, const CacheKey& key) {
+  // On a hash collision, the new geometry replaces the old entry's.
+  const auto it = cache_index.find(hash);
+  if (it != cache_index.end()) {
+    cache.erase(it->second);
+    cache_index.erase(it);
+  }
+
+  if (cache.size() == kMeshCacheSize) {
+    cache_index.erase(cache.back().hash);
+    cache.pop_back();
+  }
+  cache.push_front({hash, key, solid_geo, water_geo, solid_materials,
+                    water_materials, smooth_geo});
+  cache_index[hash] = cache.begin();
+}
+
+uint64_t Mesher::computeCacheKey(int y_min, int y_max, CacheKey& key) const {
+  static_assert(sizeof(Block) == 1);
+  static_assert(decltype(voxels)::stride[1] == 1);
+
+  // The key includes the voxels, the mesher kind, the face mask, and the
+  // registry version only: the heightmap and equilevels are derived from
+  // the voxels, and they only affect how fast we mesh, not the geometry that
+  // we produce.
+  //
+  // Geometry for the levels [y_min, y_max) depends on the voxels in mesher
+  // levels [y_min, y_max + 2), because of the border and of AO lookups.
+  key.y_min = y_min;
+  key.y_max = y_max;
+  key.kind = kind;
+  key.face_mask = face_mask;
+  key.version = registry.getVersion();
+  key.voxels.clear();
+
+  const auto count = static_cast<size_t>(y_max + 2 - y_min);
+  for (auto x = 0; x < voxels.shape[0]; x++) {
+    for (auto z = 0; z < voxels.shape[2]; z++) {
+      const auto column = &voxels.data[voxels.index(x, y_min, z)];
+      key.voxels.insert(key.voxels.end(), column, column + count);
+    }
+  }
+
+  // A simple multiply-xorshift hash over 8-byte words.
+  auto result = static_cast<uint64_t>(0xcbf29ce484222325ull);
+  const auto mix = [&](uint64_t word) {
+    result = (result ^ word) * 0x100000001b3ull;
+    result ^= result >> 29;
+  };
+  mix((static_cast<uint64_t>(y_min) << 32) | static_cast<uint64_t>(y_max));
+  mix(static_cast<uint64_t>(kind));
+  mix(static_cast<uint64_t>(face_mask));
+  mix(static_cast<uint64_t>(key.version));
+
+  const auto bytes = reinterpret_cast<const uint8_t*>(key.voxels.data());
+  const auto size = key.voxels.size();
+  const auto words = size / sizeof(uint64_t);
+  for (size_t i = 0; i < words; i++) {
+    uint64_t word;
+    memcpy(&word, &bytes[i * sizeof(uint64_t)], sizeof(uint64_t));
+    mix(word);
+  }
+  uint64_t tail = 0;
+  memcpy(&tail, &bytes[words * sizeof(uint64_t)],
+         size - words * sizeof(uint64_t));
+  mix(tail);
+  return result;
+}
+
+void Mesher::meshReference(int y_min, int y_max) {
+  assert(0 <= y_min && y_min < y_max && y_max <= kWorldHeight);
+  reference_geo.clear();
+  computeNaiveGeometry(y_min, y_max, kAllFaces, &reference_geo, &reference_geo);
+}
+
+void Mesher::computeNaiveGeometry(int y_min, int y_max, int mask,
+                                  Quads* solid, Quads* water) {
+  // Visit each unit face between two voxels. As in meshChunk, each voxel
+  // owns its side faces and the face below it, and we skip faces on the
+  // chunk's x and z borders that face into a neighboring chunk.
+  for (auto d = 0; d < 3; d++) {
+    const auto face = 2 * d;
+    const auto planes = d == 1 ? y_max : kChunkWidth + 1;
+    for (auto p = d == 1 ? y_min : 0; p < planes; p++) {
+      for (auto x = 0; x < (d == 0 ? 1 : kChunkWidth); x++) {
+        for (auto y = y_min; y < (d == 1 ? y_min + 1 : y_max); y++) {
+          for (auto z = 0; z < (d == 2 ? 1 : kChunkWidth); z++) {
+            Pos pos = {x, y, z};
+            pos[d] = p;
+            Pos prev = pos;
+            prev[d]--;
+
+            // Mesher voxels are offset by 1 on each axis for the border.
+            const auto block0 = voxels.get(prev[0] + 1, prev[1] + 1, prev[2] + 1);
+            const auto block1 = voxels.get(pos[0] + 1, pos[1] + 1, pos[2] + 1);
+            if (block0 == block1) continue;
+            const auto dir = getFaceDir(block0, block1, face);
+            if (dir == 0) continue;
+            if (d != 1 && p == 0 && dir > 0) continue;
+            if (d != 1 && p == kChunkWidth && dir < 0) continue;
+
+            const auto id = dir > 0
+              ? registry.getBlockUnsafe(block0).faces[face + 0]
+              : registry.getBlockUnsafe(block1).faces[face + 1];
+            const auto& material =
+                registry.getMaterialUnsafe(assertMaterialUnsafe(id));
+            const auto face_bit = 1 << (face + (dir > 0 ? 1 : 0));
+            if (!(mask & face_bit) && !material.alphaTest) continue;
+            const auto quads = material.liquid ? water : solid;
+            addQuad(quads, material, dir, 0, 0, d, 1, 1, pos);
+            if (material.alphaTest && !material.liquid) {
+              addQuad(quads, material, -dir, 0, 0, d, 1, 1, pos);
+            }
+            tagMaterials(quads, id);
+          }
+        }
+      }
+    }
+  }
+}
+
+void Mesher::compareGeometry(int y_min, int y_max, const Quads& expected,
+                             const Quads& actual, MeshReport& report) {
+  assert(0 <= y_min && y_min < y_max && y_max <= kWorldHeight);
+
+  // Counts of quads covering each unit face, indexed by the face's normal
+  // (d, dir) and by the coordinates of its min corner, which lie on a grid
+  // one larger than the chunk in each dimension.
+  constexpr auto nx = kChunkWidth + 1, ny = kWorldHeight + 1;
+  constexpr auto nz = kChunkWidth + 1, cells = nx * ny * nz;
+  const auto cell = [&](int d, int dir, const Pos& pos) {
+    const auto [x, y, z] = pos;
+    return (2 * d + (dir > 0 ? 1 : 0)) * cells + x + nx * (y + ny * z);
+  };
+
+  // Decodes each quad and marks the unit faces that it covers. Returns the
+  // number of quads outside the chunk or with a bad d or index field.
+  const auto mark = [&](const Quads& quads, uint8_t* covered) {
+    auto out_of_range = 0;
+    for (const auto& quad : quads) {
+      const auto d = static_cast<int>((quad[3] >> 28) & 3);
+      const auto dir = (quad[3] >> 30) & 1 ? 1 : -1;
+      const auto indices = static_cast<int>((quad[1] >> 16) & 0xfff);
+      const auto valid_indices = std::find(
+          kIndexOffsets.begin(), kIndexOffsets.end(), indices);
+      if (d > 2 || valid_indices == kIndexOffsets.end()) {
+        out_of_range++;
+        continue;
+      }
+
+      const Pos pos = {static_cast<int16_t>(quad[0] & 0xffff),
+                       static_cast<int16_t>(quad[0] >> 16),
+                       static_cast<int16_t>(quad[1] & 0xffff)};
+      const auto w = static_cast<int>(static_cast<int16_t>(quad[2] & 0xffff));
+      const auto h = static_cast<int>(static_cast<int16_t>(quad[2] >> 16));
+
+      // Liquid surfaces come with zero-area patch quads. They cover nothing.
+      if (w == 0 || h == 0) continue;
+
+      const auto u = (d + 1) % 3, v = (d + 2) % 3;
+      const Pos limit = {kChunkWidth, y_max, kChunkWidth};
+      const auto min_d = d == 1 ? y_min : 0;
+      const auto max_d = d == 1 ? y_max - 1 : limit[d];
+      const auto lo_u = u == 1 ? y_min : 0, lo_v = v == 1 ? y_min : 0;
+      if (pos[d] < min_d || pos[d] > max_d || w < 0 || h < 0 ||
+          pos[u] < lo_u || pos[u] + w > limit[u] ||
+          pos[v] < lo_v || pos[v] + h > limit[v]) {
+        out_of_range++;
+        continue;
+      }
+
+      auto tmp = pos;
+      for (auto i = 0; i < w; i++) {
+        for (auto j = 0; j < h; j++) {
+          tmp[u] = pos[u] + i;
+          tmp[v] = pos[v] + j;
+          auto& count = covered[cell(d, dir, tmp)];
+          if (count < 0xff) count++;
+        }
+      }
+    }
+    return out_of_range;
+  };
+
+  ArenaScope scope(frameArena());
+  const auto lhs = frameArena().allocate<uint8_t>(6 * cells, 0);
+  const auto rhs = frameArena().allocate<uint8_t>(6 * cells, 0);
+  mark(expected, lhs);
+  report.out_of_range += mark(actual, rhs);
+
+  for (auto i = 0; i < 6 * cells; i++) {
+    if (rhs[i] > 1) report.overlaps += rhs[i] - 1;
+    if (lhs[i] && !rhs[i]) report.holes++;
+    if (!lhs[i] && rhs[i]) report.extras++;
+  }
+}
+
+void Mesher::validateGeometry(int y_min, int y_max, MeshReport& report) {
+  auto& actual = validate_geo;
+  actual.assign(solid_geo.begin(), solid_geo.end());
+  actual.insert(actual.end(), water_geo.begin(), water_geo.end());
+  report.quads += static_cast<int>(actual.size());
+
+  meshReference(y_min, y_max);
+  compareGeometry(y_min, y_max, reference_geo, actual, report);
+
+  // Sort the quads to find exact duplicates.
+  std::sort(actual.begin(), actual.end());
+  for (size_t i = 1; i < actual.size(); i++) {
+    if (actual[i] == actual[i - 1]) report.duplicates++;
+  }
+}
+
+void Mesher::meshHeightmap(const HeightmapEntry* start, int n, Point pos) {
+  solid_geo.clear();
+  water_geo.clear();
+  solid_materials.clear();
+  water_materials.clear();
+
+  const auto stride = Point{2, 2 * n};
+  const auto ptr = &start[0].fields[0];
+  const auto error = std::exchange(frontier_error, 0);
+  const auto skirt = std::exchange(frontier_skirt, kWorldHeight);
+  computeFrontierGeometry(&solid_geo, ptr + 0, n, pos, stride, 1, 0, 1);
+  computeFrontierGeometry(&water_geo, ptr + 1, n, pos, stride, 1, 0, 0);
+  frontier_error = error;
+  frontier_skirt = skirt;
+}
+
 void Mesher::meshFrontier(const HeightmapEntry* start, int n,
                           Point pos, int scale, int index) {
   solid_geo.clear();
   water_geo.clear();
+  solid_materials.clear();
+  water_materials.clear();
 
   assert(n % 2 == 0);
   const auto half = n >> 1;
@@ -139,17 +480,26 @@ void Mesher::addQuad(
            (static_cast<uint32_t>(b & 0xffff) << 16);
   };
 
+  // indices only uses 12 bits, so we pack the UV transform above it.
+  //
+  // Quads don't store normals. The top 3 bits, d and dir_bit, are a face id,
+  // and the voxel shader rebuilds the normal from it: normal[d] = dir.
+  //
+  // The last bit marks full-bright quads, which the shader doesn't light.
+  const auto uv_transform = material.uv_transform & (kUVRotationMask | kUVFlip);
+  const auto bright_bit = material.fullBright ? 1 : 0;
   quad[0] = packTwoInts(x, y);
-  quad[1] = packTwoInts(z, indices);
+  quad[1] = packTwoInts(z, indices | (uv_transform << 12));
   quad[2] = packTwoInts(w, h);
-  quad[3] = (static_cast<uint32_t>(texture) << 8)  |
-            (static_cast<uint32_t>(ao)      << 16) |
-            (static_cast<uint32_t>(wave)    << 24) |
-            (static_cast<uint32_t>(d)       << 28) |
-            (static_cast<uint32_t>(dir_bit) << 30);
+  quad[3] = (static_cast<uint32_t>(texture)    << 8)  |
+            (static_cast<uint32_t>(ao)         << 16) |
+            (static_cast<uint32_t>(wave)       << 24) |
+            (static_cast<uint32_t>(d)          << 28) |
+            (static_cast<uint32_t>(dir_bit)    << 30) |
+            (static_cast<uint32_t>(bright_bit) << 31);
 }
 
-void Mesher::computeChunkGeometry(int y_min, int y_max) {
+void Mesher::computeLevelGeometry(int y_min, int y_max) {
   Pos pos;
   Pos stride{
     static_cast<int>(voxels.stride[0]),
@@ -157,7 +507,7 @@ void Mesher::computeChunkGeometry(int y_min, int y_max) {
     static_cast<int>(voxels.stride[2])};
   Pos shape{
     static_cast<int>(voxels.shape[0]),
-    static_cast<int>(y_max - y_min),
+    static_cast<int>(y_max - y_min + 2),
     static_cast<int>(voxels.shape[2])};
 
   for (auto dx = 0; dx < 3; dx++) {
@@ -165,10 +515,15 @@ void Mesher::computeChunkGeometry(int y_min, int y_max) {
     const auto face = 2 * d;
     const auto v = d == 1 ? 0 : 1;
     const auto u = 3 - d - v;
-    const auto ld = shape[d] - 1, lu = shape[u] - 2, lv = shape[v] - 2;
+    const auto ld = shape[d] - (d == 1 ? 2 : 1);
+    const auto lu = shape[u] - 2, lv = shape[v] - 2;
     const auto sd = stride[d], su = stride[u], sv = stride[v];
     const auto base = su + sv + y_min * stride[1];
 
+    // Each voxel in the levels [y_min, y_max) owns its side faces and the
+    // face below it. That way, meshing two adjacent level ranges separately
+    // produces the same faces as meshing their union.
+    //
     // d is the dimension that the quad faces. A d of {0, 1, 2} corresponds
     // to a quad with a normal that's a unit vector on the {x, y, z} axis,
     // respectively. u and v are the orthogonal dimensions along which we
@@ -198,12 +553,9 @@ void Mesher::computeChunkGeometry(int y_min, int y_max) {
     const auto sv_fixed = d > 0 ? sv : su;
 
     const auto area = lu * lv;
-    if (mask_data.size() < area) {
-      mask_data.resize(area);
-    }
-    if (mask_union.size() < lu) {
-      mask_union.resize(lu);
-    }
+    ArenaScope scope(frameArena());
+    const auto mask_data = frameArena().allocate<int>(area, 0);
+    const auto mask_union = frameArena().allocate<int>(lu);
 
     for (auto id = 0; id < ld; id++) {
       auto n = 0;
@@ -232,10 +584,17 @@ void Mesher::computeChunkGeometry(int y_min, int y_max) {
           const auto material = dir > 0
             ? registry.getBlockUnsafe(block0).faces[face + 0]
             : registry.getBlockUnsafe(block1).faces[face + 1];
-          const auto ao = dir > 0
+          const auto& data =
+              registry.getMaterialUnsafe(assertMaterialUnsafe(material));
+          const auto face_bit = 1 << (face + (dir > 0 ? 1 : 0));
+          if (!(face_mask & face_bit) && !data.alphaTest) continue;
+
+          // Full-bright faces have no AO, so they merge into larger quads.
+          const auto ao = data.fullBright ? 0 : dir > 0
             ? packAOMask(index + sd, index, su_fixed, sv_fixed)
             : packAOMask(index, index + sd, su_fixed, sv_fixed);
-          const auto mask = (material.id << 9) | (dir > 0 ? 1 << 8 : 0) | ao;
+          const auto mask = (material.id << kMaskMaterialShift) |
+                            (dir > 0 ? kMaskDirBit : 0) | ao;
 
           mask_data[n] = mask;
           mask_union[iu] |= mask;
@@ -253,91 +612,60 @@ void Mesher::computeChunkGeometry(int y_min, int y_max) {
       if (d != 1) {
         if (id == 0) {
           for (auto i = 0; i < area; i++) {
-            if ((mask_data[i] & 0x100) != 0) mask_data[i] = 0;
+            if ((mask_data[i] & kMaskDirBit) != 0) mask_data[i] = 0;
           }
         } else if (id == ld - 1) {
           for (auto i = 0; i < area; i++) {
-            if ((mask_data[i] & 0x100) == 0) mask_data[i] = 0;
+            if ((mask_data[i] & kMaskDirBit) == 0) mask_data[i] = 0;
           }
         }
       }
 
-      n = 0;
-      for (auto iu = 0; iu < lu; iu++) {
-        if (mask_union[iu] == 0) {
-          n += lv;
-          continue;
-        }
+      const auto fn = [&](int iu, int iv, int w, int h, int mask) {
+        pos[d] = id;
+        pos[u] = iu;
+        pos[v] = iv;
+        pos[1] += y_min;
 
-        auto h = 1;
-        for (auto iv = 0; iv < lv; iv += h, n += h) {
-          const auto mask = mask_data[n];
-          if (mask == 0) {
-            h = 1;
-            continue;
-          }
+        const auto ao  = mask & kMaskAOBits;
+        const auto dir = mask & kMaskDirBit ? 1 : -1;
 
-          for (h = 1; h < lv - iv; h++) {
-            if (mask != mask_data[n + h]) break;
-          }
+        static_assert(sizeof(MaybeMaterial) == 1);
+        const auto material_id =
+            static_cast<uint8_t>(mask >> kMaskMaterialShift);
+        const auto& material = registry.getMaterialUnsafe(
+            assertMaterialUnsafe({material_id}));
+        const auto geo = material.color[3] < 1 ? &water_geo : &solid_geo;
 
-          auto w = 1;
-          auto nw = n + lv;
-          for (; w < lu - iu; w++, nw += lv) {
-            for (auto x = 0; x < h; x++) {
-              if (mask != mask_data[nw + x]) goto done;
-            }
-          }
-          done:
-
-          pos[d] = id;
-          pos[u] = iu;
-          pos[v] = iv;
-          pos[1] += y_min;
-
-          const auto ao  = mask & 0xff;
-          const auto dir = mask & 0x100 ? 1 : -1;
-
-          static_assert(sizeof(MaybeMaterial) == 1);
-          const auto& material = registry.getMaterialUnsafe(
-              assertMaterialUnsafe({static_cast<uint8_t>(mask >> 9)}));
-          const auto geo = material.color[3] < 1 ? &water_geo : &solid_geo;
-
-          const auto w_fixed = d > 0 ? w : h;
-          const auto h_fixed = d > 0 ? h : w;
-
-          if (material.liquid) {
-            if (d == 1) {
-              if (dir > 0) {
-                const auto wave = kWaveValues[d];
-                addQuad(geo, material, dir, ao, wave, d, w, h, pos);
-                patchLiquidSurfaceQuads(geo, ao, w, h, pos);
-              } else {
-                addQuad(geo, material, dir, ao, 0, d, w, h, pos);
-              }
-            } else {
+        const auto w_fixed = d > 0 ? w : h;
+        const auto h_fixed = d > 0 ? h : w;
+
+        if (material.liquid) {
+          if (d == 1) {
+            if (dir > 0) {
               const auto wave = kWaveValues[d];
-              if (h == lv - iv) {
-                addQuad(geo, material, dir, ao, wave, d, w_fixed, h_fixed, pos);
-              } else {
-                splitLiquidSideQuads(geo, material, dir, ao, wave, d, w, h, pos);
-              }
+              addQuad(geo, material, dir, ao, wave, d, w, h, pos);
+              patchLiquidSurfaceQuads(geo, ao, w, h, pos);
+            } else {
+              addQuad(geo, material, dir, ao, 0, d, w, h, pos);
             }
           } else {
-            addQuad(geo, material, dir, ao, 0, d, w_fixed, h_fixed, pos);
-            if (material.alphaTest) {
-              addQuad(geo, material, -dir, ao, 0, d, w_fixed, h_fixed, pos);
+            const auto wave = kWaveValues[d];
+            if (h == lv - iv) {
+              addQuad(geo, material, dir, ao, wave, d, w_fixed, h_fixed, pos);
+            } else {
+              splitLiquidSideQuads(geo, material, dir, ao, wave, d, w, h, pos);
             }
           }
-
-          nw = n;
-          for (auto wx = 0; wx < w; wx++, nw += lv) {
-            for (auto hx = 0; hx < h; hx++) {
-              mask_data[nw + hx] = 0;
-            }
+        } else {
+          addQuad(geo, material, dir, ao, 0, d, w_fixed, h_fixed, pos);
+          if (material.alphaTest) {
+            addQuad(geo, material, -dir, ao, 0, d, w_fixed, h_fixed, pos);
           }
         }
-      }
+        tagMaterials(geo, {material_id});
+      };
+      greedyRects(mask_data, mask_union, lu, lv, fn);
     }
   }
 }
@@ -351,15 +679,25 @@ void Mesher::computeFrontierGeometry(
 
   const auto size = n + 2;
   const auto area = size * size;
-  if (height_mask.size() < area) {
-    height_mask.resize(area, {Block::Air, 0});
-  }
+  ArenaScope scope(frameArena());
+  const auto height_mask =
+      frameArena().allocate<HeightmapField>(area, {Block::Air, 0});
+
+  // Rounding each height to the nearest multiple of step moves it by at most
+  // frontier_error. Rounding is monotonic, so water stays above the ground.
+  const auto step = 2 * frontier_error;
+  const auto snap = [&](int height) {
+    if (step == 0) return height;
+    return std::min((height + frontier_error) / step * step, 0xff);
+  };
 
   for (auto z = 0; z < n; z++) {
     auto source = &start[z * stride.z];
     const auto target = (z + 1) * size + 1;
     for (auto x = 0; x < n; x++, source += stride.x) {
-      height_mask[target + x] = *source;
+      auto& field = height_mask[target + x];
+      field = *source;
+      field.height = static_cast<uint8_t>(snap(field.height));
     }
   }
 
@@ -388,7 +726,11 @@ void Mesher::computeFrontierGeometry(
         const auto id = registry.getBlockUnsafe(block).faces[2];
         if (id == kNoMaterial) continue;
 
-        const auto neighbor_height = height_mask[offset + di].height;
+        // Neighbors past the border are empty, so these faces are skirts.
+        const auto border = i == (dir > 0 ? n - 1 : 0);
+        const auto skirt = border ? height - frontier_skirt : 0;
+        const auto neighbor_height =
+            std::max<int>(height_mask[offset + di].height, skirt);
         if (neighbor_height >= height) continue;
 
         auto w = 1;
@@ -396,7 +738,8 @@ void Mesher::computeFrontierGeometry(
         for (auto index = offset + sj; w < limit; w++, index += sj) {
           const auto match = height_mask[index].block == block &&
                              height_mask[index].height == height &&
-                             height_mask[index + di].height == neighbor_height;
+                             std::max<int>(height_mask[index + di].height,
+                                           skirt) == neighbor_height;
           if (!match) break;
         }
 
@@ -410,6 +753,7 @@ void Mesher::computeFrontierGeometry(
         const auto wave = material.liquid ? 0b1111 : 0;
         addQuad(quads, material, dir, ao, wave, d, wi, hi, tmp);
         quads->back()[3] |= static_cast<uint32_t>(mask_8bits);
+        tagMaterials(quads, id);
 
         const auto extra = w - 1;
         offset += extra * sj;
@@ -448,6 +792,7 @@ void Mesher::computeFrontierGeometry(
       const auto wave = material.liquid ? 0b1111 : 0;
       addQuad(quads, material, 1, 0, wave, 1, scale * w, scale * h, tmp);
       quads->back()[3] |= static_cast<uint32_t>(mask_8bits);
+      tagMaterials(quads, id);
 
       for (auto wi = 0; wi < w; wi++) {
         const auto target = &prev[wi * size];
@@ -561,6 +906,13 @@ void Mesher::splitLiquidSideQuads(
   }
 }
 
+// Records the material of the quads that we just added, if they're output
+// geometry rather than reference or validation geometry.
+void Mesher::tagMaterials(const Quads* quads, MaybeMaterial id) {
+  if (quads == &solid_geo) solid_materials.resize(solid_geo.size(), id);
+  if (quads == &water_geo) water_materials.resize(water_geo.size(), id);
+}
+
 bool Mesher::getTriangleHint(int ao) const {
   const auto a00 = (ao >> 0) & 3;
   const auto a10 = (ao >> 2) & 3;
@@ -576,8 +928,11 @@ int Mesher::getFaceDir(Block block0, Block block1, int face) const {
   const auto opaque0 = data0.opaque;
   const auto opaque1 = data1.opaque;
   if (opaque0 && opaque1) return 0;
-  if (opaque0) return 1;
-  if (opaque1) return -1;
+  if (opaque0 || opaque1) {
+    // The smooth meshers emit the surfaces of opaque blocks instead.
+    if (smooth()) return 0;
+    return opaque0 ? 1 : -1;
+  }
 
   const auto material0 = data0.faces[face];
   const auto material1 = data1.faces[face];
@@ -637,4 +992,148 @@ int Mesher::packAOMask(int ipos, int ineg, int dj, int dk) const {
 
 //////////////////////////////////////////////////////////////////////////////
 
+void weldQuads(const VoxelMesh::Quads& quads, IndexedMesh& result,
+               bool repeats) {
+  // Keys pack 14 bits for each coordinate and 18 bits of attributes, paired
+  // with the quad's size if we're emitting repeats.
+  constexpr auto kCoordBits = 14;
+  constexpr auto kCoordMask = (1 << kCoordBits) - 1;
+  HashMap<std::pair<uint64_t, uint32_t>, uint32_t> welded;
+
+  for (const auto& quad : quads) {
+    const auto indices = static_cast<int>((quad[1] >> 16) & 0xfff);
+    const auto uv_transform = static_cast<int>((quad[1] >> 28) & 7);
+    const auto texture = static_cast<int>((quad[3] >> 8) & 0xff);
+    const auto ao = static_cast<int>((quad[3] >> 16) & 0xff);
+    const auto wave = static_cast<int>((quad[3] >> 24) & 0xf);
+    const auto d = static_cast<int>((quad[3] >> 28) & 3);
+    const auto dir_bit = static_cast<int>((quad[3] >> 30) & 1);
+    const auto bright_bit = static_cast<int>((quad[3] >> 31) & 1);
+    const auto w = static_cast<int>(static_cast<int16_t>(quad[2] & 0xffff));
+    const auto h = static_cast<int>(static_cast<int16_t>(quad[2] >> 16));
+    const std::array<int, 3> pos = {
+      static_cast<int16_t>(quad[0] & 0xffff),
+      static_cast<int16_t>(quad[0] >> 16),
+      static_cast<int16_t>(quad[1] & 0xffff),
+    };
+    const auto size = repeats ? quad[2] : 0;
+
+    // Corners are numbered as in the voxel shader, and indices holds six
+    // 2-bit corner numbers, for the quad's two triangles.
+    std::array<uint32_t, 4> corners;
+    for (auto i = 0; i < 4; i++) {
+      auto corner = pos;
+      corner[(d + 1) % 3] += ((i + 1) & 3) >> 1 ? w : 0;
+      corner[(d + 2) % 3] += ((i + 0) & 3) >> 1 ? h : 0;
+      const auto attributes =
+          texture | (((ao >> (2 * i)) & 3) << 8) | (((wave >> i) & 1) << 10) |
+          (d << 11) | (dir_bit << 13) | (uv_transform << 14) |
+          (bright_bit << 17);
+
+      auto key = static_cast<uint64_t>(attributes);
+      for (const auto x : corner) {
+        assert((x & kCoordMask) == x);
+        key = (key << kCoordBits) | static_cast<uint64_t>(x);
+      }
+
+      const auto next = static_cast<uint32_t>(result.vertices.size() / 4);
+      const auto [it, inserted] = welded.emplace(std::pair{key, size}, next);
+      if (inserted) {
+        result.vertices.insert(result.vertices.end(),
+                               {corner[0], corner[1], corner[2], attributes});
+        if (repeats) {
+          result.repeats.insert(result.repeats.end(),
+                                {static_cast<uint16_t>(w),
+                                 static_cast<uint16_t>(h)});
+        }
+      }
+      corners[i] = it->second;
+    }
+    for (auto i = 0; i < 6; i++) {
+      result.indices.push_back(corners[(indices >> (2 * i)) & 3]);
+    }
+  }
+}
+
+void batchIndexedMesh(const IndexedMesh& mesh, const uint8_t* pages,
+                      std::vector<MeshBatch>& result) {
+  result.clear();
+  const auto num_vertices = mesh.vertices.size() / 4;
+  const auto num_quads = mesh.indices.size() / 6;
+  const auto repeats = !mesh.repeats.empty();
+  const auto materials = !mesh.quad_materials.empty();
+  assert(!repeats || mesh.repeats.size() == 2 * num_vertices);
+  assert(!materials || mesh.quad_materials.size() == num_quads);
+
+  // A quad's corners share its texture, so each quad has one page.
+  const auto page = [&](uint32_t quad) {
+    const auto vertex = mesh.indices[6 * quad];
+    return static_cast<int>(pages[mesh.vertices[4 * vertex + 3] & 0xff]);
+  };
+  std::vector<uint32_t> quads(num_quads);
+  for (size_t i = 0; i < quads.size(); i++) {
+    quads[i] = static_cast<uint32_t>(i);
+  }
+  std::stable_sort(quads.begin(), quads.end(),
+                   [&](uint32_t a, uint32_t b) { return page(a) < page(b); });
+
+  // remap[i] is vertex i's index in the current batch, or -1 if it has none.
+  // We reset the entries that we set when we start a new batch.
+  std::vector<int> remap(num_vertices, -1);
+  std::vector<uint32_t> mapped;
+  MeshBatch* batch = nullptr;
+
+  for (const auto quad : quads) {
+    const auto start = &mesh.indices[6 * quad];
+    auto added = 0;
+    for (auto i = 0; i < 6; i++) {
+      if (remap[start[i]] >= 0) continue;
+      auto repeated = false;
+      for (auto j = 0; j < i; j++) repeated |= start[j] == start[i];
+      added += repeated ? 0 : 1;
+    }
+
+    const auto current = page(quad);
+    const auto size = batch ? batch->vertices.size() / kBatchVertexFloats : 0;
+    if (!batch || batch->page != current ||
+        size + added > static_cast<size_t>(kMaxBatchVertices)) {
+      for (const auto vertex : mapped) remap[vertex] = -1;
+      mapped.clear();
+      batch = &result.emplace_back();
+      batch->page = current;
+    }
+
+    if (materials) batch->quad_materials.push_back(mesh.quad_materials[quad]);
+    for (auto i = 0; i < 6; i++) {
+      const auto vertex = start[i];
+      if (remap[vertex] < 0) {
+        const auto count = batch->vertices.size() / kBatchVertexFloats;
+        remap[vertex] = static_cast<int>(count);
+        mapped.push_back(vertex);
+        const auto source = &mesh.vertices[4 * vertex];
+        const auto bits = source[3];
+        batch->vertices.insert(batch->vertices.end(), {
+          static_cast<float>(source[0]),
+          static_cast<float>(source[1]),
+          static_cast<float>(source[2]),
+          static_cast<float>(bits & 0xff),
+          static_cast<float>((bits >> 8) & 3),
+          static_cast<float>((bits >> 10) & 1),
+          static_cast<float>((bits >> 11) & 3),
+          static_cast<float>((bits >> 13) & 1),
+          static_cast<float>((bits >> 14) & 7),
+          static_cast<float>((bits >> 17) & 1),
+        });
+        if (repeats) {
+          const auto repeat = mesh.repeats.begin() + 2 * vertex;
+          batch->repeats.insert(batch->repeats.end(), repeat, repeat + 2);
+        }
+      }
+      batch->indices.push_back(static_cast<uint16_t>(remap[vertex]));
+    }
+  }
+}
+
+//////////////////////////////////////////////////////////////////////////////
+
 } // namespace voxels
@@ -2,11 +2,20 @@
 
 #include <array>
 #include <cassert>
+#include <list>
 #include <ranges>
 #include <vector>
 
 #include "base.h"
+#include "fire.h"
+#include "hooks.h"
+#include "mining.h"
+#include "power.h"
 #include "renderer.h"
+#include "smooth.h"
+#include "spread.h"
+#include "trees.h"
+#include "triggers.h"
 
 //////////////////////////////////////////////////////////////////////////////
 
@@ -38,6 +47,12 @@ constexpr MesherOffset kMesherOffsets[9] = {
 
 //////////////////////////////////////////////////////////////////////////////
 
+// Registry limits. Block ids and material ids are bytes in voxels, quads,
+// and packets, and material id 0 is reserved for kNoMaterial, so one fewer
+// material fits than blocks. Hosts must check these before registering.
+constexpr int kMaxBlocks = 256;
+constexpr int kMaxMaterials = 255;
+
 struct Material { uint8_t id; };
 
 struct MaybeMaterial {
@@ -56,11 +71,19 @@ constexpr Material assertMaterial(MaybeMaterial m) {
   return Material{static_cast<uint8_t>(m.id - 1)};
 }
 
+// UV transforms for directional textures: a rotation in 90-degree steps,
+// counterclockwise, plus an optional flip of u, which we apply first.
+constexpr int kUVRotationMask = 3;
+constexpr int kUVFlip = 1 << 2;
+
 struct MaterialData {
   bool liquid;
   bool alphaTest;
   uint8_t texture;
   double color[4];
+  uint8_t uv_transform = 0;
+  // Full-bright faces skip AO and lighting, for glowing or UI-like blocks.
+  bool fullBright = false;
 };
 
 struct BlockData {
@@ -71,22 +94,60 @@ struct BlockData {
   MaybeMaterial faces[6];
 };
 
+// A type of projectile, such as an arrow or a bullet. Projectiles fall with
+// gravity, in blocks/s^2, lose drag of their speed per second, and keep
+// restitution of their speed when they bounce. When one enters a solid
+// block, its response to that block decides whether it sticks, bounces, or
+// passes through. It passes through non-solid blocks without a hit.
+enum class ProjectileResponse : uint8_t { Stick, Bounce, Pass, Count };
+
+struct ProjectileKind {
+  double gravity;
+  double drag;
+  double restitution;
+  std::array<ProjectileResponse, 256> responses;
+};
+
+// Block class tags, used by gameplay rules that apply to a class of blocks.
+constexpr uint8_t kBlockTagLog    = 1 << 0;
+constexpr uint8_t kBlockTagLeaves = 1 << 1;
+constexpr uint8_t kBlockTagFixed  = 1 << 2;
+
 struct Registry {
   static_assert(sizeof(Block) == 1);
   static_assert(sizeof(Material) == 1);
 
-  Registry() {}
+  Registry() {
+    tags.fill(0); hooks.fill(0); attenuation.fill(0); resistance.fill(0.0);
+    priority.fill(0);
+  }
 
   void addBlock(Block block, BlockData data) {
     assert(static_cast<size_t>(block) == numBlocks);
-    assert(static_cast<size_t>(block) < blocks.size());
+    assert(static_cast<size_t>(block) < kMaxBlocks);
     blocks[numBlocks++] = data;
+    version++;
   }
 
   void addMaterial(Material material, MaterialData data) {
     assert(material.id == numMaterials);
-    assert(material.id < materials.size());
+    assert(material.id < kMaxMaterials);
     materials[numMaterials++] = data;
+    version++;
+  }
+
+  // Changes whenever block or material data that the mesher reads changes,
+  // so that geometry cached under an older version is never reused.
+  uint32_t getVersion() const {
+    return version;
+  }
+
+  size_t getNumBlocks() const {
+    return numBlocks;
+  }
+
+  size_t getNumMaterials() const {
+    return numMaterials;
   }
 
   const BlockData& getBlock(Block block) const {
@@ -107,34 +168,437 @@ struct Registry {
     return materials[material.id];
   }
 
+  void addDropEntry(int table, DropEntry entry) {
+    drop_tables.add(table, entry);
+  }
+
+  void setMining(Block block, MiningData data) {
+    assert(static_cast<size_t>(block) < numBlocks);
+    mining[static_cast<size_t>(block)] = data;
+  }
+
+  const MiningData& getMining(Block block) const {
+    assert(static_cast<size_t>(block) < numBlocks);
+    return mining[static_cast<size_t>(block)];
+  }
+
+  void setTags(Block block, uint8_t value) {
+    assert(static_cast<size_t>(block) < numBlocks);
+    tags[static_cast<size_t>(block)] = value;
+  }
+
+  bool hasTag(Block block, uint8_t tag) const {
+    return (tags[static_cast<size_t>(block)] & tag) != 0;
+  }
+
+  void addTreeSpecies(int species, TreeSpecies data) {
+    assert(species == trees.size());
+    assert(data.min_height <= data.max_height);
+    trees.push_back(data);
+  }
+
+  const TreeSpecies* getTreeSpecies(int species) const {
+    if (!(0 <= species && species < trees.size())) return nullptr;
+    return &trees[species];
+  }
+
+  // Projectiles stick in every solid block until we set another response.
+  void addProjectileKind(int kind, double gravity, double drag,
+                         double restitution) {
+    assert(kind == projectiles.size());
+    assert(drag >= 0 && restitution >= 0);
+    auto& data = projectiles.emplace_back();
+    data.gravity = gravity;
+    data.drag = drag;
+    data.restitution = restitution;
+    data.responses.fill(ProjectileResponse::Stick);
+  }
+
+  void setProjectileResponse(int kind, Block block,
+                             ProjectileResponse response) {
+    assert(0 <= kind && kind < projectiles.size());
+    assert(static_cast<size_t>(block) < numBlocks);
+    projectiles[kind].responses[static_cast<size_t>(block)] = response;
+  }
+
+  const ProjectileKind* getProjectileKind(int kind) const {
+    if (!(0 <= kind && kind < projectiles.size())) return nullptr;
+    return &projectiles[kind];
+  }
+
+  // Hooks is a bitmask of hookBit(BlockHook) values. See hooks.h.
+  void setHooks(Block block, uint8_t mask) {
+    assert(static_cast<size_t>(block) < numBlocks);
+    hooks[static_cast<size_t>(block)] = mask;
+  }
+
+  bool hasHook(Block block, BlockHook hook) const {
+    return hooks[static_cast<size_t>(block)] & hookBit(hook);
+  }
+
+  // Extra light levels that a transparent block absorbs, on top of the one
+  // level that light loses per step: water and leaves dim light without
+  // blocking it. Lights are a single channel, so we can't filter colors.
+  void setAttenuation(Block block, uint8_t value) {
+    assert(static_cast<size_t>(block) < numBlocks);
+    attenuation[static_cast<size_t>(block)] = value;
+  }
+
+  int getAttenuation(Block block) const {
+    return attenuation[static_cast<size_t>(block)];
+  }
+
+  // How much of an explosion ray's intensity a block absorbs. See
+  // World::explode. Stone-like blocks are around 6; dirt is around 0.5.
+  void setBlastResistance(Block block, double value) {
+    assert(static_cast<size_t>(block) < numBlocks);
+    assert(value >= 0);
+    resistance[static_cast<size_t>(block)] = value;
+  }
+
+  double getBlastResistance(Block block) const {
+    return resistance[static_cast<size_t>(block)];
+  }
+
+  // CSG edits only replace a block if its priority is at most that of the
+  // block replacing it, which is air when carving. Raising a block's
+  // priority, e.g. for ores or bedrock, keeps building tools from carving it.
+  void setEditPriority(Block block, uint8_t value) {
+    assert(static_cast<size_t>(block) < numBlocks);
+    priority[static_cast<size_t>(block)] = value;
+  }
+
+  int getEditPriority(Block block) const {
+    return priority[static_cast<size_t>(block)];
+  }
+
+  // Blocks with a trigger raise events as entities overlap its volume. See
+  // triggers.h and World::stepTriggers.
+  void setTrigger(Block block, const TriggerVolume& value) {
+    assert(static_cast<size_t>(block) < numBlocks);
+    assert(value.type < kMaxTriggerTypes);
+    triggers[static_cast<size_t>(block)] = value;
+  }
+
+  const TriggerVolume& getTrigger(Block block) const {
+    return triggers[static_cast<size_t>(block)];
+  }
+
+  void setPower(Block block, PowerData data) {
+    assert(static_cast<size_t>(block) < numBlocks);
+    assert(data.level <= kMaxPowerLevel);
+    power[static_cast<size_t>(block)] = data;
+  }
+
+  const PowerData& getPower(Block block) const {
+    return power[static_cast<size_t>(block)];
+  }
+
+  void setSapling(Block block, SaplingData data) {
+    assert(static_cast<size_t>(block) < numBlocks);
+    saplings[static_cast<size_t>(block)] = data;
+  }
+
+  const SaplingData& getSapling(Block block) const {
+    return saplings[static_cast<size_t>(block)];
+  }
+
+  void setSpread(Block block, SpreadData data) {
+    assert(static_cast<size_t>(block) < numBlocks);
+    spread[static_cast<size_t>(block)] = data;
+  }
+
+  const SpreadData& getSpread(Block block) const {
+    return spread[static_cast<size_t>(block)];
+  }
+
+  void setFire(Block block, FireData data) {
+    assert(static_cast<size_t>(block) < numBlocks);
+    fire[static_cast<size_t>(block)] = data;
+  }
+
+  const FireData& getFire(Block block) const {
+    return fire[static_cast<size_t>(block)];
+  }
+
+  // The block used for fire, or Block::Air if fire is disabled.
+  void setFireBlock(Block block) {
+    assert(static_cast<size_t>(block) < numBlocks);
+    fireBlock = block;
+  }
+
+  Block getFireBlock() const { return fireBlock; }
+
+  // Returns the time in seconds to break the block, or -1 if it can't be.
+  double getBreakTime(Block block, int tool, int tier) const {
+    return voxels::getBreakTime(getMining(block), tool, tier);
+  }
+
+  // Rolls the block's drops for the given tool, using seed for randomness.
+  void getDrops(Block block, int tool, int tier, uint32_t seed,
+                std::vector<DropRecord>& result) const {
+    drop_tables.roll(block, getMining(block), tool, tier, seed, result);
+  }
+
  private:
   size_t numBlocks = 0;
   size_t numMaterials = 0;
+  uint32_t version = 0;
 
   NonCopyArray<BlockData, 256> blocks;
   NonCopyArray<MaterialData, 256> materials;
+  NonCopyArray<MiningData, 256> mining;
+  NonCopyArray<FireData, 256> fire;
+  NonCopyArray<SpreadData, 256> spread;
+  NonCopyArray<uint8_t, 256> tags;
+  NonCopyArray<uint8_t, 256> hooks;
+  NonCopyArray<uint8_t, 256> attenuation;
+  NonCopyArray<double, 256> resistance;
+  NonCopyArray<uint8_t, 256> priority;
+  NonCopyArray<TriggerVolume, 256> triggers;
+  NonCopyArray<SaplingData, 256> saplings;
+  NonCopyArray<PowerData, 256> power;
+  std::vector<TreeSpecies> trees;
+  std::vector<ProjectileKind> projectiles;
+  Block fireBlock = Block::Air;
+  DropTables drop_tables;
 
   DISALLOW_COPY_AND_ASSIGN(Registry);
 };
 
+// Greedily merges an lu x lv mask, stored with v as the fast axis, into
+// maximal rectangles of equal non-zero values. Calls fn(iu, iv, w, h, value)
+// for each rectangle with extent w along u and h along v, and clears the mask
+// as it goes. If row_union is set, rows with row_union[iu] == 0 are skipped.
+template <typename Fn>
+void greedyRects(int* mask, const int* row_union, int lu, int lv, Fn&& fn) {
+  auto n = 0;
+  for (auto iu = 0; iu < lu; iu++) {
+    if (row_union && row_union[iu] == 0) {
+      n += lv;
+      continue;
+    }
+
+    auto h = 1;
+    for (auto iv = 0; iv < lv; iv += h, n += h) {
+      const auto value = mask[n];
+      if (value == 0) {
+        h = 1;
+        continue;
+      }
+
+      for (h = 1; h < lv - iv; h++) {
+        if (value != mask[n + h]) break;
+      }
+
+      auto w = 1;
+      auto nw = n + lv;
+      for (; w < lu - iu; w++, nw += lv) {
+        for (auto x = 0; x < h; x++) {
+          if (value != mask[nw + x]) goto done;
+        }
+      }
+      done:
+
+      fn(iu, iv, w, h, value);
+
+      nw = n;
+      for (auto wx = 0; wx < w; wx++, nw += lv) {
+        for (auto hx = 0; hx < h; hx++) {
+          mask[nw + hx] = 0;
+        }
+      }
+    }
+  }
+}
+
+//////////////////////////////////////////////////////////////////////////////
+
+// Problems found by Mesher::validateGeometry. Each count is a number of quads,
+// except for overlaps, holes, and extras, which count unit face cells:
+//
+//   - duplicates:   quads identical to an earlier quad
+//   - overlaps:     faces covered by more than one quad
+//   - holes:        faces that should be visible but aren't covered
+//   - extras:       faces that are covered but that should be hidden
+//   - out_of_range: quads outside the chunk, or with a bad d or index field
+//
+struct MeshReport {
+  bool valid() const {
+    return duplicates + overlaps + holes + extras + out_of_range == 0;
+  }
+
+  int quads = 0;
+  int duplicates = 0;
+  int overlaps = 0;
+  int holes = 0;
+  int extras = 0;
+  int out_of_range = 0;
+};
+
+// An indexed triangle mesh, for consumers that can't draw our instanced quads
+// (exporters, physics, or a fallback renderer). Each vertex is four ints:
+// x, y, z, and attributes, packed as:
+//
+//   texture | ao << 8 | wave << 10 | d << 11 | dir << 13 |
+//   uv_transform << 14 | bright << 17
+//
+// where ao and wave are the corner's values, (d, dir) is the face id, and
+// bright is set on full-bright quads, which consumers shouldn't light.
+// UVs aren't stored: consumers derive them from the position and face id, so
+// that corners shared by adjacent quads have equal attributes.
+//
+// Repeats are optional. If requested, they hold two per vertex: the size of
+// its quad along the face's axes (d + 1) % 3 and (d + 2) % 3, which is how
+// many times the texture tiles across it. Renderers that pack textures in
+// an atlas, without texture arrays, need them to wrap each tile in the
+// fragment shader, because the sampler's wrapping would bleed into other
+// tiles. Corners of quads of different sizes don't weld.
+//
+// Each quad is two triangles, so six indices, in the order of the quads.
+// quad_materials holds the material of each quad, if the caller knows it.
+struct IndexedMesh {
+  std::vector<int32_t> vertices;
+  std::vector<uint32_t> indices;
+  std::vector<uint16_t> repeats;
+  std::vector<MaybeMaterial> quad_materials;
+};
+
+// Converts quads to an indexed mesh, welding corners whose position and
+// attributes match exactly. Quads that can't merge because of AO or material
+// changes still share many corners, so this saves 20-40% of the vertices on
+// busy chunks. Appends to the result, welding only the new vertices.
+void weldQuads(const VoxelMesh::Quads& quads, IndexedMesh& result,
+               bool repeats);
+
+// A draw's worth of an indexed mesh, for constrained GL (WebGL1 on older
+// mobile hardware) without texture arrays or 32-bit indices. Every triangle
+// in a batch samples the same atlas page, and a batch has at most
+// kMaxBatchVertices vertices, so that its indices fit in 16 bits. Repeats
+// and quad materials have the same layout as in IndexedMesh.
+//
+// WebGL1 has no integer attributes, and its shaders have no bitwise ops, so
+// we unpack each vertex into kBatchVertexFloats floats:
+//
+//   x, y, z, texture, ao, wave, d, dir, uv_transform, bright
+constexpr int kMaxBatchVertices = 1 << 16;
+constexpr int kBatchVertexFloats = 10;
+
+struct MeshBatch {
+  int page;
+  std::vector<float> vertices;
+  std::vector<uint16_t> indices;
+  std::vector<uint16_t> repeats;
+  std::vector<MaybeMaterial> quad_materials;
+};
+
+// Splits an indexed mesh into batches by atlas page, where pages maps each
+// texture id to its page, and then by vertex count. Batches come out in page
+// order, and a page's quads keep their order. Vertices shared between
+// batches are duplicated. Clears the result first.
+void batchIndexedMesh(const IndexedMesh& mesh, const uint8_t* pages,
+                      std::vector<MeshBatch>& result);
+
+// Algorithms that meshChunk can use. They share the cache and the rest of the
+// GeometryData plumbing, and they all emit quads in the same layout.
+//
+//   - Greedy: merges faces into large quads, with AO and liquid waves.
+//
+//   - Naive: one unit quad per visible face, with no AO. It's much slower to
+//     render, but it's useful for debugging and as a baseline for experiments.
+//
+//   - SurfaceNets and MarchingCubes: smooth meshers, which emit the surface
+//     of opaque blocks as triangles in smooth_geo. See smooth.h. Other blocks,
+//     like liquids and glass, are still greedy-meshed quads.
+//
+enum class MesherKind : uint8_t {
+  Greedy, Naive, SurfaceNets, MarchingCubes, Count
+};
+
+// A bitmask of face directions, for meshing far chunks that the camera can
+// only see from one side. Bit (2 * d + (dir > 0 ? 1 : 0)) is the face whose
+// normal is dir along axis d, so the bits are [-x, +x, -y, +y, -z, +z].
+constexpr int kAllFaces = (1 << 6) - 1;
+
 struct Mesher {
   struct HeightmapField { Block block; uint8_t height; };
   struct HeightmapEntry { std::array<HeightmapField, 2> fields; };
 
   Mesher(const Registry& r);
 
-  void meshChunk();
+  // Meshes voxels in the (unpadded) levels [y_min, y_max) of the chunk,
+  // using the algorithm selected by `kind`.
+  void meshChunk(int y_min = 0, int y_max = kWorldHeight);
+  // Meshes an n x n heightmap whose corner is at the render-space point pos.
   void meshFrontier(const HeightmapEntry* start, int n,
                     Point pos, int scale, int index);
 
+  // Meshes an n x n heightmap at full resolution, without the voxel tensor:
+  // a fast path for superflat worlds and menu backgrounds. The output has
+  // the same layout as meshChunk's. pos is the render-space (x, z) position
+  // of the heightmap's corner, and column (x, z) is at index x + n * z.
+  // Ignores the frontier decimation and skirt settings.
+  void meshHeightmap(const HeightmapEntry* start, int n, Point pos);
+
+  // A reference mesher, for testing: it emits one unit quad per visible face
+  // in the (unpadded) levels [y_min, y_max), with no merging and no AO. Its
+  // output covers the same faces that meshChunk's should.
+  void meshReference(int y_min = 0, int y_max = kWorldHeight);
+
+  // Checks the output of meshChunk(y_min, y_max) against meshReference's,
+  // adding problems to the report. It's slow: use it in debug builds or as a
+  // fuzzing oracle for mesher changes. It doesn't check AO or materials.
+  void validateGeometry(int y_min, int y_max, MeshReport& report);
+
  private:
   using Quad  = VoxelMesh::Quad;
   using Quads = VoxelMesh::Quads;
   using Pos = std::array<int, 3>;
 
+  // The inputs that determine a chunk's geometry. The voxels include a
+  // border from neighboring chunks, and the registry version covers block
+  // and material changes, so equal keys produce equal geometry.
+  struct CacheKey {
+    int y_min;
+    int y_max;
+    MesherKind kind;
+    int face_mask;
+    uint32_t version;
+    std::vector<Block> voxels;
+
+    bool operator==(const CacheKey& o) const {
+      return y_min == o.y_min && y_max == o.y_max && kind == o.kind &&
+             face_mask == o.face_mask && version == o.version &&
+             voxels == o.voxels;
+    }
+  };
+
+  // An LRU cache of chunk geometry, indexed by a hash of its key. We keep
+  // the full key, too, and check it on lookup, so a hash collision is just
+  // a cache miss rather than another chunk's geometry.
+  struct CachedGeometry {
+    uint64_t hash;
+    CacheKey key;
+    std::vector<Quad> solid_geo;
+    std::vector<Quad> water_geo;
+    std::vector<MaybeMaterial> solid_materials;
+    std::vector<MaybeMaterial> water_materials;
+    std::vector<float> smooth_geo;
+  };
+  using MeshCache = std::list<CachedGeometry>;
+
   void addQuad(Quads* quads, const MaterialData& material, int dir, int ao,
                int wave, int d, int w, int h, const Pos& pos);
   void computeChunkGeometry(int y_min, int y_max);
+  void computeNaiveGeometry(int y_min, int y_max, int mask,
+                            Quads* solid, Quads* water);
+  void computeLevelGeometry(int y_min, int y_max);
+  void computeSmoothGeometry(int y_min, int y_max);
+  bool smooth() const;
+
+  bool lookupCachedGeometry(uint64_t hash, const CacheKey& key);
+  void insertCachedGeometry(uint64_t hash, const CacheKey& key);
+  uint64_t computeCacheKey(int y_min, int y_max, CacheKey& key) const;
 
   void computeFrontierGeometry(
       Quads* quads, const HeightmapField* start, int n, Point pos,
@@ -146,25 +610,75 @@ struct Mesher {
       Quads* quads, const MaterialData& material, int dir, int ao,
       int wave, int d, int w, int h, const Pos& pos);
 
+  void tagMaterials(const Quads* quads, MaybeMaterial id);
+
   bool getTriangleHint(int ao) const;
   int getFaceDir(Block block0, Block block1, int face) const;
   int packAOMask(int ipos, int ineg, int dj, int dk) const;
 
  public:
+  // meshChunk input: the render-space position of the chunk's (0, 0, 0)
+  // voxel, i.e. its world-space position minus the render origin. We add it
+  // to the quads that we emit, so that every mesh is in render space and can
+  // go straight into a shared vertex pool, with no per-mesh transform. It's
+  // not part of the cache key: we cache quads before adding it.
+  std::array<int, 3> origin = {0, 0, 0};
+
+  // meshChunk input: the meshing algorithm. It's part of the cache key, so
+  // it's safe to change between calls.
+  MesherKind kind = MesherKind::Greedy;
+
+  // meshChunk input: the face directions to emit. Alpha-tested faces are
+  // always emitted, because they're visible from both sides.
+  int face_mask = kAllFaces;
+
   // meshChunk inputs: set up these values prior to the call.
   MeshTensor1<uint8_t> equilevels;
   MeshTensor2<uint8_t> heightmap;
   MeshTensor3<Block> voxels;
 
-  // meshChunk outputs: read these values after the call.
+  // meshFrontier input: the max error, in voxels, for decimating frontier
+  // geometry. We snap heightmap heights to multiples of twice this value, so
+  // that noisy terrain collapses into fewer, larger quads, at the cost of a
+  // slightly wrong silhouette. 0 disables decimation.
+  int frontier_error = 0;
+
+  // meshFrontier input: the depth of skirts, in voxels. Heightmap columns on
+  // a frontier chunk's border get side quads that extend this far down, to
+  // hide cracks against neighbors at a different LOD. The default runs them
+  // to the bottom of the world, which is watertight but costs fill rate.
+  int frontier_skirt = kWorldHeight;
+
+  // meshChunk outputs: read these values after the call. The materials hold
+  // the material of each quad in the geometry, for custom renderers that
+  // bucket or recolor quads by material. meshFrontier sets them, too.
   std::vector<Quad> solid_geo;
   std::vector<Quad> water_geo;
+  std::vector<MaybeMaterial> solid_materials;
+  std::vector<MaybeMaterial> water_materials;
+
+  // meshChunk output for the smooth meshers: the surface of opaque blocks,
+  // as triangles with kSmoothVertexFloats floats per vertex. It's empty for
+  // the other meshers.
+  std::vector<float> smooth_geo;
+
+  // meshReference output.
+  std::vector<Quad> reference_geo;
+
+  // Compares the unit faces covered by two meshes of the levels [y_min,
+  // y_max), counting holes, extras, and overlaps in `actual` in the report.
+  // Downstream mesher changes can use this to check against meshReference.
+  static void compareGeometry(int y_min, int y_max, const Quads& expected,
+                              const Quads& actual, MeshReport& report);
 
  private:
   const Registry& registry;
-  std::vector<int> mask_data;
-  std::vector<int> mask_union;
-  std::vector<HeightmapField> height_mask;
+  std::vector<Quad> validate_geo;
+  SmoothTextures smooth_textures;
+
+  MeshCache cache;
+  CacheKey cache_key;
+  HashMap<uint64_t, MeshCache::iterator> cache_index;
 
   DISALLOW_COPY_AND_ASSIGN(Mesher);
 };
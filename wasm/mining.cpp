@@ -0,0 +1,64 @@
+#include "mining.h"
+
+#include <algorithm>
+#include <cassert>
+#include <iterator>
+
+//////////////////////////////////////////////////////////////////////////////
+
+namespace voxels {
+
+//////////////////////////////////////////////////////////////////////////////
+
+namespace {
+
+// The speed multiplier for breaking blocks with the preferred tool, by tier.
+constexpr double kToolTierSpeeds[] = {1.0, 2.0, 4.0, 6.0, 8.0, 12.0};
+
+} // namespace
+
+//////////////////////////////////////////////////////////////////////////////
+
+double getBreakTime(const MiningData& data, int tool, int tier) {
+  if (data.hardness < 0) return -1;
+
+  const auto preferred = data.tool != 0 && tool == data.tool;
+  const auto harvest = data.tool == 0 || (preferred && tier >= data.tier);
+  const auto base = data.hardness * (harvest ? 1.5 : 5.0);
+  if (!preferred) return base;
+
+  constexpr auto kTiers = static_cast<int>(std::size(kToolTierSpeeds));
+  return base / kToolTierSpeeds[std::clamp(tier, 0, kTiers - 1)];
+}
+
+void DropTables::add(int table, DropEntry entry) {
+  assert(0 <= table);
+  assert(entry.min <= entry.max);
+  if (table >= tables.size()) tables.resize(table + 1);
+  tables[table].push_back(entry);
+}
+
+void DropTables::roll(Block block, const MiningData& data, int tool,
+                      int tier, uint32_t seed,
+                      std::vector<DropRecord>& result) const {
+  result.clear();
+  if (data.tool != 0 && (tool != data.tool || tier < data.tier)) return;
+
+  if (data.drop_table < 0) {
+    result.push_back({static_cast<int>(block), 1});
+    return;
+  }
+  if (data.drop_table >= tables.size()) return;
+
+  auto rng = Random(seed);
+  for (const auto& entry : tables[data.drop_table]) {
+    if (rng.uniform() >= entry.chance) continue;
+    const auto range = entry.max - entry.min + 1;
+    const auto count = entry.min + static_cast<int>(rng.uniform() * range);
+    if (count > 0) result.push_back({entry.item, count});
+  }
+}
+
+//////////////////////////////////////////////////////////////////////////////
+
+} // namespace voxels
@@ -0,0 +1,58 @@
+#pragma once
+
+#include <cstdint>
+#include <vector>
+
+#include "base.h"
+
+//////////////////////////////////////////////////////////////////////////////
+
+namespace voxels {
+
+//////////////////////////////////////////////////////////////////////////////
+
+// Mining: how long blocks take to break, and what they drop. The registry
+// stores each block's MiningData and the drop tables; see Registry::setMining
+// and Registry::addDropEntry. For richer drops, with pools, weights, and
+// fortune, see the loot tables in loot.h.
+
+// Gameplay data for breaking a block. A negative hardness is unbreakable.
+// A block with a tool class only drops items if it's broken with that tool
+// at the given tier or above. A drop table of -1 drops the block itself.
+struct MiningData {
+  double hardness = 0.0;
+  uint8_t tool = 0;
+  uint8_t tier = 0;
+  int drop_table = -1;
+};
+
+// Each entry in a drop table independently drops [min, max] of the item,
+// uniformly at random, with the given chance.
+struct DropEntry {
+  int item;
+  int min;
+  int max;
+  double chance;
+};
+
+struct DropRecord { int item; int count; };
+
+// Returns the time in seconds to break a block with the given data, or -1
+// if it can't be broken.
+double getBreakTime(const MiningData& data, int tool, int tier);
+
+struct DropTables {
+  void add(int table, DropEntry entry);
+
+  // Rolls the drops of a block with the given data for the given tool,
+  // using seed for randomness. Clears the result first.
+  void roll(Block block, const MiningData& data, int tool, int tier,
+            uint32_t seed, std::vector<DropRecord>& result) const;
+
+ private:
+  std::vector<std::vector<DropEntry>> tables;
+};
+
+//////////////////////////////////////////////////////////////////////////////
+
+} // namespace voxels
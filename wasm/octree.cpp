@@ -0,0 +1,143 @@
+#include "octree.h"
+
+#include <algorithm>
+
+//////////////////////////////////////////////////////////////////////////////
+
+namespace voxels {
+
+//////////////////////////////////////////////////////////////////////////////
+
+namespace {
+
+constexpr uint32_t kOctreeLeaf = 1u << 31;
+
+constexpr uint32_t leaf(Block block) {
+  return kOctreeLeaf | static_cast<uint32_t>(block);
+}
+
+constexpr Block leafBlock(uint32_t node) {
+  return static_cast<Block>(node & 0xff);
+}
+
+bool isLiquid(const Registry& registry, Block block) {
+  const auto face = registry.getBlockUnsafe(block).faces[0];
+  if (face == kNoMaterial) return false;
+  return registry.getMaterial(assertMaterial(face)).liquid;
+}
+
+} // namespace
+
+//////////////////////////////////////////////////////////////////////////////
+
+Octree::Octree(const Block* voxels) {
+  for (auto i = 0; i < kOctreeRoots; i++) {
+    roots[i] = build(voxels, 0, i * kChunkWidth, 0, kChunkWidth);
+  }
+}
+
+Block Octree::get(int x, int y, int z) const {
+  auto size = 0;
+  return find(x, y, z, size);
+}
+
+void Octree::toDense(Block* voxels) const {
+  for (auto i = 0; i < kOctreeRoots; i++) {
+    fill(roots[i], voxels, 0, i * kChunkWidth, 0, kChunkWidth);
+  }
+}
+
+void Octree::computeHeightmap(const Registry& registry, int step,
+                              Mesher::HeightmapEntry* result) const {
+  assert(0 < step && step <= kChunkWidth);
+  assert(kChunkWidth % step == 0);
+
+  const auto n = kChunkWidth / step;
+  for (auto j = 0; j < n; j++) {
+    for (auto i = 0; i < n; i++) {
+      const auto x = step * i + step / 2, z = step * j + step / 2;
+      auto& entry = result[i + n * j];
+      entry.fields[0] = {Block::Air, 0};
+      entry.fields[1] = {Block::Air, 0};
+
+      // Walk down the column, skipping each uniform cube in one step.
+      for (auto y = kWorldHeight - 1; y >= 0;) {
+        auto size = 0;
+        const auto block = find(x, y, z, size);
+        const auto height = static_cast<uint8_t>(y + 1);
+        const auto& data = registry.getBlockUnsafe(block);
+        if (data.opaque) {
+          entry.fields[0] = {block, height};
+          break;
+        } else if (entry.fields[1].block == Block::Air &&
+                   isLiquid(registry, block)) {
+          entry.fields[1] = {block, height};
+        }
+        y = (y & ~(size - 1)) - 1;
+      }
+    }
+  }
+}
+
+Block Octree::find(int x, int y, int z, int& size) const {
+  assert(0 <= x && x < kChunkWidth);
+  assert(0 <= y && y < kWorldHeight);
+  assert(0 <= z && z < kChunkWidth);
+
+  size = kChunkWidth;
+  auto node = roots[y >> kChunkBits];
+  y &= kChunkWidth - 1;
+  while (!(node & kOctreeLeaf)) {
+    size >>= 1;
+    const auto child = ((x & size) ? 1 : 0) |
+                       ((y & size) ? 2 : 0) |
+                       ((z & size) ? 4 : 0);
+    node = nodes[node + child];
+  }
+  return leafBlock(node);
+}
+
+uint32_t Octree::build(const Block* voxels, int x, int y, int z, int size) {
+  if (size == 1) return leaf(voxels[ChunkTensor3<Block>::index(x, y, z)]);
+
+  // Build the children first, so that each node's children are contiguous.
+  std::array<uint32_t, 8> children;
+  const auto half = size >> 1;
+  for (auto i = 0; i < 8; i++) {
+    children[i] = build(voxels, x + ((i & 1) ? half : 0),
+                        y + ((i & 2) ? half : 0),
+                        z + ((i & 4) ? half : 0), half);
+  }
+
+  const auto first = children[0];
+  auto uniform = (first & kOctreeLeaf) != 0;
+  for (auto i = 1; uniform && i < 8; i++) uniform = children[i] == first;
+  if (uniform) return first;
+
+  const auto result = static_cast<uint32_t>(nodes.size());
+  nodes.insert(nodes.end(), children.begin(), children.end());
+  return result;
+}
+
+void Octree::fill(uint32_t node, Block* voxels,
+                  int x, int y, int z, int size) const {
+  if (node & kOctreeLeaf) {
+    const auto block = leafBlock(node);
+    for (auto dz = 0; dz < size; dz++) {
+      for (auto dx = 0; dx < size; dx++) {
+        const auto index = ChunkTensor3<Block>::index(x + dx, y, z + dz);
+        std::fill(voxels + index, voxels + index + size, block);
+      }
+    }
+    return;
+  }
+  const auto half = size >> 1;
+  for (auto i = 0; i < 8; i++) {
+    fill(nodes[node + i], voxels, x + ((i & 1) ? half : 0),
+         y + ((i & 2) ? half : 0), z + ((i & 4) ? half : 0), half);
+  }
+}
+
+//////////////////////////////////////////////////////////////////////////////
+
+} // namespace voxels
@@ -0,0 +1,61 @@
+#pragma once
+
+#include <array>
+#include <cstdint>
+#include <vector>
+
+#include "base.h"
+#include "mesher.h"
+
+//////////////////////////////////////////////////////////////////////////////
+
+namespace voxels {
+
+//////////////////////////////////////////////////////////////////////////////
+
+// A sparse voxel octree copy of a chunk's blocks, for far, read-mostly
+// chunks. A chunk column is a stack of kOctreeRoots cubes, each with side
+// kChunkWidth, and each cube is a tree whose uniform subtrees collapse into
+// a single leaf. Terrain is mostly runs of air and stone, so an octree takes
+// a small fraction of the dense chunk's 64 KB.
+//
+// Octrees are immutable. To edit one, convert it back to dense voxels with
+// toDense and rebuild it later.
+
+constexpr int kOctreeRoots = kWorldHeight / kChunkWidth;
+
+struct Octree {
+  // Builds an octree from voxels in chunk index order.
+  explicit Octree(const Block* voxels);
+
+  Block get(int x, int y, int z) const;
+
+  // Writes all of the octree's voxels, in chunk index order.
+  void toDense(Block* voxels) const;
+
+  // Samples a (kChunkWidth / step)^2 grid of columns, at (step * i + step / 2)
+  // in x and z, as heightmap entries for Mesher::meshFrontier. The solid field
+  // is the highest opaque block and the water field is the liquid above it,
+  // if any. Entry (i, j) is at index i + (kChunkWidth / step) * j.
+  void computeHeightmap(const Registry& registry, int step,
+                        Mesher::HeightmapEntry* result) const;
+
+  size_t bytes() const { return sizeof(uint32_t) * nodes.size(); }
+
+ private:
+  // Returns the block at (x, y, z) and sets size to the side of the uniform
+  // cube, aligned to its size, that contains it.
+  Block find(int x, int y, int z, int& size) const;
+
+  uint32_t build(const Block* voxels, int x, int y, int z, int size);
+  void fill(uint32_t node, Block* voxels, int x, int y, int z, int size) const;
+
+  // A node is either a leaf (kOctreeLeaf | block) or the index in `nodes`
+  // of its 8 children, where child i is offset by (i & 1, i & 2, i & 4).
+  std::array<uint32_t, kOctreeRoots> roots;
+  std::vector<uint32_t> nodes;
+};
+
+//////////////////////////////////////////////////////////////////////////////
+
+} // namespace voxels
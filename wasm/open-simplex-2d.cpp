@@ -104,7 +104,16 @@ Noise2D::Noise2D(uint32_t seed) {
 }
 
 double Noise2D::query(double x, double y) const {
-  const auto stretchOffset = (x + y) * kStretch2D;
+  return queryImpl(x, y);
+}
+
+Fixed Noise2D::query(Fixed x, Fixed y) const {
+  return queryImpl(x, y);
+}
+
+template <typename T>
+T Noise2D::queryImpl(T x, T y) const {
+  const auto stretchOffset = (x + y) * T(kStretch2D);
 
   const auto xs = x + stretchOffset;
   const auto ys = y + stretchOffset;
@@ -112,7 +121,7 @@ double Noise2D::query(double x, double y) const {
   const auto xsb = floor(xs);
   const auto ysb = floor(ys);
 
-  const auto squishOffset = (xsb + ysb) * kSquish2D;
+  const auto squishOffset = (xsb + ysb) * T(kSquish2D);
 
   const auto dx0 = x - (xsb + squishOffset);
   const auto dy0 = y - (ysb + squishOffset);
@@ -126,12 +135,12 @@ double Noise2D::query(double x, double y) const {
                     (static_cast<int>(inSum + yins)    << 2) |
                     (static_cast<int>(inSum + xins)    << 4);
 
-  auto value = 0.0;
+  auto value = T(0);
   const auto index = kPrecomputation.lookup[hash];
 
   for (const auto& contribution : kPrecomputation.contributions[index]) {
-    const auto dx = dx0 + contribution.dx;
-    const auto dy = dy0 + contribution.dy;
+    const auto dx = dx0 + T(contribution.dx);
+    const auto dy = dy0 + T(contribution.dy);
 
     const auto attn = 2 - dx * dx - dy * dy;
     if (attn <= 0) continue;
@@ -150,7 +159,7 @@ double Noise2D::query(double x, double y) const {
     const auto valuePart = gradientX * dx + gradientY * dy;
     value += attn * attn * attn * attn * valuePart;
   }
-  return value * kNorm2D;
+  return value * T(kNorm2D);
 }
 
 //////////////////////////////////////////////////////////////////////////////
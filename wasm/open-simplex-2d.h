@@ -3,6 +3,7 @@
 #include <array>
 
 #include "base.h"
+#include "fixed.h"
 
 //////////////////////////////////////////////////////////////////////////////
 
@@ -14,8 +15,12 @@ struct Noise2D {
   Noise2D(uint32_t seed = 0);
 
   double query(double x, double y) const;
+  Fixed query(Fixed x, Fixed y) const;
 
  private:
+  template <typename T>
+  T queryImpl(T x, T y) const;
+
   NonCopyArray<uint8_t, 256> perm;
 
   DISALLOW_COPY_AND_ASSIGN(Noise2D);
@@ -0,0 +1,381 @@
+#include "packets.h"
+
+#include <algorithm>
+#include <cstring>
+
+//////////////////////////////////////////////////////////////////////////////
+
+namespace voxels {
+
+//////////////////////////////////////////////////////////////////////////////
+
+namespace {
+
+struct Writer {
+  void start(PacketType type) {
+    data.assign(sizeof(uint32_t), 0);
+    u8(static_cast<uint8_t>(type));
+  }
+
+  const uint8_t* finish() {
+    const auto size = static_cast<uint32_t>(data.size() - sizeof(uint32_t));
+    memcpy(data.data(), &size, sizeof(size));
+    return data.data();
+  }
+
+  void u8(uint8_t value) {
+    data.push_back(value);
+  }
+
+  void varint(uint32_t value) {
+    for (; value >= 0x80; value >>= 7) {
+      data.push_back(static_cast<uint8_t>(value | 0x80));
+    }
+    data.push_back(static_cast<uint8_t>(value));
+  }
+
+  void svarint(int32_t value) {
+    const auto bits = static_cast<uint32_t>(value);
+    varint((bits << 1) ^ static_cast<uint32_t>(value >> 31));
+  }
+
+  template <typename T>
+  void raw(T value) {
+    uint8_t bytes[sizeof(T)];
+    memcpy(bytes, &value, sizeof(T));
+    data.insert(data.end(), bytes, bytes + sizeof(T));
+  }
+
+  void blob(const std::vector<uint8_t>& value) {
+    varint(static_cast<uint32_t>(value.size()));
+    data.insert(data.end(), value.begin(), value.end());
+  }
+
+  std::vector<uint8_t> data;
+};
+
+struct Reader {
+  Reader(const uint8_t* data, int size, PacketType type)
+      : cur(data), end(data + size) {
+    uint8_t value = 0;
+    ok = size >= 0 && u8(value) && value == static_cast<uint8_t>(type);
+  }
+
+  bool done() const {
+    return ok && cur == end;
+  }
+
+  bool u8(uint8_t& value) {
+    if (cur == end) return ok = false;
+    value = *(cur++);
+    return true;
+  }
+
+  bool varint(uint32_t& value) {
+    value = 0;
+    for (auto shift = 0; shift < 35; shift += 7) {
+      uint8_t byte = 0;
+      if (!u8(byte)) return false;
+      value |= static_cast<uint32_t>(byte & 0x7f) << shift;
+      if (!(byte & 0x80)) return true;
+    }
+    return ok = false;
+  }
+
+  bool svarint(int32_t& value) {
+    uint32_t bits = 0;
+    if (!varint(bits)) return false;
+    value = static_cast<int32_t>((bits >> 1) ^ (~(bits & 1) + 1));
+    return true;
+  }
+
+  template <typename T>
+  bool raw(T& value) {
+    if (end - cur < static_cast<int>(sizeof(T))) return ok = false;
+    memcpy(&value, cur, sizeof(T));
+    cur += sizeof(T);
+    return true;
+  }
+
+  bool block(Block& value) {
+    uint8_t byte = 0;
+    if (!u8(byte)) return false;
+    value = static_cast<Block>(byte);
+    return true;
+  }
+
+  bool blob(std::vector<uint8_t>& value) {
+    uint32_t size = 0;
+    if (!varint(size)) return false;
+    if (end - cur < static_cast<int64_t>(size)) return ok = false;
+    value.assign(cur, cur + size);
+    cur += size;
+    return true;
+  }
+
+  const uint8_t* cur;
+  const uint8_t* end;
+  bool ok;
+};
+
+Writer writer;
+
+} // namespace
+
+//////////////////////////////////////////////////////////////////////////////
+
+const uint8_t* encodeChunkData(int cx, int cz, const Block* voxels,
+                               const std::vector<uint8_t>& extra,
+                               const std::vector<BlockEntity>& entities) {
+  writer.start(PacketType::ChunkData);
+  writer.svarint(cx);
+  writer.svarint(cz);
+  for (auto i = 0; i < kChunkVoxels;) {
+    const auto block = voxels[i];
+    auto j = i + 1;
+    while (j < kChunkVoxels && voxels[j] == block) j++;
+    writer.u8(static_cast<uint8_t>(block));
+    writer.varint(static_cast<uint32_t>(j - i));
+    i = j;
+  }
+  writer.blob(extra);
+  writer.varint(static_cast<uint32_t>(entities.size()));
+  for (const auto& entity : entities) {
+    writer.varint(static_cast<uint32_t>(entity.index));
+    writer.svarint(entity.type);
+    writer.blob(entity.data);
+  }
+  return writer.finish();
+}
+
+bool decodeChunkData(const uint8_t* data, int size, int& cx, int& cz,
+                     Block* voxels, std::vector<uint8_t>& extra,
+                     std::vector<BlockEntity>& entities) {
+  Reader reader(data, size, PacketType::ChunkData);
+  if (!reader.svarint(cx) || !reader.svarint(cz)) return false;
+  for (auto i = 0; i < kChunkVoxels;) {
+    auto block = Block::Air;
+    uint32_t run = 0;
+    if (!reader.block(block) || !reader.varint(run)) return false;
+    if (run == 0 || run > static_cast<uint32_t>(kChunkVoxels - i)) {
+      return false;
+    }
+    std::fill(voxels + i, voxels + i + run, block);
+    i += static_cast<int>(run);
+  }
+  extra.clear();
+  entities.clear();
+  if (reader.done()) return true;
+  if (!reader.blob(extra)) return false;
+  if (reader.done()) return true;
+
+  // Each entity takes at least 3 bytes, which bounds a valid count.
+  uint32_t count = 0;
+  if (!reader.varint(count)) return false;
+  if (count > static_cast<uint32_t>(size / 3)) return false;
+  for (auto i = 0; i < count; i++) {
+    uint32_t index = 0;
+    BlockEntity entity;
+    if (!reader.varint(index) || !reader.svarint(entity.type) ||
+        !reader.blob(entity.data)) {
+      return false;
+    }
+    if (index >= static_cast<uint32_t>(kChunkVoxels)) return false;
+    entity.index = static_cast<int>(index);
+    entities.push_back(std::move(entity));
+  }
+  return reader.done();
+}
+
+const uint8_t* encodeChunkDelta(int cx, int cz,
+                                const std::vector<ChunkEdit>& edits) {
+  writer.start(PacketType::ChunkDelta);
+  writer.svarint(cx);
+  writer.svarint(cz);
+  writer.varint(static_cast<uint32_t>(edits.size()));
+  for (const auto& edit : edits) {
+    assert(0 <= edit.index && edit.index < kChunkVoxels);
+    writer.varint(static_cast<uint32_t>(edit.index));
+    writer.u8(static_cast<uint8_t>(edit.block));
+  }
+  return writer.finish();
+}
+
+bool decodeChunkDelta(const uint8_t* data, int size,
+                      int& cx, int& cz, std::vector<ChunkEdit>& edits) {
+  Reader reader(data, size, PacketType::ChunkDelta);
+  uint32_t count = 0;
+  if (!reader.svarint(cx) || !reader.svarint(cz)) return false;
+  if (!reader.varint(count) || count > kChunkVoxels) return false;
+
+  edits.clear();
+  for (auto i = 0; i < count; i++) {
+    uint32_t index = 0;
+    auto block = Block::Air;
+    if (!reader.varint(index) || !reader.block(block)) return false;
+    if (index >= kChunkVoxels) return false;
+    edits.push_back({static_cast<int>(index), block});
+  }
+  return reader.done();
+}
+
+const uint8_t* encodeBlockEdit(const BlockEdit& edit) {
+  writer.start(PacketType::BlockEdit);
+  writer.svarint(edit.x);
+  writer.svarint(edit.y);
+  writer.svarint(edit.z);
+  writer.u8(static_cast<uint8_t>(edit.block));
+  return writer.finish();
+}
+
+bool decodeBlockEdit(const uint8_t* data, int size, BlockEdit& edit) {
+  Reader reader(data, size, PacketType::BlockEdit);
+  return reader.svarint(edit.x) && reader.svarint(edit.y) &&
+         reader.svarint(edit.z) && reader.block(edit.block) &&
+         reader.done();
+}
+
+const uint8_t* encodeEntitySnapshot(
+    double time, const EntityTransform* transforms, int count) {
+  writer.start(PacketType::EntitySnapshot);
+  writer.raw(time);
+  writer.varint(static_cast<uint32_t>(count));
+  for (auto i = 0; i < count; i++) {
+    const auto& transform = transforms[i];
+    writer.svarint(transform.id);
+    writer.raw(transform.x);
+    writer.raw(transform.y);
+    writer.raw(transform.z);
+    writer.raw(transform.heading);
+  }
+  return writer.finish();
+}
+
+bool decodeEntitySnapshot(const uint8_t* data, int size, double& time,
+                          std::vector<EntityTransform>& transforms) {
+  Reader reader(data, size, PacketType::EntitySnapshot);
+  uint32_t count = 0;
+  if (!reader.raw(time) || !reader.varint(count)) return false;
+
+  // Each transform takes at least 29 bytes, which bounds a valid count.
+  if (count > static_cast<uint32_t>(size / 29)) return false;
+
+  transforms.clear();
+  for (auto i = 0; i < count; i++) {
+    EntityTransform transform;
+    if (!reader.svarint(transform.id) || !reader.raw(transform.x) ||
+        !reader.raw(transform.y) || !reader.raw(transform.z) ||
+        !reader.raw(transform.heading)) {
+      return false;
+    }
+    transforms.push_back(transform);
+  }
+  return reader.done();
+}
+
+const uint8_t* encodeRegistrySync(const Registry& registry) {
+  writer.start(PacketType::RegistrySync);
+  const auto count = registry.getNumBlocks();
+  writer.varint(static_cast<uint32_t>(count));
+  for (auto i = 0; i < count; i++) {
+    const auto& data = registry.getBlock(static_cast<Block>(i));
+    writer.u8(static_cast<uint8_t>(
+        (data.mesh ? 1 : 0) | (data.opaque ? 2 : 0) | (data.solid ? 4 : 0)));
+    writer.u8(static_cast<uint8_t>(data.light));
+    for (const auto& face : data.faces) writer.u8(face.id);
+  }
+  const auto materials = registry.getNumMaterials();
+  writer.varint(static_cast<uint32_t>(materials));
+  for (auto i = 0; i < materials; i++) {
+    const auto& data = registry.getMaterial({static_cast<uint8_t>(i)});
+    writer.u8(static_cast<uint8_t>((data.liquid ? 1 : 0) |
+                                   (data.alphaTest ? 2 : 0) |
+                                   (data.fullBright ? 4 : 0)));
+    writer.u8(data.texture);
+    writer.u8(data.uv_transform);
+    for (const auto& channel : data.color) writer.raw(channel);
+  }
+  return writer.finish();
+}
+
+bool decodeRegistrySync(const uint8_t* data, int size,
+                        std::vector<BlockData>& blocks,
+                        std::vector<MaterialData>& materials) {
+  Reader reader(data, size, PacketType::RegistrySync);
+  uint32_t count = 0;
+  if (!reader.varint(count) || count > 256) return false;
+
+  blocks.clear();
+  for (auto i = 0; i < count; i++) {
+    uint8_t flags = 0, light = 0;
+    if (!reader.u8(flags) || !reader.u8(light)) return false;
+
+    BlockData block;
+    block.mesh = flags & 1;
+    block.opaque = flags & 2;
+    block.solid = flags & 4;
+    block.light = static_cast<int8_t>(light);
+    for (auto& face : block.faces) {
+      if (!reader.u8(face.id)) return false;
+    }
+    blocks.push_back(block);
+  }
+
+  if (!reader.varint(count) || count > 256) return false;
+  materials.clear();
+  for (auto i = 0; i < count; i++) {
+    uint8_t flags = 0;
+    MaterialData material;
+    if (!reader.u8(flags) || !reader.u8(material.texture) ||
+        !reader.u8(material.uv_transform)) {
+      return false;
+    }
+    material.liquid = flags & 1;
+    material.alphaTest = flags & 2;
+    material.fullBright = flags & 4;
+    for (auto& channel : material.color) {
+      if (!reader.raw(channel)) return false;
+    }
+    materials.push_back(material);
+  }
+  return reader.done();
+}
+
+const uint8_t* encodeInventory(const Inventory& inventory) {
+  writer.start(PacketType::Inventory);
+  writer.varint(static_cast<uint32_t>(inventory.size()));
+  for (auto i = 0; i < inventory.size(); i++) {
+    const auto& stack = inventory.get(i);
+    writer.svarint(stack.item);
+    if (stack.item != kNoItem) writer.varint(stack.count);
+  }
+  return writer.finish();
+}
+
+bool decodeInventory(const uint8_t* data, int size,
+                     std::vector<ItemStack>& slots) {
+  Reader reader(data, size, PacketType::Inventory);
+  uint32_t count = 0;
+  if (!reader.varint(count)) return false;
+
+  // Each slot takes at least 1 byte, which bounds a valid count.
+  if (count > static_cast<uint32_t>(size)) return false;
+
+  slots.clear();
+  for (auto i = 0; i < count; i++) {
+    ItemStack stack;
+    if (!reader.svarint(stack.item)) return false;
+    if (stack.item != kNoItem) {
+      uint32_t value = 0;
+      if (stack.item < 0 || !reader.varint(value)) return false;
+      if (value == 0 || value > 0x7fffffff) return false;
+      stack.count = static_cast<int>(value);
+    }
+    slots.push_back(stack);
+  }
+  return reader.done();
+}
+
+//////////////////////////////////////////////////////////////////////////////
+
+} // namespace voxels
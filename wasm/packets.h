@@ -0,0 +1,94 @@
+#pragma once
+
+#include <cstdint>
+#include <vector>
+
+#include "base.h"
+#include "inventory.h"
+#include "mesher.h"
+#include "snapshots.h"
+
+//////////////////////////////////////////////////////////////////////////////
+
+namespace voxels {
+
+//////////////////////////////////////////////////////////////////////////////
+
+// The wire format for network messages. Each packet starts with a one-byte
+// type, followed by its fields. Integers are LEB128 varints (zigzag-encoded
+// if signed) and floats are little-endian IEEE 754.
+//
+// Encoders return a buffer: a uint32_t byte count, followed by the packet,
+// which is valid until the next call to any encoder. Decoders return false
+// if the packet is truncated, has trailing bytes, or has the wrong type.
+
+enum class PacketType : uint8_t {
+  ChunkData = 1,
+  ChunkDelta,
+  BlockEdit,
+  EntitySnapshot,
+  RegistrySync,
+  Inventory,
+};
+
+// Chunk data is run-length encoded in the chunk's voxel index order. It's
+// followed by the chunk's extra data: an opaque blob that the game attaches
+// to the chunk, with a varint size prefix. Then come the chunk's block
+// entities, with a varint count, in order of index. Packets from before we
+// had these sections end early, and decode with the sections empty.
+constexpr int kChunkVoxels = kChunkWidth * kChunkWidth * kWorldHeight;
+
+// Rich state for the block at a voxel index in a chunk, such as a chest's
+// contents or a sign's text. Types are game-defined and data is opaque.
+struct BlockEntity {
+  int index;
+  int type;
+  std::vector<uint8_t> data;
+};
+
+struct ChunkEdit {
+  int index;
+  Block block;
+};
+
+struct BlockEdit {
+  int x, y, z;
+  Block block;
+};
+
+const uint8_t* encodeChunkData(int cx, int cz, const Block* voxels,
+                               const std::vector<uint8_t>& extra,
+                               const std::vector<BlockEntity>& entities);
+bool decodeChunkData(const uint8_t* data, int size, int& cx, int& cz,
+                     Block* voxels, std::vector<uint8_t>& extra,
+                     std::vector<BlockEntity>& entities);
+
+const uint8_t* encodeChunkDelta(int cx, int cz,
+                                const std::vector<ChunkEdit>& edits);
+bool decodeChunkDelta(const uint8_t* data, int size,
+                      int& cx, int& cz, std::vector<ChunkEdit>& edits);
+
+const uint8_t* encodeBlockEdit(const BlockEdit& edit);
+bool decodeBlockEdit(const uint8_t* data, int size, BlockEdit& edit);
+
+const uint8_t* encodeEntitySnapshot(
+    double time, const EntityTransform* transforms, int count);
+bool decodeEntitySnapshot(const uint8_t* data, int size, double& time,
+                          std::vector<EntityTransform>& transforms);
+
+// Registry syncs carry the block table, and then the material table that
+// its faces refer to, each with a varint count.
+const uint8_t* encodeRegistrySync(const Registry& registry);
+bool decodeRegistrySync(const uint8_t* data, int size,
+                        std::vector<BlockData>& blocks,
+                        std::vector<MaterialData>& materials);
+
+// Inventories are a slot count, followed by each slot's item, and then its
+// count if the slot isn't empty. Decoding doesn't check max stack sizes.
+const uint8_t* encodeInventory(const Inventory& inventory);
+bool decodeInventory(const uint8_t* data, int size,
+                     std::vector<ItemStack>& slots);
+
+//////////////////////////////////////////////////////////////////////////////
+
+} // namespace voxels
@@ -0,0 +1,167 @@
+#include "paths.h"
+
+#include <algorithm>
+#include <cmath>
+
+//////////////////////////////////////////////////////////////////////////////
+
+namespace voxels {
+
+//////////////////////////////////////////////////////////////////////////////
+
+namespace {
+
+using Vec3 = std::array<double, 3>;
+
+// We sample splines at most this far apart, so the polyline we sweep stays
+// within a small fraction of a voxel of the curve.
+constexpr double kSplineStep = 0.25;
+
+Vec3 lerp(const Vec3& a, const Vec3& b, double t) {
+  return {a[0] + t * (b[0] - a[0]),
+          a[1] + t * (b[1] - a[1]),
+          a[2] + t * (b[2] - a[2])};
+}
+
+double distance(const Vec3& a, const Vec3& b) {
+  const auto dx = b[0] - a[0], dy = b[1] - a[1], dz = b[2] - a[2];
+  return std::sqrt(dx * dx + dy * dy + dz * dz);
+}
+
+// A uniform Catmull-Rom spline through p1 and p2, at t in [0, 1].
+Vec3 catmullRom(const Vec3& p0, const Vec3& p1, const Vec3& p2,
+                const Vec3& p3, double t) {
+  Vec3 result;
+  const auto t2 = t * t, t3 = t2 * t;
+  for (auto i = 0; i < 3; i++) {
+    result[i] = 0.5 * (2 * p1[i] + (p2[i] - p0[i]) * t +
+                       (2 * p0[i] - 5 * p1[i] + 4 * p2[i] - p3[i]) * t2 +
+                       (3 * p1[i] - p0[i] - 3 * p2[i] + p3[i]) * t3);
+  }
+  return result;
+}
+
+// Converts the points to the polyline to sweep, sampling the spline if the
+// brush asks for one. The spline repeats its end points as control points.
+void buildPolyline(const std::vector<Vec3>& points, bool spline,
+                   std::vector<Vec3>& result) {
+  result.clear();
+  if (!spline || points.size() < 3) {
+    result = points;
+    return;
+  }
+  const auto last = points.size() - 1;
+  result.push_back(points[0]);
+  for (size_t i = 0; i < last; i++) {
+    const auto& p0 = points[i > 0 ? i - 1 : 0];
+    const auto& p1 = points[i];
+    const auto& p2 = points[i + 1];
+    const auto& p3 = points[std::min(i + 2, last)];
+    const auto steps = std::max(
+        static_cast<int>(std::ceil(distance(p1, p2) / kSplineStep)), 1);
+    for (auto j = 1; j <= steps; j++) {
+      result.push_back(catmullRom(p0, p1, p2, p3,
+                                  static_cast<double>(j) / steps));
+    }
+  }
+}
+
+// Returns true if the profile, swept along the segment from a to b, covers
+// the point p. See paths.h for the profiles' shapes.
+bool segmentCovers(const Vec3& a, const Vec3& b, const Vec3& p,
+                   const PathBrush& brush) {
+  const auto dx = b[0] - a[0], dy = b[1] - a[1], dz = b[2] - a[2];
+  const auto px = p[0] - a[0], py = p[1] - a[1], pz = p[2] - a[2];
+  const auto half = 0.5 * brush.width;
+
+  if (brush.profile == PathProfile::Circle) {
+    const auto length2 = dx * dx + dy * dy + dz * dz;
+    const auto t = length2 > 0
+        ? std::clamp((px * dx + py * dy + pz * dz) / length2, 0.0, 1.0) : 0.0;
+    return distance(lerp(a, b, t), p) <= half;
+  }
+
+  // Rectangular profiles stay upright, so we measure across the path in the
+  // x-z plane. On a vertical segment, such as a shaft, the profile is a box
+  // around the segment's axis.
+  const auto length2 = dx * dx + dz * dz;
+  if (length2 == 0) {
+    const auto lo = std::min(a[1], b[1]);
+    const auto hi = std::max(a[1], b[1]) + brush.height;
+    return std::abs(px) <= half && std::abs(pz) <= half &&
+           lo <= p[1] && p[1] <= hi;
+  }
+  const auto length = std::sqrt(length2);
+  const auto along = (px * dx + pz * dz) / length;
+  const auto across = (pz * dx - px * dz) / length;
+  if (along < -0.5 || along > length + 0.5) return false;
+  if (std::abs(across) > half) return false;
+  const auto t = std::clamp(along / length, 0.0, 1.0);
+  const auto rise = py - t * dy;
+  return 0 <= rise && rise <= brush.height;
+}
+
+} // namespace
+
+//////////////////////////////////////////////////////////////////////////////
+
+void sweepPath(const double* points, int count, const PathBrush& input,
+               std::vector<std::array<int, 3>>& result) {
+  result.clear();
+  if (count <= 0) return;
+
+  auto brush = input;
+  brush.width = std::clamp(brush.width, 0.0, kMaxPathBrushSize);
+  brush.height = std::clamp(brush.height, 0.0, kMaxPathBrushSize);
+
+  static std::vector<Vec3> control;
+  control.clear();
+  auto lo = Vec3{INFINITY, INFINITY, INFINITY};
+  auto hi = Vec3{-INFINITY, -INFINITY, -INFINITY};
+  for (auto i = 0; i < count; i++) {
+    const auto point = Vec3{points[3 * i + 0], points[3 * i + 1],
+                            points[3 * i + 2]};
+    for (auto j = 0; j < 3; j++) {
+      if (!std::isfinite(point[j])) return;
+      lo[j] = std::min(lo[j], point[j]);
+      hi[j] = std::max(hi[j], point[j]);
+    }
+    control.push_back(point);
+  }
+  for (auto j = 0; j < 3; j++) {
+    if (hi[j] - lo[j] > kMaxPathExtent) return;
+  }
+
+  static std::vector<Vec3> polyline;
+  buildPolyline(control, brush.spline, polyline);
+  if (polyline.size() == 1) polyline.push_back(polyline[0]);
+
+  const auto circle = brush.profile == PathProfile::Circle;
+  const auto pad = 0.5 * brush.width;
+  for (size_t i = 0; i + 1 < polyline.size(); i++) {
+    const auto& a = polyline[i];
+    const auto& b = polyline[i + 1];
+    std::array<int, 3> min, max;
+    for (auto j = 0; j < 3; j++) {
+      const auto below = circle || j != 1 ? pad + 0.5 : 0.0;
+      const auto above = circle || j != 1 ? pad + 0.5 : brush.height;
+      min[j] = static_cast<int>(std::floor(std::min(a[j], b[j]) - below));
+      max[j] = static_cast<int>(std::floor(std::max(a[j], b[j]) + above));
+    }
+    for (auto x = min[0]; x <= max[0]; x++) {
+      for (auto y = min[1]; y <= max[1]; y++) {
+        for (auto z = min[2]; z <= max[2]; z++) {
+          const auto p = Vec3{x + 0.5, y + 0.5, z + 0.5};
+          if (segmentCovers(a, b, p, brush)) result.push_back({x, y, z});
+        }
+      }
+    }
+  }
+
+  std::sort(result.begin(), result.end());
+  result.erase(std::unique(result.begin(), result.end()), result.end());
+}
+
+//////////////////////////////////////////////////////////////////////////////
+
+} // namespace voxels
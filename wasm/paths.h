@@ -0,0 +1,46 @@
+#pragma once
+
+#include <array>
+#include <cstdint>
+#include <vector>
+
+#include "base.h"
+
+//////////////////////////////////////////////////////////////////////////////
+
+namespace voxels {
+
+//////////////////////////////////////////////////////////////////////////////
+
+// Path brushes, for carving roads, mine tunnels, and rivers. We sweep a
+// profile along a polyline, or along a Catmull-Rom spline through its
+// points, and collect the voxels whose centers the profile passes over.
+//
+// Circular profiles sweep a tube of the given diameter, `width`, with round
+// ends. Rectangular profiles stay upright, like a road or a tunnel: `width`
+// runs horizontally across the path and `height` runs up from the path, so
+// that a road's points sit on its surface. Rectangular sweeps end flat.
+//
+// Sizes are clamped to kMaxPathBrushSize, and we give up, returning no
+// voxels, if the path spans more than kMaxPathExtent voxels on any axis.
+
+constexpr double kMaxPathBrushSize = 64;
+constexpr int kMaxPathExtent = 4096;
+
+enum class PathProfile : uint8_t { Rect, Circle };
+
+struct PathBrush {
+  PathProfile profile;
+  double width;
+  double height;
+  bool spline;
+};
+
+// Takes (x, y, z) for each point. Clears the result first, then writes the
+// voxels in sorted order.
+void sweepPath(const double* points, int count, const PathBrush& brush,
+               std::vector<std::array<int, 3>>& result);
+
+//////////////////////////////////////////////////////////////////////////////
+
+} // namespace voxels
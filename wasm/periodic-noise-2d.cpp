@@ -0,0 +1,95 @@
+#include "periodic-noise-2d.h"
+
+#include <cmath>
+#include <cstdint>
+
+//////////////////////////////////////////////////////////////////////////////
+
+namespace voxels {
+
+//////////////////////////////////////////////////////////////////////////////
+
+namespace {
+
+uint32_t shuffleSeed(uint32_t seed) {
+  return seed * 1664525 + 1013904223;
+}
+
+constexpr int kGradients[8][2] = {
+  {1,  1}, {-1,  1}, {1, -1}, {-1, -1},
+  {1,  0}, {-1,  0}, {0,  1}, { 0, -1},
+};
+
+int wrapCell(int cell, int period) {
+  return ((cell % period) + period) % period;
+}
+
+template <typename T>
+T fade(T t) {
+  return t * t * t * (t * (t * 6 - 15) + 10);
+}
+
+template <typename T>
+T lerp(T a, T b, T t) {
+  return a + (b - a) * t;
+}
+
+} // namespace
+
+//////////////////////////////////////////////////////////////////////////////
+
+PeriodicNoise2D::PeriodicNoise2D(uint32_t seed) {
+  seed = shuffleSeed(shuffleSeed(shuffleSeed(seed)));
+
+  NonCopyArray<uint8_t, 256> source;
+  for (auto i = 0; i < source.size(); i++) {
+    source[i] = static_cast<uint8_t>(i);
+  }
+
+  for (int i = source.size() - 1; i >= 0; i--) {
+    seed = shuffleSeed(seed);
+    uint32_t r = (seed + 31) % (i + 1);
+    perm[i] = source[r];
+    source[r] = source[i];
+  }
+}
+
+double PeriodicNoise2D::query(double x, double y, int period) const {
+  return queryImpl(x, y, period);
+}
+
+Fixed PeriodicNoise2D::query(Fixed x, Fixed y, int period) const {
+  return queryImpl(x, y, period);
+}
+
+template <typename T>
+T PeriodicNoise2D::queryImpl(T x, T y, int period) const {
+  assert(period > 0);
+  const auto xb = floor(x);
+  const auto yb = floor(y);
+  const auto dx = x - xb;
+  const auto dy = y - yb;
+
+  // We wrap lattice cells before hashing them, so the gradients repeat.
+  const auto x0 = wrapCell(static_cast<int>(xb), period);
+  const auto y0 = wrapCell(static_cast<int>(yb), period);
+  const auto x1 = wrapCell(x0 + 1, period);
+  const auto y1 = wrapCell(y0 + 1, period);
+
+  const auto corner = [&](int px, int py, T ox, T oy) {
+    const auto index = perm[(perm[px & 0xff] + py) & 0xff] & 0x7;
+    const auto& gradient = kGradients[index];
+    return gradient[0] * ox + gradient[1] * oy;
+  };
+
+  const auto u = fade(dx);
+  const auto v = fade(dy);
+  const auto a = lerp(corner(x0, y0, dx, dy), corner(x1, y0, dx - 1, dy), u);
+  const auto b = lerp(corner(x0, y1, dx, dy - 1),
+                      corner(x1, y1, dx - 1, dy - 1), u);
+  return lerp(a, b, v);
+}
+
+//////////////////////////////////////////////////////////////////////////////
+
+} // namespace voxels
@@ -0,0 +1,36 @@
+#pragma once
+
+#include <array>
+
+#include "base.h"
+#include "fixed.h"
+
+//////////////////////////////////////////////////////////////////////////////
+
+namespace voxels {
+
+//////////////////////////////////////////////////////////////////////////////
+
+// Gradient noise that repeats every `period` lattice cells along x and y, for
+// worlds that wrap. OpenSimplex noise samples a skewed lattice, which can't
+// tile a square, so this is Perlin noise on a square lattice instead. Like
+// Noise2D, it returns values in about [-1, 1], for doubles or Fixed.
+
+struct PeriodicNoise2D {
+  PeriodicNoise2D(uint32_t seed = 0);
+
+  double query(double x, double y, int period) const;
+  Fixed query(Fixed x, Fixed y, int period) const;
+
+ private:
+  template <typename T>
+  T queryImpl(T x, T y, int period) const;
+
+  NonCopyArray<uint8_t, 256> perm;
+
+  DISALLOW_COPY_AND_ASSIGN(PeriodicNoise2D);
+};
+
+//////////////////////////////////////////////////////////////////////////////
+
+} // namespace voxels
@@ -0,0 +1,346 @@
+#include "png.h"
+
+#include <array>
+#include <cstring>
+#include <cstdlib>
+
+//////////////////////////////////////////////////////////////////////////////
+
+namespace voxels {
+
+//////////////////////////////////////////////////////////////////////////////
+
+namespace {
+
+// Inflate, after Mark Adler's puff.c: a small, slow, and careful decoder
+// for the deflate streams inside zlib streams.
+
+constexpr int kMaxCodeBits = 15;
+
+constexpr uint16_t kLengthBase[] = {
+  3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31,
+  35, 43, 51, 59, 67, 83, 99, 115, 131, 163, 195, 227, 258};
+constexpr uint8_t kLengthExtra[] = {
+  0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2,
+  3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0};
+constexpr uint16_t kDistanceBase[] = {
+  1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193,
+  257, 385, 513, 769, 1025, 1537, 2049, 3073, 4097, 6145,
+  8193, 12289, 16385, 24577};
+constexpr uint8_t kDistanceExtra[] = {
+  0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6,
+  7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13, 13};
+constexpr uint8_t kCodeLengthOrder[] = {
+  16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15};
+
+struct BitReader {
+  const uint8_t* data;
+  size_t size;
+  size_t pos = 0;
+  uint32_t bits = 0;
+  int count = 0;
+
+  // Reads n <= 16 bits, least-significant first.
+  bool read(int n, uint32_t& result) {
+    while (count < n) {
+      if (pos >= size) return false;
+      bits |= static_cast<uint32_t>(data[pos++]) << count;
+      count += 8;
+    }
+    result = bits & ((1u << n) - 1);
+    bits >>= n;
+    count -= n;
+    return true;
+  }
+
+  void align() {
+    bits >>= count & 7;
+    count -= count & 7;
+  }
+};
+
+// A canonical Huffman code: the number of codes of each length, and the
+// symbols in code order.
+struct Huffman {
+  std::array<uint16_t, kMaxCodeBits + 1> counts;
+  std::vector<uint16_t> symbols;
+
+  void build(const uint8_t* lengths, int n) {
+    counts.fill(0);
+    for (auto i = 0; i < n; i++) counts[lengths[i]]++;
+    counts[0] = 0;
+
+    std::array<uint16_t, kMaxCodeBits + 1> offsets = {};
+    for (auto i = 1; i < kMaxCodeBits; i++) {
+      offsets[i + 1] = offsets[i] + counts[i];
+    }
+    symbols.assign(n, 0);
+    for (auto i = 0; i < n; i++) {
+      if (lengths[i]) symbols[offsets[lengths[i]]++] = static_cast<uint16_t>(i);
+    }
+  }
+
+  bool decode(BitReader& in, int& symbol) const {
+    auto code = 0, first = 0, index = 0;
+    for (auto i = 1; i <= kMaxCodeBits; i++) {
+      uint32_t bit = 0;
+      if (!in.read(1, bit)) return false;
+      code |= bit;
+      const auto count = static_cast<int>(counts[i]);
+      if (code - count < first) {
+        symbol = symbols[index + code - first];
+        return true;
+      }
+      index += count;
+      first = (first + count) << 1;
+      code <<= 1;
+    }
+    return false;
+  }
+};
+
+bool inflateCodes(BitReader& in, const Huffman& lengths,
+                  const Huffman& distances, size_t limit,
+                  std::vector<uint8_t>& out) {
+  while (true) {
+    auto symbol = 0;
+    if (!lengths.decode(in, symbol)) return false;
+    if (symbol < 256) {
+      if (out.size() >= limit) return false;
+      out.push_back(static_cast<uint8_t>(symbol));
+      continue;
+    }
+    if (symbol == 256) return true;
+
+    symbol -= 257;
+    if (symbol >= static_cast<int>(std::size(kLengthBase))) return false;
+    uint32_t extra = 0;
+    if (!in.read(kLengthExtra[symbol], extra)) return false;
+    const auto length = kLengthBase[symbol] + extra;
+
+    if (!distances.decode(in, symbol)) return false;
+    if (symbol >= static_cast<int>(std::size(kDistanceBase))) return false;
+    if (!in.read(kDistanceExtra[symbol], extra)) return false;
+    const auto distance = kDistanceBase[symbol] + extra;
+
+    if (distance > out.size() || out.size() + length > limit) return false;
+    for (size_t i = 0; i < length; i++) {
+      out.push_back(out[out.size() - distance]);
+    }
+  }
+}
+
+bool inflateDynamic(BitReader& in, size_t limit, std::vector<uint8_t>& out) {
+  uint32_t nlen = 0, ndist = 0, ncode = 0;
+  if (!in.read(5, nlen) || !in.read(5, ndist) || !in.read(4, ncode)) {
+    return false;
+  }
+  nlen += 257;
+  ndist += 1;
+  ncode += 4;
+  if (nlen > 286 || ndist > 30) return false;
+
+  std::array<uint8_t, 320> lengths = {};
+  for (size_t i = 0; i < ncode; i++) {
+    uint32_t length = 0;
+    if (!in.read(3, length)) return false;
+    lengths[kCodeLengthOrder[i]] = static_cast<uint8_t>(length);
+  }
+  Huffman codes;
+  codes.build(lengths.data(), 19);
+
+  lengths.fill(0);
+  for (size_t i = 0; i < nlen + ndist;) {
+    auto symbol = 0;
+    if (!codes.decode(in, symbol)) return false;
+    if (symbol < 16) {
+      lengths[i++] = static_cast<uint8_t>(symbol);
+      continue;
+    }
+    uint32_t repeat = 0;
+    auto value = 0;
+    if (symbol == 16) {
+      if (i == 0 || !in.read(2, repeat)) return false;
+      value = lengths[i - 1];
+      repeat += 3;
+    } else if (symbol == 17) {
+      if (!in.read(3, repeat)) return false;
+      repeat += 3;
+    } else {
+      if (!in.read(7, repeat)) return false;
+      repeat += 11;
+    }
+    if (i + repeat > nlen + ndist) return false;
+    while (repeat--) lengths[i++] = static_cast<uint8_t>(value);
+  }
+  if (lengths[256] == 0) return false;
+
+  Huffman lencode, distcode;
+  lencode.build(lengths.data(), nlen);
+  distcode.build(lengths.data() + nlen, ndist);
+  return inflateCodes(in, lencode, distcode, limit, out);
+}
+
+bool inflateFixed(BitReader& in, size_t limit, std::vector<uint8_t>& out) {
+  std::array<uint8_t, 288> lengths;
+  for (auto i = 0; i < 288; i++) {
+    lengths[i] = i < 144 ? 8 : i < 256 ? 9 : i < 280 ? 7 : 8;
+  }
+  Huffman lencode, distcode;
+  lencode.build(lengths.data(), 288);
+  lengths.fill(5);
+  distcode.build(lengths.data(), 30);
+  return inflateCodes(in, lencode, distcode, limit, out);
+}
+
+// Decodes a zlib stream, failing if it would produce more than `limit` bytes.
+bool inflateZlib(const std::vector<uint8_t>& data, size_t limit,
+                 std::vector<uint8_t>& out) {
+  out.clear();
+  if (data.size() < 2) return false;
+  const auto cmf = data[0], flg = data[1];
+  if ((cmf & 0x0f) != 8 || (cmf * 256 + flg) % 31 != 0) return false;
+  if (flg & 0x20) return false;
+
+  auto in = BitReader{data.data() + 2, data.size() - 2};
+  while (true) {
+    uint32_t last = 0, type = 0;
+    if (!in.read(1, last) || !in.read(2, type)) return false;
+    if (type == 0) {
+      in.align();
+      uint32_t length = 0, inverse = 0;
+      if (!in.read(16, length) || !in.read(16, inverse)) return false;
+      if ((length ^ 0xffff) != inverse) return false;
+      if (out.size() + length > limit) return false;
+      for (uint32_t i = 0; i < length; i++) {
+        uint32_t byte = 0;
+        if (!in.read(8, byte)) return false;
+        out.push_back(static_cast<uint8_t>(byte));
+      }
+    } else if (type == 1) {
+      if (!inflateFixed(in, limit, out)) return false;
+    } else if (type == 2) {
+      if (!inflateDynamic(in, limit, out)) return false;
+    } else {
+      return false;
+    }
+    if (last) return true;
+  }
+}
+
+uint32_t readBigEndian(const uint8_t* data) {
+  return (static_cast<uint32_t>(data[0]) << 24) |
+         (static_cast<uint32_t>(data[1]) << 16) |
+         (static_cast<uint32_t>(data[2]) << 8) |
+         static_cast<uint32_t>(data[3]);
+}
+
+uint8_t paeth(int a, int b, int c) {
+  const auto p = a + b - c;
+  const auto pa = std::abs(p - a), pb = std::abs(p - b), pc = std::abs(p - c);
+  if (pa <= pb && pa <= pc) return static_cast<uint8_t>(a);
+  return static_cast<uint8_t>(pb <= pc ? b : c);
+}
+
+// Reverses the PNG row filters in place. With one byte per pixel or less,
+// the filters' "previous pixel" is always the previous byte.
+bool unfilter(std::vector<uint8_t>& data, int stride, int height) {
+  const auto row_size = static_cast<size_t>(stride) + 1;
+  for (auto y = 0; y < height; y++) {
+    const auto row = &data[y * row_size + 1];
+    const auto prev = y > 0 ? &data[(y - 1) * row_size + 1] : nullptr;
+    const auto filter = row[-1];
+    for (auto i = 0; i < stride; i++) {
+      const int a = i > 0 ? row[i - 1] : 0;
+      const int b = prev ? prev[i] : 0;
+      const int c = prev && i > 0 ? prev[i - 1] : 0;
+      auto delta = 0;
+      switch (filter) {
+        case 0: break;
+        case 1: delta = a; break;
+        case 2: delta = b; break;
+        case 3: delta = (a + b) / 2; break;
+        case 4: delta = paeth(a, b, c); break;
+        default: return false;
+      }
+      row[i] = static_cast<uint8_t>(row[i] + delta);
+    }
+  }
+  return true;
+}
+
+} // namespace
+
+//////////////////////////////////////////////////////////////////////////////
+
+bool decodeIndexedPng(const uint8_t* data, size_t size, IndexedImage& result) {
+  constexpr uint8_t kSignature[] = {137, 80, 78, 71, 13, 10, 26, 10};
+  result = IndexedImage{};
+  if (size < 8 || memcmp(data, kSignature, 8) != 0) return false;
+
+  auto depth = 0;
+  auto header = false;
+  std::vector<uint8_t> compressed;
+  for (size_t pos = 8; pos + 12 <= size;) {
+    const auto length = readBigEndian(&data[pos]);
+    if (length > size - pos - 12) return false;
+    const auto type = &data[pos + 4];
+    const auto body = &data[pos + 8];
+    pos += length + 12;
+
+    if (memcmp(type, "IHDR", 4) == 0) {
+      if (length != 13) return false;
+      const auto w = readBigEndian(body), h = readBigEndian(body + 4);
+      if (!(0 < w && w <= kMaxIndexedImageSize)) return false;
+      if (!(0 < h && h <= kMaxIndexedImageSize)) return false;
+      depth = body[8];
+      if (!(depth == 1 || depth == 2 || depth == 4 || depth == 8)) return false;
+      if (body[9] != 3 || body[10] != 0 || body[11] != 0) return false;
+      if (body[12] != 0) return false;
+      result.width = static_cast<int>(w);
+      result.height = static_cast<int>(h);
+      header = true;
+    } else if (!header) {
+      return false;
+    } else if (memcmp(type, "PLTE", 4) == 0) {
+      if (length % 3 != 0 || length / 3 > 256) return false;
+      for (size_t i = 0; i < length; i += 3) {
+        result.palette.push_back((body[i] << 16) | (body[i + 1] << 8) |
+                                 body[i + 2]);
+      }
+    } else if (memcmp(type, "tRNS", 4) == 0) {
+      result.alpha.assign(body, body + length);
+    } else if (memcmp(type, "IDAT", 4) == 0) {
+      compressed.insert(compressed.end(), body, body + length);
+    } else if (memcmp(type, "IEND", 4) == 0) {
+      break;
+    }
+  }
+  if (!header || result.palette.empty()) return false;
+  if (result.alpha.size() > result.palette.size()) return false;
+  result.alpha.resize(result.palette.size(), 255);
+
+  const auto w = result.width, h = result.height;
+  const auto stride = (w * depth + 7) / 8;
+  const auto expected = static_cast<size_t>(stride + 1) * h;
+  std::vector<uint8_t> rows;
+  if (!inflateZlib(compressed, expected, rows)) return false;
+  if (rows.size() != expected || !unfilter(rows, stride, h)) return false;
+
+  const auto mask = (1 << depth) - 1;
+  result.pixels.resize(static_cast<size_t>(w) * h);
+  for (auto y = 0; y < h; y++) {
+    const auto row = &rows[y * static_cast<size_t>(stride + 1) + 1];
+    for (auto x = 0; x < w; x++) {
+      const auto bit = x * depth;
+      const auto index = (row[bit / 8] >> (8 - depth - bit % 8)) & mask;
+      if (index >= static_cast<int>(result.palette.size())) return false;
+      result.pixels[x + w * y] = static_cast<uint8_t>(index);
+    }
+  }
+  return true;
+}
+
+//////////////////////////////////////////////////////////////////////////////
+
+} // namespace voxels
@@ -0,0 +1,39 @@
+#pragma once
+
+#include <cstddef>
+#include <cstdint>
+#include <vector>
+
+#include "base.h"
+
+//////////////////////////////////////////////////////////////////////////////
+
+namespace voxels {
+
+//////////////////////////////////////////////////////////////////////////////
+
+// A decoder for paletted PNGs, so that designers can paint maps in an image
+// editor and import them a layer at a time. We only take color type 3, at
+// any bit depth, without interlacing: other images fail to decode. We don't
+// check CRCs or the zlib checksum, since the images come from the host.
+//
+// Pixels are row-major palette indices, one byte each. Palette colors are
+// 0xRRGGBB, and alpha comes from the tRNS chunk, defaulting to opaque.
+
+constexpr int kMaxIndexedImageSize = 1024;
+
+struct IndexedImage {
+  int width = 0;
+  int height = 0;
+  std::vector<uint8_t> pixels;
+  std::vector<uint32_t> palette;
+  std::vector<uint8_t> alpha;
+};
+
+// Returns false if the data isn't a valid paletted PNG, or if it's larger
+// than kMaxIndexedImageSize on either side.
+bool decodeIndexedPng(const uint8_t* data, size_t size, IndexedImage& result);
+
+//////////////////////////////////////////////////////////////////////////////
+
+} // namespace voxels
@@ -0,0 +1,102 @@
+#include "power.h"
+
+#include <algorithm>
+
+#include "mesher.h"
+
+//////////////////////////////////////////////////////////////////////////////
+
+namespace voxels {
+
+//////////////////////////////////////////////////////////////////////////////
+
+void propagatePower(BlockAccess& world, const Registry& registry,
+                    const int* positions, int count,
+                    std::vector<PowerChange>& result) {
+  struct Node { int x, y, z; PowerKind kind; int level; };
+  static HashMap<uint64_t, int> lookup;
+  static std::vector<Node> nodes;
+  lookup.clear();
+  nodes.clear();
+  result.clear();
+
+  const auto key = [](int x, int y, int z) {
+    return (static_cast<uint64_t>(x & 0xfffffff) << 36) |
+           (static_cast<uint64_t>(z & 0xfffffff) << 8) |
+           static_cast<uint64_t>(y & 0xff);
+  };
+  const auto visit = [&](int x, int y, int z) {
+    if (!(0 <= y && y < kBuildHeight)) return;
+    if (nodes.size() >= kMaxPowerNodes) return;
+    const auto kind = registry.getPower(world.getBlock(x, y, z)).kind;
+    if (kind != PowerKind::Wire && kind != PowerKind::Sink) return;
+    if (!lookup.emplace(key(x, y, z), nodes.size()).second) return;
+    nodes.push_back({x, y, z, kind, 0});
+  };
+
+  for (auto i = 0; i < count; i++) {
+    const auto x = positions[3 * i + 0];
+    const auto y = positions[3 * i + 1];
+    const auto z = positions[3 * i + 2];
+    visit(x, y, z);
+    for (const auto& [dx, dy, dz] : kFaceNeighbors) {
+      visit(x + dx, y + dy, z + dz);
+    }
+  }
+  for (auto i = 0; i < nodes.size(); i++) {
+    const auto [x, y, z, kind, level] = nodes[i];
+    if (kind != PowerKind::Wire) continue;
+    for (const auto& [dx, dy, dz] : kFaceNeighbors) {
+      visit(x + dx, y + dy, z + dz);
+    }
+  }
+
+  // Seed each node with the power that it gets from outside the circuit:
+  // from sources, and from wires past the node limit.
+  static NonCopyArray<std::vector<int>, kMaxPowerLevel + 1> buckets;
+  for (auto& bucket : buckets) bucket.clear();
+  for (auto i = 0; i < nodes.size(); i++) {
+    auto& node = nodes[i];
+    for (const auto& [dx, dy, dz] : kFaceNeighbors) {
+      const auto nx = node.x + dx, ny = node.y + dy, nz = node.z + dz;
+      const auto& data = registry.getPower(world.getBlock(nx, ny, nz));
+      auto level = 0;
+      if (data.kind == PowerKind::Source) {
+        level = data.level;
+      } else if (data.kind == PowerKind::Wire &&
+                 !lookup.contains(key(nx, ny, nz))) {
+        level = world.getMetadata(nx, ny, nz) - 1;
+      }
+      node.level = std::max(node.level, level);
+    }
+    if (node.level > 0) buckets[node.level].push_back(i);
+  }
+
+  for (auto level = kMaxPowerLevel; level > 0; level--) {
+    auto& bucket = buckets[level];
+    for (auto j = 0; j < bucket.size(); j++) {
+      const auto& node = nodes[bucket[j]];
+      if (node.level != level || node.kind != PowerKind::Wire) continue;
+      for (const auto& [dx, dy, dz] : kFaceNeighbors) {
+        const auto it = lookup.find(key(node.x + dx, node.y + dy,
+                                        node.z + dz));
+        if (it == lookup.end()) continue;
+        auto& other = nodes[it->second];
+        const auto next = other.kind == PowerKind::Wire ? level - 1 : level;
+        if (other.level >= next) continue;
+        other.level = next;
+        if (next > 0) buckets[next].push_back(it->second);
+      }
+    }
+  }
+
+  for (const auto& node : nodes) {
+    const auto level = static_cast<uint8_t>(node.level);
+    if (world.getMetadata(node.x, node.y, node.z) == level) continue;
+    result.push_back({node.x, node.y, node.z, level});
+  }
+}
+
+//////////////////////////////////////////////////////////////////////////////
+
+} // namespace voxels
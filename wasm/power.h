@@ -0,0 +1,53 @@
+#pragma once
+
+#include <cstdint>
+#include <vector>
+
+#include "access.h"
+#include "base.h"
+
+//////////////////////////////////////////////////////////////////////////////
+
+namespace voxels {
+
+//////////////////////////////////////////////////////////////////////////////
+
+// Power circuits. A source powers the wires and sinks next to it at its own
+// level. A wire at level L powers the wires next to it at L - 1 and the sinks
+// next to it at L. Sinks take the highest level offered to them and pass
+// nothing on, and sources don't take power at all. Wires and sinks store
+// their levels as block metadata, which propagatePower recomputes for the
+// circuits around an edit, highest level first.
+//
+// We update at most kMaxPowerNodes wires and sinks in one propagation.
+// Circuits larger than this are updated in part, treating the wires past
+// the limit as fixed: they power their neighbors at their stored level - 1.
+
+struct Registry;
+
+enum class PowerKind : uint8_t { None, Source, Wire, Sink };
+
+constexpr int kMaxPowerLevel = 15;
+constexpr int kMaxPowerNodes = 1 << 12;
+
+struct PowerData {
+  PowerKind kind = PowerKind::None;
+  uint8_t level = 0;
+};
+
+struct PowerChange { int x; int y; int z; uint8_t level; };
+
+// Recomputes power levels for the circuits touching the given (x, y, z)
+// positions, which the host has just edited. Clears the result, then writes
+// each wire or sink whose level differs from its metadata.
+//
+// We flood-fill the wires and sinks connected to the edits, clear their
+// levels, and then refill them from adjacent sources, processing levels in
+// decreasing order so that each block is finalized when first popped.
+void propagatePower(BlockAccess& world, const Registry& registry,
+                    const int* positions, int count,
+                    std::vector<PowerChange>& result);
+
+//////////////////////////////////////////////////////////////////////////////
+
+} // namespace voxels
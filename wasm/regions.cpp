@@ -0,0 +1,180 @@
+#include "regions.h"
+
+#include <cerrno>
+#include <cstdio>
+#include <cstring>
+
+#include "packets.h"
+
+//////////////////////////////////////////////////////////////////////////////
+
+namespace voxels {
+
+//////////////////////////////////////////////////////////////////////////////
+
+namespace {
+
+// File header: magic, version, and then an (offset, size) pair for each
+// chunk in the region, indexed by (x | z << kRegionBits). A size of 0 means
+// the chunk is missing. All values are little-endian uint32_ts.
+constexpr char kRegionMagic[4] = {'W', 'V', 'R', 'G'};
+constexpr uint32_t kRegionVersion = 1;
+constexpr size_t kRegionHeader = 8 + 8 * kRegionChunks;
+
+uint32_t readU32(const uint8_t* data) {
+  uint32_t result;
+  memcpy(&result, data, sizeof(result));
+  return result;
+}
+
+void writeU32(uint8_t* data, uint32_t value) {
+  memcpy(data, &value, sizeof(value));
+}
+
+int chunkIndex(int cx, int cz) {
+  return (cx & kRegionMask) | ((cz & kRegionMask) << kRegionBits);
+}
+
+} // namespace
+
+//////////////////////////////////////////////////////////////////////////////
+
+RegionStore::RegionStore(std::string directory)
+    : directory(std::move(directory)) {}
+
+bool RegionStore::loadChunk(int cx, int cz, Block* voxels,
+                            std::vector<uint8_t>& extra,
+                            std::vector<BlockEntity>& entities) {
+  const auto& region = getRegion(cx, cz);
+  if (region.readonly) return false;
+  const auto& chunk = region.chunks[chunkIndex(cx, cz)];
+  if (chunk.empty()) return false;
+
+  // Payloads are ChunkData packets with their size prefix stripped.
+  auto px = 0, pz = 0;
+  const auto size = static_cast<int>(chunk.size());
+  if (!decodeChunkData(chunk.data(), size, px, pz, voxels, extra,
+                       entities)) {
+    return false;
+  }
+  return px == cx && pz == cz;
+}
+
+bool RegionStore::saveChunk(int cx, int cz, const Block* voxels,
+                            const std::vector<uint8_t>& extra,
+                            const std::vector<BlockEntity>& entities) {
+  auto& region = getRegion(cx, cz);
+  if (region.readonly) return false;
+  const auto packet = encodeChunkData(cx, cz, voxels, extra, entities);
+  const auto size = readU32(packet);
+  const auto start = packet + sizeof(uint32_t);
+  region.chunks[chunkIndex(cx, cz)].assign(start, start + size);
+  region.dirty = true;
+  return true;
+}
+
+bool RegionStore::flush() {
+  auto result = true;
+  for (auto& [key, region] : regions) {
+    if (!region.dirty) continue;
+    if (writeRegion(region)) {
+      region.dirty = false;
+    } else {
+      result = false;
+    }
+  }
+  return result;
+}
+
+RegionStore::Region& RegionStore::getRegion(int cx, int cz) {
+  const auto rx = cx >> kRegionBits, rz = cz >> kRegionBits;
+  const auto key = (static_cast<uint64_t>(static_cast<uint32_t>(rx)) << 32) |
+                   static_cast<uint64_t>(static_cast<uint32_t>(rz));
+  const auto it = regions.find(key);
+  if (it != regions.end()) return it->second;
+
+  auto& region = regions[key];
+  region.rx = rx;
+  region.rz = rz;
+  region.chunks.resize(kRegionChunks);
+  const auto result = readRegion(region);
+  if (result == ReadResult::Ok) return region;
+
+  for (auto& chunk : region.chunks) chunk.clear();
+  if (result == ReadResult::Corrupt) {
+    const auto path = getPath(rx, rz);
+    const auto aside = path + ".corrupt";
+    region.readonly = rename(path.c_str(), aside.c_str()) != 0;
+  } else if (result == ReadResult::Failed) {
+    region.readonly = true;
+  }
+  return region;
+}
+
+std::string RegionStore::getPath(int rx, int rz) const {
+  return directory + "/r." + std::to_string(rx) + "." +
+         std::to_string(rz) + ".region";
+}
+
+RegionStore::ReadResult RegionStore::readRegion(Region& region) const {
+  const auto file = fopen(getPath(region.rx, region.rz).c_str(), "rb");
+  if (!file) return errno == ENOENT ? ReadResult::Missing : ReadResult::Failed;
+
+  std::vector<uint8_t> data;
+  uint8_t buffer[1 << 16];
+  for (size_t read; (read = fread(buffer, 1, sizeof(buffer), file)) > 0;) {
+    data.insert(data.end(), buffer, buffer + read);
+  }
+  const auto failed = ferror(file) != 0;
+  fclose(file);
+  if (failed) return ReadResult::Failed;
+
+  // A file from a newer version isn't corrupt, but we can't safely rewrite
+  // it, so we leave it in place and treat the region as read-only.
+  if (data.size() < kRegionHeader) return ReadResult::Corrupt;
+  if (memcmp(data.data(), kRegionMagic, sizeof(kRegionMagic)) != 0) {
+    return ReadResult::Corrupt;
+  }
+  if (readU32(&data[4]) != kRegionVersion) return ReadResult::Failed;
+
+  for (auto i = 0; i < kRegionChunks; i++) {
+    const auto offset = readU32(&data[8 + 8 * i]);
+    const auto size = readU32(&data[12 + 8 * i]);
+    if (size == 0) continue;
+    if (offset < kRegionHeader || offset > data.size() ||
+        size > data.size() - offset) {
+      return ReadResult::Corrupt;
+    }
+    region.chunks[i].assign(&data[offset], &data[offset] + size);
+  }
+  return ReadResult::Ok;
+}
+
+bool RegionStore::writeRegion(const Region& region) const {
+  std::vector<uint8_t> data(kRegionHeader, 0);
+  memcpy(data.data(), kRegionMagic, sizeof(kRegionMagic));
+  writeU32(&data[4], kRegionVersion);
+  for (auto i = 0; i < kRegionChunks; i++) {
+    const auto& chunk = region.chunks[i];
+    if (chunk.empty()) continue;
+    writeU32(&data[8 + 8 * i], static_cast<uint32_t>(data.size()));
+    writeU32(&data[12 + 8 * i], static_cast<uint32_t>(chunk.size()));
+    data.insert(data.end(), chunk.begin(), chunk.end());
+  }
+
+  const auto path = getPath(region.rx, region.rz);
+  const auto temp = path + ".tmp";
+  const auto file = fopen(temp.c_str(), "wb");
+  if (!file) return false;
+  const auto written = fwrite(data.data(), 1, data.size(), file);
+  const auto closed = fclose(file) == 0;
+  if (written != data.size() || !closed) {
+    remove(temp.c_str());
+    return false;
+  }
+  return rename(temp.c_str(), path.c_str()) == 0;
+}
+
+//////////////////////////////////////////////////////////////////////////////
+
+} // namespace voxels
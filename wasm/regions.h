@@ -0,0 +1,78 @@
+#pragma once
+
+#include <cstdint>
+#include <string>
+#include <vector>
+
+#include "base.h"
+#include "packets.h"
+
+//////////////////////////////////////////////////////////////////////////////
+
+namespace voxels {
+
+//////////////////////////////////////////////////////////////////////////////
+
+// Chunk storage for native builds, where we have a filesystem. We group
+// chunks into regions of kRegionWidth x kRegionWidth chunks, one per file.
+// Each file has a header with an offset table, followed by chunk payloads,
+// which are run-length-encoded ChunkData packets (see packets.h).
+//
+// The store caches the regions it touches. saveChunk only updates the cache;
+// flush writes each modified region to a temporary file and then renames it
+// over the old one, so a crash mid-save never leaves a torn region.
+//
+// A missing region file is an empty region. A corrupt one is renamed aside,
+// with a ".corrupt" suffix, before we start a fresh region in its place, so
+// that flush never overwrites it. If we can't read the file, can't move it
+// aside, or it has a newer version, the region is read-only: it loads no
+// chunks and saves none.
+
+constexpr int kRegionBits = 5;
+constexpr int kRegionWidth = 1 << kRegionBits;
+constexpr int kRegionMask = kRegionWidth - 1;
+constexpr int kRegionChunks = kRegionWidth * kRegionWidth;
+
+struct RegionStore {
+  explicit RegionStore(std::string directory);
+
+  // Reads a chunk's voxels, in chunk index order, into `voxels`, and its
+  // extra data and block entities. Returns false if the chunk was never
+  // saved, if its payload is corrupt, or if its region is read-only.
+  bool loadChunk(int cx, int cz, Block* voxels, std::vector<uint8_t>& extra,
+                 std::vector<BlockEntity>& entities);
+
+  // Returns false, without saving the chunk, if its region is read-only.
+  bool saveChunk(int cx, int cz, const Block* voxels,
+                 const std::vector<uint8_t>& extra,
+                 const std::vector<BlockEntity>& entities);
+
+  // Writes all modified regions. Returns false if any write fails; those
+  // regions stay modified, so a later flush will retry them.
+  bool flush();
+
+ private:
+  enum class ReadResult { Ok, Missing, Corrupt, Failed };
+
+  struct Region {
+    int rx;
+    int rz;
+    bool dirty = false;
+    bool readonly = false;
+    std::vector<std::vector<uint8_t>> chunks;
+  };
+
+  Region& getRegion(int cx, int cz);
+  std::string getPath(int rx, int rz) const;
+  ReadResult readRegion(Region& region) const;
+  bool writeRegion(const Region& region) const;
+
+  std::string directory;
+  HashMap<uint64_t, Region> regions;
+
+  DISALLOW_COPY_AND_ASSIGN(RegionStore);
+};
+
+//////////////////////////////////////////////////////////////////////////////
+
+} // namespace voxels
@@ -11,12 +11,13 @@ namespace voxels {
 #define JS(return_type, name, arg_types) \
   EM_JS(return_type, name, arg_types, { throw new Error(); });
 
-JS(int,  js_AddLightTexture,  (const uint8_t* data, int size));
+JS(int,  js_AddLightTexture,  (const uint8_t* data, int size, int x, int z));
 JS(int,  js_FreeLightTexture, (int handle));
 
 JS(int,  js_AddInstancedMesh,      (int block, int x, int y, int z));
 JS(void, js_FreeInstancedMesh,     (int handle));
 JS(int,  js_SetInstancedMeshLight, (int handle, int level));
+JS(int,  js_SetInstancedMeshPosition, (int handle, int x, int y, int z));
 
 JS(int,  js_AddVoxelMesh,  (const uint32_t* data, int size, int phase));
 JS(void, js_FreeVoxelMesh, (int handle));
@@ -24,30 +25,133 @@ JS(int,  js_AddVoxelMeshGeometry, (int handle, const uint32_t* data, int size));
 JS(int,  js_SetVoxelMeshGeometry, (int handle, const uint32_t* data, int size));
 JS(int,  js_SetVoxelMeshLight,    (int handle, int texture));
 JS(int,  js_SetVoxelMeshMask,     (int handle, int m0, int m1, bool shown));
-JS(int,  js_SetVoxelMeshPosition, (int handle, int x, int y, int z));
+
+JS(int,  js_AddSmoothMesh,  (const float* data, int size));
+JS(void, js_FreeSmoothMesh, (int handle));
+JS(int,  js_SetSmoothMeshGeometry, (int handle, const float* data, int size));
+JS(int,  js_SetSmoothMeshLight,    (int handle, int texture));
+JS(int,  js_SetSmoothMeshShown,    (int handle, bool shown));
 
 #undef JS
 
 //////////////////////////////////////////////////////////////////////////////
 
-LightTexture::LightTexture(const LightTensor3<uint8_t>& lights) {
-  binding = js_AddLightTexture(lights.data.data(), lights.data.size());
+namespace {
+
+using Position = std::array<int, 3>;
+
+Position render_origin = {0, 0, 0};
+int render_layer = 0;
+
+// The state of live instances, by binding, so that we can send it again when
+// the render origin moves or when we show the instance's layer again. Our
+// bindings are stable, but the host's handle is -1 while the layer is hidden.
+struct InstanceState {
+  Block block;
+  Position position;
+  int layer;
+  int light;
+  int handle;
+};
+HashMap<int, InstanceState> instances;
+int next_instance = 0;
+
+// The masks of live voxel meshes, by binding.
+struct MeshMask {
+  int m0;
+  int m1;
+  bool shown;
+  int layer;
+};
+HashMap<int, MeshMask> voxel_mesh_masks;
+
+// The render layers of live smooth meshes, by binding.
+HashMap<int, int> smooth_mesh_layers;
+
+Position relative(const Position& p) {
+  const auto& o = render_origin;
+  return {p[0] - o[0], p[1] - o[1], p[2] - o[2]};
+}
+
+int addInstance(const InstanceState& state) {
+  const auto [rx, ry, rz] = relative(state.position);
+  const auto handle =
+      js_AddInstancedMesh(static_cast<int>(state.block), rx, ry, rz);
+  if (state.light >= 0) js_SetInstancedMeshLight(handle, state.light);
+  return handle;
+}
+
+void sendMask(int binding, const MeshMask& mask) {
+  const auto shown = mask.shown && mask.layer == render_layer;
+  js_SetVoxelMeshMask(binding, mask.m0, mask.m1, shown);
+}
+
+} // namespace
+
+void setRenderOrigin(int x, int y, int z) {
+  render_origin = {x, y, z};
+  for (const auto& [binding, state] : instances) {
+    if (state.handle < 0) continue;
+    const auto [rx, ry, rz] = relative(state.position);
+    js_SetInstancedMeshPosition(state.handle, rx, ry, rz);
+  }
+}
+
+const std::array<int, 3>& getRenderOrigin() { return render_origin; }
+
+void setRenderLayer(int layer) {
+  if (layer == render_layer) return;
+  const auto prev = render_layer;
+  render_layer = layer;
+
+  for (auto& [binding, state] : instances) {
+    if (state.layer == layer && state.handle < 0) {
+      state.handle = addInstance(state);
+    } else if (state.layer == prev && state.handle >= 0) {
+      js_FreeInstancedMesh(state.handle);
+      state.handle = -1;
+    }
+  }
+  for (const auto& [binding, mask] : voxel_mesh_masks) {
+    if (mask.layer == layer || mask.layer == prev) sendMask(binding, mask);
+  }
+  for (const auto& [binding, mesh_layer] : smooth_mesh_layers) {
+    if (mesh_layer != layer && mesh_layer != prev) continue;
+    js_SetSmoothMeshShown(binding, mesh_layer == layer);
+  }
+}
+
+//////////////////////////////////////////////////////////////////////////////
+
+LightTexture::LightTexture(const LightTensor3<uint8_t>& lights,
+                           int x, int z) {
+  const auto& data = lights.data;
+  binding = js_AddLightTexture(data.data(), data.size(), x, z);
 }
 
 LightTexture::~LightTexture() { js_FreeLightTexture(binding); }
 
 InstancedMesh::InstancedMesh(Block block, int x, int y, int z) {
-  binding = js_AddInstancedMesh(static_cast<int>(block), x, y, z);
+  InstanceState state{block, {x, y, z}, render_layer, -1, -1};
+  state.handle = addInstance(state);
+  binding = next_instance++;
   lightLevel = -1;
+  instances.emplace(binding, state);
 }
 
 InstancedMesh::~InstancedMesh() {
-  if (binding >= 0) js_FreeInstancedMesh(binding);
+  if (binding < 0) return;
+  const auto it = instances.find(binding);
+  assert(it != instances.end());
+  if (it->second.handle >= 0) js_FreeInstancedMesh(it->second.handle);
+  instances.erase(it);
 }
 
 void InstancedMesh::setLight(int level) {
   if (binding < 0 || level == lightLevel) return;
-  js_SetInstancedMeshLight(binding, level);
+  auto& state = instances.at(binding);
+  if (state.handle >= 0) js_SetInstancedMeshLight(state.handle, level);
+  state.light = level;
   lightLevel = level;
 }
 
@@ -55,9 +159,13 @@ VoxelMesh::VoxelMesh(const Quads& quads, int phase) {
   const auto data = reinterpret_cast<const uint32_t*>(quads.data());
   const auto size = quads.size() * sizeof(Quad) / sizeof(uint32_t);
   binding = js_AddVoxelMesh(data, size, phase);
+  voxel_mesh_masks[binding] = {0, 0, true, render_layer};
 }
 
-VoxelMesh::~VoxelMesh() { js_FreeVoxelMesh(binding); }
+VoxelMesh::~VoxelMesh() {
+  js_FreeVoxelMesh(binding);
+  voxel_mesh_masks.erase(binding);
+}
 
 void VoxelMesh::appendGeometry(const Quads& quads) {
   const auto data = reinterpret_cast<const uint32_t*>(quads.data());
@@ -76,11 +184,27 @@ void VoxelMesh::setLight(const LightTexture& texture) {
 }
 
 void VoxelMesh::setMask(int m0, int m1, bool shown) {
-  js_SetVoxelMeshMask(binding, m0, m1, shown);
+  auto& mask = voxel_mesh_masks.at(binding);
+  mask = {m0, m1, shown, mask.layer};
+  sendMask(binding, mask);
+}
+
+SmoothMesh::SmoothMesh(const std::vector<float>& geo) {
+  binding = js_AddSmoothMesh(geo.data(), geo.size());
+  smooth_mesh_layers[binding] = render_layer;
+}
+
+SmoothMesh::~SmoothMesh() {
+  js_FreeSmoothMesh(binding);
+  smooth_mesh_layers.erase(binding);
+}
+
+void SmoothMesh::setGeometry(const std::vector<float>& geo) {
+  js_SetSmoothMeshGeometry(binding, geo.data(), geo.size());
 }
 
-void VoxelMesh::setPosition(int x, int y, int z) {
-  js_SetVoxelMeshPosition(binding, x, y, z);
+void SmoothMesh::setLight(const LightTexture& texture) {
+  js_SetSmoothMeshLight(binding, texture.binding);
 }
 
 //////////////////////////////////////////////////////////////////////////////
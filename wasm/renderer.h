@@ -12,11 +12,28 @@ namespace voxels {
 
 //////////////////////////////////////////////////////////////////////////////
 
+// Mesh positions are sent to the renderer relative to a render origin, so
+// that the renderer's f32 positions stay small in worlds that are millions
+// of blocks across. Moving the origin re-sends the positions of instances.
+// Voxel meshes have no transform: their quads are already relative to the
+// origin, so the caller must remesh them when it moves.
+void setRenderOrigin(int x, int y, int z);
+const std::array<int, 3>& getRenderOrigin();
+
+// Meshes belong to the render layer that was active when we created them,
+// and we only show the active layer's meshes, so that several worlds, like
+// dimensions, can share the renderer. Switching layers hides and shows them.
+void setRenderLayer(int layer);
+
+// A chunk's lights, including a one-voxel border. (x, z) is the world-space
+// position of the chunk's (0, 0, 0) voxel, which the renderer uses to find a
+// quad's texels. Unlike mesh positions, it's not relative to the origin.
 struct LightTexture {
-  LightTexture(const LightTensor3<uint8_t>& lights);
+  LightTexture(const LightTensor3<uint8_t>& lights, int x, int z);
   ~LightTexture();
 
  private:
+  friend struct SmoothMesh;
   friend struct VoxelMesh;
 
   int binding;
@@ -59,7 +76,6 @@ struct VoxelMesh {
   void setLight(const LightTexture& light);
   void setGeometry(const Quads& quads);
   void setMask(int m0, int m1, bool shown);
-  void setPosition(int x, int y, int z);
 
  private:
   int binding;
@@ -67,6 +83,21 @@ struct VoxelMesh {
   DISALLOW_COPY_AND_ASSIGN(VoxelMesh);
 };
 
+// Triangles from a smooth mesher, which are in render space, like a voxel
+// mesh's quads. See smooth.h for the vertex layout.
+struct SmoothMesh {
+  SmoothMesh(const std::vector<float>& geo);
+  ~SmoothMesh();
+
+  void setGeometry(const std::vector<float>& geo);
+  void setLight(const LightTexture& light);
+
+ private:
+  int binding;
+
+  DISALLOW_COPY_AND_ASSIGN(SmoothMesh);
+};
+
 //////////////////////////////////////////////////////////////////////////////
 
 } // namespace voxels
@@ -0,0 +1,225 @@
+#include "replay.h"
+
+#include <cstring>
+#include <iterator>
+
+//////////////////////////////////////////////////////////////////////////////
+
+namespace voxels {
+
+//////////////////////////////////////////////////////////////////////////////
+
+namespace {
+
+constexpr char kReplayMagic[4] = {'W', 'V', 'R', 'P'};
+constexpr uint8_t kReplayVersion = 2;
+constexpr size_t kReplayHeader = sizeof(kReplayMagic) + 1;
+
+// The number of arguments for each op, indexed by op.
+constexpr int kReplayArity[] = {
+  0, // unused
+  0, // Tick
+  2, // Recenter: x, z
+  0, // Remesh
+  4, // SetBlock: x, y, z, block
+  8, // FillBlocks: x0, y0, z0, x1, y1, z1, block, collect_drops
+  4, // SetPointLight: x, y, z, level
+  5, // PushBlocks: x, y, z, dir, max_count
+  5, // GrowTree: x, y, z, species, seed
+  3, // TickFire: cx, cz, raining
+  7, // ProtectRegion: x0, y0, z0, x1, y1, z1, owner
+  1, // UnprotectRegions: owner
+  1, // SetEditor: owner
+  5, // SetDynamicLight: id, x, y, z, level
+  5, // Explode: x, y, z, power (float bits), seed
+  1, // SetDimension: id
+  1, // RemoveDimension: id
+  1, // Advance: dt in microseconds
+  0, // Snapshot
+  1, // Rollback: snapshot id
+  7, // CopyToClipboard: id, x0, y0, z0, x1, y1, z1
+  7, // CreateClipboardShape: id, shape, sx, sy, sz, block, shell
+  5, // ApplyCsg: id, op, x, y, z
+  7, // SetSymmetry: mirrors, plane x2, y2, z2, radial, axis x2, z2
+  6, // SetSelection: x0, y0, z0, x1, y1, z1
+  0, // ClearSelection
+  2, // ExpandSelection: face, amount
+  3, // ShiftSelection: dx, dy, dz
+  1, // SetSelectionMask: enabled
+  3, // ReplaceInSelection: from, to, collect_drops
+  5, // PlayerEdit: player, x, y, z, block
+  2, // AddProjectile: id, kind; payload: x, y, z, vx, vy, vz doubles
+  1, // RemoveProjectile: id
+  0, // StepProjectiles: payload: dt double
+  2, // SetChunkExtra: cx, cz; payload: the blob
+  4, // CreateBlockEntity: x, y, z, type; payload: the entity's data
+  3, // RemoveBlockEntity: x, y, z
+  2, // AddItemDrop: item, count; payload: x, y, z, vx, vy, vz doubles
+  1, // RemoveItemDrop: id
+  0, // SetItemDropRules: payload: the rules, as 7 doubles
+  0, // SetItemMagnets: payload: (x, y, z) doubles for each magnet
+  0, // StepItemDrops: payload: dt double
+  1, // PickUpItemDrops: inventory; payload: x, y, z, radius doubles
+  2, // SetTickRate: tick length in microseconds, max ticks
+  1, // SetRaining: raining
+  7, // VoxelizeMesh: vertices, indices, block, solid, x, y, z; payload:
+     // resolution double, then the vertex floats and the index uint32_ts
+  3, // CarvePath: profile, spline, block; payload: width and height
+     // doubles, then (x, y, z) doubles for each point
+  5, // ImportHeightmap: w, h, layers, x, z; payload: scale double, then the
+     // (depth, block) int32_ts for each layer, then the uint16_t samples
+  4, // ImportLayerPng: colors, x, y, z; payload: the (color, block) int32_ts
+     // for each color, then the PNG's bytes
+  1, // SetSkyDarkness: level
+  2, // LoadSlice: cx, cz; payload: the slice's text, without a terminator
+  1, // PropagatePower: count; payload: (x, y, z) int32_ts for each position
+  1, // SetWorldWrap: chunks
+  1, // SetWorldSeed: seed
+  0, // DecodeSnapshot: payload: the encoded snapshot
+};
+static_assert(std::size(kReplayArity) == static_cast<int>(ReplayOp::Count));
+
+// Returns the payload size in bytes for ops that end with one, kAnySize for
+// ops that take a buffer of any size, or 0 for ops without a payload.
+constexpr int kAnySize = -1;
+
+int payloadSize(ReplayOp op) {
+  switch (op) {
+    case ReplayOp::AddProjectile: return 6 * sizeof(double);
+    case ReplayOp::StepProjectiles: return sizeof(double);
+    case ReplayOp::SetChunkExtra: return kAnySize;
+    case ReplayOp::CreateBlockEntity: return kAnySize;
+    case ReplayOp::AddItemDrop: return 6 * sizeof(double);
+    case ReplayOp::SetItemDropRules: return 7 * sizeof(double);
+    case ReplayOp::SetItemMagnets: return kAnySize;
+    case ReplayOp::StepItemDrops: return sizeof(double);
+    case ReplayOp::PickUpItemDrops: return 4 * sizeof(double);
+    case ReplayOp::VoxelizeMesh: return kAnySize;
+    case ReplayOp::CarvePath: return kAnySize;
+    case ReplayOp::ImportHeightmap: return kAnySize;
+    case ReplayOp::ImportLayerPng: return kAnySize;
+    case ReplayOp::LoadSlice: return kAnySize;
+    case ReplayOp::PropagatePower: return kAnySize;
+    case ReplayOp::DecodeSnapshot: return kAnySize;
+    default: return 0;
+  }
+}
+
+} // namespace
+
+//////////////////////////////////////////////////////////////////////////////
+
+void ReplayLog::start() {
+  active = true;
+  tick = last_tick = 0;
+  bytes.assign(sizeof(uint32_t), 0);
+  bytes.insert(bytes.end(), kReplayMagic, kReplayMagic + sizeof(kReplayMagic));
+  bytes.push_back(kReplayVersion);
+}
+
+void ReplayLog::stop() {
+  active = false;
+}
+
+void ReplayLog::record(ReplayOp op, std::initializer_list<int32_t> args) {
+  assert(payloadSize(op) == 0);
+  record(op, args, nullptr, 0);
+}
+
+void ReplayLog::record(ReplayOp op, std::initializer_list<int32_t> args,
+                       const void* payload, int size) {
+  if (!active) return;
+  const auto index = static_cast<int>(op);
+  assert(0 < index && index < static_cast<int>(ReplayOp::Count));
+  assert(static_cast<int>(args.size()) == kReplayArity[index]);
+  assert(size >= 0 && (size == 0 || payload));
+  const auto expected = payloadSize(op);
+  assert(expected == kAnySize || expected == size);
+
+  bytes.push_back(static_cast<uint8_t>(op));
+  varint(static_cast<uint32_t>(tick - last_tick));
+  last_tick = tick;
+  for (const auto arg : args) {
+    const auto bits = static_cast<uint32_t>(arg);
+    varint((bits << 1) ^ static_cast<uint32_t>(arg >> 31));
+  }
+  if (expected != 0) {
+    const auto start = static_cast<const uint8_t*>(payload);
+    varint(static_cast<uint32_t>(size));
+    bytes.insert(bytes.end(), start, start + size);
+  }
+  if (op == ReplayOp::Tick) tick++;
+}
+
+const uint8_t* ReplayLog::data() {
+  if (bytes.empty()) {
+    start();
+    stop();
+  }
+  const auto size = static_cast<uint32_t>(bytes.size() - sizeof(uint32_t));
+  memcpy(bytes.data(), &size, sizeof(size));
+  return bytes.data();
+}
+
+void ReplayLog::varint(uint32_t value) {
+  for (; value >= 0x80; value >>= 7) {
+    bytes.push_back(static_cast<uint8_t>(value | 0x80));
+  }
+  bytes.push_back(static_cast<uint8_t>(value));
+}
+
+bool decodeReplay(const uint8_t* data, int size,
+                  std::vector<ReplayCommand>& commands) {
+  commands.clear();
+  if (size < static_cast<int>(kReplayHeader)) return false;
+  if (memcmp(data, kReplayMagic, sizeof(kReplayMagic)) != 0) return false;
+  if (data[sizeof(kReplayMagic)] != kReplayVersion) return false;
+
+  auto cur = data + kReplayHeader;
+  const auto end = data + size;
+  const auto varint = [&](uint32_t& value) {
+    value = 0;
+    for (auto shift = 0; shift < 35 && cur != end; shift += 7) {
+      const auto byte = *(cur++);
+      value |= static_cast<uint32_t>(byte & 0x7f) << shift;
+      if (!(byte & 0x80)) return true;
+    }
+    return false;
+  };
+
+  auto tick = 0;
+  while (cur != end) {
+    const auto index = static_cast<int>(*(cur++));
+    if (!(0 < index && index < static_cast<int>(ReplayOp::Count))) {
+      return false;
+    }
+
+    uint32_t delta = 0;
+    if (!varint(delta)) return false;
+    tick += static_cast<int>(delta);
+
+    auto& command = commands.emplace_back();
+    command.op = static_cast<ReplayOp>(index);
+    command.tick = tick;
+    for (auto i = 0; i < kReplayArity[index]; i++) {
+      uint32_t bits = 0;
+      if (!varint(bits)) return false;
+      command.args.push_back(static_cast<int32_t>((bits >> 1) ^ -(bits & 1)));
+    }
+    if (const auto expected = payloadSize(command.op); expected != 0) {
+      uint32_t count = 0;
+      if (!varint(count)) return false;
+      if (end - cur < static_cast<int64_t>(count)) return false;
+      if (expected != kAnySize && count != static_cast<uint32_t>(expected)) {
+        return false;
+      }
+      command.payload.assign(cur, cur + count);
+      cur += count;
+    }
+  }
+  return true;
+}
+
+//////////////////////////////////////////////////////////////////////////////
+
+} // namespace voxels
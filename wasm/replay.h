@@ -0,0 +1,128 @@
+#pragma once
+
+#include <cstdint>
+#include <initializer_list>
+#include <vector>
+
+#include "base.h"
+
+//////////////////////////////////////////////////////////////////////////////
+
+namespace voxels {
+
+//////////////////////////////////////////////////////////////////////////////
+
+// Record and replay of the exports that change the world, for reproducing
+// bug reports against a debug build. A recording starts with a magic and a
+// version byte. Each command is then a one-byte op, the number of ticks since
+// the previous command as a varint, and the op's arguments as zigzag varints.
+// Ops for calls that take buffers end with a payload: a varint byte count,
+// followed by the buffer's bytes.
+//
+// Start recording right after registering blocks and materials, before any
+// chunks load. To replay, set up the world with the same registrations and
+// feed the stream back in. Worldgen and ticks are deterministic given the
+// same commands. Starting a recording and starting a replay both drop the
+// partial tick in advanceWorld's accumulator, so that both streams of ticks
+// line up. Loading chunk packets from the host isn't recorded, so sessions
+// that do so may not replay exactly.
+
+enum class ReplayOp : uint8_t {
+  Tick = 1,
+  Recenter,
+  Remesh,
+  SetBlock,
+  FillBlocks,
+  SetPointLight,
+  PushBlocks,
+  GrowTree,
+  TickFire,
+  ProtectRegion,
+  UnprotectRegions,
+  SetEditor,
+  SetDynamicLight,
+  Explode,
+  SetDimension,
+  RemoveDimension,
+  Advance,
+  Snapshot,
+  Rollback,
+  CopyToClipboard,
+  CreateClipboardShape,
+  ApplyCsg,
+  SetSymmetry,
+  SetSelection,
+  ClearSelection,
+  ExpandSelection,
+  ShiftSelection,
+  SetSelectionMask,
+  ReplaceInSelection,
+  PlayerEdit,
+  AddProjectile,
+  RemoveProjectile,
+  StepProjectiles,
+  SetChunkExtra,
+  CreateBlockEntity,
+  RemoveBlockEntity,
+  AddItemDrop,
+  RemoveItemDrop,
+  SetItemDropRules,
+  SetItemMagnets,
+  StepItemDrops,
+  PickUpItemDrops,
+  SetTickRate,
+  SetRaining,
+  VoxelizeMesh,
+  CarvePath,
+  ImportHeightmap,
+  ImportLayerPng,
+  SetSkyDarkness,
+  LoadSlice,
+  PropagatePower,
+  SetWorldWrap,
+  SetWorldSeed,
+  DecodeSnapshot,
+  Count,
+};
+
+struct ReplayCommand {
+  ReplayOp op;
+  int tick;
+  std::vector<int32_t> args;
+  std::vector<uint8_t> payload;
+};
+
+struct ReplayLog {
+  ReplayLog() = default;
+
+  void start();
+  void stop();
+  bool recording() const { return active; }
+
+  // A no-op unless we're recording. The number of args must match the op,
+  // and ops that take a payload must be recorded with one.
+  void record(ReplayOp op, std::initializer_list<int32_t> args);
+  void record(ReplayOp op, std::initializer_list<int32_t> args,
+              const void* payload, int size);
+
+  // Returns the stream: a uint32_t byte count, followed by the bytes.
+  const uint8_t* data();
+
+ private:
+  void varint(uint32_t value);
+
+  bool active = false;
+  int tick = 0;
+  int last_tick = 0;
+  std::vector<uint8_t> bytes;
+
+  DISALLOW_COPY_AND_ASSIGN(ReplayLog);
+};
+
+// Returns false if the stream is malformed or truncated.
+bool decodeReplay(const uint8_t* data, int size,
+                  std::vector<ReplayCommand>& commands);
+
+//////////////////////////////////////////////////////////////////////////////
+
+} // namespace voxels
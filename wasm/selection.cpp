@@ -0,0 +1,51 @@
+#include "selection.h"
+
+#include <algorithm>
+
+//////////////////////////////////////////////////////////////////////////////
+
+namespace voxels {
+
+//////////////////////////////////////////////////////////////////////////////
+
+Selection::Selection(const std::array<int, 3>& a,
+                     const std::array<int, 3>& b) {
+  for (auto i = 0; i < 3; i++) {
+    min[i] = std::min(a[i], b[i]);
+    max[i] = std::max(a[i], b[i]);
+  }
+}
+
+void Selection::expand(int face, int amount) {
+  assert(0 <= face && face < 6);
+  const auto axis = face >> 1;
+  if (face & 1) {
+    max[axis] = std::max(max[axis] + amount, min[axis]);
+  } else {
+    min[axis] = std::min(min[axis] - amount, max[axis]);
+  }
+}
+
+void Selection::shift(int dx, int dy, int dz) {
+  const auto delta = std::array{dx, dy, dz};
+  for (auto i = 0; i < 3; i++) {
+    min[i] += delta[i];
+    max[i] += delta[i];
+  }
+}
+
+bool Selection::contains(int x, int y, int z) const {
+  return min[0] <= x && x <= max[0] &&
+         min[1] <= y && y <= max[1] &&
+         min[2] <= z && z <= max[2];
+}
+
+int64_t Selection::volume() const {
+  auto result = int64_t{1};
+  for (auto i = 0; i < 3; i++) result *= int64_t{max[i]} - min[i] + 1;
+  return result;
+}
+
+//////////////////////////////////////////////////////////////////////////////
+
+} // namespace voxels
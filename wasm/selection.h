@@ -0,0 +1,36 @@
+#pragma once
+
+#include <array>
+#include <cstdint>
+
+#include "base.h"
+
+//////////////////////////////////////////////////////////////////////////////
+
+namespace voxels {
+
+//////////////////////////////////////////////////////////////////////////////
+
+// A builder tool's selection: an inclusive box of voxels. Tools grow it a
+// face at a time, move it, and measure what's inside. While the world's
+// selection mask is on, bulk edits only touch voxels in the selection.
+//
+// Faces are numbered as for face masks: [-x, +x, -y, +y, -z, +z]. Growing a
+// face by a negative amount shrinks the box, but never below one voxel.
+
+struct Selection {
+  std::array<int, 3> min;
+  std::array<int, 3> max;
+
+  Selection(const std::array<int, 3>& a, const std::array<int, 3>& b);
+
+  void expand(int face, int amount);
+  void shift(int dx, int dy, int dz);
+
+  bool contains(int x, int y, int z) const;
+  int64_t volume() const;
+};
+
+//////////////////////////////////////////////////////////////////////////////
+
+} // namespace voxels
@@ -0,0 +1,97 @@
+#include "slices.h"
+
+#include <cstdio>
+#include <cstring>
+
+//////////////////////////////////////////////////////////////////////////////
+
+namespace voxels {
+
+//////////////////////////////////////////////////////////////////////////////
+
+namespace {
+
+constexpr char kSliceChars[] =
+    ".123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
+constexpr int kNumSliceChars = sizeof(kSliceChars) - 1;
+constexpr char kAxisNames[] = "xyz";
+
+std::array<int, 2> getShape(int axis) {
+  return axis == 1 ? std::array{kChunkWidth, kChunkWidth}
+                   : std::array{kWorldHeight, kChunkWidth};
+}
+
+} // namespace
+
+//////////////////////////////////////////////////////////////////////////////
+
+std::array<int, 3> Slice::getVoxel(int row, int col) const {
+  const auto y = kWorldHeight - 1 - row;
+  if (axis == 0) return {index, y, col};
+  if (axis == 1) return {col, index, row};
+  return {col, y, index};
+}
+
+std::string dumpSlice(const Block* voxels, int axis, int index) {
+  assert(0 <= axis && axis < 3);
+  const auto limit = axis == 1 ? kWorldHeight : kChunkWidth;
+  assert(0 <= index && index < limit);
+
+  Slice slice;
+  slice.axis = axis;
+  slice.index = index;
+  const auto [rows, cols] = getShape(axis);
+
+  auto result = std::string("slice ") + kAxisNames[axis] + " " +
+                std::to_string(index) + "\n";
+  for (auto row = 0; row < rows; row++) {
+    for (auto col = 0; col < cols; col++) {
+      const auto [x, y, z] = slice.getVoxel(row, col);
+      const auto block = voxels[ChunkTensor3<Block>::index(x, y, z)];
+      const auto id = static_cast<int>(block);
+      result.push_back(id < kNumSliceChars ? kSliceChars[id] : '?');
+    }
+    result.push_back('\n');
+  }
+  return result;
+}
+
+bool parseSlice(const char* text, Slice& result) {
+  char axis = 0;
+  auto index = 0, consumed = 0;
+  if (sscanf(text, "slice %c %d%n", &axis, &index, &consumed) != 2) {
+    return false;
+  }
+  const auto name = strchr(kAxisNames, axis);
+  if (axis == 0 || name == nullptr) return false;
+
+  result.axis = static_cast<int>(name - kAxisNames);
+  result.index = index;
+  const auto limit = result.axis == 1 ? kWorldHeight : kChunkWidth;
+  if (!(0 <= index && index < limit)) return false;
+
+  const auto [rows, cols] = getShape(result.axis);
+  result.rows = rows;
+  result.cols = cols;
+  result.blocks.clear();
+
+  // Each row is a line of exactly `cols` characters. We allow \r\n endings
+  // and trailing blank lines, since these slices get pasted into reports.
+  auto cursor = text + consumed;
+  for (auto row = 0; row < rows; row++) {
+    while (*cursor == '\r') cursor++;
+    if (*cursor++ != '\n') return false;
+    for (auto col = 0; col < cols; col++) {
+      const auto found = *cursor ? strchr(kSliceChars, *cursor) : nullptr;
+      if (found == nullptr) return false;
+      result.blocks.push_back(static_cast<Block>(found - kSliceChars));
+      cursor++;
+    }
+  }
+  while (*cursor == '\r' || *cursor == '\n') cursor++;
+  return *cursor == '\0';
+}
+
+//////////////////////////////////////////////////////////////////////////////
+
+} // namespace voxels
@@ -0,0 +1,46 @@
+#pragma once
+
+#include <array>
+#include <string>
+#include <vector>
+
+#include "base.h"
+
+//////////////////////////////////////////////////////////////////////////////
+
+namespace voxels {
+
+//////////////////////////////////////////////////////////////////////////////
+
+// A text format for one axis-aligned slice of a chunk's voxels, so that bug
+// reports for the mesher and for lighting can include exact voxel content.
+// The first line is "slice <axis> <index>", where axis is x, y, or z, and
+// each following line is a row of the slice, with one character per block:
+//
+//   - axis x: rows are y, from the top of the world down; columns are z
+//   - axis y: rows are z, from 0 up; columns are x
+//   - axis z: rows are y, from the top of the world down; columns are x
+//
+// Air is '.', and blocks 1 through 61 are [1-9A-Za-z]. Blocks beyond that
+// print as '?', which the parser rejects, so those slices don't round-trip.
+
+struct Slice {
+  int axis = 0;
+  int index = 0;
+  int rows = 0;
+  int cols = 0;
+  std::vector<Block> blocks; // rows x cols, row-major
+
+  // Returns the chunk coordinates of the block at (row, col).
+  std::array<int, 3> getVoxel(int row, int col) const;
+};
+
+// Takes a chunk's voxels in chunk index order.
+std::string dumpSlice(const Block* voxels, int axis, int index);
+
+// Returns false if the text is malformed or has the wrong dimensions.
+bool parseSlice(const char* text, Slice& result);
+
+//////////////////////////////////////////////////////////////////////////////
+
+} // namespace voxels
@@ -0,0 +1,287 @@
+#include "smooth.h"
+
+#include <cmath>
+
+//////////////////////////////////////////////////////////////////////////////
+
+namespace voxels {
+
+//////////////////////////////////////////////////////////////////////////////
+
+namespace {
+
+using Vec3 = std::array<float, 3>;
+
+// The 12 edges of a cell, as pairs of its corners. Corner i is at the offset
+// (i & 1, (i >> 1) & 1, (i >> 2) & 1) from the cell's min corner.
+struct CellEdges {
+  CellEdges() {
+    for (auto& row : index) row.fill(-1);
+    auto next = 0;
+    for (auto d = 0; d < 3; d++) {
+      for (auto c = 0; c < 8; c++) {
+        if (c & (1 << d)) continue;
+        corners[next] = {c, c | (1 << d)};
+        index[c][c | (1 << d)] = index[c | (1 << d)][c] = next;
+        next++;
+      }
+    }
+  }
+
+  std::array<std::array<int, 2>, 12> corners;
+  std::array<std::array<int, 8>, 8> index;
+};
+
+const CellEdges& getCellEdges() {
+  static const CellEdges result;
+  return result;
+}
+
+// The marching cubes triangles for each case, where bit i of the case is
+// set if corner i is solid. Each triangle is three edge indices.
+//
+// We build the table rather than spelling it out. On each face of the cell,
+// seen from outside, we walk the corners counterclockwise, and we cut each
+// run of solid corners off with a segment from the edge where we enter it to
+// the edge where we leave it. An edge is entered on one of its faces and left
+// on the other, so the segments chain into loops around the solid part of
+// the cell, and we fan-triangulate each one. On an ambiguous face, with two
+// solid corners on a diagonal, each corner is its own run, so we keep them
+// apart: that only depends on the face, so both cells sharing it agree.
+struct MarchingCubesTable {
+  MarchingCubesTable() {
+    const auto& edges = getCellEdges();
+
+    std::array<std::array<int, 4>, 6> faces;
+    for (auto d = 0; d < 3; d++) {
+      const auto u = (d + 1) % 3, v = (d + 2) % 3;
+      for (auto side = 0; side < 2; side++) {
+        auto& face = faces[2 * d + side];
+        constexpr std::array<std::array<int, 2>, 4> kLoop = {{
+          {0, 0}, {1, 0}, {1, 1}, {0, 1},
+        }};
+        for (auto k = 0; k < 4; k++) {
+          // Counterclockwise from +d is clockwise from -d.
+          const auto [a, b] = kLoop[side ? k : 3 - k];
+          face[k] = (side << d) | (a << u) | (b << v);
+        }
+      }
+    }
+
+    for (auto c = 0; c < 256; c++) {
+      const auto solid = [&](int corner) { return (c >> corner) & 1; };
+
+      std::array<int, 12> next;
+      next.fill(-1);
+      for (const auto& face : faces) {
+        for (auto k = 0; k < 4; k++) {
+          const auto prev = face[(k + 3) % 4];
+          if (!solid(face[k]) || solid(prev)) continue;
+          auto j = k;
+          while (solid(face[(j + 1) % 4])) j = (j + 1) % 4;
+          const auto enter = edges.index[prev][face[k]];
+          next[enter] = edges.index[face[j]][face[(j + 1) % 4]];
+        }
+      }
+
+      std::array<bool, 12> seen = {};
+      for (auto e = 0; e < 12; e++) {
+        if (next[e] < 0 || seen[e]) continue;
+        std::vector<int> loop;
+        for (auto x = e; !seen[x]; x = next[x]) {
+          assert(next[x] >= 0);
+          seen[x] = true;
+          loop.push_back(x);
+        }
+        for (size_t i = 1; i + 1 < loop.size(); i++) {
+          cases[c].push_back({loop[0], loop[i], loop[i + 1]});
+        }
+      }
+    }
+  }
+
+  std::array<std::vector<std::array<int, 3>>, 256> cases;
+};
+
+const MarchingCubesTable& getMarchingCubesTable() {
+  static const MarchingCubesTable result;
+  return result;
+}
+
+// Voxel positions are in padded mesher coordinates, which are one more than
+// chunk coordinates, and voxel centers are half a voxel past their corners.
+Vec3 getVoxelCenter(int x, int y, int z) {
+  const auto center = [](int i) { return static_cast<float>(i) - 0.5f; };
+  return {center(x), center(y), center(z)};
+}
+
+// Returns the offset of the midpoint of the edge between two corners of a
+// cell from the cell's min corner.
+Vec3 getMidpoint(int c0, int c1) {
+  const auto mean = [](int a, int b) {
+    return 0.5f * static_cast<float>(a + b);
+  };
+  return {mean(c0 & 1, c1 & 1), mean((c0 >> 1) & 1, (c1 >> 1) & 1),
+          mean(c0 >> 2, c1 >> 2)};
+}
+
+bool isSolid(const MeshTensor3<Block>& voxels,
+             const SmoothTextures& textures, int x, int y, int z) {
+  const auto block = static_cast<int>(voxels.get(x, y, z));
+  return textures[6 * block] >= 0;
+}
+
+// Adds the triangle (a, b, c), textured with the face of the block at
+// `source` that's closest to the triangle's normal.
+void addTriangle(const MeshTensor3<Block>& voxels,
+                 const SmoothTextures& textures,
+                 const std::array<int, 3>& source,
+                 const Vec3& a, const Vec3& b, const Vec3& c,
+                 std::vector<float>& result) {
+  const Vec3 ab = {b[0] - a[0], b[1] - a[1], b[2] - a[2]};
+  const Vec3 ac = {c[0] - a[0], c[1] - a[1], c[2] - a[2]};
+  const Vec3 normal = {
+    ab[1] * ac[2] - ab[2] * ac[1],
+    ab[2] * ac[0] - ab[0] * ac[2],
+    ab[0] * ac[1] - ab[1] * ac[0],
+  };
+  auto d = 0;
+  for (auto i = 1; i < 3; i++) {
+    if (std::abs(normal[i]) > std::abs(normal[d])) d = i;
+  }
+  const auto face = 2 * d + (normal[d] > 0 ? 0 : 1);
+
+  const auto [x, y, z] = source;
+  const auto block = static_cast<int>(voxels.get(x, y, z));
+  const auto texture = static_cast<float>(textures[6 * block + face]);
+  for (const auto& p : {a, b, c}) {
+    result.insert(result.end(), {p[0], p[1], p[2], texture});
+  }
+}
+
+} // namespace
+
+//////////////////////////////////////////////////////////////////////////////
+
+void meshSurfaceNets(const MeshTensor3<Block>& voxels,
+                     const SmoothTextures& textures, int y_min, int y_max,
+                     std::vector<float>& result) {
+  assert(0 <= y_min && y_min < y_max && y_max <= kWorldHeight);
+  const auto& edges = getCellEdges();
+  const auto solid = [&](int x, int y, int z) {
+    return isSolid(voxels, textures, x, y, z);
+  };
+
+  // The vertex of each cell around the edges that we own, by min corner:
+  // x and z are in [0, kChunkWidth], and y is in [y_min, y_max]. Cells that
+  // the surface doesn't cross have no vertex, but we never look those up.
+  constexpr auto nx = kChunkWidth + 1, nz = kChunkWidth + 1;
+  const auto ny = y_max - y_min + 1;
+  const auto cell = [&](int x, int y, int z) {
+    return x + nx * ((y - y_min) + ny * z);
+  };
+  static std::vector<Vec3> vertices;
+  vertices.resize(nx * ny * nz);
+
+  for (auto z = 0; z < nz; z++) {
+    for (auto x = 0; x < nx; x++) {
+      for (auto y = y_min; y <= y_max; y++) {
+        auto mask = 0;
+        for (auto i = 0; i < 8; i++) {
+          const auto s = solid(x + (i & 1), y + ((i >> 1) & 1), z + (i >> 2));
+          mask |= s ? (1 << i) : 0;
+        }
+        if (mask == 0 || mask == 0xff) continue;
+
+        Vec3 sum = {0, 0, 0};
+        auto count = 0.0f;
+        for (const auto& [c0, c1] : edges.corners) {
+          if (((mask >> c0) & 1) == ((mask >> c1) & 1)) continue;
+          const auto midpoint = getMidpoint(c0, c1);
+          for (auto i = 0; i < 3; i++) sum[i] += midpoint[i];
+          count += 1;
+        }
+        const auto base = getVoxelCenter(x, y, z);
+        auto& vertex = vertices[cell(x, y, z)];
+        for (auto i = 0; i < 3; i++) vertex[i] = base[i] + sum[i] / count;
+      }
+    }
+  }
+
+  // We own the edges that start at the voxels in our columns and levels.
+  // Each crossed edge gets a quad joining the vertices of the four cells
+  // around it, facing away from its solid end.
+  for (auto z = 1; z <= kChunkWidth; z++) {
+    for (auto x = 1; x <= kChunkWidth; x++) {
+      for (auto y = y_min + 1; y <= y_max; y++) {
+        const std::array<int, 3> pos = {x, y, z};
+        const auto s0 = solid(x, y, z);
+        for (auto d = 0; d < 3; d++) {
+          auto end = pos;
+          end[d]++;
+          if (s0 == solid(end[0], end[1], end[2])) continue;
+
+          const auto u = (d + 1) % 3, v = (d + 2) % 3;
+          const auto vertex = [&](int du, int dv) {
+            auto p = pos;
+            p[u] -= du;
+            p[v] -= dv;
+            return vertices[cell(p[0], p[1], p[2])];
+          };
+          const auto c00 = vertex(1, 1), c10 = vertex(0, 1);
+          const auto c11 = vertex(0, 0), c01 = vertex(1, 0);
+          const auto& source = s0 ? pos : end;
+          if (s0) {
+            addTriangle(voxels, textures, source, c00, c10, c11, result);
+            addTriangle(voxels, textures, source, c00, c11, c01, result);
+          } else {
+            addTriangle(voxels, textures, source, c00, c11, c10, result);
+            addTriangle(voxels, textures, source, c00, c01, c11, result);
+          }
+        }
+      }
+    }
+  }
+}
+
+void meshMarchingCubes(const MeshTensor3<Block>& voxels,
+                       const SmoothTextures& textures, int y_min, int y_max,
+                       std::vector<float>& result) {
+  assert(0 <= y_min && y_min < y_max && y_max <= kWorldHeight);
+  const auto& edges = getCellEdges();
+  const auto& table = getMarchingCubesTable();
+
+  // We own the cells whose min corners are the voxels in our columns and
+  // levels. Crossings are at edge midpoints, since densities are 0 or 1.
+  for (auto z = 1; z <= kChunkWidth; z++) {
+    for (auto x = 1; x <= kChunkWidth; x++) {
+      for (auto y = y_min + 1; y <= y_max; y++) {
+        std::array<std::array<int, 3>, 8> corners;
+        auto mask = 0;
+        for (auto i = 0; i < 8; i++) {
+          corners[i] = {x + (i & 1), y + ((i >> 1) & 1), z + (i >> 2)};
+          const auto [cx, cy, cz] = corners[i];
+          mask |= isSolid(voxels, textures, cx, cy, cz) ? (1 << i) : 0;
+        }
+
+        const auto base = getVoxelCenter(x, y, z);
+        const auto crossing = [&](int edge) {
+          const auto [c0, c1] = edges.corners[edge];
+          const auto midpoint = getMidpoint(c0, c1);
+          return Vec3{base[0] + midpoint[0], base[1] + midpoint[1],
+                      base[2] + midpoint[2]};
+        };
+        for (const auto& [e0, e1, e2] : table.cases[mask]) {
+          const auto [c0, c1] = edges.corners[e0];
+          const auto& source = corners[(mask >> c0) & 1 ? c0 : c1];
+          addTriangle(voxels, textures, source, crossing(e0), crossing(e1),
+                      crossing(e2), result);
+        }
+      }
+    }
+  }
+}
+
+//////////////////////////////////////////////////////////////////////////////
+
+} // namespace voxels
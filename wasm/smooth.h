@@ -0,0 +1,61 @@
+#pragma once
+
+#include <array>
+#include <cstdint>
+#include <vector>
+
+#include "base.h"
+
+//////////////////////////////////////////////////////////////////////////////
+
+namespace voxels {
+
+//////////////////////////////////////////////////////////////////////////////
+
+// Smooth meshers turn the blocky voxel field into a smooth surface, for
+// worlds with a natural style. A voxel is either solid, with density 1 at its
+// center, or empty, with density 0, and both meshers extract the 0.5 level
+// set. On flat terrain, that's exactly the block faces, but corners and
+// edges are cut:
+//
+//   - Surface nets: one vertex per cell of 8 voxel centers that the surface
+//     crosses, at the mean of the crossings on its edges, and one quad per
+//     crossed edge between voxel centers. It rounds corners and edges off.
+//
+//   - Marching cubes: vertices at the crossings themselves, with triangles
+//     from a table of the 256 cases of a cell's corners. It chamfers them.
+//     We split ambiguous faces so that solid corners stay apart, which is
+//     consistent between the cells on either side, so the mesh is watertight.
+//
+// Both take the mesher's padded voxels, which have a border of one voxel on
+// each side, and emit the surface for the (unpadded) levels [y_min, y_max)
+// of the chunk. Each chunk and section owns the cells and edges that start
+// at its own voxels, so adjacent surfaces meet without gaps or overlaps.
+//
+// Textures come from the solid voxel next to each triangle, as the face of
+// that block that's closest to the triangle's normal. textures holds each
+// block's face textures in the registry's order, at 6 * block + face, or -1
+// for blocks that we don't smooth, which count as empty.
+//
+// We emit unindexed triangles in chunk coordinates, counterclockwise seen
+// from outside, with kSmoothVertexFloats floats per vertex:
+//
+//   x, y, z, texture
+//
+// The renderer derives normals from the positions, and UVs from the
+// positions and normals, so triangles of one texture tile seamlessly.
+constexpr int kSmoothVertexFloats = 4;
+
+using SmoothTextures = std::array<int16_t, 6 * 256>;
+
+void meshSurfaceNets(const MeshTensor3<Block>& voxels,
+                     const SmoothTextures& textures, int y_min, int y_max,
+                     std::vector<float>& result);
+
+void meshMarchingCubes(const MeshTensor3<Block>& voxels,
+                       const SmoothTextures& textures, int y_min, int y_max,
+                       std::vector<float>& result);
+
+//////////////////////////////////////////////////////////////////////////////
+
+} // namespace voxels
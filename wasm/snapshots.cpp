@@ -0,0 +1,91 @@
+#include "snapshots.h"
+
+#include <algorithm>
+#include <cmath>
+
+//////////////////////////////////////////////////////////////////////////////
+
+namespace voxels {
+
+//////////////////////////////////////////////////////////////////////////////
+
+bool SnapshotBuffer::push(
+    double time, const EntityTransform* transforms, int count) {
+  assert(count >= 0);
+  if (!std::isfinite(time)) return false;
+  if (size > 0 && get(size - 1).time > time) return false;
+
+  if (size == kMaxSnapshots) {
+    start = (start + 1) % kMaxSnapshots;
+    size--;
+  }
+  auto& snapshot = snapshots[(start + size) % kMaxSnapshots];
+  size++;
+
+  snapshot.time = time;
+  snapshot.transforms.assign(transforms, transforms + count);
+  std::sort(snapshot.transforms.begin(), snapshot.transforms.end(),
+            [](const auto& a, const auto& b) { return a.id < b.id; });
+  return true;
+}
+
+void SnapshotBuffer::clear() {
+  start = 0;
+  size = 0;
+}
+
+const float* SnapshotBuffer::sample(
+    double time, const std::array<int, 3>& origin) {
+  result.clear();
+  result.push_back(0);
+  if (size == 0) return result.data();
+
+  // Pick the snapshots a and b to blend, and the blend factor t. Before our
+  // first snapshot, we hold it; after the last, we extrapolate.
+  auto i = 0;
+  while (i + 1 < size && get(i + 1).time <= time) i++;
+  const auto last = i + 1 == size;
+  const auto& a = get(last ? std::max(i - 1, 0) : i);
+  const auto& b = get(last ? i : i + 1);
+
+  auto t = 0.0;
+  const auto span = b.time - a.time;
+  if (span > 0) {
+    const auto clamped = std::min(time, b.time + kMaxExtrapolation);
+    t = std::max(clamped - a.time, 0.0) / span;
+  }
+  if (&a == &b) t = 1;
+
+  // Entities that appear only in b snap to their new transforms. Entities
+  // that appear only in a have been removed.
+  const auto lerp = [&](float x, float y) {
+    return static_cast<float>(x + t * (y - x));
+  };
+  const auto place = [&](double x, double y, int o) {
+    return static_cast<float>(x + t * (y - x) - o);
+  };
+  for (const auto& next : b.transforms) {
+    const auto it = std::lower_bound(
+        a.transforms.begin(), a.transforms.end(), next.id,
+        [](const auto& x, int id) { return x.id < id; });
+    const auto found = it != a.transforms.end() && it->id == next.id;
+    const auto& prev = found ? *it : next;
+
+    auto delta = next.heading - prev.heading;
+    delta -= static_cast<float>(2 * M_PI * std::round(delta / (2 * M_PI)));
+
+    result.insert(result.end(), {
+      static_cast<float>(next.id),
+      place(prev.x, next.x, origin[0]),
+      place(prev.y, next.y, origin[1]),
+      place(prev.z, next.z, origin[2]),
+      lerp(prev.heading, prev.heading + delta),
+    });
+    result[0]++;
+  }
+  return result.data();
+}
+
+//////////////////////////////////////////////////////////////////////////////
+
+} // namespace voxels
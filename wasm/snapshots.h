@@ -0,0 +1,71 @@
+#pragma once
+
+#include <array>
+#include <cstdint>
+#include <vector>
+
+#include "base.h"
+
+//////////////////////////////////////////////////////////////////////////////
+
+namespace voxels {
+
+//////////////////////////////////////////////////////////////////////////////
+
+// Networked entity state arrives as timestamped snapshots. We render remote
+// entities slightly in the past, interpolating between the two snapshots
+// around the render time. If the render time is past our latest snapshot,
+// we extrapolate from the last two, for at most kMaxExtrapolation seconds.
+//
+// Positions are doubles, so that entities far from the world origin don't
+// jitter. We interpolate in double precision and only convert to floats,
+// relative to the render origin, when we emit the sampled transforms.
+
+constexpr int kMaxSnapshots = 32;
+constexpr double kMaxExtrapolation = 0.25;
+
+struct EntityTransform {
+  int id;
+  double x;
+  double y;
+  double z;
+  float heading;
+};
+
+struct SnapshotBuffer {
+  SnapshotBuffer() = default;
+
+  // Adds a snapshot of `count` transforms. Snapshots must arrive in order of
+  // time; when the buffer is full, we drop the oldest one. Returns false,
+  // dropping the snapshot, if it's older than the latest one, since packets
+  // may be reordered in transit.
+  bool push(double time, const EntityTransform* transforms, int count);
+  void clear();
+
+  // Returns a packed buffer: a count, followed by (id, x, y, z, heading) for
+  // each entity in the snapshots around the given time. Ids are stored as
+  // floats, which is exact for ids below 2^24. Positions are relative to the
+  // given origin, which should be the render origin.
+  const float* sample(double time, const std::array<int, 3>& origin);
+
+ private:
+  struct Snapshot {
+    double time = 0;
+    std::vector<EntityTransform> transforms;
+  };
+
+  const Snapshot& get(int i) const {
+    return snapshots[(start + i) % kMaxSnapshots];
+  }
+
+  int start = 0;
+  int size = 0;
+  std::vector<float> result;
+  NonCopyArray<Snapshot, kMaxSnapshots> snapshots;
+
+  DISALLOW_COPY_AND_ASSIGN(SnapshotBuffer);
+};
+
+//////////////////////////////////////////////////////////////////////////////
+
+} // namespace voxels
@@ -0,0 +1,39 @@
+#include "spread.h"
+
+#include "mesher.h"
+
+//////////////////////////////////////////////////////////////////////////////
+
+namespace voxels {
+
+//////////////////////////////////////////////////////////////////////////////
+
+std::optional<BlockChange> spreadBlock(
+    BlockAccess& world, const Registry& registry, int x, int y, int z,
+    Block block, const SpreadData& data, Random& rng) {
+  const auto covered = [&](int x, int y, int z) {
+    return registry.getBlock(world.getBlock(x, y + 1, z)).opaque;
+  };
+  if (covered(x, y, z)) {
+    if (data.decay == Block::Air) return std::nullopt;
+    return BlockChange{x, y, z, data.decay};
+  }
+
+  // Pick a random target in the 3 x 5 x 3 box around this block, biased
+  // downwards so that grass can spread down hillsides.
+  const auto r = rng.next();
+  const auto tx = x + static_cast<int>(r % 3) - 1;
+  const auto ty = y + static_cast<int>((r >> 8) % 5) - 3;
+  const auto tz = z + static_cast<int>((r >> 16) % 3) - 1;
+  if (world.getBlock(tx, ty, tz) != data.target || covered(tx, ty, tz)) {
+    return std::nullopt;
+  }
+  if (world.getLightLevel(tx, ty + 1, tz) < data.min_light) {
+    return std::nullopt;
+  }
+  return BlockChange{tx, ty, tz, block};
+}
+
+//////////////////////////////////////////////////////////////////////////////
+
+} // namespace voxels
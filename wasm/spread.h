@@ -0,0 +1,37 @@
+#pragma once
+
+#include <cstdint>
+#include <optional>
+
+#include "access.h"
+#include "base.h"
+
+//////////////////////////////////////////////////////////////////////////////
+
+namespace voxels {
+
+//////////////////////////////////////////////////////////////////////////////
+
+// Block spread and decay, like grass spreading over dirt, which runs on
+// random ticks. See Registry::setSpread and World::randomTick.
+
+struct Registry;
+
+// On a random tick, a spreading block (like grass) converts a nearby target
+// block (like dirt) if the target is uncovered and lit to at least min_light.
+// If the spreading block is covered by an opaque block, it decays instead.
+struct SpreadData {
+  Block target = Block::Air;
+  Block decay = Block::Air;
+  uint8_t min_light = 0;
+};
+
+// Runs a random tick for the spreading block at (x, y, z), returning the
+// change to make, if any.
+std::optional<BlockChange> spreadBlock(
+    BlockAccess& world, const Registry& registry, int x, int y, int z,
+    Block block, const SpreadData& data, Random& rng);
+
+//////////////////////////////////////////////////////////////////////////////
+
+} // namespace voxels
@@ -0,0 +1,87 @@
+#include "structures.h"
+
+#include <algorithm>
+#include <limits>
+
+//////////////////////////////////////////////////////////////////////////////
+
+namespace voxels {
+
+//////////////////////////////////////////////////////////////////////////////
+
+void StructureIndex::add(const Structure& structure) {
+  const auto& [type, min, max] = structure;
+  for (auto i = 0; i < 3; i++) assert(min[i] <= max[i]);
+
+  const auto index = static_cast<int>(structures.size());
+  structures.push_back(structure);
+  for (auto rz = min[2] >> kShift; rz <= max[2] >> kShift; rz++) {
+    for (auto rx = min[0] >> kShift; rx <= max[0] >> kShift; rx++) {
+      regions[regionKey(rx, rz)].push_back(index);
+    }
+  }
+}
+
+void StructureIndex::clear() {
+  structures.clear();
+  regions.clear();
+}
+
+void StructureIndex::query(
+    const std::array<int, 3>& min, const std::array<int, 3>& max,
+    std::vector<Structure>& result) const {
+  for (auto i = 0; i < 3; i++) assert(min[i] <= max[i]);
+
+  for (auto rz = min[2] >> kShift; rz <= max[2] >> kShift; rz++) {
+    for (auto rx = min[0] >> kShift; rx <= max[0] >> kShift; rx++) {
+      const auto it = regions.find(regionKey(rx, rz));
+      if (it == regions.end()) continue;
+
+      for (const auto index : it->second) {
+        const auto& structure = structures[index];
+        auto overlaps = true;
+        for (auto i = 0; i < 3; i++) {
+          if (structure.max[i] < min[i] || max[i] < structure.min[i]) {
+            overlaps = false;
+          }
+        }
+        if (!overlaps) continue;
+
+        // A structure that spans regions is in each of their lists. Report
+        // it from the first region that both it and the query cover.
+        const auto fx = std::max(structure.min[0], min[0]) >> kShift;
+        const auto fz = std::max(structure.min[2], min[2]) >> kShift;
+        if (fx == rx && fz == rz) result.push_back(structure);
+      }
+    }
+  }
+}
+
+const Structure* StructureIndex::nearest(int type, int x, int z) const {
+  const auto distance = [&](const Structure& structure) {
+    const auto dx = std::max({structure.min[0] - x, 0, x - structure.max[0]});
+    const auto dz = std::max({structure.min[2] - z, 0, z - structure.max[2]});
+    return static_cast<int64_t>(dx) * dx + static_cast<int64_t>(dz) * dz;
+  };
+
+  const Structure* result = nullptr;
+  auto best = std::numeric_limits<int64_t>::max();
+  for (const auto& structure : structures) {
+    if (structure.type != type) continue;
+    const auto d = distance(structure);
+    if (d < best) {
+      best = d;
+      result = &structure;
+    }
+  }
+  return result;
+}
+
+uint64_t StructureIndex::regionKey(int rx, int rz) {
+  return (static_cast<uint64_t>(static_cast<uint32_t>(rx)) << 32) |
+         static_cast<uint64_t>(static_cast<uint32_t>(rz));
+}
+
+//////////////////////////////////////////////////////////////////////////////
+
+} // namespace voxels
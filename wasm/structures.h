@@ -0,0 +1,56 @@
+#pragma once
+
+#include <array>
+#include <cstdint>
+#include <vector>
+
+#include "base.h"
+#include "regions.h"
+
+//////////////////////////////////////////////////////////////////////////////
+
+namespace voxels {
+
+//////////////////////////////////////////////////////////////////////////////
+
+// An index of the structures placed in the world, such as villages, by type
+// and bounding box, for "locate" commands and for spawn rules. Types are
+// game-defined ints. Bounds are inclusive world-space voxel coordinates.
+//
+// We bucket structures by the region (see regions.h) of each chunk column
+// that they overlap, so box queries only touch nearby regions. The index
+// is independent of which chunks are loaded: structures stay in it after
+// their chunks unload.
+
+struct Structure {
+  int type;
+  std::array<int, 3> min;
+  std::array<int, 3> max;
+};
+
+struct StructureIndex {
+  void add(const Structure& structure);
+  void clear();
+
+  // Appends each structure that overlaps the box [min, max] to the result.
+  void query(const std::array<int, 3>& min, const std::array<int, 3>& max,
+             std::vector<Structure>& result) const;
+
+  // Returns the structure of the given type closest to (x, z), measured
+  // horizontally from the point to its box, or nullptr if there isn't one.
+  const Structure* nearest(int type, int x, int z) const;
+
+  size_t size() const { return structures.size(); }
+
+ private:
+  static constexpr int kShift = kRegionBits + kChunkBits;
+
+  static uint64_t regionKey(int rx, int rz);
+
+  std::vector<Structure> structures;
+  HashMap<uint64_t, std::vector<int>> regions;
+};
+
+//////////////////////////////////////////////////////////////////////////////
+
+} // namespace voxels
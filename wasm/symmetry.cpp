@@ -0,0 +1,104 @@
+#include "symmetry.h"
+
+#include <algorithm>
+#include <cmath>
+
+//////////////////////////////////////////////////////////////////////////////
+
+namespace voxels {
+
+//////////////////////////////////////////////////////////////////////////////
+
+namespace {
+
+int countBits(int mask) {
+  auto result = 0;
+  for (; mask; mask &= mask - 1) result++;
+  return result;
+}
+
+} // namespace
+
+//////////////////////////////////////////////////////////////////////////////
+
+int Symmetry::count() const {
+  const auto mask = mirrors & (kMirrorX | kMirrorY | kMirrorZ);
+  return (1 << countBits(mask)) * std::clamp(radial, 1, kMaxRadialSymmetry);
+}
+
+std::array<int, 3> Symmetry::apply(int transform,
+                                   const std::array<int, 3>& p) const {
+  const auto n = std::clamp(radial, 1, kMaxRadialSymmetry);
+  const auto rotation = transform % n;
+  auto mask = transform / n;
+
+  // The k-th bit of the mirror mask applies the k-th enabled plane.
+  auto result = p;
+  for (auto i = 0; i < 3; i++) {
+    if (!(mirrors & (1 << i))) continue;
+    if (mask & 1) result[i] = planes2[i] - result[i] - 1;
+    mask >>= 1;
+  }
+  if (rotation == 0) return result;
+
+  // Rotate the voxel's center around the axis, in doubled coordinates.
+  const auto u = 2 * result[0] + 1 - axis_x2;
+  const auto v = 2 * result[2] + 1 - axis_z2;
+  if (4 * rotation % n == 0) {
+    const auto quarter = 4 * rotation / n;
+    auto ru = u, rv = v;
+    for (auto i = 0; i < quarter; i++) {
+      const auto t = ru;
+      ru = -rv;
+      rv = t;
+    }
+    result[0] = static_cast<int>(std::floor((ru + axis_x2 - 1) / 2.0 + 0.5));
+    result[2] = static_cast<int>(std::floor((rv + axis_z2 - 1) / 2.0 + 0.5));
+    return result;
+  }
+  const auto angle = 2 * M_PI * rotation / n;
+  const auto c = std::cos(angle), s = std::sin(angle);
+  const auto ru = c * u - s * v, rv = s * u + c * v;
+  result[0] = static_cast<int>(std::floor((ru + axis_x2 - 1) / 2 + 0.5));
+  result[2] = static_cast<int>(std::floor((rv + axis_z2 - 1) / 2 + 0.5));
+  return result;
+}
+
+bool Symmetry::preservesBoxes(int transform) const {
+  const auto n = std::clamp(radial, 1, kMaxRadialSymmetry);
+  return 4 * (transform % n) % n == 0;
+}
+
+void Symmetry::applyToBox(int transform, std::array<int, 3>& min,
+                          std::array<int, 3>& max) const {
+  auto lo = apply(transform, min);
+  auto hi = lo;
+  for (auto i = 1; i < 8; i++) {
+    const auto corner = std::array{(i & 1) ? max[0] : min[0],
+                                   (i & 2) ? max[1] : min[1],
+                                   (i & 4) ? max[2] : min[2]};
+    const auto image = apply(transform, corner);
+    for (auto j = 0; j < 3; j++) {
+      lo[j] = std::min(lo[j], image[j]);
+      hi[j] = std::max(hi[j], image[j]);
+    }
+  }
+  min = lo;
+  max = hi;
+}
+
+void Symmetry::images(const std::array<int, 3>& p,
+                      std::vector<std::array<int, 3>>& result) const {
+  result.clear();
+  const auto total = count();
+  for (auto i = 0; i < total; i++) {
+    const auto image = apply(i, p);
+    if (std::find(result.begin(), result.end(), image) == result.end()) {
+      result.push_back(image);
+    }
+  }
+}
+
+//////////////////////////////////////////////////////////////////////////////
+
+} // namespace voxels
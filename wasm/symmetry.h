@@ -0,0 +1,57 @@
+#pragma once
+
+#include <array>
+#include <vector>
+
+#include "base.h"
+
+//////////////////////////////////////////////////////////////////////////////
+
+namespace voxels {
+
+//////////////////////////////////////////////////////////////////////////////
+
+// Symmetry for builder edits: the world repeats an edit at each image of the
+// edited voxels, so a tool can build a mirrored or radial structure with one
+// edit. We first mirror across any of the enabled planes, then rotate the
+// results around a vertical axis by each multiple of 360 / radial degrees.
+//
+// Planes and the axis are given at twice their coordinates, so that ones
+// through voxel centers (odd) and ones between voxels (even) are integers.
+// Rotations by multiples of 90 degrees are exact if the axis goes through
+// voxel centers or corners in both x and z; other rotations round images to
+// the nearest voxel, so their edits may have small gaps.
+
+constexpr int kMirrorX = 1 << 0;
+constexpr int kMirrorY = 1 << 1;
+constexpr int kMirrorZ = 1 << 2;
+constexpr int kMaxRadialSymmetry = 64;
+
+struct Symmetry {
+  int mirrors = 0;
+  std::array<int, 3> planes2 = {0, 0, 0};
+  int radial = 1;
+  int axis_x2 = 0;
+  int axis_z2 = 0;
+
+  // The number of transforms, including the identity, which is transform 0.
+  int count() const;
+
+  std::array<int, 3> apply(int transform, const std::array<int, 3>& p) const;
+
+  // True if the transform maps boxes to boxes, so that we can edit a box's
+  // image as a box instead of voxel by voxel.
+  bool preservesBoxes(int transform) const;
+
+  // Writes the bounds of the image of the inclusive box [min, max].
+  void applyToBox(int transform, std::array<int, 3>& min,
+                  std::array<int, 3>& max) const;
+
+  // Writes the voxel's distinct images, starting with the voxel itself.
+  void images(const std::array<int, 3>& p,
+              std::vector<std::array<int, 3>>& result) const;
+};
+
+//////////////////////////////////////////////////////////////////////////////
+
+} // namespace voxels
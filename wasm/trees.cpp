@@ -0,0 +1,57 @@
+#include "trees.h"
+
+#include <algorithm>
+
+//////////////////////////////////////////////////////////////////////////////
+
+namespace voxels {
+
+//////////////////////////////////////////////////////////////////////////////
+
+void buildTree(const TreeSpecies& species, uint64_t seed,
+               std::vector<StampBlock>& result) {
+  result.clear();
+
+  auto rng = Random(seed);
+  const auto range = species.max_height - species.min_height + 1;
+  const auto height =
+      species.min_height + static_cast<int>(rng.next() % range);
+  for (auto dy = 0; dy < height; dy++) {
+    result.push_back({0, dy, 0, species.trunk, true});
+  }
+
+  const auto r = static_cast<int>(species.radius);
+  const auto leaf = [&](int dx, int dy, int dz) {
+    result.push_back({dx, dy, dz, species.leaves, false});
+  };
+
+  if (species.conical) {
+    // Discs that widen by one block every two levels down from the top.
+    for (auto dy = std::max(height - 2 * r, 2); dy <= height; dy++) {
+      const auto radius = std::min(r, (height - dy + 1) / 2);
+      const auto limit = radius * radius + radius;
+      for (auto dx = -radius; dx <= radius; dx++) {
+        for (auto dz = -radius; dz <= radius; dz++) {
+          if (dx * dx + dz * dz <= limit) leaf(dx, dy, dz);
+        }
+      }
+    }
+  } else {
+    // A ball around the top of the trunk, with randomly trimmed edges.
+    const auto outer = (r + 0.5) * (r + 0.5);
+    const auto inner = (r - 0.5) * (r - 0.5);
+    for (auto dx = -r; dx <= r; dx++) {
+      for (auto dy = -r; dy <= r; dy++) {
+        for (auto dz = -r; dz <= r; dz++) {
+          const auto d2 = dx * dx + dy * dy + dz * dz;
+          if (d2 > outer || (d2 > inner && rng.uniform() < 0.5)) continue;
+          leaf(dx, height - 1 + dy, dz);
+        }
+      }
+    }
+  }
+}
+
+//////////////////////////////////////////////////////////////////////////////
+
+} // namespace voxels
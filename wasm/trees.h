@@ -0,0 +1,48 @@
+#pragma once
+
+#include <cstdint>
+#include <vector>
+
+#include "base.h"
+
+//////////////////////////////////////////////////////////////////////////////
+
+namespace voxels {
+
+//////////////////////////////////////////////////////////////////////////////
+
+// Trees: procedurally varied species, grown from saplings on random ticks
+// or placed directly. We build a tree as a list of blocks at offsets from
+// its base, which World::stamp places if the trunk fits. See
+// Registry::addTreeSpecies and Registry::setSapling.
+
+// A procedurally varied tree. Trunks are [min_height, max_height] tall, and
+// they're topped by a canopy of the given radius that's round or conical.
+struct TreeSpecies {
+  Block trunk;
+  Block leaves;
+  uint8_t min_height;
+  uint8_t max_height;
+  uint8_t radius;
+  bool conical;
+};
+
+// On a random tick, a sapling grows into a tree of its species with the
+// given chance. A species of -1 means the block isn't a sapling.
+struct SaplingData {
+  int species = -1;
+  double chance = 0.0;
+};
+
+// A block to place at an offset from a stamp's origin. A stamp fails if a
+// required block's cell is occupied; other blocks are skipped instead.
+struct StampBlock { int dx, dy, dz; Block block; bool required; };
+
+// Builds a tree of the given species, based at the origin, using seed for
+// randomness. The trunk blocks are required. Clears the result first.
+void buildTree(const TreeSpecies& species, uint64_t seed,
+               std::vector<StampBlock>& result);
+
+//////////////////////////////////////////////////////////////////////////////
+
+} // namespace voxels
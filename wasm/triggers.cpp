@@ -0,0 +1,45 @@
+#include "triggers.h"
+
+//////////////////////////////////////////////////////////////////////////////
+
+namespace voxels {
+
+//////////////////////////////////////////////////////////////////////////////
+
+void TriggerTracker::update(
+    const std::vector<std::pair<int, TriggerContacts>>& current,
+    bool stays, std::vector<TriggerChange>& result) {
+  result.clear();
+
+  const auto exit = [&](int entity, const TriggerContacts& before,
+                        uint32_t mask) {
+    for (auto type = 1; type < kMaxTriggerTypes; type++) {
+      if (!(mask & (1u << type))) continue;
+      result.push_back(
+          {TriggerPhase::Exit, entity, type, before.voxels[type]});
+    }
+  };
+
+  HashMap<int, TriggerContacts> next;
+  for (const auto& [entity, now] : current) {
+    const auto it = contacts.find(entity);
+    const auto before = it != contacts.end() ? it->second.mask : 0;
+    for (auto type = 1; type < kMaxTriggerTypes; type++) {
+      const auto bit = 1u << type;
+      if (!(now.mask & bit) || ((before & bit) && !stays)) continue;
+      const auto phase =
+          before & bit ? TriggerPhase::Stay : TriggerPhase::Enter;
+      result.push_back({phase, entity, type, now.voxels[type]});
+    }
+    if (it != contacts.end()) exit(entity, it->second, before & ~now.mask);
+    next[entity] = now;
+  }
+  for (const auto& [entity, before] : contacts) {
+    if (next.find(entity) == next.end()) exit(entity, before, before.mask);
+  }
+  contacts = std::move(next);
+}
+
+//////////////////////////////////////////////////////////////////////////////
+
+} // namespace voxels
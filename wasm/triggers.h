@@ -0,0 +1,67 @@
+#pragma once
+
+#include <array>
+#include <cstdint>
+#include <utility>
+#include <vector>
+
+#include "base.h"
+
+//////////////////////////////////////////////////////////////////////////////
+
+namespace voxels {
+
+//////////////////////////////////////////////////////////////////////////////
+
+// Trigger volumes: boxes inside blocks, such as the bottom sixteenth of a
+// pressure plate or the whole voxel of a portal or of water, that raise
+// events as entities enter them, stay in them, and exit them. The host
+// passes every entity's AABB to World::stepTriggers after each physics step,
+// so that the overlap tests run here instead of in JS.
+//
+// Trigger types are game-defined, from 1 to kMaxTriggerTypes - 1; type 0 is
+// no trigger. We track contacts per entity and type, not per voxel, so an
+// entity crossing from one water voxel to the next stays in the trigger.
+// Each contact's voxel is the first voxel of the type that we find.
+//
+// We skip entities spanning more than kMaxTriggerExtent voxels on any axis.
+
+constexpr int kMaxTriggerTypes = 32;
+constexpr int kMaxTriggerExtent = 16;
+
+// A block's trigger: its type, and its box, in the unit voxel.
+struct TriggerVolume {
+  uint8_t type = 0;
+  std::array<double, 3> min = {0, 0, 0};
+  std::array<double, 3> max = {1, 1, 1};
+};
+
+struct TriggerContacts {
+  uint32_t mask = 0;
+  std::array<std::array<int, 3>, kMaxTriggerTypes> voxels;
+};
+
+enum class TriggerPhase : uint8_t { Enter, Stay, Exit };
+
+struct TriggerChange {
+  TriggerPhase phase;
+  int entity;
+  int type;
+  std::array<int, 3> voxel;
+};
+
+struct TriggerTracker {
+  // Replaces every entity's contacts with `current`, in which each entity
+  // appears once, and writes the enters and exits to the result, plus the
+  // stays if requested. Entities missing from `current` exit every trigger.
+  // Exits report the voxel of the last contact.
+  void update(const std::vector<std::pair<int, TriggerContacts>>& current,
+              bool stays, std::vector<TriggerChange>& result);
+
+ private:
+  HashMap<int, TriggerContacts> contacts;
+};
+
+//////////////////////////////////////////////////////////////////////////////
+
+} // namespace voxels
@@ -0,0 +1,107 @@
+#include "updates.h"
+
+#include "mesher.h"
+
+//////////////////////////////////////////////////////////////////////////////
+
+namespace voxels {
+
+//////////////////////////////////////////////////////////////////////////////
+
+void findDecayingLeaves(BlockAccess& world, const Registry& registry,
+                        int x, int y, int z,
+                        std::vector<std::array<int, 3>>& result) {
+  constexpr auto R = kLeafDecayDistance;
+  constexpr auto S = 4 * R + 1;
+  static std::vector<int> distances;
+  static std::vector<std::array<int, 3>> queue;
+  distances.assign(S * S * S, -1);
+  queue.clear();
+
+  // Local coordinates in the box of size S centered on (x, y, z). A leaf
+  // within R of (x, y, z) is only reachable from logs within 2R of it.
+  const auto index = [&](int lx, int ly, int lz) {
+    return lx + S * (ly + S * lz);
+  };
+  for (auto lx = 0; lx < S; lx++) {
+    for (auto ly = 0; ly < S; ly++) {
+      for (auto lz = 0; lz < S; lz++) {
+        const auto block = world.getBlock(x + lx - 2 * R, y + ly - 2 * R,
+                                          z + lz - 2 * R);
+        if (!registry.hasTag(block, kBlockTagLog)) continue;
+        distances[index(lx, ly, lz)] = 0;
+        queue.push_back({lx, ly, lz});
+      }
+    }
+  }
+
+  for (auto i = 0; i < queue.size(); i++) {
+    const auto [lx, ly, lz] = queue[i];
+    const auto distance = distances[index(lx, ly, lz)];
+    if (distance >= R) continue;
+    for (const auto& [dx, dy, dz] : kFaceNeighbors) {
+      const auto nx = lx + dx, ny = ly + dy, nz = lz + dz;
+      if (!(0 <= nx && nx < S && 0 <= ny && ny < S && 0 <= nz && nz < S)) {
+        continue;
+      }
+      auto& entry = distances[index(nx, ny, nz)];
+      if (entry >= 0) continue;
+      const auto block = world.getBlock(x + nx - 2 * R, y + ny - 2 * R,
+                                        z + nz - 2 * R);
+      if (!registry.hasTag(block, kBlockTagLeaves)) continue;
+      entry = distance + 1;
+      queue.push_back({nx, ny, nz});
+    }
+  }
+
+  for (auto dx = -R; dx <= R; dx++) {
+    for (auto dy = -R; dy <= R; dy++) {
+      for (auto dz = -R; dz <= R; dz++) {
+        const auto l = index(dx + 2 * R, dy + 2 * R, dz + 2 * R);
+        if (distances[l] >= 0) continue;
+        const auto block = world.getBlock(x + dx, y + dy, z + dz);
+        if (!registry.hasTag(block, kBlockTagLeaves)) continue;
+        result.push_back({x + dx, y + dy, z + dz});
+      }
+    }
+  }
+}
+
+bool hasLogNearby(BlockAccess& world, const Registry& registry,
+                  int x, int y, int z) {
+  constexpr auto R = kLeafDecayDistance;
+  constexpr auto S = 2 * R + 1;
+  static std::vector<bool> visited;
+  static std::vector<std::pair<std::array<int, 3>, int>> queue;
+  visited.assign(S * S * S, false);
+  queue.clear();
+
+  // Local coordinates in the box of size S centered on (x, y, z). Every
+  // voxel within R steps of (x, y, z) is in this box.
+  const auto index = [&](int lx, int ly, int lz) {
+    return lx + S * (ly + S * lz);
+  };
+  visited[index(R, R, R)] = true;
+  queue.push_back({{R, R, R}, 0});
+
+  for (auto i = 0; i < queue.size(); i++) {
+    const auto [pos, distance] = queue[i];
+    if (distance >= R) continue;
+    for (const auto& [dx, dy, dz] : kFaceNeighbors) {
+      const auto nx = pos[0] + dx, ny = pos[1] + dy, nz = pos[2] + dz;
+      const auto l = index(nx, ny, nz);
+      if (visited[l]) continue;
+      visited[l] = true;
+
+      const auto block = world.getBlock(x + nx - R, y + ny - R, z + nz - R);
+      if (registry.hasTag(block, kBlockTagLog)) return true;
+      if (!registry.hasTag(block, kBlockTagLeaves)) continue;
+      queue.push_back({{nx, ny, nz}, distance + 1});
+    }
+  }
+  return false;
+}
+
+//////////////////////////////////////////////////////////////////////////////
+
+} // namespace voxels
@@ -0,0 +1,54 @@
+#pragma once
+
+#include <array>
+#include <cstdint>
+#include <queue>
+#include <vector>
+
+#include "access.h"
+#include "base.h"
+
+//////////////////////////////////////////////////////////////////////////////
+
+namespace voxels {
+
+//////////////////////////////////////////////////////////////////////////////
+
+// Scheduled block updates, which run a given number of game ticks after we
+// schedule them, and leaf decay, which uses them: when a log is removed,
+// leaves that are no longer near any log decay after a random delay. See
+// World::tick and World::runScheduledUpdate.
+
+struct Registry;
+
+// Leaves more than kLeafDecayDistance steps (through other leaves) from the
+// nearest log decay. We schedule each decay at a random delay of up to
+// kLeafDecayMaxDelay ticks, so that canopies crumble instead of vanishing.
+constexpr int kLeafDecayDistance = 6;
+constexpr int kLeafDecayMaxDelay = 40;
+constexpr int kMaxScheduledUpdatesPerTick = 64;
+
+struct ScheduledUpdate {
+  uint64_t tick;
+  int x, y, z;
+  bool operator<(const ScheduledUpdate& o) const { return tick > o.tick; }
+};
+
+// A queue of updates with the earliest tick on top.
+using UpdateQueue = std::priority_queue<ScheduledUpdate>;
+
+// Called when we remove the log at (x, y, z). Labels leaves around it with
+// their distance to the nearest remaining log, using a multi-source BFS over
+// leaves, and appends the leaves that are now too far to the result.
+void findDecayingLeaves(BlockAccess& world, const Registry& registry,
+                        int x, int y, int z,
+                        std::vector<std::array<int, 3>>& result);
+
+// Returns true if there's a log within kLeafDecayDistance steps of the
+// leaves at (x, y, z), moving only through other leaves.
+bool hasLogNearby(BlockAccess& world, const Registry& registry,
+                  int x, int y, int z);
+
+//////////////////////////////////////////////////////////////////////////////
+
+} // namespace voxels
@@ -0,0 +1,175 @@
+#include "voxelize.h"
+
+#include <algorithm>
+#include <cmath>
+
+//////////////////////////////////////////////////////////////////////////////
+
+namespace voxels {
+
+//////////////////////////////////////////////////////////////////////////////
+
+namespace {
+
+using Vec3 = std::array<double, 3>;
+
+// We cast parity rays slightly off of voxel centers, so that they don't pass
+// exactly through the vertices and edges of meshes built on a grid.
+constexpr double kJitterX = 1.7320508e-6;
+constexpr double kJitterZ = 1.4142136e-6;
+
+Vec3 sub(const Vec3& a, const Vec3& b) {
+  return {a[0] - b[0], a[1] - b[1], a[2] - b[2]};
+}
+
+Vec3 cross(const Vec3& a, const Vec3& b) {
+  return {a[1] * b[2] - a[2] * b[1],
+          a[2] * b[0] - a[0] * b[2],
+          a[0] * b[1] - a[1] * b[0]};
+}
+
+double dot(const Vec3& a, const Vec3& b) {
+  return a[0] * b[0] + a[1] * b[1] + a[2] * b[2];
+}
+
+// The separating axis test for a triangle and a unit voxel, after Akenine-
+// Möller. Touching counts as overlap, so that faces lying in a voxel's face
+// plane keep the voxels on both sides.
+bool triangleHitsVoxel(const std::array<Vec3, 3>& triangle,
+                       int x, int y, int z) {
+  constexpr auto h = 0.5;
+  const auto center = Vec3{x + h, y + h, z + h};
+  const auto v = std::array<Vec3, 3>{sub(triangle[0], center),
+                                     sub(triangle[1], center),
+                                     sub(triangle[2], center)};
+
+  const auto separated = [&](const Vec3& axis) {
+    const auto p0 = dot(v[0], axis);
+    const auto p1 = dot(v[1], axis);
+    const auto p2 = dot(v[2], axis);
+    const auto r = h * (std::abs(axis[0]) + std::abs(axis[1]) +
+                        std::abs(axis[2]));
+    return std::min({p0, p1, p2}) > r || std::max({p0, p1, p2}) < -r;
+  };
+
+  for (auto i = 0; i < 3; i++) {
+    auto axis = Vec3{0, 0, 0};
+    axis[i] = 1;
+    if (separated(axis)) return false;
+  }
+  const auto edges = std::array<Vec3, 3>{
+      sub(v[1], v[0]), sub(v[2], v[1]), sub(v[0], v[2])};
+  for (const auto& edge : edges) {
+    for (auto i = 0; i < 3; i++) {
+      auto axis = Vec3{0, 0, 0};
+      axis[i] = 1;
+      if (separated(cross(edge, axis))) return false;
+    }
+  }
+  return !separated(cross(edges[0], edges[1]));
+}
+
+// Twice the signed area of (a, b, p), projected onto the x-z plane.
+double edgeXZ(const Vec3& a, const Vec3& b, double px, double pz) {
+  return (b[0] - a[0]) * (pz - a[2]) - (b[2] - a[2]) * (px - a[0]);
+}
+
+uint64_t columnKey(int x, int z) {
+  return (static_cast<uint64_t>(static_cast<uint32_t>(x)) << 32) |
+         static_cast<uint64_t>(static_cast<uint32_t>(z));
+}
+
+// Records the heights at which the triangle crosses the vertical rays
+// through the voxel columns in its x-z bounds.
+void addCrossings(const std::array<Vec3, 3>& triangle,
+                  HashMap<uint64_t, std::vector<double>>& columns) {
+  const auto& [a, b, c] = triangle;
+  const auto area = edgeXZ(a, b, c[0], c[2]);
+  if (area == 0) return;
+
+  const auto x0 = static_cast<int>(std::floor(std::min({a[0], b[0], c[0]})));
+  const auto x1 = static_cast<int>(std::floor(std::max({a[0], b[0], c[0]})));
+  const auto z0 = static_cast<int>(std::floor(std::min({a[2], b[2], c[2]})));
+  const auto z1 = static_cast<int>(std::floor(std::max({a[2], b[2], c[2]})));
+  for (auto x = x0; x <= x1; x++) {
+    for (auto z = z0; z <= z1; z++) {
+      const auto px = x + 0.5 + kJitterX, pz = z + 0.5 + kJitterZ;
+      const auto wa = edgeXZ(b, c, px, pz) / area;
+      const auto wb = edgeXZ(c, a, px, pz) / area;
+      const auto wc = edgeXZ(a, b, px, pz) / area;
+      if (wa < 0 || wb < 0 || wc < 0) continue;
+      columns[columnKey(x, z)].push_back(wa * a[1] + wb * b[1] + wc * c[1]);
+    }
+  }
+}
+
+} // namespace
+
+//////////////////////////////////////////////////////////////////////////////
+
+void voxelizeMesh(const float* vertices, int num_vertices,
+                  const uint32_t* indices, int num_indices,
+                  double resolution, VoxelizeFill fill,
+                  std::vector<std::array<int, 3>>& result) {
+  result.clear();
+  if (!(resolution > 0) || num_vertices <= 0) return;
+
+  auto lo = Vec3{INFINITY, INFINITY, INFINITY};
+  auto hi = Vec3{-INFINITY, -INFINITY, -INFINITY};
+  for (auto i = 0; i < 3 * num_vertices; i++) {
+    const auto value = vertices[i] * resolution;
+    if (!std::isfinite(value)) return;
+    lo[i % 3] = std::min(lo[i % 3], value);
+    hi[i % 3] = std::max(hi[i % 3], value);
+  }
+  for (auto i = 0; i < 3; i++) {
+    if (std::floor(hi[i]) - std::floor(lo[i]) >= kMaxVoxelizeExtent) return;
+  }
+
+  const auto vertex = [&](uint32_t index) {
+    const auto v = &vertices[3 * index];
+    return Vec3{v[0] * resolution, v[1] * resolution, v[2] * resolution};
+  };
+
+  HashMap<uint64_t, std::vector<double>> columns;
+  for (auto i = 0; i + 2 < num_indices; i += 3) {
+    const auto ia = indices[i], ib = indices[i + 1], ic = indices[i + 2];
+    const auto limit = static_cast<uint32_t>(num_vertices);
+    if (ia >= limit || ib >= limit || ic >= limit) continue;
+    const auto triangle =
+        std::array<Vec3, 3>{vertex(ia), vertex(ib), vertex(ic)};
+
+    std::array<int, 3> min, max;
+    for (auto j = 0; j < 3; j++) {
+      const auto a = triangle[0][j], b = triangle[1][j], c = triangle[2][j];
+      min[j] = static_cast<int>(std::floor(std::min({a, b, c})));
+      max[j] = static_cast<int>(std::floor(std::max({a, b, c})));
+    }
+    for (auto x = min[0]; x <= max[0]; x++) {
+      for (auto y = min[1]; y <= max[1]; y++) {
+        for (auto z = min[2]; z <= max[2]; z++) {
+          if (triangleHitsVoxel(triangle, x, y, z)) result.push_back({x, y, z});
+        }
+      }
+    }
+    if (fill == VoxelizeFill::Solid) addCrossings(triangle, columns);
+  }
+
+  for (auto& [key, crossings] : columns) {
+    const auto x = static_cast<int32_t>(key >> 32);
+    const auto z = static_cast<int32_t>(key);
+    std::sort(crossings.begin(), crossings.end());
+    for (size_t i = 0; i + 1 < crossings.size(); i += 2) {
+      const auto y0 = static_cast<int>(std::ceil(crossings[i] - 0.5));
+      const auto y1 = static_cast<int>(std::floor(crossings[i + 1] - 0.5));
+      for (auto y = y0; y <= y1; y++) result.push_back({x, y, z});
+    }
+  }
+
+  std::sort(result.begin(), result.end());
+  result.erase(std::unique(result.begin(), result.end()), result.end());
+}
+
+//////////////////////////////////////////////////////////////////////////////
+
+} // namespace voxels
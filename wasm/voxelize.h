@@ -0,0 +1,41 @@
+#pragma once
+
+#include <array>
+#include <cstdint>
+#include <vector>
+
+#include "base.h"
+
+//////////////////////////////////////////////////////////////////////////////
+
+namespace voxels {
+
+//////////////////////////////////////////////////////////////////////////////
+
+// Voxelization of triangle meshes, so that imported models can become block
+// structures. Vertices are (x, y, z) floats in model units, and indices list
+// three vertices per triangle. A model unit spans `resolution` voxels, and
+// the voxel containing the model point p is floor(p * resolution).
+//
+// Surface fill is conservative: it keeps every voxel that a triangle touches,
+// so thin or sloped faces never leak. Solid fill also keeps voxels inside
+// the mesh, by parity: we cast a ray up each column of voxel centers and
+// fill between pairs of crossings. It expects a closed mesh; an odd number
+// of crossings in a column leaves the last span unfilled.
+//
+// We give up, and return no voxels, if the mesh spans more than
+// kMaxVoxelizeExtent voxels along any axis.
+
+constexpr int kMaxVoxelizeExtent = 256;
+
+enum class VoxelizeFill : uint8_t { Surface, Solid };
+
+// Clears the result first, then writes the voxels in sorted order.
+void voxelizeMesh(const float* vertices, int num_vertices,
+                  const uint32_t* indices, int num_indices,
+                  double resolution, VoxelizeFill fill,
+                  std::vector<std::array<int, 3>>& result);
+
+//////////////////////////////////////////////////////////////////////////////
+
+} // namespace voxels
@@ -2,10 +2,11 @@
 
 #include <algorithm>
 #include <cmath>
-#include <limits>
+#include <memory>
 #include <vector>
 
 #include "base.h"
+#include "fixed.h"
 #include "open-simplex-2d.h"
 
 //////////////////////////////////////////////////////////////////////////////
@@ -29,17 +30,7 @@ constexpr auto kCaveCutoff = 0.25;
 constexpr auto kCaveWaveHeight = 16;
 constexpr auto kCaveWaveRadius = 256;
 
-struct HeightmapResult {
-  Block block;
-  int height;
-  int snow_depth;
-};
-
-static ChunkData chunkData;
-static std::vector<uint32_t> heightmapData;
-
 static constexpr uint32_t kSeed = 0;
-static uint32_t g_noise2DSeed = kSeed;
 
 const std::vector<uint16_t>& getRandomness() {
   static std::vector<uint16_t> result;
@@ -62,84 +53,196 @@ const uint16_t hash_point(int x, int z) {
   return randomness[(x << bits) | z];
 }
 
-auto MinetestNoise2D(double offset, double scale, double spread,
-                     size_t octaves, double persistence, double lacunarity) {
-  const auto inverse_spread = 1 / spread;
-  auto components = std::make_unique<Noise2D[]>(octaves);
-  for (auto i = 0; i < octaves; i++) {
-    new (&components[i]) Noise2D(g_noise2DSeed++);
+// 16 * (r / kIslandRadius)^2, where r is the distance from the origin. In
+// a wrapped world, that's the distance to the nearest copy of the origin.
+Real islandFalloff(int x, int z, int wrap) {
+  if (wrap) {
+    x = std::min(x, wrap - x);
+    z = std::min(z, wrap - z);
   }
+#ifdef VOXELS_FIXED_POINT
+  constexpr auto limit = int64_t{kSeaLevel} * kIslandRadius * kIslandRadius;
+  const auto r2 = int64_t{x} * x + int64_t{z} * z;
+  if (16 * r2 >= limit) return Fixed(kSeaLevel);
+  return Fixed::fromRaw(
+      (16 * r2 << kFixedBits) / (int64_t{kIslandRadius} * kIslandRadius));
+#else
+  const auto base = sqrt(x * x + z * z) / kIslandRadius;
+  return 16 * base * base;
+#endif
+}
 
-  return [=, components = std::move(components)](double x, double y) {
-    auto s = inverse_spread, g = 1.0, result = 0.0;
-    for (auto i = 0; i < octaves; i++) {
-      result += g * components[i].query(x * s, y * s);
-      g *= persistence;
-      s *= lacunarity;
-    }
-    return scale * result + offset;
-  };
+// x^1.5. The fixed-point version returns 0 for negative x, instead of NaN;
+// both values fail the comparisons that our callers make.
+Real pow15(Real x) {
+#ifdef VOXELS_FIXED_POINT
+  return x * sqrt(x);
+#else
+  return pow(x, 1.5);
+#endif
+}
+
+//////////////////////////////////////////////////////////////////////////////
+
+} // namespace
+
+//////////////////////////////////////////////////////////////////////////////
+
+// Terrain math uses Real, so that VOXELS_FIXED_POINT builds generate the same
+// terrain on every platform. Noise parameters are constants, so converting
+// them from doubles is deterministic.
+
+Real NoiseOctave::query(Real x, Real y, Real s, int wrap) const {
+  if (wrap == 0) return simplex.query(x * s, y * s);
+  const auto cells = std::max(static_cast<int>(wrap * s + Real(0.5)), 1);
+  const auto scale = Real(cells) / Real(wrap);
+  return periodic.query(x * scale, y * scale, cells);
+}
+
+FractalNoise::FractalNoise(
+    double offset, double scale, double spread, int octaves,
+    double persistence, double lacunarity, bool ridged)
+    : offset(offset), scale(scale), inverse_spread(1 / spread),
+      persistence(persistence), lacunarity(lacunarity),
+      num_octaves(octaves), ridged(ridged) {}
+
+void FractalNoise::seed(uint32_t& next_seed) {
+  octaves.clear();
+  for (auto i = 0; i < num_octaves; i++) {
+    octaves.push_back(std::make_unique<NoiseOctave>(next_seed++));
+  }
+}
+
+Real FractalNoise::query(Real x, Real y, int wrap) const {
+  auto s = inverse_spread, g = Real(1), result = Real(0);
+  for (const auto& octave : octaves) {
+    const auto n = octave->query(x, y, s, wrap);
+    result += g * (ridged ? 1 - abs(n) : n);
+    g *= persistence;
+    s *= lacunarity;
+  }
+  return scale * result + offset;
+}
+
+//////////////////////////////////////////////////////////////////////////////
+
+Worldgen::Worldgen(uint32_t seed)
+    : cliff_select   (0, 1,  512, 4, 0.7, 2.0),
+      mountain_select(0, 1,  512, 4, 0.7, 2.0),
+      terrain_ground (2, 8,  512, 6, 0.6, 2.0),
+      terrain_cliff  (8, 16, 512, 6, 0.6, 2.0),
+      mountain_ridge (0, 1,  500, 4, 0.5, 2.0, /*ridged=*/true) {
+  setSeed(seed);
 }
 
-auto RidgeNoise(size_t octaves, double persistence, double spread) {
-  const auto inverse_spread = 1 / spread;
-  auto components = std::make_unique<Noise2D[]>(octaves);
-  for (auto i = 0; i < octaves; i++) {
-    new (&components[i]) Noise2D(g_noise2DSeed++);
+void Worldgen::setSeed(uint32_t seed) {
+  // Each world takes 30 noise seeds in a row. We spread the worlds' ranges
+  // out with a multiplicative hash, so that nearby seeds don't overlap.
+  auto next_seed = kSeed + seed * 0x9e3779b9u;
+  cliff_select.seed(next_seed);
+  mountain_select.seed(next_seed);
+  terrain_ground.seed(next_seed);
+  terrain_cliff.seed(next_seed);
+  mountain_ridge.seed(next_seed);
+  cave_noises.clear();
+  for (auto i = 0; i < 2 * kCaveLevels; i++) {
+    cave_noises.push_back(std::make_unique<NoiseOctave>(next_seed++));
   }
+  hash_offset = static_cast<int>((seed * 0x85ebca6bu) >> 22);
+  cached_chunk.reset();
+}
+
+void Worldgen::setWrap(int chunks) {
+  assert(chunks >= 0);
+  wrap = chunks * kChunkWidth;
+  cached_chunk.reset();
+}
+
+int Worldgen::getBaseHeight(int x, int z) {
+  return heightmap(x, z).height;
+}
 
-  return [=, components = std::move(components)](double x, double y) {
-    auto s = inverse_spread, g = 1.0, result = 0.0;
-    for (auto i = 0; i < octaves; i++) {
-      result += (1 - abs(components[i].query(x * s, y * s))) * g;
-      g *= persistence;
-      s *= 2;
+ChunkDataRange Worldgen::loadChunkData(int cx, int cz) {
+  chunk_data.reset();
+  if (wrap) {
+    const auto chunks = wrap >> kChunkBits;
+    cx = ((cx % chunks) + chunks) % chunks;
+    cz = ((cz % chunks) + chunks) % chunks;
+  }
+  const auto bx = cx << kChunkBits, bz = cz << kChunkBits;
+  for (auto j = 0; j < kChunkWidth; j++) {
+    for (auto i = 0; i < kChunkWidth; i++) {
+      loadColumn(i + bx, j + bz);
     }
-    return result;
-  };
+  }
+  return {chunk_data.data(), chunk_data.data() + chunk_data.size()};
 }
 
-const auto mgv7_np_cliff_select    = MinetestNoise2D(0, 1,  512, 4, 0.7, 2.0);
-const auto mgv7_np_mountain_select = MinetestNoise2D(0, 1,  512, 4, 0.7, 2.0);
-const auto mgv7_np_terrain_ground  = MinetestNoise2D(2, 8,  512, 6, 0.6, 2.0);
-const auto mgv7_np_terrain_cliff   = MinetestNoise2D(8, 16, 512, 6, 0.6, 2.0);
+HeightmapRange Worldgen::loadHeightmap(int cx, int cz, int level) {
+  heightmap_data.clear();
+  const auto bx = cx << kChunkBits, bz = cz << kChunkBits;
+  for (auto j = 0; j < kChunkWidth; j++) {
+    for (auto i = 0; i < kChunkWidth; i++) {
+      const auto ax = (2 * (i + bx) + 1) << level;
+      const auto az = (2 * (j + bz) + 1) << level;
+      heightmap_data.push_back(packHeightmapData(ax, az));
+    }
+  }
+  return {heightmap_data.data(), heightmap_data.data() + heightmap_data.size()};
+}
 
-const auto mgv7_mountain_ridge = RidgeNoise(4, 0.5, 500);
+HeightmapRange Worldgen::loadImpostor(int cx, int cz, int resolution) {
+  assert(0 < resolution && resolution <= kChunkWidth);
+  assert(kChunkWidth % resolution == 0);
 
-static_assert(kCaveLevels == 3);
-const Noise2D cave_noises[2 * kCaveLevels] = {
-  Noise2D(g_noise2DSeed++), Noise2D(g_noise2DSeed++),
-  Noise2D(g_noise2DSeed++), Noise2D(g_noise2DSeed++),
-  Noise2D(g_noise2DSeed++), Noise2D(g_noise2DSeed++),
-};
+  heightmap_data.clear();
+  const auto step = kChunkWidth / resolution;
+  const auto bx = cx << kChunkBits, bz = cz << kChunkBits;
+  for (auto j = 0; j < resolution; j++) {
+    for (auto i = 0; i < resolution; i++) {
+      const auto ax = 2 * bx + step * (2 * i + 1);
+      const auto az = 2 * bz + step * (2 * j + 1);
+      heightmap_data.push_back(packHeightmapData(ax, az));
+    }
+  }
+  return {heightmap_data.data(), heightmap_data.data() + heightmap_data.size()};
+}
 
-HeightmapResult* heightmap(int x, int z) {
-  static HeightmapResult kHeightmapResult;
+int Worldgen::wrapCoordinate(int x) const {
+  return wrap ? ((x % wrap) + wrap) % wrap : x;
+}
 
-  const auto base = sqrt(x * x + z * z) / kIslandRadius;
-  const auto falloff = 16 * base * base;
+const Worldgen::HeightmapResult& Worldgen::heightmap(int x, int z) {
+  auto& result = heightmap_result;
+  x = wrapCoordinate(x);
+  z = wrapCoordinate(z);
+
+  const auto falloff = islandFalloff(x, z, wrap);
   if (falloff >= kSeaLevel) {
-    kHeightmapResult.block = Block::Bedrock;
-    kHeightmapResult.height = 0;
-    kHeightmapResult.snow_depth = 0;
-    return &kHeightmapResult;
+    result.block = Block::Bedrock;
+    result.height = 0;
+    result.snow_depth = 0;
+    return result;
   }
 
-  const auto cliff_select = mgv7_np_cliff_select(x, z);
-  const auto cliff_x = std::clamp(16 * abs(cliff_select) - 4, 0.0, 1.0);
+  const auto rx = Real(x), rz = Real(z);
+  const auto cliff_select_value = cliff_select.query(rx, rz, wrap);
+  const auto cliff_x =
+      std::clamp(16 * abs(cliff_select_value) - 4, Real(0), Real(1));
 
-  const auto mountain_select = mgv7_np_mountain_select(x, z);
-  const auto mountain_x = sqrt(fmax(8 * mountain_select, 0.0));
+  const auto mountain_select_value = mountain_select.query(rx, rz, wrap);
+  const auto mountain_x = sqrt(fmax(8 * mountain_select_value, Real(0)));
 
   const auto cliff = cliff_x - mountain_x;
   const auto mountain = -cliff;
 
-  const auto height_ground = mgv7_np_terrain_ground(x, z);
+  const auto height_ground = terrain_ground.query(rx, rz, wrap);
   const auto height_cliff = cliff > 0
-    ? mgv7_np_terrain_cliff(x, z)
+    ? terrain_cliff.query(rx, rz, wrap)
     : height_ground;
+  const auto ridge = [&]{ return mountain_ridge.query(rx, rz, wrap); };
   const auto height_mountain = mountain > 0
-    ? height_ground + 64 * pow((mgv7_mountain_ridge(x, z) - 1.25), 1.5)
+    ? height_ground + 64 * pow15(ridge() - Real(1.25))
     : height_ground;
 
   const auto height = [&]{
@@ -163,109 +266,106 @@ HeightmapResult* heightmap(int x, int z) {
     return truncated < 1 ? Block::Sand : Block::Grass;
   }();
 
-  kHeightmapResult.block = block;
-  kHeightmapResult.height = abs_height;
-  kHeightmapResult.snow_depth = block == Block::Snow
+  result.block = block;
+  result.height = abs_height;
+  result.snow_depth = block == Block::Snow
     ? static_cast<int>(height - (72 - 8 * mountain))
     : 0;
-  return &kHeightmapResult;
+  return result;
 }
 
-int carveCaves(int x, int z, int limit, int height, ChunkData* data) {
+int Worldgen::carveCaves(int x, int z, int limit, int height) {
   auto max = 0;
   auto min = kWorldHeight;
-  const auto start = kSeaLevel - 1.0 * kCaveDeltaY * (kCaveLevels - 1) / 2;
+  const auto start =
+      Real(kSeaLevel) - Real(kCaveDeltaY * (kCaveLevels - 1)) / 2;
 
   for (auto i = 0; i < kCaveLevels; i++) {
-    const auto& carver_noise = cave_noises[2 * i + 0];
-    const auto& height_noise = cave_noises[2 * i + 1];
+    const auto& carver_noise = *cave_noises[2 * i + 0];
+    const auto& height_noise = *cave_noises[2 * i + 1];
     const auto carver = carver_noise.query(
-        1.0 * x / kCaveRadius, 1.0 * z / kCaveRadius);
-    if (carver <= kCaveCutoff) continue;
+        Real(x), Real(z), Real(1) / kCaveRadius, wrap);
+    if (carver <= Real(kCaveCutoff)) continue;
 
     const auto dy = start + i * kCaveDeltaY;
     const auto height = height_noise.query(
-        1.0 * x / kCaveWaveRadius, 1.0 * z / kCaveWaveRadius);
+        Real(x), Real(z), Real(1) / kCaveWaveRadius, wrap);
     const auto offset = static_cast<int>(dy + kCaveWaveHeight * height);
-    const auto blocks = static_cast<int>((carver - kCaveCutoff) * kCaveHeight);
+    const auto blocks = static_cast<int>(
+        (carver - Real(kCaveCutoff)) * kCaveHeight);
 
     const auto ay = offset - blocks;
     const auto by = std::min(offset + blocks + 3, limit);
     for (auto i = ay; i < by; i++) {
-      data->decorate(Block::Air, i);
+      chunk_data.decorate(Block::Air, i);
     }
     max = std::max(max, by);
     min = std::min(min, ay);
   }
 
-  if (max < height && max < limit && (hash_point(x, z) & 63) == 4) {
-    data->decorate(Block::Fungi, min);
+  const auto fungi = (hash_point(x + hash_offset, z) & 63) == 4;
+  if (max < height && max < limit && fungi) {
+    chunk_data.decorate(Block::Fungi, min);
   }
   return max;
 }
 
-void loadChunk(int x, int z, ChunkData* data) {
+void Worldgen::loadColumn(int x, int z) {
   static_assert(isPowTwo(kChunkWidth));
 
-  constexpr auto kBuffer = 1;
-  constexpr auto kExpandedWidth = kChunkWidth + 2 * kBuffer;
-  constexpr auto max = std::numeric_limits<int>::max();
   constexpr int kNeighborOffsets[5] =
       {0, 1, -1, kExpandedWidth, -kExpandedWidth};
 
-  static Point lastChunk = {max, max};
-  static NonCopyArray<HeightmapResult, kExpandedWidth * kExpandedWidth> raw;
-
   const auto cx = (x & ~(kChunkWidth - 1)) / kChunkWidth;
   const auto cz = (z & ~(kChunkWidth - 1)) / kChunkWidth;
   const auto dx = cx * kChunkWidth - kBuffer;
   const auto dz = cz * kChunkWidth - kBuffer;
   const auto chunk = Point{cx, cz};
 
-  if (lastChunk != chunk) {
-    lastChunk = chunk;
+  if (cached_chunk != chunk) {
+    cached_chunk = chunk;
     for (auto j = 0; j < kExpandedWidth; j++) {
       for (auto i = 0; i < kExpandedWidth; i++) {
-        raw[i + j * kExpandedWidth] = *heightmap(i + dx, j + dz);
+        cached[i + j * kExpandedWidth] = heightmap(i + dx, j + dz);
       }
     }
   }
 
   const auto index = (x - dx) + (z - dz) * kExpandedWidth;
-  const auto& cache = raw[index];
+  const auto& cache = cached[index];
   if (cache.block == Block::Snow) {
-    data->push(Block::Stone, cache.height - cache.snow_depth);
+    chunk_data.push(Block::Stone, cache.height - cache.snow_depth);
   } else if (cache.block != Block::Stone) {
-    data->push(Block::Stone, cache.height - 4);
-    data->push(Block::Dirt,  cache.height - 1);
+    chunk_data.push(Block::Stone, cache.height - 4);
+    chunk_data.push(Block::Dirt,  cache.height - 1);
   }
-  data->push(cache.block, cache.height);
-  data->push(Block::Water, kSeaLevel);
+  chunk_data.push(cache.block, cache.height);
+  chunk_data.push(Block::Water, kSeaLevel);
 
   auto limit = kWorldHeight - 1;
   for (const auto offset : kNeighborOffsets) {
-    const auto neighbor_height = raw[index + offset].height;
+    const auto neighbor_height = cached[index + offset].height;
     if (neighbor_height >= kSeaLevel) continue;
     limit = std::min(limit, neighbor_height - 1);
   }
-  const auto cave_height = carveCaves(x, z, limit, cache.height, data);
+  const auto cave_height = carveCaves(x, z, limit, cache.height);
 
   if (cache.block == Block::Grass && cave_height < cache.height) {
-    const auto hash = hash_point(x, z) & 63;
-    if (hash < 2) data->decorate(Block::Bush, cache.height);
-    else if (hash < 4) data->decorate(Block::Rock, cache.height);
+    const auto hash = hash_point(x + hash_offset, z) & 63;
+    if (hash < 2) chunk_data.decorate(Block::Bush, cache.height);
+    else if (hash < 4) chunk_data.decorate(Block::Rock, cache.height);
   }
-  data->commit();
+  chunk_data.commit();
 }
 
-uint32_t packHeightmapData(int x, int z) {
+uint32_t Worldgen::packHeightmapData(int x, int z) {
   static_assert(sizeof(Block) == 1);
   static_assert(static_cast<uint32_t>(Block::Air) == 0);
 
-  const auto result = heightmap(x, z);
-  const Block solid_block = result->block;
+  const auto& result = heightmap(x, z);
+  const Block solid_block = result.block;
   const uint8_t solid_height =
-      static_cast<uint8_t>(std::clamp(result->height, 0x00, 0xff));
+      static_cast<uint8_t>(std::clamp(result.height, 0x00, 0xff));
 
   if (solid_height >= kSeaLevel) {
     return (static_cast<uint32_t>(solid_block)  << 0) |
@@ -279,10 +379,6 @@ uint32_t packHeightmapData(int x, int z) {
 
 //////////////////////////////////////////////////////////////////////////////
 
-} // namespace
-
-//////////////////////////////////////////////////////////////////////////////
-
 void ChunkData::commit() {
   push(Block::Air, kWorldHeight);
   serialized.push_back(static_cast<uint8_t>(decorated));
@@ -332,34 +428,6 @@ void ChunkData::clearDecoration(Decoration& decoration) {
 
 //////////////////////////////////////////////////////////////////////////////
 
-int getBaseHeight(int x, int z) {
-  return heightmap(x, z)->height;
-}
-
-ChunkDataRange loadChunkData(int cx, int cz) {
-  chunkData.reset();
-  const auto bx = cx << kChunkBits, bz = cz << kChunkBits;
-  for (auto j = 0; j < kChunkWidth; j++) {
-    for (auto i = 0; i < kChunkWidth; i++) {
-      loadChunk(i + bx, j + bz, &chunkData);
-    }
-  }
-  return {chunkData.data(), chunkData.data() + chunkData.size()};
-}
-
-HeightmapRange loadHeightmap(int cx, int cz, int level) {
-  heightmapData.clear();
-  const auto bx = cx << kChunkBits, bz = cz << kChunkBits;
-  for (auto j = 0; j < kChunkWidth; j++) {
-    for (auto i = 0; i < kChunkWidth; i++) {
-      const auto ax = (2 * (i + bx) + 1) << level;
-      const auto az = (2 * (j + bz) + 1) << level;
-      heightmapData.push_back(packHeightmapData(ax, az));
-    }
-  }
-  return {heightmapData.data(), heightmapData.data() + heightmapData.size()};
-}
-
 //////////////////////////////////////////////////////////////////////////////
 
 } // namespace voxels
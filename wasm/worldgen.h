@@ -3,9 +3,15 @@
 #include "base.h"
 
 #include <array>
+#include <memory>
+#include <optional>
 #include <ranges>
 #include <vector>
 
+#include "fixed.h"
+#include "open-simplex-2d.h"
+#include "periodic-noise-2d.h"
+
 //////////////////////////////////////////////////////////////////////////////
 
 namespace voxels {
@@ -39,19 +45,114 @@ struct ChunkData {
   NonCopyArray<Decoration, kWorldHeight> decorations;
 };
 
-int getBaseHeight(int x, int z);
-
 struct ChunkDataRange {
   const uint8_t* start;
   const uint8_t* end;
 };
-ChunkDataRange loadChunkData(int cx, int cz);
 
 struct HeightmapRange {
   const uint32_t* start;
   const uint32_t* end;
 };
-HeightmapRange loadHeightmap(int cx, int cz, int level);
+
+// One octave of noise. In a wrapped world, we sample periodic noise instead,
+// and round the octave's frequency so that a whole number of noise cells
+// spans the world. Terrain in a wrapped world looks a bit different, but it
+// keeps its full contrast everywhere, and it's continuous across the seam.
+struct NoiseOctave {
+  explicit NoiseOctave(uint32_t seed) : simplex(seed), periodic(seed) {}
+
+  // Queries noise at (x * s, y * s). `wrap` is the world's period, in blocks.
+  Real query(Real x, Real y, Real s, int wrap) const;
+
+ private:
+  Noise2D simplex;
+  PeriodicNoise2D periodic;
+};
+
+// A sum of octaves, as in Minetest's mapgen v7. Ridged noise sums 1 - |n|
+// for each octave n, instead of n. Call seed before querying it: it creates
+// the octaves, each with the next seed.
+struct FractalNoise {
+  FractalNoise(double offset, double scale, double spread, int octaves,
+               double persistence, double lacunarity, bool ridged = false);
+
+  void seed(uint32_t& next_seed);
+  Real query(Real x, Real y, int wrap) const;
+
+ private:
+  Real offset;
+  Real scale;
+  Real inverse_spread;
+  Real persistence;
+  Real lacunarity;
+  int num_octaves;
+  bool ridged;
+  std::vector<std::unique_ptr<NoiseOctave>> octaves;
+};
+
+// Terrain generation for one world. Each dimension has its own generator,
+// so dimensions can have different seeds, and wrapping one doesn't change
+// the terrain of the others. Results point into buffers that the next call
+// to the same generator overwrites.
+struct Worldgen {
+  explicit Worldgen(uint32_t seed = 0);
+
+  // Like setWrap, set the seed before loading any chunks. Seed 0 generates
+  // the terrain that we had before worlds had seeds.
+  void setSeed(uint32_t seed);
+
+  // Makes worldgen periodic, with a period of `chunks` chunks along x and z,
+  // for toroidal worlds. 0 restores an unbounded world. Set it before loading
+  // any chunks: we don't regenerate chunks that are already loaded.
+  void setWrap(int chunks);
+
+  int getBaseHeight(int x, int z);
+  ChunkDataRange loadChunkData(int cx, int cz);
+  HeightmapRange loadHeightmap(int cx, int cz, int level);
+
+  // Samples a resolution x resolution grid of heightmap entries at the
+  // centers of equal-sized cells of the chunk. Entries are packed as in
+  // loadHeightmap.
+  HeightmapRange loadImpostor(int cx, int cz, int resolution);
+
+ private:
+  struct HeightmapResult {
+    Block block;
+    int height;
+    int snow_depth;
+  };
+
+  static constexpr int kBuffer = 1;
+  static constexpr int kExpandedWidth = kChunkWidth + 2 * kBuffer;
+
+  int wrapCoordinate(int x) const;
+  const HeightmapResult& heightmap(int x, int z);
+  int carveCaves(int x, int z, int limit, int height);
+  void loadColumn(int x, int z);
+  uint32_t packHeightmapData(int x, int z);
+
+  // The world's period along x and z, in blocks, or 0 if it doesn't wrap.
+  int wrap = 0;
+
+  // Decorations hash each column; we shift the columns to vary them by seed.
+  int hash_offset = 0;
+
+  FractalNoise cliff_select;
+  FractalNoise mountain_select;
+  FractalNoise terrain_ground;
+  FractalNoise terrain_cliff;
+  FractalNoise mountain_ridge;
+  std::vector<std::unique_ptr<NoiseOctave>> cave_noises;
+
+  HeightmapResult heightmap_result;
+  std::optional<Point> cached_chunk;
+  NonCopyArray<HeightmapResult, kExpandedWidth * kExpandedWidth> cached;
+  ChunkData chunk_data;
+  std::vector<uint32_t> heightmap_data;
+
+  DISALLOW_COPY_AND_ASSIGN(Worldgen);
+};
 
 //////////////////////////////////////////////////////////////////////////////
 